@@ -1,10 +1,58 @@
+pub mod output;
+
 use alloy_genesis::Genesis;
-use alloy_primitives::Address;
-use clap::Parser;
+use alloy_primitives::{Address, Bytes};
+use alloy_provider::ProviderBuilder;
+use clap::{Parser, Subcommand};
+use kakarot_exex::{
+    air,
+    analytics::{self, AnalyticsExporter, AnalyticsFormat},
+    artifacts::{ArtifactStore, LifecyclePolicy},
+    benchmark::{self, BenchmarkReport, CostModels},
+    blob,
+    calltracer::CallTracerConfig,
+    campaign::{self, Campaign, CommandVerifier},
+    chain, checkpoint,
+    compression::{self, MigrationOptions},
+    db::Database,
+    deferred,
+    divergence::{self, ReportFormat},
+    fact::FactRegistryClient,
+    input::provider::ProviderInputSource,
+    instance::InstanceConfig,
+    integrity::{self, FsckOptions},
+    light_client::{self, StateProofRequest},
+    limits::ExecutionLimits,
+    otlp::{self, Otlp, OtlpConfig},
+    prestate::{self, PrestateTracerConfig},
+    profiler::{self, ProfileFormat},
+    program::KakarotProgram,
+    quorum::QuorumConfig,
+    retry::RetryPolicy,
+    serde::codegen::{self, CodegenOptions},
+    sharp::{self, SharpClient},
+    store,
+    structlog::StructLoggerConfig,
+    telemetry::{self, TraceSampling},
+    tracer::{self, TraceOptions, Tracer},
+    verifier::VerifierRegistry,
+};
+use output::{
+    AirInputsOutput, AnalyticsOutput, BenchmarkOutput, CampaignStatusOutput, ChainHeadOutput,
+    CheckpointOutput, CodegenOutput, CompressOutput, DivergenceOutput, ExportOutput, FsckOutput,
+    ImportOutput, LifecycleOutput, LightClientOutput, OutputArgs, ProfileOutput, ProgramHashOutput,
+    ReportedFailure, ResumeOutput, RetryOutput, SharpSubmitOutput, TierOutput, TraceOutput,
+    VerifyOutput,
+};
 use reth_chainspec::{Chain, ChainSpec};
 use reth_node_core::args::DevArgs;
-use std::{str::FromStr, time::Duration};
-use tracing_subscriber::EnvFilter;
+use std::{fs, path::PathBuf, str::FromStr, time::Duration};
+use tracing_subscriber::{
+    fmt::{format::FmtSpan, writer::BoxMakeWriter},
+    layer::SubscriberExt,
+    util::SubscriberInitExt,
+    EnvFilter,
+};
 
 #[derive(Debug, Parser)]
 pub struct Cli {
@@ -12,18 +60,1097 @@ pub struct Cli {
     pub chain: ChainArgs,
     #[command(flatten)]
     pub log: LogArgs,
+    #[command(flatten)]
+    pub output: OutputArgs,
+    #[command(flatten)]
+    pub exex: ExExArgs,
+    /// The command to run instead of the node.
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Commands {
+    /// Generate the Rust type definitions of the structs of a compiled Cairo program.
+    Codegen(CodegenArgs),
+    /// Export the queued proving jobs of a deferred-proving instance as a bundle of Cairo PIEs.
+    ExportJobs(ExportJobsArgs),
+    /// Import the proofs produced for the exported proving jobs of an instance.
+    ImportProofs(ImportProofsArgs),
+    /// Export light-client bundles of proven blocks: their validity proof, output and the
+    /// Merkle-Patricia proofs of requested accounts and storage slots against their post-state.
+    ExportLightClient(ExportLightClientArgs),
+    /// Reconcile the proven blocks with the L1 fact registry, reporting the blocks proven locally
+    /// whose fact is not registered on-chain yet.
+    ReconcileFacts(ReconcileFactsArgs),
+    /// Prove the queued jobs of a deferred-proving instance with SHARP.
+    #[command(subcommand)]
+    Sharp(SharpCommands),
+    /// Manage the re-proving campaigns of historical blocks with an upgraded program.
+    #[command(subcommand)]
+    Campaign(CampaignCommands),
+    /// Compress the artifacts stored uncompressed in the database of an instance, and upgrade the
+    /// ones written in an older format.
+    #[command(visible_alias = "migrate-artifacts")]
+    CompressArtifacts(CompressArtifactsArgs),
+    /// Tier the artifacts stored locally by an instance to an object storage, applying its
+    /// lifecycle rules.
+    TierArtifacts(TierArtifactsArgs),
+    /// Check the integrity of the artifacts of an instance, reporting the corrupted, missing and
+    /// orphaned ones and optionally scheduling the re-generation of the affected blocks.
+    Fsck(FsckArgs),
+    /// Trace a transaction from the stored Cairo execution of its block, with a tracer of geth.
+    TraceTransaction(TraceTransactionArgs),
+    /// Trace the pre-state of the accounts touched by a stored block, replaying it on its recorded
+    /// witness, as geth's `prestateTracer`.
+    TracePrestate(TracePrestateArgs),
+    /// Compare the stored Cairo execution of a block with its native execution on its witness,
+    /// saving a report for each diverging transaction.
+    Divergence(DivergenceArgs),
+    /// Re-run a stored block, saving checkpoints of the VM state at the start of its transactions.
+    Checkpoint(CheckpointArgs),
+    /// Resume the execution of a block from a saved checkpoint.
+    Resume(ResumeArgs),
+    /// Print the canonical hash of a compiled Cairo program, as hashed by the bootloader.
+    ProgramHash(ProgramHashArgs),
+    /// Profile the Cairo steps of a stored block by Cairo function, for flamegraphs.
+    Profile(ProfileArgs),
+    /// Run a compiled Cairo program in proof mode and write the AIR inputs of the Stone prover.
+    AirInputs(AirInputsArgs),
+    /// Replay a range of stored blocks on their recorded program inputs and project the proving
+    /// hours and hardware cost of a day of chain with prover cost models.
+    Benchmark(BenchmarkArgs),
+    /// Export the opcode, builtin, selector and transaction statistics of a range of stored blocks
+    /// to Parquet or Arrow IPC files.
+    ExportAnalytics(ExportAnalyticsArgs),
+    /// Verify that the blobs posted for a block hold its program input, against the KZG
+    /// commitments stored with the block.
+    VerifyBlobs(VerifyBlobsArgs),
+    /// Print the JSON schemas of the results of the commands with `--output json`, by command.
+    OutputSchema,
+}
+
+impl Commands {
+    pub fn run(self, output: &OutputArgs) -> eyre::Result<()> {
+        match self {
+            Self::Codegen(args) => args.run(output),
+            Self::ExportJobs(args) => args.run(output),
+            Self::ImportProofs(args) => args.run(output),
+            Self::ExportLightClient(args) => args.run(output),
+            Self::ReconcileFacts(args) => args.run(output),
+            Self::Sharp(command) => command.run(output),
+            Self::Campaign(command) => command.run(output),
+            Self::CompressArtifacts(args) => args.run(output),
+            Self::TierArtifacts(args) => args.run(output),
+            Self::Fsck(args) => args.run(output),
+            Self::TraceTransaction(args) => args.run(output),
+            Self::TracePrestate(args) => args.run(output),
+            Self::Divergence(args) => args.run(output),
+            Self::Checkpoint(args) => args.run(output),
+            Self::Resume(args) => args.run(output),
+            Self::ProgramHash(args) => args.run(output),
+            Self::Profile(args) => args.run(output),
+            Self::AirInputs(args) => args.run(output),
+            Self::Benchmark(args) => args.run(output),
+            Self::ExportAnalytics(args) => args.run(output),
+            Self::VerifyBlobs(args) => args.run(output),
+            Self::OutputSchema => {
+                println!("{}", serde_json::to_string_pretty(&output::schemas())?);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct CodegenArgs {
+    /// The path of the compiled Cairo program.
+    #[clap(long)]
+    pub program: PathBuf,
+    /// The scopes of the structs to generate, e.g. `src.model.model`, all the structs being
+    /// generated when omitted.
+    #[clap(long = "scope")]
+    pub scopes: Vec<String>,
+    /// The path of the serde module in the generated code.
+    #[clap(long, default_value = "kakarot_exex::serde")]
+    pub serde_path: String,
+    /// The file to write the generated code to, the standard output when omitted.
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+impl CodegenArgs {
+    pub fn run(self, output: &OutputArgs) -> eyre::Result<()> {
+        let options = CodegenOptions { scopes: self.scopes, serde_path: self.serde_path };
+        let code = codegen::generate_from_file(&self.program, &options)?;
+        let result = match self.output {
+            Some(path) => {
+                fs::write(&path, code)?;
+                CodegenOutput { path: Some(path), code: None }
+            }
+            None => CodegenOutput { path: None, code: Some(code) },
+        };
+        output.emit(&result)
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct ExportJobsArgs {
+    /// The path of the database of the instance.
+    #[clap(long)]
+    pub db: PathBuf,
+    /// The directory to write the bundle to.
+    #[clap(short, long)]
+    pub output: PathBuf,
+    /// The maximum number of jobs to export.
+    #[clap(long, default_value = "100")]
+    pub limit: usize,
+    /// The path of the redb file storing the proofs and the metadata of the proven blocks, the
+    /// database when omitted.
+    #[clap(long)]
+    pub proof_store: Option<PathBuf>,
+}
+
+impl ExportJobsArgs {
+    pub fn run(self, output: &OutputArgs) -> eyre::Result<()> {
+        let db = Database::open(&self.db)?;
+        let store = store::open_store(&db, self.proof_store.as_deref())?;
+        let manifest = deferred::export_bundle(&db, &*store, &self.output, self.limit)?;
+        output.emit(&ExportOutput::new(self.output, &manifest))
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct ExportLightClientArgs {
+    /// The path of the database of the instance.
+    #[clap(long)]
+    pub db: PathBuf,
+    /// The URL of the RPC endpoint serving the proofs of the state after the blocks, with
+    /// `eth_getProof`.
+    #[clap(long)]
+    pub rpc_url: String,
+    /// The first block to export.
+    #[clap(long)]
+    pub from_block: u64,
+    /// The last block to export, the first one when omitted.
+    #[clap(long)]
+    pub to_block: Option<u64>,
+    /// An account to prove with its storage slots, as `address[:slot,...]`.
+    #[clap(long = "account", required = true)]
+    pub accounts: Vec<StateProofRequest>,
+    /// The directory to write the bundles to.
+    #[clap(short, long)]
+    pub output: PathBuf,
+    /// The path of the redb file storing the proofs and the metadata of the proven blocks, the
+    /// database when omitted.
+    #[clap(long)]
+    pub proof_store: Option<PathBuf>,
+}
+
+impl ExportLightClientArgs {
+    pub fn run(self, output: &OutputArgs) -> eyre::Result<()> {
+        let db = Database::open(&self.db)?;
+        let store = store::open_store(&db, self.proof_store.as_deref())?;
+        let source =
+            ProviderInputSource::new(ProviderBuilder::new().on_http(self.rpc_url.parse()?));
+        let manifest = tokio::runtime::Runtime::new()?.block_on(light_client::export_bundles(
+            &*store,
+            &source,
+            &self.output,
+            self.from_block,
+            self.to_block.unwrap_or(self.from_block),
+            &self.accounts,
+        ))?;
+        output.emit(&LightClientOutput::new(self.output, &manifest))
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct ReconcileFactsArgs {
+    /// The path of the database of the instance.
+    #[clap(long)]
+    pub db: PathBuf,
+    /// The URL of the RPC endpoint of the chain of the fact registry.
+    #[clap(long)]
+    pub rpc_url: String,
+    /// The address of the fact registry contract.
+    #[clap(long)]
+    pub registry: Address,
+    /// The first block to reconcile.
+    #[clap(long)]
+    pub from_block: u64,
+    /// The last block to reconcile, the first one when omitted.
+    #[clap(long)]
+    pub to_block: Option<u64>,
+    /// The path of the redb file storing the proofs and the metadata of the proven blocks, the
+    /// database when omitted.
+    #[clap(long)]
+    pub proof_store: Option<PathBuf>,
+}
+
+impl ReconcileFactsArgs {
+    pub fn run(self, output: &OutputArgs) -> eyre::Result<()> {
+        let db = Database::open(&self.db)?;
+        let store = store::open_store(&db, self.proof_store.as_deref())?;
+        let client = FactRegistryClient::new(
+            ProviderBuilder::new().on_http(self.rpc_url.parse()?),
+            self.registry,
+        );
+        let statuses = tokio::runtime::Runtime::new()?.block_on(client.reconcile(
+            &*store,
+            &db,
+            self.from_block,
+            self.to_block.unwrap_or(self.from_block),
+            Vec::new(),
+        ))?;
+        output.emit(&FactsOutput::from(statuses))
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct ImportProofsArgs {
+    /// The path of the database of the instance.
+    #[clap(long)]
+    pub db: PathBuf,
+    /// The directory holding the proofs and their manifest.
+    #[clap(short, long)]
+    pub input: PathBuf,
+    /// The comma separated provers which must agree on the fact of a block for it to be proven.
+    #[clap(long, value_delimiter = ',')]
+    pub quorum: Vec<String>,
+    /// The maximum number of attempts of a failed job, including the first one.
+    #[clap(long, default_value = "5")]
+    pub max_attempts: u32,
+    /// The delay in seconds before the first retry of a failed job, doubled on each attempt.
+    #[clap(long, default_value = "60")]
+    pub backoff_secs: u64,
+    /// The maximum delay in seconds between two attempts of a failed job.
+    #[clap(long, default_value = "3600")]
+    pub max_backoff_secs: u64,
+    /// The path of the redb file storing the proofs and the metadata of the proven blocks, the
+    /// database when omitted.
+    #[clap(long)]
+    pub proof_store: Option<PathBuf>,
+    /// The path of the registry of the verifier parameters the proofs are checked against.
+    #[clap(long)]
+    pub verifier_params: Option<PathBuf>,
+}
+
+impl ImportProofsArgs {
+    pub fn run(self, output: &OutputArgs) -> eyre::Result<()> {
+        let db = Database::open(&self.db)?;
+        let store = store::open_store(&db, self.proof_store.as_deref())?;
+        let quorum =
+            (!self.quorum.is_empty()).then(|| QuorumConfig::new(self.quorum)).transpose()?;
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_secs(self.backoff_secs),
+            max_backoff: Duration::from_secs(self.max_backoff_secs),
+            max_attempts: self.max_attempts,
+            ..Default::default()
+        };
+        let verifiers = self.verifier_params.map(VerifierRegistry::load).transpose()?;
+        let blocks = deferred::import_proofs(
+            &db,
+            &*store,
+            &self.input,
+            quorum.as_ref(),
+            verifiers.as_ref(),
+            &policy,
+        )?;
+
+        // The imported proofs extend the chain of the proven blocks, checking its continuity. The
+        // block breaking it is queued again.
+        let extension = chain::extend_and_requeue(&db, &*store, &policy)?;
+        let chain_head = extension
+            .links
+            .last()
+            .map(|head| ChainHeadOutput { block_number: head.block_number, hash: head.hash });
+        let rejected = extension.rejected.map(|err| err.block_number());
+        output.emit(&ImportOutput { blocks, chain_head, rejected })
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct CompressArtifactsArgs {
+    /// The path of the database of the instance.
+    #[clap(long)]
+    pub db: PathBuf,
+    /// Compress without training the missing dictionaries.
+    #[clap(long)]
+    pub no_train: bool,
+    /// The maximum number of artifacts of each kind the dictionaries are trained on.
+    #[clap(long, default_value = "1000")]
+    pub samples: usize,
+    /// The number of artifacts rewritten in each transaction.
+    #[clap(long, default_value = "100")]
+    pub batch_size: usize,
+}
+
+impl CompressArtifactsArgs {
+    pub fn run(self, output: &OutputArgs) -> eyre::Result<()> {
+        let db = Database::open(&self.db)?;
+        let options = MigrationOptions {
+            train: !self.no_train,
+            samples: self.samples,
+            batch_size: self.batch_size,
+        };
+        let report = compression::migrate(&db, &options)?;
+        output.emit(&CompressOutput::from(&report))
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct TierArtifactsArgs {
+    /// The path of the database of the instance.
+    #[clap(long)]
+    pub db: PathBuf,
+    /// The URL of the object storage, e.g. `s3://bucket/keth` or `gs://bucket/keth`.
+    #[clap(long)]
+    pub store: String,
+    /// The directory caching the tiered artifacts, not cached when omitted.
+    #[clap(long)]
+    pub cache: Option<PathBuf>,
+    /// The number of traces tiered in each transaction.
+    #[clap(long, default_value = "100")]
+    pub batch_size: usize,
+    /// The path of the lifecycle rules to apply once tiered.
+    #[clap(long, requires = "tip")]
+    pub lifecycle: Option<PathBuf>,
+    /// The tip the lifecycle rules are applied at.
+    #[clap(long)]
+    pub tip: Option<u64>,
+}
+
+impl TierArtifactsArgs {
+    pub fn run(self, output: &OutputArgs) -> eyre::Result<()> {
+        let store = ArtifactStore::open(&self.store, self.cache)?;
+        let db = Database::open(&self.db)?.with_artifact_store(store);
+        let traces = db.tier_traces(self.batch_size)?;
+        let dumps = db.tier_dumps()?;
+
+        let lifecycle = match (self.lifecycle, self.tip) {
+            (Some(lifecycle), Some(tip)) => {
+                let report = db.apply_lifecycle(&LifecyclePolicy::load(lifecycle)?, tip)?;
+                Some(LifecycleOutput::from(&report))
+            }
+            _ => None,
+        };
+        output.emit(&TierOutput { traces, dumps, lifecycle })
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct FsckArgs {
+    /// The path of the database of the instance.
+    #[clap(long)]
+    pub db: PathBuf,
+    /// The URL of the object storage the artifacts are tiered to, if any.
+    #[clap(long)]
+    pub store: Option<String>,
+    /// The directory caching the tiered artifacts, not cached when omitted.
+    #[clap(long, requires = "store")]
+    pub cache: Option<PathBuf>,
+    /// Schedule the re-generation of the affected blocks and delete the orphaned objects.
+    #[clap(long)]
+    pub repair: bool,
+}
+
+impl FsckArgs {
+    pub fn run(self, output: &OutputArgs) -> eyre::Result<()> {
+        let mut db = Database::open(&self.db)?;
+        if let Some(store) = &self.store {
+            db = db.with_artifact_store(ArtifactStore::open(store, self.cache)?);
+        }
+        let report = integrity::fsck(&db, &FsckOptions { repair: self.repair })?;
+        output.emit(&FsckOutput::from(&report))?;
+        if !report.is_clean() && !self.repair {
+            let damaged = report.corrupted.len() + report.missing.len() + report.orphaned.len();
+            let message = format!("Found {damaged} damaged artifacts, run with --repair");
+            return Err(ReportedFailure(message).into());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct TraceTransactionArgs {
+    /// The path of the database of the instance.
+    #[clap(long)]
+    pub db: PathBuf,
+    /// The path of the compiled Cairo program which executed the block.
+    #[clap(long)]
+    pub program: PathBuf,
+    /// The number of the block of the transaction.
+    #[clap(long)]
+    pub block: u64,
+    /// The index of the transaction in the block.
+    #[clap(long)]
+    pub tx: u32,
+    /// Omit the memory from the struct logs.
+    #[clap(long)]
+    pub disable_memory: bool,
+    /// Omit the stack from the struct logs.
+    #[clap(long)]
+    pub disable_stack: bool,
+    /// Omit the storage from the struct logs.
+    #[clap(long)]
+    pub disable_storage: bool,
+    /// Include the return data in the struct logs.
+    #[clap(long)]
+    pub enable_return_data: bool,
+    /// The tracer: `structLogger`, `callTracer`, `4byteTracer` or `opcodeTracer`.
+    #[clap(long, default_value = "structLogger")]
+    pub tracer: Tracer,
+    /// Omit the sub-calls from the call tree of the `callTracer`.
+    #[clap(long)]
+    pub only_top_call: bool,
+    /// The file to write the trace to, the standard output when omitted.
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+impl TraceTransactionArgs {
+    pub fn run(self, output: &OutputArgs) -> eyre::Result<()> {
+        let db = Database::open(&self.db)?;
+        let options = TraceOptions {
+            tracer: self.tracer,
+            struct_logger: StructLoggerConfig {
+                disable_memory: self.disable_memory,
+                disable_stack: self.disable_stack,
+                disable_storage: self.disable_storage,
+                enable_return_data: self.enable_return_data,
+            },
+            call_tracer: CallTracerConfig { only_top_call: self.only_top_call },
+        };
+        let trace = tracer::trace_transaction(&db, &self.program, self.block, self.tx, &options)?;
+        output.emit(&TraceOutput::write(trace, self.output)?)
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct TracePrestateArgs {
+    /// The path of the database of the instance.
+    #[clap(long)]
+    pub db: PathBuf,
+    /// The number of the block.
+    #[clap(long)]
+    pub block: u64,
+    /// Report the pre-state and the post-state of the modified accounts.
+    #[clap(long)]
+    pub diff_mode: bool,
+    /// Omit the code from the accounts.
+    #[clap(long)]
+    pub disable_code: bool,
+    /// Omit the storage from the accounts.
+    #[clap(long)]
+    pub disable_storage: bool,
+    /// The file to write the trace to, the standard output when omitted.
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+impl TracePrestateArgs {
+    pub fn run(self, output: &OutputArgs) -> eyre::Result<()> {
+        let config = PrestateTracerConfig {
+            diff_mode: self.diff_mode,
+            disable_code: self.disable_code,
+            disable_storage: self.disable_storage,
+        };
+        let db = Database::open(&self.db)?;
+        let trace = prestate::trace_prestate(&db, self.block, &config)?;
+        output.emit(&TraceOutput::write(trace.to_json(), self.output)?)
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct DivergenceArgs {
+    /// The path of the database of the instance.
+    #[clap(long)]
+    pub db: PathBuf,
+    /// The path of the compiled Cairo program which executed the block.
+    #[clap(long)]
+    pub program: PathBuf,
+    /// The number of the block.
+    #[clap(long)]
+    pub block: u64,
+    /// The indexes of the transactions to report, all of them when omitted.
+    #[clap(long = "tx", value_delimiter = ',')]
+    pub transactions: Vec<u32>,
+    /// The format of the reports: `markdown` or `html`.
+    #[clap(long, default_value = "markdown")]
+    pub format: ReportFormat,
+    /// The directory to write the reports to, in a directory per block.
+    #[clap(short, long)]
+    pub output: PathBuf,
+}
+
+impl DivergenceArgs {
+    pub fn run(self, output: &OutputArgs) -> eyre::Result<()> {
+        let db = Database::open(&self.db)?;
+        let reports = divergence::compare_stored_block(&db, &self.program, self.block)?
+            .into_iter()
+            .filter(|report| {
+                self.transactions.is_empty() || self.transactions.contains(&report.tx_index)
+            })
+            .map(|report| report.save(&self.output, self.format))
+            .collect::<eyre::Result<_>>()?;
+        output.emit(&DivergenceOutput { reports })
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct CheckpointArgs {
+    /// The path of the database of the instance.
+    #[clap(long)]
+    pub db: PathBuf,
+    /// The path of the compiled Cairo program which executed the block.
+    #[clap(long)]
+    pub program: PathBuf,
+    /// The number of the block.
+    #[clap(long)]
+    pub block: u64,
+    /// The indexes of the transactions to checkpoint, all of them when omitted.
+    #[clap(long = "tx", value_delimiter = ',')]
+    pub transactions: Vec<u32>,
+    /// The directory to write the checkpoints to.
+    #[clap(short, long)]
+    pub output: PathBuf,
+}
+
+impl CheckpointArgs {
+    pub fn run(self, output: &OutputArgs) -> eyre::Result<()> {
+        let db = Database::open(&self.db)?;
+        let checkpoints = checkpoint::checkpoint_block(
+            &db,
+            &self.program,
+            self.block,
+            &self.transactions,
+            &self.output,
+        )?;
+        output.emit(&CheckpointOutput { checkpoints })
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct ResumeArgs {
+    /// The path of the database of the instance, holding the program input of the block.
+    #[clap(long)]
+    pub db: PathBuf,
+    /// The path of the compiled Cairo program which produced the checkpoint.
+    #[clap(long)]
+    pub program: PathBuf,
+    /// The path of the checkpoint.
+    #[clap(long)]
+    pub checkpoint: PathBuf,
+    /// The maximum number of Cairo steps run after the checkpoint, unlimited when omitted.
+    #[clap(long)]
+    pub max_steps: Option<usize>,
+}
+
+impl ResumeArgs {
+    pub fn run(self, output: &OutputArgs) -> eyre::Result<()> {
+        let limits = ExecutionLimits { max_steps: self.max_steps, timeout: None };
+        let db = Database::open(&self.db)?;
+        let diagnostics =
+            checkpoint::resume_checkpoint(&db, &self.program, &self.checkpoint, &limits)?;
+        output.emit(&ResumeOutput { completed: diagnostics.is_none(), diagnostics })
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct ProgramHashArgs {
+    /// The path of the compiled Cairo program.
+    #[clap(long)]
+    pub program: PathBuf,
+    /// The path of a registry of verifier parameters to look the program hash up in.
+    #[clap(long)]
+    pub verifier_params: Option<PathBuf>,
+    /// The prover version of the verifier parameters to look up.
+    #[clap(long, default_value = "stone")]
+    pub prover: String,
+}
+
+impl ProgramHashArgs {
+    pub fn run(self, output: &OutputArgs) -> eyre::Result<()> {
+        let program_hash = KakarotProgram::load(&self.program)?.program_hash()?;
+
+        let verifier = match self.verifier_params {
+            Some(path) => {
+                let registry = VerifierRegistry::load(path)?;
+                match registry.params(program_hash, &self.prover) {
+                    Some(params) => Some(params.clone()),
+                    None => eyre::bail!(
+                        "No verifier registered for program {program_hash} and prover '{}'",
+                        self.prover
+                    ),
+                }
+            }
+            None => None,
+        };
+        output.emit(&ProgramHashOutput { program_hash, verifier })
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct ProfileArgs {
+    /// The path of the database of the instance.
+    #[clap(long)]
+    pub db: PathBuf,
+    /// The path of the compiled Cairo program which executed the block.
+    #[clap(long)]
+    pub program: PathBuf,
+    /// The number of the block.
+    #[clap(long)]
+    pub block: u64,
+    /// The format of the profile: `folded` for the flamegraph tools or `pprof`.
+    #[clap(long, default_value = "folded")]
+    pub format: ProfileFormat,
+    /// The file to write the profile to.
+    #[clap(short, long)]
+    pub output: PathBuf,
+}
+
+impl ProfileArgs {
+    pub fn run(self, output: &OutputArgs) -> eyre::Result<()> {
+        let db = Database::open(&self.db)?;
+        let profile =
+            profiler::profile_block(&db, &self.program, self.block, self.format, &self.output)?;
+        output.emit(&ProfileOutput {
+            path: self.output,
+            steps: profile.steps(),
+            stacks: profile.stacks.len(),
+        })
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct AirInputsArgs {
+    /// The path of the compiled Cairo program.
+    #[clap(long)]
+    pub program: PathBuf,
+    /// The directory to write the trace, the memory, the public and private inputs and the public
+    /// output to.
+    #[clap(short, long)]
+    pub output: PathBuf,
+}
+
+impl AirInputsArgs {
+    pub fn run(self, output: &OutputArgs) -> eyre::Result<()> {
+        let paths = air::run_air_inputs(&self.program, &self.output)?;
+        output.emit(&AirInputsOutput::from(paths))
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct BenchmarkArgs {
+    /// The path of the database of the instance.
+    #[clap(long)]
+    pub db: PathBuf,
+    /// The path of the compiled Kakarot program replaying the blocks without a recorded program.
+    #[clap(long)]
+    pub program: PathBuf,
+    /// The first block to replay.
+    #[clap(long)]
+    pub from: u64,
+    /// The last block to replay, included.
+    #[clap(long)]
+    pub to: u64,
+    /// The path of the prover cost models, as a JSON array.
+    #[clap(long)]
+    pub cost_models: PathBuf,
+    /// The time between two blocks in seconds, derived from the block timestamps when omitted.
+    #[clap(long)]
+    pub block_time: Option<f64>,
+    /// The file to write the full report to, with the resources of each block, as JSON.
+    #[clap(long)]
+    pub report: Option<PathBuf>,
+}
+
+impl BenchmarkArgs {
+    pub fn run(self, output: &OutputArgs) -> eyre::Result<()> {
+        let db = Database::open(&self.db)?;
+        let models = CostModels::load(&self.cost_models)?;
+        let samples = benchmark::replay_range(&db, &self.program, self.from, self.to)?;
+        let report = BenchmarkReport::new(samples, &models, self.block_time)?;
+        if let Some(path) = &self.report {
+            fs::write(path, serde_json::to_vec_pretty(&report)?)?;
+        }
+        output.emit(&BenchmarkOutput::new(&report, self.report))
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct ExportAnalyticsArgs {
+    /// The path of the database of the instance.
+    #[clap(long)]
+    pub db: PathBuf,
+    /// The path of the compiled Cairo program which executed the blocks.
+    #[clap(long)]
+    pub program: PathBuf,
+    /// The first block to export.
+    #[clap(long)]
+    pub from: u64,
+    /// The last block to export, included.
+    #[clap(long)]
+    pub to: u64,
+    /// The format of the exported files: `parquet` or `arrow-ipc`.
+    #[clap(long, default_value = "parquet")]
+    pub format: AnalyticsFormat,
+    /// The directory to write the exported files to.
+    #[clap(short, long)]
+    pub output: PathBuf,
+}
+
+impl ExportAnalyticsArgs {
+    pub fn run(self, output: &OutputArgs) -> eyre::Result<()> {
+        let db = Database::open(&self.db)?;
+        let mut exporter = AnalyticsExporter::create(&self.output, self.format)?;
+        let blocks =
+            analytics::export_analytics(&db, &self.program, self.from..=self.to, &mut exporter)?;
+        exporter.finish()?;
+        output.emit(&AnalyticsOutput { dir: self.output, blocks })
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct VerifyBlobsArgs {
+    /// The path of the database of the instance.
+    #[clap(long)]
+    pub db: PathBuf,
+    /// The number of the block.
+    #[clap(long)]
+    pub block: u64,
+    /// The path of a JSON array of the posted blobs, as hex strings, in order.
+    #[clap(long)]
+    pub blobs: PathBuf,
+}
+
+impl VerifyBlobsArgs {
+    pub fn run(self, output: &OutputArgs) -> eyre::Result<()> {
+        let db = Database::open(&self.db)?;
+        let blobs: Vec<Bytes> = serde_json::from_slice(&fs::read(&self.blobs)?)?;
+        let witness = blob::verify_posted(&db, self.block, &blobs)?
+            .ok_or_else(|| eyre::eyre!("No blob commitments stored for block {}", self.block))?;
+        output.emit(&BlobsOutput::from(witness))
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SharpCommands {
+    /// Submit the queued proving jobs to SHARP as Cairo PIEs.
+    Submit(SharpSubmitArgs),
+    /// Poll the submitted jobs, recording the proofs of the processed ones and extending the chain
+    /// of the proven blocks.
+    Poll(SharpPollArgs),
+}
+
+impl SharpCommands {
+    pub fn run(self, output: &OutputArgs) -> eyre::Result<()> {
+        let runtime = tokio::runtime::Runtime::new()?;
+        match self {
+            Self::Submit(args) => {
+                let db = Database::open(&args.sharp.db)?;
+                let store = store::open_store(&db, args.sharp.proof_store.as_deref())?;
+                let client = SharpClient::new(args.sharp.gateway_url);
+                let verifiers = args.verifier_params.map(VerifierRegistry::load).transpose()?;
+                let jobs = runtime.block_on(sharp::submit_jobs(
+                    &db,
+                    &*store,
+                    &client,
+                    &args.work_dir,
+                    args.limit,
+                    verifiers.as_ref(),
+                ))?;
+                output.emit(&SharpSubmitOutput::from(jobs))
+            }
+            Self::Poll(args) => {
+                let db = Database::open(&args.sharp.db)?;
+                let store = store::open_store(&db, args.sharp.proof_store.as_deref())?;
+                let client = SharpClient::new(args.sharp.gateway_url);
+                let quorum = (!args.quorum.is_empty())
+                    .then(|| QuorumConfig::new(args.quorum))
+                    .transpose()?;
+                let policy = RetryPolicy {
+                    initial_backoff: Duration::from_secs(args.backoff_secs),
+                    max_backoff: Duration::from_secs(args.max_backoff_secs),
+                    max_attempts: args.max_attempts,
+                    ..Default::default()
+                };
+                let blocks = runtime.block_on(sharp::poll_jobs(
+                    &db,
+                    &*store,
+                    &client,
+                    quorum.as_ref(),
+                    &policy,
+                ))?;
+
+                // The proven blocks extend the chain of the proven blocks, as the imported proofs.
+                let extension = chain::extend_and_requeue(&db, &*store, &policy)?;
+                let chain_head = extension.links.last().map(|head| ChainHeadOutput {
+                    block_number: head.block_number,
+                    hash: head.hash,
+                });
+                let rejected = extension.rejected.map(|err| err.block_number());
+                output.emit(&ImportOutput { blocks, chain_head, rejected })
+            }
+        }
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct SharpArgs {
+    /// The path of the database of the instance.
+    #[clap(long)]
+    pub db: PathBuf,
+    /// The URL of the SHARP gateway.
+    #[clap(long)]
+    pub gateway_url: String,
+    /// The path of the redb file storing the proofs and the metadata of the proven blocks, the
+    /// database when omitted.
+    #[clap(long)]
+    pub proof_store: Option<PathBuf>,
+}
+
+#[derive(Debug, Parser)]
+pub struct SharpSubmitArgs {
+    #[command(flatten)]
+    pub sharp: SharpArgs,
+    /// The directory to write the Cairo PIEs to before their submission.
+    #[clap(long)]
+    pub work_dir: PathBuf,
+    /// The maximum number of jobs to submit.
+    #[clap(long, default_value = "100")]
+    pub limit: usize,
+    /// The path of the registry of the verifier parameters the programs of the jobs are checked
+    /// against.
+    #[clap(long)]
+    pub verifier_params: Option<PathBuf>,
+}
+
+#[derive(Debug, Parser)]
+pub struct SharpPollArgs {
+    #[command(flatten)]
+    pub sharp: SharpArgs,
+    /// The comma separated provers which must agree on the fact of a block for it to be proven,
+    /// including `sharp`.
+    #[clap(long, value_delimiter = ',')]
+    pub quorum: Vec<String>,
+    /// The maximum number of attempts of a failed job, including the first one.
+    #[clap(long, default_value = "5")]
+    pub max_attempts: u32,
+    /// The delay in seconds before the first retry of a failed job, doubled on each attempt.
+    #[clap(long, default_value = "60")]
+    pub backoff_secs: u64,
+    /// The maximum delay in seconds between two attempts of a failed job.
+    #[clap(long, default_value = "3600")]
+    pub max_backoff_secs: u64,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CampaignCommands {
+    /// Start a campaign re-proving a range of blocks with a new program.
+    Start(CampaignStartArgs),
+    /// Print the progress of a campaign.
+    Status(CampaignArgs),
+    /// Export the pending blocks of a campaign as a bundle of Cairo PIEs.
+    Export(CampaignExportArgs),
+    /// Import the candidate proofs of the exported blocks of a campaign.
+    Import(CampaignImportArgs),
+    /// Verify the candidate proofs of a campaign, replacing the proofs of the blocks which verify.
+    Verify(CampaignVerifyArgs),
+    /// Schedule the blocks whose candidate proof failed to verify again.
+    Retry(CampaignArgs),
+}
+
+impl CampaignCommands {
+    pub fn run(self, output: &OutputArgs) -> eyre::Result<()> {
+        match self {
+            Self::Start(args) => {
+                let db = Database::open(&args.campaign.db)?;
+                let CampaignStartArgs { campaign: target, program, from_block, to_block } = args;
+                let new = Campaign::new(target.name, program, from_block, to_block)?;
+                let progress = campaign::start_campaign(&db, &new)?;
+                output.emit(&CampaignStatusOutput::new(new.name, &progress))
+            }
+            Self::Status(args) => {
+                let db = Database::open(&args.db)?;
+                let progress = db.campaign_progress(&args.name)?;
+                output.emit(&CampaignStatusOutput::new(args.name, &progress))
+            }
+            Self::Export(args) => {
+                let db = Database::open(&args.campaign.db)?;
+                let manifest =
+                    campaign::export_campaign(&db, &args.campaign.name, &args.output, args.limit)?;
+                output.emit(&ExportOutput::new(args.output, &manifest))
+            }
+            Self::Import(args) => {
+                let db = Database::open(&args.campaign.db)?;
+                let blocks =
+                    campaign::import_campaign_proofs(&db, &args.campaign.name, &args.input)?;
+                output.emit(&ImportOutput { blocks, chain_head: None, rejected: None })
+            }
+            Self::Verify(args) => {
+                let db = Database::open(&args.campaign.db)?;
+                let verifier = CommandVerifier { program: args.verifier, args: args.verifier_args };
+                let blocks = campaign::verify_campaign(&db, &args.campaign.name, &verifier)?;
+                output.emit(&VerifyOutput { blocks })
+            }
+            Self::Retry(args) => {
+                let db = Database::open(&args.db)?;
+                let blocks = campaign::retry_failed(&db, &args.name)?;
+                output.emit(&RetryOutput { blocks })
+            }
+        }
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct CampaignArgs {
+    /// The path of the database of the instance.
+    #[clap(long)]
+    pub db: PathBuf,
+    /// The name of the campaign.
+    #[clap(long)]
+    pub name: String,
+}
+
+#[derive(Debug, Parser)]
+pub struct CampaignStartArgs {
+    #[command(flatten)]
+    pub campaign: CampaignArgs,
+    /// The path of the compiled Kakarot program re-proving the blocks.
+    #[clap(long)]
+    pub program: PathBuf,
+    /// The first block to re-prove.
+    #[clap(long)]
+    pub from_block: u64,
+    /// The last block to re-prove, included.
+    #[clap(long)]
+    pub to_block: u64,
+}
+
+#[derive(Debug, Parser)]
+pub struct CampaignExportArgs {
+    #[command(flatten)]
+    pub campaign: CampaignArgs,
+    /// The directory to write the bundle to.
+    #[clap(short, long)]
+    pub output: PathBuf,
+    /// The maximum number of blocks to export.
+    #[clap(long, default_value = "100")]
+    pub limit: usize,
+}
+
+#[derive(Debug, Parser)]
+pub struct CampaignImportArgs {
+    #[command(flatten)]
+    pub campaign: CampaignArgs,
+    /// The directory holding the proofs and their manifest.
+    #[clap(short, long)]
+    pub input: PathBuf,
+}
+
+#[derive(Debug, Parser)]
+pub struct CampaignVerifyArgs {
+    #[command(flatten)]
+    pub campaign: CampaignArgs,
+    /// The verifier executable, reading a proof on its standard input and exiting successfully
+    /// when it verifies.
+    #[clap(long)]
+    pub verifier: PathBuf,
+    /// The arguments of the verifier.
+    #[clap(last = true)]
+    pub verifier_args: Vec<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct ExExArgs {
+    /// A Kakarot instance run by the ExEx, e.g.
+    /// `name=shadow,program=os.json,chain-id=1,start-block=100,proving=deferred`. Can be repeated
+    /// to run several programs side by side, the ExEx being disabled when omitted.
+    #[clap(long = "instance")]
+    pub instances: Vec<InstanceConfig>,
 }
 
 #[derive(Debug, Parser)]
 pub struct LogArgs {
     #[clap(short, long, default_value = "info")]
     pub filter: String,
+    /// Log the close of the spans of the pipeline of the blocks, with their durations.
+    #[clap(long)]
+    pub spans: bool,
+    /// Trace the pipeline of one block out of the given number, the spans of the other blocks
+    /// being disabled.
+    #[clap(long, default_value = "1")]
+    pub trace_sample: u64,
+    #[command(flatten)]
+    pub otlp: OtlpArgs,
 }
 
 impl LogArgs {
-    pub fn init_tracing(&self) {
-        let filter = EnvFilter::builder().parse(&self.filter).expect("failed to parse filter");
-        tracing_subscriber::fmt().with_env_filter(filter).init();
+    /// Initializes the subscriber, along with the OTLP exporters which are flushed when the
+    /// returned guard is dropped, failing when the exporters cannot be installed.
+    ///
+    /// The logs are written to the standard error when the results are printed as JSON, and
+    /// filtered down to the warnings and the errors when quiet.
+    pub fn init_tracing(&self, output: &OutputArgs) -> eyre::Result<Option<Otlp>> {
+        telemetry::set_sampling(TraceSampling::every(self.trace_sample));
+        let config = OtlpConfig::from(&self.otlp);
+        let otlp = config.is_enabled().then(|| Otlp::install(&config)).transpose()?;
+
+        let filter = if output.quiet { "warn" } else { self.filter.as_str() };
+        let filter = EnvFilter::builder().parse(filter).expect("failed to parse filter");
+        let span_events = if self.spans { FmtSpan::CLOSE } else { FmtSpan::NONE };
+        let writer = if output.is_json() {
+            BoxMakeWriter::new(std::io::stderr)
+        } else {
+            BoxMakeWriter::new(std::io::stdout)
+        };
+        let spans = otlp
+            .as_ref()
+            .and_then(Otlp::tracer)
+            .map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer));
+        tracing_subscriber::registry()
+            .with(filter)
+            .with(
+                tracing_subscriber::fmt::layer().with_span_events(span_events).with_writer(writer),
+            )
+            .with(spans)
+            .init();
+        Ok(otlp)
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct OtlpArgs {
+    /// Export the spans of the pipeline to the OTLP collector at the given gRPC endpoint, e.g.
+    /// `http://localhost:4317`.
+    #[clap(long)]
+    pub otlp_traces_endpoint: Option<String>,
+    /// Export the metrics to the OTLP collector at the given gRPC endpoint.
+    #[clap(long)]
+    pub otlp_metrics_endpoint: Option<String>,
+    /// The interval between two exports of the metrics, in seconds.
+    #[clap(long, default_value = "60")]
+    pub otlp_metrics_interval: u64,
+    /// The name of the service in the resource attributes of the exported data.
+    #[clap(long, default_value = otlp::SERVICE_NAME)]
+    pub otlp_service_name: String,
+    /// A resource attribute of the exported data as `key=value`, e.g.
+    /// `deployment.environment=staging`.
+    #[clap(long = "otlp-resource", value_parser = otlp::parse_attribute)]
+    pub otlp_resources: Vec<(String, String)>,
+}
+
+impl From<&OtlpArgs> for OtlpConfig {
+    fn from(args: &OtlpArgs) -> Self {
+        Self {
+            traces_endpoint: args.otlp_traces_endpoint.clone(),
+            metrics_endpoint: args.otlp_metrics_endpoint.clone(),
+            metrics_interval: Duration::from_secs(args.otlp_metrics_interval),
+            service_name: args.otlp_service_name.clone(),
+            attributes: args.otlp_resources.clone(),
+        }
     }
 }
 
@@ -0,0 +1,859 @@
+//! The results of the commands, printed either for humans or as JSON documents.
+//!
+//! With `--output json`, each command prints a single JSON document on the standard output, its
+//! logs being written to the standard error, and a failed command prints an [`ErrorOutput`],
+//! unless it already printed its result, see [`ReportedFailure`]. The
+//! documents are serialized from the structs of this module, whose JSON schemas, generated from
+//! the structs and their documentation, are printed by `keth output-schema`. Fields are only ever
+//! added to the documents, so that scripts parsing them keep working across releases.
+
+use alloy_primitives::B256;
+use clap::{Parser, ValueEnum};
+use kakarot_exex::{
+    air::AirInputPaths, artifacts::LifecycleReport, benchmark::BenchmarkReport, blob::BlobWitness,
+    campaign::CampaignProgress, compression::MigrationReport, deferred::BundleManifest,
+    fact::FactStatus, integrity::FsckReport, light_client::LightClientManifest,
+    limits::Diagnostics, sharp::SharpJob, verifier::VerifierParams,
+};
+use schemars::{schema::RootSchema, schema_for, JsonSchema};
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt, fs, path::PathBuf};
+
+/// The format of the results of the commands.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable results, along with the logs.
+    #[default]
+    Human,
+    /// A JSON document on the standard output, the logs being written to the standard error.
+    Json,
+}
+
+#[derive(Debug, Parser)]
+pub struct OutputArgs {
+    /// The format of the results of the commands: `human` or `json`.
+    #[clap(long = "output", value_enum, default_value = "human")]
+    pub format: OutputFormat,
+    /// Only log the warnings and the errors, the results being printed alone.
+    #[clap(short, long)]
+    pub quiet: bool,
+}
+
+impl OutputArgs {
+    /// Returns whether the results are printed as JSON.
+    pub fn is_json(&self) -> bool {
+        self.format == OutputFormat::Json
+    }
+
+    /// Prints the result of a command.
+    pub fn emit<T: Output>(&self, output: &T) -> eyre::Result<()> {
+        match self.format {
+            OutputFormat::Human => print!("{output}"),
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(output)?),
+        }
+        Ok(())
+    }
+
+    /// Prints the error of a failed command.
+    pub fn fail(&self, err: &eyre::Report) {
+        match self.format {
+            OutputFormat::Human => eprintln!("Error: {err:?}"),
+            // The result was printed, it is the single document of the command.
+            OutputFormat::Json if err.is::<ReportedFailure>() => {}
+            OutputFormat::Json => {
+                let output = ErrorOutput::from(err);
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&output).expect("error is serializable")
+                );
+            }
+        }
+    }
+}
+
+/// The result of a command, serialized as JSON and displayed for humans, each line ending with a
+/// newline.
+pub trait Output: Serialize + JsonSchema + fmt::Display {}
+
+impl<T: Serialize + JsonSchema + fmt::Display> Output for T {}
+
+/// Returns the JSON schemas of the results of the commands, by command.
+pub fn schemas() -> BTreeMap<&'static str, RootSchema> {
+    BTreeMap::from([
+        ("error", schema_for!(ErrorOutput)),
+        ("codegen", schema_for!(CodegenOutput)),
+        ("export-jobs", schema_for!(ExportOutput)),
+        ("import-proofs", schema_for!(ImportOutput)),
+        ("export-light-client", schema_for!(LightClientOutput)),
+        ("reconcile-facts", schema_for!(FactsOutput)),
+        ("sharp submit", schema_for!(SharpSubmitOutput)),
+        ("sharp poll", schema_for!(ImportOutput)),
+        ("campaign start", schema_for!(CampaignStatusOutput)),
+        ("campaign status", schema_for!(CampaignStatusOutput)),
+        ("campaign export", schema_for!(ExportOutput)),
+        ("campaign import", schema_for!(ImportOutput)),
+        ("campaign verify", schema_for!(VerifyOutput)),
+        ("campaign retry", schema_for!(RetryOutput)),
+        ("compress-artifacts", schema_for!(CompressOutput)),
+        ("tier-artifacts", schema_for!(TierOutput)),
+        ("fsck", schema_for!(FsckOutput)),
+        ("trace-transaction", schema_for!(TraceOutput)),
+        ("trace-prestate", schema_for!(TraceOutput)),
+        ("divergence", schema_for!(DivergenceOutput)),
+        ("checkpoint", schema_for!(CheckpointOutput)),
+        ("resume", schema_for!(ResumeOutput)),
+        ("program-hash", schema_for!(ProgramHashOutput)),
+        ("profile", schema_for!(ProfileOutput)),
+        ("air-inputs", schema_for!(AirInputsOutput)),
+        ("benchmark", schema_for!(BenchmarkOutput)),
+        ("export-analytics", schema_for!(AnalyticsOutput)),
+        ("verify-blobs", schema_for!(BlobsOutput)),
+    ])
+}
+
+/// The failure of a command which printed its result, e.g. `fsck` finding damaged artifacts: the
+/// command exits with a nonzero code, without printing an [`ErrorOutput`] in JSON.
+#[derive(Debug, Clone)]
+pub struct ReportedFailure(pub String);
+
+impl fmt::Display for ReportedFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ReportedFailure {}
+
+/// The error of a failed command.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ErrorOutput {
+    /// The message of the error.
+    pub error: String,
+    /// The messages of the causes of the error, from the outermost.
+    pub causes: Vec<String>,
+}
+
+impl From<&eyre::Report> for ErrorOutput {
+    fn from(err: &eyre::Report) -> Self {
+        Self {
+            error: err.to_string(),
+            causes: err.chain().skip(1).map(ToString::to_string).collect(),
+        }
+    }
+}
+
+/// The result of `codegen`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct CodegenOutput {
+    /// The file the code was written to, if any.
+    pub path: Option<PathBuf>,
+    /// The generated code, when not written to a file.
+    pub code: Option<String>,
+}
+
+impl fmt::Display for CodegenOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.code, &self.path) {
+            (Some(code), _) => f.write_str(code),
+            (None, Some(path)) => writeln!(f, "Generated code in {}", path.display()),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// The result of `export-jobs` and `campaign export`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ExportOutput {
+    /// The directory of the bundle.
+    pub path: PathBuf,
+    /// The numbers of the exported blocks.
+    pub blocks: Vec<u64>,
+}
+
+impl ExportOutput {
+    pub fn new(path: PathBuf, manifest: &BundleManifest) -> Self {
+        Self { path, blocks: manifest.entries.iter().map(|entry| entry.job.block_number).collect() }
+    }
+}
+
+impl fmt::Display for ExportOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Exported {} jobs to {}", self.blocks.len(), self.path.display())
+    }
+}
+
+/// The result of `sharp submit`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct SharpSubmitOutput {
+    /// The submitted jobs.
+    pub jobs: Vec<SharpJobOutput>,
+}
+
+/// A job submitted by `sharp submit`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct SharpJobOutput {
+    /// The number of the block.
+    pub block_number: u64,
+    /// The key of the SHARP job.
+    pub job_key: String,
+    /// The fact registered once the job is proven.
+    pub fact: B256,
+}
+
+impl From<Vec<SharpJob>> for SharpSubmitOutput {
+    fn from(jobs: Vec<SharpJob>) -> Self {
+        let jobs = jobs
+            .into_iter()
+            .map(|job| SharpJobOutput {
+                block_number: job.block_number,
+                job_key: job.job_key,
+                fact: job.fact,
+            })
+            .collect();
+        Self { jobs }
+    }
+}
+
+impl fmt::Display for SharpSubmitOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Submitted {} jobs to SHARP", self.jobs.len())?;
+        for job in &self.jobs {
+            writeln!(f, "  block {}: job {}", job.block_number, job.job_key)?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of `reconcile-facts`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct FactsOutput {
+    /// The reconciled blocks, whose proof and output are recorded.
+    pub blocks: Vec<FactOutput>,
+}
+
+/// A block reconciled by `reconcile-facts`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct FactOutput {
+    /// The number of the block.
+    pub block_number: u64,
+    /// The fact of the proof of the block.
+    pub fact: B256,
+    /// Whether the fact is registered on-chain.
+    pub registered: bool,
+}
+
+impl From<Vec<FactStatus>> for FactsOutput {
+    fn from(statuses: Vec<FactStatus>) -> Self {
+        let blocks = statuses
+            .into_iter()
+            .map(|status| FactOutput {
+                block_number: status.block_number,
+                fact: status.fact,
+                registered: status.registered,
+            })
+            .collect();
+        Self { blocks }
+    }
+}
+
+impl fmt::Display for FactsOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unregistered: Vec<_> = self.blocks.iter().filter(|block| !block.registered).collect();
+        writeln!(
+            f,
+            "Reconciled {} proven blocks, {} of them unregistered",
+            self.blocks.len(),
+            unregistered.len()
+        )?;
+        for block in unregistered {
+            writeln!(
+                f,
+                "  block {} is proven but fact {} is not registered",
+                block.block_number, block.fact
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of `export-light-client`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct LightClientOutput {
+    /// The directory of the bundles.
+    pub path: PathBuf,
+    /// The exported blocks.
+    pub blocks: Vec<LightClientBlock>,
+}
+
+/// A block exported by `export-light-client`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct LightClientBlock {
+    /// The number of the block.
+    pub block_number: u64,
+    /// The post-state root of the output of the block, echoed from its header.
+    #[schemars(with = "String")]
+    pub post_state_root: B256,
+}
+
+impl LightClientOutput {
+    pub fn new(path: PathBuf, manifest: &LightClientManifest) -> Self {
+        let blocks = manifest
+            .entries
+            .iter()
+            .map(|entry| LightClientBlock {
+                block_number: entry.block_number,
+                post_state_root: entry.post_state_root,
+            })
+            .collect();
+        Self { path, blocks }
+    }
+}
+
+impl fmt::Display for LightClientOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Exported {} light-client bundles to {}",
+            self.blocks.len(),
+            self.path.display()
+        )?;
+        for block in &self.blocks {
+            writeln!(
+                f,
+                "  block {}: post-state root {}",
+                block.block_number, block.post_state_root
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The head of the chain of the proven blocks.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ChainHeadOutput {
+    /// The number of the last proven block of the chain.
+    pub block_number: u64,
+    /// The hash of the link of the block.
+    #[schemars(with = "String")]
+    pub hash: B256,
+}
+
+/// The result of `import-proofs` and `campaign import`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ImportOutput {
+    /// The numbers of the blocks whose proof was imported.
+    pub blocks: Vec<u64>,
+    /// The new head of the chain of the proven blocks, if it was extended.
+    pub chain_head: Option<ChainHeadOutput>,
+    /// The number of the block whose proof broke the continuity of the chain and was rejected.
+    pub rejected: Option<u64>,
+}
+
+impl fmt::Display for ImportOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Imported {} proofs", self.blocks.len())?;
+        if let Some(head) = &self.chain_head {
+            writeln!(f, "Extended proof chain to block {} ({})", head.block_number, head.hash)?;
+        }
+        if let Some(number) = self.rejected {
+            writeln!(f, "Rejected the proof of block {number}, queued again")?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of `campaign start` and `campaign status`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct CampaignStatusOutput {
+    /// The name of the campaign.
+    pub name: String,
+    /// The number of blocks waiting to be exported.
+    pub pending: u64,
+    /// The number of blocks being proven.
+    pub exported: u64,
+    /// The number of blocks whose candidate proof waits to be verified.
+    pub proven: u64,
+    /// The number of blocks whose candidate proof replaced the previous one.
+    pub verified: u64,
+    /// The number of blocks whose candidate proof did not verify.
+    pub failed: u64,
+    /// Whether all the blocks were verified.
+    pub complete: bool,
+}
+
+impl CampaignStatusOutput {
+    pub fn new(name: String, progress: &CampaignProgress) -> Self {
+        Self {
+            name,
+            pending: progress.pending,
+            exported: progress.exported,
+            proven: progress.proven,
+            verified: progress.verified,
+            failed: progress.failed,
+            complete: progress.is_complete(),
+        }
+    }
+}
+
+impl fmt::Display for CampaignStatusOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Campaign {}: {} pending, {} exported, {} proven, {} verified, {} failed{}",
+            self.name,
+            self.pending,
+            self.exported,
+            self.proven,
+            self.verified,
+            self.failed,
+            if self.complete { " (complete)" } else { "" }
+        )
+    }
+}
+
+/// The result of `campaign verify`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct VerifyOutput {
+    /// The numbers of the blocks whose candidate proof verified and replaced their proof.
+    pub blocks: Vec<u64>,
+}
+
+impl fmt::Display for VerifyOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Replaced {} proofs", self.blocks.len())
+    }
+}
+
+/// The result of `campaign retry`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct RetryOutput {
+    /// The number of failed blocks scheduled again.
+    pub blocks: u64,
+}
+
+impl fmt::Display for RetryOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Scheduled {} failed blocks", self.blocks)
+    }
+}
+
+/// The result of `compress-artifacts`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct CompressOutput {
+    /// The kinds of artifacts whose dictionary was trained.
+    pub trained: Vec<String>,
+    /// The number of artifacts compressed or upgraded, by kind.
+    pub rewritten: BTreeMap<String, usize>,
+}
+
+impl From<&MigrationReport> for CompressOutput {
+    fn from(report: &MigrationReport) -> Self {
+        Self {
+            trained: report.trained.iter().map(ToString::to_string).collect(),
+            rewritten: report
+                .compressed
+                .iter()
+                .map(|(kind, count)| (kind.to_string(), *count))
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for CompressOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for kind in &self.trained {
+            writeln!(f, "Trained the {kind} dictionary")?;
+        }
+        let rewritten: usize = self.rewritten.values().sum();
+        writeln!(f, "Compressed and upgraded {rewritten} artifacts")
+    }
+}
+
+/// The lifecycle rules applied by `tier-artifacts`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct LifecycleOutput {
+    /// The number of cached copies evicted.
+    pub evicted: usize,
+    /// The number of objects deleted from the storage.
+    pub expired: usize,
+}
+
+impl From<&LifecycleReport> for LifecycleOutput {
+    fn from(report: &LifecycleReport) -> Self {
+        Self { evicted: report.evicted, expired: report.expired }
+    }
+}
+
+/// The result of `tier-artifacts`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct TierOutput {
+    /// The number of traces tiered.
+    pub traces: usize,
+    /// The number of memory dumps tiered.
+    pub dumps: usize,
+    /// The outcome of the lifecycle rules, if applied.
+    pub lifecycle: Option<LifecycleOutput>,
+}
+
+impl fmt::Display for TierOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Tiered {} traces and {} dumps", self.traces, self.dumps)?;
+        if let Some(lifecycle) = &self.lifecycle {
+            writeln!(
+                f,
+                "Evicted {} cached copies and expired {} objects",
+                lifecycle.evicted, lifecycle.expired
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of `fsck`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct FsckOutput {
+    /// The number of artifacts checked.
+    pub checked: usize,
+    /// The keys of the artifacts whose content does not match their checksum.
+    pub corrupted: Vec<String>,
+    /// The keys of the artifacts recorded but missing.
+    pub missing: Vec<String>,
+    /// The keys of the objects of the storage recorded by no artifact.
+    pub orphaned: Vec<String>,
+    /// The blocks scheduled for re-generation by the repair.
+    pub scheduled: Vec<u64>,
+    /// Whether no problem was found.
+    pub clean: bool,
+}
+
+impl From<&FsckReport> for FsckOutput {
+    fn from(report: &FsckReport) -> Self {
+        Self {
+            checked: report.checked,
+            corrupted: report.corrupted.iter().map(ToString::to_string).collect(),
+            missing: report.missing.iter().map(ToString::to_string).collect(),
+            orphaned: report.orphaned.clone(),
+            scheduled: report.scheduled.clone(),
+            clean: report.is_clean(),
+        }
+    }
+}
+
+impl fmt::Display for FsckOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for artifact in &self.corrupted {
+            writeln!(f, "Corrupted artifact {artifact}")?;
+        }
+        for artifact in &self.missing {
+            writeln!(f, "Missing artifact {artifact}")?;
+        }
+        for key in &self.orphaned {
+            writeln!(f, "Orphaned object {key}")?;
+        }
+        if !self.scheduled.is_empty() {
+            writeln!(f, "Scheduled the re-generation of blocks {:?}", self.scheduled)?;
+        }
+        writeln!(f, "Checked {} artifacts", self.checked)
+    }
+}
+
+/// The result of `trace-transaction` and `trace-prestate`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct TraceOutput {
+    /// The file the trace was written to, if any.
+    pub path: Option<PathBuf>,
+    /// The trace in the format of the geth tracer, when not written to a file.
+    pub trace: Option<serde_json::Value>,
+}
+
+impl TraceOutput {
+    /// Writes a trace to the given file, or keeps it to be printed when omitted.
+    pub fn write(trace: serde_json::Value, path: Option<PathBuf>) -> eyre::Result<Self> {
+        match path {
+            Some(path) => {
+                fs::write(&path, format!("{trace:#}"))?;
+                Ok(Self { path: Some(path), trace: None })
+            }
+            None => Ok(Self { path: None, trace: Some(trace) }),
+        }
+    }
+}
+
+impl fmt::Display for TraceOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.trace, &self.path) {
+            (Some(trace), _) => writeln!(f, "{trace:#}"),
+            (None, Some(path)) => writeln!(f, "Saved trace to {}", path.display()),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// The result of `divergence`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct DivergenceOutput {
+    /// The paths of the reports of the diverging transactions.
+    pub reports: Vec<PathBuf>,
+}
+
+impl fmt::Display for DivergenceOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.reports.is_empty() {
+            return writeln!(f, "No divergence found");
+        }
+        for path in &self.reports {
+            writeln!(f, "Saved report to {}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of `checkpoint`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct CheckpointOutput {
+    /// The paths of the saved checkpoints, in the order of the transactions.
+    pub checkpoints: Vec<PathBuf>,
+}
+
+impl fmt::Display for CheckpointOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Saved {} checkpoints", self.checkpoints.len())
+    }
+}
+
+/// The result of `resume`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ResumeOutput {
+    /// Whether the execution completed.
+    pub completed: bool,
+    /// The diagnostics of the execution, when interrupted by its limits.
+    #[schemars(with = "Option<serde_json::Value>")]
+    pub diagnostics: Option<Diagnostics>,
+}
+
+impl fmt::Display for ResumeOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.diagnostics {
+            Some(diagnostics) => writeln!(f, "{diagnostics:#?}"),
+            None => writeln!(f, "Execution completed"),
+        }
+    }
+}
+
+/// The result of `program-hash`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ProgramHashOutput {
+    /// The hash of the program, as hashed by the bootloader.
+    #[schemars(with = "String")]
+    pub program_hash: B256,
+    /// The parameters of the verifier of the program, when looked up.
+    #[schemars(with = "Option<serde_json::Value>")]
+    pub verifier: Option<VerifierParams>,
+}
+
+impl fmt::Display for ProgramHashOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.program_hash)?;
+        if let Some(params) = &self.verifier {
+            writeln!(f, "{params:#?}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of `profile`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ProfileOutput {
+    /// The file the profile was written to.
+    pub path: PathBuf,
+    /// The number of Cairo steps profiled.
+    pub steps: usize,
+    /// The number of distinct call stacks.
+    pub stacks: usize,
+}
+
+impl fmt::Display for ProfileOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Saved the profile of {} steps over {} stacks to {}",
+            self.steps,
+            self.stacks,
+            self.path.display()
+        )
+    }
+}
+
+/// The result of `air-inputs`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct AirInputsOutput {
+    /// The encoded trace.
+    pub trace: PathBuf,
+    /// The encoded memory.
+    pub memory: PathBuf,
+    /// The public input.
+    pub public_input: PathBuf,
+    /// The private input.
+    pub private_input: PathBuf,
+    /// The program hash and output of the run.
+    pub public_output: PathBuf,
+}
+
+impl From<AirInputPaths> for AirInputsOutput {
+    fn from(paths: AirInputPaths) -> Self {
+        Self {
+            trace: paths.trace,
+            memory: paths.memory,
+            public_input: paths.public_input,
+            private_input: paths.private_input,
+            public_output: paths.public_output,
+        }
+    }
+}
+
+impl fmt::Display for AirInputsOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Saved AIR inputs to {}", self.private_input.display())
+    }
+}
+
+/// The result of `benchmark`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct BenchmarkOutput {
+    /// The number of replayed blocks.
+    pub blocks: usize,
+    /// The total number of Cairo steps.
+    pub steps: usize,
+    /// The total gas used.
+    pub gas_used: u64,
+    /// The number of Cairo steps per gas, fitted over the replayed blocks.
+    pub steps_per_gas: f64,
+    /// The time between two blocks, in seconds.
+    pub block_time_secs: f64,
+    /// The projections of a day of chain, by cost model.
+    pub projections: Vec<ProjectionOutput>,
+    /// The file the full report was written to, if any.
+    pub report: Option<PathBuf>,
+}
+
+/// The projection of a cost model by `benchmark`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ProjectionOutput {
+    /// The name of the cost model.
+    pub model: String,
+    /// The mean proving time of a block, in seconds.
+    pub secs_per_block: f64,
+    /// The proving hours of a day of chain.
+    pub proving_hours_per_day: f64,
+    /// The number of machines proving in parallel needed to keep up with the chain.
+    pub machines: u64,
+    /// The hardware cost of a day of chain.
+    pub cost_per_day: f64,
+}
+
+impl BenchmarkOutput {
+    pub fn new(report: &BenchmarkReport, path: Option<PathBuf>) -> Self {
+        let projections = report
+            .projections
+            .iter()
+            .map(|projection| ProjectionOutput {
+                model: projection.model.clone(),
+                secs_per_block: projection.secs_per_block,
+                proving_hours_per_day: projection.proving_hours_per_day,
+                machines: projection.machines,
+                cost_per_day: projection.cost_per_day,
+            })
+            .collect();
+        Self {
+            blocks: report.blocks.len(),
+            steps: report.steps,
+            gas_used: report.gas_used,
+            steps_per_gas: report.steps_per_gas.slope,
+            block_time_secs: report.block_time_secs,
+            projections,
+            report: path,
+        }
+    }
+}
+
+impl fmt::Display for BenchmarkOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Replayed {} blocks: {} steps for {} gas ({:.2} steps per gas), one block every {:.1}s",
+            self.blocks, self.steps, self.gas_used, self.steps_per_gas, self.block_time_secs
+        )?;
+        for projection in &self.projections {
+            writeln!(
+                f,
+                "  {}: {:.1}s per block, {:.1} proving hours per day on {} machines, {:.2} per day",
+                projection.model,
+                projection.secs_per_block,
+                projection.proving_hours_per_day,
+                projection.machines,
+                projection.cost_per_day
+            )?;
+        }
+        if let Some(path) = &self.report {
+            writeln!(f, "Saved the report to {}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of `export-analytics`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct AnalyticsOutput {
+    /// The directory the files were written to.
+    pub dir: PathBuf,
+    /// The numbers of the exported blocks, the blocks without a stored trace being skipped.
+    pub blocks: Vec<u64>,
+}
+
+impl fmt::Display for AnalyticsOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Exported the analytics of {} blocks to {}",
+            self.blocks.len(),
+            self.dir.display()
+        )
+    }
+}
+
+/// The result of `verify-blobs`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct BlobsOutput {
+    /// The number of the block.
+    pub block_number: u64,
+    /// The keccak256 hash of the program input held by the blobs.
+    pub witness_hash: B256,
+    /// The versioned hashes of the verified blobs, in order.
+    pub versioned_hashes: Vec<B256>,
+}
+
+impl From<BlobWitness> for BlobsOutput {
+    fn from(witness: BlobWitness) -> Self {
+        Self {
+            block_number: witness.block_number,
+            witness_hash: witness.witness_hash,
+            versioned_hashes: witness.versioned_hashes(),
+        }
+    }
+}
+
+impl fmt::Display for BlobsOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "The {} blobs of block {} hold its program input {}",
+            self.versioned_hashes.len(),
+            self.block_number,
+            self.witness_hash
+        )?;
+        for hash in &self.versioned_hashes {
+            writeln!(f, "  {hash}")?;
+        }
+        Ok(())
+    }
+}
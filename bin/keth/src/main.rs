@@ -1,4 +1,5 @@
 use clap::Parser;
+use kakarot_exex::exex::{Instance, KakarotRollup};
 use kakarot_node::node::KakarotNode;
 use keth::cli::Cli;
 use reth_chainspec::ChainSpec;
@@ -10,9 +11,24 @@ use std::sync::Arc;
 
 fn main() {
     let args = Cli::parse();
-    args.log.init_tracing();
+    let _otlp = match args.log.init_tracing(&args.output) {
+        Ok(otlp) => otlp,
+        Err(err) => {
+            args.output.fail(&err);
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(command) = args.command {
+        if let Err(err) = command.run(&args.output) {
+            args.output.fail(&err);
+            std::process::exit(1);
+        }
+        return;
+    }
 
     let chain_args = args.chain;
+    let instances = args.exex.instances;
 
     let chain_spec: ChainSpec = (&chain_args).into();
     let dev_args = (&chain_args).into();
@@ -29,13 +45,29 @@ fn main() {
     let database =
         Arc::new(init_db(db_path, config.db.database_args()).expect("failed to init db"));
 
+    let exex_dir = data_dir.data_dir().join("exex");
+
     let builder = NodeBuilder::new(config).with_database(database);
 
     let runner = CliRunner::default();
     runner
-        .run_command_until_exit(|ctx| async {
+        .run_command_until_exit(|ctx| async move {
             let builder = builder.with_launch_context(ctx.task_executor);
-            let handle = builder.launch_node(KakarotNode::default()).await?;
+            let handle = if instances.is_empty() {
+                builder.launch_node(KakarotNode::default()).await?
+            } else {
+                builder
+                    .node(KakarotNode::default())
+                    .install_exex("Kakarot", move |ctx| async move {
+                        let instances = instances
+                            .into_iter()
+                            .map(|config| Instance::open(config, &exex_dir))
+                            .collect::<eyre::Result<Vec<_>>>()?;
+                        Ok(KakarotRollup::with_instances(ctx, instances)?.start())
+                    })
+                    .launch()
+                    .await?
+            };
             handle.node_exit_future.await
         })
         .expect("failed to run command until exit")
@@ -54,6 +54,22 @@ func main{
 
     // TODO: Compare the final state root hash with block.state_root
     end:
+    // Write the public output of the block, see `ProgramOutput`.
+    // The output echoes the header given as input and is not checked against the execution: the
+    // hash is supplied with the header and the state root is not compared yet, see the TODO above.
+    assert [output_ptr] = header.number;
+    assert [output_ptr + 1] = header.hash.low;
+    assert [output_ptr + 2] = header.hash.high;
+    assert [output_ptr + 3] = header.parent_hash.low;
+    assert [output_ptr + 4] = header.parent_hash.high;
+    assert [output_ptr + 5] = header.state_root.low;
+    assert [output_ptr + 6] = header.state_root.high;
+    assert [output_ptr + 7] = header.transactions_root.low;
+    assert [output_ptr + 8] = header.transactions_root.high;
+    assert [output_ptr + 9] = header.receipt_root.low;
+    assert [output_ptr + 10] = header.receipt_root.high;
+    assert [output_ptr + 11] = header.gas_used;
+    let output_ptr = output_ptr + 12;
     return ();
 }
 
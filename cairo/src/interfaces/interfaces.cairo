@@ -12,8 +12,11 @@ namespace ICairo1Helpers {
     func recover_eth_address(msg_hash: Uint256, r: Uint256, s: Uint256, y_parity: felt) -> (
         success: felt, address: felt
     ) {
-        // TODO: Implement this function.
-        return (0, 0);
+        // The signer is recovered by the `recover_eth_address` hint and is not verified.
+        tempvar success;
+        tempvar address;
+        %{ recover_eth_address %}
+        return (success, address);
     }
 
     func verify_signature_secp256r1(
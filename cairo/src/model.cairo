@@ -235,6 +235,8 @@ namespace model {
         requests_root: Option,
         extra_data_len: felt,
         extra_data: felt*,
+        // The hash of the block, supplied with the input and not recomputed from the header.
+        hash: Uint256,
     }
 
     // @notice A struct representing an encoded Ethereum transaction.
@@ -69,6 +69,7 @@ class BlockHeader(BaseModelIterValuesOnly):
             "difficulty",
             "mix_hash",
             "prev_randao",
+            "hash",
         ]:
             if key not in values:
                 key = to_camel(key)
@@ -203,6 +204,8 @@ class BlockHeader(BaseModelIterValuesOnly):
     requests_root_value: Tuple[int, int]
     extra_data_len: int
     extra_data: bytes
+    hash_low: int = 0
+    hash_high: int = 0
 
 
 class TransactionEncoded(BaseModelIterValuesOnly):
@@ -0,0 +1,127 @@
+//! Benchmarks of the hints feeding the program input to the Kakarot OS.
+
+use alloy_consensus::Header;
+use alloy_primitives::{Address, Bytes, U256, U64};
+use cairo_vm::types::program::Program;
+use criterion::{criterion_group, criterion_main, Criterion};
+use kakarot_exex::{
+    executor::dry_run,
+    hints::KakarotHintProcessor,
+    input::program_input::{
+        AccountStateInput, BlockInput, HeaderInput, ProgramInput, TransactionInput,
+    },
+    limits::ExecutionLimits,
+    precompute::{HintCache, PrecomputedAccount, RecoveryInput},
+    serde::cache::ProgramLayoutCache,
+    tuning::RunnerTuning,
+};
+use std::{collections::BTreeMap, hint::black_box, sync::Arc};
+
+/// The compiled Kakarot OS.
+const OS: &[u8] = include_bytes!("../../../cairo/programs/os.json");
+
+/// Returns a pre-state of `accounts` accounts with a 24 KiB code and `slots` storage slots each,
+/// the size of the pre-state of a busy mainnet block.
+fn state(accounts: u8, slots: u64) -> BTreeMap<Address, AccountStateInput> {
+    (0..accounts)
+        .map(|byte| {
+            // Cycle through the opcodes, so that the code has both push data and jump destinations.
+            let code: Bytes = (0..24 * 1024).map(|i| (i as u8).wrapping_add(byte)).collect();
+            let account = AccountStateInput {
+                balance: U256::from(1_000_000),
+                code,
+                nonce: U64::from(1),
+                storage: (0..slots).map(|slot| (U256::from(slot), U256::from(slot))).collect(),
+            };
+            (Address::repeat_byte(byte), account)
+        })
+        .collect()
+}
+
+fn state_hint(c: &mut Criterion) {
+    let state = state(128, 64);
+    let mut group = c.benchmark_group("state_hint");
+    group.bench_function("sequential", |b| {
+        b.iter(|| black_box(&state).values().map(PrecomputedAccount::compute).collect::<Vec<_>>())
+    });
+    group.bench_function("parallel", |b| b.iter(|| HintCache::from_state(black_box(&state))));
+    group.finish();
+}
+
+/// Returns `count` EIP-1559 transactions, the number of transactions of a busy mainnet block.
+fn transactions(count: u16) -> Vec<TransactionInput> {
+    // The `x` coordinate of the generator, so that every signature recovers a signer.
+    let r = U256::from_str_radix(
+        "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        16,
+    )
+    .unwrap();
+    (0..count)
+        .map(|nonce| {
+            let rlp: Bytes = [0x02, 0xc2, 0x01, (nonce % 0x80) as u8].into_iter().collect();
+            let s = U256::from(nonce) + U256::from(1);
+            let signature = [
+                r.to::<u128>(),
+                (r >> 128).to::<u128>(),
+                s.to::<u128>(),
+                (s >> 128).to::<u128>(),
+                1,
+            ];
+            TransactionInput {
+                rlp_len: rlp.len(),
+                rlp,
+                signature_len: signature.len(),
+                signature: signature.to_vec(),
+                sender: Address::ZERO,
+            }
+        })
+        .collect()
+}
+
+fn recover_eth_address_hint(c: &mut Criterion) {
+    let transactions = transactions(200);
+    let mut group = c.benchmark_group("recover_eth_address_hint");
+    group.bench_function("sequential", |b| {
+        b.iter(|| {
+            black_box(&transactions)
+                .iter()
+                .map(|transaction| RecoveryInput::from_transaction(transaction, 1)?.recover())
+                .collect::<Vec<_>>()
+        })
+    });
+    group.bench_function("parallel", |b| {
+        b.iter(|| HintCache::from_transactions(black_box(&transactions), 1))
+    });
+    group.finish();
+}
+
+fn run_os(c: &mut Criterion) {
+    let program = Program::from_bytes(OS, Some("main")).unwrap();
+    let layouts = Arc::new(ProgramLayoutCache::new(&program));
+    let header = Header { number: 1, gas_limit: 30_000_000, ..Default::default() };
+    let input = Arc::new(ProgramInput {
+        block: BlockInput { block_header: HeaderInput::from(&header), transactions: Vec::new() },
+        state: state(16, 16),
+        chain_id: 1,
+        system_calls: Vec::new(),
+        block_hashes: BTreeMap::new(),
+    });
+
+    c.bench_function("run_os", |b| {
+        b.iter(|| {
+            let mut hint_processor =
+                KakarotHintProcessor::default().with_program_input(layouts.clone(), input.clone());
+            dry_run(
+                OS,
+                &mut hint_processor,
+                &ExecutionLimits::default(),
+                &RunnerTuning::default(),
+                &mut || false,
+            )
+            .unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, state_hint, recover_eth_address_hint, run_os);
+criterion_main!(benches);
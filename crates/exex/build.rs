@@ -0,0 +1,8 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Use the vendored `protoc` binary so that building the crate does not require a system-wide
+    // protobuf installation.
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    tonic_build::compile_protos("proto/keth/v1/execution.proto")?;
+    tonic_build::compile_protos("proto/perftools/profiles/profile.proto")?;
+    Ok(())
+}
@@ -0,0 +1,327 @@
+//! The AIR inputs of a proof-mode run, in the files consumed by the Stone prover.
+//!
+//! A run is proven from four files: the relocated trace and memory in the binary encoding of the
+//! Cairo runner, the public input, and the private input referencing the trace and memory files
+//! along with the inputs of the builtins, e.g. the pedersen, ecdsa and keccak inputs. Writing them
+//! from a completed run is enough to prove it without any external tooling.
+//!
+//! The inputs can also be kept in memory as [`AirInputs`], e.g. to hand them to a proving backend
+//! without going through files, along with the program hash and the output segment of the run
+//! which make up the fact of its proof.
+
+use crate::{
+    executor::{execute, ExecutionMode},
+    hints::KakarotHintProcessor,
+    limits::{ExecutionLimits, LimitedRun},
+    output::read_output,
+    program::{program_hash, ProgramError},
+    tuning::RunnerTuning,
+};
+use alloy_primitives::B256;
+use bincode::{enc::write::Writer, error::EncodeError};
+use cairo_vm::{
+    air_private_input::AirPrivateInput,
+    air_public_input::PublicInputError,
+    cairo_run::{write_encoded_memory, write_encoded_trace, EncodeTraceError},
+    vm::{
+        errors::{cairo_run_errors::CairoRunError, memory_errors::MemoryError},
+        runners::cairo_runner::CairoRunner,
+        trace::trace_entry::RelocatedTraceEntry,
+    },
+    Felt252,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// The file name of the encoded trace.
+pub const TRACE_FILE: &str = "trace.bin";
+
+/// The file name of the encoded memory.
+pub const MEMORY_FILE: &str = "memory.bin";
+
+/// The file name of the public input.
+pub const PUBLIC_INPUT_FILE: &str = "air_public_input.json";
+
+/// The file name of the private input.
+pub const PRIVATE_INPUT_FILE: &str = "air_private_input.json";
+
+/// The file name of the program hash and output of the run.
+pub const PUBLIC_OUTPUT_FILE: &str = "public_output.json";
+
+/// Represents errors that can occur when writing the AIR inputs of a run.
+#[derive(Debug, Error)]
+pub enum AirError {
+    /// Error variant indicating a run without relocated trace, i.e. not run in proof mode.
+    #[error("The run has no relocated trace")]
+    MissingTrace,
+
+    /// Error variant indicating a failure to build the public input.
+    #[error(transparent)]
+    PublicInput(#[from] PublicInputError),
+
+    /// Error variant indicating a failure to hash the program.
+    #[error(transparent)]
+    Program(#[from] ProgramError),
+
+    /// Error variant indicating a failure to read the output segment.
+    #[error(transparent)]
+    Output(#[from] MemoryError),
+
+    /// Error variant indicating a failure of the Cairo run.
+    #[error(transparent)]
+    Run(#[from] CairoRunError),
+
+    /// Error variant indicating a failure to serialize an input.
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    /// Error variant indicating a failure to encode the trace or the memory.
+    #[error(transparent)]
+    Encode(#[from] EncodeTraceError),
+
+    /// Error variant indicating a failure to write an input.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// The paths of the AIR inputs of a run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AirInputPaths {
+    /// The encoded trace.
+    pub trace: PathBuf,
+    /// The encoded memory.
+    pub memory: PathBuf,
+    /// The public input.
+    pub public_input: PathBuf,
+    /// The private input.
+    pub private_input: PathBuf,
+    /// The program hash and output of the run.
+    pub public_output: PathBuf,
+}
+
+impl AirInputPaths {
+    /// Returns the paths of the inputs in `dir`.
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            trace: dir.join(TRACE_FILE),
+            memory: dir.join(MEMORY_FILE),
+            public_input: dir.join(PUBLIC_INPUT_FILE),
+            private_input: dir.join(PRIVATE_INPUT_FILE),
+            public_output: dir.join(PUBLIC_OUTPUT_FILE),
+        }
+    }
+}
+
+/// The program hash and output segment of a run, the public values its proof attests to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublicOutput {
+    /// The hash of the program, as hashed by the bootloader.
+    pub program_hash: B256,
+    /// The felts of the output segment, empty when the program has no output builtin.
+    pub output: Vec<Felt252>,
+}
+
+impl PublicOutput {
+    /// Reads the public output of an ended run.
+    pub fn from_runner(runner: &CairoRunner) -> Result<Self, AirError> {
+        Ok(Self {
+            program_hash: program_hash(runner.get_program())?,
+            output: read_output(runner)?.unwrap_or_default(),
+        })
+    }
+}
+
+/// The AIR inputs of a completed proof-mode run, in memory.
+#[derive(Debug, Clone)]
+pub struct AirInputs {
+    /// The encoded trace, see [`write_trace`].
+    pub trace: Vec<u8>,
+    /// The encoded memory, see [`write_memory`].
+    pub memory: Vec<u8>,
+    /// The public input, serialized in JSON.
+    pub public_input: Vec<u8>,
+    /// The private input, holding the inputs of the builtins.
+    pub private_input: AirPrivateInput,
+    /// The program hash and output of the run.
+    pub public_output: PublicOutput,
+}
+
+impl AirInputs {
+    /// Collects the AIR inputs of a completed proof-mode run.
+    pub fn from_runner(runner: &CairoRunner) -> Result<Self, AirError> {
+        let relocated_trace = runner.relocated_trace.as_ref().ok_or(AirError::MissingTrace)?;
+
+        let mut trace = Vec::new();
+        write_trace(relocated_trace, &mut trace)?;
+        let mut memory = Vec::new();
+        write_memory(&runner.relocated_memory, &mut memory)?;
+
+        Ok(Self {
+            trace,
+            memory,
+            public_input: serde_json::to_vec(&runner.get_air_public_input()?)?,
+            private_input: runner.get_air_private_input(),
+            public_output: PublicOutput::from_runner(runner)?,
+        })
+    }
+
+    /// Writes the inputs to `dir`.
+    pub fn write(&self, dir: &Path) -> Result<AirInputPaths, AirError> {
+        fs::create_dir_all(dir)?;
+        let paths = AirInputPaths::new(dir);
+
+        fs::write(&paths.trace, &self.trace)?;
+        fs::write(&paths.memory, &self.memory)?;
+        fs::write(&paths.public_input, &self.public_input)?;
+        write_private_input(&self.private_input, &paths)?;
+        fs::write(&paths.public_output, serde_json::to_vec(&self.public_output)?)?;
+
+        Ok(paths)
+    }
+}
+
+/// Writes the private input, which references the trace and memory files by path.
+fn write_private_input(
+    private_input: &AirPrivateInput,
+    paths: &AirInputPaths,
+) -> Result<(), AirError> {
+    let private_input = private_input.to_serializable(
+        paths.trace.to_string_lossy().into_owned(),
+        paths.memory.to_string_lossy().into_owned(),
+    );
+    fs::write(&paths.private_input, serde_json::to_vec(&private_input)?)?;
+    Ok(())
+}
+
+/// Adapts an [`io::Write`] to the [`Writer`] of the encoders of the Cairo runner.
+struct IoWriter<W> {
+    /// The underlying writer.
+    writer: W,
+    /// The number of bytes written so far.
+    written: usize,
+}
+
+impl<W: Write> IoWriter<W> {
+    /// Creates a new [`IoWriter`] writing to `writer`.
+    const fn new(writer: W) -> Self {
+        Self { writer, written: 0 }
+    }
+
+    /// Flushes the underlying writer.
+    fn flush(mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> Writer for IoWriter<W> {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), EncodeError> {
+        self.writer
+            .write_all(bytes)
+            .map_err(|inner| EncodeError::Io { inner, index: self.written })?;
+        self.written += bytes.len();
+        Ok(())
+    }
+}
+
+/// Writes a relocated trace with [`write_encoded_trace`], each entry as its `ap`, `fp` and `pc`
+/// in 64-bit little endian.
+pub fn write_trace<W: Write>(trace: &[RelocatedTraceEntry], writer: W) -> Result<(), AirError> {
+    let mut writer = IoWriter::new(writer);
+    write_encoded_trace(trace, &mut writer)?;
+    Ok(writer.flush()?)
+}
+
+/// Writes a relocated memory with [`write_encoded_memory`], each written cell as its address in
+/// 64-bit little endian followed by its value in 256-bit little endian.
+pub fn write_memory<W: Write>(memory: &[Option<Felt252>], writer: W) -> Result<(), AirError> {
+    let mut writer = IoWriter::new(writer);
+    write_encoded_memory(memory, &mut writer)?;
+    Ok(writer.flush()?)
+}
+
+/// Writes the AIR inputs of a completed proof-mode run to `dir`, streaming the trace and memory to
+/// their files rather than encoding them in memory as [`AirInputs`].
+pub fn write_air_inputs(runner: &CairoRunner, dir: &Path) -> Result<AirInputPaths, AirError> {
+    let trace = runner.relocated_trace.as_ref().ok_or(AirError::MissingTrace)?;
+    fs::create_dir_all(dir)?;
+    let paths = AirInputPaths::new(dir);
+
+    write_trace(trace, BufWriter::new(File::create(&paths.trace)?))?;
+    write_memory(&runner.relocated_memory, BufWriter::new(File::create(&paths.memory)?))?;
+
+    let public_input = runner.get_air_public_input()?;
+    fs::write(&paths.public_input, serde_json::to_vec(&public_input)?)?;
+    write_private_input(&runner.get_air_private_input(), &paths)?;
+    let public_output = PublicOutput::from_runner(runner)?;
+    fs::write(&paths.public_output, serde_json::to_vec(&public_output)?)?;
+
+    Ok(paths)
+}
+
+/// Runs a compiled program in proof mode and writes its AIR inputs to `dir`.
+pub fn run_air_inputs(program: &Path, dir: &Path) -> eyre::Result<AirInputPaths> {
+    let mut hint_processor = KakarotHintProcessor::default();
+    let run = execute(
+        &fs::read(program)?,
+        ExecutionMode::Proof,
+        &mut hint_processor,
+        &ExecutionLimits::default(),
+        &RunnerTuning::default(),
+        &mut || false,
+    )?;
+    let LimitedRun::Completed(runner) = run else {
+        eyre::bail!("The run of {} did not complete", program.display());
+    };
+    Ok(write_air_inputs(&runner, dir)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{executor::TRACE_ENTRY_BYTES, program::KakarotProgram};
+
+    #[test]
+    fn test_write_air_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let program =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/keccak_add_uint256.json");
+        let paths = run_air_inputs(&program, dir.path()).unwrap();
+        assert_eq!(paths, AirInputPaths::new(dir.path()));
+
+        let trace_size = fs::metadata(&paths.trace).unwrap().len();
+        assert!(trace_size > 0 && trace_size % TRACE_ENTRY_BYTES as u64 == 0);
+        assert_eq!(fs::metadata(&paths.memory).unwrap().len() % 40, 0);
+
+        let public_input: serde_json::Value =
+            serde_json::from_slice(&fs::read(&paths.public_input).unwrap()).unwrap();
+        assert_eq!(public_input["layout"], "all_cairo");
+
+        let private_input: serde_json::Value =
+            serde_json::from_slice(&fs::read(&paths.private_input).unwrap()).unwrap();
+        assert_eq!(private_input["trace_path"], paths.trace.to_string_lossy().as_ref());
+        assert_eq!(private_input["memory_path"], paths.memory.to_string_lossy().as_ref());
+        assert!(private_input["bitwise"].is_array());
+
+        let public_output: PublicOutput =
+            serde_json::from_slice(&fs::read(&paths.public_output).unwrap()).unwrap();
+        assert_eq!(
+            public_output.program_hash,
+            KakarotProgram::load(&program).unwrap().program_hash().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_write_memory() {
+        let mut bytes = Vec::new();
+        write_memory(&[None, Some(Felt252::from(7)), None], &mut bytes).unwrap();
+
+        let mut expected = 1u64.to_le_bytes().to_vec();
+        expected.extend(Felt252::from(7).to_bytes_le());
+        assert_eq!(bytes, expected);
+    }
+}
@@ -0,0 +1,792 @@
+use crate::{
+    attribution::{FrameSpec, TransactionResources},
+    calltracer::{call_tree, is_precompile, CallFrame, CallTracerConfig},
+    db::Database,
+    receipts::{CairoOutcome, ExecuteLayout},
+    serde::relocated::RelocatedMemory,
+    structlog::{decode_steps, Step, StepLayout},
+};
+use alloy_primitives::{hex, B256, U256};
+use arrow_array::{
+    ArrayRef, BooleanArray, FixedSizeBinaryArray, RecordBatch, StringArray, UInt32Array,
+    UInt64Array, UInt8Array,
+};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use cairo_vm::{types::program::Program, vm::runners::cairo_runner::ExecutionResources};
+use once_cell::sync::Lazy;
+use parquet::{
+    arrow::ArrowWriter,
+    basic::{Compression, ZstdLevel},
+    errors::ParquetError,
+    file::properties::WriterProperties,
+};
+use reth_primitives::revm_primitives::ExecutionResult;
+use reth_revm::interpreter::OpCode;
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+use thiserror::Error;
+
+/// The schema of the per-opcode statistics.
+static OPCODE_SCHEMA: Lazy<SchemaRef> = Lazy::new(|| {
+    Arc::new(Schema::new(vec![
+        Field::new("block_number", DataType::UInt64, false),
+        Field::new("tx_index", DataType::UInt32, false),
+        Field::new("opcode", DataType::UInt8, false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("count", DataType::UInt64, false),
+        Field::new("gas", DataType::UInt64, false),
+    ]))
+});
+
+/// The schema of the per-builtin statistics.
+static BUILTIN_SCHEMA: Lazy<SchemaRef> = Lazy::new(|| {
+    Arc::new(Schema::new(vec![
+        Field::new("block_number", DataType::UInt64, false),
+        Field::new("builtin", DataType::Utf8, false),
+        Field::new("instances", DataType::UInt64, false),
+    ]))
+});
+
+/// The schema of the per-selector statistics.
+static SELECTOR_SCHEMA: Lazy<SchemaRef> = Lazy::new(|| {
+    Arc::new(Schema::new(vec![
+        Field::new("block_number", DataType::UInt64, false),
+        Field::new("tx_index", DataType::UInt32, false),
+        Field::new("selector", DataType::FixedSizeBinary(4), false),
+        Field::new("calldata_size", DataType::UInt64, false),
+        Field::new("count", DataType::UInt64, false),
+    ]))
+});
+
+/// The schema of the per-transaction statistics.
+static TRANSACTION_SCHEMA: Lazy<SchemaRef> = Lazy::new(|| {
+    Arc::new(Schema::new(vec![
+        Field::new("block_number", DataType::UInt64, false),
+        Field::new("tx_index", DataType::UInt32, false),
+        Field::new("tx_hash", DataType::FixedSizeBinary(32), false),
+        Field::new("success", DataType::Boolean, false),
+        Field::new("gas_used", DataType::UInt64, false),
+        Field::new("gas_refunded", DataType::UInt64, false),
+        Field::new("opcodes_executed", DataType::UInt64, false),
+    ]))
+});
+
+/// Represents errors that can occur when exporting analytics data.
+#[derive(Debug, Error)]
+pub enum AnalyticsError {
+    /// Error variant indicating a failure to build or write Arrow data.
+    #[error(transparent)]
+    Arrow(#[from] ArrowError),
+
+    /// Error variant indicating a failure to write a Parquet file.
+    #[error(transparent)]
+    Parquet(#[from] ParquetError),
+
+    /// Error variant indicating a failure to create an output file.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// Error variant indicating an unknown export format.
+    #[error("Unknown analytics format '{0}', expected 'parquet' or 'arrow-ipc'")]
+    UnknownFormat(String),
+}
+
+/// The execution statistics of a single opcode within a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OpcodeStat {
+    /// The number of the block.
+    pub block_number: u64,
+    /// The index of the transaction in the block.
+    pub tx_index: u32,
+    /// The opcode.
+    pub opcode: u8,
+    /// The number of times the opcode was executed.
+    pub count: u64,
+    /// The gas charged by the opcode, including the gas forwarded to sub-calls.
+    pub gas: u64,
+}
+
+impl OpcodeStat {
+    /// Returns the statistics of the opcodes executed by a transaction, from the steps of its
+    /// Cairo execution.
+    pub fn from_steps(block_number: u64, tx_index: u32, steps: &[Step]) -> Vec<Self> {
+        let mut counter = OpcodeCounter::default();
+        for step in steps {
+            counter.record(step.op, step.gas_cost);
+        }
+        counter.take_stats(block_number, tx_index)
+    }
+
+    /// Returns the mnemonic of the opcode, `UNKNOWN` for undefined opcodes.
+    pub fn name(&self) -> &'static str {
+        OpCode::new(self.opcode).map_or("UNKNOWN", OpCode::as_str)
+    }
+}
+
+/// The number of calls of a function selector with a calldata size within a transaction, as
+/// counted by geth's `4byteTracer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SelectorStat {
+    /// The number of the block.
+    pub block_number: u64,
+    /// The index of the transaction in the block.
+    pub tx_index: u32,
+    /// The function selector, the first 4 bytes of the calldata.
+    pub selector: [u8; 4],
+    /// The size of the calldata following the selector.
+    pub calldata_size: u64,
+    /// The number of calls.
+    pub count: u64,
+}
+
+impl SelectorStat {
+    /// Returns the statistics of the calls of the call tree of a transaction, sorted by selector
+    /// and calldata size.
+    ///
+    /// As geth, the contract creations, the precompile calls and the calls whose calldata is
+    /// shorter than a selector are not counted.
+    pub fn from_call_tree(block_number: u64, tx_index: u32, root: &CallFrame) -> Vec<Self> {
+        let mut counts = BTreeMap::new();
+        let mut frames = vec![root];
+        while let Some(frame) = frames.pop() {
+            frames.extend(&frame.calls);
+            if frame.kind.is_create() || frame.to.is_some_and(is_precompile) {
+                continue;
+            }
+            if let Some((selector, calldata)) = frame.input.split_first_chunk::<4>() {
+                *counts.entry((*selector, calldata.len() as u64)).or_default() += 1;
+            }
+        }
+
+        counts
+            .into_iter()
+            .map(|((selector, calldata_size), count)| Self {
+                block_number,
+                tx_index,
+                selector,
+                calldata_size,
+                count,
+            })
+            .collect()
+    }
+
+    /// Returns the key of the statistic in the output of the `4byteTracer`, e.g. `0x27dc297e-128`.
+    pub fn key(&self) -> String {
+        format!("{}-{}", hex::encode_prefixed(self.selector), self.calldata_size)
+    }
+}
+
+/// The number of instances of a Cairo builtin used to execute a block.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BuiltinStat {
+    /// The number of the block.
+    pub block_number: u64,
+    /// The name of the builtin.
+    pub builtin: String,
+    /// The number of instances of the builtin.
+    pub instances: u64,
+}
+
+impl BuiltinStat {
+    /// Returns the statistics of the builtins used by the execution of a block, sorted by
+    /// builtin name.
+    pub fn from_resources(block_number: u64, resources: &ExecutionResources) -> Vec<Self> {
+        let mut stats = resources
+            .builtin_instance_counter
+            .iter()
+            .map(|(builtin, instances)| Self {
+                block_number,
+                builtin: builtin.to_str().to_string(),
+                instances: *instances as u64,
+            })
+            .collect::<Vec<_>>();
+        stats.sort_by(|a, b| a.builtin.cmp(&b.builtin));
+        stats
+    }
+
+    /// Returns the statistics of the builtins used by the transactions of a block, from their
+    /// attributed resources, sorted by builtin name.
+    pub fn from_transactions(block_number: u64, resources: &[TransactionResources]) -> Vec<Self> {
+        let mut instances = BTreeMap::<&str, u64>::new();
+        for transaction in resources {
+            for (builtin, count) in &transaction.builtins {
+                *instances.entry(builtin).or_default() += count;
+            }
+        }
+
+        instances
+            .into_iter()
+            .map(|(builtin, instances)| Self {
+                block_number,
+                builtin: builtin.to_string(),
+                instances,
+            })
+            .collect()
+    }
+}
+
+/// The execution statistics of a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TransactionStat {
+    /// The number of the block.
+    pub block_number: u64,
+    /// The index of the transaction in the block.
+    pub tx_index: u32,
+    /// The hash of the transaction.
+    pub tx_hash: B256,
+    /// Whether the transaction succeeded.
+    pub success: bool,
+    /// The gas used by the transaction.
+    pub gas_used: u64,
+    /// The gas refunded to the sender.
+    pub gas_refunded: u64,
+    /// The total number of opcodes executed by the transaction.
+    pub opcodes_executed: u64,
+}
+
+impl TransactionStat {
+    /// Builds the statistics of a transaction from its execution result and its opcode
+    /// statistics.
+    pub fn new(
+        block_number: u64,
+        tx_index: u32,
+        tx_hash: B256,
+        result: &ExecutionResult,
+        opcodes: &[OpcodeStat],
+    ) -> Self {
+        let gas_refunded = match result {
+            ExecutionResult::Success { gas_refunded, .. } => *gas_refunded,
+            _ => 0,
+        };
+
+        Self {
+            block_number,
+            tx_index,
+            tx_hash,
+            success: result.is_success(),
+            gas_used: result.gas_used(),
+            gas_refunded,
+            opcodes_executed: opcodes.iter().map(|stat| stat.count).sum(),
+        }
+    }
+}
+
+/// A counter of the executed opcodes and the gas they charge, fed with the steps decoded from
+/// the Cairo execution.
+///
+/// The counter aggregates the statistics of all the recorded steps, it should be drained with
+/// [`OpcodeCounter::take_stats`] after each transaction.
+#[derive(Debug, Default, Clone)]
+pub struct OpcodeCounter {
+    /// The executed opcodes, with their count and charged gas.
+    opcodes: BTreeMap<u8, (u64, u64)>,
+}
+
+impl OpcodeCounter {
+    /// Records the execution of an opcode.
+    pub fn record(&mut self, opcode: u8, gas: u64) {
+        let (count, total_gas) = self.opcodes.entry(opcode).or_default();
+        *count += 1;
+        *total_gas = total_gas.saturating_add(gas);
+    }
+
+    /// Returns the statistics of the recorded transaction and resets the counter.
+    pub fn take_stats(&mut self, block_number: u64, tx_index: u32) -> Vec<OpcodeStat> {
+        std::mem::take(&mut self.opcodes)
+            .into_iter()
+            .map(|(opcode, (count, gas))| OpcodeStat { block_number, tx_index, opcode, count, gas })
+            .collect()
+    }
+}
+
+/// The analytics data of a batch of blocks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AnalyticsBatch {
+    /// The per-opcode statistics.
+    pub opcodes: Vec<OpcodeStat>,
+    /// The per-builtin statistics.
+    pub builtins: Vec<BuiltinStat>,
+    /// The per-transaction statistics.
+    pub transactions: Vec<TransactionStat>,
+    /// The per-selector statistics.
+    pub selectors: Vec<SelectorStat>,
+}
+
+impl AnalyticsBatch {
+    /// Returns whether the batch holds no statistics.
+    pub fn is_empty(&self) -> bool {
+        self.opcodes.is_empty() &&
+            self.builtins.is_empty() &&
+            self.transactions.is_empty() &&
+            self.selectors.is_empty()
+    }
+
+    /// Appends the statistics of another batch.
+    pub fn extend(&mut self, other: Self) {
+        self.opcodes.extend(other.opcodes);
+        self.builtins.extend(other.builtins);
+        self.transactions.extend(other.transactions);
+        self.selectors.extend(other.selectors);
+    }
+
+    /// Converts the per-opcode statistics into a [`RecordBatch`].
+    pub fn opcodes_record_batch(&self) -> Result<RecordBatch, ArrowError> {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(UInt64Array::from_iter_values(self.opcodes.iter().map(|s| s.block_number))),
+            Arc::new(UInt32Array::from_iter_values(self.opcodes.iter().map(|s| s.tx_index))),
+            Arc::new(UInt8Array::from_iter_values(self.opcodes.iter().map(|s| s.opcode))),
+            Arc::new(StringArray::from_iter_values(self.opcodes.iter().map(OpcodeStat::name))),
+            Arc::new(UInt64Array::from_iter_values(self.opcodes.iter().map(|s| s.count))),
+            Arc::new(UInt64Array::from_iter_values(self.opcodes.iter().map(|s| s.gas))),
+        ];
+        RecordBatch::try_new(OPCODE_SCHEMA.clone(), columns)
+    }
+
+    /// Converts the per-builtin statistics into a [`RecordBatch`].
+    pub fn builtins_record_batch(&self) -> Result<RecordBatch, ArrowError> {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(UInt64Array::from_iter_values(self.builtins.iter().map(|s| s.block_number))),
+            Arc::new(StringArray::from_iter_values(self.builtins.iter().map(|s| &s.builtin))),
+            Arc::new(UInt64Array::from_iter_values(self.builtins.iter().map(|s| s.instances))),
+        ];
+        RecordBatch::try_new(BUILTIN_SCHEMA.clone(), columns)
+    }
+
+    /// Converts the per-transaction statistics into a [`RecordBatch`].
+    pub fn transactions_record_batch(&self) -> Result<RecordBatch, ArrowError> {
+        let txs = &self.transactions;
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(UInt64Array::from_iter_values(txs.iter().map(|s| s.block_number))),
+            Arc::new(UInt32Array::from_iter_values(txs.iter().map(|s| s.tx_index))),
+            Arc::new(FixedSizeBinaryArray::try_from_sparse_iter_with_size(
+                txs.iter().map(|s| Some(s.tx_hash.0)),
+                32,
+            )?),
+            Arc::new(BooleanArray::from(txs.iter().map(|s| s.success).collect::<Vec<_>>())),
+            Arc::new(UInt64Array::from_iter_values(txs.iter().map(|s| s.gas_used))),
+            Arc::new(UInt64Array::from_iter_values(txs.iter().map(|s| s.gas_refunded))),
+            Arc::new(UInt64Array::from_iter_values(txs.iter().map(|s| s.opcodes_executed))),
+        ];
+        RecordBatch::try_new(TRANSACTION_SCHEMA.clone(), columns)
+    }
+
+    /// Converts the per-selector statistics into a [`RecordBatch`].
+    pub fn selectors_record_batch(&self) -> Result<RecordBatch, ArrowError> {
+        let stats = &self.selectors;
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(UInt64Array::from_iter_values(stats.iter().map(|s| s.block_number))),
+            Arc::new(UInt32Array::from_iter_values(stats.iter().map(|s| s.tx_index))),
+            Arc::new(FixedSizeBinaryArray::try_from_sparse_iter_with_size(
+                stats.iter().map(|s| Some(s.selector)),
+                4,
+            )?),
+            Arc::new(UInt64Array::from_iter_values(stats.iter().map(|s| s.calldata_size))),
+            Arc::new(UInt64Array::from_iter_values(stats.iter().map(|s| s.count))),
+        ];
+        RecordBatch::try_new(SELECTOR_SCHEMA.clone(), columns)
+    }
+}
+
+/// The file format of the analytics export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AnalyticsFormat {
+    /// Zstd compressed Parquet files, one row group per written batch.
+    #[default]
+    Parquet,
+    /// Arrow IPC streams, one record batch per written batch.
+    ArrowIpc,
+}
+
+impl AnalyticsFormat {
+    /// Returns the extension of the exported files.
+    pub const fn extension(&self) -> &'static str {
+        match self {
+            Self::Parquet => "parquet",
+            Self::ArrowIpc => "arrows",
+        }
+    }
+}
+
+impl FromStr for AnalyticsFormat {
+    type Err = AnalyticsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "parquet" => Ok(Self::Parquet),
+            "arrow-ipc" => Ok(Self::ArrowIpc),
+            _ => Err(AnalyticsError::UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+/// A writer of record batches sharing the same schema.
+enum TableWriter {
+    Parquet(ArrowWriter<File>),
+    ArrowIpc(StreamWriter<File>),
+}
+
+impl TableWriter {
+    fn create(
+        path: &Path,
+        schema: &SchemaRef,
+        format: AnalyticsFormat,
+    ) -> Result<Self, AnalyticsError> {
+        let file = File::create(path)?;
+        Ok(match format {
+            AnalyticsFormat::Parquet => {
+                let properties = WriterProperties::builder()
+                    .set_compression(Compression::ZSTD(ZstdLevel::default()))
+                    .build();
+                Self::Parquet(ArrowWriter::try_new(file, schema.clone(), Some(properties))?)
+            }
+            AnalyticsFormat::ArrowIpc => Self::ArrowIpc(StreamWriter::try_new(file, schema)?),
+        })
+    }
+
+    fn write(&mut self, batch: &RecordBatch) -> Result<(), AnalyticsError> {
+        match self {
+            Self::Parquet(writer) => writer.write(batch)?,
+            Self::ArrowIpc(writer) => writer.write(batch)?,
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), AnalyticsError> {
+        match self {
+            Self::Parquet(writer) => {
+                writer.close()?;
+            }
+            Self::ArrowIpc(mut writer) => writer.finish()?,
+        }
+        Ok(())
+    }
+}
+
+/// Exports analytics batches into a directory, as one file per table:
+/// - `opcodes.<ext>`: the per-opcode statistics of each transaction.
+/// - `builtins.<ext>`: the per-builtin statistics of each block.
+/// - `transactions.<ext>`: the per-transaction statistics.
+/// - `selectors.<ext>`: the per-selector statistics of each transaction.
+///
+/// Batches are streamed to the files as they are written, so that exports of millions of rows do
+/// not need to be held in memory. The files are only valid once [`AnalyticsExporter::finish`] has
+/// been called.
+pub struct AnalyticsExporter {
+    /// The directory of the exported files.
+    dir: PathBuf,
+    /// The writers of the opcodes, builtins, transactions and selectors tables.
+    writers: [TableWriter; 4],
+}
+
+impl std::fmt::Debug for AnalyticsExporter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AnalyticsExporter").field("dir", &self.dir).finish_non_exhaustive()
+    }
+}
+
+impl AnalyticsExporter {
+    /// Creates the export files in the given directory, which is created if missing.
+    pub fn create(
+        dir: impl Into<PathBuf>,
+        format: AnalyticsFormat,
+    ) -> Result<Self, AnalyticsError> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+
+        let writer = |name: &str, schema: &SchemaRef| {
+            TableWriter::create(&dir.join(format!("{name}.{}", format.extension())), schema, format)
+        };
+        let writers = [
+            writer("opcodes", &OPCODE_SCHEMA)?,
+            writer("builtins", &BUILTIN_SCHEMA)?,
+            writer("transactions", &TRANSACTION_SCHEMA)?,
+            writer("selectors", &SELECTOR_SCHEMA)?,
+        ];
+
+        Ok(Self { dir, writers })
+    }
+
+    /// Returns the directory of the exported files.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Writes a batch of statistics.
+    pub fn write(&mut self, batch: &AnalyticsBatch) -> Result<(), AnalyticsError> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let [opcodes, builtins, transactions, selectors] = &mut self.writers;
+        opcodes.write(&batch.opcodes_record_batch()?)?;
+        builtins.write(&batch.builtins_record_batch()?)?;
+        transactions.write(&batch.transactions_record_batch()?)?;
+        selectors.write(&batch.selectors_record_batch()?)?;
+        Ok(())
+    }
+
+    /// Flushes and closes the export files.
+    pub fn finish(self) -> Result<(), AnalyticsError> {
+        for writer in self.writers {
+            writer.finish()?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds the analytics of a block from its stored Cairo execution, with the program which
+/// executed it.
+///
+/// The opcodes and the selectors are read from the steps decoded from the trace, the results of
+/// the transactions from the returned EVMs, and the builtins from the resources attributed to the
+/// transactions.
+pub fn block_analytics(
+    db: &Database,
+    program: &Path,
+    block_number: u64,
+) -> eyre::Result<AnalyticsBatch> {
+    let bytes = fs::read(program)?;
+    let parsed = Program::from_bytes(&bytes, Some("main"))?;
+    let execute = FrameSpec::execute(&parsed)?;
+    let block = db
+        .block(U256::from(block_number))?
+        .ok_or_else(|| eyre::eyre!("Block {block_number} not found"))?;
+    let (trace, memory) = db
+        .execution_trace(block_number)?
+        .ok_or_else(|| eyre::eyre!("No trace found for block {block_number}"))?;
+
+    let mut steps: BTreeMap<u32, Vec<Step>> = BTreeMap::new();
+    for step in decode_steps(&execute, &StepLayout::from_program(&parsed)?, &trace, &memory)? {
+        steps.entry(step.tx_index).or_default().push(step);
+    }
+    // The types of the transactions are only needed for the receipts, not for their results.
+    let inputs = db.program_input(block_number)?.map(|input| input.block.transactions);
+    let outcome = CairoOutcome::from_trace(
+        &bytes,
+        &ExecuteLayout::from_program(&parsed)?,
+        block_number,
+        &inputs.unwrap_or_default(),
+        &trace,
+        RelocatedMemory::from_cells(memory.iter().copied().map(Some).collect()),
+    )?;
+
+    let resources = db.transaction_resources(block_number)?;
+    let mut batch = AnalyticsBatch {
+        builtins: BuiltinStat::from_transactions(block_number, &resources),
+        ..Default::default()
+    };
+    let transactions =
+        block.body.transactions.iter().zip(&block.senders).zip(&outcome.transactions);
+    for (index, ((transaction, sender), cairo)) in transactions.enumerate() {
+        let tx_index = index as u32;
+        let steps = steps.remove(&tx_index).unwrap_or_default();
+        let opcodes = OpcodeStat::from_steps(block_number, tx_index, &steps);
+        let root = call_tree(
+            CallFrame::from_transaction(transaction, *sender),
+            cairo.result.gas_used(),
+            &steps,
+            &CallTracerConfig::default(),
+        );
+        batch.selectors.extend(SelectorStat::from_call_tree(block_number, tx_index, &root));
+        batch.transactions.push(TransactionStat::new(
+            block_number,
+            tx_index,
+            transaction.hash(),
+            &cairo.result,
+            &opcodes,
+        ));
+        batch.opcodes.extend(opcodes);
+    }
+    Ok(batch)
+}
+
+/// Exports the analytics of the stored executions of a range of blocks, skipping the blocks
+/// without a stored trace, and returns the numbers of the exported blocks.
+pub fn export_analytics(
+    db: &Database,
+    program: &Path,
+    blocks: RangeInclusive<u64>,
+    exporter: &mut AnalyticsExporter,
+) -> eyre::Result<Vec<u64>> {
+    let mut exported = Vec::new();
+    for block_number in blocks {
+        if !db.has_execution_trace(block_number)? {
+            continue;
+        }
+        exporter.write(&block_analytics(db, program, block_number)?)?;
+        exported.push(block_number);
+    }
+    Ok(exported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calltracer::CallKind;
+    use alloy_primitives::{Address, Bytes, U64};
+    use arrow_array::cast::AsArray;
+    use arrow_ipc::reader::StreamReader;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    fn batch() -> AnalyticsBatch {
+        let mut counter = OpcodeCounter::default();
+        counter.record(0x60, 3);
+        counter.record(0x60, 3);
+        counter.record(0x55, 20_000);
+
+        let opcodes = counter.take_stats(1, 0);
+        let result = ExecutionResult::Halt {
+            reason: reth_primitives::revm_primitives::HaltReason::OutOfFunds,
+            gas_used: 21_000,
+        };
+
+        AnalyticsBatch {
+            transactions: vec![TransactionStat::new(1, 0, B256::repeat_byte(1), &result, &opcodes)],
+            builtins: vec![BuiltinStat {
+                block_number: 1,
+                builtin: "range_check".to_string(),
+                instances: 42,
+            }],
+            selectors: vec![SelectorStat {
+                block_number: 1,
+                tx_index: 0,
+                selector: [0xa9, 0x05, 0x9c, 0xbb],
+                calldata_size: 64,
+                count: 1,
+            }],
+            opcodes,
+        }
+    }
+
+    #[test]
+    fn test_opcode_counter() {
+        let mut counter = OpcodeCounter::default();
+        counter.record(0x01, 3);
+        counter.record(0x01, 3);
+        counter.record(0xfe, 100);
+
+        let stats = counter.take_stats(7, 2);
+        assert_eq!(
+            stats,
+            vec![
+                OpcodeStat { block_number: 7, tx_index: 2, opcode: 0x01, count: 2, gas: 6 },
+                OpcodeStat { block_number: 7, tx_index: 2, opcode: 0xfe, count: 1, gas: 100 },
+            ]
+        );
+        assert_eq!(stats[0].name(), "ADD");
+
+        // The counter is reset between transactions.
+        assert!(counter.take_stats(7, 3).is_empty());
+    }
+
+    #[test]
+    fn test_cairo_stats() {
+        let step = |op: u8, gas_cost: u64| Step { op, gas_cost, ..Default::default() };
+        let stats = OpcodeStat::from_steps(3, 1, &[step(0x60, 3), step(0x60, 3), step(0x00, 0)]);
+        assert_eq!(stats.len(), 2);
+        assert_eq!((stats[1].name(), stats[1].count, stats[1].gas), ("PUSH1", 2, 6));
+
+        let frame = |to: u8, input: &[u8], calls: Vec<CallFrame>| CallFrame {
+            kind: CallKind::Call,
+            from: Address::ZERO,
+            to: Some(Address::with_last_byte(to)),
+            value: None,
+            gas: U64::ZERO,
+            gas_used: U64::ZERO,
+            input: Bytes::copy_from_slice(input),
+            output: None,
+            error: None,
+            revert_reason: None,
+            calls,
+        };
+        let transfer = [0xa9, 0x05, 0x9c, 0xbb, 0, 0];
+        let root = frame(
+            0xaa,
+            &transfer,
+            vec![
+                frame(0xbb, &transfer, vec![]),
+                frame(0x02, &transfer, vec![]),
+                frame(0xcc, &[1], vec![]),
+            ],
+        );
+
+        // The precompile call and the short calldata are not counted.
+        let stats = SelectorStat::from_call_tree(3, 1, &root);
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].count, 2);
+        assert_eq!(stats[0].key(), "0xa9059cbb-2");
+
+        let resources = |tx_index: u32, range_check: u64| TransactionResources {
+            tx_index,
+            steps: 100,
+            builtins: BTreeMap::from([
+                ("range_check".to_string(), range_check),
+                ("bitwise".to_string(), 1),
+            ]),
+        };
+        let stats = BuiltinStat::from_transactions(3, &[resources(0, 10), resources(1, 32)]);
+        assert_eq!(
+            stats.iter().map(|stat| (stat.builtin.as_str(), stat.instances)).collect::<Vec<_>>(),
+            vec![("bitwise", 2), ("range_check", 42)]
+        );
+    }
+
+    #[test]
+    fn test_analytics_formats() {
+        assert!(matches!("arrow-ipc".parse(), Ok(AnalyticsFormat::ArrowIpc)));
+        assert!(matches!("csv".parse::<AnalyticsFormat>(), Err(AnalyticsError::UnknownFormat(_))));
+    }
+
+    #[test]
+    fn test_transaction_stat() {
+        let batch = batch();
+        let stat = batch.transactions[0];
+        assert!(!stat.success);
+        assert_eq!(stat.gas_used, 21_000);
+        assert_eq!(stat.opcodes_executed, 3);
+    }
+
+    #[test]
+    fn test_export_parquet() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut exporter = AnalyticsExporter::create(dir.path(), AnalyticsFormat::Parquet).unwrap();
+        exporter.write(&batch()).unwrap();
+        exporter.write(&batch()).unwrap();
+        exporter.finish().unwrap();
+
+        let file = File::open(dir.path().join("opcodes.parquet")).unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(file).unwrap().build().unwrap();
+        let rows: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+        // Two opcodes per batch.
+        assert_eq!(rows, 4);
+
+        let file = File::open(dir.path().join("selectors.parquet")).unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(file).unwrap().build().unwrap();
+        let batches: Vec<_> = reader.map(|batch| batch.unwrap()).collect();
+        assert_eq!(batches.iter().map(RecordBatch::num_rows).sum::<usize>(), 2);
+        let selectors = batches[0].column_by_name("selector").unwrap().as_fixed_size_binary();
+        assert_eq!(selectors.value(0), [0xa9, 0x05, 0x9c, 0xbb]);
+    }
+
+    #[test]
+    fn test_export_arrow_ipc() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut exporter =
+            AnalyticsExporter::create(dir.path(), AnalyticsFormat::ArrowIpc).unwrap();
+        exporter.write(&batch()).unwrap();
+        exporter.finish().unwrap();
+
+        let file = File::open(dir.path().join("transactions.arrows")).unwrap();
+        let reader = StreamReader::try_new(file, None).unwrap();
+        assert_eq!(reader.schema(), TRANSACTION_SCHEMA.clone());
+        let rows: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+        assert_eq!(rows, 1);
+    }
+}
@@ -0,0 +1,417 @@
+//! Tiering of the large artifacts to an object storage, with a local cache.
+//!
+//! The execution traces, memory dumps and Cairo PIEs of the blocks grow much faster than their
+//! metadata. With an [`ArtifactStore`], they are uploaded to an object storage (S3, GCS or a local
+//! directory) under an [`object_key`], the database only recording the key of each object in its
+//! `artifact_object` table. The objects read or written are kept in a local cache directory, so
+//! that recent blocks are served without a round trip.
+//!
+//! A [`LifecyclePolicy`] bounds the footprint of the artifacts: per kind of artifact, the cached
+//! copies are evicted past a number of blocks behind the tip, and the objects are deleted from the
+//! storage past another one.
+//!
+//! The store is blocking, running the requests on its own runtime, so that it can be used from the
+//! synchronous database. The credentials of the storage are read from the environment, e.g.
+//! `AWS_ACCESS_KEY_ID` for S3 and `GOOGLE_SERVICE_ACCOUNT` for GCS.
+
+use futures::TryStreamExt;
+use object_store::{
+    aws::AmazonS3Builder, gcp::GoogleCloudStorageBuilder, local::LocalFileSystem, memory::InMemory,
+    path::Path as ObjectPath, ObjectStore,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    future::Future,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use thiserror::Error;
+use tokio::runtime::{Handle, Runtime, RuntimeFlavor};
+
+/// The name of the cache directory of the store, in the artifacts directory of an instance.
+pub const CACHE_DIR: &str = "cache";
+
+/// The kind of the Cairo PIEs in the object keys, the other kinds being the
+/// [`ArtifactKind`](crate::compression::ArtifactKind)s.
+pub const PIE_KIND: &str = "pie";
+
+/// Represents errors that can occur when storing the artifacts.
+#[derive(Debug, Error)]
+pub enum ArtifactStoreError {
+    /// Error variant indicating an invalid URL of the store.
+    #[error("Invalid artifact store URL '{0}'")]
+    InvalidUrl(String),
+
+    /// Error variant indicating an unsupported scheme of the URL of the store.
+    #[error("Unsupported artifact store scheme '{0}', expected 's3', 'gs', 'file' or 'memory'")]
+    UnsupportedScheme(String),
+
+    /// Error variant indicating a failure of the object storage.
+    #[error(transparent)]
+    Store(#[from] object_store::Error),
+
+    /// Error variant indicating a failure of the local cache.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Returns the key of an artifact of a block in the store, e.g. `trace/42/execution`.
+pub fn object_key(kind: &str, number: u64, name: &str) -> String {
+    format!("{kind}/{number}/{name}")
+}
+
+/// A runtime shut down in the background when dropped, as the store may be dropped from an async
+/// context.
+#[derive(Debug)]
+struct BlockingRuntime(Option<Runtime>);
+
+impl Drop for BlockingRuntime {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.0.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// An object storage of the artifacts, with a local cache.
+#[derive(Debug, Clone)]
+pub struct ArtifactStore {
+    /// The object storage.
+    store: Arc<dyn ObjectStore>,
+    /// The prefix of the keys in the storage.
+    prefix: String,
+    /// The directory caching the objects, not cached when `None`.
+    cache_dir: Option<PathBuf>,
+    /// The runtime of the requests.
+    runtime: Arc<BlockingRuntime>,
+}
+
+impl ArtifactStore {
+    /// Creates an [`ArtifactStore`] of the given object storage, its keys being prefixed with
+    /// `prefix`.
+    pub fn new(
+        store: Arc<dyn ObjectStore>,
+        prefix: &str,
+        cache_dir: Option<PathBuf>,
+    ) -> Result<Self, ArtifactStoreError> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("artifact-store")
+            .enable_all()
+            .build()?;
+        Ok(Self {
+            store,
+            prefix: prefix.trim_matches('/').to_string(),
+            cache_dir,
+            runtime: Arc::new(BlockingRuntime(Some(runtime))),
+        })
+    }
+
+    /// Opens the store at the given URL, e.g. `s3://bucket/prefix`, `gs://bucket/prefix`,
+    /// `file:///var/lib/keth/objects` or `memory://`, caching the objects in `cache_dir`.
+    pub fn open(url: &str, cache_dir: Option<PathBuf>) -> Result<Self, ArtifactStoreError> {
+        let (scheme, location) =
+            url.split_once("://").ok_or_else(|| ArtifactStoreError::InvalidUrl(url.to_string()))?;
+        // The location of the buckets is `bucket/prefix`
+        let prefix = location.split_once('/').map(|(_, prefix)| prefix).unwrap_or_default();
+        let (store, prefix): (Arc<dyn ObjectStore>, _) = match scheme {
+            "s3" => (Arc::new(AmazonS3Builder::from_env().with_url(url).build()?), prefix),
+            "gs" => {
+                (Arc::new(GoogleCloudStorageBuilder::from_env().with_url(url).build()?), prefix)
+            }
+            "file" => {
+                fs::create_dir_all(location)?;
+                (Arc::new(LocalFileSystem::new_with_prefix(location)?), "")
+            }
+            "memory" => (Arc::new(InMemory::new()), ""),
+            scheme => return Err(ArtifactStoreError::UnsupportedScheme(scheme.to_string())),
+        };
+        Self::new(store, prefix, cache_dir)
+    }
+
+    /// Returns the path of an object in the storage.
+    fn path(&self, key: &str) -> ObjectPath {
+        match self.prefix.as_str() {
+            "" => ObjectPath::from(key),
+            prefix => ObjectPath::from(format!("{prefix}/{key}")),
+        }
+    }
+
+    /// Returns the path of the cached copy of an object, if the store has a cache.
+    pub fn cache_path(&self, key: &str) -> Option<PathBuf> {
+        self.cache_dir.as_ref().map(|dir| dir.join(key))
+    }
+
+    /// Runs a request of the store, blocking the current thread.
+    ///
+    /// A runtime cannot be entered from the thread of another one: from a worker of a
+    /// multi-threaded runtime, the worker hands its tasks over while blocked, and from a
+    /// current-thread runtime, which cannot, the request is run from a scoped thread.
+    fn block_on<F>(&self, future: F) -> F::Output
+    where
+        F: Future + Send,
+        F::Output: Send,
+    {
+        let runtime = self.runtime.0.as_ref().expect("runtime is set until dropped");
+        match Handle::try_current().map(|handle| handle.runtime_flavor()) {
+            Ok(RuntimeFlavor::MultiThread) => {
+                tokio::task::block_in_place(|| runtime.block_on(future))
+            }
+            Ok(_) => std::thread::scope(|scope| {
+                scope
+                    .spawn(|| runtime.block_on(future))
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            }),
+            Err(_) => runtime.block_on(future),
+        }
+    }
+
+    /// Uploads an object, keeping a copy in the cache.
+    pub fn put(&self, key: &str, data: Vec<u8>) -> Result<(), ArtifactStoreError> {
+        self.write_cache(key, &data)?;
+        self.block_on(self.store.put(&self.path(key), data.into()))?;
+        Ok(())
+    }
+
+    /// Retrieves an object from the cache, or downloads it into the cache, if it exists.
+    pub fn get(&self, key: &str) -> Result<Option<Vec<u8>>, ArtifactStoreError> {
+        if let Some(path) = self.cache_path(key) {
+            match fs::read(path) {
+                Ok(data) => return Ok(Some(data)),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        let data = self.block_on(async {
+            match self.store.get(&self.path(key)).await {
+                Ok(result) => result.bytes().await.map(|bytes| Some(bytes.to_vec())),
+                Err(object_store::Error::NotFound { .. }) => Ok(None),
+                Err(err) => Err(err),
+            }
+        })?;
+        if let Some(data) = &data {
+            self.write_cache(key, data)?;
+        }
+        Ok(data)
+    }
+
+    /// Deletes an object from the storage and the cache.
+    pub fn delete(&self, key: &str) -> Result<(), ArtifactStoreError> {
+        self.evict(key)?;
+        match self.block_on(self.store.delete(&self.path(key))) {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Lists the keys of all the objects of the storage.
+    pub fn list(&self) -> Result<Vec<String>, ArtifactStoreError> {
+        let prefix = (!self.prefix.is_empty()).then(|| ObjectPath::from(self.prefix.as_str()));
+        let objects: Vec<_> =
+            self.block_on(self.store.list(prefix.as_ref()).try_collect::<Vec<_>>())?;
+        Ok(objects
+            .into_iter()
+            .map(|object| {
+                let path = object.location.to_string();
+                match self.prefix.as_str() {
+                    "" => path,
+                    prefix => path.strip_prefix(&format!("{prefix}/")).unwrap_or(&path).to_string(),
+                }
+            })
+            .collect())
+    }
+
+    /// Evicts the cached copy of an object, returning whether it was cached.
+    pub fn evict(&self, key: &str) -> Result<bool, ArtifactStoreError> {
+        let Some(path) = self.cache_path(key) else { return Ok(false) };
+        match fs::remove_file(path) {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Writes the cached copy of an object, if the store has a cache.
+    fn write_cache(&self, key: &str, data: &[u8]) -> Result<(), ArtifactStoreError> {
+        if let Some(path) = self.cache_path(key) {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, data)?;
+        }
+        Ok(())
+    }
+}
+
+/// The lifecycle of the artifacts of a kind, in blocks behind the tip.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LifecycleRule {
+    /// The kind of the artifacts, e.g. `trace` or `pie`.
+    pub kind: String,
+    /// The number of blocks behind the tip past which the cached copies are evicted.
+    #[serde(default)]
+    pub cache_blocks: Option<u64>,
+    /// The number of blocks behind the tip past which the objects are deleted from the storage.
+    #[serde(default)]
+    pub expire_blocks: Option<u64>,
+}
+
+impl LifecycleRule {
+    /// Returns whether the cached copy of an artifact of the given block is evicted at `tip`.
+    pub fn evicts(&self, number: u64, tip: u64) -> bool {
+        self.cache_blocks.is_some_and(|blocks| number.saturating_add(blocks) < tip)
+    }
+
+    /// Returns whether an artifact of the given block is deleted at `tip`.
+    pub fn expires(&self, number: u64, tip: u64) -> bool {
+        self.expire_blocks.is_some_and(|blocks| number.saturating_add(blocks) < tip)
+    }
+}
+
+/// The lifecycle rules of the stored artifacts, loaded from a JSON file, e.g.
+/// `{"rules": [{"kind": "trace", "cache_blocks": 1000, "expire_blocks": 100000}]}`.
+///
+/// Artifacts of a kind without a rule are kept forever, in the storage and the cache.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LifecyclePolicy {
+    /// The rules, by kind of artifact.
+    pub rules: Vec<LifecycleRule>,
+}
+
+impl LifecyclePolicy {
+    /// Loads a policy from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    /// Returns the rule of the given kind of artifact, if any.
+    pub fn rule(&self, kind: &str) -> Option<&LifecycleRule> {
+        self.rules.iter().find(|rule| rule.kind == kind)
+    }
+}
+
+/// The outcome of the application of a [`LifecyclePolicy`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LifecycleReport {
+    /// The number of cached copies evicted.
+    pub evicted: usize,
+    /// The number of objects deleted from the storage.
+    pub expired: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_artifact_store() {
+        let cache = tempfile::tempdir().unwrap();
+        let store = ArtifactStore::open("memory://", Some(cache.path().to_path_buf())).unwrap();
+        let key = object_key("trace", 42, "execution");
+        assert_eq!(key, "trace/42/execution");
+
+        store.put(&key, b"trace".to_vec()).unwrap();
+        assert!(cache.path().join(&key).exists());
+
+        // Evicted objects are downloaded again into the cache
+        assert!(store.evict(&key).unwrap());
+        assert_eq!(store.get(&key).unwrap(), Some(b"trace".to_vec()));
+        assert!(cache.path().join(&key).exists());
+        assert_eq!(store.list().unwrap(), vec![key.clone()]);
+
+        store.delete(&key).unwrap();
+        assert_eq!(store.get(&key).unwrap(), None);
+        assert!(matches!(
+            ArtifactStore::open("ftp://host/objects", None),
+            Err(ArtifactStoreError::UnsupportedScheme(_))
+        ));
+    }
+
+    #[test]
+    fn test_lifecycle_policy() {
+        let policy: LifecyclePolicy = serde_json::from_str(
+            r#"{"rules": [{"kind": "trace", "cache_blocks": 10, "expire_blocks": 100}]}"#,
+        )
+        .unwrap();
+        let rule = policy.rule("trace").unwrap();
+        assert!(rule.evicts(89, 100) && !rule.evicts(90, 100));
+        assert!(rule.expires(0, 101) && !rule.expires(1, 101));
+        assert_eq!(policy.rule(PIE_KIND), None);
+    }
+
+    #[test]
+    fn test_apply_lifecycle() {
+        let cache = tempfile::tempdir().unwrap();
+        let store = ArtifactStore::open("memory://", Some(cache.path().to_path_buf())).unwrap();
+        let db = Database::new(Connection::open_in_memory().unwrap())
+            .unwrap()
+            .with_artifact_store(store.clone());
+        for number in [1, 50, 100] {
+            db.store_artifact(PIE_KIND, number, "block.pie.zip", vec![1]).unwrap();
+        }
+
+        let policy = LifecyclePolicy {
+            rules: vec![LifecycleRule {
+                kind: PIE_KIND.to_string(),
+                cache_blocks: Some(10),
+                expire_blocks: Some(60),
+            }],
+        };
+        let report = db.apply_lifecycle(&policy, 100).unwrap();
+        assert_eq!(report, LifecycleReport { evicted: 1, expired: 1 });
+        assert_eq!(db.apply_lifecycle(&policy, 100).unwrap(), LifecycleReport::default());
+
+        // The expired artifacts are deleted, the evicted ones are still stored
+        let key = |number| object_key(PIE_KIND, number, "block.pie.zip");
+        assert_eq!(store.get(&key(1)).unwrap(), None);
+        assert!(!cache.path().join(key(50)).exists());
+        assert_eq!(store.get(&key(50)).unwrap(), Some(vec![1]));
+        assert!(cache.path().join(key(100)).exists());
+    }
+
+    #[test]
+    fn test_apply_lifecycle_expires_traces() {
+        let store = ArtifactStore::open("memory://", None).unwrap();
+        let db = Database::new(Connection::open_in_memory().unwrap())
+            .unwrap()
+            .with_artifact_store(store.clone());
+        for number in [1, 100] {
+            db.lock()
+                .unwrap()
+                .execute(
+                    "INSERT INTO trace (number, execution, memory, air_public_input, air_private_input)
+                    VALUES (?, ?, ?, ?, ?)",
+                    (number, vec![1u8], vec![2u8], vec![3u8], vec![4u8]),
+                )
+                .unwrap();
+        }
+        assert_eq!(db.tier_traces(10).unwrap(), 2);
+
+        let policy = LifecyclePolicy {
+            rules: vec![LifecycleRule {
+                kind: "trace".to_string(),
+                cache_blocks: None,
+                expire_blocks: Some(10),
+            }],
+        };
+        assert_eq!(db.apply_lifecycle(&policy, 100).unwrap().expired, 1);
+
+        // The trace of the expired artifact is deleted with it
+        assert!(!db.has_execution_trace(1).unwrap());
+        assert!(db.has_execution_trace(100).unwrap());
+        assert_eq!(store.get(&object_key("trace", 1, "execution")).unwrap(), None);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_artifact_store_current_thread_runtime() {
+        let store = ArtifactStore::open("memory://", None).unwrap();
+        store.put("trace/1/execution", b"trace".to_vec()).unwrap();
+        assert_eq!(store.get("trace/1/execution").unwrap(), Some(b"trace".to_vec()));
+    }
+}
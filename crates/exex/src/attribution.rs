@@ -0,0 +1,311 @@
+use crate::serde::{cache::ProgramLayoutCache, CairoType};
+use cairo_vm::{types::program::Program, vm::trace::trace_entry::RelocatedTraceEntry, Felt252};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// The full name of the Cairo function executing a single EVM transaction.
+pub const EXECUTE_FUNCTION: &str = "src.interpreter.Interpreter.execute";
+
+/// The relocated address of the first cell of the program segment.
+pub const PROGRAM_BASE: usize = 1;
+
+/// Represents errors that can occur when attributing the Cairo resources to transactions.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AttributionError {
+    /// Error variant indicating that an identifier is missing from the program.
+    #[error("Identifier '{0}' not found in the program")]
+    MissingIdentifier(String),
+
+    /// Error variant indicating that the program does not execute the transactions through the
+    /// given function, e.g. a program only validating them.
+    #[error("Program does not execute the transactions through '{0}'")]
+    MissingEntryPoint(String),
+
+    /// Error variant indicating that the frame of an invocation does not fit the function.
+    #[error("Invalid frame of the invocation at step {0}")]
+    InvalidFrame(usize),
+
+    /// Error variant indicating that the size of a type cannot be computed.
+    #[error("Unable to compute the size of type '{0}'")]
+    UnknownSize(String),
+
+    /// Error variant indicating that a builtin pointer cannot be read from the memory.
+    #[error("Builtin pointer '{name}' not found at address {address}")]
+    MissingPointer {
+        /// The name of the implicit argument.
+        name: String,
+        /// The relocated address of the pointer.
+        address: usize,
+    },
+}
+
+/// The frame layout of a Cairo function, used to find its invocations in a relocated trace and
+/// to read its builtin pointers on entry and on return.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameSpec {
+    /// The relocated pc of the first instruction of the function.
+    pub pc: usize,
+    /// The builtin pointers among the implicit arguments, with their offset.
+    pub builtin_ptrs: Vec<(String, usize)>,
+    /// The size of the implicit arguments.
+    pub implicit_args_size: usize,
+    /// The size of the explicit arguments.
+    pub args_size: usize,
+    /// The size of the explicit return values.
+    pub return_size: usize,
+}
+
+impl FrameSpec {
+    /// Builds the frame layout of [`EXECUTE_FUNCTION`], executing a single EVM transaction.
+    ///
+    /// Fails with [`AttributionError::MissingEntryPoint`] if the program does not execute the
+    /// transactions, in which case nothing can be read from its traces about them.
+    pub fn execute(program: &Program) -> Result<Self, AttributionError> {
+        if program.get_identifier(EXECUTE_FUNCTION).is_none() {
+            return Err(AttributionError::MissingEntryPoint(EXECUTE_FUNCTION.to_string()));
+        }
+        Self::from_program(program, EXECUTE_FUNCTION)
+    }
+
+    /// Builds the frame layout of a function from the identifiers of the program.
+    ///
+    /// Implicit arguments whose name ends with `_ptr` are considered builtin pointers.
+    pub fn from_program(program: &Program, function: &str) -> Result<Self, AttributionError> {
+        let identifier = |name: String| {
+            program.get_identifier(&name).ok_or(AttributionError::MissingIdentifier(name))
+        };
+
+        let pc = identifier(function.to_string())?
+            .pc
+            .ok_or_else(|| AttributionError::MissingIdentifier(function.to_string()))?;
+        let implicit_args = identifier(format!("{function}.ImplicitArgs"))?;
+        let args = identifier(format!("{function}.Args"))?;
+        let return_type =
+            identifier(format!("{function}.Return"))?.cairo_type.clone().unwrap_or_default();
+
+        let mut builtin_ptrs: Vec<_> = implicit_args
+            .members
+            .iter()
+            .flatten()
+            .filter(|(name, _)| name.ends_with("_ptr"))
+            .map(|(name, member)| (name.clone(), member.offset))
+            .collect();
+        builtin_ptrs.sort_by_key(|(_, offset)| *offset);
+
+        Ok(Self {
+            pc: pc + PROGRAM_BASE,
+            builtin_ptrs,
+            implicit_args_size: implicit_args.size.unwrap_or_default(),
+            args_size: args.size.unwrap_or_default(),
+            return_size: ProgramLayoutCache::new(program)
+                .type_size(&CairoType::parse(&return_type))
+                .map_err(|_| AttributionError::UnknownSize(return_type))?,
+        })
+    }
+}
+
+/// The Cairo resources used to execute a single EVM transaction.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionResources {
+    /// The index of the transaction in the block.
+    pub tx_index: u32,
+    /// The number of Cairo steps, including the nested calls.
+    pub steps: u64,
+    /// The number of instances of each builtin, by builtin name.
+    pub builtins: BTreeMap<String, u64>,
+}
+
+/// Attributes the steps and builtins of a relocated trace to the invocations of a function.
+///
+/// An invocation starts when the pc reaches the function, and ends on the first step whose frame
+/// pointer is below the frame of the invocation, i.e. once the function returned. The builtin
+/// usage is the progress of the builtin pointers between the arguments and the return values.
+pub fn attribute_transactions(
+    spec: &FrameSpec,
+    trace: &[RelocatedTraceEntry],
+    memory: &[Felt252],
+) -> Result<Vec<TransactionResources>, AttributionError> {
+    let read = |name: &str, address: usize| {
+        memory
+            .get(address)
+            .and_then(felt_to_usize)
+            .ok_or_else(|| AttributionError::MissingPointer { name: name.to_string(), address })
+    };
+
+    let mut transactions = Vec::new();
+    for (start, end) in invocations(spec, trace) {
+        let mut builtins = BTreeMap::new();
+        if let Some(exit) = trace.get(end) {
+            let args_start = trace[start]
+                .fp
+                .checked_sub(2 + spec.implicit_args_size + spec.args_size)
+                .ok_or(AttributionError::InvalidFrame(start))?;
+            let returns_start = exit
+                .ap
+                .checked_sub(spec.implicit_args_size + spec.return_size)
+                .ok_or(AttributionError::InvalidFrame(end))?;
+            for (name, offset) in &spec.builtin_ptrs {
+                let used = read(name, returns_start + offset)?
+                    .saturating_sub(read(name, args_start + offset)?);
+                let builtin = name.trim_end_matches("_ptr");
+                builtins.insert(builtin.to_string(), (used / cells_per_instance(builtin)) as u64);
+            }
+        }
+
+        transactions.push(TransactionResources {
+            tx_index: transactions.len() as u32,
+            steps: (end - start) as u64,
+            builtins,
+        });
+    }
+
+    Ok(transactions)
+}
+
+/// Returns the steps of the invocations of a function in a relocated trace, as the step entering
+/// the function and the step following its return, `trace.len()` if it never returns.
+pub(crate) fn invocations(spec: &FrameSpec, trace: &[RelocatedTraceEntry]) -> Vec<(usize, usize)> {
+    let mut invocations = Vec::new();
+    let mut step = 0;
+    while step < trace.len() {
+        let entry = &trace[step];
+        if entry.pc != spec.pc {
+            step += 1;
+            continue;
+        }
+
+        let end = trace[step..]
+            .iter()
+            .position(|other| other.fp < entry.fp)
+            .map_or(trace.len(), |position| step + position);
+        invocations.push((step, end));
+        step = end;
+    }
+    invocations
+}
+
+/// Returns the number of memory cells of an instance of the builtin, `1` for unknown builtins.
+fn cells_per_instance(builtin: &str) -> usize {
+    match builtin {
+        "pedersen" => 3,
+        "ecdsa" => 2,
+        "bitwise" => 5,
+        "ec_op" => 7,
+        "keccak" => 16,
+        "poseidon" => 6,
+        "add_mod" | "mul_mod" => 7,
+        _ => 1,
+    }
+}
+
+/// Converts a felt holding a relocated address into a `usize`.
+pub(crate) fn felt_to_usize(value: &Felt252) -> Option<usize> {
+    let bytes = value.to_bytes_be();
+    let (high, low) = bytes.split_at(bytes.len() - 8);
+    high.iter().all(|byte| *byte == 0).then(|| u64::from_be_bytes(low.try_into().unwrap()) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(pc: usize, ap: usize, fp: usize) -> RelocatedTraceEntry {
+        RelocatedTraceEntry { pc, ap, fp }
+    }
+
+    #[test]
+    fn test_attribute_transactions() {
+        // A function taking a single `range_check_ptr` implicit argument and one argument, and
+        // returning one value.
+        let spec = FrameSpec {
+            pc: 10,
+            builtin_ptrs: vec![("range_check_ptr".to_string(), 0)],
+            implicit_args_size: 1,
+            args_size: 1,
+            return_size: 1,
+        };
+
+        // The first invocation is entered with `fp = 100`, its arguments being at 96 and 97, and
+        // returns with `ap = 110`, its return values being at 108 and 109. The second one is
+        // entered with `fp = 120` and never returns.
+        let trace = vec![
+            entry(1, 98, 98),
+            entry(10, 100, 100),
+            entry(20, 103, 103),
+            entry(11, 108, 100),
+            entry(2, 110, 98),
+            entry(10, 120, 120),
+            entry(11, 121, 120),
+        ];
+        let mut memory = vec![Felt252::ZERO; 130];
+        memory[96] = Felt252::from(500);
+        memory[108] = Felt252::from(507);
+
+        let transactions = attribute_transactions(&spec, &trace, &memory).unwrap();
+
+        assert_eq!(
+            transactions,
+            vec![
+                TransactionResources {
+                    tx_index: 0,
+                    steps: 3,
+                    builtins: BTreeMap::from([("range_check".to_string(), 7)]),
+                },
+                TransactionResources { tx_index: 1, steps: 2, builtins: BTreeMap::new() },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_attribute_transactions_missing_pointer() {
+        let spec = FrameSpec {
+            pc: 10,
+            builtin_ptrs: vec![("bitwise_ptr".to_string(), 0)],
+            implicit_args_size: 1,
+            args_size: 0,
+            return_size: 0,
+        };
+        let trace = vec![entry(10, 100, 100), entry(2, 110, 98)];
+
+        let result = attribute_transactions(&spec, &trace, &[]);
+
+        assert_eq!(
+            result,
+            Err(AttributionError::MissingPointer { name: "bitwise_ptr".to_string(), address: 109 })
+        );
+    }
+
+    #[test]
+    fn test_frame_spec_missing_function() {
+        let program_content = include_bytes!("../testdata/keccak_add_uint256.json");
+        let program = Program::from_bytes(program_content, Some("main")).unwrap();
+
+        assert_eq!(
+            FrameSpec::from_program(&program, EXECUTE_FUNCTION),
+            Err(AttributionError::MissingIdentifier(EXECUTE_FUNCTION.to_string()))
+        );
+        assert_eq!(
+            FrameSpec::execute(&program),
+            Err(AttributionError::MissingEntryPoint(EXECUTE_FUNCTION.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_frame_spec_from_program() {
+        let program_content = include_bytes!("../testdata/keccak_add_uint256.json");
+        let program = Program::from_bytes(program_content, Some("main")).unwrap();
+
+        let spec = FrameSpec::from_program(&program, "__main__.main").unwrap();
+
+        assert_eq!(
+            spec.builtin_ptrs,
+            vec![
+                ("output_ptr".to_string(), 0),
+                ("range_check_ptr".to_string(), 1),
+                ("bitwise_ptr".to_string(), 2)
+            ]
+        );
+        assert_eq!(spec.implicit_args_size, 3);
+    }
+}
@@ -0,0 +1,474 @@
+//! Replay benchmarks of historical blocks, projected to the proving cost of the chain.
+//!
+//! A range of stored blocks is replayed with a dry run of the program of each block on its recorded
+//! program input, recording the Cairo steps and builtin instances they use alongside their gas, and
+//! the utilization of their memory segments in the report of each block. The samples are then
+//! priced with [`ProverCostModel`]s, linear models of the proving time of a block, to project the
+//! proving hours and the hardware cost of a day of chain, which operators need to size their
+//! provers.
+
+use crate::{
+    db::Database,
+    executor::{dry_run, DryRun},
+    hints::KakarotHintProcessor,
+    limits::ExecutionLimits,
+    output::ProgramOutput,
+    serde::cache::ProgramLayoutCache,
+    tuning::{RunnerTuning, SegmentUtilization},
+};
+use alloy_primitives::U256;
+use cairo_vm::types::program::Program;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use thiserror::Error;
+
+/// The number of seconds in a day.
+const SECS_PER_DAY: f64 = 86_400.0;
+
+/// Represents errors that can occur when benchmarking a range of blocks.
+#[derive(Debug, Error, PartialEq)]
+pub enum BenchmarkError {
+    /// Error variant indicating an empty block range.
+    #[error("Invalid block range {from}..={to}")]
+    InvalidRange {
+        /// The first block of the range.
+        from: u64,
+        /// The last block of the range.
+        to: u64,
+    },
+
+    /// Error variant indicating a block missing from the database.
+    #[error("Block {0} not found")]
+    MissingBlock(u64),
+
+    /// Error variant indicating a block without recorded program input.
+    #[error("No program input for block {0}")]
+    MissingInput(u64),
+
+    /// Error variant indicating a replay whose output is not the header of its block.
+    #[error("Replay of block {0} did not output its header")]
+    InvalidOutput(u64),
+
+    /// Error variant indicating a replay which did not complete.
+    #[error("Replay of block {0} did not complete")]
+    Incomplete(u64),
+
+    /// Error variant indicating that the block time can't be derived from the samples.
+    #[error("Block time unknown, the sampled blocks share the same timestamp")]
+    UnknownBlockTime,
+}
+
+/// The resources used by a replayed block.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockSample {
+    /// The number of the block.
+    pub block_number: u64,
+    /// The timestamp of the block.
+    pub timestamp: u64,
+    /// The gas used by the block.
+    pub gas_used: u64,
+    /// The number of Cairo steps run.
+    pub steps: usize,
+    /// The number of instances of each builtin used, by name.
+    pub builtins: BTreeMap<String, usize>,
+    /// The number of memory holes of the run.
+    #[serde(default)]
+    pub memory_holes: usize,
+    /// The utilization of the memory segments, by name.
+    #[serde(default)]
+    pub utilization: BTreeMap<String, SegmentUtilization>,
+}
+
+/// A linear model of the proving time of a block.
+///
+/// The steps are padded to the next power of two, as the trace proven by the prover.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProverCostModel {
+    /// The name of the model, e.g. the prover and its machine.
+    pub name: String,
+    /// The fixed proving time of a block, in seconds.
+    #[serde(default)]
+    pub fixed_secs: f64,
+    /// The proving time of a million padded steps, in seconds.
+    pub secs_per_million_steps: f64,
+    /// The proving time of a thousand instances of each builtin, in seconds, by builtin name.
+    #[serde(default)]
+    pub secs_per_thousand_builtins: BTreeMap<String, f64>,
+    /// The cost of an hour of the proving machine.
+    pub hourly_cost: f64,
+}
+
+impl ProverCostModel {
+    /// Returns the proving time of a block, in seconds.
+    pub fn proving_secs(&self, sample: &BlockSample) -> f64 {
+        let steps = sample.steps.next_power_of_two() as f64;
+        let builtins: f64 = sample
+            .builtins
+            .iter()
+            .filter_map(|(name, instances)| {
+                let secs = self.secs_per_thousand_builtins.get(name)?;
+                Some(secs * *instances as f64 / 1_000.0)
+            })
+            .sum();
+        self.fixed_secs + self.secs_per_million_steps * steps / 1_000_000.0 + builtins
+    }
+}
+
+/// The prover cost models of a benchmark, as loaded from a JSON array.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CostModels(pub Vec<ProverCostModel>);
+
+impl CostModels {
+    /// Loads the cost models from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+}
+
+/// A least-squares line `y = intercept + slope * x`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct LinearFit {
+    /// The value at `x = 0`.
+    pub intercept: f64,
+    /// The increase of `y` per unit of `x`.
+    pub slope: f64,
+}
+
+impl LinearFit {
+    /// Fits a line through the points, the slope being zero when all `x` are equal.
+    pub fn fit(points: &[(f64, f64)]) -> Self {
+        if points.is_empty() {
+            return Self::default();
+        }
+        let n = points.len() as f64;
+        let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+        let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+        let covariance: f64 = points.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+        let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+
+        let slope = if variance > 0.0 { covariance / variance } else { 0.0 };
+        Self { intercept: mean_y - slope * mean_x, slope }
+    }
+
+    /// Returns the value of the line at `x`.
+    pub fn at(&self, x: f64) -> f64 {
+        self.intercept + self.slope * x
+    }
+}
+
+/// The projection of the proving cost of a day of chain with a cost model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Projection {
+    /// The name of the cost model.
+    pub model: String,
+    /// The mean proving time of a block, in seconds.
+    pub secs_per_block: f64,
+    /// The proving time of a million gas, in seconds, `None` when the blocks used no gas.
+    pub secs_per_million_gas: Option<f64>,
+    /// The proving hours of a day of chain.
+    pub proving_hours_per_day: f64,
+    /// The number of machines proving in parallel needed to keep up with the chain.
+    pub machines: u64,
+    /// The hardware cost of a day of chain.
+    pub cost_per_day: f64,
+}
+
+/// The report of a benchmark: the replayed blocks and the projections of each cost model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    /// The replayed blocks.
+    pub blocks: Vec<BlockSample>,
+    /// The total number of Cairo steps.
+    pub steps: usize,
+    /// The total gas used.
+    pub gas_used: u64,
+    /// The total number of instances of each builtin, by name.
+    pub builtins: BTreeMap<String, usize>,
+    /// The fit of the Cairo steps of a block to its gas.
+    pub steps_per_gas: LinearFit,
+    /// The time between two blocks, in seconds.
+    pub block_time_secs: f64,
+    /// The projections, by cost model.
+    pub projections: Vec<Projection>,
+}
+
+impl BenchmarkReport {
+    /// Aggregates the samples and projects them with each cost model.
+    ///
+    /// The block time defaults to the mean interval between the timestamps of the samples.
+    pub fn new(
+        blocks: Vec<BlockSample>,
+        models: &CostModels,
+        block_time_secs: Option<f64>,
+    ) -> Result<Self, BenchmarkError> {
+        let block_time_secs = match block_time_secs {
+            Some(secs) => secs,
+            None => mean_block_time(&blocks).ok_or(BenchmarkError::UnknownBlockTime)?,
+        };
+
+        let steps = blocks.iter().map(|block| block.steps).sum();
+        let gas_used = blocks.iter().map(|block| block.gas_used).sum();
+        let mut builtins = BTreeMap::new();
+        for block in &blocks {
+            for (name, instances) in &block.builtins {
+                *builtins.entry(name.clone()).or_default() += instances;
+            }
+        }
+        let points: Vec<_> =
+            blocks.iter().map(|block| (block.gas_used as f64, block.steps as f64)).collect();
+
+        let blocks_per_day = SECS_PER_DAY / block_time_secs;
+        let projections = models
+            .0
+            .iter()
+            .map(|model| {
+                let total: f64 = blocks.iter().map(|block| model.proving_secs(block)).sum();
+                let secs_per_block = total / blocks.len().max(1) as f64;
+                let proving_hours_per_day = secs_per_block * blocks_per_day / 3_600.0;
+                Projection {
+                    model: model.name.clone(),
+                    secs_per_block,
+                    secs_per_million_gas: (gas_used > 0)
+                        .then(|| total * 1_000_000.0 / gas_used as f64),
+                    proving_hours_per_day,
+                    machines: (proving_hours_per_day / 24.0).ceil() as u64,
+                    cost_per_day: proving_hours_per_day * model.hourly_cost,
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            blocks,
+            steps,
+            gas_used,
+            builtins,
+            steps_per_gas: LinearFit::fit(&points),
+            block_time_secs,
+            projections,
+        })
+    }
+}
+
+/// Returns the mean interval between the timestamps of the blocks, `None` when it is zero.
+fn mean_block_time(blocks: &[BlockSample]) -> Option<f64> {
+    let first = blocks.iter().min_by_key(|block| block.block_number)?;
+    let last = blocks.iter().max_by_key(|block| block.block_number)?;
+    let count = last.block_number - first.block_number;
+    let elapsed = last.timestamp.checked_sub(first.timestamp)?;
+    (count > 0 && elapsed > 0).then(|| elapsed as f64 / count as f64)
+}
+
+/// Replays the stored blocks `from..=to` with a dry run of their program, the program recorded
+/// for the block if any, `program` otherwise, on the program input recorded for the block.
+///
+/// The output of each replay must be the header of its block, so that the samples measure the
+/// execution of the block.
+pub fn replay_range(
+    db: &Database,
+    program: &Path,
+    from: u64,
+    to: u64,
+) -> eyre::Result<Vec<BlockSample>> {
+    if from > to {
+        return Err(BenchmarkError::InvalidRange { from, to }.into());
+    }
+
+    // The programs are read and laid out once.
+    let mut programs: HashMap<PathBuf, (Vec<u8>, Arc<ProgramLayoutCache>)> = HashMap::new();
+    let mut samples = Vec::new();
+    for number in from..=to {
+        let block = db.block(U256::from(number))?.ok_or(BenchmarkError::MissingBlock(number))?;
+        let input = db.program_input(number)?.ok_or(BenchmarkError::MissingInput(number))?;
+        let path = match db.block_program(number)? {
+            Some(recorded) => recorded.program,
+            None => program.to_path_buf(),
+        };
+        let (bytes, layouts) = match programs.get(&path) {
+            Some(program) => program.clone(),
+            None => {
+                let bytes = fs::read(&path)?;
+                let layouts =
+                    Arc::new(ProgramLayoutCache::new(&Program::from_bytes(&bytes, Some("main"))?));
+                programs.insert(path, (bytes.clone(), layouts.clone()));
+                (bytes, layouts)
+            }
+        };
+
+        let expected = ProgramOutput::from(&input.block.block_header);
+        let mut hint_processor =
+            KakarotHintProcessor::default().with_program_input(layouts, Arc::new(input));
+        let report = match dry_run(
+            &bytes,
+            &mut hint_processor,
+            &ExecutionLimits::default(),
+            &RunnerTuning::default(),
+            &mut || false,
+        )? {
+            DryRun::Completed(report) => report,
+            DryRun::Interrupted(_) | DryRun::Preempted => {
+                return Err(BenchmarkError::Incomplete(number).into())
+            }
+        };
+        if report.output != Some(expected) || expected.block_number != number {
+            return Err(BenchmarkError::InvalidOutput(number).into());
+        }
+
+        samples.push(BlockSample {
+            block_number: number,
+            timestamp: block.timestamp,
+            gas_used: block.gas_used,
+            steps: report.steps,
+            builtins: report.builtins,
+            memory_holes: report.profile.memory_holes(),
+            utilization: report.profile.utilization,
+        });
+    }
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::program_input::{BlockInput, HeaderInput, ProgramInput};
+    use alloy_consensus::Header;
+    use reth_primitives::SealedBlockWithSenders;
+    use reth_revm::db::BundleState;
+    use rusqlite::Connection;
+
+    fn sample(block_number: u64, timestamp: u64, gas_used: u64, steps: usize) -> BlockSample {
+        BlockSample {
+            block_number,
+            timestamp,
+            gas_used,
+            steps,
+            builtins: BTreeMap::from([("bitwise".to_string(), 1_000)]),
+            ..Default::default()
+        }
+    }
+
+    fn model() -> ProverCostModel {
+        ProverCostModel {
+            name: "stone".to_string(),
+            fixed_secs: 10.0,
+            secs_per_million_steps: 100.0,
+            secs_per_thousand_builtins: BTreeMap::from([("bitwise".to_string(), 2.0)]),
+            hourly_cost: 3.0,
+        }
+    }
+
+    #[test]
+    fn test_proving_secs() {
+        // 600_000 steps are padded to 2^20.
+        let secs = model().proving_secs(&sample(1, 0, 0, 600_000));
+        assert!((secs - (10.0 + 104.8576 + 2.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_linear_fit() {
+        let fit = LinearFit::fit(&[(1.0, 3.0), (2.0, 5.0), (3.0, 7.0)]);
+        assert!((fit.slope - 2.0).abs() < 1e-9 && (fit.intercept - 1.0).abs() < 1e-9);
+        assert!((fit.at(10.0) - 21.0).abs() < 1e-9);
+
+        // Points sharing the same `x` fit a horizontal line through their mean.
+        assert_eq!(
+            LinearFit::fit(&[(1.0, 2.0), (1.0, 4.0)]),
+            LinearFit { intercept: 3.0, slope: 0.0 }
+        );
+        assert_eq!(LinearFit::fit(&[]), LinearFit::default());
+    }
+
+    #[test]
+    fn test_benchmark_report() {
+        let blocks = vec![sample(10, 100, 1_000_000, 1 << 20), sample(12, 124, 3_000_000, 1 << 20)];
+        let models = CostModels(vec![model()]);
+
+        let report = BenchmarkReport::new(blocks.clone(), &models, None).unwrap();
+        assert_eq!(report.steps, 2 << 20);
+        assert_eq!(report.gas_used, 4_000_000);
+        assert_eq!(report.builtins, BTreeMap::from([("bitwise".to_string(), 2_000)]));
+        assert_eq!(report.steps_per_gas.slope, 0.0);
+        assert_eq!(report.block_time_secs, 12.0);
+
+        // 7_200 blocks a day, proven in 116.8576 seconds each.
+        let projection = &report.projections[0];
+        assert_eq!(projection.model, "stone");
+        assert!((projection.secs_per_block - 116.8576).abs() < 1e-9);
+        assert!((projection.secs_per_million_gas.unwrap() - 58.4288).abs() < 1e-9);
+        assert!((projection.proving_hours_per_day - 233.7152).abs() < 1e-9);
+        assert_eq!(projection.machines, 10);
+        assert!((projection.cost_per_day - 701.1456).abs() < 1e-9);
+
+        // The block time is required when it can't be derived from the samples.
+        assert_eq!(
+            BenchmarkReport::new(vec![sample(1, 5, 0, 1)], &models, None),
+            Err(BenchmarkError::UnknownBlockTime)
+        );
+        let report = BenchmarkReport::new(vec![sample(1, 5, 0, 1)], &models, Some(2.0)).unwrap();
+        assert_eq!(report.projections[0].secs_per_million_gas, None);
+    }
+
+    #[test]
+    fn test_load_cost_models() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("models.json");
+        fs::write(&path, r#"[{"name": "stone", "secs_per_million_steps": 60, "hourly_cost": 2}]"#)
+            .unwrap();
+
+        let models = CostModels::load(&path).unwrap();
+        assert_eq!(models.0.len(), 1);
+        assert_eq!(models.0[0].fixed_secs, 0.0);
+        assert!(models.0[0].secs_per_thousand_builtins.is_empty());
+    }
+
+    #[test]
+    fn test_replay_range() {
+        let db = Database::new(Connection::open_in_memory().unwrap()).unwrap();
+        db.insert_block_with_bundle(&SealedBlockWithSenders::default(), BundleState::default())
+            .unwrap();
+        let program = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../cairo/programs/os.json");
+
+        // The block is not replayed without its program input.
+        let err = replay_range(&db, &program, 0, 0).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&BenchmarkError::MissingInput(0)));
+
+        let header =
+            Header { gas_limit: 30_000_000, base_fee_per_gas: Some(7), ..Default::default() };
+        let input = ProgramInput {
+            block: BlockInput {
+                block_header: HeaderInput::from(&header),
+                transactions: Vec::new(),
+            },
+            state: BTreeMap::new(),
+            chain_id: 1,
+            system_calls: Vec::new(),
+            block_hashes: BTreeMap::new(),
+        };
+        db.insert_program_input(0, &input).unwrap();
+
+        let samples = replay_range(&db, &program, 0, 0).unwrap();
+        assert_eq!(samples.len(), 1);
+        assert!(samples[0].steps > 0);
+        assert!(samples[0].builtins.contains_key("range_check"));
+
+        let err = replay_range(&db, &program, 0, 1).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&BenchmarkError::MissingBlock(1)));
+        let err = replay_range(&db, &program, 2, 1).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&BenchmarkError::InvalidRange { from: 2, to: 1 }));
+
+        // The replay of the input of another block does not output the header of the block.
+        let header = Header { number: 1, ..header };
+        let input = ProgramInput {
+            block: BlockInput { block_header: HeaderInput::from(&header), ..input.block },
+            ..input
+        };
+        db.insert_program_input(0, &input).unwrap();
+        let err = replay_range(&db, &program, 0, 0).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&BenchmarkError::InvalidOutput(0)));
+    }
+}
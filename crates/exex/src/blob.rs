@@ -0,0 +1,280 @@
+//! KZG commitments over the witnesses of the blocks posted as EIP-4844 blobs.
+//!
+//! When the inputs of the proven runs are made available on L1 as blobs, the witness of a block is
+//! encoded in blobs whose KZG commitments, proofs and versioned hashes are computed and stored
+//! alongside the block. The posted blobs can then be checked against the stored commitments and
+//! the witness used in the proof, tying the data availability of a block to its validity.
+//!
+//! The witness is prefixed with its length as a big-endian `u64`, and split in chunks of 31 bytes
+//! stored in the low bytes of the field elements of the blobs, so that each field element is lower
+//! than the BLS12-381 modulus.
+
+use crate::{db::Database, input::program_input::ProgramInput};
+use alloy_eips::eip4844::env_settings::EnvKzgSettings;
+use alloy_primitives::{keccak256, Bytes, FixedBytes, B256};
+use c_kzg::{Blob, Bytes48, KzgCommitment, KzgProof};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+/// The number of field elements of a blob.
+pub const FIELD_ELEMENTS_PER_BLOB: usize = 4096;
+
+/// The number of bytes of a field element.
+pub const BYTES_PER_FIELD_ELEMENT: usize = 32;
+
+/// The number of bytes of a blob.
+pub const BYTES_PER_BLOB: usize = FIELD_ELEMENTS_PER_BLOB * BYTES_PER_FIELD_ELEMENT;
+
+/// The number of witness bytes stored in each field element, its first byte being zero.
+pub const USABLE_BYTES_PER_FIELD_ELEMENT: usize = BYTES_PER_FIELD_ELEMENT - 1;
+
+/// The version byte of the versioned hashes of the KZG commitments.
+pub const VERSIONED_HASH_VERSION_KZG: u8 = 0x01;
+
+/// The number of bytes of the length prefix of the encoded witness.
+const LENGTH_PREFIX: usize = 8;
+
+/// Represents errors that can occur when committing to or verifying the blobs of a witness.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum BlobError {
+    /// Error variant indicating a failure of the KZG library.
+    #[error("KZG error: {0}")]
+    Kzg(String),
+
+    /// Error variant indicating that the number of posted blobs does not match the commitments.
+    #[error("Expected {expected} blobs, got {actual}")]
+    CountMismatch {
+        /// The number of committed blobs.
+        expected: usize,
+        /// The number of posted blobs.
+        actual: usize,
+    },
+
+    /// Error variant indicating that a posted blob does not match its commitment.
+    #[error("Blob {0} does not match its commitment")]
+    InvalidBlob(usize),
+
+    /// Error variant indicating a field element with a non-zero first byte, or a length prefix
+    /// exceeding the blobs.
+    #[error("Invalid blob encoding")]
+    InvalidEncoding,
+
+    /// Error variant indicating that the posted blobs do not hold the witness of the proof.
+    #[error("Blob data does not match the witness, expected hash {expected}, got {actual}")]
+    WitnessMismatch {
+        /// The hash of the witness of the proof.
+        expected: B256,
+        /// The hash of the data of the blobs.
+        actual: B256,
+    },
+}
+
+impl From<c_kzg::Error> for BlobError {
+    fn from(err: c_kzg::Error) -> Self {
+        Self::Kzg(err.to_string())
+    }
+}
+
+/// The KZG commitment of a blob of a witness.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobCommitment {
+    /// The KZG commitment of the blob.
+    pub commitment: FixedBytes<48>,
+    /// The KZG proof of the blob against its commitment.
+    pub proof: FixedBytes<48>,
+    /// The versioned hash of the commitment, as referenced by the blob transaction.
+    pub versioned_hash: B256,
+}
+
+/// The blobs of the witness of a block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobWitness {
+    /// The number of the block.
+    pub block_number: u64,
+    /// The keccak256 hash of the witness.
+    pub witness_hash: B256,
+    /// The commitments of the blobs, in order.
+    pub blobs: Vec<BlobCommitment>,
+}
+
+impl BlobWitness {
+    /// Returns the versioned hashes of the blobs, in order.
+    pub fn versioned_hashes(&self) -> Vec<B256> {
+        self.blobs.iter().map(|blob| blob.versioned_hash).collect()
+    }
+}
+
+/// Encodes a witness in blobs.
+pub fn encode(witness: &[u8]) -> Vec<Bytes> {
+    let mut data = (witness.len() as u64).to_be_bytes().to_vec();
+    data.extend_from_slice(witness);
+
+    let per_blob = FIELD_ELEMENTS_PER_BLOB * USABLE_BYTES_PER_FIELD_ELEMENT;
+    data.chunks(per_blob)
+        .map(|chunk| {
+            let mut blob = vec![0; BYTES_PER_BLOB];
+            for (element, bytes) in blob
+                .chunks_exact_mut(BYTES_PER_FIELD_ELEMENT)
+                .zip(chunk.chunks(USABLE_BYTES_PER_FIELD_ELEMENT))
+            {
+                element[1..=bytes.len()].copy_from_slice(bytes);
+            }
+            blob.into()
+        })
+        .collect()
+}
+
+/// Decodes the witness encoded in blobs.
+pub fn decode(blobs: &[Bytes]) -> Result<Vec<u8>, BlobError> {
+    let mut data = Vec::new();
+    for blob in blobs {
+        if blob.len() != BYTES_PER_BLOB {
+            return Err(BlobError::InvalidEncoding);
+        }
+        for element in blob.chunks_exact(BYTES_PER_FIELD_ELEMENT) {
+            if element[0] != 0 {
+                return Err(BlobError::InvalidEncoding);
+            }
+            data.extend_from_slice(&element[1..]);
+        }
+    }
+
+    let prefix = data.get(..LENGTH_PREFIX).ok_or(BlobError::InvalidEncoding)?;
+    let length = u64::from_be_bytes(prefix.try_into().expect("8 bytes")) as usize;
+    let end = LENGTH_PREFIX.checked_add(length).ok_or(BlobError::InvalidEncoding)?;
+    data.get(LENGTH_PREFIX..end).map(<[u8]>::to_vec).ok_or(BlobError::InvalidEncoding)
+}
+
+/// Returns the versioned hash of a KZG commitment: its sha256 hash, whose first byte is replaced
+/// by [`VERSIONED_HASH_VERSION_KZG`].
+pub fn versioned_hash(commitment: &[u8]) -> B256 {
+    let mut hash: [u8; 32] = Sha256::digest(commitment).into();
+    hash[0] = VERSIONED_HASH_VERSION_KZG;
+    B256::from(hash)
+}
+
+/// Encodes the witness of a block in blobs, computing their commitments.
+pub fn commit(block_number: u64, witness: &[u8]) -> Result<BlobWitness, BlobError> {
+    let settings = EnvKzgSettings::Default.get();
+    let blobs = encode(witness)
+        .iter()
+        .map(|data| {
+            let blob = Blob::from_bytes(data)?;
+            let commitment = KzgCommitment::blob_to_kzg_commitment(&blob, settings)?.to_bytes();
+            let proof = KzgProof::compute_blob_kzg_proof(&blob, &commitment, settings)?.to_bytes();
+            Ok(BlobCommitment {
+                commitment: FixedBytes::from(*commitment),
+                proof: FixedBytes::from(*proof),
+                versioned_hash: versioned_hash(commitment.as_slice()),
+            })
+        })
+        .collect::<Result<_, BlobError>>()?;
+
+    Ok(BlobWitness { block_number, witness_hash: keccak256(witness), blobs })
+}
+
+/// Verifies that posted blobs match the commitments of a witness and hold the witness itself.
+pub fn verify(witness: &BlobWitness, blobs: &[Bytes]) -> Result<(), BlobError> {
+    if blobs.len() != witness.blobs.len() {
+        return Err(BlobError::CountMismatch { expected: witness.blobs.len(), actual: blobs.len() });
+    }
+
+    let settings = EnvKzgSettings::Default.get();
+    for (index, (data, committed)) in blobs.iter().zip(&witness.blobs).enumerate() {
+        let blob = Blob::from_bytes(data).map_err(|_| BlobError::InvalidBlob(index))?;
+        let commitment = Bytes48::from_bytes(committed.commitment.as_slice())?;
+        let proof = Bytes48::from_bytes(committed.proof.as_slice())?;
+        let valid = KzgProof::verify_blob_kzg_proof(&blob, &commitment, &proof, settings)
+            .map_err(|_| BlobError::InvalidBlob(index))?;
+        if !valid || versioned_hash(committed.commitment.as_slice()) != committed.versioned_hash {
+            return Err(BlobError::InvalidBlob(index));
+        }
+    }
+
+    let actual = keccak256(decode(blobs)?);
+    if actual != witness.witness_hash {
+        return Err(BlobError::WitnessMismatch { expected: witness.witness_hash, actual });
+    }
+    Ok(())
+}
+
+/// Commits to the program input of a block posted as blobs, storing the commitments.
+pub fn commit_program_input(
+    db: &Database,
+    block_number: u64,
+    input: &ProgramInput,
+) -> eyre::Result<BlobWitness> {
+    let witness = commit(block_number, &serde_json::to_vec(input)?)?;
+    db.insert_blob_witness(&witness)?;
+    Ok(witness)
+}
+
+/// Verifies that posted blobs hold the program input of a block, against its stored commitments.
+///
+/// Returns the verified commitments, `None` if no commitments are stored for the block.
+pub fn verify_posted(
+    db: &Database,
+    block_number: u64,
+    blobs: &[Bytes],
+) -> eyre::Result<Option<BlobWitness>> {
+    let Some(witness) = db.blob_witness(block_number)? else { return Ok(None) };
+    verify(&witness, blobs)?;
+    Ok(Some(witness))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_roundtrip() {
+        for length in [0, 30, 31, 1000, FIELD_ELEMENTS_PER_BLOB * USABLE_BYTES_PER_FIELD_ELEMENT] {
+            let witness: Vec<u8> = (0..length).map(|i| i as u8).collect();
+            let blobs = encode(&witness);
+
+            assert!(blobs.iter().all(|blob| blob.len() == BYTES_PER_BLOB));
+            assert_eq!(decode(&blobs).unwrap(), witness);
+        }
+        assert_eq!(encode(&[0; 200_000]).len(), 2);
+    }
+
+    #[test]
+    fn test_decode_invalid_encoding() {
+        let mut blob = encode(b"witness")[0].to_vec();
+        blob[32] = 1;
+        assert_eq!(decode(&[blob.into()]), Err(BlobError::InvalidEncoding));
+
+        // A length prefix overflowing the offset of the payload.
+        let mut blob = encode(b"witness")[0].to_vec();
+        blob[1..=LENGTH_PREFIX].fill(0xff);
+        assert_eq!(decode(&[blob.into()]), Err(BlobError::InvalidEncoding));
+    }
+
+    #[test]
+    fn test_versioned_hash() {
+        let hash = versioned_hash(&[0xc0; 48]);
+        assert_eq!(hash[0], VERSIONED_HASH_VERSION_KZG);
+        assert_eq!(hash[1..], Sha256::digest([0xc0; 48])[1..]);
+    }
+
+    #[test]
+    fn test_commit_and_verify() {
+        let witness = commit(1, b"program input").unwrap();
+        let blobs = encode(b"program input");
+        assert_eq!(witness.blobs.len(), 1);
+        assert!(verify(&witness, &blobs).is_ok());
+
+        // A blob of another witness does not match the commitment.
+        assert_eq!(verify(&witness, &encode(b"other input")), Err(BlobError::InvalidBlob(0)));
+        assert_eq!(verify(&witness, &[]), Err(BlobError::CountMismatch { expected: 1, actual: 0 }));
+
+        // Blobs matching their commitments must still hold the witness of the proof.
+        let mut other = commit(1, b"other input").unwrap();
+        other.witness_hash = witness.witness_hash;
+        assert!(matches!(
+            verify(&other, &encode(b"other input")),
+            Err(BlobError::WitnessMismatch { .. })
+        ));
+    }
+}
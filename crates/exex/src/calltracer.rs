@@ -0,0 +1,446 @@
+//! Geth-compatible `callTracer` traces generated from the Cairo execution of a block.
+//!
+//! The call tree of a transaction is rebuilt from its [`Step`]s: a call or creation opcode followed
+//! by a deeper step opens a frame, whose arguments are read from the stack and the memory of the
+//! opcode, and the last step of the frame closes it with its output and error. The calls which do
+//! not create a frame, i.e. the precompile calls, the calls to accounts without code and the calls
+//! failing before their execution, are leaves of the tree, their output being the return data of
+//! the following step.
+//!
+//! The reverted subtrees are kept, each frame carrying its own error, as geth does.
+
+use crate::{
+    db::Database,
+    halt::EvmHalt,
+    revert::{RevertReason, REVERT_MESSAGE},
+    structlog::{transaction_steps, Step, REVERT},
+};
+use alloy_primitives::{Address, Bytes, TxKind, U256, U64};
+use reth_primitives::TransactionSigned;
+use serde::{Deserialize, Serialize};
+use std::{fmt, path::Path};
+
+/// The `CREATE` opcode.
+const CREATE: u8 = 0xf0;
+
+/// The `CALL` opcode.
+const CALL: u8 = 0xf1;
+
+/// The `CALLCODE` opcode.
+const CALLCODE: u8 = 0xf2;
+
+/// The `DELEGATECALL` opcode.
+const DELEGATECALL: u8 = 0xf4;
+
+/// The `CREATE2` opcode.
+const CREATE2: u8 = 0xf5;
+
+/// The `STATICCALL` opcode.
+const STATICCALL: u8 = 0xfa;
+
+/// The address of the RIP-7212 `P256VERIFY` precompile, supported by Kakarot.
+const P256_VERIFY: u64 = 0x100;
+
+/// The type of a call frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum CallKind {
+    /// A message call.
+    Call,
+    /// A `CALLCODE`.
+    CallCode,
+    /// A `DELEGATECALL`.
+    DelegateCall,
+    /// A `STATICCALL`.
+    StaticCall,
+    /// A contract creation, by a transaction or `CREATE`.
+    Create,
+    /// A `CREATE2`.
+    Create2,
+}
+
+impl CallKind {
+    /// Returns the kind of the frame opened by an opcode, `None` if it does not open a frame.
+    pub const fn from_opcode(op: u8) -> Option<Self> {
+        match op {
+            CALL => Some(Self::Call),
+            CALLCODE => Some(Self::CallCode),
+            DELEGATECALL => Some(Self::DelegateCall),
+            STATICCALL => Some(Self::StaticCall),
+            CREATE => Some(Self::Create),
+            CREATE2 => Some(Self::Create2),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the frame creates a contract.
+    pub const fn is_create(&self) -> bool {
+        matches!(self, Self::Create | Self::Create2)
+    }
+
+    /// Returns the name of the kind, as formatted by geth.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Call => "CALL",
+            Self::CallCode => "CALLCODE",
+            Self::DelegateCall => "DELEGATECALL",
+            Self::StaticCall => "STATICCALL",
+            Self::Create => "CREATE",
+            Self::Create2 => "CREATE2",
+        }
+    }
+}
+
+impl fmt::Display for CallKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The options of the geth `callTracer`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CallTracerConfig {
+    /// Whether the sub-calls are omitted from the trace.
+    pub only_top_call: bool,
+}
+
+/// A call frame, as formatted by the geth `callTracer`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallFrame {
+    /// The type of the frame.
+    #[serde(rename = "type")]
+    pub kind: CallKind,
+    /// The caller.
+    pub from: Address,
+    /// The callee, or the created contract.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<Address>,
+    /// The transferred value, `None` for the calls which do not transfer value.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<U256>,
+    /// The gas given to the frame.
+    pub gas: U64,
+    /// The gas used by the frame.
+    pub gas_used: U64,
+    /// The calldata, or the init code.
+    pub input: Bytes,
+    /// The output, or the deployed code.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<Bytes>,
+    /// The error of a reverted or halted frame.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The decoded reason of a reverted frame.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revert_reason: Option<String>,
+    /// The sub-calls of the frame.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub calls: Vec<CallFrame>,
+}
+
+impl CallFrame {
+    /// Returns the top-level frame of a transaction, before its execution.
+    pub fn from_transaction(transaction: &TransactionSigned, sender: Address) -> Self {
+        let (kind, to) = match transaction.kind() {
+            TxKind::Call(to) => (CallKind::Call, Some(to)),
+            TxKind::Create => (CallKind::Create, None),
+        };
+        Self {
+            kind,
+            from: sender,
+            to,
+            value: Some(transaction.value()),
+            gas: U64::from(transaction.gas_limit()),
+            gas_used: U64::ZERO,
+            input: transaction.input().clone(),
+            output: None,
+            error: None,
+            revert_reason: None,
+            calls: Vec::new(),
+        }
+    }
+
+    /// Returns the frame opened by a call or creation step, `None` for the other opcodes.
+    ///
+    /// The callee of a creation is only known from the first step of the created frame.
+    pub fn from_step(step: &Step) -> Option<Self> {
+        let kind = CallKind::from_opcode(step.op)?;
+        let (to, value, input) = match kind {
+            CallKind::Create | CallKind::Create2 => {
+                (None, Some(step.peek(0)), step.memory_slice(step.peek(1), step.peek(2)))
+            }
+            CallKind::Call | CallKind::CallCode => (
+                Some(word_address(step.peek(1))),
+                Some(step.peek(2)),
+                step.memory_slice(step.peek(3), step.peek(4)),
+            ),
+            CallKind::DelegateCall | CallKind::StaticCall => (
+                Some(word_address(step.peek(1))),
+                None,
+                step.memory_slice(step.peek(2), step.peek(3)),
+            ),
+        };
+        let gas = if kind.is_create() { step.gas } else { step.peek(0).saturating_to() };
+
+        Some(Self {
+            kind,
+            from: step.address,
+            to,
+            value,
+            gas: U64::from(gas.min(step.gas)),
+            gas_used: U64::ZERO,
+            input,
+            output: None,
+            error: None,
+            revert_reason: None,
+            calls: Vec::new(),
+        })
+    }
+
+    /// Closes the frame with its last step: its output, its error and the gas it used.
+    fn close(&mut self, last: &Step) {
+        let output = last.output();
+        if let Some(error) = last.error {
+            self.error = Some(error.message().to_string());
+            self.gas_used = self.gas;
+        } else {
+            let gas_left = last.gas.saturating_sub(last.gas_cost);
+            self.gas_used = U64::from(self.gas.to::<u64>().saturating_sub(gas_left));
+            if last.op == REVERT {
+                self.error = Some(REVERT_MESSAGE.to_string());
+                self.revert_reason = RevertReason::decode(&output).reason();
+            }
+        }
+        if self.kind.is_create() && self.error.is_some() {
+            self.to = None;
+        }
+        self.output = (!output.is_empty()).then_some(output);
+    }
+
+    /// Completes a frame which did not open a frame, with the step following its opcode.
+    fn close_leaf(&mut self, step: &Step, next: Option<&Step>) {
+        self.gas_used = U64::from(step.gas_cost.min(self.gas.to::<u64>()));
+        let Some(next) = next else { return };
+
+        let success = !next.peek(0).is_zero();
+        if !success {
+            let precompile = self.to.is_some_and(is_precompile);
+            self.error = Some(if precompile {
+                EvmHalt::PrecompileError.message().to_string()
+            } else {
+                REVERT_MESSAGE.to_string()
+            });
+            self.revert_reason = RevertReason::decode(&next.return_data).reason();
+        }
+        if self.kind.is_create() {
+            self.to = success.then(|| word_address(next.peek(0)));
+        } else if !next.return_data.is_empty() {
+            self.output = Some(next.return_data.clone());
+        }
+    }
+
+    /// Returns the JSON value of the frame, as returned by geth.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).expect("call frame is always serializable")
+    }
+}
+
+/// Builds the call tree of a transaction from its top-level frame, its gas used and its steps.
+pub fn call_tree(
+    mut root: CallFrame,
+    gas_used: u64,
+    steps: &[Step],
+    config: &CallTracerConfig,
+) -> CallFrame {
+    // The open frames, from the top-level frame to the frame of the current step.
+    let mut frames = Vec::new();
+
+    for (index, step) in steps.iter().enumerate() {
+        let next = steps.get(index + 1);
+
+        if step.error.is_none() {
+            if let Some(mut frame) = CallFrame::from_step(step) {
+                match next.filter(|next| next.depth > step.depth) {
+                    Some(first) => {
+                        if frame.kind.is_create() {
+                            frame.to = Some(first.address);
+                        }
+                        frame.gas = U64::from(first.gas);
+                        frames.push(frame);
+                        continue;
+                    }
+                    None => {
+                        frame.close_leaf(step, next);
+                        push_call(&mut root, &mut frames, frame);
+                    }
+                }
+            }
+        }
+
+        let exits = next.is_none_or(|next| next.depth < step.depth);
+        if exits {
+            match frames.pop() {
+                Some(mut frame) => {
+                    frame.close(step);
+                    push_call(&mut root, &mut frames, frame);
+                }
+                None => root.close(step),
+            }
+        }
+    }
+
+    // A created contract is the callee of the first top-level step.
+    if root.kind.is_create() && root.error.is_none() {
+        root.to = steps.iter().find(|step| step.depth == 1).map(|step| step.address);
+    }
+    root.gas_used = U64::from(gas_used);
+    if config.only_top_call {
+        root.calls.clear();
+    }
+    root
+}
+
+/// Traces the calls of a transaction of a block from its stored Cairo execution, with the program
+/// which executed it.
+pub fn trace_calls(
+    db: &Database,
+    program: &Path,
+    block_number: u64,
+    tx_index: u32,
+    config: &CallTracerConfig,
+) -> eyre::Result<CallFrame> {
+    // The steps are decoded first, failing for programs which do not execute the transactions.
+    let (gas_used, steps) = transaction_steps(db, program, block_number, tx_index)?;
+
+    let block = db
+        .block(U256::from(block_number))?
+        .ok_or_else(|| eyre::eyre!("Block {block_number} not found"))?;
+    let (transaction, sender) =
+        block.body.transactions.iter().zip(&block.senders).nth(tx_index as usize).ok_or_else(
+            || eyre::eyre!("Transaction {tx_index} not found in block {block_number}"),
+        )?;
+    Ok(call_tree(CallFrame::from_transaction(transaction, *sender), gas_used, &steps, config))
+}
+
+/// Appends a closed frame to the calls of its parent, the innermost open frame or the root.
+fn push_call(root: &mut CallFrame, frames: &mut [CallFrame], frame: CallFrame) {
+    frames.last_mut().unwrap_or(root).calls.push(frame);
+}
+
+/// Returns the address held by the low 20 bytes of a stack item.
+fn word_address(word: U256) -> Address {
+    Address::from_word(word.into())
+}
+
+/// Returns whether an address is a precompile supported by Kakarot.
+pub(crate) fn is_precompile(address: Address) -> bool {
+    let word = U256::from_be_slice(address.as_slice());
+    (U256::from(1)..=U256::from(10)).contains(&word) || word == U256::from(P256_VERIFY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::address;
+
+    const CALLER: Address = address!("00000000000000000000000000000000000000aa");
+    const CONTRACT: Address = address!("00000000000000000000000000000000000000bb");
+    const CALLEE: Address = address!("00000000000000000000000000000000000000cc");
+
+    fn step(op: u8, depth: u64, address: Address, gas: u64, stack: &[U256]) -> Step {
+        Step { op, depth, address, gas, gas_cost: 3, stack: stack.to_vec(), ..Default::default() }
+    }
+
+    fn root() -> CallFrame {
+        CallFrame {
+            kind: CallKind::Call,
+            from: CALLER,
+            to: Some(CONTRACT),
+            value: Some(U256::ZERO),
+            gas: U64::from(100_000),
+            gas_used: U64::ZERO,
+            input: Bytes::new(),
+            output: None,
+            error: None,
+            revert_reason: None,
+            calls: Vec::new(),
+        }
+    }
+
+    /// Returns the stack of a `CALL`, from its bottom to its top.
+    fn call_stack(to: Address, gas: u64) -> Vec<U256> {
+        let (value, args_offset, args_size, ret_offset, ret_size) =
+            (U256::ZERO, U256::ZERO, U256::from(4), U256::ZERO, U256::ZERO);
+        vec![
+            ret_size,
+            ret_offset,
+            args_size,
+            args_offset,
+            value,
+            U256::from_be_slice(to.as_slice()),
+            U256::from(gas),
+        ]
+    }
+
+    #[test]
+    fn test_call_tree() {
+        let mut call = step(CALL, 1, CONTRACT, 90_000, &call_stack(CALLEE, 50_000));
+        call.memory = Bytes::from(vec![0x12; 32]);
+        let mut precompile = step(STATICCALL, 1, CONTRACT, 60_000, &[]);
+        precompile.stack =
+            vec![U256::ZERO, U256::ZERO, U256::ZERO, U256::ZERO, U256::from(1), U256::from(3_000)];
+        precompile.gas_cost = 3_100;
+        let mut reverted = step(REVERT, 2, CALLEE, 40_000, &[U256::ZERO, U256::ZERO]);
+        reverted.gas_cost = 0;
+
+        let steps = [
+            step(0x60, 1, CONTRACT, 95_000, &[]),
+            call,
+            step(0x60, 2, CALLEE, 50_000, &[]),
+            reverted,
+            step(0x60, 1, CONTRACT, 80_000, &[U256::ZERO]),
+            precompile,
+            step(0x00, 1, CONTRACT, 56_900, &[U256::from(1)]),
+        ];
+
+        let tree = call_tree(root(), 43_100, &steps, &CallTracerConfig::default());
+        assert_eq!(tree.gas_used, U64::from(43_100));
+        assert_eq!(tree.error, None);
+        assert_eq!(tree.calls.len(), 2);
+
+        // The reverted subtree is kept with its error.
+        let call = &tree.calls[0];
+        assert_eq!((call.kind, call.from, call.to), (CallKind::Call, CONTRACT, Some(CALLEE)));
+        assert_eq!(call.input, Bytes::from(vec![0x12; 4]));
+        assert_eq!((call.gas, call.gas_used), (U64::from(50_000), U64::from(10_000)));
+        assert_eq!(call.error.as_deref(), Some(REVERT_MESSAGE));
+
+        // The precompile call is a leaf.
+        let precompile = &tree.calls[1];
+        assert_eq!(precompile.kind, CallKind::StaticCall);
+        assert_eq!(precompile.to, Some(address!("0000000000000000000000000000000000000001")));
+        assert_eq!((precompile.gas, precompile.gas_used), (U64::from(3_000), U64::from(3_000)));
+        assert_eq!(precompile.value, None);
+        assert_eq!(precompile.error, None);
+
+        let top = call_tree(root(), 43_100, &steps, &CallTracerConfig { only_top_call: true });
+        assert!(top.calls.is_empty());
+    }
+
+    #[test]
+    fn test_call_tree_halt() {
+        let mut halted = step(0x01, 1, CONTRACT, 90_000, &[]);
+        halted.error = Some(EvmHalt::StackUnderflow);
+
+        let tree = call_tree(root(), 100_000, &[halted], &CallTracerConfig::default());
+        assert_eq!(tree.error.as_deref(), Some("stack underflow"));
+        assert_eq!(tree.gas, U64::from(100_000));
+
+        let json = tree.to_json();
+        assert_eq!(json["type"], "CALL");
+        assert_eq!(json["gasUsed"], "0x186a0");
+        assert!(json.get("calls").is_none());
+    }
+}
@@ -0,0 +1,453 @@
+//! Re-proving campaigns of historical blocks.
+//!
+//! When the Kakarot program or the prover is upgraded, a [`Campaign`] re-proves a range of blocks
+//! with the new program. The blocks of the campaign go through the same bundle export and proof
+//! import as the deferred proving jobs, see [`crate::deferred`], but the imported proofs are kept
+//! as candidates: the proof of a block is only replaced once its candidate is verified, so that a
+//! block never loses its old proof to a new one which does not verify.
+
+use crate::{
+    db::Database,
+    deferred::{self, ProvingJob},
+    output::ProofMetadata,
+    retry::WorkerSize,
+};
+use reth_tracing::tracing::warn;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+
+/// Represents errors that can occur in a re-proving campaign.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CampaignError {
+    /// Error variant indicating that a campaign with the same name already exists.
+    #[error("Campaign '{0}' already exists")]
+    AlreadyExists(String),
+
+    /// Error variant indicating an unknown campaign.
+    #[error("Unknown campaign '{0}'")]
+    UnknownCampaign(String),
+
+    /// Error variant indicating an empty block range.
+    #[error("Invalid block range {from}..={to}")]
+    InvalidRange {
+        /// The first block of the range.
+        from: u64,
+        /// The last block of the range.
+        to: u64,
+    },
+
+    /// Error variant indicating an unknown campaign block state.
+    #[error("Unknown campaign block state '{0}'")]
+    UnknownState(String),
+
+    /// Error variant indicating a proof of a block outside of the campaign.
+    #[error("Block {number} is not part of campaign '{campaign}'")]
+    UnknownBlock {
+        /// The name of the campaign.
+        campaign: String,
+        /// The number of the block.
+        number: u64,
+    },
+}
+
+/// A re-proving campaign of a range of blocks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Campaign {
+    /// The unique name of the campaign.
+    pub name: String,
+    /// The path of the Kakarot program re-proving the blocks.
+    pub program: PathBuf,
+    /// The first block of the range.
+    pub from_block: u64,
+    /// The last block of the range, included.
+    pub to_block: u64,
+    /// The UNIX timestamp, in seconds, at which the campaign was started.
+    pub created_at: u64,
+}
+
+impl Campaign {
+    /// Creates a new [`Campaign`] started now.
+    pub fn new(
+        name: String,
+        program: PathBuf,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Self, CampaignError> {
+        if from_block > to_block {
+            return Err(CampaignError::InvalidRange { from: from_block, to: to_block });
+        }
+        let created_at =
+            SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+        Ok(Self { name, program, from_block, to_block, created_at })
+    }
+}
+
+/// The state of a block in a [`Campaign`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BlockState {
+    /// The block waits to be exported.
+    Pending,
+    /// The block was exported in a bundle and is being proven.
+    Exported,
+    /// The candidate proof of the block was imported and waits to be verified.
+    Proven,
+    /// The candidate proof was verified and replaced the previous proof of the block.
+    Verified,
+    /// The candidate proof did not verify, the previous proof of the block being kept.
+    Failed,
+}
+
+impl BlockState {
+    /// Returns the name of the state, as stored in the database.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Exported => "exported",
+            Self::Proven => "proven",
+            Self::Verified => "verified",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+impl fmt::Display for BlockState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for BlockState {
+    type Err = CampaignError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(Self::Pending),
+            "exported" => Ok(Self::Exported),
+            "proven" => Ok(Self::Proven),
+            "verified" => Ok(Self::Verified),
+            "failed" => Ok(Self::Failed),
+            _ => Err(CampaignError::UnknownState(s.to_string())),
+        }
+    }
+}
+
+/// The number of blocks of a [`Campaign`] in each state.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CampaignProgress {
+    /// The number of blocks waiting to be exported.
+    pub pending: u64,
+    /// The number of blocks being proven.
+    pub exported: u64,
+    /// The number of blocks whose candidate proof waits to be verified.
+    pub proven: u64,
+    /// The number of blocks whose candidate proof replaced the previous one.
+    pub verified: u64,
+    /// The number of blocks whose candidate proof did not verify.
+    pub failed: u64,
+}
+
+impl CampaignProgress {
+    /// Returns the total number of blocks of the campaign.
+    pub const fn total(&self) -> u64 {
+        self.pending + self.exported + self.proven + self.verified + self.failed
+    }
+
+    /// Returns whether every block of the campaign has a verified proof.
+    pub const fn is_complete(&self) -> bool {
+        self.verified == self.total()
+    }
+
+    /// Adds `count` blocks in the given state.
+    pub(crate) fn add(&mut self, state: BlockState, count: u64) {
+        match state {
+            BlockState::Pending => self.pending += count,
+            BlockState::Exported => self.exported += count,
+            BlockState::Proven => self.proven += count,
+            BlockState::Verified => self.verified += count,
+            BlockState::Failed => self.failed += count,
+        }
+    }
+}
+
+/// A verifier of the candidate proofs of a campaign.
+pub trait ProofVerifier {
+    /// Returns whether the proof verifies against its metadata.
+    fn verify(&self, metadata: &ProofMetadata, proof: &[u8]) -> eyre::Result<bool>;
+}
+
+/// A [`ProofVerifier`] running an external verifier on each proof.
+///
+/// The proof is written to the standard input of the command, the proof verifying when the
+/// command exits successfully.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandVerifier {
+    /// The path of the verifier executable.
+    pub program: PathBuf,
+    /// The arguments of the verifier.
+    pub args: Vec<String>,
+}
+
+impl ProofVerifier for CommandVerifier {
+    fn verify(&self, _metadata: &ProofMetadata, proof: &[u8]) -> eyre::Result<bool> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+        child.stdin.take().expect("stdin is piped").write_all(proof)?;
+        Ok(child.wait()?.success())
+    }
+}
+
+/// Starts a campaign, scheduling all the blocks of its range.
+pub fn start_campaign(db: &Database, campaign: &Campaign) -> eyre::Result<CampaignProgress> {
+    if db.campaign(&campaign.name)?.is_some() {
+        return Err(CampaignError::AlreadyExists(campaign.name.clone()).into());
+    }
+    db.insert_campaign(campaign)?;
+    db.campaign_progress(&campaign.name)
+}
+
+/// Exports at most `limit` pending blocks of a campaign as a bundle in `dir`, marking them as
+/// exported.
+///
+/// The bundle has the format of the deferred proving bundles, see [`deferred::export_bundle`].
+pub fn export_campaign(
+    db: &Database,
+    name: &str,
+    dir: &Path,
+    limit: usize,
+) -> eyre::Result<deferred::BundleManifest> {
+    let campaign = load(db, name)?;
+    let jobs = db
+        .campaign_blocks(name, BlockState::Pending, limit)?
+        .into_iter()
+        .map(|number| (ProvingJob::new(number, campaign.program.clone()), WorkerSize::default()))
+        .collect();
+    let manifest = deferred::write_bundle(db, db, dir, jobs)?;
+
+    // Blocks are only marked once the manifest is written, so that a failed export is retried.
+    for entry in &manifest.entries {
+        db.set_campaign_block_state(name, entry.job.block_number, BlockState::Exported)?;
+    }
+
+    Ok(manifest)
+}
+
+/// Imports the proofs of the manifest in `dir` as the candidate proofs of the campaign blocks.
+///
+/// The previous proofs of the blocks are kept until [`verify_campaign`] promotes the candidates.
+/// Returns the numbers of the blocks whose candidate proof was imported.
+pub fn import_campaign_proofs(db: &Database, name: &str, dir: &Path) -> eyre::Result<Vec<u64>> {
+    load(db, name)?;
+    let manifest = deferred::read_proof_manifest(dir)?;
+
+    let mut imported = Vec::new();
+    for entry in manifest.proofs {
+        let number = entry.metadata.block_number;
+        if db.campaign_block_state(name, number)?.is_none() {
+            let campaign = name.to_string();
+            return Err(CampaignError::UnknownBlock { campaign, number }.into());
+        }
+
+        let proof = fs::read(dir.join(&entry.proof))?;
+        db.insert_candidate_proof(name, &entry.metadata, &proof)?;
+        imported.push(number);
+    }
+
+    Ok(imported)
+}
+
+/// Verifies the candidate proofs of a campaign, replacing the proofs of the blocks whose candidate
+/// verifies.
+///
+/// Blocks whose candidate does not verify are marked as failed and keep their previous proof.
+/// Returns the numbers of the blocks whose proof was replaced.
+pub fn verify_campaign(
+    db: &Database,
+    name: &str,
+    verifier: &dyn ProofVerifier,
+) -> eyre::Result<Vec<u64>> {
+    load(db, name)?;
+
+    let mut promoted = Vec::new();
+    for (metadata, proof) in db.candidate_proofs(name)? {
+        let number = metadata.block_number;
+        if verifier.verify(&metadata, &proof)? {
+            db.promote_candidate_proof(name, &metadata, &proof)?;
+            promoted.push(number);
+        } else {
+            warn!(target: "kkrt::campaign", campaign = name, number, "Proof failed");
+            db.set_campaign_block_state(name, number, BlockState::Failed)?;
+        }
+    }
+
+    Ok(promoted)
+}
+
+/// Schedules the failed blocks of a campaign again, returning their number.
+pub fn retry_failed(db: &Database, name: &str) -> eyre::Result<u64> {
+    load(db, name)?;
+    let failed = db.campaign_blocks(name, BlockState::Failed, usize::MAX)?;
+    for number in &failed {
+        db.set_campaign_block_state(name, *number, BlockState::Pending)?;
+    }
+    Ok(failed.len() as u64)
+}
+
+/// Loads a campaign, failing when it is unknown.
+fn load(db: &Database, name: &str) -> eyre::Result<Campaign> {
+    db.campaign(name)?.ok_or_else(|| CampaignError::UnknownCampaign(name.to_string()).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        deferred::{ProofEntry, ProofManifest, BUNDLE_MANIFEST, PROOF_MANIFEST},
+        input::program_input::{BlockInput, HeaderInput, ProgramInput},
+    };
+    use alloy_consensus::Header;
+    use rusqlite::Connection;
+    use std::collections::BTreeMap;
+
+    /// A verifier accepting the proofs with the given content.
+    struct AcceptVerifier(&'static [u8]);
+
+    impl ProofVerifier for AcceptVerifier {
+        fn verify(&self, _metadata: &ProofMetadata, proof: &[u8]) -> eyre::Result<bool> {
+            Ok(proof == self.0)
+        }
+    }
+
+    fn setup_db() -> Database {
+        Database::new(Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    fn testdata_program() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/keccak_add_uint256.json")
+    }
+
+    fn write_proofs(dir: &Path, proofs: &[(u64, &[u8])]) {
+        let proofs = proofs
+            .iter()
+            .map(|(number, proof)| {
+                let file = format!("{number}.proof");
+                fs::write(dir.join(&file), proof).unwrap();
+                let metadata = ProofMetadata { block_number: *number, ..Default::default() };
+                ProofEntry { metadata, proof: file, fact: None }
+            })
+            .collect();
+        let manifest = serde_json::to_vec(&ProofManifest { proofs, failures: vec![] }).unwrap();
+        fs::write(dir.join(PROOF_MANIFEST), manifest).unwrap();
+    }
+
+    #[test]
+    fn test_campaign_invalid_range() {
+        assert_eq!(
+            Campaign::new("upgrade".to_string(), testdata_program(), 5, 4),
+            Err(CampaignError::InvalidRange { from: 5, to: 4 })
+        );
+    }
+
+    #[test]
+    fn test_start_campaign_twice() {
+        let db = setup_db();
+        let campaign = Campaign::new("upgrade".to_string(), testdata_program(), 1, 3).unwrap();
+
+        let progress = start_campaign(&db, &campaign).unwrap();
+        assert_eq!(progress, CampaignProgress { pending: 3, ..Default::default() });
+
+        let err = start_campaign(&db, &campaign).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&CampaignError::AlreadyExists("upgrade".to_string())));
+    }
+
+    #[test]
+    fn test_export_campaign() {
+        let db = setup_db();
+        let dir = tempfile::tempdir().unwrap();
+        let program = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../cairo/programs/os.json");
+        let campaign = Campaign::new("upgrade".to_string(), program.clone(), 1, 2).unwrap();
+        start_campaign(&db, &campaign).unwrap();
+        let header = Header {
+            number: 1,
+            gas_limit: 30_000_000,
+            base_fee_per_gas: Some(7),
+            ..Default::default()
+        };
+        let input = ProgramInput {
+            block: BlockInput {
+                block_header: HeaderInput::from(&header),
+                transactions: Vec::new(),
+            },
+            state: BTreeMap::new(),
+            chain_id: 1,
+            system_calls: Vec::new(),
+            block_hashes: BTreeMap::new(),
+        };
+        db.insert_program_input(1, &input).unwrap();
+
+        let manifest = export_campaign(&db, "upgrade", dir.path(), 1).unwrap();
+
+        assert_eq!(manifest.entries.len(), 1);
+        assert_eq!(manifest.entries[0].job.program, program);
+        assert!(dir.path().join(BUNDLE_MANIFEST).exists());
+        assert_eq!(
+            db.campaign_progress("upgrade").unwrap(),
+            CampaignProgress { pending: 1, exported: 1, ..Default::default() }
+        );
+    }
+
+    #[test]
+    fn test_old_proofs_kept_until_verified() {
+        let db = setup_db();
+        let dir = tempfile::tempdir().unwrap();
+        let campaign = Campaign::new("upgrade".to_string(), testdata_program(), 1, 2).unwrap();
+        start_campaign(&db, &campaign).unwrap();
+        for number in 1..=2 {
+            let metadata = ProofMetadata { block_number: number, ..Default::default() };
+            db.insert_proof(&metadata, b"old").unwrap();
+        }
+
+        write_proofs(dir.path(), &[(1, b"good"), (2, b"bad")]);
+        assert_eq!(import_campaign_proofs(&db, "upgrade", dir.path()).unwrap(), vec![1, 2]);
+        assert_eq!(db.proof(1).unwrap().map(|(_, proof)| proof), Some(b"old".to_vec()));
+
+        let promoted = verify_campaign(&db, "upgrade", &AcceptVerifier(b"good")).unwrap();
+
+        assert_eq!(promoted, vec![1]);
+        assert_eq!(db.proof(1).unwrap().map(|(_, proof)| proof), Some(b"good".to_vec()));
+        assert_eq!(db.proof(2).unwrap().map(|(_, proof)| proof), Some(b"old".to_vec()));
+        assert_eq!(
+            db.campaign_progress("upgrade").unwrap(),
+            CampaignProgress { verified: 1, failed: 1, ..Default::default() }
+        );
+
+        assert_eq!(retry_failed(&db, "upgrade").unwrap(), 1);
+        assert_eq!(db.campaign_block_state("upgrade", 2).unwrap(), Some(BlockState::Pending));
+    }
+
+    #[test]
+    fn test_import_campaign_proofs_unknown_block() {
+        let db = setup_db();
+        let dir = tempfile::tempdir().unwrap();
+        let campaign = Campaign::new("upgrade".to_string(), testdata_program(), 1, 2).unwrap();
+        start_campaign(&db, &campaign).unwrap();
+        write_proofs(dir.path(), &[(7, b"proof")]);
+
+        let err = import_campaign_proofs(&db, "upgrade", dir.path()).unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref(),
+            Some(&CampaignError::UnknownBlock { campaign: "upgrade".to_string(), number: 7 })
+        );
+    }
+}
@@ -0,0 +1,319 @@
+//! Chaining of the proofs of consecutive blocks by their state commitments.
+//!
+//! Each proven block is linked to the previous one: the parent hash of its output must equal the
+//! hash of the previous block, and the proof must commit to the recorded output. The links form a
+//! hash chain, each link hashing the previous one with the output root of its block, so that a
+//! consumer trusting the head of the chain can check the continuity of the recorded outputs from
+//! its anchor without checking every block.
+//!
+//! The hashes and roots of the outputs echo the headers given as input to the program and are not
+//! checked by its execution yet, see [`ProgramOutput`]: the chain links the headers the blocks were
+//! proven with, it does not prove them.
+
+use crate::{
+    db::Database,
+    output::ProgramOutput,
+    retry::{self, RetryPolicy},
+    ssz::Ssz,
+    store::KethStore,
+};
+use alloy_primitives::{keccak256, B256};
+use reth_tracing::tracing::warn;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Represents errors that can occur when chaining the proofs.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ChainError {
+    /// Error variant indicating that the parent of a block is not the previous block.
+    #[error("Parent hash {actual} of block {block_number} is not the previous hash {expected}")]
+    ParentMismatch {
+        /// The number of the block.
+        block_number: u64,
+        /// The hash of the previous block.
+        expected: B256,
+        /// The parent hash of the block.
+        actual: B256,
+    },
+
+    /// Error variant indicating that the proof of a block does not commit to its recorded output.
+    #[error("Proof of block {block_number} commits to output {proven}, recorded {recorded}")]
+    OutputMismatch {
+        /// The number of the block.
+        block_number: u64,
+        /// The output root of the proof.
+        proven: B256,
+        /// The root of the recorded output.
+        recorded: B256,
+    },
+
+    /// Error variant indicating that a block does not follow the head of the chain.
+    #[error("Block {actual} does not follow the chain head, expected block {expected}")]
+    NotNext {
+        /// The number of the block following the head.
+        expected: u64,
+        /// The number of the block.
+        actual: u64,
+    },
+}
+
+/// A link of the chain of the proven blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainLink {
+    /// The number of the block.
+    pub block_number: u64,
+    /// The hash of the block.
+    pub block_hash: B256,
+    /// The state root after the execution of the block, as echoed by its output.
+    pub post_state_root: B256,
+    /// The hash tree root of the [`ProgramOutput`] of the block, committed to by its proof.
+    pub output_root: B256,
+    /// The number of the first block of the chain.
+    pub anchor: u64,
+    /// The hash of the previous link, zero for the anchor.
+    pub previous: B256,
+    /// The hash of the link, i.e. `keccak256(previous || output_root)`.
+    pub hash: B256,
+}
+
+impl ChainLink {
+    /// Returns the anchor link of a chain starting at the block of the given output.
+    pub fn anchor(output: &ProgramOutput) -> Self {
+        Self::link(output, output.block_number, B256::ZERO)
+    }
+
+    /// Returns the link of the block of the given output, following this link.
+    pub fn next(&self, output: &ProgramOutput) -> Result<Self, ChainError> {
+        let block_number = output.block_number;
+        if block_number != self.block_number + 1 {
+            return Err(ChainError::NotNext {
+                expected: self.block_number + 1,
+                actual: block_number,
+            });
+        }
+        if output.parent_hash != self.block_hash {
+            return Err(ChainError::ParentMismatch {
+                block_number,
+                expected: self.block_hash,
+                actual: output.parent_hash,
+            });
+        }
+        Ok(Self::link(output, self.anchor, self.hash))
+    }
+
+    /// Returns whether the hash of the link matches its content.
+    pub fn is_valid(&self) -> bool {
+        self.hash == link_hash(self.previous, self.output_root)
+    }
+
+    fn link(output: &ProgramOutput, anchor: u64, previous: B256) -> Self {
+        let output_root = output.hash_tree_root();
+        Self {
+            block_number: output.block_number,
+            block_hash: output.block_hash,
+            post_state_root: output.post_state_root,
+            output_root,
+            anchor,
+            previous,
+            hash: link_hash(previous, output_root),
+        }
+    }
+}
+
+impl ChainError {
+    /// Returns the number of the block breaking the continuity of the chain.
+    pub const fn block_number(&self) -> u64 {
+        match self {
+            Self::ParentMismatch { block_number, .. } |
+            Self::OutputMismatch { block_number, .. } => *block_number,
+            Self::NotNext { actual, .. } => *actual,
+        }
+    }
+}
+
+/// The result of the extension of the chain of the proven blocks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChainExtension {
+    /// The new links of the chain, in ascending block order.
+    pub links: Vec<ChainLink>,
+    /// The error of the block whose proof was rejected, if any.
+    pub rejected: Option<ChainError>,
+}
+
+/// Extends the chain with the consecutive proven blocks following its head, returning the new
+/// links and the rejected block, if any.
+///
+/// Without head, the chain is anchored at the first proven block. The extension stops at the
+/// first block which is not proven yet or whose output is not recorded, and at the first block
+/// breaking the continuity of the chain. The proof of the latter is deleted, so that the block can
+/// be proven again and the chain extended by a later import.
+pub fn extend(db: &(impl KethStore + ?Sized)) -> eyre::Result<ChainExtension> {
+    let mut head = db.chain_head()?;
+    let mut number = match head {
+        Some(head) => head.block_number + 1,
+        None => match db.proven_blocks(0, i64::MAX as u64)?.first() {
+            Some(number) => *number,
+            None => return Ok(ChainExtension::default()),
+        },
+    };
+
+    let mut extension = ChainExtension::default();
+    while let (Some((metadata, _)), Some(output)) = (db.proof(number)?, db.program_output(number)?)
+    {
+        let recorded = output.hash_tree_root();
+        let link = if metadata.output_root == recorded {
+            match head {
+                Some(head) => head.next(&output),
+                None => Ok(ChainLink::anchor(&output)),
+            }
+        } else {
+            Err(ChainError::OutputMismatch {
+                block_number: number,
+                proven: metadata.output_root,
+                recorded,
+            })
+        };
+
+        let link = match link {
+            Ok(link) => link,
+            Err(err) => {
+                warn!(target: "kkrt::chain", number, %err, "Rejected proof");
+                db.delete_proof(number)?;
+                extension.rejected = Some(err);
+                break;
+            }
+        };
+        db.insert_chain_link(&link)?;
+        extension.links.push(link);
+        head = Some(link);
+        number += 1;
+    }
+
+    Ok(extension)
+}
+
+/// Extends the chain of the proven blocks of a store, queuing the block whose proof was rejected
+/// again in the database according to the retry policy.
+pub fn extend_and_requeue(
+    db: &Database,
+    store: &dyn KethStore,
+    policy: &RetryPolicy,
+) -> eyre::Result<ChainExtension> {
+    let extension = extend(store)?;
+    if let Some(err) = &extension.rejected {
+        retry::record_failure(db, policy, err.block_number(), &err.to_string())?;
+    }
+    Ok(extension)
+}
+
+/// Returns the hash of a link from the hash of the previous one and the output root of its block.
+fn link_hash(previous: B256, output_root: B256) -> B256 {
+    let mut preimage = [0; 64];
+    preimage[..32].copy_from_slice(previous.as_slice());
+    preimage[32..].copy_from_slice(output_root.as_slice());
+    keccak256(preimage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        deferred::{JobState, ProvingJob},
+        output::ProofMetadata,
+    };
+    use rusqlite::Connection;
+    use std::path::PathBuf;
+
+    fn output(number: u64) -> ProgramOutput {
+        ProgramOutput {
+            block_number: number,
+            block_hash: B256::with_last_byte(number as u8),
+            parent_hash: B256::with_last_byte(number as u8 - 1),
+            post_state_root: B256::repeat_byte(number as u8),
+            ..Default::default()
+        }
+    }
+
+    fn prove(db: &Database, output: &ProgramOutput) {
+        let metadata = ProofMetadata {
+            block_number: output.block_number,
+            output_root: output.hash_tree_root(),
+            ..Default::default()
+        };
+        db.insert_program_output(output).unwrap();
+        db.insert_proof(&metadata, b"proof").unwrap();
+    }
+
+    #[test]
+    fn test_chain_links() {
+        let anchor = ChainLink::anchor(&output(1));
+        let next = anchor.next(&output(2)).unwrap();
+
+        assert_eq!(next.anchor, 1);
+        assert_eq!(next.previous, anchor.hash);
+        assert!(anchor.is_valid() && next.is_valid());
+
+        let mut fork = output(2);
+        fork.parent_hash = B256::with_last_byte(42);
+        assert_eq!(
+            anchor.next(&fork),
+            Err(ChainError::ParentMismatch {
+                block_number: 2,
+                expected: output(1).block_hash,
+                actual: B256::with_last_byte(42)
+            })
+        );
+        assert_eq!(anchor.next(&output(3)), Err(ChainError::NotNext { expected: 2, actual: 3 }));
+    }
+
+    #[test]
+    fn test_extend_chain() {
+        let db = Database::new(Connection::open_in_memory().unwrap()).unwrap();
+        prove(&db, &output(1));
+        prove(&db, &output(2));
+        prove(&db, &output(4));
+
+        let links = extend(&db).unwrap().links;
+        assert_eq!(links.iter().map(|link| link.block_number).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(db.chain_head().unwrap(), Some(links[1]));
+
+        // The chain is extended once the gap is proven.
+        prove(&db, &output(3));
+        assert_eq!(extend(&db).unwrap().links.len(), 2);
+        assert_eq!(db.chain_head().unwrap().map(|head| head.block_number), Some(4));
+    }
+
+    #[test]
+    fn test_extend_chain_output_mismatch() {
+        let db = Database::new(Connection::open_in_memory().unwrap()).unwrap();
+        db.enqueue_proving_job(&ProvingJob::new(2, PathBuf::from("os.json"))).unwrap();
+        db.set_proving_job_state(2, JobState::Proven).unwrap();
+        prove(&db, &output(1));
+        db.insert_program_output(&output(2)).unwrap();
+        db.insert_proof(&ProofMetadata { block_number: 2, ..Default::default() }, b"").unwrap();
+        prove(&db, &output(3));
+
+        let extension = extend_and_requeue(&db, &db, &RetryPolicy::default()).unwrap();
+        assert_eq!(extension.links.len(), 1);
+        assert_eq!(
+            extension.rejected,
+            Some(ChainError::OutputMismatch {
+                block_number: 2,
+                proven: B256::ZERO,
+                recorded: output(2).hash_tree_root()
+            })
+        );
+        // The links preceding the rejected block are kept, its proof is deleted and its job queued
+        // again.
+        assert_eq!(db.chain_head().unwrap().map(|head| head.block_number), Some(1));
+        assert_eq!(db.proof(2).unwrap(), None);
+        assert_eq!(db.proving_job_state(2).unwrap(), Some(JobState::Queued));
+
+        // The chain is extended past the block once it is proven again.
+        prove(&db, &output(2));
+        let extension = extend(&db).unwrap();
+        assert_eq!(extension.links.len(), 2);
+        assert_eq!(extension.rejected, None);
+        assert_eq!(db.chain_head().unwrap().map(|head| head.block_number), Some(3));
+    }
+}
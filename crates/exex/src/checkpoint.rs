@@ -0,0 +1,360 @@
+//! Checkpoints of the Cairo VM state at the transaction boundaries of a block.
+//!
+//! A [`VmCheckpoint`] holds the [`VmSnapshot`] of a runner at the step entering the execution of a
+//! transaction. Resuming from a checkpoint restores a freshly initialized runner to that state and
+//! runs it to the end, so that debugging a failure in the last transactions of a block does not
+//! require re-running the previous ones.
+//!
+//! The steps entering the transactions are located in the stored trace of the block, and the
+//! checkpointing run is a proof-mode run without trace, so that its steps match the trace. Both the
+//! checkpointing and the resumed runs are fed the program input stored with the block. The resumed
+//! runs are not traced either, the resources of their steps before the checkpoint being lost as
+//! described in [`crate::runner`].
+
+use crate::{
+    attribution::{invocations, AttributionError, FrameSpec},
+    compression::{ArtifactKind, Codec},
+    db::Database,
+    hints::KakarotHintProcessor,
+    input::program_input::ProgramInput,
+    limits::{Diagnostics, ExecutionLimits, LimitedRun},
+    runner::{KakarotRunner, VmSnapshot},
+    serde::cache::ProgramLayoutCache,
+};
+use cairo_vm::{
+    cairo_run::CairoRunConfig,
+    hint_processor::hint_processor_definition::HintProcessor,
+    types::{layout_name::LayoutName, program::Program},
+    vm::{errors::cairo_run_errors::CairoRunError, trace::trace_entry::RelocatedTraceEntry},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use thiserror::Error;
+
+/// The version of the checkpoint format, bumped on breaking changes.
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+/// Represents errors that can occur when checkpointing or resuming an execution.
+#[derive(Debug, Error)]
+pub enum CheckpointError {
+    /// Error variant indicating a failure of the Cairo run.
+    #[error(transparent)]
+    Run(#[from] CairoRunError),
+
+    /// Error variant indicating boundaries which are not in increasing step order.
+    #[error("Checkpoint at step {step} before the current step {current}")]
+    UnorderedBoundary {
+        /// The step of the boundary.
+        step: usize,
+        /// The step reached by the run.
+        current: usize,
+    },
+
+    /// Error variant indicating a failure of the checkpoint handler.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// Error variant indicating a block stored without its program input.
+    #[error("No program input for block {0}")]
+    MissingInput(u64),
+
+    /// Error variant indicating a transaction whose execution is not found in the stored trace.
+    #[error("No execution of transaction {0} found in the trace")]
+    MissingTransaction(u32),
+}
+
+/// The state of a runner at the step entering a transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VmCheckpoint {
+    /// The version of the checkpoint format.
+    pub version: u32,
+    /// The number of the block.
+    pub block_number: u64,
+    /// The index of the transaction entered.
+    pub tx_index: u32,
+    /// The number of steps run before the checkpoint.
+    pub steps: usize,
+    /// The state of the VM.
+    #[serde(flatten)]
+    pub snapshot: VmSnapshot,
+}
+
+impl VmCheckpoint {
+    /// Captures the state of a runner.
+    pub fn capture(
+        runner: &mut KakarotRunner,
+        block_number: u64,
+        tx_index: u32,
+        steps: usize,
+    ) -> Self {
+        Self {
+            version: CHECKPOINT_VERSION,
+            block_number,
+            tx_index,
+            steps,
+            snapshot: runner.snapshot(),
+        }
+    }
+
+    /// Writes the compressed checkpoint.
+    pub fn write_compressed<W: Write>(&self, writer: W, codec: &Codec) -> io::Result<()> {
+        let mut encoder = codec.encoder(ArtifactKind::Dump, writer)?;
+        serde_json::to_writer(&mut encoder, self)?;
+        encoder.finish()?.flush()
+    }
+
+    /// Reads a compressed checkpoint, decompressing it on the fly.
+    pub fn read_compressed<R: BufRead>(reader: R, codec: &Codec) -> io::Result<Self> {
+        let checkpoint: Self = serde_json::from_reader(codec.decoder(ArtifactKind::Dump, reader)?)?;
+        if checkpoint.version != CHECKPOINT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported checkpoint version {}", checkpoint.version),
+            ));
+        }
+        Ok(checkpoint)
+    }
+
+    /// Saves the compressed checkpoint to a file.
+    pub fn save(&self, path: impl AsRef<Path>, codec: &Codec) -> io::Result<()> {
+        self.write_compressed(BufWriter::new(File::create(path)?), codec)
+    }
+
+    /// Loads a compressed checkpoint from a file.
+    pub fn load(path: impl AsRef<Path>, codec: &Codec) -> io::Result<Self> {
+        Self::read_compressed(BufReader::new(File::open(path)?), codec)
+    }
+
+    /// Returns the name of the file of the checkpoint, e.g. `block-100-tx-180.ckpt.zst`.
+    pub fn file_name(&self) -> String {
+        format!("block-{}-tx-{}.ckpt.zst", self.block_number, self.tx_index)
+    }
+}
+
+/// Returns the configuration of the checkpointing and resumed runs: a proof-mode run, whose steps
+/// match the stored traces, without trace nor relocated memory.
+pub fn checkpoint_config() -> CairoRunConfig<'static> {
+    CairoRunConfig {
+        layout: LayoutName::all_cairo,
+        trace_enabled: false,
+        relocate_mem: false,
+        proof_mode: true,
+        ..Default::default()
+    }
+}
+
+/// Returns the steps entering the execution of each transaction in the relocated trace of a
+/// block, by transaction index.
+pub fn transaction_boundaries(
+    program: &Program,
+    trace: &[RelocatedTraceEntry],
+) -> Result<Vec<(u32, usize)>, AttributionError> {
+    let spec = FrameSpec::execute(program)?;
+    Ok(invocations(&spec, trace)
+        .into_iter()
+        .enumerate()
+        .map(|(tx_index, (start, _))| (tx_index as u32, start))
+        .collect())
+}
+
+/// Runs a program to its end with [`checkpoint_config`], capturing a checkpoint at each of the
+/// given `(transaction index, step)` boundaries, in increasing step order.
+pub fn run_with_checkpoints(
+    program: &[u8],
+    hint_processor: &mut dyn HintProcessor,
+    block_number: u64,
+    boundaries: &[(u32, usize)],
+    on_checkpoint: &mut dyn FnMut(VmCheckpoint) -> Result<(), CheckpointError>,
+) -> Result<LimitedRun, CheckpointError> {
+    let mut runner = KakarotRunner::new(program, checkpoint_config())?;
+
+    let mut steps = 0;
+    for (tx_index, step) in boundaries {
+        let chunk = step
+            .checked_sub(steps)
+            .ok_or(CheckpointError::UnorderedBoundary { step: *step, current: steps })?;
+        if chunk > 0 {
+            runner.run_for_steps(chunk, hint_processor).map_err(CairoRunError::from)?;
+        }
+        steps = *step;
+        on_checkpoint(VmCheckpoint::capture(&mut runner, block_number, *tx_index, steps))?;
+    }
+
+    Ok(runner.run_to_end(hint_processor, &ExecutionLimits::default(), &mut || false)?)
+}
+
+/// Resumes the execution of a program from a checkpoint, the limits applying to the steps run
+/// after the checkpoint.
+pub fn resume(
+    program: &[u8],
+    checkpoint: &VmCheckpoint,
+    hint_processor: &mut dyn HintProcessor,
+    limits: &ExecutionLimits,
+) -> Result<LimitedRun, CheckpointError> {
+    let mut runner = KakarotRunner::new(program, checkpoint_config())?;
+    runner.restore(&checkpoint.snapshot)?;
+    Ok(runner.run_to_end(hint_processor, limits, &mut || false)?)
+}
+
+/// Returns the hint processor feeding the program input stored with a block to a program.
+fn block_hint_processor(program: &Program, input: Arc<ProgramInput>) -> KakarotHintProcessor {
+    let layouts = Arc::new(ProgramLayoutCache::new(program));
+    KakarotHintProcessor::default().with_program_input(layouts, input)
+}
+
+/// Re-runs the program of a stored block on its program input, saving a checkpoint at the start of
+/// the given transactions, of all of them when empty, in `dir`. Returns the paths of the
+/// checkpoints.
+///
+/// Fails if the execution of a transaction of the block is not found in its stored trace.
+pub fn checkpoint_block(
+    db: &Database,
+    program: &Path,
+    block_number: u64,
+    transactions: &[u32],
+    dir: &Path,
+) -> eyre::Result<Vec<PathBuf>> {
+    let (trace, _) = db
+        .execution_trace(block_number)?
+        .ok_or_else(|| eyre::eyre!("No trace found for block {block_number}"))?;
+    let input = Arc::new(
+        db.program_input(block_number)?.ok_or(CheckpointError::MissingInput(block_number))?,
+    );
+    let bytes = fs::read(program)?;
+    let parsed = Program::from_bytes(&bytes, Some("main"))?;
+    let boundaries = transaction_boundaries(&parsed, &trace)?;
+
+    // All the transactions of the block are checkpointed when none is given.
+    let requested: Vec<u32> = if transactions.is_empty() {
+        (0..input.block.transactions.len() as u32).collect()
+    } else {
+        transactions.to_vec()
+    };
+    if let Some(missing) =
+        requested.iter().find(|tx_index| !boundaries.iter().any(|(index, _)| index == *tx_index))
+    {
+        return Err(CheckpointError::MissingTransaction(*missing).into());
+    }
+    let boundaries: Vec<_> =
+        boundaries.into_iter().filter(|(tx_index, _)| requested.contains(tx_index)).collect();
+
+    fs::create_dir_all(dir)?;
+    let codec = Codec::default();
+    let mut hint_processor = block_hint_processor(&parsed, input);
+    let mut paths = Vec::new();
+    run_with_checkpoints(
+        &bytes,
+        &mut hint_processor,
+        block_number,
+        &boundaries,
+        &mut |checkpoint| {
+            let path = dir.join(checkpoint.file_name());
+            checkpoint.save(&path, &codec)?;
+            paths.push(path);
+            Ok(())
+        },
+    )?;
+    Ok(paths)
+}
+
+/// Resumes the execution of a program from a saved checkpoint, on the program input stored with
+/// its block, returning the diagnostics of the execution when it is interrupted by its limits.
+pub fn resume_checkpoint(
+    db: &Database,
+    program: &Path,
+    checkpoint: &Path,
+    limits: &ExecutionLimits,
+) -> eyre::Result<Option<Diagnostics>> {
+    let checkpoint = VmCheckpoint::load(checkpoint, &Codec::default())?;
+    let block_number = checkpoint.block_number;
+    let input =
+        db.program_input(block_number)?.ok_or(CheckpointError::MissingInput(block_number))?;
+    let bytes = fs::read(program)?;
+    let parsed = Program::from_bytes(&bytes, Some("main"))?;
+    let mut hint_processor = block_hint_processor(&parsed, Arc::new(input));
+    match resume(&bytes, &checkpoint, &mut hint_processor, limits)? {
+        LimitedRun::Interrupted(partial) => Ok(Some(partial.diagnostics)),
+        LimitedRun::Completed(_) | LimitedRun::Preempted => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tuning::RunProfile;
+    use cairo_vm::vm::runners::cairo_runner::CairoRunner;
+    use rusqlite::Connection;
+
+    const PROGRAM: &[u8] = include_bytes!("../testdata/keccak_add_uint256.json");
+
+    fn completed(run: LimitedRun) -> CairoRunner {
+        let LimitedRun::Completed(runner) = run else { panic!("Expected a completed run") };
+        runner
+    }
+
+    #[test]
+    fn test_resume_from_checkpoint() {
+        let mut hint_processor = KakarotHintProcessor::default();
+        let mut checkpoints = Vec::new();
+        let full = run_with_checkpoints(
+            PROGRAM,
+            &mut hint_processor,
+            1,
+            &[(0, 0), (1, 10), (2, 50)],
+            &mut |checkpoint| {
+                checkpoints.push(checkpoint);
+                Ok(())
+            },
+        )
+        .unwrap();
+        let full = completed(full);
+
+        assert_eq!(checkpoints.len(), 3);
+        assert_eq!(checkpoints[2].steps, 50);
+        assert_eq!(checkpoints[2].file_name(), "block-1-tx-2.ckpt.zst");
+
+        // The run resumed from any checkpoint ends in the same state as the full run.
+        for checkpoint in &checkpoints {
+            let mut hint_processor = KakarotHintProcessor::default();
+            let resumed = resume(PROGRAM, checkpoint, &mut hint_processor, &Default::default());
+            let resumed = completed(resumed.unwrap());
+            assert_eq!(RunProfile::from_runner(&resumed), RunProfile::from_runner(&full));
+            assert_eq!(resumed.vm.get_pc(), full.vm.get_pc());
+        }
+
+        let mut hint_processor = KakarotHintProcessor::default();
+        let result =
+            run_with_checkpoints(PROGRAM, &mut hint_processor, 1, &[(0, 10), (1, 5)], &mut |_| {
+                Ok(())
+            });
+        assert!(matches!(result, Err(CheckpointError::UnorderedBoundary { step: 5, current: 10 })));
+    }
+
+    #[test]
+    fn test_save_load_checkpoint() {
+        let mut hint_processor = KakarotHintProcessor::default();
+        let mut checkpoints = Vec::new();
+        run_with_checkpoints(PROGRAM, &mut hint_processor, 7, &[(3, 20)], &mut |checkpoint| {
+            checkpoints.push(checkpoint);
+            Ok(())
+        })
+        .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(checkpoints[0].file_name());
+        checkpoints[0].save(&path, &Codec::default()).unwrap();
+        assert_eq!(VmCheckpoint::load(&path, &Codec::default()).unwrap(), checkpoints[0]);
+
+        // A checkpoint is only resumed on the program input of its block.
+        let db = Database::new(Connection::open_in_memory().unwrap()).unwrap();
+        let program = dir.path().join("program.json");
+        fs::write(&program, PROGRAM).unwrap();
+        let err = resume_checkpoint(&db, &program, &path, &Default::default()).unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(CheckpointError::MissingInput(7))));
+    }
+}
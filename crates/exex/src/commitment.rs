@@ -0,0 +1,435 @@
+//! Merkle commitments over the relocated trace and memory of the executions.
+//!
+//! The elements of a segment are split in chunks of a fixed number of elements, whose hashes are
+//! the leaves of a binary Merkle tree. The commitment of the segment is the root of the tree mixed
+//! with the number of elements, so that a range of elements can be opened against it with only
+//! the covering chunks and the sibling hashes of their subtree, e.g. to audit the content of a
+//! memory cell without shipping the whole dump.
+//!
+//! Leaves and nodes are hashed with keccak256, with distinct prefixes so that a leaf cannot be
+//! passed off as a node.
+
+use crate::db::Database;
+use alloy_primitives::{keccak256, B256};
+use cairo_vm::{vm::trace::trace_entry::RelocatedTraceEntry, Felt252};
+use serde::{Deserialize, Serialize};
+use std::{fmt, ops::Range, str::FromStr};
+use thiserror::Error;
+
+/// The default number of elements of a chunk.
+pub const DEFAULT_CHUNK_SIZE: usize = 1024;
+
+/// The prefix of the hashes of the leaves.
+const LEAF_PREFIX: u8 = 0;
+
+/// The prefix of the hashes of the inner nodes.
+const NODE_PREFIX: u8 = 1;
+
+/// Represents errors that can occur when committing to or opening a segment.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CommitmentError {
+    /// Error variant indicating a chunk size of zero.
+    #[error("The chunk size must be positive")]
+    InvalidChunkSize,
+
+    /// Error variant indicating that an opened range is empty or out of the segment.
+    #[error("Invalid range {start}..{end} for a segment of {length} elements")]
+    InvalidRange {
+        /// The first element of the range.
+        start: usize,
+        /// The end of the range.
+        end: usize,
+        /// The number of elements of the segment.
+        length: usize,
+    },
+
+    /// Error variant indicating an unknown segment kind.
+    #[error("Unknown segment kind '{0}'")]
+    UnknownKind(String),
+}
+
+/// The kind of a committed segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SegmentKind {
+    /// The relocated execution trace.
+    Trace,
+    /// The relocated memory.
+    Memory,
+}
+
+impl SegmentKind {
+    /// Returns the name of the kind, as stored in the database.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Trace => "trace",
+            Self::Memory => "memory",
+        }
+    }
+}
+
+impl fmt::Display for SegmentKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SegmentKind {
+    type Err = CommitmentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "trace" => Ok(Self::Trace),
+            "memory" => Ok(Self::Memory),
+            _ => Err(CommitmentError::UnknownKind(s.to_string())),
+        }
+    }
+}
+
+/// An element of a committed segment.
+pub trait Element {
+    /// Appends the bytes of the element hashed in the leaf of its chunk.
+    fn append_bytes(&self, out: &mut Vec<u8>);
+}
+
+impl Element for Felt252 {
+    fn append_bytes(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_bytes_be());
+    }
+}
+
+impl Element for RelocatedTraceEntry {
+    fn append_bytes(&self, out: &mut Vec<u8>) {
+        for register in [self.pc, self.ap, self.fp] {
+            out.extend_from_slice(&(register as u64).to_be_bytes());
+        }
+    }
+}
+
+/// The commitment to a segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SegmentCommitment {
+    /// The kind of the segment.
+    pub kind: SegmentKind,
+    /// The number of elements of each chunk.
+    pub chunk_size: usize,
+    /// The number of elements of the segment.
+    pub length: usize,
+    /// The root of the Merkle tree of the chunks, mixed with the number of elements.
+    pub root: B256,
+}
+
+impl SegmentCommitment {
+    /// Returns the number of chunks of the segment.
+    pub const fn chunks(&self) -> usize {
+        self.length.div_ceil(self.chunk_size)
+    }
+}
+
+/// A binary Merkle tree, whose leaves are padded with zero hashes to a power of two.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleTree {
+    /// The levels of the tree, from the leaves to the root.
+    levels: Vec<Vec<B256>>,
+}
+
+impl MerkleTree {
+    /// Builds the tree of the given leaves.
+    pub fn new(mut leaves: Vec<B256>) -> Self {
+        leaves.resize(leaves.len().next_power_of_two(), B256::ZERO);
+
+        let mut levels = vec![leaves];
+        while levels.last().is_some_and(|level| level.len() > 1) {
+            let level = levels.last().expect("non empty levels");
+            let parents = level.chunks_exact(2).map(|pair| hash_node(&pair[0], &pair[1])).collect();
+            levels.push(parents);
+        }
+        Self { levels }
+    }
+
+    /// Returns the root of the tree.
+    pub fn root(&self) -> B256 {
+        self.levels.last().and_then(|level| level.first()).copied().unwrap_or_default()
+    }
+
+    /// Returns the sibling hashes authenticating the contiguous range of leaves, ordered by level
+    /// and, within a level, left before right.
+    pub fn prove(&self, leaves: Range<usize>) -> Vec<B256> {
+        let (mut low, mut high) = (leaves.start, leaves.end - 1);
+        let mut proof = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            if low % 2 == 1 {
+                proof.push(level[low - 1]);
+            }
+            if high % 2 == 0 {
+                proof.push(level[high + 1]);
+            }
+            low /= 2;
+            high /= 2;
+        }
+        proof
+    }
+}
+
+/// Returns the root of a tree of `width` leaves from a contiguous range of its leaves starting at
+/// `first`, and the sibling hashes returned by [`MerkleTree::prove`], `None` if the proof does not
+/// match the range.
+pub fn range_root(width: usize, first: usize, leaves: &[B256], proof: &[B256]) -> Option<B256> {
+    if leaves.is_empty() || first + leaves.len() > width {
+        return None;
+    }
+
+    let mut proof = proof.iter();
+    let mut nodes = leaves.to_vec();
+    let (mut low, mut width) = (first, width);
+    while width > 1 {
+        let high = low + nodes.len() - 1;
+        if low % 2 == 1 {
+            nodes.insert(0, *proof.next()?);
+        }
+        if high % 2 == 0 {
+            nodes.push(*proof.next()?);
+        }
+        nodes = nodes.chunks_exact(2).map(|pair| hash_node(&pair[0], &pair[1])).collect();
+        low /= 2;
+        width /= 2;
+    }
+
+    proof.next().is_none().then(|| nodes[0])
+}
+
+/// Commits to the elements of a segment, returning the commitment and the tree of the chunks.
+pub fn commit<T: Element>(
+    kind: SegmentKind,
+    elements: &[T],
+    chunk_size: usize,
+) -> Result<(SegmentCommitment, MerkleTree), CommitmentError> {
+    if chunk_size == 0 {
+        return Err(CommitmentError::InvalidChunkSize);
+    }
+
+    let tree = MerkleTree::new(elements.chunks(chunk_size).map(hash_chunk).collect());
+    let root = mix_length(tree.root(), elements.len());
+    Ok((SegmentCommitment { kind, chunk_size, length: elements.len(), root }, tree))
+}
+
+/// The opening of a range of elements of a committed segment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Opening<T> {
+    /// The index of the first chunk covering the range.
+    pub first_chunk: usize,
+    /// The elements of the chunks covering the range.
+    pub elements: Vec<T>,
+    /// The sibling hashes authenticating the chunks.
+    pub proof: Vec<B256>,
+}
+
+impl<T: Element + Clone> Opening<T> {
+    /// Opens the range of elements of a committed segment.
+    pub fn new(
+        commitment: &SegmentCommitment,
+        tree: &MerkleTree,
+        elements: &[T],
+        range: Range<usize>,
+    ) -> Result<Self, CommitmentError> {
+        if range.is_empty() || range.end > commitment.length {
+            return Err(CommitmentError::InvalidRange {
+                start: range.start,
+                end: range.end,
+                length: commitment.length,
+            });
+        }
+
+        let chunks = range.start / commitment.chunk_size..range.end.div_ceil(commitment.chunk_size);
+        let start = chunks.start * commitment.chunk_size;
+        let end = (chunks.end * commitment.chunk_size).min(elements.len());
+        Ok(Self {
+            first_chunk: chunks.start,
+            elements: elements[start..end].to_vec(),
+            proof: tree.prove(chunks),
+        })
+    }
+}
+
+impl<T: Element> Opening<T> {
+    /// Returns the opened element at the given index of the segment, if covered by the opening.
+    pub fn element(&self, commitment: &SegmentCommitment, index: usize) -> Option<&T> {
+        let start = self.first_chunk * commitment.chunk_size;
+        self.elements.get(index.checked_sub(start)?)
+    }
+
+    /// Verifies the opening against the commitment of its segment.
+    pub fn verify(&self, commitment: &SegmentCommitment) -> bool {
+        let start = self.first_chunk * commitment.chunk_size;
+        let end = start + self.elements.len();
+        // Only the last chunk of the segment can be partial.
+        if commitment.chunk_size == 0 ||
+            end > commitment.length ||
+            (self.elements.len() % commitment.chunk_size != 0 && end != commitment.length)
+        {
+            return false;
+        }
+
+        let leaves: Vec<_> = self.elements.chunks(commitment.chunk_size).map(hash_chunk).collect();
+        let width = commitment.chunks().next_power_of_two();
+        range_root(width, self.first_chunk, &leaves, &self.proof)
+            .is_some_and(|root| mix_length(root, commitment.length) == commitment.root)
+    }
+}
+
+/// Commits to the relocated trace and memory of an execution, storing their commitments.
+pub fn commit_execution(
+    db: &Database,
+    number: u64,
+    trace: &[RelocatedTraceEntry],
+    memory: &[Felt252],
+) -> eyre::Result<(SegmentCommitment, SegmentCommitment)> {
+    let (trace, _) = commit(SegmentKind::Trace, trace, DEFAULT_CHUNK_SIZE)?;
+    let (memory, _) = commit(SegmentKind::Memory, memory, DEFAULT_CHUNK_SIZE)?;
+    db.insert_segment_commitment(number, &trace)?;
+    db.insert_segment_commitment(number, &memory)?;
+    Ok((trace, memory))
+}
+
+/// Opens a range of the relocated memory of a block against its stored commitment.
+pub fn open_memory(
+    db: &Database,
+    number: u64,
+    range: Range<usize>,
+) -> eyre::Result<Option<Opening<Felt252>>> {
+    let Some(commitment) = db.segment_commitment(number, SegmentKind::Memory)? else {
+        return Ok(None);
+    };
+    let Some((_, memory)) = db.execution_trace(number)? else { return Ok(None) };
+    let (_, tree) = commit(SegmentKind::Memory, &memory, commitment.chunk_size)?;
+    Ok(Some(Opening::new(&commitment, &tree, &memory, range)?))
+}
+
+/// Opens a range of the relocated trace of a block against its stored commitment.
+pub fn open_trace(
+    db: &Database,
+    number: u64,
+    range: Range<usize>,
+) -> eyre::Result<Option<Opening<RelocatedTraceEntry>>> {
+    let Some(commitment) = db.segment_commitment(number, SegmentKind::Trace)? else {
+        return Ok(None);
+    };
+    let Some((trace, _)) = db.execution_trace(number)? else { return Ok(None) };
+    let (_, tree) = commit(SegmentKind::Trace, &trace, commitment.chunk_size)?;
+    Ok(Some(Opening::new(&commitment, &tree, &trace, range)?))
+}
+
+/// Returns the hash of the leaf of a chunk.
+fn hash_chunk<T: Element>(chunk: &[T]) -> B256 {
+    let mut bytes = vec![LEAF_PREFIX];
+    chunk.iter().for_each(|element| element.append_bytes(&mut bytes));
+    keccak256(bytes)
+}
+
+/// Returns the hash of an inner node.
+fn hash_node(left: &B256, right: &B256) -> B256 {
+    let mut bytes = [0; 65];
+    bytes[0] = NODE_PREFIX;
+    bytes[1..33].copy_from_slice(left.as_slice());
+    bytes[33..].copy_from_slice(right.as_slice());
+    keccak256(bytes)
+}
+
+/// Mixes the number of elements of a segment in the root of its tree.
+fn mix_length(root: B256, length: usize) -> B256 {
+    let mut bytes = [0; 40];
+    bytes[..32].copy_from_slice(root.as_slice());
+    bytes[32..].copy_from_slice(&(length as u64).to_be_bytes());
+    keccak256(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory(length: u64) -> Vec<Felt252> {
+        (0..length).map(|i| Felt252::from(i * 3)).collect()
+    }
+
+    #[test]
+    fn test_range_proofs() {
+        for width in [1, 2, 5, 8] {
+            let leaves: Vec<_> = (0..width).map(|i| B256::with_last_byte(i as u8 + 1)).collect();
+            let tree = MerkleTree::new(leaves.clone());
+            let padded = width.next_power_of_two();
+
+            for start in 0..width {
+                for end in start + 1..=width {
+                    let proof = tree.prove(start..end);
+                    assert_eq!(
+                        range_root(padded, start, &leaves[start..end], &proof),
+                        Some(tree.root()),
+                        "range {start}..{end} of {width} leaves"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_open_memory() {
+        let memory = memory(10);
+        let (commitment, tree) = commit(SegmentKind::Memory, &memory, 3).unwrap();
+        assert_eq!(commitment.chunks(), 4);
+
+        let opening = Opening::new(&commitment, &tree, &memory, 4..5).unwrap();
+        assert_eq!(opening.first_chunk, 1);
+        assert_eq!(opening.elements, memory[3..6]);
+        assert_eq!(opening.element(&commitment, 4), Some(&Felt252::from(12)));
+        assert!(opening.verify(&commitment));
+
+        // The last chunk is partial.
+        let opening = Opening::new(&commitment, &tree, &memory, 8..10).unwrap();
+        assert_eq!(opening.elements, memory[6..]);
+        assert!(opening.verify(&commitment));
+    }
+
+    #[test]
+    fn test_tampered_opening() {
+        let memory = memory(10);
+        let (commitment, tree) = commit(SegmentKind::Memory, &memory, 3).unwrap();
+        let opening = Opening::new(&commitment, &tree, &memory, 0..4).unwrap();
+
+        let mut tampered = opening.clone();
+        tampered.elements[1] = Felt252::from(42);
+        assert!(!tampered.verify(&commitment));
+
+        let mut shifted = opening.clone();
+        shifted.first_chunk = 1;
+        assert!(!shifted.verify(&commitment));
+
+        let other_length = SegmentCommitment { length: 9, ..commitment };
+        assert!(!opening.verify(&other_length));
+    }
+
+    #[test]
+    fn test_open_trace() {
+        let trace: Vec<_> =
+            (0..5).map(|i| RelocatedTraceEntry { pc: i, ap: i + 1, fp: i + 2 }).collect();
+        let (commitment, tree) = commit(SegmentKind::Trace, &trace, 2).unwrap();
+
+        let opening = Opening::new(&commitment, &tree, &trace, 2..5).unwrap();
+        assert!(opening.verify(&commitment));
+        assert_eq!(opening.element(&commitment, 4).map(|entry| entry.fp), Some(6));
+    }
+
+    #[test]
+    fn test_invalid_opening() {
+        let memory = memory(4);
+        let (commitment, tree) = commit(SegmentKind::Memory, &memory, 2).unwrap();
+
+        assert_eq!(
+            Opening::new(&commitment, &tree, &memory, 2..5),
+            Err(CommitmentError::InvalidRange { start: 2, end: 5, length: 4 })
+        );
+        assert_eq!(
+            commit(SegmentKind::Memory, &memory, 0).map(|(commitment, _)| commitment),
+            Err(CommitmentError::InvalidChunkSize)
+        );
+    }
+}
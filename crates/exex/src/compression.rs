@@ -0,0 +1,584 @@
+//! Transparent compression of the stored artifacts: execution traces, AIR inputs, memory dumps,
+//! proofs and program outputs.
+//!
+//! Artifacts are stored as zstd frames, compressed with a dictionary trained on previous artifacts
+//! of the same kind when one is available: their JSON shapes are very repetitive, so that a
+//! dictionary greatly improves the ratio of the small ones. The id of the dictionary is recorded
+//! in the frame header, so that frames remain readable after a dictionary is retrained as long as
+//! the old dictionary is kept.
+//!
+//! Frames are wrapped in the versioned [`envelope`](crate::envelope) recording the kind of the
+//! artifact and the version of its format, artifacts of older versions being upgraded when read.
+//! Data which is neither enveloped nor a zstd frame is read as is, so that the artifacts stored
+//! before compression remain readable until they are migrated with [`migrate`].
+
+use crate::{
+    db::Database,
+    envelope::{Encoding, Header, Migrations, FIRST_VERSION, HEADER_LEN},
+};
+use reth_tracing::tracing::warn;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    io::{self, BufRead, Read, Write},
+    str::FromStr,
+};
+use thiserror::Error;
+use zstd::{zstd_safe, Decoder, Encoder};
+
+/// The magic number starting every zstd frame.
+pub const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// The default zstd compression level of the artifacts.
+pub const COMPRESSION_LEVEL: i32 = 3;
+
+/// The maximum size of the trained dictionaries, the default of the zstd CLI.
+pub const DICTIONARY_SIZE: usize = 112_640;
+
+/// The minimum number of samples a dictionary is trained on.
+pub const MIN_TRAINING_SAMPLES: usize = 16;
+
+/// The number of bytes sampled from the start of each artifact a dictionary is trained on.
+pub const MAX_SAMPLE_SIZE: usize = 128 * 1024;
+
+/// The maximum total size of the samples a dictionary is trained on, a hundred times the size of
+/// the dictionary as advised by zstd.
+pub const MAX_TRAINING_SIZE: usize = 100 * DICTIONARY_SIZE;
+
+/// Represents errors that can occur when compressing the artifacts.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CompressionError {
+    /// Error variant indicating an unknown artifact kind.
+    #[error("Unknown artifact kind '{0}'")]
+    UnknownKind(String),
+
+    /// Error variant indicating that a frame was compressed with an unknown dictionary.
+    #[error("Missing compression dictionary {0}")]
+    MissingDictionary(u32),
+
+    /// Error variant indicating that a dictionary has no id, e.g. raw content.
+    #[error("Invalid compression dictionary, expected a zstd dictionary with an id")]
+    InvalidDictionary,
+}
+
+/// The kinds of stored artifacts, each compressed with its own dictionary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ArtifactKind {
+    /// The relocated execution traces.
+    Trace,
+    /// The relocated memories.
+    Memory,
+    /// The public and private AIR inputs.
+    AirInput,
+    /// The proofs, including the candidate proofs of the campaigns.
+    Proof,
+    /// The memory dumps of the interrupted executions.
+    Dump,
+    /// The outputs of the Kakarot program.
+    Output,
+}
+
+impl ArtifactKind {
+    /// All the artifact kinds.
+    pub const ALL: [Self; 6] =
+        [Self::Trace, Self::Memory, Self::AirInput, Self::Proof, Self::Dump, Self::Output];
+
+    /// Returns the name of the kind, as stored in the database.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Trace => "trace",
+            Self::Memory => "memory",
+            Self::AirInput => "air_input",
+            Self::Proof => "proof",
+            Self::Dump => "dump",
+            Self::Output => "output",
+        }
+    }
+}
+
+impl fmt::Display for ArtifactKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ArtifactKind {
+    type Err = CompressionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == s)
+            .ok_or_else(|| CompressionError::UnknownKind(s.to_string()))
+    }
+}
+
+/// The compression settings, dictionaries and format migrations of the artifacts.
+#[derive(Debug, Clone)]
+pub struct Codec {
+    /// The zstd compression level.
+    level: i32,
+    /// The known dictionaries, by id.
+    dictionaries: HashMap<u32, Vec<u8>>,
+    /// The id of the dictionary compressing each kind of artifact.
+    active: HashMap<ArtifactKind, u32>,
+    /// The migrations of the formats of the artifacts.
+    migrations: Migrations,
+}
+
+impl Default for Codec {
+    fn default() -> Self {
+        Self::new(COMPRESSION_LEVEL)
+    }
+}
+
+impl Codec {
+    /// Creates a new [`Codec`] without dictionaries.
+    pub fn new(level: i32) -> Self {
+        Self {
+            level,
+            dictionaries: HashMap::new(),
+            active: HashMap::new(),
+            migrations: Migrations::default(),
+        }
+    }
+
+    /// Sets the migrations of the formats of the artifacts, the latest version of each format
+    /// being written from now on.
+    pub fn with_migrations(mut self, migrations: Migrations) -> Self {
+        self.migrations = migrations;
+        self
+    }
+
+    /// Returns the migrations of the formats of the artifacts.
+    pub const fn migrations(&self) -> &Migrations {
+        &self.migrations
+    }
+
+    /// Adds a dictionary, compressing the artifacts of the given kind from now on.
+    ///
+    /// Returns the id of the dictionary.
+    pub fn add_dictionary(
+        &mut self,
+        kind: ArtifactKind,
+        dictionary: Vec<u8>,
+    ) -> Result<u32, CompressionError> {
+        let id = zstd_safe::get_dict_id_from_dict(&dictionary)
+            .ok_or(CompressionError::InvalidDictionary)?
+            .get();
+        self.dictionaries.insert(id, dictionary);
+        self.active.insert(kind, id);
+        Ok(id)
+    }
+
+    /// Returns the dictionary compressing the artifacts of the given kind, if any.
+    pub fn dictionary(&self, kind: ArtifactKind) -> Option<&[u8]> {
+        self.active.get(&kind).and_then(|id| self.dictionaries.get(id)).map(Vec::as_slice)
+    }
+
+    /// Returns a streaming encoder of an artifact of the given kind, writing its envelope header
+    /// with the current version of its format.
+    pub fn encoder<W: Write>(
+        &self,
+        kind: ArtifactKind,
+        mut writer: W,
+    ) -> io::Result<Encoder<'static, W>> {
+        let header =
+            Header { kind, version: self.migrations.version(kind), encoding: Encoding::Zstd };
+        writer.write_all(&header.encode())?;
+        match self.dictionary(kind) {
+            Some(dictionary) => Encoder::with_dictionary(writer, self.level, dictionary),
+            None => Encoder::new(writer, self.level),
+        }
+    }
+
+    /// Returns a streaming decoder of an artifact of the given kind, reading it as is when it is
+    /// not compressed and upgrading it to the current version of its format.
+    ///
+    /// The headers are read from the buffer of the reader, which must hold them entirely, as
+    /// in-memory and [`io::BufReader`] readers do. Artifacts of an older version are buffered to
+    /// be upgraded.
+    pub fn decoder<'a, R: BufRead + 'a>(
+        &self,
+        kind: ArtifactKind,
+        reader: R,
+    ) -> io::Result<Box<dyn Read + 'a>> {
+        let (header, mut payload) = self.open(kind, reader)?;
+        if header.version == self.migrations.version(header.kind) {
+            return Ok(payload);
+        }
+
+        let mut content = Vec::new();
+        payload.read_to_end(&mut content)?;
+        let content = self.migrations.upgrade(header.kind, header.version, content)?;
+        Ok(Box::new(io::Cursor::new(content)))
+    }
+
+    /// Reads the envelope header of an artifact of the given kind, returning it with a decoder of
+    /// its payload.
+    ///
+    /// The artifacts stored before the envelope are read as the first version of their format.
+    fn open<'a, R: BufRead + 'a>(
+        &self,
+        kind: ArtifactKind,
+        mut reader: R,
+    ) -> io::Result<(Header, Box<dyn Read + 'a>)> {
+        let data = reader.fill_buf()?;
+        let header = match Header::parse(data)? {
+            Some(header) => {
+                reader.consume(HEADER_LEN);
+                header
+            }
+            None => {
+                let encoding =
+                    if data.starts_with(&ZSTD_MAGIC) { Encoding::Zstd } else { Encoding::Raw };
+                Header { kind, version: FIRST_VERSION, encoding }
+            }
+        };
+
+        let payload: Box<dyn Read + 'a> = match header.encoding {
+            Encoding::Raw => Box::new(reader),
+            Encoding::Zstd => self.frame_decoder(reader)?,
+        };
+        Ok((header, payload))
+    }
+
+    /// Returns a streaming decoder of a zstd frame, with the dictionary recorded in its header.
+    fn frame_decoder<'a, R: BufRead + 'a>(&self, mut reader: R) -> io::Result<Box<dyn Read + 'a>> {
+        match zstd_safe::get_dict_id_from_frame(reader.fill_buf()?) {
+            Some(id) => {
+                let dictionary = self.dictionaries.get(&id.get()).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        CompressionError::MissingDictionary(id.get()),
+                    )
+                })?;
+                Ok(Box::new(Decoder::with_dictionary(reader, dictionary)?))
+            }
+            None => Ok(Box::new(Decoder::with_buffer(reader)?)),
+        }
+    }
+
+    /// Compresses an artifact of the given kind, in the current version of its format.
+    pub fn compress(&self, kind: ArtifactKind, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut encoder = self.encoder(kind, Vec::new())?;
+        encoder.write_all(data)?;
+        encoder.finish()
+    }
+
+    /// Serializes an artifact of the given kind to compressed JSON.
+    pub fn compress_json<T: Serialize>(
+        &self,
+        kind: ArtifactKind,
+        value: &T,
+    ) -> eyre::Result<Vec<u8>> {
+        let mut encoder = self.encoder(kind, Vec::new())?;
+        serde_json::to_writer(&mut encoder, value)?;
+        Ok(encoder.finish()?)
+    }
+
+    /// Decompresses an artifact of the given kind, returning it as is when it is not compressed,
+    /// upgraded to the current version of its format.
+    pub fn decompress(&self, kind: ArtifactKind, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut decompressed = Vec::new();
+        self.decoder(kind, data)?.read_to_end(&mut decompressed)?;
+        Ok(decompressed)
+    }
+
+    /// Decompresses an artifact of the given kind without upgrading it, returning its envelope
+    /// header with its content as written, e.g. to verify its checksum.
+    pub fn unpack(&self, kind: ArtifactKind, data: &[u8]) -> io::Result<(Header, Vec<u8>)> {
+        let (header, mut payload) = self.open(kind, data)?;
+        let mut content = Vec::new();
+        payload.read_to_end(&mut content)?;
+        Ok((header, content))
+    }
+
+    /// Deserializes an artifact of the given kind from JSON, decompressing it on the fly.
+    pub fn decompress_json<T: DeserializeOwned>(
+        &self,
+        kind: ArtifactKind,
+        data: &[u8],
+    ) -> eyre::Result<T> {
+        Ok(serde_json::from_reader(self.decoder(kind, data)?)?)
+    }
+
+    /// Returns whether an artifact of the given kind is compressed in the current version of its
+    /// format, i.e. whether it is left as is by [`migrate`].
+    pub fn is_current(&self, kind: ArtifactKind, data: &[u8]) -> bool {
+        Header::parse(data).ok().flatten().is_some_and(|header| {
+            header.kind == kind &&
+                header.encoding == Encoding::Zstd &&
+                header.version == self.migrations.version(kind)
+        })
+    }
+}
+
+/// Returns whether some data is compressed, either enveloped or a bare zstd frame.
+pub fn is_compressed(data: &[u8]) -> bool {
+    match Header::parse(data) {
+        Ok(Some(header)) => header.encoding == Encoding::Zstd,
+        _ => data.starts_with(&ZSTD_MAGIC),
+    }
+}
+
+/// Returns whether an error is a [`CompressionError::MissingDictionary`], possibly wrapped in the
+/// [`io::Error`] of a decoder.
+pub fn is_missing_dictionary(err: &eyre::Report) -> bool {
+    let missing = |err: Option<&CompressionError>| {
+        matches!(err, Some(CompressionError::MissingDictionary(_)))
+    };
+    err.chain().any(|err| {
+        missing(err.downcast_ref()) ||
+            missing(
+                err.downcast_ref::<io::Error>()
+                    .and_then(io::Error::get_ref)
+                    .and_then(|err| err.downcast_ref()),
+            )
+    })
+}
+
+/// Trains a dictionary of at most `max_size` bytes on uncompressed artifacts.
+pub fn train_dictionary(samples: &[Vec<u8>], max_size: usize) -> io::Result<Vec<u8>> {
+    zstd::dict::from_samples(samples, max_size)
+}
+
+/// The options of the migration of the stored artifacts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MigrationOptions {
+    /// Whether to train the dictionaries of the kinds without one before compressing.
+    pub train: bool,
+    /// The maximum number of artifacts of each kind the dictionaries are trained on.
+    pub samples: usize,
+    /// The number of artifacts rewritten in each database transaction.
+    pub batch_size: usize,
+}
+
+impl Default for MigrationOptions {
+    fn default() -> Self {
+        Self { train: true, samples: 1_000, batch_size: 100 }
+    }
+}
+
+/// The outcome of the migration of the stored artifacts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MigrationReport {
+    /// The kinds whose dictionary was trained.
+    pub trained: Vec<ArtifactKind>,
+    /// The number of artifacts compressed or upgraded, by kind.
+    pub compressed: BTreeMap<ArtifactKind, usize>,
+}
+
+/// Compresses the artifacts stored uncompressed in the database and upgrades the ones of an older
+/// version of their format, training the missing dictionaries first when enabled.
+///
+/// The memory dumps, which were always compressed, are only sampled to train their dictionary.
+/// Kinds with too few artifacts to train a dictionary on are compressed without one.
+pub fn migrate(db: &Database, options: &MigrationOptions) -> eyre::Result<MigrationReport> {
+    let mut report = MigrationReport::default();
+
+    if options.train {
+        for kind in ArtifactKind::ALL {
+            if db.codec().dictionary(kind).is_some() {
+                continue;
+            }
+            let samples = db.artifact_samples(kind, options.samples)?;
+            if samples.len() < MIN_TRAINING_SAMPLES {
+                continue;
+            }
+            match train_dictionary(&samples, DICTIONARY_SIZE) {
+                Ok(dictionary) => {
+                    db.insert_compression_dictionary(kind, dictionary)?;
+                    report.trained.push(kind);
+                }
+                Err(err) => {
+                    warn!(target: "kkrt::compression", %kind, %err, "Training failed");
+                }
+            }
+        }
+    }
+
+    for kind in ArtifactKind::ALL {
+        let compressed = db.compress_artifacts(kind, options.batch_size)?;
+        if compressed > 0 {
+            report.compressed.insert(kind, compressed);
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        envelope::{Migration, Upgrade},
+        output::ProofMetadata,
+    };
+    use rusqlite::Connection;
+
+    fn samples() -> Vec<Vec<u8>> {
+        (0..1_000u64)
+            .map(|i| {
+                let (pc, ap, fp) = (i * 7 % 613, i * 3, i / 5);
+                let step = |pc, ap| format!(r#"{{"pc":{pc},"ap":{ap},"fp":{fp}}}"#);
+                format!("[{},{}]", step(pc, ap), step(pc + 2, ap + 1)).into_bytes()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compress_roundtrip() {
+        let codec = Codec::default();
+        let data = br#"{"pc":1,"ap":2,"fp":3}"#.repeat(100);
+
+        let compressed = codec.compress(ArtifactKind::Trace, &data).unwrap();
+        assert!(is_compressed(&compressed));
+        assert!(codec.is_current(ArtifactKind::Trace, &compressed));
+        assert!(!codec.is_current(ArtifactKind::Proof, &compressed));
+        assert!(compressed.len() < data.len());
+        assert_eq!(codec.decompress(ArtifactKind::Trace, &compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn test_decompress_uncompressed() {
+        let codec = Codec::default();
+        let legacy = serde_json::to_vec(&vec![1u64, 2, 3]).unwrap();
+
+        assert_eq!(codec.decompress(ArtifactKind::Output, &legacy).unwrap(), legacy);
+        assert_eq!(
+            codec.decompress_json::<Vec<u64>>(ArtifactKind::Output, &legacy).unwrap(),
+            vec![1, 2, 3]
+        );
+        assert!(!codec.is_current(ArtifactKind::Output, &legacy));
+
+        // Bare zstd frames, stored before the envelope, are read as the first version.
+        let frame = zstd::encode_all(legacy.as_slice(), COMPRESSION_LEVEL).unwrap();
+        assert!(is_compressed(&frame));
+        assert!(!codec.is_current(ArtifactKind::Output, &frame));
+        let (header, content) = codec.unpack(ArtifactKind::Output, &frame).unwrap();
+        assert_eq!(
+            header,
+            Header { kind: ArtifactKind::Output, version: FIRST_VERSION, encoding: Encoding::Zstd }
+        );
+        assert_eq!(content, legacy);
+    }
+
+    #[test]
+    fn test_upgrade_on_access() {
+        let upgrade: Upgrade = |content| Ok([b"v2:".as_slice(), &content].concat());
+        let migrations =
+            Migrations::new(vec![Migration { kind: ArtifactKind::Proof, from: 1, upgrade }]);
+        let codec = Codec::default().with_migrations(migrations);
+
+        // Artifacts written by a previous release are upgraded when read.
+        let old = Codec::default().compress(ArtifactKind::Proof, b"proof").unwrap();
+        assert!(!codec.is_current(ArtifactKind::Proof, &old));
+        assert_eq!(codec.decompress(ArtifactKind::Proof, &old).unwrap(), b"v2:proof");
+        assert_eq!(codec.decompress(ArtifactKind::Proof, b"legacy").unwrap(), b"v2:legacy");
+        assert_eq!(codec.unpack(ArtifactKind::Proof, &old).unwrap().1, b"proof");
+
+        // Artifacts are written in the current version, and not upgraded again.
+        let new = codec.compress(ArtifactKind::Proof, b"v2:proof").unwrap();
+        assert!(codec.is_current(ArtifactKind::Proof, &new));
+        assert_eq!(codec.decompress(ArtifactKind::Proof, &new).unwrap(), b"v2:proof");
+
+        // Artifacts written by a newer release cannot be read.
+        let err = Codec::default().decompress(ArtifactKind::Proof, &new).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_compress_with_dictionary() {
+        let samples = samples();
+        let dictionary = train_dictionary(&samples, 4_096).unwrap();
+        let mut codec = Codec::default();
+        let id = codec.add_dictionary(ArtifactKind::Trace, dictionary).unwrap();
+
+        let compressed = codec.compress(ArtifactKind::Trace, &samples[42]).unwrap();
+        let frame = &compressed[HEADER_LEN..];
+        assert_eq!(zstd_safe::get_dict_id_from_frame(frame).map(|id| id.get()), Some(id));
+        assert_eq!(codec.decompress(ArtifactKind::Trace, &compressed).unwrap(), samples[42]);
+
+        // The frame cannot be read without its dictionary.
+        let err = Codec::default().decompress(ArtifactKind::Trace, &compressed).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // Other kinds are compressed without dictionary.
+        let proof = codec.compress(ArtifactKind::Proof, b"proof").unwrap();
+        assert_eq!(Codec::default().decompress(ArtifactKind::Proof, &proof).unwrap(), b"proof");
+    }
+
+    #[test]
+    fn test_add_invalid_dictionary() {
+        assert_eq!(
+            Codec::default().add_dictionary(ArtifactKind::Proof, b"raw content".to_vec()),
+            Err(CompressionError::InvalidDictionary)
+        );
+    }
+
+    #[test]
+    fn test_parse_artifact_kind() {
+        for kind in ArtifactKind::ALL {
+            assert_eq!(kind.as_str().parse(), Ok(kind));
+        }
+        assert_eq!(
+            "receipt".parse::<ArtifactKind>(),
+            Err(CompressionError::UnknownKind("receipt".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_migrate() {
+        let db = Database::new(Connection::open_in_memory().unwrap()).unwrap();
+        let metadata = ProofMetadata { block_number: 1, ..Default::default() };
+        db.lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO proof (number, metadata, proof) VALUES (?, ?, ?)",
+                ("1", serde_json::to_string(&metadata).unwrap(), b"legacy proof".as_slice()),
+            )
+            .unwrap();
+
+        let options = MigrationOptions { train: false, ..Default::default() };
+        let report = migrate(&db, &options).unwrap();
+
+        assert_eq!(report.compressed, BTreeMap::from([(ArtifactKind::Proof, 1)]));
+        assert_eq!(db.proof(1).unwrap().map(|(_, proof)| proof), Some(b"legacy proof".to_vec()));
+        // The migration is idempotent.
+        assert_eq!(migrate(&db, &options).unwrap(), MigrationReport::default());
+    }
+
+    #[test]
+    fn test_dictionary_trained_by_another_process() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keth.db");
+        let reader = Database::open(&path).unwrap();
+
+        // The dictionary is trained and a proof compressed with it by another process, e.g.
+        // `keth compress-artifacts`, after the database of the instance was opened.
+        let writer = Database::open(&path).unwrap();
+        let dictionary = train_dictionary(&samples(), 4_096).unwrap();
+        writer.insert_compression_dictionary(ArtifactKind::Proof, dictionary).unwrap();
+        let metadata = ProofMetadata { block_number: 1, ..Default::default() };
+        writer.insert_proof(&metadata, &samples()[42]).unwrap();
+
+        assert!(reader.codec().dictionary(ArtifactKind::Proof).is_none());
+        assert_eq!(reader.proof(1).unwrap().map(|(_, proof)| proof), Some(samples()[42].clone()));
+        assert!(reader.codec().dictionary(ArtifactKind::Proof).is_some());
+    }
+
+    #[test]
+    fn test_artifact_samples_bounded() {
+        let db = Database::new(Connection::open_in_memory().unwrap()).unwrap();
+        let proof = vec![b'p'; 2 * MAX_SAMPLE_SIZE];
+        for block_number in 0..4 {
+            let metadata = ProofMetadata { block_number, ..Default::default() };
+            db.insert_proof(&metadata, &proof).unwrap();
+        }
+
+        let samples = db.artifact_samples(ArtifactKind::Proof, 3).unwrap();
+        assert_eq!(samples, vec![proof[..MAX_SAMPLE_SIZE].to_vec(); 3]);
+    }
+}
@@ -1,3 +1,25 @@
+use crate::{
+    artifacts::{object_key, ArtifactStore, LifecyclePolicy, LifecycleReport},
+    attribution::TransactionResources,
+    blob::BlobWitness,
+    campaign::{BlockState, Campaign, CampaignProgress},
+    chain::ChainLink,
+    commitment::{SegmentCommitment, SegmentKind},
+    compression::{is_missing_dictionary, ArtifactKind, Codec, MAX_SAMPLE_SIZE, MAX_TRAINING_SIZE},
+    deferred::{JobState, ProvingJob},
+    events::{IndexedLog, LogFilter},
+    fact::FactStatus,
+    failures::RecurringFailure,
+    input::program_input::ProgramInput,
+    integrity::{checksum, ArtifactRef},
+    limits::{Diagnostics, ExecutionLimits},
+    output::{ProgramOutput, ProofMetadata},
+    program::BlockProgram,
+    quorum::ProverResult,
+    retry::RetryState,
+    sharp::{SharpJob, SharpStatus},
+    tuning::RunProfile,
+};
 use alloy_primitives::{Address, B256, U256};
 use cairo_vm::{
     air_private_input::AirPrivateInput, air_public_input::PublicInput,
@@ -9,31 +31,47 @@ use reth_primitives::{
 };
 use reth_provider::OriginalValuesKnown;
 use reth_revm::db::BundleState;
-use rusqlite::Connection;
+use reth_tracing::tracing::warn;
+use rusqlite::{types::ValueRef, Connection, Row};
 use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::{self, BufReader, Read},
     ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
     str::FromStr,
-    sync::{Arc, Mutex, MutexGuard},
+    sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard},
 };
 
 /// A struct representing the database, encapsulating a connection to the SQLite database.
 ///
 /// The connection is protected by a `Mutex` for thread-safe access and is shared across
-/// instances using `Arc`.
-#[derive(Debug)]
-pub struct Database(Arc<Mutex<Connection>>);
+/// instances using `Arc`, along with the codec compressing the stored artifacts and the object
+/// storage they are tiered to, if any.
+///
+/// The codec lock is only acquired while holding the connection, never the other way around, so
+/// that the two locks are always taken in the same order.
+#[derive(Debug, Clone)]
+pub struct Database {
+    /// The connection to the SQLite database.
+    connection: Arc<Mutex<Connection>>,
+    /// The codec of the artifacts, holding the dictionaries stored in the database.
+    codec: Arc<RwLock<Codec>>,
+    /// The object storage of the large artifacts, stored in the database when `None`.
+    store: Option<ArtifactStore>,
+}
 
 impl Deref for Database {
     type Target = Arc<Mutex<Connection>>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.connection
     }
 }
 
 impl DerefMut for Database {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        &mut self.connection
     }
 }
 
@@ -41,21 +79,76 @@ impl Database {
     /// Creates a new `Database` instance with the provided SQLite `Connection`.
     pub fn new(connection: Connection) -> eyre::Result<Self> {
         // Create the database instance and create the required tables.
-        let database = Self(Arc::new(Mutex::new(connection)));
+        let database = Self {
+            connection: Arc::new(Mutex::new(connection)),
+            codec: Arc::new(RwLock::new(Codec::default())),
+            store: None,
+        };
         database.create_tables()?;
+        database.load_compression_dictionaries()?;
         Ok(database)
     }
 
+    /// Opens the SQLite database at the given path, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        Self::new(Connection::open(path)?)
+    }
+
+    /// Tiers the large artifacts written from now on to the given object storage, see
+    /// [`crate::artifacts`].
+    pub fn with_artifact_store(mut self, store: ArtifactStore) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Returns the object storage the large artifacts are tiered to, if any.
+    pub const fn artifact_store(&self) -> Option<&ArtifactStore> {
+        self.store.as_ref()
+    }
+
     /// Acquires a lock on the database connection and returns a `MutexGuard` for access.
     fn connection(&self) -> MutexGuard<'_, Connection> {
         self.lock().expect("failed to acquire database lock")
     }
 
+    /// Returns the codec compressing the stored artifacts.
+    pub fn codec(&self) -> RwLockReadGuard<'_, Codec> {
+        self.codec.read().expect("failed to acquire codec lock")
+    }
+
     /// Creates the necessary tables in the SQLite database if they do not already exist.
     ///
     /// This function sets up the following tables:
     /// - `block`: Stores blocks with a unique block number and associated data.
     /// - `account`: Stores account data with a unique address.
+    /// - `trace`: Stores the Cairo execution traces of the blocks.
+    /// - `transaction_resources`: Stores the Cairo resources used by each transaction.
+    /// - `partial_run`: Stores the diagnostics of the executions interrupted by their limits.
+    /// - `retry`: Stores the blocks to execute again, with their relaxed limits.
+    /// - `proving_job`: Stores the blocks waiting to be proven in deferred mode.
+    /// - `program_input`: Stores the program inputs of the processed blocks, run again when their
+    ///   deferred jobs are exported or when they are replayed.
+    /// - `proof`: Stores the imported proofs of the blocks.
+    /// - `log`: Stores the logs emitted during the Cairo execution, indexed by address and first
+    ///   topic.
+    /// - `campaign`: Stores the re-proving campaigns.
+    /// - `campaign_block`: Stores the state of the blocks of the campaigns, with their candidate
+    ///   proofs.
+    /// - `run_profile`: Stores the resources used by the execution of the blocks, to tune the
+    ///   runners.
+    /// - `program_output`: Stores the output of the Kakarot program for each block.
+    /// - `block_program`: Stores the program selected for each block, with its hash.
+    /// - `fact`: Stores the facts of the proven blocks with their on-chain registration status.
+    /// - `sharp_job`: Stores the blocks submitted to SHARP with the status of their jobs.
+    /// - `compression_dictionary`: Stores the zstd dictionaries of the compressed artifacts.
+    /// - `segment_commitment`: Stores the Merkle commitments to the trace and memory of the blocks.
+    /// - `prover_result`: Stores the results of each prover of a quorum, with their proofs.
+    /// - `proving_retry`: Stores the retry state of the failed proving jobs.
+    /// - `proof_chain`: Stores the hash chain linking the proofs of consecutive blocks.
+    /// - `blob_witness`: Stores the KZG commitments of the witnesses of the blocks posted as blobs.
+    /// - `artifact_object`: Stores the keys of the artifacts tiered to the object storage.
+    /// - `artifact_checksum`: Stores the checksums of the content of the persisted artifacts.
+    /// - `failure`: Stores the counts of the failures of the blocks, by fingerprint.
     fn create_tables(&self) -> eyre::Result<()> {
         // Acquires a lock on the database connection and executes SQL commands to create tables.
         self.connection().execute_batch(
@@ -77,6 +170,159 @@ impl Database {
                 air_public_input    TEXT,
                 air_private_input   TEXT
             );
+            CREATE TABLE IF NOT EXISTS transaction_resources (
+                id          INTEGER PRIMARY KEY,
+                number      TEXT,
+                tx_index    INTEGER,
+                data        TEXT,
+                UNIQUE(number, tx_index)
+            );
+            CREATE TABLE IF NOT EXISTS partial_run (
+                id          INTEGER PRIMARY KEY,
+                number      TEXT UNIQUE,
+                diagnostics TEXT,
+                dump_path   TEXT
+            );
+            CREATE TABLE IF NOT EXISTS retry (
+                id      INTEGER PRIMARY KEY,
+                number  TEXT UNIQUE,
+                limits  TEXT
+            );
+            CREATE TABLE IF NOT EXISTS proving_job (
+                id      INTEGER PRIMARY KEY,
+                number  TEXT UNIQUE,
+                data    TEXT,
+                state   TEXT
+            );
+            CREATE TABLE IF NOT EXISTS program_input (
+                id      INTEGER PRIMARY KEY,
+                number  TEXT UNIQUE,
+                data    TEXT
+            );
+            CREATE TABLE IF NOT EXISTS proof (
+                id          INTEGER PRIMARY KEY,
+                number      TEXT UNIQUE,
+                metadata    TEXT,
+                proof       BLOB
+            );
+            CREATE TABLE IF NOT EXISTS log (
+                id          INTEGER PRIMARY KEY,
+                number      INTEGER,
+                tx_index    INTEGER,
+                log_index   INTEGER,
+                address     TEXT,
+                topic0      TEXT,
+                data        TEXT,
+                UNIQUE(number, tx_index, log_index)
+            );
+            CREATE INDEX IF NOT EXISTS log_address ON log (address, number);
+            CREATE INDEX IF NOT EXISTS log_topic0 ON log (topic0, number);
+            CREATE TABLE IF NOT EXISTS log_block (
+                number      INTEGER PRIMARY KEY
+            );
+            CREATE TABLE IF NOT EXISTS campaign (
+                id      INTEGER PRIMARY KEY,
+                name    TEXT UNIQUE,
+                data    TEXT
+            );
+            CREATE TABLE IF NOT EXISTS campaign_block (
+                id          INTEGER PRIMARY KEY,
+                campaign    TEXT,
+                number      TEXT,
+                state       TEXT,
+                metadata    TEXT,
+                proof       BLOB,
+                UNIQUE(campaign, number)
+            );
+            CREATE TABLE IF NOT EXISTS run_profile (
+                id      INTEGER PRIMARY KEY,
+                number  TEXT UNIQUE,
+                data    TEXT
+            );
+            CREATE TABLE IF NOT EXISTS program_output (
+                id      INTEGER PRIMARY KEY,
+                number  TEXT UNIQUE,
+                data    TEXT
+            );
+            CREATE TABLE IF NOT EXISTS block_program (
+                id      INTEGER PRIMARY KEY,
+                number  TEXT UNIQUE,
+                data    TEXT
+            );
+            CREATE TABLE IF NOT EXISTS fact (
+                id          INTEGER PRIMARY KEY,
+                number      TEXT UNIQUE,
+                fact        TEXT,
+                registered  INTEGER
+            );
+            CREATE TABLE IF NOT EXISTS sharp_job (
+                id      INTEGER PRIMARY KEY,
+                number  TEXT UNIQUE,
+                status  TEXT,
+                data    TEXT
+            );
+            CREATE TABLE IF NOT EXISTS compression_dictionary (
+                id      INTEGER PRIMARY KEY,
+                kind    TEXT,
+                data    BLOB
+            );
+            CREATE TABLE IF NOT EXISTS segment_commitment (
+                id      INTEGER PRIMARY KEY,
+                number  TEXT,
+                kind    TEXT,
+                data    TEXT,
+                UNIQUE(number, kind)
+            );
+            CREATE TABLE IF NOT EXISTS prover_result (
+                id          INTEGER PRIMARY KEY,
+                number      TEXT,
+                prover      TEXT,
+                data        TEXT,
+                proof       BLOB,
+                UNIQUE(number, prover)
+            );
+            CREATE TABLE IF NOT EXISTS proving_retry (
+                id              INTEGER PRIMARY KEY,
+                number          TEXT UNIQUE,
+                next_attempt    INTEGER,
+                data            TEXT
+            );
+            CREATE TABLE IF NOT EXISTS proof_chain (
+                id      INTEGER PRIMARY KEY,
+                number  TEXT UNIQUE,
+                data    TEXT
+            );
+            CREATE TABLE IF NOT EXISTS blob_witness (
+                id      INTEGER PRIMARY KEY,
+                number  TEXT UNIQUE,
+                data    TEXT
+            );
+            CREATE TABLE IF NOT EXISTS artifact_object (
+                id      INTEGER PRIMARY KEY,
+                number  INTEGER,
+                kind    TEXT,
+                key     TEXT UNIQUE,
+                cached  INTEGER
+            );
+            CREATE INDEX IF NOT EXISTS artifact_object_kind ON artifact_object (kind, number);
+            CREATE TABLE IF NOT EXISTS artifact_checksum (
+                id          INTEGER PRIMARY KEY,
+                number      INTEGER,
+                kind        TEXT,
+                name        TEXT,
+                checksum    TEXT,
+                UNIQUE(kind, number, name)
+            );
+            CREATE TABLE IF NOT EXISTS failure (
+                id          INTEGER PRIMARY KEY,
+                fingerprint TEXT UNIQUE,
+                summary     TEXT,
+                count       INTEGER,
+                first_block INTEGER,
+                last_block  INTEGER,
+                first_seen  INTEGER,
+                last_seen   INTEGER
+            );
             ",
         )?;
         Ok(())
@@ -120,6 +366,16 @@ impl Database {
         Ok(())
     }
 
+    /// Inserts a block, replacing a previous one with the same number, without changing the
+    /// plain state of the accounts.
+    pub fn insert_block(&self, block: &SealedBlockWithSenders) -> eyre::Result<()> {
+        self.connection().execute(
+            "INSERT OR REPLACE INTO block (number, data) VALUES (?, ?)",
+            (block.header.number.to_string(), serde_json::to_string(block)?),
+        )?;
+        Ok(())
+    }
+
     /// Retrieves a block from the database using its block number.
     ///
     /// This function queries the database for a block with the specified block number.
@@ -178,16 +434,84 @@ impl Database {
         memory: Vec<Felt252>,
         air_public_input: PublicInput<'_>,
         air_private_input: AirPrivateInput,
+    ) -> eyre::Result<()> {
+        let contents = [
+            serde_json::to_vec(&trace)?,
+            serde_json::to_vec(&memory)?,
+            serde_json::to_vec(&air_public_input)?,
+            serde_json::to_vec(&air_private_input.0)?,
+        ];
+        let checksums = contents.each_ref().map(|content| checksum(content));
+        let codec = self.codec();
+        let artifacts = [
+            codec.compress(ArtifactKind::Trace, &contents[0])?,
+            codec.compress(ArtifactKind::Memory, &contents[1])?,
+            codec.compress(ArtifactKind::AirInput, &contents[2])?,
+            codec.compress(ArtifactKind::AirInput, &contents[3])?,
+        ];
+        drop(codec);
+
+        let Some(store) = &self.store else {
+            return self.commit_execution_trace(number, checksums, Some(artifacts));
+        };
+
+        // The compressed trace is tiered to the object storage before the transaction, so that the
+        // connection is not held during the uploads, the `trace` table only recording the block.
+        // The uploaded objects are deleted again if the trace is not committed.
+        let mut keys = Vec::new();
+        for ((kind, column), data) in TRACE_ARTIFACTS.into_iter().zip(artifacts) {
+            let key = object_key(kind.as_str(), number, column);
+            if let Err(err) = store.put(&key, data) {
+                self.delete_objects(&keys);
+                return Err(err.into());
+            }
+            keys.push(key);
+        }
+        let result = self.commit_execution_trace(number, checksums, None);
+        if result.is_err() {
+            self.delete_objects(&keys);
+        }
+        result
+    }
+
+    /// Commits the rows of an execution trace: the checksums of its artifacts, and either its
+    /// compressed artifacts or the keys of their objects when they are tiered.
+    fn commit_execution_trace(
+        &self,
+        number: u64,
+        checksums: [B256; 4],
+        artifacts: Option<[Vec<u8>; 4]>,
     ) -> eyre::Result<()> {
         // Acquire a database connection and begin a transaction.
         let mut connection = self.connection();
         let tx = connection.transaction()?;
 
-        // Insert the trace into the `trace` table.
-        tx.execute(
-            "INSERT INTO trace (number, execution, memory, air_public_input, air_private_input) VALUES (?, ?, ?, ?, ?)",
-            (number, serde_json::to_string(&trace)?, serde_json::to_string(&memory)?, serde_json::to_string(&air_public_input)?, serde_json::to_string(&air_private_input.0)?),
-        )?;
+        // Record the checksums of the uncompressed artifacts.
+        for ((kind, column), checksum) in TRACE_ARTIFACTS.into_iter().zip(checksums) {
+            insert_artifact_checksum(
+                &tx,
+                &ArtifactRef::new(kind.as_str(), number, column),
+                checksum,
+            )?;
+        }
+
+        match artifacts {
+            // Insert the compressed trace into the `trace` table.
+            Some([execution, memory, air_public_input, air_private_input]) => {
+                tx.execute(
+                    "INSERT INTO trace (number, execution, memory, air_public_input, air_private_input) VALUES (?, ?, ?, ?, ?)",
+                    (number, execution, memory, air_public_input, air_private_input),
+                )?;
+            }
+            // Record the keys of the tiered artifacts.
+            None => {
+                for (kind, column) in TRACE_ARTIFACTS {
+                    let key = object_key(kind.as_str(), number, column);
+                    insert_artifact_object(&tx, number, kind.as_str(), &key)?;
+                }
+                tx.execute("INSERT INTO trace (number) VALUES (?)", (number,))?;
+            }
+        }
 
         // Commit the transaction to persist all changes.
         tx.commit()?;
@@ -204,16 +528,37 @@ impl Database {
         let connection = self.connection();
         let mut statement =
             connection.prepare("SELECT execution, memory FROM trace WHERE number = ?")?;
-        let res: Result<(String, String), _> = statement
-            .query_map([number.to_string()], |row| Ok((row.get(0)?, row.get(1)?)))?
+        let res: Result<(Option<Vec<u8>>, Option<Vec<u8>>), _> = statement
+            .query_map([number.to_string()], |row| {
+                Ok((optional_artifact(row, 0)?, optional_artifact(row, 1)?))
+            })?
             .next()
             .ok_or(eyre::eyre!("No trace found for block"))?;
+        drop(statement);
+        drop(connection);
 
         match res {
-            // If the trace is found, deserialize the JSON strings into `(Vec<RelocatedTraceEntry>,
-            // Vec<Felt252>)`.
+            // If the trace is found, decompress, verify and deserialize the JSON into
+            // `(Vec<RelocatedTraceEntry>, Vec<Felt252>)`, the artifacts missing from the table
+            // being tiered to the object storage.
             Ok((trace, memory)) => {
-                Ok(Some((serde_json::from_str(&trace)?, serde_json::from_str(&memory)?)))
+                let trace = match trace {
+                    Some(trace) => trace,
+                    None => self.tiered_artifact(ArtifactKind::Trace, number, "execution")?,
+                };
+                let memory = match memory {
+                    Some(memory) => memory,
+                    None => self.tiered_artifact(ArtifactKind::Memory, number, "memory")?,
+                };
+                let trace = self.verified_artifact(
+                    &ArtifactRef::new(ArtifactKind::Trace.as_str(), number, "execution"),
+                    &trace,
+                )?;
+                let memory = self.verified_artifact(
+                    &ArtifactRef::new(ArtifactKind::Memory.as_str(), number, "memory"),
+                    &memory,
+                )?;
+                Ok(Some((serde_json::from_slice(&trace)?, serde_json::from_slice(&memory)?)))
             }
             // If no rows are returned by the query, it means the trace does not exist for the given
             // block in the database.
@@ -223,57 +568,1630 @@ impl Database {
         }
     }
 
-    /// Inserts a new account if it doesn't exist or updates it if it does.
-    pub fn set_account(&self, address: Address, account_info: AccountInfo) -> eyre::Result<()> {
+    /// Returns whether an execution trace is stored for a specific block.
+    pub fn has_execution_trace(&self, number: u64) -> eyre::Result<bool> {
+        Ok(self.connection().query_row(
+            "SELECT EXISTS(SELECT 1 FROM trace WHERE number = ?)",
+            (number.to_string(),),
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Inserts the Cairo resources used by the transactions of a block, replacing previous ones.
+    pub fn insert_transaction_resources(
+        &self,
+        number: u64,
+        resources: &[TransactionResources],
+    ) -> eyre::Result<()> {
+        // Acquire a database connection and begin a transaction.
+        let mut connection = self.connection();
+        let tx = connection.transaction()?;
+
+        for resources in resources {
+            tx.execute(
+                "INSERT OR REPLACE INTO transaction_resources (number, tx_index, data) VALUES (?, ?, ?)",
+                (number.to_string(), resources.tx_index, serde_json::to_string(resources)?),
+            )?;
+        }
+
+        // Commit the transaction to persist all changes.
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// Retrieves the Cairo resources used by the transactions of a block, ordered by index.
+    pub fn transaction_resources(&self, number: u64) -> eyre::Result<Vec<TransactionResources>> {
+        let connection = self.connection();
+        let mut statement = connection
+            .prepare("SELECT data FROM transaction_resources WHERE number = ? ORDER BY tx_index")?;
+        let rows = statement.query_map([number.to_string()], |row| row.get::<_, String>(0))?;
+
+        rows.map(|data| Ok(serde_json::from_str(&data?)?)).collect()
+    }
+
+    /// Inserts the diagnostics of an interrupted execution, with the path of its memory dump.
+    pub fn insert_partial_run(
+        &self,
+        number: u64,
+        diagnostics: &Diagnostics,
+        dump_path: Option<&Path>,
+    ) -> eyre::Result<()> {
         self.connection().execute(
-            "INSERT INTO account (address, data) VALUES (?, ?) ON CONFLICT(address) DO UPDATE SET data = excluded.data",
-            (address.to_string(), serde_json::to_string(&account_info)?),
+            "INSERT OR REPLACE INTO partial_run (number, diagnostics, dump_path) VALUES (?, ?, ?)",
+            (
+                number.to_string(),
+                serde_json::to_string(diagnostics)?,
+                dump_path.map(|path| path.display().to_string()),
+            ),
         )?;
+        Ok(())
+    }
 
+    /// Retrieves the diagnostics of the interrupted execution of a block, if any.
+    pub fn partial_run(&self, number: u64) -> eyre::Result<Option<Diagnostics>> {
+        let diagnostics = self.connection().query_row::<String, _, _>(
+            "SELECT diagnostics FROM partial_run WHERE number = ?",
+            (number.to_string(),),
+            |row| row.get(0),
+        );
+
+        match diagnostics {
+            Ok(data) => Ok(Some(serde_json::from_str(&data)?)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Marks a block to be executed again with the given limits.
+    pub fn mark_for_retry(&self, number: u64, limits: &ExecutionLimits) -> eyre::Result<()> {
+        self.connection().execute(
+            "INSERT OR REPLACE INTO retry (number, limits) VALUES (?, ?)",
+            (number.to_string(), serde_json::to_string(limits)?),
+        )?;
         Ok(())
     }
 
-    /// Retrieves an account from the database using its address.
-    pub fn account(&self, address: Address) -> eyre::Result<Option<AccountInfo>> {
-        match self.connection().query_row::<String, _, _>(
-            "SELECT data FROM account WHERE address = ?",
-            (address.to_string(),),
+    /// Retrieves the limits of the next execution of a block marked for retry, if any.
+    pub fn retry_limits(&self, number: u64) -> eyre::Result<Option<ExecutionLimits>> {
+        let limits = self.connection().query_row::<String, _, _>(
+            "SELECT limits FROM retry WHERE number = ?",
+            (number.to_string(),),
             |row| row.get(0),
-        ) {
-            Ok(account_info) => Ok(Some(serde_json::from_str(&account_info)?)),
+        );
+
+        match limits {
+            Ok(data) => Ok(Some(serde_json::from_str(&data)?)),
             Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
             Err(e) => Err(e.into()),
         }
     }
-}
 
-impl reth_revm::Database for Database {
-    type Error = eyre::Report;
+    /// Retrieves the blocks marked for retry, with the limits of their next execution.
+    pub fn retries(&self) -> eyre::Result<Vec<(u64, ExecutionLimits)>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare("SELECT number, limits FROM retry")?;
+        let rows = statement
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
 
-    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
-        self.account(address)
+        rows.map(|row| {
+            let (number, limits) = row?;
+            Ok((number.parse::<u64>()?, serde_json::from_str(&limits)?))
+        })
+        .collect()
     }
 
-    fn code_by_hash(&mut self, _code_hash: B256) -> Result<Bytecode, Self::Error> {
-        Ok(Default::default())
+    /// Removes the retry mark of a block, once it was executed within its limits.
+    pub fn clear_retry(&self, number: u64) -> eyre::Result<()> {
+        self.connection().execute("DELETE FROM retry WHERE number = ?", [number.to_string()])?;
+        Ok(())
     }
 
-    fn storage(&mut self, _address: Address, _index: U256) -> Result<U256, Self::Error> {
-        Ok(Default::default())
+    /// Enqueues a proving job, unless the block already has one.
+    pub fn enqueue_proving_job(&self, job: &ProvingJob) -> eyre::Result<()> {
+        self.connection().execute(
+            "INSERT OR IGNORE INTO proving_job (number, data, state) VALUES (?, ?, ?)",
+            (job.block_number.to_string(), serde_json::to_string(job)?, JobState::Queued.as_str()),
+        )?;
+        Ok(())
     }
 
-    fn block_hash(&mut self, number: u64) -> Result<B256, Self::Error> {
-        let block_hash = self.connection().query_row::<String, _, _>(
-            "SELECT hash FROM block WHERE number = ?",
+    /// Retrieves at most `limit` proving jobs in the given state, ordered by block number.
+    pub fn proving_jobs(&self, state: JobState, limit: usize) -> eyre::Result<Vec<ProvingJob>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(
+            "SELECT data FROM proving_job WHERE state = ? ORDER BY CAST(number AS INTEGER) LIMIT ?",
+        )?;
+        let rows =
+            statement.query_map((state.as_str(), limit as i64), |row| row.get::<_, String>(0))?;
+
+        rows.map(|data| Ok(serde_json::from_str(&data?)?)).collect()
+    }
+
+    /// Retrieves at most `limit` queued proving jobs which are due at `now`, i.e. whose retry
+    /// backoff elapsed, ordered by block number.
+    pub fn due_proving_jobs(&self, now: u64, limit: usize) -> eyre::Result<Vec<ProvingJob>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(
+            "SELECT job.data FROM proving_job job
+            LEFT JOIN proving_retry retry ON retry.number = job.number
+            WHERE job.state = ? AND (retry.next_attempt IS NULL OR retry.next_attempt <= ?)
+            ORDER BY CAST(job.number AS INTEGER) LIMIT ?",
+        )?;
+        let rows = statement
+            .query_map((JobState::Queued.as_str(), now as i64, limit as i64), |row| {
+                row.get::<_, String>(0)
+            })?;
+
+        rows.map(|data| Ok(serde_json::from_str(&data?)?)).collect()
+    }
+
+    /// Inserts the program input of a block, replacing a previous one.
+    pub fn insert_program_input(&self, number: u64, input: &ProgramInput) -> eyre::Result<()> {
+        self.connection().execute(
+            "INSERT OR REPLACE INTO program_input (number, data) VALUES (?, ?)",
+            (number.to_string(), serde_json::to_string(input)?),
+        )?;
+        Ok(())
+    }
+
+    /// Retrieves the program input of a block, if any.
+    pub fn program_input(&self, number: u64) -> eyre::Result<Option<ProgramInput>> {
+        let data = self.connection().query_row::<String, _, _>(
+            "SELECT data FROM program_input WHERE number = ?",
             (number.to_string(),),
             |row| row.get(0),
         );
-        match block_hash {
-            Ok(data) => Ok(B256::from_str(&data).unwrap()),
-            // No special handling for `QueryReturnedNoRows` is needed, because revm does block
-            // number bound checks on its own.
-            // See https://github.com/bluealloy/revm/blob/1ca3d39f6a9e9778f8eb0fcb74fe529345a531b4/crates/interpreter/src/instructions/host.rs#L106-L123.
-            Err(err) => Err(err.into()),
+
+        match data {
+            Ok(data) => Ok(Some(serde_json::from_str(&data)?)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Retrieves the state of the proving job of a block, if any.
+    pub fn proving_job_state(&self, number: u64) -> eyre::Result<Option<JobState>> {
+        let state = self.connection().query_row::<String, _, _>(
+            "SELECT state FROM proving_job WHERE number = ?",
+            (number.to_string(),),
+            |row| row.get(0),
+        );
+
+        match state {
+            Ok(state) => Ok(Some(state.parse()?)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Updates the state of the proving job of a block.
+    pub fn set_proving_job_state(&self, number: u64, state: JobState) -> eyre::Result<()> {
+        self.connection().execute(
+            "UPDATE proving_job SET state = ? WHERE number = ?",
+            (state.as_str(), number.to_string()),
+        )?;
+        Ok(())
+    }
+
+    /// Marks a proving job as exported, recording its export time and the steps of its run.
+    pub fn mark_proving_job_exported(&self, job: &ProvingJob) -> eyre::Result<()> {
+        self.connection().execute(
+            "UPDATE proving_job SET data = ?, state = ? WHERE number = ?",
+            (
+                serde_json::to_string(job)?,
+                JobState::Exported.as_str(),
+                job.block_number.to_string(),
+            ),
+        )?;
+        Ok(())
+    }
+
+    /// Records an import of proofs in a single transaction: the jobs of the proven blocks are
+    /// marked as proven, and the failed jobs are queued again or marked as failed with their retry
+    /// state.
+    pub fn commit_proof_import(&self, proven: &[u64], failed: &[RetryState]) -> eyre::Result<()> {
+        // Acquire a database connection and begin a transaction.
+        let mut connection = self.connection();
+        let tx = connection.transaction()?;
+
+        for number in proven {
+            tx.execute(
+                "UPDATE proving_job SET state = ? WHERE number = ?",
+                (JobState::Proven.as_str(), number.to_string()),
+            )?;
+        }
+        for state in failed {
+            tx.execute(
+                "INSERT OR REPLACE INTO proving_retry (number, next_attempt, data) VALUES (?, ?, ?)",
+                (
+                    state.block_number.to_string(),
+                    state.next_attempt_at.map(|at| at as i64),
+                    serde_json::to_string(state)?,
+                ),
+            )?;
+            tx.execute(
+                "UPDATE proving_job SET state = ? WHERE number = ?",
+                (state.job_state().as_str(), state.block_number.to_string()),
+            )?;
+        }
+
+        // Commit the transaction to persist all changes.
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// Inserts the retry state of a proving job, replacing a previous one.
+    pub fn insert_proving_retry(&self, state: &RetryState) -> eyre::Result<()> {
+        self.connection().execute(
+            "INSERT OR REPLACE INTO proving_retry (number, next_attempt, data) VALUES (?, ?, ?)",
+            (
+                state.block_number.to_string(),
+                state.next_attempt_at.map(|at| at as i64),
+                serde_json::to_string(state)?,
+            ),
+        )?;
+        Ok(())
+    }
+
+    /// Retrieves the retry state of the proving job of a block, if it failed.
+    pub fn proving_retry(&self, number: u64) -> eyre::Result<Option<RetryState>> {
+        match self.connection().query_row(
+            "SELECT data FROM proving_retry WHERE number = ?",
+            (number.to_string(),),
+            |row| row.get::<_, String>(0),
+        ) {
+            Ok(data) => Ok(Some(serde_json::from_str(&data)?)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Inserts the proof of a block, replacing a previous one.
+    pub fn insert_proof(&self, metadata: &ProofMetadata, proof: &[u8]) -> eyre::Result<()> {
+        let connection = self.connection();
+        connection.execute(
+            "INSERT OR REPLACE INTO proof (number, metadata, proof) VALUES (?, ?, ?)",
+            (
+                metadata.block_number.to_string(),
+                serde_json::to_string(metadata)?,
+                self.codec().compress(ArtifactKind::Proof, proof)?,
+            ),
+        )?;
+        insert_artifact_checksum(&connection, &proof_ref(metadata.block_number), checksum(proof))?;
+        Ok(())
+    }
+
+    /// Retrieves the proof of a block with its metadata, if any.
+    pub fn proof(&self, number: u64) -> eyre::Result<Option<(ProofMetadata, Vec<u8>)>> {
+        let proof = self.connection().query_row::<(String, Vec<u8>), _, _>(
+            "SELECT metadata, proof FROM proof WHERE number = ?",
+            (number.to_string(),),
+            |row| Ok((row.get(0)?, artifact(row, 1)?)),
+        );
+
+        match proof {
+            Ok((metadata, proof)) => Ok(Some((
+                serde_json::from_str(&metadata)?,
+                self.verified_artifact(&proof_ref(number), &proof)?,
+            ))),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Inserts the result of a prover of a quorum with its proof, replacing a previous result of
+    /// the prover for the block.
+    pub fn insert_prover_result(&self, result: &ProverResult, proof: &[u8]) -> eyre::Result<()> {
+        self.connection().execute(
+            "INSERT OR REPLACE INTO prover_result (number, prover, data, proof)
+            VALUES (?, ?, ?, ?)",
+            (
+                result.metadata.block_number.to_string(),
+                &result.metadata.prover,
+                serde_json::to_string(result)?,
+                self.codec().compress(ArtifactKind::Proof, proof)?,
+            ),
+        )?;
+        Ok(())
+    }
+
+    /// Retrieves the results of the provers of a block, ordered by prover.
+    pub fn prover_results(&self, number: u64) -> eyre::Result<Vec<ProverResult>> {
+        let connection = self.connection();
+        let mut statement = connection
+            .prepare("SELECT data FROM prover_result WHERE number = ? ORDER BY prover")?;
+        let rows = statement.query_map((number.to_string(),), |row| row.get::<_, String>(0))?;
+
+        rows.map(|data| Ok(serde_json::from_str(&data?)?)).collect()
+    }
+
+    /// Retrieves the proof of a prover for a block with its metadata, if any.
+    pub fn prover_proof(
+        &self,
+        number: u64,
+        prover: &str,
+    ) -> eyre::Result<Option<(ProofMetadata, Vec<u8>)>> {
+        let proof = self.connection().query_row::<(String, Vec<u8>), _, _>(
+            "SELECT data, proof FROM prover_result WHERE number = ? AND prover = ?",
+            (number.to_string(), prover),
+            |row| Ok((row.get(0)?, artifact(row, 1)?)),
+        );
+
+        match proof {
+            Ok((data, proof)) => {
+                let result: ProverResult = serde_json::from_str(&data)?;
+                let proof =
+                    self.decode(|codec| Ok(codec.decompress(ArtifactKind::Proof, &proof)?))?;
+                Ok(Some((result.metadata, proof)))
+            }
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
         }
     }
+
+    /// Inserts a link of the chain of the proven blocks, replacing a previous link of the block.
+    pub fn insert_chain_link(&self, link: &ChainLink) -> eyre::Result<()> {
+        self.connection().execute(
+            "INSERT OR REPLACE INTO proof_chain (number, data) VALUES (?, ?)",
+            (link.block_number.to_string(), serde_json::to_string(link)?),
+        )?;
+        Ok(())
+    }
+
+    /// Retrieves the link of a block in the chain of the proven blocks, if any.
+    pub fn chain_link(&self, number: u64) -> eyre::Result<Option<ChainLink>> {
+        match self.connection().query_row(
+            "SELECT data FROM proof_chain WHERE number = ?",
+            (number.to_string(),),
+            |row| row.get::<_, String>(0),
+        ) {
+            Ok(data) => Ok(Some(serde_json::from_str(&data)?)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Retrieves the head of the chain of the proven blocks, i.e. its link of highest block number.
+    pub fn chain_head(&self) -> eyre::Result<Option<ChainLink>> {
+        match self.connection().query_row(
+            "SELECT data FROM proof_chain ORDER BY CAST(number AS INTEGER) DESC LIMIT 1",
+            [],
+            |row| row.get::<_, String>(0),
+        ) {
+            Ok(data) => Ok(Some(serde_json::from_str(&data)?)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Inserts the blob commitments of the witness of a block, replacing previous ones.
+    pub fn insert_blob_witness(&self, witness: &BlobWitness) -> eyre::Result<()> {
+        self.connection().execute(
+            "INSERT OR REPLACE INTO blob_witness (number, data) VALUES (?, ?)",
+            (witness.block_number.to_string(), serde_json::to_string(witness)?),
+        )?;
+        Ok(())
+    }
+
+    /// Retrieves the blob commitments of the witness of a block, if any.
+    pub fn blob_witness(&self, number: u64) -> eyre::Result<Option<BlobWitness>> {
+        match self.connection().query_row(
+            "SELECT data FROM blob_witness WHERE number = ?",
+            (number.to_string(),),
+            |row| row.get::<_, String>(0),
+        ) {
+            Ok(data) => Ok(Some(serde_json::from_str(&data)?)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Retrieves the numbers of the proven blocks in `[from_block, to_block]`, in ascending order.
+    pub fn proven_blocks(&self, from_block: u64, to_block: u64) -> eyre::Result<Vec<u64>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(
+            "SELECT number FROM proof WHERE CAST(number AS INTEGER) BETWEEN ? AND ?
+            ORDER BY CAST(number AS INTEGER)",
+        )?;
+        let rows = statement
+            .query_map((from_block as i64, to_block as i64), |row| row.get::<_, String>(0))?;
+
+        rows.map(|number| Ok(number?.parse()?)).collect()
+    }
+
+    /// Inserts the output of the Kakarot program for a block, replacing a previous one.
+    pub fn insert_program_output(&self, output: &ProgramOutput) -> eyre::Result<()> {
+        let data = self.codec().compress_json(ArtifactKind::Output, output)?;
+        self.connection().execute(
+            "INSERT OR REPLACE INTO program_output (number, data) VALUES (?, ?)",
+            (output.block_number.to_string(), data),
+        )?;
+        Ok(())
+    }
+
+    /// Retrieves the output of the Kakarot program for a block, if any.
+    pub fn program_output(&self, number: u64) -> eyre::Result<Option<ProgramOutput>> {
+        let data = self.connection().query_row::<Vec<u8>, _, _>(
+            "SELECT data FROM program_output WHERE number = ?",
+            (number.to_string(),),
+            |row| artifact(row, 0),
+        );
+
+        match data {
+            Ok(data) => {
+                Ok(Some(self.decode(|codec| codec.decompress_json(ArtifactKind::Output, &data))?))
+            }
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Inserts the program selected for a block, replacing a previous one.
+    pub fn insert_block_program(&self, program: &BlockProgram) -> eyre::Result<()> {
+        self.connection().execute(
+            "INSERT OR REPLACE INTO block_program (number, data) VALUES (?, ?)",
+            (program.block_number.to_string(), serde_json::to_string(program)?),
+        )?;
+        Ok(())
+    }
+
+    /// Retrieves the program selected for a block, if any.
+    pub fn block_program(&self, number: u64) -> eyre::Result<Option<BlockProgram>> {
+        match self.connection().query_row(
+            "SELECT data FROM block_program WHERE number = ?",
+            (number.to_string(),),
+            |row| row.get::<_, String>(0),
+        ) {
+            Ok(data) => Ok(Some(serde_json::from_str(&data)?)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Inserts the registration status of the fact of a proven block, replacing a previous one.
+    pub fn insert_fact_status(&self, status: &FactStatus) -> eyre::Result<()> {
+        self.connection().execute(
+            "INSERT OR REPLACE INTO fact (number, fact, registered) VALUES (?, ?, ?)",
+            (status.block_number.to_string(), status.fact.to_string(), status.registered),
+        )?;
+        Ok(())
+    }
+
+    /// Retrieves the facts of the proven blocks which are not registered on-chain, ordered by
+    /// block number.
+    pub fn unregistered_facts(&self) -> eyre::Result<Vec<FactStatus>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(
+            "SELECT number, fact FROM fact WHERE registered = 0 ORDER BY CAST(number AS INTEGER)",
+        )?;
+        let rows = statement
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+
+        rows.map(|row| {
+            let (number, fact) = row?;
+            Ok(FactStatus { block_number: number.parse()?, fact: fact.parse()?, registered: false })
+        })
+        .collect()
+    }
+
+    /// Inserts a block submitted to SHARP with its job in progress, replacing a previous
+    /// submission of the block.
+    pub fn insert_sharp_job(&self, job: &SharpJob) -> eyre::Result<()> {
+        self.connection().execute(
+            "INSERT OR REPLACE INTO sharp_job (number, status, data) VALUES (?, ?, ?)",
+            (
+                job.block_number.to_string(),
+                SharpStatus::InProgress.as_str(),
+                serde_json::to_string(job)?,
+            ),
+        )?;
+        Ok(())
+    }
+
+    /// Retrieves the blocks submitted to SHARP whose job has the given status, ordered by block
+    /// number.
+    pub fn sharp_jobs(&self, status: SharpStatus) -> eyre::Result<Vec<SharpJob>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(
+            "SELECT data FROM sharp_job WHERE status = ? ORDER BY CAST(number AS INTEGER)",
+        )?;
+        let rows = statement.query_map((status.as_str(),), |row| row.get::<_, String>(0))?;
+
+        rows.map(|data| Ok(serde_json::from_str(&data?)?)).collect()
+    }
+
+    /// Updates the status of the SHARP job of a block.
+    pub fn set_sharp_job_status(&self, number: u64, status: SharpStatus) -> eyre::Result<()> {
+        self.connection().execute(
+            "UPDATE sharp_job SET status = ? WHERE number = ?",
+            (status.as_str(), number.to_string()),
+        )?;
+        Ok(())
+    }
+
+    /// Inserts the commitment to a segment of the execution of a block, replacing a previous
+    /// commitment to the segment.
+    pub fn insert_segment_commitment(
+        &self,
+        number: u64,
+        commitment: &SegmentCommitment,
+    ) -> eyre::Result<()> {
+        self.connection().execute(
+            "INSERT OR REPLACE INTO segment_commitment (number, kind, data) VALUES (?, ?, ?)",
+            (number.to_string(), commitment.kind.as_str(), serde_json::to_string(commitment)?),
+        )?;
+        Ok(())
+    }
+
+    /// Retrieves the commitment to a segment of the execution of a block, if any.
+    pub fn segment_commitment(
+        &self,
+        number: u64,
+        kind: SegmentKind,
+    ) -> eyre::Result<Option<SegmentCommitment>> {
+        match self.connection().query_row(
+            "SELECT data FROM segment_commitment WHERE number = ? AND kind = ?",
+            (number.to_string(), kind.as_str()),
+            |row| row.get::<_, String>(0),
+        ) {
+            Ok(data) => Ok(Some(serde_json::from_str(&data)?)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Inserts the logs emitted during the Cairo execution of a block, replacing previous ones,
+    /// and marks the block as indexed.
+    pub fn insert_logs(&self, number: u64, logs: &[IndexedLog]) -> eyre::Result<()> {
+        // Acquire a database connection and begin a transaction.
+        let mut connection = self.connection();
+        let tx = connection.transaction()?;
+
+        tx.execute("DELETE FROM log WHERE number = ?", [number as i64])?;
+        tx.execute("INSERT OR IGNORE INTO log_block (number) VALUES (?)", [number as i64])?;
+        for log in logs {
+            tx.execute(
+                "INSERT INTO log (number, tx_index, log_index, address, topic0, data) VALUES (?, ?, ?, ?, ?, ?)",
+                (
+                    number as i64,
+                    log.tx_index,
+                    log.log_index,
+                    log.log.address.to_string(),
+                    log.log.topics().first().map(ToString::to_string),
+                    serde_json::to_string(log)?,
+                ),
+            )?;
+        }
+
+        // Commit the transaction to persist all changes.
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// Retrieves the logs of the proven blocks matching the filter, ordered by block, transaction
+    /// and log index.
+    ///
+    /// Logs of blocks which were executed but not proven yet are excluded.
+    pub fn proven_logs(&self, filter: &LogFilter) -> eyre::Result<Vec<IndexedLog>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(
+            "SELECT log.data FROM log JOIN proof ON proof.number = CAST(log.number AS TEXT)
+            WHERE log.number BETWEEN ?1 AND ?2
+                AND (?3 IS NULL OR log.address = ?3)
+                AND (?4 IS NULL OR log.topic0 = ?4)
+            ORDER BY log.number, log.tx_index, log.log_index",
+        )?;
+        let rows = statement.query_map(
+            (
+                filter.from_block as i64,
+                filter.to_block as i64,
+                filter.address.map(|address| address.to_string()),
+                filter.topic0.map(|topic| topic.to_string()),
+            ),
+            |row| row.get::<_, String>(0),
+        )?;
+
+        rows.map(|data| Ok(serde_json::from_str(&data?)?)).collect()
+    }
+
+    /// Retrieves the numbers of the proven blocks in the range whose logs were never indexed, e.g.
+    /// because their program does not execute the transactions.
+    pub fn unindexed_proven_blocks(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> eyre::Result<Vec<u64>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(
+            "SELECT CAST(number AS INTEGER) AS block FROM proof
+            WHERE block BETWEEN ?1 AND ?2 AND block NOT IN (SELECT number FROM log_block)
+            ORDER BY block",
+        )?;
+        let rows = statement
+            .query_map((from_block as i64, to_block as i64), |row| row.get::<_, i64>(0))?;
+
+        rows.map(|number| Ok(number? as u64)).collect()
+    }
+
+    /// Inserts the profile of the execution of a block, replacing a previous one.
+    pub fn insert_run_profile(&self, number: u64, profile: &RunProfile) -> eyre::Result<()> {
+        self.connection().execute(
+            "INSERT OR REPLACE INTO run_profile (number, data) VALUES (?, ?)",
+            (number.to_string(), serde_json::to_string(profile)?),
+        )?;
+        Ok(())
+    }
+
+    /// Retrieves the profiles of the last `limit` executed blocks, latest first.
+    pub fn run_profiles(&self, limit: usize) -> eyre::Result<Vec<RunProfile>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(
+            "SELECT data FROM run_profile ORDER BY CAST(number AS INTEGER) DESC LIMIT ?",
+        )?;
+        let rows = statement.query_map((limit as i64,), |row| row.get::<_, String>(0))?;
+
+        rows.map(|data| Ok(serde_json::from_str(&data?)?)).collect()
+    }
+
+    /// Inserts a campaign with all the blocks of its range pending.
+    pub fn insert_campaign(&self, campaign: &Campaign) -> eyre::Result<()> {
+        // Acquire a database connection and begin a transaction.
+        let mut connection = self.connection();
+        let tx = connection.transaction()?;
+
+        tx.execute(
+            "INSERT INTO campaign (name, data) VALUES (?, ?)",
+            (&campaign.name, serde_json::to_string(campaign)?),
+        )?;
+        for number in campaign.from_block..=campaign.to_block {
+            tx.execute(
+                "INSERT INTO campaign_block (campaign, number, state) VALUES (?, ?, ?)",
+                (&campaign.name, number.to_string(), BlockState::Pending.as_str()),
+            )?;
+        }
+
+        // Commit the transaction to persist all changes.
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// Retrieves a campaign using its name.
+    pub fn campaign(&self, name: &str) -> eyre::Result<Option<Campaign>> {
+        let data = self.connection().query_row::<String, _, _>(
+            "SELECT data FROM campaign WHERE name = ?",
+            (name,),
+            |row| row.get(0),
+        );
+
+        match data {
+            Ok(data) => Ok(Some(serde_json::from_str(&data)?)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Retrieves the number of blocks of a campaign in each state.
+    pub fn campaign_progress(&self, name: &str) -> eyre::Result<CampaignProgress> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(
+            "SELECT state, COUNT(*) FROM campaign_block WHERE campaign = ? GROUP BY state",
+        )?;
+        let rows = statement
+            .query_map((name,), |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))?;
+
+        let mut progress = CampaignProgress::default();
+        for row in rows {
+            let (state, count) = row?;
+            progress.add(state.parse()?, count as u64);
+        }
+        Ok(progress)
+    }
+
+    /// Retrieves at most `limit` blocks of a campaign in the given state, in ascending order.
+    pub fn campaign_blocks(
+        &self,
+        name: &str,
+        state: BlockState,
+        limit: usize,
+    ) -> eyre::Result<Vec<u64>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(
+            "SELECT number FROM campaign_block WHERE campaign = ? AND state = ?
+            ORDER BY CAST(number AS INTEGER) LIMIT ?",
+        )?;
+        let rows = statement
+            .query_map((name, state.as_str(), i64::try_from(limit).unwrap_or(i64::MAX)), |row| {
+                row.get::<_, String>(0)
+            })?;
+
+        rows.map(|number| Ok(number?.parse()?)).collect()
+    }
+
+    /// Retrieves the state of a block in a campaign, `None` if the block is not part of it.
+    pub fn campaign_block_state(
+        &self,
+        name: &str,
+        number: u64,
+    ) -> eyre::Result<Option<BlockState>> {
+        let state = self.connection().query_row::<String, _, _>(
+            "SELECT state FROM campaign_block WHERE campaign = ? AND number = ?",
+            (name, number.to_string()),
+            |row| row.get(0),
+        );
+
+        match state {
+            Ok(state) => Ok(Some(state.parse()?)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Updates the state of a block in a campaign.
+    pub fn set_campaign_block_state(
+        &self,
+        name: &str,
+        number: u64,
+        state: BlockState,
+    ) -> eyre::Result<()> {
+        self.connection().execute(
+            "UPDATE campaign_block SET state = ? WHERE campaign = ? AND number = ?",
+            (state.as_str(), name, number.to_string()),
+        )?;
+        Ok(())
+    }
+
+    /// Inserts the candidate proof of a block in a campaign, marking it as proven.
+    pub fn insert_candidate_proof(
+        &self,
+        name: &str,
+        metadata: &ProofMetadata,
+        proof: &[u8],
+    ) -> eyre::Result<()> {
+        self.connection().execute(
+            "UPDATE campaign_block SET state = ?, metadata = ?, proof = ?
+            WHERE campaign = ? AND number = ?",
+            (
+                BlockState::Proven.as_str(),
+                serde_json::to_string(metadata)?,
+                self.codec().compress(ArtifactKind::Proof, proof)?,
+                name,
+                metadata.block_number.to_string(),
+            ),
+        )?;
+        Ok(())
+    }
+
+    /// Retrieves the candidate proofs of a campaign waiting to be verified, ordered by block
+    /// number.
+    pub fn candidate_proofs(&self, name: &str) -> eyre::Result<Vec<(ProofMetadata, Vec<u8>)>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(
+            "SELECT metadata, proof FROM campaign_block WHERE campaign = ? AND state = ?
+            ORDER BY CAST(number AS INTEGER)",
+        )?;
+        let rows = statement
+            .query_map((name, BlockState::Proven.as_str()), |row| {
+                Ok((row.get::<_, String>(0)?, artifact(row, 1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        drop(statement);
+        drop(connection);
+
+        rows.into_iter()
+            .map(|(metadata, proof)| {
+                let proof =
+                    self.decode(|codec| Ok(codec.decompress(ArtifactKind::Proof, &proof)?))?;
+                Ok((serde_json::from_str(&metadata)?, proof))
+            })
+            .collect()
+    }
+
+    /// Replaces the proof of a block with its verified candidate proof, atomically marking the
+    /// block as verified in the campaign.
+    pub fn promote_candidate_proof(
+        &self,
+        name: &str,
+        metadata: &ProofMetadata,
+        proof: &[u8],
+    ) -> eyre::Result<()> {
+        // Acquire a database connection and begin a transaction.
+        let mut connection = self.connection();
+        let tx = connection.transaction()?;
+
+        let number = metadata.block_number.to_string();
+        tx.execute(
+            "INSERT OR REPLACE INTO proof (number, metadata, proof) VALUES (?, ?, ?)",
+            (
+                &number,
+                serde_json::to_string(metadata)?,
+                self.codec().compress(ArtifactKind::Proof, proof)?,
+            ),
+        )?;
+        insert_artifact_checksum(&tx, &proof_ref(metadata.block_number), checksum(proof))?;
+        // The candidate is cleared once promoted, the proof table holding its only copy.
+        tx.execute(
+            "UPDATE campaign_block SET state = ?, proof = NULL WHERE campaign = ? AND number = ?",
+            (BlockState::Verified.as_str(), name, &number),
+        )?;
+
+        // Commit the transaction to persist all changes.
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// Loads the compression dictionaries stored in the database into the codec, the last
+    /// dictionary of each kind compressing its artifacts.
+    ///
+    /// The dictionaries are loaded again when an artifact compressed with a dictionary unknown to
+    /// the codec is read, see [`Self::decode`], or before the artifacts are migrated, since they
+    /// may have been trained since the database was opened, e.g. by `keth compress-artifacts`.
+    fn load_compression_dictionaries(&self) -> eyre::Result<()> {
+        let connection = self.connection();
+        let mut statement =
+            connection.prepare("SELECT kind, data FROM compression_dictionary ORDER BY id")?;
+        let rows = statement
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?)))?;
+
+        let mut codec = self.codec.write().expect("failed to acquire codec lock");
+        for row in rows {
+            let (kind, dictionary) = row?;
+            codec.add_dictionary(kind.parse()?, dictionary)?;
+        }
+        Ok(())
+    }
+
+    /// Decodes an artifact with the codec, loading the stored dictionaries again and decoding it
+    /// once more if it was compressed with a dictionary unknown to the codec.
+    ///
+    /// The connection must not be held by the caller.
+    fn decode<T>(&self, f: impl Fn(&Codec) -> eyre::Result<T>) -> eyre::Result<T> {
+        let result = f(&self.codec());
+        match result {
+            Err(err) if is_missing_dictionary(&err) => {
+                self.load_compression_dictionaries()?;
+                f(&self.codec())
+            }
+            result => result,
+        }
+    }
+
+    /// Inserts a compression dictionary, compressing the artifacts of its kind from now on.
+    ///
+    /// Previous dictionaries are kept, to read the artifacts they compressed.
+    pub fn insert_compression_dictionary(
+        &self,
+        kind: ArtifactKind,
+        dictionary: Vec<u8>,
+    ) -> eyre::Result<()> {
+        self.connection().execute(
+            "INSERT INTO compression_dictionary (kind, data) VALUES (?, ?)",
+            (kind.as_str(), &dictionary),
+        )?;
+        self.codec
+            .write()
+            .expect("failed to acquire codec lock")
+            .add_dictionary(kind, dictionary)?;
+        Ok(())
+    }
+
+    /// Retrieves at most `limit` uncompressed artifacts of the given kind, to train a compression
+    /// dictionary on.
+    ///
+    /// Only the first [`MAX_SAMPLE_SIZE`] bytes of each artifact are sampled, and the sampling
+    /// stops once [`MAX_TRAINING_SIZE`] bytes are sampled, so that training on large artifacts
+    /// such as the traces does not hold them in memory. The memory dumps are read from the files
+    /// of the interrupted executions.
+    pub fn artifact_samples(&self, kind: ArtifactKind, limit: usize) -> eyre::Result<Vec<Vec<u8>>> {
+        self.load_compression_dictionaries()?;
+
+        let mut samples = Vec::new();
+        let mut size = 0;
+        // Records a sample, returning whether to sample more artifacts.
+        let mut push = |sample: Vec<u8>| {
+            size += sample.len();
+            samples.push(sample);
+            samples.len() < limit && size < MAX_TRAINING_SIZE
+        };
+
+        if kind == ArtifactKind::Dump {
+            for path in self.dump_paths(limit)?.into_iter().filter(|path| path.exists()) {
+                let reader = self.codec().decoder(kind, BufReader::new(File::open(path)?))?;
+                if !push(sample(reader)?) {
+                    break;
+                }
+            }
+            return Ok(samples);
+        }
+
+        let connection = self.connection();
+        'tables: for (table, column) in artifact_columns(kind) {
+            let mut statement = connection.prepare(&format!(
+                "SELECT {column} FROM {table} WHERE {column} IS NOT NULL LIMIT ?"
+            ))?;
+            let rows = statement.query_map((limit as i64,), |row| artifact(row, 0))?;
+            for data in rows {
+                let data = data?;
+                if !push(sample(self.codec().decoder(kind, data.as_slice())?)?) {
+                    break 'tables;
+                }
+            }
+        }
+        Ok(samples)
+    }
+
+    /// Compresses the artifacts of the given kind stored uncompressed and upgrades the ones of an
+    /// older version of their format, `batch_size` of them per transaction, returning the number
+    /// of rewritten artifacts.
+    ///
+    /// The checksums of the upgraded artifacts are updated along with their content.
+    pub fn compress_artifacts(&self, kind: ArtifactKind, batch_size: usize) -> eyre::Result<usize> {
+        self.load_compression_dictionaries()?;
+
+        let mut compressed = 0;
+        for (table, column) in artifact_columns(kind) {
+            let mut last_id = 0;
+            loop {
+                // Acquire a database connection and begin a transaction.
+                let mut connection = self.connection();
+                let tx = connection.transaction()?;
+
+                let batch = {
+                    let mut statement = tx.prepare(&format!(
+                        "SELECT id, number, {column} FROM {table}
+                        WHERE id > ? AND {column} IS NOT NULL ORDER BY id LIMIT ?"
+                    ))?;
+                    let rows = statement.query_map((last_id, batch_size as i64), |row| {
+                        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, artifact(row, 2)?))
+                    })?;
+                    rows.collect::<Result<Vec<_>, _>>()?
+                };
+                let Some((id, ..)) = batch.last() else { break };
+                last_id = *id;
+
+                let codec = self.codec();
+                for (id, number, data) in
+                    batch.iter().filter(|(.., data)| !codec.is_current(kind, data))
+                {
+                    let (header, content) = codec.unpack(kind, data)?;
+                    let upgraded =
+                        codec.migrations().upgrade(header.kind, header.version, content.clone())?;
+                    if upgraded != content {
+                        // The checksum is only updated if recorded for this content, e.g. not
+                        // for the candidate proofs of the campaigns.
+                        tx.execute(
+                            "UPDATE artifact_checksum SET checksum = ?
+                            WHERE kind = ? AND number = ? AND name = ? AND checksum = ?",
+                            (
+                                checksum(&upgraded).to_string(),
+                                kind.as_str(),
+                                number.parse::<i64>()?,
+                                column,
+                                checksum(&content).to_string(),
+                            ),
+                        )?;
+                    }
+                    tx.execute(
+                        &format!("UPDATE {table} SET {column} = ? WHERE id = ?"),
+                        (codec.compress(kind, &upgraded)?, id),
+                    )?;
+                    compressed += 1;
+                }
+
+                // Commit the transaction to persist all changes.
+                tx.commit()?;
+            }
+        }
+        Ok(compressed)
+    }
+
+    /// Retrieves the paths of at most `limit` memory dumps of the interrupted executions.
+    pub fn dump_paths(&self, limit: usize) -> eyre::Result<Vec<PathBuf>> {
+        let connection = self.connection();
+        let mut statement = connection
+            .prepare("SELECT dump_path FROM partial_run WHERE dump_path IS NOT NULL LIMIT ?")?;
+        let rows = statement.query_map((limit as i64,), |row| row.get::<_, String>(0))?;
+
+        rows.map(|path| Ok(PathBuf::from(path?))).collect()
+    }
+
+    /// Retrieves an artifact of a block tiered to the object storage.
+    fn tiered_artifact(
+        &self,
+        kind: ArtifactKind,
+        number: u64,
+        name: &str,
+    ) -> eyre::Result<Vec<u8>> {
+        let key = object_key(kind.as_str(), number, name);
+        let store =
+            self.store.as_ref().ok_or_else(|| eyre::eyre!("No store to read {key} from"))?;
+        store.get(&key)?.ok_or_else(|| eyre::eyre!("Artifact {key} is missing, e.g. expired"))
+    }
+
+    /// Uploads an artifact of a block to the object storage, recording its key.
+    ///
+    /// Returns the key of the artifact, or `None` when the artifacts are not tiered.
+    pub fn store_artifact(
+        &self,
+        kind: &str,
+        number: u64,
+        name: &str,
+        data: Vec<u8>,
+    ) -> eyre::Result<Option<String>> {
+        let Some(store) = &self.store else { return Ok(None) };
+        let artifact = ArtifactRef::new(kind, number, name);
+        let key = artifact.key();
+        let digest = checksum(&data);
+        store.put(&key, data)?;
+        let connection = self.connection();
+        insert_artifact_object(&connection, number, kind, &key)?;
+        insert_artifact_checksum(&connection, &artifact, digest)?;
+        Ok(Some(key))
+    }
+
+    /// Tiers the execution traces stored in the database to the object storage, `batch_size` of
+    /// them per transaction, returning the number of tiered traces.
+    pub fn tier_traces(&self, batch_size: usize) -> eyre::Result<usize> {
+        let store = self.store.as_ref().ok_or_else(|| eyre::eyre!("No artifact store"))?;
+        let mut tiered = 0;
+        loop {
+            // Acquire a database connection and begin a transaction.
+            let mut connection = self.connection();
+            let tx = connection.transaction()?;
+
+            let batch = {
+                let mut statement = tx.prepare(
+                    "SELECT id, number, execution, memory, air_public_input, air_private_input
+                    FROM trace WHERE execution IS NOT NULL ORDER BY id LIMIT ?",
+                )?;
+                let rows = statement.query_map((batch_size as i64,), |row| {
+                    let artifacts = [
+                        artifact(row, 2)?,
+                        artifact(row, 3)?,
+                        artifact(row, 4)?,
+                        artifact(row, 5)?,
+                    ];
+                    Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, artifacts))
+                })?;
+                rows.collect::<Result<Vec<_>, _>>()?
+            };
+            if batch.is_empty() {
+                break;
+            }
+
+            for (id, number, artifacts) in batch {
+                let number = number.parse()?;
+                for ((kind, column), data) in TRACE_ARTIFACTS.into_iter().zip(artifacts) {
+                    let key = object_key(kind.as_str(), number, column);
+                    store.put(&key, data)?;
+                    insert_artifact_object(&tx, number, kind.as_str(), &key)?;
+                }
+                tx.execute(
+                    "UPDATE trace SET execution = NULL, memory = NULL, air_public_input = NULL,
+                    air_private_input = NULL WHERE id = ?",
+                    (id,),
+                )?;
+                tiered += 1;
+            }
+
+            // Commit the transaction to persist all changes.
+            tx.commit()?;
+        }
+        Ok(tiered)
+    }
+
+    /// Tiers the memory dumps of the interrupted executions to the object storage, returning the
+    /// number of tiered dumps.
+    ///
+    /// The local files are removed, the recorded paths of the dumps becoming the paths of their
+    /// cached copies, if the store has a cache.
+    pub fn tier_dumps(&self) -> eyre::Result<usize> {
+        let store = self.store.as_ref().ok_or_else(|| eyre::eyre!("No artifact store"))?;
+        let dumps = {
+            let connection = self.connection();
+            let mut statement = connection.prepare(
+                "SELECT number, dump_path FROM partial_run
+                WHERE dump_path IS NOT NULL AND CAST(number AS INTEGER) NOT IN
+                (SELECT number FROM artifact_object WHERE kind = ?)",
+            )?;
+            let rows = statement.query_map((ArtifactKind::Dump.as_str(),), |row| {
+                Ok((row.get::<_, String>(0)?, PathBuf::from(row.get::<_, String>(1)?)))
+            })?;
+            rows.collect::<Result<Vec<_>, _>>()?
+        };
+
+        let mut tiered = 0;
+        for (number, path) in dumps.into_iter().filter(|(_, path)| path.exists()) {
+            let number = number.parse()?;
+            let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+            self.store_artifact(ArtifactKind::Dump.as_str(), number, &name, std::fs::read(&path)?)?;
+            let key = object_key(ArtifactKind::Dump.as_str(), number, &name);
+            std::fs::remove_file(&path)?;
+            self.connection().execute(
+                "UPDATE partial_run SET dump_path = ? WHERE number = ?",
+                (store.cache_path(&key).map(|path| path.display().to_string()), number.to_string()),
+            )?;
+            tiered += 1;
+        }
+        Ok(tiered)
+    }
+
+    /// Applies the lifecycle rules to the tiered artifacts at the given tip, evicting their cached
+    /// copies and deleting them from the object storage.
+    pub fn apply_lifecycle(
+        &self,
+        policy: &LifecyclePolicy,
+        tip: u64,
+    ) -> eyre::Result<LifecycleReport> {
+        let mut report = LifecycleReport::default();
+        let Some(store) = &self.store else { return Ok(report) };
+
+        // Select the keys of the artifacts of a kind older than the given number of blocks, with
+        // their block.
+        let keys =
+            |kind: &str, blocks: u64, cached_only: bool| -> eyre::Result<Vec<(String, i64)>> {
+                let connection = self.connection();
+                let mut statement = connection.prepare(
+                    "SELECT key, number FROM artifact_object
+                WHERE kind = ? AND number + ? < ? AND (cached = 1 OR NOT ?)",
+                )?;
+                let rows = statement
+                    .query_map((kind, blocks as i64, tip as i64, cached_only), |row| {
+                        Ok((row.get(0)?, row.get(1)?))
+                    })?;
+                Ok(rows.collect::<Result<Vec<_>, _>>()?)
+            };
+
+        for rule in &policy.rules {
+            if let Some(blocks) = rule.expire_blocks {
+                let expired = keys(&rule.kind, blocks, false)?;
+
+                // The rows are deleted before the objects, so that a failed deletion does not leave
+                // rows referencing deleted objects. A trace cannot be read without its artifacts,
+                // the trace of an expired artifact is deleted along with its rows.
+                let mut connection = self.connection();
+                let tx = connection.transaction()?;
+                for (key, _) in &expired {
+                    tx.execute("DELETE FROM artifact_object WHERE key = ?", (key,))?;
+                }
+                if TRACE_ARTIFACTS.iter().any(|(kind, _)| kind.as_str() == rule.kind) {
+                    let numbers: BTreeSet<_> = expired.iter().map(|(_, number)| *number).collect();
+                    for number in numbers {
+                        tx.execute(
+                            "DELETE FROM trace WHERE CAST(number AS INTEGER) = ?",
+                            (number,),
+                        )?;
+                    }
+                }
+                tx.commit()?;
+                drop(connection);
+
+                let keys: Vec<_> = expired.into_iter().map(|(key, _)| key).collect();
+                report.expired += keys.len();
+                self.delete_objects(&keys);
+            }
+            if let Some(blocks) = rule.cache_blocks {
+                for (key, _) in keys(&rule.kind, blocks, true)? {
+                    store.evict(&key)?;
+                    self.connection()
+                        .execute("UPDATE artifact_object SET cached = 0 WHERE key = ?", (&key,))?;
+                    report.evicted += 1;
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Deletes objects from the object storage, an object failing to be deleted being orphaned.
+    fn delete_objects(&self, keys: &[String]) {
+        let Some(store) = &self.store else { return };
+        for key in keys {
+            if let Err(err) = store.delete(key) {
+                warn!(%key, %err, "Failed to delete artifact");
+            }
+        }
+    }
+
+    /// Decompresses an artifact read from the database or the object storage, verifying its content
+    /// against its checksum, if recorded, before upgrading it to the current version of its format.
+    fn verified_artifact(&self, artifact: &ArtifactRef, data: &[u8]) -> eyre::Result<Vec<u8>> {
+        let checksum = self.artifact_checksum(artifact)?;
+        self.decode(|codec| {
+            let (header, content) = codec.unpack(artifact.kind.parse()?, data)?;
+            artifact.verify(checksum, &content)?;
+            Ok(codec.migrations().upgrade(header.kind, header.version, content)?)
+        })
+    }
+
+    /// Retrieves the recorded checksum of an artifact, if any.
+    pub fn artifact_checksum(&self, artifact: &ArtifactRef) -> eyre::Result<Option<B256>> {
+        let checksum = self.connection().query_row::<String, _, _>(
+            "SELECT checksum FROM artifact_checksum WHERE kind = ? AND number = ? AND name = ?",
+            (&artifact.kind, artifact.number as i64, &artifact.name),
+            |row| row.get(0),
+        );
+
+        match checksum {
+            Ok(checksum) => Ok(Some(checksum.parse()?)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Retrieves the references of all the persisted artifacts, i.e. the artifacts with a
+    /// checksum and the artifacts tiered to the object storage.
+    pub fn artifact_refs(&self) -> eyre::Result<Vec<ArtifactRef>> {
+        let connection = self.connection();
+        let mut artifacts = BTreeSet::new();
+
+        let mut statement =
+            connection.prepare("SELECT kind, number, name FROM artifact_checksum")?;
+        let rows = statement.query_map([], |row| {
+            Ok(ArtifactRef {
+                kind: row.get(0)?,
+                number: row.get::<_, i64>(1)? as u64,
+                name: row.get(2)?,
+            })
+        })?;
+        for artifact in rows {
+            artifacts.insert(artifact?);
+        }
+
+        // The keys of the tiered artifacts are `kind/number/name`.
+        let mut statement = connection.prepare("SELECT key FROM artifact_object")?;
+        let rows = statement.query_map([], |row| row.get::<_, String>(0))?;
+        for key in rows {
+            let key = key?;
+            let mut parts = key.splitn(3, '/');
+            if let (Some(kind), Some(Ok(number)), Some(name)) =
+                (parts.next(), parts.next().map(str::parse), parts.next())
+            {
+                artifacts.insert(ArtifactRef::new(kind, number, name));
+            }
+        }
+
+        Ok(artifacts.into_iter().collect())
+    }
+
+    /// Retrieves the stored content of an artifact, compressed or not, from the database or the
+    /// object storage, `None` when missing.
+    pub fn raw_artifact(&self, artifact: &ArtifactRef) -> eyre::Result<Option<Vec<u8>>> {
+        let column = TRACE_ARTIFACTS
+            .into_iter()
+            .find(|(kind, column)| kind.as_str() == artifact.kind && *column == artifact.name)
+            .map(|(_, column)| ("trace", column))
+            .or_else(|| (artifact == &proof_ref(artifact.number)).then_some(("proof", "proof")));
+
+        if let Some((table, column)) = column {
+            let data = self.connection().query_row(
+                &format!("SELECT {column} FROM {table} WHERE number = ?"),
+                (artifact.number.to_string(),),
+                |row| optional_artifact(row, 0),
+            );
+            match data {
+                Ok(Some(data)) => return Ok(Some(data)),
+                // The artifact is tiered to the object storage.
+                Ok(None) => {}
+                Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        match &self.store {
+            Some(store) => Ok(store.get(&artifact.key())?),
+            None => Ok(None),
+        }
+    }
+
+    /// Deletes the execution trace of a block, with the checksums and the tiered objects of its
+    /// artifacts.
+    ///
+    /// The objects are deleted once the rows referencing them are, as in [`Self::revert_blocks`].
+    pub fn delete_execution_trace(&self, number: u64) -> eyre::Result<()> {
+        // Acquire a database connection and begin a transaction.
+        let mut connection = self.connection();
+        let tx = connection.transaction()?;
+
+        tx.execute("DELETE FROM trace WHERE number = ?", (number.to_string(),))?;
+        let mut keys = Vec::new();
+        for (kind, column) in TRACE_ARTIFACTS {
+            tx.execute(
+                "DELETE FROM artifact_checksum WHERE kind = ? AND number = ? AND name = ?",
+                (kind.as_str(), number as i64, column),
+            )?;
+            let key = object_key(kind.as_str(), number, column);
+            if tx.execute("DELETE FROM artifact_object WHERE key = ?", (&key,))? > 0 {
+                keys.push(key);
+            }
+        }
+
+        // Commit the transaction to persist all changes.
+        tx.commit()?;
+        drop(connection);
+
+        self.delete_objects(&keys);
+        Ok(())
+    }
+
+    /// Deletes an artifact tiered to the object storage, with its checksum.
+    pub fn delete_tiered_artifact(&self, artifact: &ArtifactRef) -> eyre::Result<()> {
+        // Acquire a database connection and begin a transaction.
+        let mut connection = self.connection();
+        let tx = connection.transaction()?;
+
+        let key = artifact.key();
+        let deleted = tx.execute("DELETE FROM artifact_object WHERE key = ?", (&key,))?;
+        tx.execute(
+            "DELETE FROM artifact_checksum WHERE kind = ? AND number = ? AND name = ?",
+            (&artifact.kind, artifact.number as i64, &artifact.name),
+        )?;
+
+        // Commit the transaction to persist all changes.
+        tx.commit()?;
+        drop(connection);
+
+        if deleted > 0 {
+            self.delete_objects(&[key]);
+        }
+        Ok(())
+    }
+
+    /// Deletes the proof of a block, with its checksum.
+    pub fn delete_proof(&self, number: u64) -> eyre::Result<()> {
+        // Acquire a database connection and begin a transaction.
+        let mut connection = self.connection();
+        let tx = connection.transaction()?;
+
+        tx.execute("DELETE FROM proof WHERE number = ?", (number.to_string(),))?;
+        tx.execute(
+            "DELETE FROM artifact_checksum WHERE kind = ? AND number = ? AND name = ?",
+            (ArtifactKind::Proof.as_str(), number as i64, "proof"),
+        )?;
+
+        // Commit the transaction to persist all changes.
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Rolls back the results persisted for the blocks from the given number onwards, e.g. when
+    /// they are reverted by a reorg, returning the number of deleted rows.
+    ///
+    /// The artifacts tiered to the object storage are deleted from it once the rows are deleted,
+    /// so that a failed rollback does not leave rows referencing deleted objects. The accounts, the
+    /// campaigns and the failure counts are kept.
+    pub fn revert_blocks(&self, from: u64) -> eyre::Result<usize> {
+        // Acquire a database connection and begin a transaction.
+        let mut connection = self.connection();
+        let tx = connection.transaction()?;
+
+        // Read the keys of the tiered artifacts, lost once their rows are deleted.
+        let keys = {
+            let mut statement = tx.prepare("SELECT key FROM artifact_object WHERE number >= ?")?;
+            let rows = statement.query_map((from as i64,), |row| row.get::<_, String>(0))?;
+            rows.collect::<Result<Vec<_>, _>>()?
+        };
+
+        let mut deleted = 0;
+        for table in REVERTED_TABLES {
+            deleted += tx.execute(
+                &format!("DELETE FROM {table} WHERE CAST(number AS INTEGER) >= ?"),
+                (from as i64,),
+            )?;
+        }
+
+        // Commit the transaction to persist all changes.
+        tx.commit()?;
+        drop(connection);
+
+        // Delete the tiered artifacts.
+        self.delete_objects(&keys);
+        Ok(deleted)
+    }
+
+    /// Records a failure of a block, incrementing the count of its fingerprint, and returns the
+    /// failures of the fingerprint.
+    ///
+    /// The summary of the first failure of the fingerprint is kept.
+    pub fn record_failure(
+        &self,
+        fingerprint: &str,
+        summary: &str,
+        number: u64,
+        timestamp: u64,
+    ) -> eyre::Result<RecurringFailure> {
+        Ok(self.connection().query_row(
+            "INSERT INTO failure (fingerprint, summary, count, first_block, last_block, first_seen, last_seen)
+            VALUES (?1, ?2, 1, ?3, ?3, ?4, ?4)
+            ON CONFLICT(fingerprint) DO UPDATE SET
+                count = count + 1, last_block = excluded.last_block, last_seen = excluded.last_seen
+            RETURNING fingerprint, summary, count, first_block, last_block, first_seen, last_seen",
+            (fingerprint, summary, number as i64, timestamp as i64),
+            recurring_failure,
+        )?)
+    }
+
+    /// Retrieves at most `limit` recurring failures, the most frequent first.
+    pub fn recurring_failures(&self, limit: usize) -> eyre::Result<Vec<RecurringFailure>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(
+            "SELECT fingerprint, summary, count, first_block, last_block, first_seen, last_seen
+            FROM failure ORDER BY count DESC, last_seen DESC LIMIT ?",
+        )?;
+        let rows = statement.query_map((limit as i64,), recurring_failure)?;
+
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// Inserts a new account if it doesn't exist or updates it if it does.
+    pub fn set_account(&self, address: Address, account_info: AccountInfo) -> eyre::Result<()> {
+        self.connection().execute(
+            "INSERT INTO account (address, data) VALUES (?, ?) ON CONFLICT(address) DO UPDATE SET data = excluded.data",
+            (address.to_string(), serde_json::to_string(&account_info)?),
+        )?;
+
+        Ok(())
+    }
+
+    /// Retrieves an account from the database using its address.
+    pub fn account(&self, address: Address) -> eyre::Result<Option<AccountInfo>> {
+        match self.connection().query_row::<String, _, _>(
+            "SELECT data FROM account WHERE address = ?",
+            (address.to_string(),),
+            |row| row.get(0),
+        ) {
+            Ok(account_info) => Ok(Some(serde_json::from_str(&account_info)?)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl reth_revm::Database for Database {
+    type Error = eyre::Report;
+
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        self.account(address)
+    }
+
+    fn code_by_hash(&mut self, _code_hash: B256) -> Result<Bytecode, Self::Error> {
+        Ok(Default::default())
+    }
+
+    fn storage(&mut self, _address: Address, _index: U256) -> Result<U256, Self::Error> {
+        Ok(Default::default())
+    }
+
+    fn block_hash(&mut self, number: u64) -> Result<B256, Self::Error> {
+        let block_hash = self.connection().query_row::<String, _, _>(
+            "SELECT hash FROM block WHERE number = ?",
+            (number.to_string(),),
+            |row| row.get(0),
+        );
+        match block_hash {
+            Ok(data) => Ok(B256::from_str(&data).unwrap()),
+            // No special handling for `QueryReturnedNoRows` is needed, because revm does block
+            // number bound checks on its own.
+            // See https://github.com/bluealloy/revm/blob/1ca3d39f6a9e9778f8eb0fcb74fe529345a531b4/crates/interpreter/src/instructions/host.rs#L106-L123.
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// The artifacts of an execution trace, by column of the `trace` table.
+const TRACE_ARTIFACTS: [(ArtifactKind, &str); 4] = [
+    (ArtifactKind::Trace, "execution"),
+    (ArtifactKind::Memory, "memory"),
+    (ArtifactKind::AirInput, "air_public_input"),
+    (ArtifactKind::AirInput, "air_private_input"),
+];
+
+/// The tables of the results persisted per block, rolled back when the block is reverted.
+const REVERTED_TABLES: [&str; 21] = [
+    "block",
+    "trace",
+    "transaction_resources",
+    "partial_run",
+    "retry",
+    "proving_job",
+    "program_input",
+    "proof",
+    "log",
+    "run_profile",
+    "program_output",
+    "block_program",
+    "fact",
+    "sharp_job",
+    "segment_commitment",
+    "prover_result",
+    "proving_retry",
+    "proof_chain",
+    "blob_witness",
+    "artifact_object",
+    "artifact_checksum",
+];
+
+/// Records the key of an artifact tiered to the object storage, with a cached copy.
+fn insert_artifact_object(
+    connection: &Connection,
+    number: u64,
+    kind: &str,
+    key: &str,
+) -> rusqlite::Result<usize> {
+    connection.execute(
+        "INSERT OR REPLACE INTO artifact_object (number, kind, key, cached) VALUES (?, ?, ?, 1)",
+        (number as i64, kind, key),
+    )
+}
+
+/// Records the checksum of the content of an artifact, replacing a previous one.
+fn insert_artifact_checksum(
+    connection: &Connection,
+    artifact: &ArtifactRef,
+    checksum: B256,
+) -> rusqlite::Result<usize> {
+    connection.execute(
+        "INSERT OR REPLACE INTO artifact_checksum (number, kind, name, checksum) VALUES (?, ?, ?, ?)",
+        (artifact.number as i64, &artifact.kind, &artifact.name, checksum.to_string()),
+    )
+}
+
+/// Reads a recurring failure from a row of the `failure` table.
+fn recurring_failure(row: &Row<'_>) -> rusqlite::Result<RecurringFailure> {
+    Ok(RecurringFailure {
+        fingerprint: row.get(0)?,
+        summary: row.get(1)?,
+        count: row.get::<_, i64>(2)? as u64,
+        first_block: row.get::<_, i64>(3)? as u64,
+        last_block: row.get::<_, i64>(4)? as u64,
+        first_seen: row.get::<_, i64>(5)? as u64,
+        last_seen: row.get::<_, i64>(6)? as u64,
+    })
+}
+
+/// Returns the reference of the proof of a block, stored in the `proof` table.
+fn proof_ref(number: u64) -> ArtifactRef {
+    ArtifactRef::new(ArtifactKind::Proof.as_str(), number, "proof")
+}
+
+/// Returns the tables and columns storing the artifacts of the given kind.
+const fn artifact_columns(kind: ArtifactKind) -> &'static [(&'static str, &'static str)] {
+    match kind {
+        ArtifactKind::Trace => &[("trace", "execution")],
+        ArtifactKind::Memory => &[("trace", "memory")],
+        ArtifactKind::AirInput => &[("trace", "air_public_input"), ("trace", "air_private_input")],
+        ArtifactKind::Proof => &[("proof", "proof"), ("campaign_block", "proof")],
+        ArtifactKind::Dump => &[],
+        ArtifactKind::Output => &[("program_output", "data")],
+    }
+}
+
+/// Reads an artifact column, `None` once tiered to the object storage.
+fn optional_artifact(row: &Row<'_>, index: usize) -> rusqlite::Result<Option<Vec<u8>>> {
+    match row.get_ref(index)? {
+        ValueRef::Null => Ok(None),
+        _ => artifact(row, index).map(Some),
+    }
+}
+
+/// Reads the first [`MAX_SAMPLE_SIZE`] bytes of a decoded artifact, to train a dictionary on.
+fn sample(reader: impl Read) -> io::Result<Vec<u8>> {
+    let mut sample = Vec::new();
+    reader.take(MAX_SAMPLE_SIZE as u64).read_to_end(&mut sample)?;
+    Ok(sample)
+}
+
+/// Reads an artifact column, stored as a blob once compressed and as text before.
+fn artifact(row: &Row<'_>, index: usize) -> rusqlite::Result<Vec<u8>> {
+    match row.get_ref(index)? {
+        ValueRef::Blob(data) | ValueRef::Text(data) => Ok(data.to_vec()),
+        value => Err(rusqlite::Error::InvalidColumnType(
+            index,
+            row.as_ref().column_name(index)?.to_string(),
+            value.data_type(),
+        )),
+    }
 }
@@ -0,0 +1,775 @@
+//! Deferred proving of the blocks.
+//!
+//! In deferred mode, an instance only executes the blocks while following the tip, with a dry run
+//! of the Kakarot program which records no trace: each block then enqueues a durable
+//! [`ProvingJob`], along with its program input. Queued jobs are later exported as a bundle of
+//! Cairo PIEs, to be proven on separate hardware, the output of each run being recorded for the
+//! proofs, and the produced proofs are imported back and linked to their blocks.
+//!
+//! A bundle is a directory holding a `manifest.json` [`BundleManifest`] and a `{number}.pie.zip`
+//! file per job. Proofs are imported from a directory holding a `proofs.json` [`ProofManifest`]
+//! and the proof files it references.
+
+use crate::{
+    artifacts::PIE_KIND,
+    db::Database,
+    hints::KakarotHintProcessor,
+    input::program_input::ProgramInput,
+    output::{read_output, ProgramOutput, ProofMetadata},
+    quorum::{self, ProverResult, QuorumConfig, QuorumError, QuorumOutcome},
+    retry::{self, RetryPolicy, RetryState, WorkerSize},
+    serde::cache::ProgramLayoutCache,
+    store::KethStore,
+    telemetry::{self, Stage},
+    verifier::VerifierRegistry,
+};
+use alloy_primitives::B256;
+use cairo_vm::{
+    cairo_run::{cairo_run, CairoRunConfig},
+    types::{layout_name::LayoutName, program::Program},
+    vm::runners::cairo_runner::CairoRunner,
+};
+use reth_tracing::tracing::warn;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+
+/// The file name of the manifest of an exported bundle.
+pub const BUNDLE_MANIFEST: &str = "manifest.json";
+
+/// The file name of the manifest of the proofs to import.
+pub const PROOF_MANIFEST: &str = "proofs.json";
+
+/// The version of the bundle format, bumped on breaking changes.
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Represents errors that can occur in deferred proving.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DeferredError {
+    /// Error variant indicating an unknown proving mode.
+    #[error("Unknown proving mode '{0}', expected 'inline', 'deferred' or 'disabled'")]
+    UnknownMode(String),
+
+    /// Error variant indicating an unknown proving job state.
+    #[error("Unknown proving job state '{0}'")]
+    UnknownState(String),
+
+    /// Error variant indicating a proof of a block without proving job.
+    #[error("No proving job for block {0}")]
+    UnknownJob(u64),
+
+    /// Error variant indicating a result of a proving job which is not being proven.
+    #[error("Proving job of block {block_number} is {state}, not exported")]
+    NotExported {
+        /// The number of the block.
+        block_number: u64,
+        /// The state of the job.
+        state: JobState,
+    },
+
+    /// Error variant indicating a job exported without the program input of its block.
+    #[error("No program input for block {0}")]
+    MissingInput(u64),
+
+    /// Error variant indicating a run of a job without the output of its block.
+    #[error("Missing or invalid program output for block {0}")]
+    MissingOutput(u64),
+
+    /// Error variant indicating a proof of another program than the one selected for its block.
+    #[error("Proof of block {block_number} for program {actual}, expected program {expected}")]
+    ProgramMismatch {
+        /// The number of the block.
+        block_number: u64,
+        /// The hash of the program selected for the block.
+        expected: B256,
+        /// The hash of the program of the proof.
+        actual: B256,
+    },
+}
+
+/// The proving mode of an instance.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProvingMode {
+    /// The Kakarot program is run for each block, its trace being stored for the prover.
+    #[default]
+    Inline,
+    /// Each block is executed by a dry run and enqueues a proving job, exported and proven later.
+    Deferred,
+    /// Execution only: the Kakarot program is run for each block and its trace stored, for the
+    /// differential checks and the trace RPCs, but the blocks are never proven. No commitment,
+    /// proving job nor proof is recorded, and no verifier parameters are loaded.
+    Disabled,
+}
+
+impl ProvingMode {
+    /// Returns whether the blocks are proven, i.e. the proving subsystem is enabled.
+    pub const fn is_enabled(&self) -> bool {
+        !matches!(self, Self::Disabled)
+    }
+}
+
+impl FromStr for ProvingMode {
+    type Err = DeferredError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "inline" => Ok(Self::Inline),
+            "deferred" => Ok(Self::Deferred),
+            "disabled" => Ok(Self::Disabled),
+            _ => Err(DeferredError::UnknownMode(s.to_string())),
+        }
+    }
+}
+
+/// The state of a [`ProvingJob`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JobState {
+    /// The job is waiting to be exported.
+    Queued,
+    /// The job was exported in a bundle and is being proven.
+    Exported,
+    /// The proof of the job was imported.
+    Proven,
+    /// The job failed and exhausted its retries.
+    Failed,
+}
+
+impl JobState {
+    /// Returns the name of the state, as stored in the database.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Exported => "exported",
+            Self::Proven => "proven",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+impl fmt::Display for JobState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for JobState {
+    type Err = DeferredError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "queued" => Ok(Self::Queued),
+            "exported" => Ok(Self::Exported),
+            "proven" => Ok(Self::Proven),
+            "failed" => Ok(Self::Failed),
+            _ => Err(DeferredError::UnknownState(s.to_string())),
+        }
+    }
+}
+
+/// A block waiting to be proven.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvingJob {
+    /// The number of the block.
+    pub block_number: u64,
+    /// The absolute path of the Kakarot program proving the block.
+    pub program: PathBuf,
+    /// The UNIX timestamp, in seconds, at which the job was enqueued.
+    pub enqueued_at: u64,
+    /// The UNIX timestamp, in seconds, at which the job was last exported, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exported_at: Option<u64>,
+    /// The number of steps of the run of the exported job, from which its proving duration is
+    /// estimated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub steps: Option<usize>,
+}
+
+impl ProvingJob {
+    /// Creates a new [`ProvingJob`] enqueued now.
+    pub fn new(block_number: u64, program: PathBuf) -> Self {
+        let enqueued_at =
+            SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+        Self { block_number, program, enqueued_at, exported_at: None, steps: None }
+    }
+}
+
+/// An exported proving job.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleEntry {
+    /// The exported job.
+    pub job: ProvingJob,
+    /// The file name of the Cairo PIE of the job, relative to the bundle directory.
+    pub pie: String,
+    /// The size of the worker the job must be proven on.
+    #[serde(default)]
+    pub worker: WorkerSize,
+}
+
+/// The manifest of an exported bundle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleManifest {
+    /// The version of the bundle format.
+    pub version: u32,
+    /// The exported jobs, ordered by block number.
+    pub entries: Vec<BundleEntry>,
+}
+
+/// A proof to import.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofEntry {
+    /// The metadata of the proof, linking it to its block.
+    pub metadata: ProofMetadata,
+    /// The file name of the proof, relative to the proofs directory.
+    pub proof: String,
+    /// The fact claimed by the prover, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fact: Option<B256>,
+}
+
+/// A failed proving job reported by the prover.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailureEntry {
+    /// The number of the block of the job.
+    pub block_number: u64,
+    /// The error of the prover.
+    pub error: String,
+}
+
+/// The manifest of the proofs to import.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofManifest {
+    /// The proofs to import.
+    pub proofs: Vec<ProofEntry>,
+    /// The failed jobs, handled according to the retry policy.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failures: Vec<FailureEntry>,
+}
+
+/// Exports at most `limit` queued jobs as a bundle in `dir`, marking them as exported.
+///
+/// The Kakarot program of each job is run in the Cairo VM on the program input of its block to
+/// produce its PIE, and the output of the run is recorded in `store`. Jobs whose retry backoff did
+/// not elapse are left queued, and retried jobs are routed to the worker size of their retry state.
+pub fn export_bundle(
+    db: &Database,
+    store: &dyn KethStore,
+    dir: &Path,
+    limit: usize,
+) -> eyre::Result<BundleManifest> {
+    let jobs = db
+        .due_proving_jobs(retry::now(), limit)?
+        .into_iter()
+        .map(|job| {
+            let worker = db.proving_retry(job.block_number)?.map(|state| state.worker);
+            Ok((job, worker.unwrap_or_default()))
+        })
+        .collect::<eyre::Result<_>>()?;
+    let manifest = write_bundle(db, store, dir, jobs)?;
+
+    // The PIEs are tiered to the object storage, if any, to be proven again later.
+    for entry in &manifest.entries {
+        let pie = fs::read(dir.join(&entry.pie))?;
+        db.store_artifact(PIE_KIND, entry.job.block_number, &entry.pie, pie)?;
+    }
+
+    // Jobs are only marked once the manifest is written, so that a failed export is retried.
+    for entry in &manifest.entries {
+        db.mark_proving_job_exported(&entry.job)?;
+    }
+
+    Ok(manifest)
+}
+
+/// Writes the PIEs of the jobs and their manifest as a bundle in `dir`, recording the output of
+/// each run in `store`.
+pub(crate) fn write_bundle(
+    db: &Database,
+    store: &dyn KethStore,
+    dir: &Path,
+    jobs: Vec<(ProvingJob, WorkerSize)>,
+) -> eyre::Result<BundleManifest> {
+    fs::create_dir_all(dir)?;
+
+    let mut entries = Vec::new();
+    for (mut job, worker) in jobs {
+        let pie = pie_file_name(job.block_number);
+        let runner = write_pie(&job, &job_input(db, job.block_number)?, &dir.join(&pie))?;
+        record_output(store, job.block_number, &runner)?;
+        job.exported_at = Some(retry::now());
+        job.steps = Some(runner.get_execution_resources()?.n_steps);
+        entries.push(BundleEntry { job, pie, worker });
+    }
+
+    let manifest = BundleManifest { version: BUNDLE_VERSION, entries };
+    fs::write(dir.join(BUNDLE_MANIFEST), serde_json::to_vec_pretty(&manifest)?)?;
+    Ok(manifest)
+}
+
+/// Returns the file name of the Cairo PIE of a block.
+pub(crate) fn pie_file_name(block_number: u64) -> String {
+    format!("{block_number}.pie.zip")
+}
+
+/// Returns the program input of the block of a job, recorded when the job was enqueued.
+pub(crate) fn job_input(db: &Database, block_number: u64) -> eyre::Result<ProgramInput> {
+    Ok(db.program_input(block_number)?.ok_or(DeferredError::MissingInput(block_number))?)
+}
+
+/// Runs the Kakarot program of a job on the program input of its block and writes its Cairo PIE
+/// to `path`, returning the ended runner.
+pub(crate) fn write_pie(
+    job: &ProvingJob,
+    input: &ProgramInput,
+    path: &Path,
+) -> eyre::Result<CairoRunner> {
+    let _span = telemetry::stage_span(Stage::Prove, job.block_number).entered();
+    let program = fs::read(&job.program)?;
+    let layouts = Arc::new(ProgramLayoutCache::new(&Program::from_bytes(&program, Some("main"))?));
+    let config = CairoRunConfig { layout: LayoutName::all_cairo, ..Default::default() };
+    let mut hint_processor =
+        KakarotHintProcessor::default().with_program_input(layouts, Arc::new(input.clone()));
+    let runner = cairo_run(&program, &config, &mut hint_processor)?;
+    runner.get_cairo_pie()?.write_zip_file(path)?;
+    Ok(runner)
+}
+
+/// Records the output of the run of a job, from which the facts of its proofs are computed.
+pub(crate) fn record_output(
+    store: &dyn KethStore,
+    block_number: u64,
+    runner: &CairoRunner,
+) -> eyre::Result<ProgramOutput> {
+    let output = read_output(runner)?
+        .and_then(|felts| ProgramOutput::from_felts(&felts).ok())
+        .filter(|output| output.block_number == block_number)
+        .ok_or(DeferredError::MissingOutput(block_number))?;
+    store.insert_program_output(&output)?;
+    Ok(output)
+}
+
+/// Reads the manifest of the proofs to import in `dir`.
+pub(crate) fn read_proof_manifest(dir: &Path) -> eyre::Result<ProofManifest> {
+    Ok(serde_json::from_slice(&fs::read(dir.join(PROOF_MANIFEST))?)?)
+}
+
+/// Imports the proofs of the manifest in `dir`, marking their jobs as proven.
+///
+/// The proofs and failures must be results of exported jobs. The manifest is validated and its
+/// proofs are read before anything is recorded, so that an invalid import records nothing. The
+/// proofs are then inserted in `store`, and the states of their jobs updated in a single
+/// transaction.
+///
+/// The proofs must be proofs of the program selected for their block, if recorded in `store`, see
+/// [`crate::program::ProgramRegistry`], and generated with the parameters of their verifier when a
+/// [`VerifierRegistry`] is given. The other proofs are rejected, their jobs being handled as
+/// failed, and the import goes on with the next proofs.
+///
+/// With a quorum, the proofs are recorded as the results of their provers, and a job is only
+/// marked as proven once all the provers of the quorum agree on its fact. The failed jobs of the
+/// manifest are queued again or marked as failed according to the retry policy.
+///
+/// Returns the numbers of the blocks proven by the import.
+pub fn import_proofs(
+    db: &Database,
+    store: &dyn KethStore,
+    dir: &Path,
+    quorum: Option<&QuorumConfig>,
+    verifiers: Option<&VerifierRegistry>,
+    policy: &RetryPolicy,
+) -> eyre::Result<Vec<u64>> {
+    let manifest = read_proof_manifest(dir)?;
+
+    let numbers = manifest
+        .failures
+        .iter()
+        .map(|failure| failure.block_number)
+        .chain(manifest.proofs.iter().map(|entry| entry.metadata.block_number));
+    for number in numbers {
+        match db.proving_job_state(number)? {
+            Some(JobState::Exported) => {}
+            Some(state) => {
+                return Err(DeferredError::NotExported { block_number: number, state }.into())
+            }
+            None => return Err(DeferredError::UnknownJob(number).into()),
+        }
+    }
+    if let Some(quorum) = quorum {
+        if let Some(entry) =
+            manifest.proofs.iter().find(|entry| !quorum.provers.contains(&entry.metadata.prover))
+        {
+            return Err(QuorumError::UnknownProver(entry.metadata.prover.clone()).into());
+        }
+    }
+
+    // The retry states of the failed jobs, a job failing twice in the manifest counting two
+    // attempts.
+    let mut failed: BTreeMap<u64, RetryState> = BTreeMap::new();
+    let mut fail = |number: u64, error: &str| -> eyre::Result<()> {
+        let previous = match failed.remove(&number) {
+            Some(state) => Some(state),
+            None => db.proving_retry(number)?,
+        };
+        failed.insert(number, policy.on_failure(previous.as_ref(), number, error, retry::now()));
+        Ok(())
+    };
+    for failure in &manifest.failures {
+        fail(failure.block_number, &failure.error)?;
+    }
+
+    let mut proofs = Vec::new();
+    for entry in manifest.proofs {
+        let number = entry.metadata.block_number;
+        if let Some(program) = store.block_program(number)? {
+            if program.program_hash != entry.metadata.program_hash {
+                let err = DeferredError::ProgramMismatch {
+                    block_number: number,
+                    expected: program.program_hash,
+                    actual: entry.metadata.program_hash,
+                };
+                warn!(target: "kkrt::deferred", number, %err, "Rejected proof");
+                fail(number, &err.to_string())?;
+                continue;
+            }
+        }
+        let proof = fs::read(dir.join(&entry.proof))?;
+        if let Some(Err(err)) =
+            verifiers.map(|verifiers| verifiers.check_proof(&entry.metadata, &proof))
+        {
+            warn!(target: "kkrt::deferred", number, %err, "Rejected proof");
+            fail(number, &err.to_string())?;
+            continue;
+        }
+        proofs.push((entry, proof));
+    }
+
+    // The proofs are inserted before their jobs are marked as proven, so that the proofs of an
+    // import failing in between are imported again.
+    let mut imported = Vec::new();
+    for (entry, proof) in proofs {
+        let number = entry.metadata.block_number;
+        match quorum {
+            Some(quorum) => {
+                let result = ProverResult { metadata: entry.metadata, fact: entry.fact };
+                if !matches!(
+                    quorum::record_result(db, store, quorum, &result, &proof)?,
+                    QuorumOutcome::Agreed(_)
+                ) {
+                    continue;
+                }
+            }
+            None => store.insert_proof(&entry.metadata, &proof)?,
+        }
+        imported.push(number);
+    }
+
+    let failed: Vec<_> = failed.into_values().collect();
+    db.commit_proof_import(&imported, &failed)?;
+    failed.iter().for_each(retry::report_failure);
+
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        input::program_input::{BlockInput, HeaderInput},
+        program::BlockProgram,
+        store::RedbStore,
+        verifier::{ChannelHash, VerifierEntry, VerifierError, VerifierParams},
+    };
+    use alloy_consensus::Header;
+    use alloy_primitives::Address;
+    use rusqlite::Connection;
+
+    fn setup_db() -> Database {
+        Database::new(Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    fn testdata_program() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/keccak_add_uint256.json")
+    }
+
+    /// Enqueues the job of an empty block proven by the Kakarot OS, with its program input.
+    fn enqueue_os_job(db: &Database, number: u64) {
+        let header = Header {
+            number,
+            gas_limit: 30_000_000,
+            base_fee_per_gas: Some(7),
+            ..Default::default()
+        };
+        let input = ProgramInput {
+            block: BlockInput {
+                block_header: HeaderInput::from(&header),
+                transactions: Vec::new(),
+            },
+            state: BTreeMap::new(),
+            chain_id: 1,
+            system_calls: Vec::new(),
+            block_hashes: BTreeMap::new(),
+        };
+        let program = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../cairo/programs/os.json");
+        db.insert_program_input(number, &input).unwrap();
+        db.enqueue_proving_job(&ProvingJob::new(number, program)).unwrap();
+    }
+
+    /// Enqueues a job and marks it as exported, awaiting its proof.
+    fn export_job(db: &Database, number: u64) {
+        db.enqueue_proving_job(&ProvingJob::new(number, testdata_program())).unwrap();
+        db.set_proving_job_state(number, JobState::Exported).unwrap();
+    }
+
+    #[test]
+    fn test_parse_proving_mode() {
+        assert_eq!("deferred".parse(), Ok(ProvingMode::Deferred));
+        assert_eq!("inline".parse(), Ok(ProvingMode::Inline));
+        assert_eq!("disabled".parse(), Ok(ProvingMode::Disabled));
+        assert!(ProvingMode::Deferred.is_enabled() && !ProvingMode::Disabled.is_enabled());
+        assert_eq!(
+            "later".parse::<ProvingMode>(),
+            Err(DeferredError::UnknownMode("later".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_export_bundle() {
+        let db = setup_db();
+        let dir = tempfile::tempdir().unwrap();
+        enqueue_os_job(&db, 1);
+        enqueue_os_job(&db, 2);
+
+        let manifest = export_bundle(&db, &db, dir.path(), 1).unwrap();
+
+        assert_eq!(manifest.entries.len(), 1);
+        assert_eq!(manifest.entries[0].job.block_number, 1);
+        assert!(dir.path().join("1.pie.zip").exists());
+        assert!(dir.path().join(BUNDLE_MANIFEST).exists());
+        assert_eq!(db.proving_job_state(1).unwrap(), Some(JobState::Exported));
+        assert_eq!(db.proving_job_state(2).unwrap(), Some(JobState::Queued));
+
+        // The export is recorded with the steps of the run, from which the proving is estimated.
+        let exported = db.proving_jobs(JobState::Exported, 10).unwrap();
+        assert_eq!(exported, vec![manifest.entries[0].job.clone()]);
+        assert!(exported[0].exported_at.is_some());
+        assert!(exported[0].steps.is_some_and(|steps| steps > 0));
+
+        // The output of the run is recorded for the proofs of the block.
+        assert_eq!(db.program_output(1).unwrap().map(|output| output.block_number), Some(1));
+        assert_eq!(db.program_output(2).unwrap(), None);
+    }
+
+    #[test]
+    fn test_export_bundle_missing_input() {
+        let db = setup_db();
+        let dir = tempfile::tempdir().unwrap();
+        db.enqueue_proving_job(&ProvingJob::new(1, testdata_program())).unwrap();
+
+        let err = export_bundle(&db, &db, dir.path(), 1).unwrap_err();
+
+        assert_eq!(err.downcast_ref(), Some(&DeferredError::MissingInput(1)));
+        assert_eq!(db.proving_job_state(1).unwrap(), Some(JobState::Queued));
+    }
+
+    #[test]
+    fn test_import_proofs() {
+        let db = setup_db();
+        let dir = tempfile::tempdir().unwrap();
+        export_job(&db, 1);
+
+        let metadata = ProofMetadata { block_number: 1, ..Default::default() };
+        let entry = ProofEntry { metadata, proof: "1.proof".to_string(), fact: None };
+        let manifest = ProofManifest { proofs: vec![entry], failures: vec![] };
+        fs::write(dir.path().join(PROOF_MANIFEST), serde_json::to_vec(&manifest).unwrap()).unwrap();
+        fs::write(dir.path().join("1.proof"), b"proof").unwrap();
+
+        assert_eq!(
+            import_proofs(&db, &db, dir.path(), None, None, &RetryPolicy::default()).unwrap(),
+            vec![1]
+        );
+        assert_eq!(db.proving_job_state(1).unwrap(), Some(JobState::Proven));
+        assert_eq!(db.proof(1).unwrap().map(|(_, proof)| proof), Some(b"proof".to_vec()));
+    }
+
+    #[test]
+    fn test_import_proofs_to_redb_store() {
+        let db = setup_db();
+        let store = RedbStore::in_memory().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        export_job(&db, 1);
+
+        let metadata = ProofMetadata { block_number: 1, ..Default::default() };
+        let entry = ProofEntry { metadata, proof: "1.proof".to_string(), fact: None };
+        let manifest = ProofManifest { proofs: vec![entry], failures: vec![] };
+        fs::write(dir.path().join(PROOF_MANIFEST), serde_json::to_vec(&manifest).unwrap()).unwrap();
+        fs::write(dir.path().join("1.proof"), b"proof").unwrap();
+
+        let policy = RetryPolicy::default();
+        assert_eq!(import_proofs(&db, &store, dir.path(), None, None, &policy).unwrap(), vec![1]);
+        // The job state stays in the database, the proof goes to the store.
+        assert_eq!(db.proving_job_state(1).unwrap(), Some(JobState::Proven));
+        assert_eq!(db.proof(1).unwrap(), None);
+        assert_eq!(store.proof(1).unwrap().map(|(_, proof)| proof), Some(b"proof".to_vec()));
+    }
+
+    #[test]
+    fn test_import_failures() {
+        let db = setup_db();
+        let dir = tempfile::tempdir().unwrap();
+        export_job(&db, 1);
+
+        let failure = FailureEntry { block_number: 1, error: "out of memory".to_string() };
+        let manifest = ProofManifest { proofs: vec![], failures: vec![failure] };
+        fs::write(dir.path().join(PROOF_MANIFEST), serde_json::to_vec(&manifest).unwrap()).unwrap();
+
+        assert!(import_proofs(&db, &db, dir.path(), None, None, &RetryPolicy::default())
+            .unwrap()
+            .is_empty());
+        assert_eq!(db.proving_job_state(1).unwrap(), Some(JobState::Queued));
+        assert_eq!(db.proving_retry(1).unwrap().map(|state| state.worker), Some(WorkerSize::Large));
+    }
+
+    #[test]
+    fn test_import_proofs_unknown_job() {
+        let db = setup_db();
+        let dir = tempfile::tempdir().unwrap();
+        let metadata = ProofMetadata { block_number: 7, ..Default::default() };
+        let entry = ProofEntry { metadata, proof: "7.proof".to_string(), fact: None };
+        let manifest = ProofManifest { proofs: vec![entry], failures: vec![] };
+        fs::write(dir.path().join(PROOF_MANIFEST), serde_json::to_vec(&manifest).unwrap()).unwrap();
+
+        let err =
+            import_proofs(&db, &db, dir.path(), None, None, &RetryPolicy::default()).unwrap_err();
+
+        assert_eq!(err.downcast_ref(), Some(&DeferredError::UnknownJob(7)));
+    }
+
+    #[test]
+    fn test_import_proofs_not_exported() {
+        let db = setup_db();
+        let dir = tempfile::tempdir().unwrap();
+        export_job(&db, 1);
+        db.enqueue_proving_job(&ProvingJob::new(2, testdata_program())).unwrap();
+
+        let proofs = [1, 2]
+            .map(|number| ProofEntry {
+                metadata: ProofMetadata { block_number: number, ..Default::default() },
+                proof: format!("{number}.proof"),
+                fact: None,
+            })
+            .to_vec();
+        let manifest = ProofManifest { proofs, failures: vec![] };
+        fs::write(dir.path().join(PROOF_MANIFEST), serde_json::to_vec(&manifest).unwrap()).unwrap();
+        fs::write(dir.path().join("1.proof"), b"proof").unwrap();
+        fs::write(dir.path().join("2.proof"), b"proof").unwrap();
+
+        let err =
+            import_proofs(&db, &db, dir.path(), None, None, &RetryPolicy::default()).unwrap_err();
+
+        // The import is rejected as a whole, the proof of the exported job included.
+        let expected = DeferredError::NotExported { block_number: 2, state: JobState::Queued };
+        assert_eq!(err.downcast_ref(), Some(&expected));
+        assert_eq!(db.proof(1).unwrap(), None);
+        assert_eq!(db.proving_job_state(1).unwrap(), Some(JobState::Exported));
+    }
+
+    #[test]
+    fn test_import_proofs_program_mismatch() {
+        let db = setup_db();
+        let dir = tempfile::tempdir().unwrap();
+        export_job(&db, 1);
+        db.insert_block_program(&BlockProgram {
+            block_number: 1,
+            fork: Some("cancun".to_string()),
+            program: testdata_program(),
+            program_hash: B256::repeat_byte(1),
+        })
+        .unwrap();
+
+        export_job(&db, 2);
+
+        let proofs = [1, 2]
+            .map(|number| ProofEntry {
+                metadata: ProofMetadata { block_number: number, ..Default::default() },
+                proof: format!("{number}.proof"),
+                fact: None,
+            })
+            .to_vec();
+        let manifest = ProofManifest { proofs, failures: vec![] };
+        fs::write(dir.path().join(PROOF_MANIFEST), serde_json::to_vec(&manifest).unwrap()).unwrap();
+        fs::write(dir.path().join("2.proof"), b"proof").unwrap();
+
+        // The mismatched proof is rejected, the next one is imported.
+        let policy = RetryPolicy::default();
+        assert_eq!(import_proofs(&db, &db, dir.path(), None, None, &policy).unwrap(), vec![2]);
+        assert_eq!(db.proof(1).unwrap(), None);
+        assert_eq!(db.proving_job_state(1).unwrap(), Some(JobState::Queued));
+        let retry = db.proving_retry(1).unwrap().unwrap();
+        let err = DeferredError::ProgramMismatch {
+            block_number: 1,
+            expected: B256::repeat_byte(1),
+            actual: B256::ZERO,
+        };
+        assert_eq!(retry.last_error, err.to_string());
+        assert_eq!(db.proving_job_state(2).unwrap(), Some(JobState::Proven));
+    }
+
+    #[test]
+    fn test_import_proofs_verifier_mismatch() {
+        let db = setup_db();
+        let dir = tempfile::tempdir().unwrap();
+        let verifiers = VerifierRegistry::from_entries(vec![VerifierEntry {
+            program_hash: B256::ZERO,
+            prover_version: "stone-v6".to_string(),
+            params: VerifierParams {
+                layout: "all_cairo".to_string(),
+                channel_hash: ChannelHash::Keccak256Masked160Lsb,
+                verifier: Address::with_last_byte(1),
+                verifier_version: "0.13.2".to_string(),
+            },
+        }])
+        .unwrap();
+
+        let proofs = [(1, "poseidon3"), (2, "keccak256_masked160_lsb")]
+            .map(|(number, channel_hash)| {
+                export_job(&db, number);
+                let proof =
+                    format!(r#"{{"proof_parameters":{{"channel_hash":"{channel_hash}"}}}}"#);
+                fs::write(dir.path().join(format!("{number}.proof")), proof).unwrap();
+                ProofEntry {
+                    metadata: ProofMetadata {
+                        block_number: number,
+                        layout: "all_cairo".to_string(),
+                        prover: "stone-v6".to_string(),
+                        ..Default::default()
+                    },
+                    proof: format!("{number}.proof"),
+                    fact: None,
+                }
+            })
+            .to_vec();
+        let manifest = ProofManifest { proofs, failures: vec![] };
+        fs::write(dir.path().join(PROOF_MANIFEST), serde_json::to_vec(&manifest).unwrap()).unwrap();
+
+        // The proof generated with another channel hash is rejected, the next one is imported.
+        let policy = RetryPolicy::default();
+        assert_eq!(
+            import_proofs(&db, &db, dir.path(), None, Some(&verifiers), &policy).unwrap(),
+            vec![2]
+        );
+        assert_eq!(db.proof(1).unwrap(), None);
+        assert_eq!(db.proving_job_state(1).unwrap(), Some(JobState::Queued));
+        let err = VerifierError::ChannelHashMismatch {
+            expected: ChannelHash::Keccak256Masked160Lsb,
+            actual: ChannelHash::Poseidon3,
+        };
+        assert_eq!(db.proving_retry(1).unwrap().unwrap().last_error, err.to_string());
+        assert_eq!(db.proving_job_state(2).unwrap(), Some(JobState::Proven));
+    }
+}
@@ -0,0 +1,1206 @@
+//! Reports of the divergences between the Cairo and the native executions of a transaction.
+//!
+//! The native execution of a transaction is recorded by revm with a [`StepRecorder`], in the
+//! [`Step`]s decoded from the Cairo execution by [`crate::structlog`], so that both executions are
+//! compared step by step. A [`DivergenceReport`] gathers everything needed to triage a diverging
+//! transaction: its status and gas divergences, the call path from the transaction down to the
+//! first diverging opcode, the stack and memory of both engines at that opcode, and the diverging
+//! storage changes, the storage dicts of the Cairo execution being serialized with the preimages of
+//! their keys recorded by the hints, see [`StoragePreimages`]. The report is rendered as a
+//! self-contained Markdown or HTML file, saved in the artifacts directory of the block.
+//!
+//! In differential mode, see [`InstanceConfig::differential`](crate::instance::InstanceConfig),
+//! each executed block is compared with its native execution on its witness by [`compare_block`],
+//! a report being saved for each diverging transaction. A stored block is compared on demand by
+//! [`compare_stored_block`], e.g. with `keth divergence`.
+
+use crate::{
+    attribution::{felt_to_usize, FrameSpec, EXECUTE_FUNCTION},
+    calltracer::{CallFrame, CallKind},
+    db::Database,
+    execution::configure_chain_block_env,
+    exex::CHAIN_SPEC,
+    halt::{EvmHalt, ExecutionStatus, StatusDivergence},
+    input::{
+        history::{blockhash_reads, HistoryError},
+        program_input::{AccountStateInput, ProgramInput},
+        system::system_call_storage,
+    },
+    output::{AccountDiff, StateDiff},
+    precompute::HintCache,
+    receipts::{CairoOutcome, ExecuteLayout, ReceiptError},
+    refund::{
+        extract_refunds, validate_gas_used, GasDivergence, RefundSpec, LEGACY_MAX_REFUND_QUOTIENT,
+        MAX_REFUND_QUOTIENT,
+    },
+    revert::Revert,
+    serde::{
+        relocated::RelocatedMemory, storage::StoragePreimages, KakarotSerde, KakarotSerdeError,
+    },
+    structlog::{decode_steps, opcode_name, Step, StepLayout},
+};
+use alloy_primitives::{hex, Address, Bytes, B256, U256, U64};
+use cairo_vm::{types::program::Program, vm::trace::trace_entry::RelocatedTraceEntry, Felt252};
+use reth_node_api::{ConfigureEvm, ConfigureEvmEnv};
+use reth_node_ethereum::EthEvmConfig;
+use reth_primitives::{
+    revm_primitives::{EVMError, ExecutionResult, ResultAndState},
+    EthereumHardfork, Receipt, SealedBlockWithSenders, TransactionSigned,
+};
+use reth_revm::{
+    interpreter::{Interpreter, SuccessOrHalt},
+    Database as RevmDatabase, DatabaseCommit, EvmContext, Inspector, StateBuilder,
+};
+use reth_tracing::tracing::debug;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Write},
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+use thiserror::Error;
+
+/// The number of bytes of memory rendered per line.
+const MEMORY_LINE_SIZE: usize = 32;
+
+/// A revm [`Inspector`] recording the steps of a transaction, compared on the fly with the steps of
+/// its Cairo execution.
+///
+/// Only the steps of the transaction at [`StepRecorder::tx_index`] are recorded, which allows
+/// executing the preceding transactions of the block with the same EVM. The recording stops at the
+/// first diverging step, the only one recorded with its memory and return data: the stack and the
+/// memory of the other steps are only kept for the call opcodes, from which the call path is
+/// rebuilt, so that the recorded steps are bounded by the steps of the Cairo execution.
+#[derive(Debug, Default, Clone)]
+pub struct StepRecorder {
+    /// The index of the recorded transaction, nothing is recorded when `None`.
+    pub tx_index: Option<u32>,
+    /// The steps of the Cairo execution of the transaction.
+    cairo: Arc<[Step]>,
+    /// The recorded steps.
+    steps: Vec<Step>,
+    /// Whether the last recorded step diverges from the Cairo execution.
+    diverged: bool,
+    /// Whether the last recorded step awaits its gas cost.
+    pending: bool,
+}
+
+impl StepRecorder {
+    /// Creates a new [`StepRecorder`] recording the transaction at the given index, compared with
+    /// the steps of its Cairo execution.
+    pub const fn new(tx_index: u32, cairo: Arc<[Step]>) -> Self {
+        Self { tx_index: Some(tx_index), cairo, steps: Vec::new(), diverged: false, pending: false }
+    }
+
+    /// Returns the recorded steps and the first diverging step, consuming the recorder.
+    pub fn finish(self) -> (Vec<Step>, Option<StepDivergence>) {
+        let divergence = if self.diverged {
+            let index = self.steps.len() - 1;
+            Some(StepDivergence {
+                index,
+                native: self.steps.last().cloned(),
+                cairo: self.cairo.get(index).cloned(),
+            })
+        } else {
+            // The native execution ended before the Cairo execution.
+            let index = self.steps.len();
+            self.cairo.get(index).cloned().map(|cairo| StepDivergence {
+                index,
+                native: None,
+                cairo: Some(cairo),
+            })
+        };
+        (self.steps, divergence)
+    }
+}
+
+impl<DB: RevmDatabase> Inspector<DB> for StepRecorder {
+    fn step(&mut self, interp: &mut Interpreter, context: &mut EvmContext<DB>) {
+        let Some(tx_index) = self.tx_index else { return };
+        if self.diverged {
+            return;
+        }
+
+        let stack = interp.stack.data();
+        let mut step = Step {
+            tx_index,
+            pc: interp.program_counter() as u64,
+            op: interp.current_opcode(),
+            gas: interp.gas.remaining(),
+            depth: context.journaled_state.depth as u64,
+            refund: interp.gas.refunded().max(0) as u64,
+            address: interp.contract.target_address,
+            ..Default::default()
+        };
+        self.diverged =
+            self.cairo.get(self.steps.len()).is_none_or(|cairo| diverges(&step, stack, cairo));
+        if self.diverged || CallKind::from_opcode(step.op).is_some() {
+            step.stack = stack.clone();
+            step.memory = Bytes::copy_from_slice(interp.shared_memory.context_memory());
+        }
+        if self.diverged {
+            step.return_data = interp.return_data_buffer.clone();
+        }
+        self.steps.push(step);
+        self.pending = true;
+    }
+
+    fn step_end(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        if !std::mem::take(&mut self.pending) {
+            return;
+        }
+        let Some(step) = self.steps.last_mut() else { return };
+        step.gas_cost = step.gas.saturating_sub(interp.gas.remaining());
+        if let SuccessOrHalt::Halt(reason) = SuccessOrHalt::from(interp.instruction_result) {
+            step.error = Some(EvmHalt::from(&reason));
+        }
+    }
+}
+
+/// The native execution of a transaction, recorded by a [`StepRecorder`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NativeExecution {
+    /// The result of the transaction, `None` if it is invalid.
+    pub result: Option<ExecutionResult>,
+    /// The recorded steps, up to the first diverging step.
+    pub steps: Vec<Step>,
+    /// The first step diverging from the Cairo execution, if any.
+    pub divergence: Option<StepDivergence>,
+    /// The storage slots written by the transaction, with their new values, by account.
+    pub storage: BTreeMap<Address, BTreeMap<U256, U256>>,
+}
+
+/// Executes the transactions of a block of the given chain through revm on its pre-state,
+/// recording each of them against its steps in the Cairo execution, by transaction index.
+pub fn record_native_executions<DB>(
+    db: DB,
+    block: &SealedBlockWithSenders,
+    chain_id: u64,
+    cairo: &BTreeMap<u32, Arc<[Step]>>,
+) -> eyre::Result<Vec<NativeExecution>>
+where
+    DB: RevmDatabase,
+    DB::Error: Into<eyre::Report> + fmt::Display,
+{
+    let header = block.header.header();
+    let config = EthEvmConfig::new(CHAIN_SPEC.clone());
+    let mut evm = config
+        .evm_with_inspector(StateBuilder::new_with_database(db).build(), StepRecorder::default());
+    configure_chain_block_env(&config, &mut evm, header, chain_id);
+
+    let mut executions = Vec::with_capacity(block.body.transactions.len());
+    let transactions = block.body.transactions.iter().zip(&block.senders);
+    for (index, (transaction, sender)) in transactions.enumerate() {
+        let tx_index = index as u32;
+        evm.context.external =
+            StepRecorder::new(tx_index, cairo.get(&tx_index).cloned().unwrap_or_default());
+        config.fill_tx_env(evm.tx_mut(), transaction, *sender);
+        let mut storage: BTreeMap<_, BTreeMap<_, _>> = BTreeMap::new();
+        let result = match evm.transact() {
+            Ok(ResultAndState { result, state }) => {
+                for (address, account) in &state {
+                    for (slot, value) in account.storage.iter().filter(|(_, v)| v.is_changed()) {
+                        storage.entry(*address).or_default().insert(*slot, value.present_value);
+                    }
+                }
+                evm.db_mut().commit(state);
+                Some(result)
+            }
+            Err(EVMError::Transaction(err)) => {
+                debug!(%err, hash = %transaction.hash(), "Skipping invalid transaction");
+                None
+            }
+            Err(EVMError::Database(err)) => return Err(err.into()),
+            Err(err) => eyre::bail!("{err}"),
+        };
+        let (steps, divergence) = std::mem::take(&mut evm.context.external).finish();
+        executions.push(NativeExecution { result, steps, divergence, storage });
+    }
+    Ok(executions)
+}
+
+/// Compares the Cairo execution of a block, from its relocated trace and memory, with its native
+/// execution on the witness of its [`ProgramInput`], returning the reports of the diverging
+/// transactions.
+///
+/// A transaction diverges when its status, its revert output, its gas used, see
+/// [`validate_gas_used`], its steps or the storage of the block after it differ between both
+/// executions, or when one of its `BLOCKHASH` reads differs from the block hash history of the
+/// witness. The storage dicts of the Cairo execution are serialized with the given preimages. The
+/// program must execute the transactions, see [`FrameSpec::execute`].
+pub fn compare_block(
+    program: &[u8],
+    input: &ProgramInput,
+    block: &SealedBlockWithSenders,
+    trace: &[RelocatedTraceEntry],
+    memory: &[Felt252],
+    preimages: &StoragePreimages,
+) -> eyre::Result<Vec<DivergenceReport>> {
+    let block_number = block.header.number;
+    let parsed = Program::from_bytes(program, Some("main"))?;
+    let execute = FrameSpec::execute(&parsed)?;
+    let mut steps: BTreeMap<u32, Vec<Step>> = BTreeMap::new();
+    for step in decode_steps(&execute, &StepLayout::from_program(&parsed)?, trace, memory)? {
+        steps.entry(step.tx_index).or_default().push(step);
+    }
+    let cairo: BTreeMap<_, Arc<[Step]>> =
+        steps.into_iter().map(|(tx_index, steps)| (tx_index, steps.into())).collect();
+    let layout = ExecuteLayout::from_program(&parsed)?;
+    let relocated = RelocatedMemory::from_cells(memory.iter().copied().map(Some).collect());
+    let outcome = CairoOutcome::from_trace(
+        program,
+        &layout,
+        block_number,
+        &input.block.transactions,
+        trace,
+        relocated.clone(),
+    )?;
+    let storages = cairo_storages(program, &layout, trace, relocated, preimages)?;
+    // The system calls modeled by the program write their storage before the transactions, the
+    // native execution starts from the state they leave.
+    let system_storage = system_call_storage(block.header.header(), &input.system_calls);
+    let mut db = input.witness_db();
+    for (address, slots) in &system_storage {
+        for (slot, value) in slots {
+            db.insert_account_storage(*address, *slot, *value)?;
+        }
+    }
+    let natives = record_native_executions(db, block, input.chain_id, &cairo)?;
+    let history = input.block_hash_history();
+
+    // The gas used by the Cairo execution, with its refund capped Rust-side, is classified against
+    // the receipts of the native execution.
+    let counters =
+        extract_refunds(&RefundSpec::from_program(&parsed, EXECUTE_FUNCTION)?, trace, memory)?;
+    let quotient = if CHAIN_SPEC.fork(EthereumHardfork::London).active_at_block(block_number) {
+        MAX_REFUND_QUOTIENT
+    } else {
+        LEGACY_MAX_REFUND_QUOTIENT
+    };
+    let gas = validate_gas_used(&counters, &native_receipts(block, &natives), quotient);
+
+    // The storage written by the system calls and the native execution, up to the current
+    // transaction.
+    let mut native_storage = system_storage;
+
+    let mut reports = Vec::new();
+    let transactions = block.body.transactions.iter().zip(&block.senders).zip(&natives);
+    for (index, ((transaction, sender), native)) in transactions.enumerate() {
+        let tx_index = index as u32;
+        for (address, slots) in &native.storage {
+            native_storage.entry(*address).or_default().extend(slots);
+        }
+        let Some(expected) = &native.result else { continue };
+        let steps = cairo.get(&tx_index).map(AsRef::as_ref).unwrap_or_default();
+        let mut report =
+            DivergenceReport::new(block_number, tx_index, transaction, *sender, native, steps);
+        if let Some(transaction) = outcome.transactions.get(index) {
+            let (expected_status, actual_status) = (
+                ExecutionStatus::from_result(expected),
+                ExecutionStatus::from_result(&transaction.result),
+            );
+            if expected_status != actual_status {
+                report = report.with_status(StatusDivergence {
+                    tx_index,
+                    expected: expected_status,
+                    actual: actual_status,
+                });
+            }
+            let (expected, actual) =
+                (Revert::from_result(expected), Revert::from_cairo(transaction));
+            if expected != actual {
+                report = report.with_revert(RevertDivergence { expected, actual });
+            }
+        }
+        if let Some(gas) = gas.iter().find(|gas| gas.tx_index == tx_index) {
+            report = report.with_gas(*gas);
+        }
+        if let Err(HistoryError::InvalidRead { block_number, expected, actual }) =
+            history.validate_reads(blockhash_reads(steps))
+        {
+            report = report.with_block_hash(BlockHashDivergence { block_number, expected, actual });
+        }
+        match storages.get(index) {
+            Some(Ok(storage)) => {
+                report = report.with_state(
+                    &storage_diff(input, &native_storage),
+                    &storage_diff(input, storage),
+                );
+            }
+            Some(Err(err)) => {
+                debug!(block_number, tx_index, %err, "Skipping the storage comparison");
+            }
+            None => {}
+        }
+        if !report.is_empty() {
+            reports.push(report);
+        }
+    }
+    Ok(reports)
+}
+
+/// Returns the receipts of the native execution of a block, the invalid transactions using no
+/// gas.
+fn native_receipts(block: &SealedBlockWithSenders, natives: &[NativeExecution]) -> Vec<Receipt> {
+    let mut cumulative_gas_used = 0;
+    block
+        .body
+        .transactions
+        .iter()
+        .zip(natives)
+        .map(|(transaction, native)| {
+            cumulative_gas_used += native.result.as_ref().map_or(0, ExecutionResult::gas_used);
+            Receipt {
+                tx_type: transaction.tx_type(),
+                success: native.result.as_ref().is_some_and(ExecutionResult::is_success),
+                cumulative_gas_used,
+                logs: Vec::new(),
+            }
+        })
+        .collect()
+}
+
+/// Serializes the storage of the `model.State` returned by each invocation of the transaction
+/// entry point, i.e. the storage of the block after each transaction.
+///
+/// A storage dict keyed by an unknown preimage fails the serialization of its transaction only.
+fn cairo_storages(
+    program: &[u8],
+    layout: &ExecuteLayout,
+    trace: &[RelocatedTraceEntry],
+    memory: RelocatedMemory,
+    preimages: &StoragePreimages,
+) -> eyre::Result<Vec<Result<BTreeMap<Address, BTreeMap<U256, U256>>, KakarotSerdeError>>> {
+    let serde = KakarotSerde::builder().program_bytes(program).relocated_memory(memory).build()?;
+    let read = |address: usize| {
+        serde
+            .read_felt(RelocatedMemory::address(address))
+            .ok()
+            .as_ref()
+            .and_then(felt_to_usize)
+            .ok_or(ReceiptError::InvalidValue(address))
+    };
+    Ok(layout
+        .invocations(trace, read)?
+        .into_iter()
+        .map(|invocation| {
+            let storage = serde
+                .serialize_state_storage(invocation.state, preimages)
+                .map_err(|err| serde.locate_error(err, &[(invocation.state, "model.State")]))?;
+            Ok(storage
+                .into_iter()
+                .map(|(address, slots)| (address, slots.into_iter().collect()))
+                .collect())
+        })
+        .collect())
+}
+
+/// Returns the storage slots whose value differs from the pre-state of the block, as a
+/// [`StateDiff`].
+fn storage_diff(
+    pre_state: &BTreeMap<Address, AccountStateInput>,
+    storage: &BTreeMap<Address, BTreeMap<U256, U256>>,
+) -> StateDiff {
+    let accounts = storage
+        .iter()
+        .filter_map(|(address, slots)| {
+            let pre = pre_state.get(address).map(|account| &account.storage);
+            let storage: BTreeMap<_, _> = slots
+                .iter()
+                .filter(|(slot, value)| {
+                    pre.and_then(|pre| pre.get(*slot)).copied().unwrap_or_default() != **value
+                })
+                .map(|(slot, value)| (B256::from(*slot), *value))
+                .collect();
+            (!storage.is_empty()).then(|| (*address, AccountDiff { storage, ..Default::default() }))
+        })
+        .collect();
+    StateDiff { accounts }
+}
+
+/// Compares the stored Cairo execution of a block, with the program which executed it, with its
+/// native execution on its recorded witness, see [`compare_block`].
+pub fn compare_stored_block(
+    db: &Database,
+    program: &Path,
+    block_number: u64,
+) -> eyre::Result<Vec<DivergenceReport>> {
+    let block = db
+        .block(U256::from(block_number))?
+        .ok_or_else(|| eyre::eyre!("Block {block_number} not found"))?;
+    let input = db
+        .program_input(block_number)?
+        .ok_or_else(|| eyre::eyre!("No program input recorded for block {block_number}"))?;
+    let (trace, memory) = db
+        .execution_trace(block_number)?
+        .ok_or_else(|| eyre::eyre!("No trace found for block {block_number}"))?;
+    // The preimages are the ones the hints record when running the block.
+    let preimages = HintCache::from_state(&input.state).preimages();
+    compare_block(&fs::read(program)?, &input, &block, &trace, &memory, &preimages)
+}
+
+/// The first step at which the Cairo and the native executions of a transaction diverge.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepDivergence {
+    /// The index of the step in the transaction.
+    pub index: usize,
+    /// The step of the native execution, `None` if it ended before.
+    pub native: Option<Step>,
+    /// The step of the Cairo execution, `None` if it ended before.
+    pub cairo: Option<Step>,
+}
+
+/// Returns whether a native step, with its stack, diverges from a Cairo step, i.e. executes a
+/// different opcode or from a different state.
+///
+/// The memories are not compared, the Cairo memory being only known by 16-byte words.
+fn diverges(native: &Step, stack: &[U256], cairo: &Step) -> bool {
+    (native.pc, native.op, native.depth, native.address, native.gas) !=
+        (cairo.pc, cairo.op, cairo.depth, cairo.address, cairo.gas) ||
+        stack != cairo.stack.as_slice()
+}
+
+/// Returns the open call frames at the last of the given steps, from the top-level frame of the
+/// transaction. The frames are returned without their sub-calls.
+pub fn call_path(root: CallFrame, steps: &[Step]) -> Vec<CallFrame> {
+    let mut path = vec![root];
+    for (step, next) in steps.iter().zip(steps.iter().skip(1)) {
+        if next.depth > step.depth {
+            if let Some(mut frame) = CallFrame::from_step(step) {
+                if frame.kind.is_create() {
+                    frame.to = Some(next.address);
+                }
+                frame.gas = U64::from(next.gas);
+                path.push(frame);
+            }
+        } else if next.depth < step.depth && path.len() > 1 {
+            path.pop();
+        }
+    }
+    path
+}
+
+/// An account whose changes differ between the native and the Cairo executions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountDivergence {
+    /// The address of the account.
+    pub address: Address,
+    /// The changes of the native execution, `None` if the account is unchanged.
+    pub native: Option<AccountDiff>,
+    /// The changes of the Cairo execution, `None` if the account is unchanged.
+    pub cairo: Option<AccountDiff>,
+}
+
+/// Compares the state changes of the native and the Cairo executions, returning the diverging
+/// accounts ordered by address.
+pub fn state_divergences(native: &StateDiff, cairo: &StateDiff) -> Vec<AccountDivergence> {
+    let addresses: BTreeSet<_> = native.accounts.keys().chain(cairo.accounts.keys()).collect();
+    addresses
+        .into_iter()
+        .filter_map(|address| {
+            let native = native.accounts.get(address);
+            let cairo = cairo.accounts.get(address);
+            (native != cairo).then(|| AccountDivergence {
+                address: *address,
+                native: native.cloned(),
+                cairo: cairo.cloned(),
+            })
+        })
+        .collect()
+}
+
+/// The format of a [`DivergenceReport`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+    /// A Markdown document.
+    #[default]
+    Markdown,
+    /// A self-contained HTML page.
+    Html,
+}
+
+/// Error variant indicating an unknown [`ReportFormat`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Unknown report format '{0}'")]
+pub struct UnknownReportFormat(pub String);
+
+impl FromStr for ReportFormat {
+    type Err = UnknownReportFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "markdown" | "md" => Ok(Self::Markdown),
+            "html" => Ok(Self::Html),
+            _ => Err(UnknownReportFormat(s.to_string())),
+        }
+    }
+}
+
+impl ReportFormat {
+    /// Returns the extension of the report files.
+    pub const fn extension(&self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Html => "html",
+        }
+    }
+}
+
+/// The report of the divergence of a transaction between the Cairo and the native executions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DivergenceReport {
+    /// The number of the block.
+    pub block_number: u64,
+    /// The index of the transaction in the block.
+    pub tx_index: u32,
+    /// The hash of the transaction.
+    pub tx_hash: B256,
+    /// The divergence of the status of the transaction, if any.
+    pub status: Option<StatusDivergence>,
+    /// The divergence of the gas used by the transaction, if any.
+    pub gas: Option<GasDivergence>,
+    /// The open call frames at the diverging step, from the top-level frame.
+    pub call_path: Vec<CallFrame>,
+    /// The first diverging step, if any.
+    pub step: Option<StepDivergence>,
+    /// The diverging state changes.
+    pub state: Vec<AccountDivergence>,
+    /// The first `BLOCKHASH` read of the Cairo execution returning another hash than the block
+    /// hash history of the witness, if any.
+    #[serde(default)]
+    pub block_hash: Option<BlockHashDivergence>,
+    /// The divergence of the revert of the transaction, if either execution reverted with another
+    /// output than the other.
+    #[serde(default)]
+    pub revert: Option<RevertDivergence>,
+}
+
+/// The reverts of a transaction whose outputs differ between the Cairo and the native executions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RevertDivergence {
+    /// The revert of the native execution, `None` if it did not revert.
+    pub expected: Option<Revert>,
+    /// The revert of the Cairo execution, `None` if it did not revert.
+    pub actual: Option<Revert>,
+}
+
+/// A `BLOCKHASH` read of the Cairo execution returning another hash than the block hash history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockHashDivergence {
+    /// The number of the read block.
+    pub block_number: u64,
+    /// The hash of the block in the history.
+    pub expected: B256,
+    /// The hash returned by the read.
+    pub actual: B256,
+}
+
+impl DivergenceReport {
+    /// Creates the report of a transaction of a block from its recorded native execution and the
+    /// steps of its Cairo execution.
+    pub fn new(
+        block_number: u64,
+        tx_index: u32,
+        transaction: &TransactionSigned,
+        sender: Address,
+        native: &NativeExecution,
+        cairo: &[Step],
+    ) -> Self {
+        let step = native.divergence.clone();
+        // The path is followed in the native execution, unless it ended before the divergence.
+        let end = step.as_ref().map_or(native.steps.len(), |step| step.index + 1);
+        let steps = if native.steps.len() >= end {
+            &native.steps[..end]
+        } else {
+            &cairo[..end.min(cairo.len())]
+        };
+        let root = CallFrame::from_transaction(transaction, sender);
+
+        Self {
+            block_number,
+            tx_index,
+            tx_hash: transaction.hash(),
+            status: None,
+            gas: None,
+            call_path: call_path(root, steps),
+            step,
+            state: Vec::new(),
+            block_hash: None,
+            revert: None,
+        }
+    }
+
+    /// Sets the divergence of the status of the transaction.
+    pub const fn with_status(mut self, status: StatusDivergence) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Sets the divergence of the gas used by the transaction.
+    pub const fn with_gas(mut self, gas: GasDivergence) -> Self {
+        self.gas = Some(gas);
+        self
+    }
+
+    /// Sets the diverging state changes from the state diffs of both executions.
+    pub fn with_state(mut self, native: &StateDiff, cairo: &StateDiff) -> Self {
+        self.state = state_divergences(native, cairo);
+        self
+    }
+
+    /// Sets the divergence of a `BLOCKHASH` read.
+    pub const fn with_block_hash(mut self, block_hash: BlockHashDivergence) -> Self {
+        self.block_hash = Some(block_hash);
+        self
+    }
+
+    /// Sets the divergence of the revert of the transaction.
+    pub fn with_revert(mut self, revert: RevertDivergence) -> Self {
+        self.revert = Some(revert);
+        self
+    }
+
+    /// Returns whether the report holds any divergence.
+    pub fn is_empty(&self) -> bool {
+        self.status.is_none() &&
+            self.gas.is_none() &&
+            self.step.is_none() &&
+            self.state.is_empty() &&
+            self.block_hash.is_none() &&
+            self.revert.is_none()
+    }
+
+    /// Returns the file name of the report in the given format.
+    pub fn file_name(&self, format: ReportFormat) -> String {
+        format!("divergence.{}.{}", self.tx_index, format.extension())
+    }
+
+    /// Renders the report in the given format.
+    pub fn render(&self, format: ReportFormat) -> String {
+        let title =
+            format!("Divergence of transaction {} of block {}", self.tx_index, self.block_number);
+        match format {
+            ReportFormat::Markdown => render_markdown(&title, &self.sections()),
+            ReportFormat::Html => render_html(&title, &self.sections()),
+        }
+    }
+
+    /// Saves the report in the directory of the block in the artifacts directory, returning the
+    /// path of the report.
+    pub fn save(&self, artifacts_dir: &Path, format: ReportFormat) -> eyre::Result<PathBuf> {
+        let block_dir = artifacts_dir.join(self.block_number.to_string());
+        fs::create_dir_all(&block_dir)?;
+        let path = block_dir.join(self.file_name(format));
+        fs::write(&path, self.render(format))?;
+        Ok(path)
+    }
+
+    /// Returns the sections of the report, rendered in any format.
+    fn sections(&self) -> Vec<Section> {
+        let mut summary = vec![format!("Transaction: `{}`", self.tx_hash)];
+        if let Some(status) = &self.status {
+            summary.push(format!(
+                "Status: native `{}`, Cairo `{}`",
+                status_name(&status.expected),
+                status_name(&status.actual)
+            ));
+        }
+        if let Some(gas) = &self.gas {
+            summary.push(format!(
+                "Gas used: receipt `{}`, Cairo `{}` ({} divergence)",
+                gas.expected, gas.actual, gas.kind
+            ));
+        }
+        if let Some(read) = &self.block_hash {
+            summary.push(format!(
+                "BLOCKHASH({}): history `{}`, Cairo `{}`",
+                read.block_number, read.expected, read.actual
+            ));
+        }
+        if let Some(revert) = &self.revert {
+            let describe = |revert: &Option<Revert>| {
+                revert.as_ref().map_or_else(|| "none".to_string(), ToString::to_string)
+            };
+            summary.push(format!(
+                "Revert: native `{}`, Cairo `{}`",
+                describe(&revert.expected),
+                describe(&revert.actual)
+            ));
+        }
+        if self.is_empty() {
+            summary.push("No divergence found".to_string());
+        }
+
+        let mut sections = vec![Section::new("Summary", Content::List(summary))];
+        sections.push(Section::new(
+            "Call path",
+            Content::List(
+                self.call_path
+                    .iter()
+                    .enumerate()
+                    .map(|(depth, frame)| {
+                        format!(
+                            "{} `{}` from `{}` to `{}` with {} gas",
+                            depth + 1,
+                            frame.kind,
+                            frame.from,
+                            frame.to.map_or_else(|| "?".to_string(), |to| to.to_string()),
+                            frame.gas
+                        )
+                    })
+                    .collect(),
+            ),
+        ));
+        if let Some(step) = &self.step {
+            sections.extend(step_sections(step));
+        }
+        if !self.state.is_empty() {
+            sections.push(Section::new("State changes", state_table(&self.state)));
+        }
+        sections
+    }
+}
+
+/// A section of a report.
+struct Section {
+    /// The title of the section.
+    title: String,
+    /// The content of the section.
+    content: Content,
+}
+
+impl Section {
+    /// Creates a new section.
+    fn new(title: impl Into<String>, content: Content) -> Self {
+        Self { title: title.into(), content }
+    }
+}
+
+/// The content of a section of a report.
+enum Content {
+    /// A list of items.
+    List(Vec<String>),
+    /// A table, with its header row.
+    Table(Vec<String>, Vec<Vec<String>>),
+    /// A preformatted block.
+    Code(String),
+}
+
+/// Returns the name of an execution status.
+fn status_name(status: &ExecutionStatus) -> String {
+    match status {
+        ExecutionStatus::Success => "success".to_string(),
+        ExecutionStatus::Revert => "revert".to_string(),
+        ExecutionStatus::Halt(halt) => format!("halt: {halt}"),
+    }
+}
+
+/// Returns the sections of a diverging step: the steps of both engines, their stacks and their
+/// memories.
+fn step_sections(divergence: &StepDivergence) -> Vec<Section> {
+    let (native, cairo) = (divergence.native.as_ref(), divergence.cairo.as_ref());
+    let field = |name: &str, value: fn(&Step) -> String| {
+        let native = native.map_or_else(|| "-".to_string(), value);
+        let cairo = cairo.map_or_else(|| "-".to_string(), value);
+        let marker = if native == cairo { "" } else { " ≠" };
+        vec![format!("{name}{marker}"), native, cairo]
+    };
+    let rows = vec![
+        field("PC", |step| step.pc.to_string()),
+        field("Opcode", |step| opcode_name(step.op)),
+        field("Gas", |step| step.gas.to_string()),
+        field("Depth", |step| step.depth.to_string()),
+        field("Address", |step| step.address.to_string()),
+        field("Error", |step| step.error.map(|error| error.to_string()).unwrap_or_default()),
+    ];
+
+    // The stacks are rendered from their top.
+    let stack = |step: Option<&Step>| step.map(|step| step.stack.clone()).unwrap_or_default();
+    let (native_stack, cairo_stack) = (stack(native), stack(cairo));
+    let depth = native_stack.len().max(cairo_stack.len());
+    let item = |stack: &[U256], position: usize| {
+        stack.iter().rev().nth(position).map(|item| format!("{item:#x}")).unwrap_or_default()
+    };
+    let stack_rows = (0..depth)
+        .map(|position| {
+            let (native, cairo) = (item(&native_stack, position), item(&cairo_stack, position));
+            let marker = if native == cairo { "" } else { " ≠" };
+            vec![format!("{position}{marker}"), native, cairo]
+        })
+        .collect();
+
+    let header = || vec![String::new(), "Native".to_string(), "Cairo".to_string()];
+    vec![
+        Section::new(
+            format!("Diverging step {}", divergence.index),
+            Content::Table(header(), rows),
+        ),
+        Section::new("Stack", Content::Table(header(), stack_rows)),
+        Section::new("Native memory", Content::Code(memory_dump(native))),
+        Section::new("Cairo memory", Content::Code(memory_dump(cairo))),
+    ]
+}
+
+/// Returns the hex dump of the memory of a step, one line per 32 bytes.
+fn memory_dump(step: Option<&Step>) -> String {
+    let Some(step) = step else { return String::new() };
+    step.memory
+        .chunks(MEMORY_LINE_SIZE)
+        .enumerate()
+        .map(|(line, chunk)| format!("{:#06x}: {}\n", line * MEMORY_LINE_SIZE, hex::encode(chunk)))
+        .collect()
+}
+
+/// Returns the table of the diverging state changes, one row per diverging field.
+fn state_table(accounts: &[AccountDivergence]) -> Content {
+    let mut rows = Vec::new();
+    for account in accounts {
+        let (native, cairo) = (account.native.clone(), account.cairo.clone());
+        let (native, cairo) = (native.unwrap_or_default(), cairo.unwrap_or_default());
+        let mut push = |field: String, native: String, cairo: String| {
+            if native != cairo {
+                rows.push(vec![account.address.to_string(), field, native, cairo]);
+            }
+        };
+        push("destroyed".to_string(), native.destroyed.to_string(), cairo.destroyed.to_string());
+        push("nonce".to_string(), optional(native.nonce), optional(cairo.nonce));
+        push("balance".to_string(), optional(native.balance), optional(cairo.balance));
+        push("code hash".to_string(), optional(native.code_hash), optional(cairo.code_hash));
+        let slots: BTreeSet<_> = native.storage.keys().chain(cairo.storage.keys()).collect();
+        for slot in slots {
+            push(
+                format!("slot {slot}"),
+                optional(native.storage.get(slot)),
+                optional(cairo.storage.get(slot)),
+            );
+        }
+    }
+    let header = ["Account", "Field", "Native", "Cairo"].map(String::from).to_vec();
+    Content::Table(header, rows)
+}
+
+/// Formats an optional value, `-` when unchanged.
+fn optional(value: Option<impl fmt::Display>) -> String {
+    value.map_or_else(|| "-".to_string(), |value| value.to_string())
+}
+
+/// Renders the sections of a report as a Markdown document.
+fn render_markdown(title: &str, sections: &[Section]) -> String {
+    let mut document = format!("# {title}\n");
+    for section in sections {
+        let _ = write!(document, "\n## {}\n\n", section.title);
+        match &section.content {
+            Content::List(items) => {
+                for item in items {
+                    let _ = writeln!(document, "- {item}");
+                }
+            }
+            Content::Table(header, rows) => {
+                let _ = writeln!(document, "| {} |", header.join(" | "));
+                let _ = writeln!(document, "|{}", "---|".repeat(header.len()));
+                for row in rows {
+                    let _ = writeln!(document, "| {} |", row.join(" | "));
+                }
+            }
+            Content::Code(code) => {
+                let _ = write!(document, "```text\n{code}```\n");
+            }
+        }
+    }
+    document
+}
+
+/// Renders the sections of a report as a self-contained HTML page.
+fn render_html(title: &str, sections: &[Section]) -> String {
+    let title = escape_html(title);
+    let mut document = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+        <style>body{{font-family:sans-serif;margin:2em}}table{{border-collapse:collapse}}\
+        td,th{{border:1px solid #ccc;padding:2px 8px;font-family:monospace}}\
+        pre{{background:#f6f8fa;padding:1em}}</style>\n</head>\n<body>\n<h1>{title}</h1>\n"
+    );
+    for section in sections {
+        let _ = writeln!(document, "<h2>{}</h2>", escape_html(&section.title));
+        match &section.content {
+            Content::List(items) => {
+                document.push_str("<ul>\n");
+                for item in items {
+                    let _ = writeln!(document, "<li>{}</li>", escape_html(item).replace('`', ""));
+                }
+                document.push_str("</ul>\n");
+            }
+            Content::Table(header, rows) => {
+                document.push_str("<table>\n<tr>");
+                for cell in header {
+                    let _ = write!(document, "<th>{}</th>", escape_html(cell));
+                }
+                document.push_str("</tr>\n");
+                for row in rows {
+                    document.push_str("<tr>");
+                    for cell in row {
+                        let _ = write!(document, "<td>{}</td>", escape_html(cell));
+                    }
+                    document.push_str("</tr>\n");
+                }
+                document.push_str("</table>\n");
+            }
+            Content::Code(code) => {
+                let _ = writeln!(document, "<pre>{}</pre>", escape_html(code));
+            }
+        }
+    }
+    document.push_str("</body>\n</html>\n");
+    document
+}
+
+/// Escapes the HTML special characters of a text.
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::refund::GasDivergenceKind;
+    use alloy_primitives::{address, bytes, TxKind};
+    use reth_revm::{
+        db::{CacheDB, EmptyDB},
+        inspector_handle_register,
+        interpreter::opcode::{ADD, CALL, PUSH1, STOP},
+        primitives::{AccountInfo, Bytecode},
+        Evm,
+    };
+
+    const CONTRACT: Address = address!("00000000000000000000000000000000000000c0");
+
+    fn step(pc: u64, op: u8, depth: u64, stack: &[u64]) -> Step {
+        Step {
+            pc,
+            op,
+            depth,
+            gas: 1000 - pc,
+            stack: stack.iter().copied().map(U256::from).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Runs `PUSH1 1, PUSH1 2, ADD, STOP` with a [`StepRecorder`] compared with the given Cairo
+    /// steps.
+    fn record(cairo: Vec<Step>) -> (Vec<Step>, Option<StepDivergence>) {
+        let code = bytes!("600160020100");
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(
+            CONTRACT,
+            AccountInfo { code: Some(Bytecode::new_raw(code)), ..Default::default() },
+        );
+
+        let mut evm = Evm::builder()
+            .with_db(db)
+            .with_external_context(StepRecorder::new(0, cairo.into()))
+            .append_handler_register(inspector_handle_register)
+            .modify_tx_env(|tx| {
+                tx.transact_to = TxKind::Call(CONTRACT);
+                tx.gas_limit = 100_000;
+            })
+            .build();
+        evm.transact().unwrap();
+        std::mem::take(&mut evm.context.external).finish()
+    }
+
+    /// Returns the Cairo steps matching the native execution of [`record`].
+    fn cairo_steps() -> Vec<Step> {
+        // Without Cairo steps, the native execution diverges at its first step.
+        let (steps, _) = record(Vec::new());
+        let gas = steps[0].gas;
+        [(0, PUSH1, vec![]), (2, PUSH1, vec![1]), (4, ADD, vec![1, 2]), (5, STOP, vec![3])]
+            .into_iter()
+            .enumerate()
+            .map(|(index, (pc, op, stack))| Step {
+                gas: gas - 3 * index as u64,
+                address: CONTRACT,
+                ..step(pc, op, 1, &stack)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_record_native_steps() {
+        let cairo = cairo_steps();
+        let (steps, divergence) = record(cairo.clone());
+        assert_eq!(divergence, None);
+        assert_eq!(steps.iter().map(|step| step.pc).collect::<Vec<_>>(), vec![0, 2, 4, 5]);
+        assert!(steps.iter().all(|step| step.depth == 1 && step.address == CONTRACT));
+        assert_eq!(steps[2].gas_cost, 3);
+        // The stack is only recorded for the call opcodes and the diverging step.
+        assert!(steps[2].stack.is_empty());
+
+        // The recording stops at the first diverging step, recorded with its stack.
+        let mut diverging = cairo.clone();
+        diverging[2].stack = vec![U256::from(1), U256::from(3)];
+        let (steps, divergence) = record(diverging.clone());
+        let divergence = divergence.unwrap();
+        assert_eq!(steps.len(), 3);
+        assert_eq!(divergence.index, 2);
+        let native = divergence.native.unwrap();
+        assert_eq!((native.stack, native.gas_cost), (vec![U256::from(1), U256::from(2)], 3));
+        assert_eq!(divergence.cairo.as_ref(), Some(&diverging[2]));
+
+        // A truncated execution diverges at its end.
+        let (_, divergence) = record(cairo[..2].to_vec());
+        assert_eq!(
+            divergence.map(|divergence| (divergence.index, divergence.cairo)),
+            Some((2, None))
+        );
+        let mut longer = cairo.clone();
+        longer.push(step(6, STOP, 1, &[]));
+        let (_, divergence) = record(longer);
+        assert_eq!(
+            divergence.map(|divergence| (divergence.index, divergence.native)),
+            Some((4, None))
+        );
+    }
+
+    #[test]
+    fn test_call_path() {
+        let callee = Address::with_last_byte(0xaa);
+        let call = step(0, CALL, 1, &[0, 0, 0, 0, 0, 0xaa, 500]);
+        let mut inner = step(0, PUSH1, 2, &[]);
+        inner.address = callee;
+        let root = CallFrame::from_transaction(&TransactionSigned::default(), Address::ZERO);
+
+        let path = call_path(root.clone(), &[call.clone(), inner.clone()]);
+        assert_eq!(path.len(), 2);
+        assert_eq!((path[1].kind, path[1].to), (CallKind::Call, Some(callee)));
+        assert_eq!(path[1].gas, U64::from(inner.gas));
+
+        // The path is closed when the call returns.
+        let path = call_path(root, &[call, inner, step(1, STOP, 1, &[1])]);
+        assert_eq!(path.len(), 1);
+    }
+
+    #[test]
+    fn test_state_divergences() {
+        let diff = |nonce| AccountDiff { nonce: Some(nonce), ..Default::default() };
+        let native = StateDiff {
+            accounts: BTreeMap::from([
+                (Address::with_last_byte(1), diff(1)),
+                (Address::with_last_byte(2), diff(1)),
+            ]),
+        };
+        let cairo = StateDiff {
+            accounts: BTreeMap::from([
+                (Address::with_last_byte(1), diff(1)),
+                (Address::with_last_byte(2), diff(2)),
+                (Address::with_last_byte(3), diff(1)),
+            ]),
+        };
+
+        let divergences = state_divergences(&native, &cairo);
+        assert_eq!(
+            divergences,
+            vec![
+                AccountDivergence {
+                    address: Address::with_last_byte(2),
+                    native: Some(diff(1)),
+                    cairo: Some(diff(2)),
+                },
+                AccountDivergence {
+                    address: Address::with_last_byte(3),
+                    native: None,
+                    cairo: Some(diff(1)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_storage_diff() {
+        let pre_state = BTreeMap::from([(
+            Address::with_last_byte(1),
+            AccountStateInput {
+                storage: BTreeMap::from([(U256::from(1), U256::from(10))]),
+                ..Default::default()
+            },
+        )]);
+        let storage = BTreeMap::from([
+            (
+                Address::with_last_byte(1),
+                BTreeMap::from([(U256::from(1), U256::from(10)), (U256::from(2), U256::from(20))]),
+            ),
+            (Address::with_last_byte(2), BTreeMap::from([(U256::from(1), U256::ZERO)])),
+        ]);
+
+        // Only the slots whose value differs from the pre-state are changes.
+        let diff = storage_diff(&pre_state, &storage);
+        assert_eq!(
+            diff.accounts,
+            BTreeMap::from([(
+                Address::with_last_byte(1),
+                AccountDiff {
+                    storage: BTreeMap::from([(B256::from(U256::from(2)), U256::from(20))]),
+                    ..Default::default()
+                }
+            )])
+        );
+    }
+
+    #[test]
+    fn test_render_report() {
+        let steps = vec![step(0, PUSH1, 1, &[]), step(2, PUSH1, 1, &[1])];
+        let mut cairo = steps.clone();
+        cairo[1].stack = vec![U256::from(2)];
+        cairo[1].memory = bytes!("3c");
+        let divergence = StepDivergence {
+            index: 1,
+            native: Some(steps[1].clone()),
+            cairo: Some(cairo[1].clone()),
+        };
+        let native = NativeExecution {
+            result: None,
+            steps,
+            divergence: Some(divergence),
+            storage: BTreeMap::new(),
+        };
+        let report = DivergenceReport::new(
+            7,
+            1,
+            &TransactionSigned::default(),
+            Address::ZERO,
+            &native,
+            &cairo,
+        )
+        .with_status(StatusDivergence {
+            tx_index: 1,
+            expected: ExecutionStatus::Success,
+            actual: ExecutionStatus::Halt(EvmHalt::OutOfGas),
+        })
+        .with_gas(GasDivergence {
+            tx_index: 1,
+            expected: 21_000,
+            actual: 22_000,
+            kind: GasDivergenceKind::Execution,
+        })
+        .with_revert(RevertDivergence { expected: None, actual: Some(Revert::new(Bytes::new())) });
+        assert_eq!(report.step.as_ref().map(|step| step.index), Some(1));
+
+        let markdown = report.render(ReportFormat::Markdown);
+        assert!(markdown.starts_with("# Divergence of transaction 1 of block 7\n"));
+        assert!(markdown.contains("- Status: native `success`, Cairo `halt: out_of_gas`"));
+        assert!(markdown.contains("- Revert: native `none`, Cairo `execution reverted`"));
+        assert!(markdown.contains("| 0 ≠ | 0x1 | 0x2 |"));
+        assert!(markdown.contains("0x0000: 3c"));
+
+        let html = report.render(ReportFormat::Html);
+        assert!(html.contains("<h1>Divergence of transaction 1 of block 7</h1>"));
+        assert!(html.contains("<td>0 ≠</td><td>0x1</td><td>0x2</td>"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = report.save(dir.path(), ReportFormat::Html).unwrap();
+        assert_eq!(path, dir.path().join("7").join("divergence.1.html"));
+        assert_eq!(fs::read_to_string(path).unwrap(), html);
+    }
+}
@@ -0,0 +1,296 @@
+//! Versioned binary envelope of the stored artifacts, with the migrations of their formats.
+//!
+//! Every artifact written by the [`Codec`](crate::compression::Codec) starts with a [`Header`]:
+//!
+//! | Offset | Size | Field                                                 |
+//! |--------|------|-------------------------------------------------------|
+//! | 0      | 4    | The magic number [`MAGIC`], `KETH`                    |
+//! | 4      | 1    | The [`ArtifactKind`] tag                              |
+//! | 5      | 2    | The version of the format of the kind, little-endian  |
+//! | 7      | 1    | The [`Encoding`] of the payload                       |
+//!
+//! The version is the version of the format of the content of the artifacts of the kind, e.g. the
+//! JSON schema of the traces. When a format changes, its version is bumped in [`Migrations`] along
+//! with the [`Migration`] upgrading the content of the previous version, so that the artifacts
+//! written by older releases remain readable: they are upgraded on access when decoded, and can be
+//! rewritten in the current format in batch with [`crate::compression::migrate`]. The checksums of
+//! the artifacts are over their content as written, see [`crate::integrity`]: artifacts are
+//! verified before being upgraded on access, and their checksums updated when rewritten in batch.
+//!
+//! The artifacts stored before the envelope, raw or bare zstd frames, are read as the first
+//! version of their format.
+
+use crate::compression::ArtifactKind;
+use std::io;
+use thiserror::Error;
+
+/// The magic number starting the envelope of every artifact.
+pub const MAGIC: [u8; 4] = *b"KETH";
+
+/// The length of the header of the envelope.
+pub const HEADER_LEN: usize = 8;
+
+/// The first version of the format of every kind, i.e. the version of the artifacts stored before
+/// the envelope.
+pub const FIRST_VERSION: u16 = 1;
+
+/// Represents errors that can occur when reading the envelope of an artifact.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum EnvelopeError {
+    /// Error variant indicating a header shorter than [`HEADER_LEN`].
+    #[error("Truncated artifact header")]
+    Truncated,
+
+    /// Error variant indicating an unknown artifact kind tag.
+    #[error("Unknown artifact kind tag {0}")]
+    UnknownKind(u8),
+
+    /// Error variant indicating an unknown payload encoding.
+    #[error("Unknown artifact encoding {0}")]
+    UnknownEncoding(u8),
+
+    /// Error variant indicating an artifact written in a format newer than the supported one,
+    /// e.g. by a newer release.
+    #[error("Unsupported {kind} format version {version}, the latest supported is {supported}")]
+    UnsupportedVersion {
+        /// The kind of the artifact.
+        kind: ArtifactKind,
+        /// The version of the artifact.
+        version: u16,
+        /// The current version of the kind.
+        supported: u16,
+    },
+
+    /// Error variant indicating a missing migration of a version of a format.
+    #[error("No migration of the {kind} format from version {version}")]
+    MissingMigration {
+        /// The kind of the artifact.
+        kind: ArtifactKind,
+        /// The version without migration.
+        version: u16,
+    },
+}
+
+impl From<EnvelopeError> for io::Error {
+    fn from(err: EnvelopeError) -> Self {
+        Self::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+/// The encoding of the payload of an artifact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// The payload is stored as is.
+    Raw,
+    /// The payload is a zstd frame, compressed with a dictionary or not.
+    Zstd,
+}
+
+impl Encoding {
+    /// Returns the tag of the encoding in the header.
+    pub const fn tag(&self) -> u8 {
+        match self {
+            Self::Raw => 0,
+            Self::Zstd => 1,
+        }
+    }
+
+    /// Returns the encoding of a tag of the header.
+    pub const fn from_tag(tag: u8) -> Result<Self, EnvelopeError> {
+        match tag {
+            0 => Ok(Self::Raw),
+            1 => Ok(Self::Zstd),
+            tag => Err(EnvelopeError::UnknownEncoding(tag)),
+        }
+    }
+}
+
+/// Returns the tag of an artifact kind in the header.
+pub const fn kind_tag(kind: ArtifactKind) -> u8 {
+    match kind {
+        ArtifactKind::Trace => 1,
+        ArtifactKind::Memory => 2,
+        ArtifactKind::AirInput => 3,
+        ArtifactKind::Proof => 4,
+        ArtifactKind::Dump => 5,
+        ArtifactKind::Output => 6,
+    }
+}
+
+/// Returns the artifact kind of a tag of the header.
+pub fn kind_from_tag(tag: u8) -> Result<ArtifactKind, EnvelopeError> {
+    ArtifactKind::ALL
+        .into_iter()
+        .find(|kind| kind_tag(*kind) == tag)
+        .ok_or(EnvelopeError::UnknownKind(tag))
+}
+
+/// The header of the envelope of an artifact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    /// The kind of the artifact.
+    pub kind: ArtifactKind,
+    /// The version of the format of the content.
+    pub version: u16,
+    /// The encoding of the payload.
+    pub encoding: Encoding,
+}
+
+impl Header {
+    /// Encodes the header.
+    pub fn encode(&self) -> [u8; HEADER_LEN] {
+        let mut header = [0; HEADER_LEN];
+        header[..4].copy_from_slice(&MAGIC);
+        header[4] = kind_tag(self.kind);
+        header[5..7].copy_from_slice(&self.version.to_le_bytes());
+        header[7] = self.encoding.tag();
+        header
+    }
+
+    /// Parses the header starting an artifact, `None` for the artifacts stored before the
+    /// envelope.
+    pub fn parse(data: &[u8]) -> Result<Option<Self>, EnvelopeError> {
+        if !data.starts_with(&MAGIC) {
+            return Ok(None);
+        }
+        if data.len() < HEADER_LEN {
+            return Err(EnvelopeError::Truncated);
+        }
+        Ok(Some(Self {
+            kind: kind_from_tag(data[4])?,
+            version: u16::from_le_bytes([data[5], data[6]]),
+            encoding: Encoding::from_tag(data[7])?,
+        }))
+    }
+}
+
+/// The migrations of the formats of the artifacts, appended to when a format changes. No format
+/// changed since the envelope was introduced.
+pub const MIGRATIONS: &[Migration] = &[];
+
+/// Upgrades the content of an artifact to the next version of its format.
+pub type Upgrade = fn(Vec<u8>) -> io::Result<Vec<u8>>;
+
+/// The migration of a format from a version to the next one.
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    /// The kind of the artifacts.
+    pub kind: ArtifactKind,
+    /// The version upgraded from, to `from + 1`.
+    pub from: u16,
+    /// The upgrade of the content.
+    pub upgrade: Upgrade,
+}
+
+/// The registry of the migrations of the formats of the artifacts.
+///
+/// The current version of a format is the version following its last migration.
+#[derive(Debug, Clone)]
+pub struct Migrations {
+    /// The migrations, by kind and version.
+    migrations: Vec<Migration>,
+}
+
+impl Default for Migrations {
+    fn default() -> Self {
+        Self::new(MIGRATIONS.to_vec())
+    }
+}
+
+impl Migrations {
+    /// Creates a registry of the given migrations.
+    pub const fn new(migrations: Vec<Migration>) -> Self {
+        Self { migrations }
+    }
+
+    /// Returns the current version of the format of a kind.
+    pub fn version(&self, kind: ArtifactKind) -> u16 {
+        self.migrations
+            .iter()
+            .filter(|migration| migration.kind == kind)
+            .map(|migration| migration.from + 1)
+            .max()
+            .unwrap_or(FIRST_VERSION)
+    }
+
+    /// Upgrades the content of an artifact from the given version to the current one.
+    pub fn upgrade(
+        &self,
+        kind: ArtifactKind,
+        version: u16,
+        mut content: Vec<u8>,
+    ) -> io::Result<Vec<u8>> {
+        let supported = self.version(kind);
+        if version > supported {
+            return Err(EnvelopeError::UnsupportedVersion { kind, version, supported }.into());
+        }
+        for version in version..supported {
+            let migration = self
+                .migrations
+                .iter()
+                .find(|migration| migration.kind == kind && migration.from == version)
+                .ok_or(EnvelopeError::MissingMigration { kind, version })?;
+            content = (migration.upgrade)(content)?;
+        }
+        Ok(content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_roundtrip() {
+        let header = Header { kind: ArtifactKind::Trace, version: 3, encoding: Encoding::Zstd };
+        let encoded = header.encode();
+        assert_eq!(&encoded[..4], b"KETH");
+        assert_eq!(Header::parse(&encoded), Ok(Some(header)));
+
+        for kind in ArtifactKind::ALL {
+            assert_eq!(kind_from_tag(kind_tag(kind)), Ok(kind));
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid_header() {
+        assert_eq!(Header::parse(br#"{"pc":1}"#), Ok(None));
+        assert_eq!(Header::parse(b"KETH"), Err(EnvelopeError::Truncated));
+        assert_eq!(Header::parse(b"KETH\x2a\x01\x00\x01"), Err(EnvelopeError::UnknownKind(42)));
+        assert_eq!(Header::parse(b"KETH\x01\x01\x00\x07"), Err(EnvelopeError::UnknownEncoding(7)));
+    }
+
+    #[test]
+    fn test_migrations() {
+        let migrations = Migrations::new(vec![
+            Migration {
+                kind: ArtifactKind::Proof,
+                from: 1,
+                upgrade: |content| Ok([b"v2:".as_slice(), &content].concat()),
+            },
+            Migration {
+                kind: ArtifactKind::Proof,
+                from: 2,
+                upgrade: |content| Ok(content.to_ascii_uppercase()),
+            },
+        ]);
+        assert_eq!(migrations.version(ArtifactKind::Proof), 3);
+        assert_eq!(migrations.version(ArtifactKind::Trace), FIRST_VERSION);
+
+        assert_eq!(
+            migrations.upgrade(ArtifactKind::Proof, 1, b"proof".to_vec()).unwrap(),
+            b"V2:PROOF"
+        );
+        assert_eq!(
+            migrations.upgrade(ArtifactKind::Proof, 3, b"proof".to_vec()).unwrap(),
+            b"proof"
+        );
+
+        let err = migrations.upgrade(ArtifactKind::Proof, 4, b"proof".to_vec()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = Migrations::new(vec![migrations.migrations[1]])
+            .upgrade(ArtifactKind::Proof, 1, b"proof".to_vec())
+            .unwrap_err();
+        assert!(err.to_string().contains("No migration of the proof format from version 1"));
+    }
+}
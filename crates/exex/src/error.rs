@@ -0,0 +1,492 @@
+//! Crate-wide taxonomy of the errors, with stable numeric codes.
+//!
+//! Each module keeps its own error type, all of them converting into a [`KethError`]. A
+//! [`KethError`] has a stable numeric [`code`](KethError::code) and an [`ErrorCategory`], the
+//! thousands of the code, so that operators and clients can automate on the classes of errors
+//! instead of matching their messages. Codes are never reused: a removed variant retires its code.
+//!
+//! The execution entry points, see [`crate::executor`], return a [`KethError`], and the errors of
+//! the modules carried by an [`eyre::Report`] are recovered with [`KethError::from_report`].
+//!
+//! The errors are served over gRPC as a [`Status`], carrying the code and the category in the
+//! [`ERROR_CODE_METADATA`] and [`ERROR_CATEGORY_METADATA`] metadata.
+
+use crate::{
+    air::AirError,
+    analytics::AnalyticsError,
+    artifacts::ArtifactStoreError,
+    attribution::AttributionError,
+    benchmark::BenchmarkError,
+    blob::BlobError,
+    campaign::CampaignError,
+    chain::ChainError,
+    checkpoint::CheckpointError,
+    commitment::CommitmentError,
+    compression::CompressionError,
+    deferred::DeferredError,
+    envelope::EnvelopeError,
+    events::EventError,
+    executor::ExecutorError,
+    input::{
+        delegation::DelegationError, history::HistoryError, program_input::ProgramInputError,
+        system::SystemCallError, InputError,
+    },
+    instance::InstanceError,
+    integrity::IntegrityError,
+    interop::InteropError,
+    light_client::LightClientError,
+    model::ConversionError,
+    otlp::OtlpError,
+    output::OutputError,
+    policy::PolicyError,
+    profiler::ProfilerError,
+    program::ProgramError,
+    quorum::QuorumError,
+    refund::RefundError,
+    rlp::RlpError,
+    runner::BuiltinError,
+    serde::{builder::BuilderError, codegen::CodegenError, KakarotSerdeError},
+    sharp::SharpError,
+    solidity::SolidityError,
+    structlog::StructLogError,
+    tracer::TracerError,
+    verifier::VerifierError,
+};
+use cairo_vm::vm::errors::cairo_run_errors::CairoRunError;
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+use tonic::{metadata::MetadataMap, Code, Status};
+
+/// The gRPC metadata key of the code of an error.
+pub const ERROR_CODE_METADATA: &str = "keth-error-code";
+
+/// The gRPC metadata key of the category of an error.
+pub const ERROR_CATEGORY_METADATA: &str = "keth-error-category";
+
+/// The category of a [`KethError`], i.e. the stage of the pipeline it occurred in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCategory {
+    /// The input of the program: the block, its witness or the configuration.
+    Input,
+    /// The execution of the program and the analysis of its traces.
+    Execution,
+    /// The serialization of the Cairo values and of the run artifacts.
+    Serde,
+    /// The proving of the runs and the verification of their proofs.
+    Proving,
+    /// The storage of the artifacts.
+    Storage,
+    /// The submission of the runs to an external prover.
+    Submission,
+}
+
+impl ErrorCategory {
+    /// All the categories, by code.
+    pub const ALL: [Self; 6] =
+        [Self::Input, Self::Execution, Self::Serde, Self::Proving, Self::Storage, Self::Submission];
+
+    /// Returns the first code of the category, the codes of the category being in the thousand
+    /// starting at it.
+    pub const fn base_code(&self) -> u32 {
+        match self {
+            Self::Input => 1000,
+            Self::Execution => 2000,
+            Self::Serde => 3000,
+            Self::Proving => 4000,
+            Self::Storage => 5000,
+            Self::Submission => 6000,
+        }
+    }
+
+    /// Returns the category of an error code, if any.
+    pub fn from_code(code: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|category| code / 1000 == category.base_code() / 1000)
+    }
+
+    /// Returns the name of the category.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Input => "input",
+            Self::Execution => "execution",
+            Self::Serde => "serde",
+            Self::Proving => "proving",
+            Self::Storage => "storage",
+            Self::Submission => "submission",
+        }
+    }
+
+    /// Returns the gRPC status code of the errors of the category.
+    pub const fn grpc_code(&self) -> Code {
+        match self {
+            Self::Input => Code::InvalidArgument,
+            Self::Execution => Code::FailedPrecondition,
+            Self::Serde | Self::Proving | Self::Storage => Code::Internal,
+            Self::Submission => Code::Unavailable,
+        }
+    }
+}
+
+impl fmt::Display for ErrorCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ErrorCategory {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|category| category.as_str() == s)
+            .ok_or_else(|| format!("Unknown error category '{s}'"))
+    }
+}
+
+/// The errors of the crate, wrapping the errors of its modules.
+#[derive(Debug, Error)]
+pub enum KethError {
+    /// An error of the input sources of the witness.
+    #[error(transparent)]
+    Input(#[from] InputError),
+    /// An error of the program input.
+    #[error(transparent)]
+    ProgramInput(#[from] ProgramInputError),
+    /// An error of the block hash history.
+    #[error(transparent)]
+    History(#[from] HistoryError),
+    /// An error of the EIP-7702 delegations of the witness.
+    #[error(transparent)]
+    Delegation(#[from] DelegationError),
+    /// An error of the system calls of the block.
+    #[error(transparent)]
+    SystemCall(#[from] SystemCallError),
+    /// An error of the RLP encoding of the Cairo values.
+    #[error(transparent)]
+    Rlp(#[from] RlpError),
+    /// An error of the conversion of the block models.
+    #[error(transparent)]
+    Conversion(#[from] ConversionError),
+    /// An error of the configuration of an instance.
+    #[error(transparent)]
+    Instance(#[from] InstanceError),
+    /// An error of the configuration of the OTLP exporters.
+    #[error(transparent)]
+    Otlp(#[from] OtlpError),
+
+    /// An error of the execution of the program.
+    #[error(transparent)]
+    Executor(#[from] ExecutorError),
+    /// An error of the run of the program in the Cairo VM.
+    #[error(transparent)]
+    CairoRun(#[from] CairoRunError),
+    /// An error of the builtins of the runner.
+    #[error(transparent)]
+    Builtin(#[from] BuiltinError),
+    /// An error of the hint policy.
+    #[error(transparent)]
+    Policy(#[from] PolicyError),
+    /// An error of the attribution of the resources to the transactions.
+    #[error(transparent)]
+    Attribution(#[from] AttributionError),
+    /// An error of the decoding of the logs.
+    #[error(transparent)]
+    Event(#[from] EventError),
+    /// An error of the program output.
+    #[error(transparent)]
+    Output(#[from] OutputError),
+    /// An error of the gas refunds accounting.
+    #[error(transparent)]
+    Refund(#[from] RefundError),
+    /// An error of the transaction tracer.
+    #[error(transparent)]
+    Tracer(#[from] TracerError),
+    /// An error of the struct logger.
+    #[error(transparent)]
+    StructLog(#[from] StructLogError),
+    /// An error of the VM checkpoints.
+    #[error(transparent)]
+    Checkpoint(#[from] CheckpointError),
+    /// An error of the step profiler.
+    #[error(transparent)]
+    Profiler(#[from] ProfilerError),
+
+    /// An error of the serialization of the Cairo values.
+    #[error(transparent)]
+    Serde(#[from] KakarotSerdeError),
+    /// An error of the builder of [`KakarotSerde`](crate::serde::KakarotSerde).
+    #[error(transparent)]
+    SerdeBuilder(#[from] BuilderError),
+    /// An error of the generation of the Rust types of the Cairo structs.
+    #[error(transparent)]
+    Codegen(#[from] CodegenError),
+    /// An error of the conversion into the wire types of kakarot-rpc.
+    #[error(transparent)]
+    Interop(#[from] InteropError),
+    /// An error of the export of the analytics.
+    #[error(transparent)]
+    Analytics(#[from] AnalyticsError),
+
+    /// An error of the compiled program.
+    #[error(transparent)]
+    Program(#[from] ProgramError),
+    /// An error of the AIR inputs of the prover.
+    #[error(transparent)]
+    Air(#[from] AirError),
+    /// An error of the commitments to the traces.
+    #[error(transparent)]
+    Commitment(#[from] CommitmentError),
+    /// An error of the chaining of the proofs.
+    #[error(transparent)]
+    Chain(#[from] ChainError),
+    /// An error of the deferred proving.
+    #[error(transparent)]
+    Deferred(#[from] DeferredError),
+    /// An error of the quorum of provers.
+    #[error(transparent)]
+    Quorum(#[from] QuorumError),
+    /// An error of the verifier parameters.
+    #[error(transparent)]
+    Verifier(#[from] VerifierError),
+    /// An error of the re-proving campaigns.
+    #[error(transparent)]
+    Campaign(#[from] CampaignError),
+    /// An error of the KZG commitments of the blobs.
+    #[error(transparent)]
+    Blob(#[from] BlobError),
+    /// An error of the serialization of the proofs for the EVM verifiers.
+    #[error(transparent)]
+    Solidity(#[from] SolidityError),
+    /// An error of the light-client bundles.
+    #[error(transparent)]
+    LightClient(#[from] LightClientError),
+    /// An error of the benchmark of the prover costs.
+    #[error(transparent)]
+    Benchmark(#[from] BenchmarkError),
+
+    /// An error of the database.
+    #[error(transparent)]
+    Database(#[from] rusqlite::Error),
+    /// An error of the filesystem.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// An error of the compression of the artifacts.
+    #[error(transparent)]
+    Compression(#[from] CompressionError),
+    /// An error of the object storage of the artifacts.
+    #[error(transparent)]
+    ArtifactStore(#[from] ArtifactStoreError),
+    /// An error of the integrity of the artifacts.
+    #[error(transparent)]
+    Integrity(#[from] IntegrityError),
+    /// An error of the envelope and format migrations of the artifacts.
+    #[error(transparent)]
+    Envelope(#[from] EnvelopeError),
+
+    /// An error of the submission to SHARP.
+    #[error(transparent)]
+    Sharp(#[from] SharpError),
+}
+
+impl KethError {
+    /// Returns the stable numeric code of the error.
+    pub const fn code(&self) -> u32 {
+        match self {
+            Self::Input(_) => 1001,
+            Self::ProgramInput(_) => 1002,
+            Self::History(_) => 1003,
+            Self::Delegation(_) => 1004,
+            Self::SystemCall(_) => 1005,
+            Self::Rlp(_) => 1006,
+            Self::Conversion(_) => 1007,
+            Self::Instance(_) => 1008,
+            Self::Otlp(_) => 1009,
+            Self::Executor(_) => 2001,
+            Self::Builtin(_) => 2002,
+            Self::Policy(_) => 2003,
+            Self::Attribution(_) => 2004,
+            Self::Event(_) => 2005,
+            Self::Output(_) => 2006,
+            Self::Refund(_) => 2007,
+            Self::Tracer(_) => 2008,
+            Self::StructLog(_) => 2009,
+            Self::Checkpoint(_) => 2010,
+            Self::Profiler(_) => 2011,
+            Self::CairoRun(_) => 2012,
+            Self::Serde(_) => 3001,
+            Self::SerdeBuilder(_) => 3002,
+            Self::Codegen(_) => 3003,
+            Self::Interop(_) => 3004,
+            Self::Analytics(_) => 3005,
+            Self::Program(_) => 4001,
+            Self::Air(_) => 4002,
+            Self::Commitment(_) => 4003,
+            Self::Chain(_) => 4004,
+            Self::Deferred(_) => 4005,
+            Self::Quorum(_) => 4006,
+            Self::Verifier(_) => 4007,
+            Self::Campaign(_) => 4008,
+            Self::Blob(_) => 4009,
+            Self::Solidity(_) => 4010,
+            Self::LightClient(_) => 4011,
+            Self::Benchmark(_) => 4012,
+            Self::Database(_) => 5001,
+            Self::Io(_) => 5002,
+            Self::Compression(_) => 5003,
+            Self::ArtifactStore(_) => 5004,
+            Self::Integrity(_) => 5005,
+            Self::Envelope(_) => 5006,
+            Self::Sharp(_) => 6001,
+        }
+    }
+
+    /// Recovers the [`KethError`] of a report, if it carries the error of a module, returning the
+    /// report otherwise.
+    pub fn from_report(report: eyre::Report) -> Result<Self, eyre::Report> {
+        macro_rules! downcast {
+            ($report:ident, $($error:ty),* $(,)?) => {{
+                let $report = match $report.downcast::<Self>() {
+                    Ok(err) => return Ok(err),
+                    Err(report) => report,
+                };
+                $(
+                    let $report = match $report.downcast::<$error>() {
+                        Ok(err) => return Ok(err.into()),
+                        Err(report) => report,
+                    };
+                )*
+                Err($report)
+            }};
+        }
+
+        downcast!(
+            report,
+            InputError,
+            ProgramInputError,
+            HistoryError,
+            DelegationError,
+            SystemCallError,
+            RlpError,
+            ConversionError,
+            InstanceError,
+            OtlpError,
+            ExecutorError,
+            CairoRunError,
+            BuiltinError,
+            PolicyError,
+            AttributionError,
+            EventError,
+            OutputError,
+            RefundError,
+            TracerError,
+            StructLogError,
+            CheckpointError,
+            ProfilerError,
+            KakarotSerdeError,
+            BuilderError,
+            CodegenError,
+            InteropError,
+            AnalyticsError,
+            ProgramError,
+            AirError,
+            CommitmentError,
+            ChainError,
+            DeferredError,
+            QuorumError,
+            VerifierError,
+            CampaignError,
+            BlobError,
+            SolidityError,
+            LightClientError,
+            BenchmarkError,
+            rusqlite::Error,
+            std::io::Error,
+            CompressionError,
+            ArtifactStoreError,
+            IntegrityError,
+            EnvelopeError,
+            SharpError,
+        )
+    }
+
+    /// Returns the category of the error.
+    pub fn category(&self) -> ErrorCategory {
+        ErrorCategory::from_code(self.code()).expect("error codes are categorized")
+    }
+
+    /// Converts the error into a gRPC [`Status`], carrying its code and category in the metadata.
+    pub fn to_status(&self) -> Status {
+        let category = self.category();
+        let mut metadata = MetadataMap::new();
+        metadata.insert(ERROR_CODE_METADATA, self.code().into());
+        metadata.insert(
+            ERROR_CATEGORY_METADATA,
+            category.as_str().parse().expect("category names are valid metadata"),
+        );
+        Status::with_metadata(category.grpc_code(), self.to_string(), metadata)
+    }
+}
+
+impl From<KethError> for Status {
+    fn from(err: KethError) -> Self {
+        err.to_status()
+    }
+}
+
+/// Returns the code of the [`KethError`] carried by a gRPC [`Status`], if any.
+pub fn status_error_code(status: &Status) -> Option<u32> {
+    status.metadata().get(ERROR_CODE_METADATA)?.to_str().ok()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_category() {
+        for category in ErrorCategory::ALL {
+            assert_eq!(ErrorCategory::from_code(category.base_code() + 1), Some(category));
+            assert_eq!(category.as_str().parse(), Ok(category));
+        }
+        assert_eq!(ErrorCategory::from_code(42), None);
+        assert_eq!(ErrorCategory::from_code(7001), None);
+    }
+
+    #[test]
+    fn test_error_code() {
+        let err = KethError::from(SharpError::UnknownStatus("LOST".to_string()));
+        assert_eq!(err.code(), 6001);
+        assert_eq!(err.category(), ErrorCategory::Submission);
+        assert_eq!(err.to_string(), "Unknown SHARP job status 'LOST'");
+
+        let err = KethError::from(CommitmentError::InvalidChunkSize);
+        assert_eq!((err.code(), err.category()), (4003, ErrorCategory::Proving));
+        let err = KethError::from(std::io::Error::other("disk full"));
+        assert_eq!(err.category(), ErrorCategory::Storage);
+    }
+
+    #[test]
+    fn test_error_status() {
+        let status = Status::from(KethError::from(ProfilerError::UnknownFormat("svg".to_string())));
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        assert_eq!(status_error_code(&status), Some(2011));
+        assert_eq!(
+            status.metadata().get(ERROR_CATEGORY_METADATA).unwrap().to_str(),
+            Ok("execution")
+        );
+        assert_eq!(status_error_code(&Status::internal("other")), None);
+    }
+
+    #[test]
+    fn test_error_from_report() {
+        let report = eyre::Report::new(BenchmarkError::MissingBlock(7));
+        let err = KethError::from_report(report).unwrap();
+        assert_eq!((err.code(), err.category()), (4012, ErrorCategory::Proving));
+
+        let report = eyre::Report::new(KethError::from(CommitmentError::InvalidChunkSize));
+        assert_eq!(KethError::from_report(report).unwrap().code(), 4003);
+
+        let report = eyre::eyre!("other");
+        assert_eq!(KethError::from_report(report).unwrap_err().to_string(), "other");
+    }
+}
@@ -0,0 +1,284 @@
+//! Decoding of the EVM logs emitted during the Cairo execution of a block.
+//!
+//! Each invocation of [`EXECUTE_FUNCTION`] returns a `model.State*`, whose `events` hold the
+//! `model.Event`s emitted by the transaction. The `topics` of an event hold the address of the
+//! emitting contract followed by the topics as `Uint256` (low, high) pairs, `topics_len` counting
+//! felts, and its `data` holds one byte per felt.
+
+use crate::{
+    attribution::{felt_to_usize, invocations, FrameSpec, EXECUTE_FUNCTION},
+    model::U128_BYTES_SIZE,
+    serde::{cache::ProgramLayoutCache, CairoType},
+};
+use alloy_primitives::{Address, Bytes, Log, LogData, B256};
+use cairo_vm::{types::program::Program, vm::trace::trace_entry::RelocatedTraceEntry, Felt252};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// The full name of the `model.State` struct.
+const STATE_STRUCT: &str = "src.model.model.State";
+
+/// The full name of the `model.Event` struct.
+const EVENT_STRUCT: &str = "src.model.model.Event";
+
+/// Represents errors that can occur when decoding the events of a block.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum EventError {
+    /// Error variant indicating that an identifier is missing from the program.
+    #[error("Identifier '{0}' not found in the program")]
+    MissingIdentifier(String),
+
+    /// Error variant indicating that a struct member is missing from the program.
+    #[error("Member '{member}' not found in struct '{struct_name}'")]
+    MissingMember {
+        /// The name of the struct.
+        struct_name: String,
+        /// The name of the member.
+        member: String,
+    },
+
+    /// Error variant indicating that a memory cell does not hold the expected value.
+    #[error("Invalid value at relocated address {0}")]
+    InvalidValue(usize),
+}
+
+/// The memory layout of the events returned by [`EXECUTE_FUNCTION`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventLayout {
+    /// The offset of the `model.State*` among the explicit return values.
+    pub state_offset: usize,
+    /// The offsets of the `events_len` and `events` members of `model.State`.
+    pub state_events: (usize, usize),
+    /// The size of a `model.Event`.
+    pub event_size: usize,
+    /// The offsets of the `topics_len` and `topics` members of `model.Event`.
+    pub topics: (usize, usize),
+    /// The offsets of the `data_len` and `data` members of `model.Event`.
+    pub data: (usize, usize),
+}
+
+impl EventLayout {
+    /// Builds the layout of the events from the identifiers of the program.
+    pub fn from_program(program: &Program) -> Result<Self, EventError> {
+        let identifier = |name: &str| {
+            program.get_identifier(name).ok_or_else(|| EventError::MissingIdentifier(name.into()))
+        };
+        let offset = |struct_name: &str, member: &str| {
+            identifier(struct_name)?
+                .members
+                .as_ref()
+                .and_then(|members| members.get(member))
+                .map(|member| member.offset)
+                .ok_or_else(|| EventError::MissingMember {
+                    struct_name: struct_name.to_string(),
+                    member: member.to_string(),
+                })
+        };
+
+        // The state is the first `model.State*` of the return values.
+        let return_name = format!("{EXECUTE_FUNCTION}.Return");
+        let return_type = identifier(&return_name)?.cairo_type.clone().unwrap_or_default();
+        let CairoType::Tuple { members, .. } = CairoType::parse(&return_type) else {
+            return Err(EventError::MissingIdentifier(format!("{return_name}.state")));
+        };
+        let layouts = ProgramLayoutCache::new(program);
+        let mut state_offset = None;
+        let mut position = 0;
+        for member in &members {
+            if is_state_pointer(&member.typ) {
+                state_offset = Some(position);
+                break;
+            }
+            position += layouts
+                .type_size(&member.typ)
+                .map_err(|_| EventError::MissingIdentifier(return_name.clone()))?;
+        }
+
+        Ok(Self {
+            state_offset: state_offset
+                .ok_or_else(|| EventError::MissingIdentifier(format!("{return_name}.state")))?,
+            state_events: (offset(STATE_STRUCT, "events_len")?, offset(STATE_STRUCT, "events")?),
+            event_size: identifier(EVENT_STRUCT)?
+                .size
+                .ok_or_else(|| EventError::MissingIdentifier(EVENT_STRUCT.to_string()))?,
+            topics: (offset(EVENT_STRUCT, "topics_len")?, offset(EVENT_STRUCT, "topics")?),
+            data: (offset(EVENT_STRUCT, "data_len")?, offset(EVENT_STRUCT, "data")?),
+        })
+    }
+}
+
+/// An EVM log, located in its block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexedLog {
+    /// The number of the block.
+    pub block_number: u64,
+    /// The index of the transaction in the block.
+    pub tx_index: u32,
+    /// The index of the log in the transaction.
+    pub log_index: u32,
+    /// The log.
+    pub log: Log,
+}
+
+/// A filter of the logs, in the style of `eth_getLogs`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogFilter {
+    /// The first block of the range.
+    pub from_block: u64,
+    /// The last block of the range, included.
+    pub to_block: u64,
+    /// The address of the emitting contract, any when `None`.
+    pub address: Option<Address>,
+    /// The first topic of the logs, any when `None`.
+    pub topic0: Option<B256>,
+}
+
+/// Decodes the logs emitted by the invocations of [`EXECUTE_FUNCTION`] in a relocated trace.
+///
+/// Invocations that never return, e.g. at the end of an interrupted trace, have no logs.
+pub fn decode_logs(
+    spec: &FrameSpec,
+    layout: &EventLayout,
+    block_number: u64,
+    trace: &[RelocatedTraceEntry],
+    memory: &[Felt252],
+) -> Result<Vec<IndexedLog>, EventError> {
+    let felt = |address: usize| memory.get(address).ok_or(EventError::InvalidValue(address));
+    let read = |address: usize| felt(address).and_then(|value| as_usize(value, address));
+
+    let mut logs = Vec::new();
+    for (tx_index, (_, end)) in invocations(spec, trace).into_iter().enumerate() {
+        let Some(exit) = trace.get(end) else { continue };
+        let returns_start = exit.ap - spec.return_size;
+        let state = read(returns_start + layout.state_offset)?;
+        let events_len = read(state + layout.state_events.0)?;
+        let events = read(state + layout.state_events.1)?;
+
+        for log_index in 0..events_len {
+            let event = events + log_index * layout.event_size;
+
+            let topics_len = read(event + layout.topics.0)?;
+            let topics_ptr = read(event + layout.topics.1)?;
+            let address = felt(topics_ptr)?.to_bytes_be();
+            let topics = (1..topics_len)
+                .step_by(2)
+                .map(|offset| {
+                    let low = felt(topics_ptr + offset)?.to_bytes_be();
+                    let high = felt(topics_ptr + offset + 1)?.to_bytes_be();
+                    let mut topic = [0; 32];
+                    topic[..U128_BYTES_SIZE].copy_from_slice(&high[U128_BYTES_SIZE..]);
+                    topic[U128_BYTES_SIZE..].copy_from_slice(&low[U128_BYTES_SIZE..]);
+                    Ok(B256::from(topic))
+                })
+                .collect::<Result<Vec<_>, EventError>>()?;
+
+            let data_len = read(event + layout.data.0)?;
+            let data_ptr = read(event + layout.data.1)?;
+            let data = (0..data_len)
+                .map(|offset| {
+                    let address = data_ptr + offset;
+                    u8::try_from(read(address)?).map_err(|_| EventError::InvalidValue(address))
+                })
+                .collect::<Result<Bytes, EventError>>()?;
+
+            logs.push(IndexedLog {
+                block_number,
+                tx_index: tx_index as u32,
+                log_index: log_index as u32,
+                log: Log {
+                    address: Address::from_slice(&address[12..]),
+                    data: LogData::new_unchecked(topics, data),
+                },
+            });
+        }
+    }
+
+    Ok(logs)
+}
+
+/// Reads a felt holding a relocated address or a length.
+fn as_usize(value: &Felt252, address: usize) -> Result<usize, EventError> {
+    felt_to_usize(value).ok_or(EventError::InvalidValue(address))
+}
+
+/// Returns whether the type is a pointer to a `model.State`.
+fn is_state_pointer(cairo_type: &CairoType) -> bool {
+    matches!(
+        cairo_type,
+        CairoType::Pointer { pointee, .. }
+            if matches!(pointee.as_ref(), CairoType::Struct { scope, .. }
+                if scope.path.last().is_some_and(|name| name == "State"))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{address, b256};
+
+    #[test]
+    fn test_decode_logs() {
+        // A function without implicit arguments nor arguments, returning `(felt, model.State*)`.
+        let spec = FrameSpec {
+            pc: 10,
+            builtin_ptrs: vec![],
+            implicit_args_size: 0,
+            args_size: 0,
+            return_size: 2,
+        };
+        let layout = EventLayout {
+            state_offset: 1,
+            state_events: (0, 1),
+            event_size: 4,
+            topics: (0, 1),
+            data: (2, 3),
+        };
+        let trace = vec![
+            RelocatedTraceEntry { pc: 10, ap: 100, fp: 100 },
+            RelocatedTraceEntry { pc: 2, ap: 52, fp: 98 },
+        ];
+
+        // The state is at 60, its single event at 70, the topics at 80 and the data at 90.
+        let mut memory = vec![Felt252::ZERO; 101];
+        memory[51] = Felt252::from(60);
+        memory[60] = Felt252::ONE;
+        memory[61] = Felt252::from(70);
+        memory[70..74].copy_from_slice(&[3u64, 80, 2, 90].map(Felt252::from));
+        memory[80] = Felt252::from(0xabc);
+        memory[81] = Felt252::from(1);
+        memory[82] = Felt252::from(2);
+        memory[90] = Felt252::from(0xde);
+        memory[91] = Felt252::from(0xad);
+
+        let logs = decode_logs(&spec, &layout, 7, &trace, &memory).unwrap();
+
+        assert_eq!(
+            logs,
+            vec![IndexedLog {
+                block_number: 7,
+                tx_index: 0,
+                log_index: 0,
+                log: Log {
+                    address: address!("0000000000000000000000000000000000000abc"),
+                    data: LogData::new_unchecked(
+                        vec![b256!(
+                            "0000000000000000000000000000000200000000000000000000000000000001"
+                        )],
+                        Bytes::from_static(&[0xde, 0xad]),
+                    ),
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn test_event_layout_missing_function() {
+        let program_content = include_bytes!("../testdata/keccak_add_uint256.json");
+        let program = Program::from_bytes(program_content, Some("main")).unwrap();
+
+        assert_eq!(
+            EventLayout::from_program(&program),
+            Err(EventError::MissingIdentifier(format!("{EXECUTE_FUNCTION}.Return")))
+        );
+    }
+}
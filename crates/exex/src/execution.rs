@@ -1,17 +1,24 @@
-use crate::{db::Database, exex::CHAIN_SPEC};
-use alloy_primitives::U256;
+use crate::{
+    db::Database,
+    exex::CHAIN_SPEC,
+    input::system::{SystemCall, BEACON_ROOTS_ADDRESS},
+};
+use alloy_primitives::{KECCAK256_EMPTY, U256};
 use reth::primitives::BlockBody;
 use reth_execution_errors::BlockValidationError;
 use reth_node_api::{ConfigureEvm, ConfigureEvmEnv};
 use reth_node_ethereum::EthEvmConfig;
 use reth_primitives::{
-    revm_primitives::{CfgEnvWithHandlerCfg, EVMError, ExecutionResult, ResultAndState},
+    revm_primitives::{
+        Account, AccountStatus, CfgEnvWithHandlerCfg, EVMError, EvmStorageSlot, ExecutionResult,
+        HashMap, ResultAndState,
+    },
     Block, BlockWithSenders, EthereumHardfork, Header, Receipt, SealedBlockWithSenders,
     TransactionSigned, TransactionSignedEcRecovered,
 };
 use reth_revm::{
     db::{states::bundle_state::BundleRetention, BundleState},
-    DBBox, DatabaseCommit, Evm, StateBuilder, StateDBBox,
+    DBBox, Database as RevmDatabase, DatabaseCommit, Evm, State, StateBuilder, StateDBBox,
 };
 use reth_tracing::tracing::debug;
 
@@ -29,6 +36,9 @@ pub async fn execute_block(
     let evm_config = EthEvmConfig::new(CHAIN_SPEC.clone());
     let mut evm = configure_evm(&evm_config, db, header);
 
+    // Apply the EIP-4788 update of the parent beacon block root before the transactions.
+    apply_beacon_root_call(&mut evm, header)?;
+
     // Execute the transactions in the block and retrieve the executed transactions, receipts, and
     // results.
     let (executed_txs, receipts, results) = execute_transactions(&mut evm, header, txs)?;
@@ -63,6 +73,20 @@ pub fn configure_evm<'a>(
             .build(),
     );
 
+    // Configure the environment of the block.
+    configure_block_env(config, &mut evm, header);
+
+    // Return the configured EVM instance.
+    evm
+}
+
+/// Configures the environment of an EVM, with any database and external context, for the given
+/// block header, e.g. of the EVMs replaying a block with an inspector.
+pub fn configure_block_env<EXT, DB: RevmDatabase>(
+    config: &EthEvmConfig,
+    evm: &mut Evm<'_, EXT, State<DB>>,
+    header: &Header,
+) {
     // Set the state clearing flag based on the active fork at the given block number.
     evm.db_mut().set_state_clear_flag(
         CHAIN_SPEC.fork(EthereumHardfork::Cancun).active_at_block(header.number),
@@ -76,9 +100,48 @@ pub fn configure_evm<'a>(
 
     // Update the EVM's configuration environment with the newly populated configuration.
     *evm.cfg_mut() = cfg.cfg_env;
+}
 
-    // Return the configured EVM instance.
-    evm
+/// Configures the environment of an EVM as [`configure_block_env`], its transactions being
+/// validated against the given chain id, e.g. the one of the instance replaying the block.
+pub fn configure_chain_block_env<EXT, DB: RevmDatabase>(
+    config: &EthEvmConfig,
+    evm: &mut Evm<'_, EXT, State<DB>>,
+    header: &Header,
+    chain_id: u64,
+) {
+    configure_block_env(config, evm, header);
+    evm.cfg_mut().chain_id = chain_id;
+}
+
+/// Applies the EIP-4788 system call of a block, storing its timestamp and parent beacon block root
+/// in the ring buffers of the beacon roots contract.
+///
+/// The call is skipped for the blocks without parent beacon block root, i.e. before Cancun, and
+/// when the contract is not deployed.
+pub fn apply_beacon_root_call(
+    evm: &mut Evm<'_, (), StateDBBox<'_, eyre::Report>>,
+    header: &Header,
+) -> eyre::Result<()> {
+    let Some(writes) = SystemCall::BeaconRoot.storage_writes(header) else { return Ok(()) };
+    let db = evm.db_mut();
+    let Some(info) = db.basic(BEACON_ROOTS_ADDRESS)? else { return Ok(()) };
+    if info.code_hash == KECCAK256_EMPTY {
+        return Ok(());
+    }
+
+    let storage = writes
+        .into_iter()
+        .map(|(slot, value)| {
+            let slot = U256::from_be_bytes(slot.0);
+            Ok((slot, EvmStorageSlot::new_changed(db.storage(BEACON_ROOTS_ADDRESS, slot)?, value)))
+        })
+        .collect::<eyre::Result<_>>()?;
+    let account = Account { info, storage, status: AccountStatus::Touched };
+    db.commit(HashMap::from_iter([(BEACON_ROOTS_ADDRESS, account)]));
+
+    debug!(number = header.number, "Applied beacon root system call");
+    Ok(())
 }
 
 /// Execute a list of transactions, returning the executed transactions, their receipts, and
@@ -154,3 +217,60 @@ pub fn execute_transactions(
     // Not sure everything will be useful, but it's better to return everything for now.
     Ok((executed_txs, receipts, results))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::B256;
+    use reth_primitives::revm_primitives::AccountInfo;
+    use rusqlite::Connection;
+    use std::collections::BTreeMap;
+
+    fn header() -> Header {
+        Header {
+            number: 10,
+            timestamp: 8192,
+            parent_beacon_block_root: Some(B256::repeat_byte(0xbb)),
+            ..Default::default()
+        }
+    }
+
+    /// Applies the beacon root system call of a block, returning the storage of the beacon roots
+    /// contract in the resulting bundle.
+    fn apply(db: &mut Database, header: &Header) -> Option<BTreeMap<U256, U256>> {
+        let config = EthEvmConfig::new(CHAIN_SPEC.clone());
+        let mut evm = configure_evm(&config, db, header);
+        apply_beacon_root_call(&mut evm, header).unwrap();
+        evm.db_mut().merge_transitions(BundleRetention::Reverts);
+        let bundle = evm.db_mut().take_bundle();
+        bundle.state.get(&BEACON_ROOTS_ADDRESS).map(|account| {
+            account.storage.iter().map(|(slot, value)| (*slot, value.present_value)).collect()
+        })
+    }
+
+    #[test]
+    fn test_apply_beacon_root_call() {
+        let mut db = Database::new(Connection::open_in_memory().unwrap()).unwrap();
+        let header = header();
+
+        // The call is skipped while the contract is not deployed.
+        assert_eq!(apply(&mut db, &header), None);
+
+        let info = AccountInfo { code_hash: B256::repeat_byte(1), ..Default::default() };
+        db.set_account(BEACON_ROOTS_ADDRESS, info).unwrap();
+        let writes = SystemCall::BeaconRoot.storage_writes(&header).unwrap();
+        assert_eq!(
+            apply(&mut db, &header),
+            Some(
+                writes
+                    .into_iter()
+                    .map(|(slot, value)| (U256::from_be_bytes(slot.0), value))
+                    .collect()
+            )
+        );
+
+        // The blocks without parent beacon block root, before Cancun, have no call.
+        let pre_cancun = Header { parent_beacon_block_root: None, ..header };
+        assert_eq!(apply(&mut db, &pre_cancun), None);
+    }
+}
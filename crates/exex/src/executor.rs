@@ -0,0 +1,222 @@
+//! The execution modes of the Kakarot program.
+//!
+//! A block is proven from a proof-mode run, which generates and relocates the trace and the memory
+//! consumed by the prover. A dry run executes the same program with the trace and the proof mode
+//! disabled, only to count its steps and builtin instances and to decode its output: it validates
+//! the inputs of a block and estimates the size of its trace before committing to the expensive
+//! proof-mode run.
+
+use crate::{
+    error::KethError,
+    limits::{run_with_limits, ExecutionLimits, LimitedRun},
+    output::{read_output, ProgramOutput},
+    tuning::{RunProfile, RunnerTuning},
+};
+use cairo_vm::{
+    cairo_run::CairoRunConfig,
+    hint_processor::hint_processor_definition::HintProcessor,
+    types::layout_name::LayoutName,
+    vm::{
+        errors::{cairo_run_errors::CairoRunError, runner_errors::RunnerError},
+        runners::cairo_runner::CairoRunner,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, str::FromStr};
+use thiserror::Error;
+
+/// The size of a relocated trace entry, its `ap`, `fp` and `pc` registers, as written by the
+/// prover.
+pub const TRACE_ENTRY_BYTES: usize = 24;
+
+/// Represents errors that can occur when executing the Kakarot program.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ExecutorError {
+    /// Error variant indicating an unknown execution mode.
+    #[error("Unknown execution mode '{0}', expected 'proof' or 'dry-run'")]
+    UnknownMode(String),
+}
+
+/// The mode of an execution of the Kakarot program.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExecutionMode {
+    /// The trace and the relocated memory are generated in proof mode, for the prover.
+    #[default]
+    Proof,
+    /// The program is run without trace nor proof mode, for its resources and output only.
+    DryRun,
+}
+
+impl ExecutionMode {
+    /// Returns the name of the mode.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Proof => "proof",
+            Self::DryRun => "dry-run",
+        }
+    }
+
+    /// Returns the configuration of the Cairo runs in this mode.
+    pub fn run_config(&self) -> CairoRunConfig<'static> {
+        let proof = *self == Self::Proof;
+        CairoRunConfig {
+            layout: LayoutName::all_cairo,
+            trace_enabled: proof,
+            relocate_mem: proof,
+            proof_mode: proof,
+            ..Default::default()
+        }
+    }
+}
+
+impl FromStr for ExecutionMode {
+    type Err = ExecutorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "proof" => Ok(Self::Proof),
+            "dry-run" => Ok(Self::DryRun),
+            _ => Err(ExecutorError::UnknownMode(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for ExecutionMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The resources and the output of a dry run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DryRunReport {
+    /// The number of Cairo steps run.
+    pub steps: usize,
+    /// The number of instances of each builtin used, by name.
+    pub builtins: BTreeMap<String, usize>,
+    /// The used size of the memory segments and the number of dictionaries.
+    pub profile: RunProfile,
+    /// The decoded output of the program, `None` when missing or invalid.
+    pub output: Option<ProgramOutput>,
+}
+
+impl DryRunReport {
+    /// Reports the resources and the output of an ended runner.
+    pub fn from_runner(runner: &CairoRunner) -> Result<Self, RunnerError> {
+        let resources = runner.get_execution_resources()?;
+        let builtins = resources
+            .builtin_instance_counter
+            .iter()
+            .map(|(builtin, instances)| (builtin.to_str().to_string(), *instances))
+            .collect();
+        let output = read_output(runner)?.and_then(|felts| ProgramOutput::from_felts(&felts).ok());
+
+        Ok(Self {
+            steps: resources.n_steps,
+            builtins,
+            profile: RunProfile::from_runner(runner),
+            output,
+        })
+    }
+
+    /// Returns the estimated size in bytes of the relocated trace of the proof-mode run, which
+    /// pads the steps to the next power of two.
+    pub fn estimated_trace_size(&self) -> usize {
+        self.steps.next_power_of_two().saturating_mul(TRACE_ENTRY_BYTES)
+    }
+}
+
+/// The outcome of [`dry_run`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DryRun {
+    /// The dry run completed.
+    Completed(DryRunReport),
+    /// The dry run was interrupted by its limits, after the given number of steps.
+    Interrupted(usize),
+    /// The dry run was preempted.
+    Preempted,
+}
+
+/// Executes a program in the given mode, as [`run_with_limits`].
+pub fn execute(
+    program: &[u8],
+    mode: ExecutionMode,
+    hint_processor: &mut dyn HintProcessor,
+    limits: &ExecutionLimits,
+    tuning: &RunnerTuning,
+    preempt: &mut dyn FnMut() -> bool,
+) -> Result<LimitedRun, KethError> {
+    Ok(run_with_limits(program, &mode.run_config(), hint_processor, limits, tuning, preempt)?)
+}
+
+/// Executes a program in [`ExecutionMode::DryRun`], reporting its resources and output.
+pub fn dry_run(
+    program: &[u8],
+    hint_processor: &mut dyn HintProcessor,
+    limits: &ExecutionLimits,
+    tuning: &RunnerTuning,
+    preempt: &mut dyn FnMut() -> bool,
+) -> Result<DryRun, KethError> {
+    let run = execute(program, ExecutionMode::DryRun, hint_processor, limits, tuning, preempt)?;
+    Ok(match run {
+        LimitedRun::Completed(runner) => {
+            let report = DryRunReport::from_runner(&runner).map_err(CairoRunError::from)?;
+            DryRun::Completed(report)
+        }
+        LimitedRun::Interrupted(partial) => DryRun::Interrupted(partial.diagnostics.steps),
+        LimitedRun::Preempted => DryRun::Preempted,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hints::KakarotHintProcessor;
+
+    #[test]
+    fn test_execution_mode() {
+        for mode in [ExecutionMode::Proof, ExecutionMode::DryRun] {
+            assert_eq!(mode.as_str().parse(), Ok(mode));
+        }
+        assert_eq!(
+            "trace".parse::<ExecutionMode>(),
+            Err(ExecutorError::UnknownMode("trace".to_string()))
+        );
+
+        let config = ExecutionMode::DryRun.run_config();
+        assert!(!config.trace_enabled && !config.relocate_mem && !config.proof_mode);
+        let config = ExecutionMode::Proof.run_config();
+        assert!(config.trace_enabled && config.relocate_mem && config.proof_mode);
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let program = include_bytes!("../testdata/keccak_add_uint256.json");
+        let mut hint_processor = KakarotHintProcessor::default();
+
+        let result = dry_run(
+            program,
+            &mut hint_processor,
+            &ExecutionLimits::default(),
+            &RunnerTuning::default(),
+            &mut || false,
+        )
+        .unwrap();
+        let DryRun::Completed(report) = result else {
+            panic!("Expected a completed dry run");
+        };
+
+        assert!(report.steps > 0);
+        assert!(report.profile.segments.contains_key(crate::tuning::EXECUTION_SEGMENT));
+        assert!(report.estimated_trace_size() >= report.steps * TRACE_ENTRY_BYTES);
+        // The test program has no Kakarot output.
+        assert_eq!(report.output, None);
+
+        let limits = ExecutionLimits { max_steps: Some(5), timeout: None };
+        let mut hint_processor = KakarotHintProcessor::default();
+        let result =
+            dry_run(program, &mut hint_processor, &limits, &RunnerTuning::default(), &mut || false)
+                .unwrap();
+        assert_eq!(result, DryRun::Interrupted(5));
+    }
+}
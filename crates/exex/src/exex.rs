@@ -1,28 +1,69 @@
-use crate::{db::Database, hints::KakarotHintProcessor};
+use crate::{
+    artifacts::{ArtifactStore, LifecyclePolicy, CACHE_DIR},
+    attribution::{attribute_transactions, FrameSpec},
+    blob,
+    commitment::commit_execution,
+    db::Database,
+    deferred::{ProvingJob, ProvingMode},
+    divergence,
+    events::{decode_logs, EventLayout},
+    executor::{dry_run, execute, DryRun, ExecutionMode},
+    failures::{self, FailureAggregator, Report},
+    hints::KakarotHintProcessor,
+    input::{
+        cache::CachedInputSource,
+        local::{BlockInputSource, LocalInputSource},
+        program_input::ProgramInput,
+    },
+    instance::InstanceConfig,
+    limits::{ExecutionLimits, LimitedRun, PartialRun, MAX_LIMIT_RETRIES},
+    output::{read_output, ProgramOutput},
+    policy::{HintAudit, HintPolicy, PolicyHintProcessor},
+    program::{BlockProgram, KakarotProgram, ProgramRegistry},
+    receipts::{CairoOutcome, ExecuteLayout},
+    retry,
+    scheduler::{Lane, Scheduler},
+    serde::{cache::ProgramLayoutCache, relocated::RelocatedMemory, storage::StoragePreimages},
+    store::{self, SharedStore},
+    telemetry::{self, Stage},
+    tuning::{RunProfile, RunnerTuning, TuningConfig},
+    watchdog::{Heartbeat, Watchdog, WatchdogConfig},
+};
 use alloy_genesis::Genesis;
-use alloy_primitives::Address;
+use alloy_primitives::{Address, B256};
 use cairo_vm::{
     air_private_input::AirPrivateInput,
     air_public_input::PublicInput,
-    cairo_run::{cairo_run, CairoRunConfig},
-    types::layout_name::LayoutName,
-    vm::trace::trace_entry::RelocatedTraceEntry,
+    types::program::Program,
+    vm::{runners::cairo_runner::CairoRunner, trace::trace_entry::RelocatedTraceEntry},
     Felt252,
 };
 use futures::StreamExt;
+use metrics::Label;
 use once_cell::sync::Lazy;
 use reth_chainspec::{ChainSpec, ChainSpecBuilder};
+use reth_execution_types::Chain;
 use reth_exex::{ExExContext, ExExEvent};
 use reth_node_api::FullNodeComponents;
-use reth_primitives::BlockNumHash;
+use reth_primitives::{BlockNumHash, SealedBlockWithSenders};
+use reth_tracing::tracing::{debug, error, info, warn};
 use rusqlite::Connection;
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
+use tokio::sync::mpsc;
 
 /// The path to the SQLite database file.
 pub const DATABASE_PATH: &str = "rollup.db";
 
+/// The file name of the memory dump of an execution interrupted by its limits.
+pub const PARTIAL_RUN_DUMP_FILE: &str = "partial.dump.zst";
+
 /// The chain ID of the Kakarot Rollup chain.
-const CHAIN_ID: u64 = 1;
+pub(crate) const CHAIN_ID: u64 = 1;
 
 /// The address of the rollup submitter (signer) to be funded with max coins.
 const ROLLUP_SUBMITTER_ADDRESS: Address = Address::new([0; 20]);
@@ -41,94 +82,951 @@ pub(crate) static CHAIN_SPEC: Lazy<Arc<ChainSpec>> = Lazy::new(|| {
 });
 
 /// The Execution Extension for the Kakarot Rollup chain.
+///
+/// The committed and reverted chains are dispatched to the queue of each [`Instance`], which
+/// processes them on its own thread, so that a slow or failing instance does not hold back the
+/// others. The finished height is reported to the node once all the instances are done with the
+/// blocks up to it, see [`FinishedHeights`].
 #[allow(missing_debug_implementations)]
 pub struct KakarotRollup<Node: FullNodeComponents> {
     /// Capture the Execution Extension context.
     ctx: ExExContext<Node>,
-    /// The SQLite database.
-    db: Database,
+    /// The Kakarot instances.
+    instances: Vec<Instance>,
+    /// The source of the program inputs shared by the instances, caching the witnesses across
+    /// blocks.
+    inputs: Arc<CachedInputSource<LocalInputSource<Node::Provider>>>,
 }
 
 impl<Node: FullNodeComponents> KakarotRollup<Node> {
-    /// Creates a new instance of the [`KakarotRollup`] structure.
+    /// Creates a new instance of the [`KakarotRollup`] structure, running the default instance.
     pub fn new(ctx: ExExContext<Node>, connection: Connection) -> eyre::Result<Self> {
-        Ok(Self { ctx, db: Database::new(connection)? })
+        let instance = Instance::new(InstanceConfig::default(), Database::new(connection)?);
+        Self::with_instances(ctx, vec![instance])
+    }
+
+    /// Creates a new [`KakarotRollup`] running several Kakarot instances.
+    ///
+    /// The program inputs of the blocks are built from the provider of the node, the witnesses
+    /// being cached across blocks and invalidated by the changes of the committed chains.
+    pub fn with_instances(ctx: ExExContext<Node>, instances: Vec<Instance>) -> eyre::Result<Self> {
+        let configs: Vec<_> = instances.iter().map(|instance| instance.config.clone()).collect();
+        InstanceConfig::validate_all(&configs)?;
+
+        let inputs =
+            Arc::new(CachedInputSource::new(LocalInputSource::new(ctx.provider().clone())));
+        let instances = instances
+            .into_iter()
+            .map(|instance| instance.with_input_source(inputs.clone()))
+            .collect();
+        Ok(Self { ctx, instances, inputs })
     }
 
     /// Starts processing chain state notifications.
     pub async fn start(mut self) -> eyre::Result<()> {
-        // Initialize the Cairo run configuration
-        let config = CairoRunConfig {
-            layout: LayoutName::all_cairo,
-            trace_enabled: true,
-            relocate_mem: true,
-            proof_mode: true,
-            ..Default::default()
+        // Spawn the watchdog of the instances watching their stages for stalls.
+        let watchdogs: Vec<_> = self
+            .instances
+            .iter()
+            .filter_map(Instance::watchdog)
+            .map(|watchdog| tokio::spawn(watchdog.run()))
+            .collect();
+
+        // Spawn a worker per instance, each with its own queue, reporting the blocks it is done
+        // with on the shared completion channel.
+        let configs = self.instances.iter().map(|instance| instance.config.clone()).collect();
+        let mut finished = FinishedHeights::new(configs);
+        let (completions, mut completed) = mpsc::unbounded_channel();
+        let (queues, workers): (Vec<_>, Vec<_>) = std::mem::take(&mut self.instances)
+            .into_iter()
+            .enumerate()
+            .map(|(index, instance)| {
+                let (sender, receiver) = mpsc::unbounded_channel();
+                let completions = completions.clone();
+                let worker =
+                    tokio::task::spawn_blocking(move || instance.run(index, receiver, completions));
+                (sender, worker)
+            })
+            .unzip();
+        drop(completions);
+
+        loop {
+            tokio::select! {
+                // Process all new chain state notifications
+                notification = self.ctx.notifications.next() => {
+                    let Some(notification) = notification else { break };
+                    let notification = notification?;
+
+                    // Dispatch the reverted chain of a reorg or a revert to the instances first,
+                    // so that they roll back its blocks before the new chain is scheduled.
+                    if let Some(reverted_chain) = notification.reverted_chain() {
+                        self.inputs.revert_chain(&reverted_chain);
+                        finished.revert(reverted_chain.first().number);
+                        for queue in &queues {
+                            queue.send(ChainEvent::Reverted(reverted_chain.clone()))?;
+                        }
+                    }
+
+                    // Check if the notification contains a committed chain.
+                    if let Some(committed_chain) = notification.committed_chain() {
+                        // Record the changes of the committed chain before its blocks are built,
+                        // so that the cached witnesses of the previous blocks serve them.
+                        self.inputs.commit_chain(&committed_chain);
+
+                        // Dispatch the committed chain to the instances, its blocks being pending
+                        // until they are done with them.
+                        finished.commit(
+                            committed_chain
+                                .blocks()
+                                .values()
+                                .map(|block| BlockNumHash::new(block.number, block.hash())),
+                        );
+                        for queue in &queues {
+                            queue.send(ChainEvent::Committed(committed_chain.clone()))?;
+                        }
+                    }
+                }
+                Some(completion) = completed.recv() => finished.complete(completion),
+            }
+
+            // Send a notification that the chain processing is finished up to the height done by
+            // all the instances.
+            //
+            // The ExEx will not require all earlier blocks which can be pruned.
+            if let Some(height) = finished.advance() {
+                self.ctx.events.send(ExExEvent::FinishedHeight(height))?;
+            }
+        }
+
+        // Close the queues and wait for the instances to drain them.
+        drop(queues);
+        for worker in workers {
+            worker.await?;
+        }
+        for watchdog in watchdogs {
+            watchdog.abort();
+        }
+
+        Ok(())
+    }
+}
+
+/// A Kakarot instance, running a Kakarot program against the committed chains.
+#[allow(missing_debug_implementations)]
+pub struct Instance {
+    /// The configuration of the instance.
+    config: InstanceConfig,
+    /// The SQLite database of the instance.
+    db: Database,
+    /// The store of the proofs and of the metadata of the proven blocks, the database unless a
+    /// proof store is configured.
+    store: SharedStore,
+    /// The metrics labels of the instance.
+    labels: Vec<Label>,
+    /// The directory of the artifacts of the instance, artifacts are not saved when `None`.
+    artifacts_dir: Option<PathBuf>,
+    /// The whitelist of the hints allowed to execute, all hints being allowed when `None`.
+    hint_policy: Option<HintPolicy>,
+    /// The lifecycle rules of the artifacts tiered to the object storage, if any.
+    lifecycle: Option<LifecyclePolicy>,
+    /// The registry of the programs proving each range of blocks, if any.
+    programs: Option<ProgramRegistry>,
+    /// The hashes of the programs selected so far, by path.
+    program_hashes: HashMap<PathBuf, B256>,
+    /// The struct layouts of the programs run so far, by path.
+    program_layouts: HashMap<PathBuf, Arc<ProgramLayoutCache>>,
+    /// The source of the program inputs of the blocks, none until the instance is run by a
+    /// [`KakarotRollup`].
+    inputs: Option<Arc<dyn BlockInputSource>>,
+    /// The rate limiter of the reports of the recurring failures.
+    failures: FailureAggregator,
+    /// The rate limiter of the utilization warnings, by segment and kind of warning.
+    utilization_warnings: FailureAggregator,
+    /// The configuration of the watchdog of the stalled stages, if any.
+    watchdog: Option<WatchdogConfig>,
+    /// The progress of the execution, watched by the watchdog.
+    heartbeat: Heartbeat,
+}
+
+impl Instance {
+    /// Creates a new [`Instance`] with the given database.
+    pub fn new(config: InstanceConfig, db: Database) -> Self {
+        let labels = config.labels();
+        let failures = FailureAggregator::new(config.failure_report_interval);
+        let utilization_warnings = FailureAggregator::new(config.failure_report_interval);
+        Self {
+            config,
+            store: Arc::new(db.clone()),
+            db,
+            labels,
+            artifacts_dir: None,
+            hint_policy: None,
+            lifecycle: None,
+            programs: None,
+            program_hashes: HashMap::new(),
+            program_layouts: HashMap::new(),
+            inputs: None,
+            failures,
+            utilization_warnings,
+            watchdog: None,
+            heartbeat: Heartbeat::default(),
+        }
+    }
+
+    /// Opens the database of the instance in its own directory of `data_dir`, and creates the
+    /// [`Instance`].
+    ///
+    /// Fails if the registry of the verifier parameters, the program registry, the hint policy,
+    /// the lifecycle rules or the watchdog configuration of the instance are invalid. The verifier
+    /// parameters are not loaded when proving is disabled, and must otherwise register a verifier
+    /// for the hash of each program of the instance.
+    ///
+    /// With an object storage, the large artifacts are tiered to it, cached in the artifacts
+    /// directory. With a proof store, the proofs and the metadata of the proven blocks are stored
+    /// in it rather than in the database.
+    pub fn open(config: InstanceConfig, data_dir: &Path) -> eyre::Result<Self> {
+        let programs = config.programs()?;
+        if let Some(programs) = &programs {
+            info!(instance = %config.name, forks = programs.entries().len(), "Loaded programs");
+        }
+        if !config.proving.is_enabled() {
+            info!(instance = %config.name, "Proving disabled, executing only");
+        } else if let Some(verifiers) = config.verifiers()? {
+            info!(instance = %config.name, entries = verifiers.len(), "Loaded verifier parameters");
+            // The proofs of a program without verifier could never be verified on-chain.
+            verifiers.check_program(KakarotProgram::load(&config.program)?.program_hash()?)?;
+            for entry in programs.iter().flat_map(ProgramRegistry::entries) {
+                verifiers.check_program(entry.load()?.1)?;
+            }
+        }
+        let hint_policy = config.hint_policy()?;
+        if let Some(policy) = &hint_policy {
+            info!(instance = %config.name, hints = policy.len(), "Loaded hint policy");
+        }
+
+        let path = config.database_path(data_dir);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut db = Database::new(Connection::open(path)?)?;
+        let artifacts_dir = config.artifacts_path(data_dir);
+        if let Some(url) = &config.artifact_store {
+            let store = ArtifactStore::open(url, Some(artifacts_dir.join(CACHE_DIR)))?;
+            info!(instance = %config.name, %url, "Tiering artifacts to object storage");
+            db = db.with_artifact_store(store);
+        }
+        let store = store::open_store(&db, config.proof_store.as_deref())?;
+        if let Some(path) = &config.proof_store {
+            info!(instance = %config.name, path = %path.display(), "Storing proofs in proof store");
+        }
+        let lifecycle = config.lifecycle()?;
+        let watchdog = config.watchdog()?;
+
+        Ok(Self {
+            store,
+            artifacts_dir: Some(artifacts_dir),
+            hint_policy,
+            lifecycle,
+            programs,
+            watchdog,
+            ..Self::new(config, db)
+        })
+    }
+
+    /// Sets the source of the program inputs of the blocks.
+    pub fn with_input_source(mut self, inputs: Arc<dyn BlockInputSource>) -> Self {
+        self.inputs = Some(inputs);
+        self
+    }
+
+    /// Returns the configuration of the instance.
+    pub const fn config(&self) -> &InstanceConfig {
+        &self.config
+    }
+
+    /// Returns the watchdog of the stalled stages of the instance, if configured.
+    fn watchdog(&self) -> Option<Watchdog> {
+        let config = self.watchdog.clone()?;
+        Some(Watchdog::new(
+            config,
+            self.config.name.clone(),
+            self.labels.clone(),
+            self.db.clone(),
+            self.heartbeat.clone(),
+        ))
+    }
+
+    /// Processes the committed chains of the queue until it is closed.
+    ///
+    /// The tip of each chain is scheduled in the tip lane, while its earlier blocks and the blocks
+    /// marked for retry are scheduled in the backfill lane. A running backfill job is preempted
+    /// when a tip job waits longer than allowed, and scheduled again.
+    ///
+    /// Errors are logged rather than returned, so that a failing instance does not stop the
+    /// others. Each block the instance is done with, processed, skipped or failed and marked for
+    /// retry, is reported as a [`Completion`] of the instance number `index`.
+    fn run(
+        mut self,
+        index: usize,
+        mut queue: mpsc::UnboundedReceiver<ChainEvent>,
+        completions: mpsc::UnboundedSender<Completion>,
+    ) {
+        let mut scheduler = Scheduler::new(self.config.scheduler);
+        // The number of reverts scheduled so far, telling the completions of the jobs started
+        // before a revert apart.
+        let mut reverts = 0;
+        loop {
+            // Wait for a chain when no job is pending, until the queue is closed.
+            let mut received = false;
+            if scheduler.is_empty() {
+                let Some(event) = queue.blocking_recv() else { break };
+                if let Some(from) = schedule_event(&self.config, &mut scheduler, &event) {
+                    self.revert(from);
+                    reverts += 1;
+                }
+                received = true;
+            }
+            while let Ok(event) = queue.try_recv() {
+                if let Some(from) = schedule_event(&self.config, &mut scheduler, &event) {
+                    self.revert(from);
+                    reverts += 1;
+                }
+                received = true;
+            }
+            if received {
+                self.schedule_retries(&mut scheduler);
+            }
+
+            let Some(job) = scheduler.pop() else { continue };
+            // The rollup may be gone when the node shuts down, the completions are then dropped.
+            let complete = |number| {
+                let _ = completions.send(Completion { instance: index, number, reverts });
+            };
+            // Backfill blocks already executed, e.g. before a restart, are skipped.
+            if job.lane == Lane::Backfill {
+                match self.executed(job.number) {
+                    Ok(true) => {
+                        complete(job.number);
+                        continue;
+                    }
+                    Ok(false) => {}
+                    Err(err) => {
+                        metrics::counter!("kakarot_exex_blocks_failed", self.labels.clone())
+                            .increment(1);
+                        self.report_failure(job.number, job.lane, &err);
+                        complete(job.number);
+                        continue;
+                    }
+                }
+            }
+
+            // Tip jobs received while running are scheduled, so that a starving tip lane preempts
+            // the running backfill job. The blocks reverted while running are rolled back once
+            // the job is over, not to race with the persistence of its results.
+            let config = self.config.clone();
+            let mut reverted: Option<u64> = None;
+            let mut reverts_while_running = 0;
+            let mut preempt = || {
+                while let Ok(event) = queue.try_recv() {
+                    if let Some(from) = schedule_event(&config, &mut scheduler, &event) {
+                        reverted = Some(reverted.map_or(from, |reverted| reverted.min(from)));
+                        reverts_while_running += 1;
+                    }
+                }
+                job.lane == Lane::Backfill && scheduler.tip_starving(Instant::now())
+            };
+
+            // The block is processed in its span, to follow its stages
+            let span = telemetry::block_span(&self.config.name, job.number);
+            let _entered = span.enter();
+            match self.process(job.number, &mut preempt) {
+                Ok(Processed::Done) => {
+                    metrics::counter!("kakarot_exex_blocks_processed", self.labels.clone())
+                        .increment(1);
+                    self.heartbeat.beat(job.number, retry::now());
+                    if job.lane == Lane::Tip {
+                        self.apply_lifecycle(job.number);
+                    }
+                    complete(job.number);
+                }
+                // A preempted job of a reverted block is dropped, the block being scheduled again
+                // by the new chain if it is part of it.
+                Ok(Processed::Preempted) if reverted.is_some_and(|from| job.number >= from) => {}
+                Ok(Processed::Preempted) => {
+                    metrics::counter!("kakarot_exex_blocks_preempted", self.labels.clone())
+                        .increment(1);
+                    info!(instance = %self.config.name, number = job.number, "Preempted backfill");
+                    scheduler.requeue(job);
+                }
+                Err(err) => {
+                    metrics::counter!("kakarot_exex_blocks_failed", self.labels.clone())
+                        .increment(1);
+                    self.report_failure(job.number, job.lane, &err);
+                    complete(job.number);
+                }
+            }
+            if let Some(from) = reverted {
+                self.revert(from);
+            }
+            reverts += reverts_while_running;
+        }
+    }
+
+    /// Returns whether a block was already executed. In deferred mode, the executed blocks have a
+    /// proving job rather than a trace.
+    fn executed(&self, number: u64) -> eyre::Result<bool> {
+        match self.config.proving {
+            ProvingMode::Deferred => Ok(self.db.proving_job_state(number)?.is_some()),
+            _ => self.db.has_execution_trace(number),
+        }
+    }
+
+    /// Rolls back the results persisted for the blocks from the given number onwards.
+    fn revert(&self, from: u64) {
+        match self.db.revert_blocks(from) {
+            Ok(deleted) => {
+                metrics::counter!("kakarot_exex_reverts", self.labels.clone()).increment(1);
+                info!(instance = %self.config.name, from, deleted, "Reverted blocks");
+            }
+            Err(err) => error!(instance = %self.config.name, from, %err, "Failed to revert blocks"),
+        }
+    }
+
+    /// Reports the failure of a block, counting it by fingerprint.
+    ///
+    /// The first failure of a fingerprint is logged in full, the next ones being summarized at
+    /// most once per report interval, see [`crate::failures`].
+    fn report_failure(&mut self, number: u64, lane: Lane, err: &eyre::Report) {
+        let message = err.to_string();
+        let fingerprint = failures::fingerprint(&message);
+        let count = match self.db.record_failure(
+            &fingerprint,
+            &failures::summary(&message),
+            number,
+            retry::now(),
+        ) {
+            Ok(failure) => failure.count,
+            Err(err) => {
+                error!(instance = %self.config.name, %err, "Failed to record failure");
+                0
+            }
+        };
+
+        match self.failures.record(&fingerprint, Instant::now()) {
+            Report::First => error!(
+                instance = %self.config.name,
+                number,
+                %lane,
+                %fingerprint,
+                %err,
+                "Failed to process block"
+            ),
+            Report::Repeated { suppressed } => error!(
+                instance = %self.config.name,
+                number,
+                %lane,
+                %fingerprint,
+                count,
+                suppressed,
+                summary = %failures::summary(&message),
+                "Failed to process block, recurring failure"
+            ),
+            Report::Suppressed => debug!(
+                instance = %self.config.name,
+                number,
+                %lane,
+                %fingerprint,
+                "Failed to process block, recurring failure"
+            ),
+        }
+    }
+
+    /// Applies the lifecycle rules of the tiered artifacts at the given tip, if any.
+    fn apply_lifecycle(&self, tip: u64) {
+        let Some(policy) = &self.lifecycle else { return };
+        match self.db.apply_lifecycle(policy, tip) {
+            Ok(report) => debug!(
+                instance = %self.config.name,
+                tip,
+                evicted = report.evicted,
+                expired = report.expired,
+                "Applied artifacts lifecycle"
+            ),
+            Err(err) => error!(instance = %self.config.name, %err, "Failed to apply lifecycle"),
+        }
+    }
+
+    /// Schedules the blocks marked for retry in the backfill lane.
+    fn schedule_retries(&self, scheduler: &mut Scheduler) {
+        match self.db.retries() {
+            Ok(retries) => {
+                for (number, _) in retries {
+                    scheduler.push(number, Lane::Backfill);
+                }
+            }
+            Err(err) => error!(instance = %self.config.name, %err, "Failed to load retries"),
+        }
+    }
+
+    /// Runs the Kakarot program of the instance for the given block.
+    ///
+    /// `preempt` is polled during the execution, which is abandoned when it returns `true`.
+    fn process(
+        &mut self,
+        number: u64,
+        preempt: &mut dyn FnMut() -> bool,
+    ) -> eyre::Result<Processed> {
+        // Select the program of the block, recorded with its hash
+        let path = self.select_program(number)?;
+
+        // Build the program input of the block, fed to the program by its hints, and record it
+        // with the block to run it again, e.g. in a replay or a tracer
+        let (block, input) = self.block_input(number)?;
+        self.db.insert_block(&block)?;
+        self.db.insert_program_input(number, &input)?;
+        if self.config.blob_da {
+            let witness = blob::commit_program_input(&self.db, number, &input)?;
+            debug!(
+                instance = %self.config.name,
+                number,
+                blobs = witness.blobs.len(),
+                "Committed to the blobs of the program input"
+            );
+        }
+        let input = Arc::new(input);
+
+        // Load the cairo program from the file, rejecting the hints out of the policy if any
+        let program = std::fs::read(&path)?;
+        if let Some(policy) = &self.hint_policy {
+            policy.check_program(&program)?;
+        }
+        let layouts = self.program_layouts(&path, &program)?;
+
+        // Execute the Kakarot os program, with the relaxed limits of a retry if any
+        let run_span = telemetry::stage_span(Stage::Run, number).entered();
+        let limits = self.db.retry_limits(number)?.unwrap_or(self.config.limits);
+        let tuning = self.tuning()?;
+
+        // Validate the block with a dry run before the proof-mode run, if enabled. In deferred
+        // mode, the block is only executed by the dry run, and proven when its job is exported.
+        let deferred = self.config.proving == ProvingMode::Deferred;
+        if self.config.dry_run || deferred {
+            let mut hint_processor = self.hint_processor(&layouts, &input);
+            match dry_run(&program, &mut hint_processor, &limits, &tuning, preempt)? {
+                DryRun::Completed(report) => {
+                    // The program outputs the header of the executed block, which must be the
+                    // processed block
+                    let expected = ProgramOutput::from(&input.block.block_header);
+                    match report.output {
+                        Some(output) if output == expected && output.block_number == number => {}
+                        Some(output) => eyre::bail!(
+                            "Output of the dry run {output:?} does not match block {number}: \
+                             {expected:?}"
+                        ),
+                        None => eyre::bail!("Invalid or missing output of the dry run"),
+                    }
+                    info!(
+                        instance = %self.config.name,
+                        number,
+                        steps = report.steps,
+                        trace_size = report.estimated_trace_size(),
+                        "Dry run completed"
+                    )
+                }
+                DryRun::Interrupted(steps) if deferred => {
+                    eyre::bail!(
+                        "Execution of block {number} interrupted by its limits after {steps} steps"
+                    )
+                }
+                // The proof-mode run is interrupted by the same limits, recording its diagnostics
+                DryRun::Interrupted(_) => {}
+                DryRun::Preempted => return Ok(Processed::Preempted),
+            }
+        }
+
+        // The job is exported from other working directories, e.g. by the CLI, so the path of its
+        // program is recorded absolute.
+        if deferred {
+            let program = std::fs::canonicalize(&path)?;
+            self.db.enqueue_proving_job(&ProvingJob::new(number, program))?;
+            return Ok(Processed::Done);
+        }
+
+        // Build the Kakarot hint processor.
+        let mut hint_processor = self.hint_processor(&layouts, &input);
+        let run =
+            execute(&program, ExecutionMode::Proof, &mut hint_processor, &limits, &tuning, preempt);
+        self.log_hint_audit(number, hint_processor.audit());
+        let mut res = match run? {
+            LimitedRun::Completed(runner) => runner,
+            LimitedRun::Interrupted(partial) => {
+                self.record_partial_run(number, &limits, *partial)?;
+                return Ok(Processed::Done);
+            }
+            LimitedRun::Preempted => return Ok(Processed::Preempted),
+        };
+        drop(run_span);
+
+        // Serialize the output and traces of the run to the database
+        let _serialize_span = telemetry::stage_span(Stage::Serialize, number).entered();
+
+        // Record the resources used by the block, to tune the runners of the next blocks
+        let profile = RunProfile::from_runner(&res);
+        self.db.insert_run_profile(number, &profile)?;
+        self.record_utilization(number, &profile);
+
+        // Retrieve the output of the program
+        let mut output_buffer = String::new();
+        res.vm.write_output(&mut output_buffer).unwrap();
+        info!(instance = %self.config.name, number, output = %output_buffer, "Program output");
+
+        // Record the decoded output, from which the fact of the proof of the block is computed
+        let output = match read_output(&res)?.map(|felts| ProgramOutput::from_felts(&felts)) {
+            Some(Ok(output)) => {
+                self.store.insert_program_output(&output)?;
+                Some(output)
+            }
+            Some(Err(err)) => {
+                warn!(instance = %self.config.name, number, %err, "Invalid output");
+                None
+            }
+            None => {
+                warn!(instance = %self.config.name, number, "Missing program output");
+                None
+            }
+        };
+
+        // Extract the execution trace
+        let trace = res.relocated_trace.clone().unwrap_or_default();
+
+        // Extract the relocated memory
+        let memory: Vec<_> =
+            res.relocated_memory.clone().into_iter().map(|x| x.unwrap_or_default()).collect();
+
+        // Attribute the Cairo resources to the transactions of the block. Nothing can be recorded
+        // about the transactions of a program which does not execute them.
+        match FrameSpec::execute(res.get_program()) {
+            Ok(spec) => {
+                let resources = attribute_transactions(&spec, &trace, &memory)?;
+                self.db.insert_transaction_resources(number, &resources)?;
+
+                // Index the logs, queryable once the block is proven.
+                match EventLayout::from_program(res.get_program()) {
+                    Ok(layout) => {
+                        let logs = decode_logs(&spec, &layout, number, &trace, &memory)?;
+                        self.db.insert_logs(number, &logs)?;
+                    }
+                    Err(err) => {
+                        error!(instance = %self.config.name, number, %err, "Logs not indexed")
+                    }
+                }
+
+                // Verify the receipts of the transactions against the header of the block.
+                if let Some(output) = &output {
+                    self.verify_receipts(number, &program, &res, &input, &trace, output)?;
+                }
+
+                // Compare the transactions with their native execution, in differential mode.
+                if self.config.differential.is_some() {
+                    let preimages = StoragePreimages::from_scopes(&res.exec_scopes)
+                        .cloned()
+                        .unwrap_or_default();
+                    self.report_divergences(&block, &program, &input, &trace, &memory, &preimages);
+                }
+            }
+            Err(err) if input.block.transactions.is_empty() => {
+                debug!(instance = %self.config.name, number, %err, "Skipping attribution");
+                // A block without transactions has no logs.
+                self.db.insert_logs(number, &[])?;
+            }
+            Err(err) => {
+                error!(
+                    instance = %self.config.name,
+                    number,
+                    %err,
+                    transactions = input.block.transactions.len(),
+                    "Transactions not executed, their resources, logs and receipts are not recorded"
+                );
+                metrics::counter!("kakarot_exex_blocks_unexecuted", self.labels.clone())
+                    .increment(1);
+            }
+        }
+
+        // Extract the public and private inputs
+        //
+        // We want to store the public input in the database in order to use them to run
+        // the prover
+        let public_input = res.get_air_public_input()?;
+        let private_input = res.get_air_private_input();
+
+        // Commit the execution trace to the database
+        self.commit_cairo_execution_traces(number, trace, memory, public_input, private_input)?;
+
+        // The block completed within its limits, it no longer needs a retry
+        self.db.clear_retry(number)?;
+
+        Ok(Processed::Done)
+    }
+
+    /// Verifies the receipts of the Cairo execution of a block, read from its trace, and the output
+    /// of the program against the header of the block, logging the mismatches.
+    fn verify_receipts(
+        &self,
+        number: u64,
+        program: &[u8],
+        runner: &CairoRunner,
+        input: &ProgramInput,
+        trace: &[RelocatedTraceEntry],
+        output: &ProgramOutput,
+    ) -> eyre::Result<()> {
+        let layout = ExecuteLayout::from_program(runner.get_program())?;
+        let outcome = CairoOutcome::from_trace(
+            program,
+            &layout,
+            number,
+            &input.block.transactions,
+            trace,
+            RelocatedMemory::from_runner(runner),
+        )?;
+        for mismatch in outcome.verify_header(output, &input.block.block_header) {
+            error!(instance = %self.config.name, %mismatch, "Receipt mismatch");
+        }
+        Ok(())
+    }
+
+    /// Compares the Cairo execution of a block with its native execution, in differential mode,
+    /// saving the report of each diverging transaction in the artifacts directory of the block.
+    ///
+    /// The comparison is a diagnostic: its failures are logged, not to fail the block.
+    fn report_divergences(
+        &self,
+        block: &SealedBlockWithSenders,
+        program: &[u8],
+        input: &ProgramInput,
+        trace: &[RelocatedTraceEntry],
+        memory: &[Felt252],
+        preimages: &StoragePreimages,
+    ) {
+        let Some(format) = self.config.differential else { return };
+        let number = block.header.number;
+        let result = divergence::compare_block(program, input, block, trace, memory, preimages);
+        let reports = match result {
+            Ok(reports) => reports,
+            Err(err) => {
+                warn!(instance = %self.config.name, number, %err, "Failed to compare the block");
+                return;
+            }
         };
 
-        // Process all new chain state notifications
-        while let Some(notification) = self.ctx.notifications.next().await {
-            // Check if the notification contains a committed chain.
-            if let Some(committed_chain) = notification?.committed_chain() {
-                // Get the tip of the committed chain.
-                let tip = committed_chain.tip();
-
-                // Send a notification that the chain processing is finished.
-                //
-                // Finished height is the tip of the committed chain.
-                //
-                // The ExEx will not require all earlier blocks which can be pruned.
-                self.ctx
-                    .events
-                    .send(ExExEvent::FinishedHeight(BlockNumHash::new(tip.number, tip.hash())))?;
-
-                // Build the Kakarot hint processor.
-                let mut hint_processor = KakarotHintProcessor::default().build();
-
-                // Load the cairo program from the file
-                let program = std::fs::read(PathBuf::from("../../cairo/programs/os.json"))?;
-
-                // Execute the Kakarot os program
-                let mut res = cairo_run(&program, &config, &mut hint_processor)?;
-
-                // Retrieve the output of the program
-                let mut output_buffer = String::new();
-                res.vm.write_output(&mut output_buffer).unwrap();
-                println!("Program output: \n{}", output_buffer);
-
-                // Extract the execution trace
-                let trace = res.relocated_trace.clone().unwrap_or_default();
-
-                // Extract the relocated memory
-                let memory = res
-                    .relocated_memory
-                    .clone()
-                    .into_iter()
-                    .map(|x| x.unwrap_or_default())
-                    .collect();
-
-                // Extract the public and private inputs
-                //
-                // We want to store the public input in the database in order to use them to run
-                // the prover
-                let public_input = res.get_air_public_input()?;
-                let private_input = res.get_air_private_input();
-
-                // Commit the execution trace to the database
-                self.commit_cairo_execution_traces(
-                    committed_chain.tip().number,
-                    trace,
-                    memory,
-                    public_input,
-                    private_input,
-                )?;
+        for report in reports {
+            let path = match self.artifacts_dir.as_ref().map(|dir| report.save(dir, format)) {
+                Some(Ok(path)) => Some(path),
+                Some(Err(err)) => {
+                    warn!(instance = %self.config.name, number, %err, "Failed to save the report");
+                    None
+                }
+                None => None,
+            };
+            error!(
+                instance = %self.config.name,
+                number,
+                tx_index = report.tx_index,
+                tx_hash = %report.tx_hash,
+                report = ?path,
+                "Transaction diverged from its native execution"
+            );
+            metrics::counter!("kakarot_exex_divergences", self.labels.clone()).increment(1);
+        }
+    }
+
+    /// Selects the program of a block in the program registry, the program of the instance
+    /// proving the blocks out of its ranges, and records it with its hash. Returns the path of the
+    /// program.
+    ///
+    /// The hash of each program is computed once, and checked against the hash pinned in the
+    /// registry if any.
+    fn select_program(&mut self, number: u64) -> eyre::Result<PathBuf> {
+        let entry = self.programs.as_ref().and_then(|programs| programs.select(number)).cloned();
+        let path = match &entry {
+            Some(entry) => entry.program.clone(),
+            None => self.config.program.clone(),
+        };
+
+        let program_hash = match self.program_hashes.get(&path) {
+            Some(hash) => *hash,
+            None => {
+                let hash = match &entry {
+                    Some(entry) => entry.load()?.1,
+                    None => KakarotProgram::load(&path)?.program_hash()?,
+                };
+                self.program_hashes.insert(path.clone(), hash);
+                hash
+            }
+        };
+
+        let fork = entry.map(|entry| entry.fork);
+        debug!(instance = %self.config.name, number, ?fork, %program_hash, "Selected program");
+        self.store.insert_block_program(&BlockProgram {
+            block_number: number,
+            fork,
+            program: path.clone(),
+            program_hash,
+        })?;
+        Ok(path)
+    }
+
+    /// Returns the tuning of the next runner: the configured one, raised to the tuning learned from
+    /// the last blocks when auto-tuning is enabled.
+    fn tuning(&self) -> eyre::Result<RunnerTuning> {
+        let TuningConfig { tuning, auto_tune_window } = &self.config.tuning;
+        Ok(match auto_tune_window {
+            Some(window) => {
+                tuning.clone().max(RunnerTuning::learn(&self.db.run_profiles(*window)?))
+            }
+            None => tuning.clone(),
+        })
+    }
+
+    /// Reads a block and builds its [`ProgramInput`] from the input source of the instance.
+    fn block_input(&self, number: u64) -> eyre::Result<(SealedBlockWithSenders, ProgramInput)> {
+        let inputs = self.inputs.as_ref().ok_or_else(|| eyre::eyre!("No program input source"))?;
+        let input = inputs.program_input(number, self.config.chain_id, self.config.system_calls)?;
+        Ok((inputs.block(number)?, input))
+    }
+
+    /// Returns the struct layouts of a program, computed once per program.
+    fn program_layouts(
+        &mut self,
+        path: &Path,
+        program: &[u8],
+    ) -> eyre::Result<Arc<ProgramLayoutCache>> {
+        if let Some(layouts) = self.program_layouts.get(path) {
+            return Ok(layouts.clone());
+        }
+        let layouts =
+            Arc::new(ProgramLayoutCache::new(&Program::from_bytes(program, Some("main"))?));
+        self.program_layouts.insert(path.to_path_buf(), layouts.clone());
+        Ok(layouts)
+    }
+
+    /// Returns the hint processor of the runs feeding the given program input, enforcing the hint
+    /// policy of the instance if any.
+    fn hint_processor(
+        &self,
+        layouts: &Arc<ProgramLayoutCache>,
+        input: &Arc<ProgramInput>,
+    ) -> PolicyHintProcessor<'_> {
+        let processor =
+            KakarotHintProcessor::default().with_program_input(layouts.clone(), input.clone());
+        PolicyHintProcessor::new(processor, self.hint_policy.as_ref())
+    }
+
+    /// Logs the audit trail of the hints of a run.
+    fn log_hint_audit(&self, number: u64, audit: &HintAudit) {
+        debug!(
+            instance = %self.config.name,
+            number,
+            executions = audit.executions(),
+            hints = audit.executed.len(),
+            "Hints executed"
+        );
+        for (hash, usage) in &audit.executed {
+            debug!(
+                instance = %self.config.name,
+                number,
+                %hash,
+                executions = usage.executions,
+                code = %usage.code,
+                "Hint usage"
+            );
+        }
+        for (hash, code) in &audit.rejected {
+            warn!(instance = %self.config.name, number, %hash, %code, "Rejected hint");
+        }
+    }
+
+    /// Exposes the memory holes and the segment utilization of a run, warning about the utilization
+    /// patterns suggesting a tuning change.
+    ///
+    /// A pattern recurring over the blocks is warned about at most once per report interval, as the
+    /// recurring failures.
+    fn record_utilization(&mut self, number: u64, profile: &RunProfile) {
+        for (segment, utilization) in &profile.utilization {
+            let mut labels = self.labels.clone();
+            labels.push(Label::new("segment", segment.clone()));
+            metrics::gauge!("kakarot_exex_segment_utilization", labels)
+                .set(utilization.percent() as f64);
+        }
+        metrics::gauge!("kakarot_exex_memory_holes", self.labels.clone())
+            .set(profile.memory_holes() as f64);
+
+        for warning in profile.utilization_warnings() {
+            let fingerprint = failures::fingerprint(&warning.to_string());
+            match self.utilization_warnings.record(&fingerprint, Instant::now()) {
+                Report::First | Report::Repeated { .. } => {
+                    warn!(instance = %self.config.name, number, %warning, "Poor segment utilization")
+                }
+                Report::Suppressed => {
+                    debug!(instance = %self.config.name, number, %warning, "Poor segment utilization")
+                }
+            }
+        }
+    }
+
+    /// Records an execution interrupted by its limits: the memory dump is saved to the artifacts
+    /// directory, the diagnostics to the database, and the block is marked for retry with relaxed
+    /// limits. A block still exceeding its limits after [`MAX_LIMIT_RETRIES`] retries fails.
+    fn record_partial_run(
+        &self,
+        number: u64,
+        limits: &ExecutionLimits,
+        partial: PartialRun,
+    ) -> eyre::Result<()> {
+        let PartialRun { diagnostics, dump } = partial;
+        warn!(
+            instance = %self.config.name,
+            number,
+            limit = ?diagnostics.limit,
+            steps = diagnostics.steps,
+            pc = %diagnostics.pc,
+            frame = diagnostics.frames.first().map(String::as_str).unwrap_or_default(),
+            opcode = ?diagnostics.last_opcode,
+            "Block execution interrupted by its limits"
+        );
+
+        let dump_path = match &self.artifacts_dir {
+            Some(artifacts_dir) => {
+                let block_dir = artifacts_dir.join(number.to_string());
+                std::fs::create_dir_all(&block_dir)?;
+                let path = block_dir.join(PARTIAL_RUN_DUMP_FILE);
+                dump.save(&path, &self.db.codec())?;
+                Some(path)
+            }
+            None => None,
+        };
+
+        self.db.insert_partial_run(number, &diagnostics, dump_path.as_deref())?;
+        if dump_path.is_some() && self.db.artifact_store().is_some() {
+            self.db.tier_dumps()?;
+        }
+        metrics::counter!("kakarot_exex_blocks_interrupted", self.labels.clone()).increment(1);
+
+        // The block is retried with relaxed limits, until they reach their cap.
+        match limits.relaxed(&self.config.limits) {
+            Some(relaxed) => self.db.mark_for_retry(number, &relaxed)?,
+            None => {
+                self.db.clear_retry(number)?;
+                eyre::bail!(
+                    "Block {number} exceeded its limits {limits:?} after {MAX_LIMIT_RETRIES} retries"
+                );
             }
         }
 
         Ok(())
     }
 
-    /// Commits the execution traces to the database.
+    /// Commits the execution traces to the database, along with the Merkle commitments to the
+    /// trace and memory unless proving is disabled.
     fn commit_cairo_execution_traces(
         &mut self,
         number: u64,
@@ -137,13 +1035,159 @@ impl<Node: FullNodeComponents> KakarotRollup<Node> {
         air_public_input: PublicInput<'_>,
         air_private_input: AirPrivateInput,
     ) -> eyre::Result<()> {
+        if self.config.proving.is_enabled() {
+            commit_execution(&self.db, number, &trace, &memory)?;
+        }
         self.db.insert_execution_trace(number, trace, memory, air_public_input, air_private_input)
     }
 }
 
+/// The outcome of the processing of a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Processed {
+    /// The block was executed, or its interrupted execution recorded.
+    Done,
+    /// The execution was preempted and must be run again.
+    Preempted,
+}
+
+/// A chain dispatched to the instances.
+#[derive(Debug, Clone)]
+enum ChainEvent {
+    /// A chain committed to the canonical chain.
+    Committed(Arc<Chain>),
+    /// A chain reverted from the canonical chain, by a reorg or a revert.
+    Reverted(Arc<Chain>),
+}
+
+/// The report of an instance that it is done with a block, processed, skipped or failed and marked
+/// for retry, so that a failing block does not hold back the node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Completion {
+    /// The index of the instance.
+    instance: usize,
+    /// The number of the block.
+    number: u64,
+    /// The number of reverts scheduled by the instance when the job of the block started.
+    reverts: usize,
+}
+
+/// The tracker of the height finished by all the instances, reported to the node as the
+/// `FinishedHeight` of the ExEx.
+///
+/// The blocks of the committed chains are pending for each instance accepting them until it
+/// completes them. As the lanes of an instance process the blocks out of order, the finished
+/// height is the last committed block below the lowest pending block of all the instances.
+#[derive(Debug)]
+struct FinishedHeights {
+    /// The configurations of the instances, by index.
+    configs: Vec<InstanceConfig>,
+    /// The pending blocks of each instance.
+    pending: Vec<BTreeSet<u64>>,
+    /// The hashes of the committed blocks from the finished height onwards.
+    hashes: BTreeMap<u64, B256>,
+    /// The first reverted block of each revert, in order.
+    reverts: Vec<u64>,
+    /// The last reported finished height.
+    finished: Option<u64>,
+}
+
+impl FinishedHeights {
+    /// Creates a new [`FinishedHeights`] for the instances with the given configurations.
+    fn new(configs: Vec<InstanceConfig>) -> Self {
+        let pending = vec![BTreeSet::new(); configs.len()];
+        Self { configs, pending, hashes: BTreeMap::new(), reverts: Vec::new(), finished: None }
+    }
+
+    /// Records the blocks of a committed chain, pending for the instances accepting them.
+    fn commit(&mut self, blocks: impl IntoIterator<Item = BlockNumHash>) {
+        for block in blocks {
+            self.hashes.insert(block.number, block.hash);
+            for (config, pending) in self.configs.iter().zip(&mut self.pending) {
+                if config.accepts(block.number) {
+                    pending.insert(block.number);
+                }
+            }
+        }
+    }
+
+    /// Records a revert from the given block onwards, whose pending jobs are cancelled by the
+    /// instances.
+    fn revert(&mut self, from: u64) {
+        self.reverts.push(from);
+        self.hashes.retain(|number, _| *number < from);
+        for pending in &mut self.pending {
+            pending.retain(|number| *number < from);
+        }
+    }
+
+    /// Records the completion of a block by an instance.
+    ///
+    /// The completion of a job started before a revert of its block is stale and ignored, the
+    /// block being pending again once committed by the new chain.
+    fn complete(&mut self, completion: Completion) {
+        let reverted =
+            self.reverts[completion.reverts..].iter().any(|from| completion.number >= *from);
+        if !reverted {
+            self.pending[completion.instance].remove(&completion.number);
+        }
+    }
+
+    /// Returns the finished height if it advanced since the last call.
+    fn advance(&mut self) -> Option<BlockNumHash> {
+        let (&tip, _) = self.hashes.last_key_value()?;
+        let height = match self.pending.iter().filter_map(BTreeSet::first).min() {
+            Some(lowest) => lowest.checked_sub(1)?,
+            None => tip,
+        };
+        let (&number, &hash) = self.hashes.range(..=height).next_back()?;
+        if self.finished.is_some_and(|finished| finished >= number) {
+            return None;
+        }
+        self.finished = Some(number);
+        self.hashes = self.hashes.split_off(&number);
+        Some(BlockNumHash::new(number, hash))
+    }
+}
+
+/// Schedules a chain event, returning the first block to roll back, if any.
+///
+/// The pending jobs of the blocks of a reverted chain, and of the blocks after it, are cancelled.
+fn schedule_event(
+    config: &InstanceConfig,
+    scheduler: &mut Scheduler,
+    event: &ChainEvent,
+) -> Option<u64> {
+    match event {
+        ChainEvent::Committed(chain) => {
+            schedule_chain(config, scheduler, chain);
+            None
+        }
+        ChainEvent::Reverted(chain) => {
+            let from = chain.first().number;
+            scheduler.cancel_from(from);
+            Some(from)
+        }
+    }
+}
+
+/// Schedules the blocks of a committed chain processed by the instance, the tip in the tip lane
+/// and the earlier blocks in the backfill lane.
+fn schedule_chain(config: &InstanceConfig, scheduler: &mut Scheduler, chain: &Chain) {
+    let tip = chain.tip().number;
+    for number in chain.range().filter(|number| config.accepts(*number)) {
+        let lane = if number == tip { Lane::Tip } else { Lane::Backfill };
+        scheduler.push(number, lane);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::input::{
+        program_input::{BlockInput, HeaderInput},
+        system::SystemCallPolicy,
+    };
     use alloy_consensus::TxEip1559;
     use alloy_primitives::{address, hex, Bytes, Sealable, B256, U256};
     use reth_execution_types::{Chain, ExecutionOutcome};
@@ -153,7 +1197,163 @@ mod tests {
         SealedBlockWithSenders, SealedHeader, TransactionSigned,
     };
     use reth_revm::primitives::AccountInfo;
-    use std::{future::Future, pin::pin, str::FromStr};
+    use std::{collections::BTreeMap, future::Future, pin::pin, str::FromStr};
+
+    /// A source of the program inputs of the blocks, returning the empty block of a single header.
+    struct HeaderInputSource(Header);
+
+    impl BlockInputSource for HeaderInputSource {
+        fn block(&self, _block_number: u64) -> eyre::Result<SealedBlockWithSenders> {
+            let header = SealedHeader::new(self.0.clone(), B256::ZERO);
+            Ok(SealedBlockWithSenders {
+                block: SealedBlock { header, body: BlockBody::default() },
+                senders: Vec::new(),
+            })
+        }
+
+        fn program_input(
+            &self,
+            _block_number: u64,
+            chain_id: u64,
+            _system_calls: SystemCallPolicy,
+        ) -> eyre::Result<ProgramInput> {
+            Ok(ProgramInput {
+                block: BlockInput {
+                    block_header: HeaderInput::from(&self.0),
+                    transactions: Vec::new(),
+                },
+                state: BTreeMap::new(),
+                chain_id,
+                system_calls: Vec::new(),
+                block_hashes: BTreeMap::new(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_process_dry_run() -> eyre::Result<()> {
+        let config = InstanceConfig {
+            program: PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../cairo/programs/os.json"),
+            proving: ProvingMode::Disabled,
+            dry_run: true,
+            ..Default::default()
+        };
+        let header = Header {
+            number: 1,
+            parent_hash: B256::repeat_byte(0xaa),
+            gas_limit: 30_000_000,
+            base_fee_per_gas: Some(7),
+            ..Default::default()
+        };
+        let instance = |header: &Header| -> eyre::Result<Instance> {
+            let db = Database::new(Connection::open_in_memory()?)?;
+            Ok(Instance::new(config.clone(), db)
+                .with_input_source(Arc::new(HeaderInputSource(header.clone()))))
+        };
+
+        // The output of the dry run matches the block, which is then executed in proof mode.
+        let mut valid = instance(&header)?;
+        assert_eq!(valid.process(1, &mut || false)?, Processed::Done);
+        assert!(valid.db.has_execution_trace(1)?);
+        assert!(valid.db.block(U256::from(1))?.is_some());
+
+        // The input of block 1 does not execute block 2.
+        let mut invalid = instance(&header)?;
+        let err = invalid.process(2, &mut || false).unwrap_err();
+        assert!(err.to_string().contains("does not match block 2"), "{err}");
+        assert!(!invalid.db.has_execution_trace(2)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_instance_completes_processed_blocks() -> eyre::Result<()> {
+        let config = InstanceConfig {
+            program: PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../cairo/programs/os.json"),
+            proving: ProvingMode::Disabled,
+            ..Default::default()
+        };
+        let header = Header {
+            number: 1,
+            gas_limit: 30_000_000,
+            base_fee_per_gas: Some(7),
+            ..Default::default()
+        };
+        let db = Database::new(Connection::open_in_memory()?)?;
+        let instance = Instance::new(config, db.clone())
+            .with_input_source(Arc::new(HeaderInputSource(header.clone())));
+
+        let (queue, receiver) = mpsc::unbounded_channel();
+        let (completions, mut completed) = mpsc::unbounded_channel();
+        let worker = std::thread::spawn(move || instance.run(3, receiver, completions));
+
+        let sealed = header.seal_slow();
+        let (header, hash) = sealed.into_parts();
+        let block = SealedBlockWithSenders {
+            block: SealedBlock {
+                header: SealedHeader::new(header, hash),
+                body: Default::default(),
+            },
+            senders: Vec::new(),
+        };
+        let chain = Chain::from_block(block, ExecutionOutcome::default(), None);
+        queue.send(ChainEvent::Committed(Arc::new(chain)))?;
+
+        // The block is completed once its results are persisted.
+        let completion = completed.blocking_recv().unwrap();
+        assert_eq!(completion, Completion { instance: 3, number: 1, reverts: 0 });
+        assert!(db.has_execution_trace(1)?);
+
+        drop(queue);
+        worker.join().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn test_finished_height_waits_for_all_instances() {
+        let late = InstanceConfig { name: "late".into(), start_block: 3, ..Default::default() };
+        let mut finished = FinishedHeights::new(vec![InstanceConfig::default(), late]);
+        let block = |number| BlockNumHash::new(number, B256::with_last_byte(number as u8));
+        let complete = |finished: &mut FinishedHeights, instance, number| {
+            finished.complete(Completion { instance, number, reverts: 0 });
+            finished.advance()
+        };
+
+        // Nothing is finished while the blocks are pending.
+        finished.commit((1..=4).map(block));
+        assert_eq!(finished.advance(), None);
+
+        // The blocks are completed out of order, the height only covering the blocks below the
+        // lowest pending one, the late instance not processing the first blocks.
+        assert_eq!(complete(&mut finished, 0, 2), None);
+        assert_eq!(complete(&mut finished, 0, 1), Some(block(2)));
+        assert_eq!(complete(&mut finished, 0, 4), None);
+        assert_eq!(complete(&mut finished, 0, 3), None);
+        assert_eq!(complete(&mut finished, 1, 4), None);
+        assert_eq!(complete(&mut finished, 1, 3), Some(block(4)));
+        assert_eq!(finished.advance(), None);
+    }
+
+    #[test]
+    fn test_finished_height_ignores_stale_completions() {
+        let mut finished = FinishedHeights::new(vec![InstanceConfig::default()]);
+        let reorged = BlockNumHash::new(2, B256::repeat_byte(0xee));
+        finished.commit([
+            BlockNumHash::new(1, B256::with_last_byte(1)),
+            BlockNumHash::new(2, B256::with_last_byte(2)),
+        ]);
+
+        // Block 2 is reorged while being processed, its completion is stale.
+        finished.revert(2);
+        finished.commit([reorged]);
+        finished.complete(Completion { instance: 0, number: 1, reverts: 0 });
+        finished.complete(Completion { instance: 0, number: 2, reverts: 0 });
+        assert_eq!(finished.advance(), Some(BlockNumHash::new(1, B256::with_last_byte(1))));
+
+        // The block of the new chain is completed after the revert.
+        finished.complete(Completion { instance: 0, number: 2, reverts: 1 });
+        assert_eq!(finished.advance(), Some(reorged));
+    }
 
     /// The initialization logic of the ExEx is just an async function.
     ///
@@ -181,7 +1381,8 @@ mod tests {
         )?;
 
         // Create the Kakarot Rollup chain instance and start processing chain state notifications.
-        Ok(KakarotRollup { ctx, db }.start())
+        let instance = Instance::new(InstanceConfig::default(), db);
+        Ok(KakarotRollup::with_instances(ctx, vec![instance])?.start())
     }
 
     #[ignore = "block_header not implemented"]
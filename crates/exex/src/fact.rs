@@ -0,0 +1,134 @@
+//! Client of the L1 fact registry, on which the verified proofs register their facts.
+//!
+//! The fact of a proof binds the hash of the proven program to its output, following the SHARP
+//! convention: `keccak256(program_hash || keccak256(output))`, each output felt being encoded as a
+//! 32-byte big-endian word. Reconciling the local proofs with the registry surfaces the blocks
+//! which are proven locally but whose fact is not registered on-chain yet.
+
+use crate::{db::Database, store::KethStore};
+use alloy_primitives::{keccak256, Address, Bytes, B256, U256};
+use alloy_provider::Provider;
+use alloy_rpc_types_eth::TransactionRequest;
+use alloy_transport::{Transport, TransportResult};
+use cairo_vm::Felt252;
+use metrics::Label;
+use reth_tracing::tracing::warn;
+use serde::{Deserialize, Serialize};
+
+/// The selector of `isValid(bytes32)`.
+const IS_VALID_SELECTOR: [u8; 4] = [0x6a, 0x93, 0x85, 0x67];
+
+/// Returns the fact of a program run, from the hash of the program and its output felts.
+pub fn fact_hash(program_hash: B256, output: &[Felt252]) -> B256 {
+    let output = output.iter().flat_map(Felt252::to_bytes_be).collect::<Vec<_>>();
+    let mut preimage = [0; 64];
+    preimage[..32].copy_from_slice(program_hash.as_slice());
+    preimage[32..].copy_from_slice(keccak256(output).as_slice());
+    keccak256(preimage)
+}
+
+/// The registration status of the fact of a proven block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FactStatus {
+    /// The number of the proven block.
+    pub block_number: u64,
+    /// The fact of the proof of the block.
+    pub fact: B256,
+    /// Whether the fact is registered on-chain.
+    pub registered: bool,
+}
+
+/// A client of a fact registry contract, queried through any alloy [`Provider`].
+#[derive(Debug, Clone)]
+pub struct FactRegistryClient<P> {
+    /// The provider of the chain of the registry.
+    provider: P,
+    /// The address of the registry contract.
+    registry: Address,
+}
+
+impl<P> FactRegistryClient<P> {
+    /// Creates a new [`FactRegistryClient`] for the registry at the given address.
+    pub const fn new(provider: P, registry: Address) -> Self {
+        Self { provider, registry }
+    }
+
+    /// Returns the address of the registry contract.
+    pub const fn registry(&self) -> Address {
+        self.registry
+    }
+
+    /// Returns whether a fact is registered, calling `isValid(bytes32)` on the registry.
+    pub async fn is_registered<T>(&self, fact: B256) -> TransportResult<bool>
+    where
+        P: Provider<T>,
+        T: Transport + Clone,
+    {
+        let mut input = IS_VALID_SELECTOR.to_vec();
+        input.extend_from_slice(fact.as_slice());
+        let request =
+            TransactionRequest::default().to(self.registry).input(Bytes::from(input).into());
+
+        let result = self.provider.call(&request).await?;
+        Ok(result.len() >= 32 && !U256::from_be_slice(&result[..32]).is_zero())
+    }
+
+    /// Reconciles the proofs of the blocks in `[from_block, to_block]` with the registry,
+    /// recording the status of their facts in the database.
+    ///
+    /// The proofs and the program outputs are read from the store of the instance, in which the
+    /// output of each proven run is recorded. Proven blocks without recorded program output are
+    /// skipped with a warning, as their fact cannot be computed. The number of proven blocks whose
+    /// fact is not registered is reported by the `kakarot_unregistered_facts` gauge.
+    pub async fn reconcile<T>(
+        &self,
+        store: &dyn KethStore,
+        db: &Database,
+        from_block: u64,
+        to_block: u64,
+        labels: Vec<Label>,
+    ) -> eyre::Result<Vec<FactStatus>>
+    where
+        P: Provider<T>,
+        T: Transport + Clone,
+    {
+        let mut statuses = Vec::new();
+        for number in store.proven_blocks(from_block, to_block)? {
+            let Some((metadata, _)) = store.proof(number)? else { continue };
+            let Some(output) = store.program_output(number)? else {
+                warn!(number, "Skipping a proven block without recorded output");
+                continue;
+            };
+
+            let fact = fact_hash(metadata.program_hash, &output.to_felts());
+            let registered = self.is_registered(fact).await?;
+            let status = FactStatus { block_number: number, fact, registered };
+            db.insert_fact_status(&status)?;
+            statuses.push(status);
+        }
+
+        let unregistered = db.unregistered_facts()?.len();
+        metrics::gauge!("kakarot_unregistered_facts", labels).set(unregistered as f64);
+
+        Ok(statuses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fact_hash() {
+        let program_hash = B256::with_last_byte(1);
+        let output = [Felt252::from(2), Felt252::from(3)];
+
+        let mut words = [0; 64];
+        words[31] = 2;
+        words[63] = 3;
+        let mut preimage = program_hash.to_vec();
+        preimage.extend_from_slice(keccak256(words).as_slice());
+
+        assert_eq!(fact_hash(program_hash, &output), keccak256(preimage));
+    }
+}
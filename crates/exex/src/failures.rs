@@ -0,0 +1,225 @@
+//! Aggregation of the recurring failures of the blocks.
+//!
+//! A block failing repeatedly, e.g. on a missing hint, fails with the same VM error at each
+//! attempt, and so do the following blocks hitting the same bug. The failures are deduplicated by
+//! their [`fingerprint`], i.e. the hash of their error with its numbers masked, so that the
+//! failures differing only by a block number, an address or a program counter are aggregated.
+//!
+//! The [`FailureAggregator`] of an instance rate-limits their reports: the first failure of a
+//! fingerprint is reported in full, the next ones at most once per interval with the number of
+//! failures suppressed in between. The counts of every fingerprint are kept in the database,
+//! so that the top recurring failures are served with the proof statuses of the gRPC service.
+
+use alloy_primitives::hex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// The default interval between two reports of the failures of a fingerprint.
+pub const DEFAULT_REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The maximum length, in characters, of the summary of a failure.
+pub const SUMMARY_LEN: usize = 256;
+
+/// The maximum number of fingerprints whose reports are tracked by a [`FailureAggregator`].
+pub const MAX_TRACKED_FINGERPRINTS: usize = 1024;
+
+/// Returns the fingerprint of an error, the hex encoded hash of its message with the tokens
+/// starting with a digit masked, e.g. `12`, `0x2a` or `3:17`.
+pub fn fingerprint(message: &str) -> String {
+    let mut masked = String::with_capacity(message.len());
+    let mut chars = message.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() {
+            while chars.next_if(|c| c.is_ascii_alphanumeric()).is_some() {}
+            masked.push('#');
+        } else {
+            masked.push(c);
+        }
+    }
+    hex::encode(&Sha256::digest(masked.as_bytes())[..8])
+}
+
+/// Returns the summary of an error, its first line truncated to [`SUMMARY_LEN`] characters.
+pub fn summary(message: &str) -> String {
+    let line = message.lines().next().unwrap_or_default();
+    match line.char_indices().nth(SUMMARY_LEN) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+/// The failures of a fingerprint, as stored in the database.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecurringFailure {
+    /// The fingerprint of the failures.
+    pub fingerprint: String,
+    /// The summary of the first failure.
+    pub summary: String,
+    /// The number of failures.
+    pub count: u64,
+    /// The block of the first failure.
+    pub first_block: u64,
+    /// The block of the last failure.
+    pub last_block: u64,
+    /// The UNIX timestamp, in seconds, of the first failure.
+    pub first_seen: u64,
+    /// The UNIX timestamp, in seconds, of the last failure.
+    pub last_seen: u64,
+}
+
+/// How a failure is reported by the [`FailureAggregator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Report {
+    /// The first failure of its fingerprint, reported in full.
+    First,
+    /// A failure reported once the interval elapsed, `suppressed` failures not being reported
+    /// since the previous report.
+    Repeated {
+        /// The number of failures suppressed since the previous report.
+        suppressed: u64,
+    },
+    /// A failure suppressed, its fingerprint being reported less than an interval ago.
+    Suppressed,
+}
+
+/// The reports of a fingerprint.
+#[derive(Debug, Clone, Copy)]
+struct Reports {
+    /// The instant of the last report.
+    last: Instant,
+    /// The number of failures suppressed since the last report.
+    suppressed: u64,
+}
+
+/// The rate limiter of the reports of the failures, by fingerprint.
+///
+/// At most [`MAX_TRACKED_FINGERPRINTS`] fingerprints are tracked: once full, the fingerprint
+/// reported the longest ago is evicted, its next failure being reported in full again. The counts
+/// of the failures are kept in the database regardless.
+#[derive(Debug, Clone)]
+pub struct FailureAggregator {
+    /// The minimum interval between two reports of a fingerprint.
+    interval: Duration,
+    /// The reports, by fingerprint.
+    reports: HashMap<String, Reports>,
+}
+
+impl Default for FailureAggregator {
+    fn default() -> Self {
+        Self::new(DEFAULT_REPORT_INTERVAL)
+    }
+}
+
+impl FailureAggregator {
+    /// Creates an aggregator reporting each fingerprint at most once per `interval`.
+    pub fn new(interval: Duration) -> Self {
+        Self { interval, reports: HashMap::new() }
+    }
+
+    /// Records a failure of the given fingerprint at `now`, returning how it is reported.
+    pub fn record(&mut self, fingerprint: &str, now: Instant) -> Report {
+        let Some(reports) = self.reports.get_mut(fingerprint) else {
+            if self.reports.len() >= MAX_TRACKED_FINGERPRINTS {
+                let oldest = self
+                    .reports
+                    .iter()
+                    .min_by_key(|(_, reports)| reports.last)
+                    .map(|(fingerprint, _)| fingerprint.clone());
+                if let Some(oldest) = oldest {
+                    self.reports.remove(&oldest);
+                }
+            }
+            self.reports.insert(fingerprint.to_string(), Reports { last: now, suppressed: 0 });
+            return Report::First;
+        };
+        if now.saturating_duration_since(reports.last) < self.interval {
+            reports.suppressed += 1;
+            return Report::Suppressed;
+        }
+        let suppressed = std::mem::take(&mut reports.suppressed);
+        reports.last = now;
+        Report::Repeated { suppressed }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_fingerprint() {
+        let first = fingerprint("Hint not whitelisted at pc 0:1234 in block 42");
+        assert_eq!(first.len(), 16);
+        assert_eq!(first, fingerprint("Hint not whitelisted at pc 0:99 in block 7"));
+        assert_ne!(first, fingerprint("Unknown hint at pc 0:1234 in block 42"));
+        assert_eq!(fingerprint("at 0xdead"), fingerprint("at 0x2a"));
+    }
+
+    #[test]
+    fn test_summary() {
+        assert_eq!(summary("VM error\nbacktrace"), "VM error");
+        let long = "x".repeat(SUMMARY_LEN + 1);
+        assert_eq!(summary(&long), format!("{}...", "x".repeat(SUMMARY_LEN)));
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let mut aggregator = FailureAggregator::new(Duration::from_secs(60));
+        let now = Instant::now();
+        assert_eq!(aggregator.record("a", now), Report::First);
+        assert_eq!(aggregator.record("a", now + Duration::from_secs(1)), Report::Suppressed);
+        assert_eq!(aggregator.record("a", now + Duration::from_secs(2)), Report::Suppressed);
+        assert_eq!(aggregator.record("b", now), Report::First);
+        assert_eq!(
+            aggregator.record("a", now + Duration::from_secs(60)),
+            Report::Repeated { suppressed: 2 }
+        );
+        assert_eq!(aggregator.record("a", now + Duration::from_secs(61)), Report::Suppressed);
+    }
+
+    #[test]
+    fn test_rate_limit_eviction() {
+        let mut aggregator = FailureAggregator::new(Duration::from_secs(60));
+        let now = Instant::now();
+        for i in 0..MAX_TRACKED_FINGERPRINTS {
+            aggregator.record(&i.to_string(), now + Duration::from_millis(i as u64));
+        }
+        assert_eq!(aggregator.reports.len(), MAX_TRACKED_FINGERPRINTS);
+
+        // The fingerprint reported the longest ago is evicted, and reported in full again.
+        assert_eq!(aggregator.record("new", now + Duration::from_secs(1)), Report::First);
+        assert_eq!(aggregator.reports.len(), MAX_TRACKED_FINGERPRINTS);
+        assert_eq!(aggregator.record("1", now + Duration::from_secs(2)), Report::Suppressed);
+        assert_eq!(aggregator.record("0", now + Duration::from_secs(2)), Report::First);
+    }
+
+    #[test]
+    fn test_recurring_failures() {
+        let db = Database::new(Connection::open_in_memory().unwrap()).unwrap();
+        db.record_failure("a", "VM error", 1, 100).unwrap();
+        db.record_failure("b", "Other error", 2, 101).unwrap();
+        let failure = db.record_failure("a", "VM error again", 3, 102).unwrap();
+        assert_eq!(
+            failure,
+            RecurringFailure {
+                fingerprint: "a".to_string(),
+                summary: "VM error".to_string(),
+                count: 2,
+                first_block: 1,
+                last_block: 3,
+                first_seen: 100,
+                last_seen: 102,
+            }
+        );
+
+        let failures = db.recurring_failures(10).unwrap();
+        assert_eq!(failures.iter().map(|f| f.count).collect::<Vec<_>>(), vec![2, 1]);
+        assert_eq!(db.recurring_failures(1).unwrap(), vec![failure]);
+    }
+}
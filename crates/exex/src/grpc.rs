@@ -0,0 +1,698 @@
+use crate::{
+    attribution::{self, AttributionError},
+    chain,
+    db::Database,
+    deferred::JobState,
+    error::KethError,
+    events::{IndexedLog, LogFilter},
+    failures, retry,
+    store::SharedStore,
+    tracer::{self, TraceOptions, Tracer},
+};
+use alloy_primitives::{Address, B256, U256};
+use futures::{stream, Stream, StreamExt};
+use proto::{
+    execution_service_server::{ExecutionService, ExecutionServiceServer},
+    ChainLink, ExecutionResult, GetChainLinkRequest, GetExecutionResultRequest,
+    GetProofStatusRequest, GetProvenLogsRequest, GetProvenLogsResponse, GetTracesRequest,
+    GetTracesResponse, GetTransactionResourcesRequest, GetTransactionResourcesResponse, ProofState,
+    ProofStatus, ProvenLog, RecurringFailure, RetryStatus, StreamExecutionResultsRequest,
+    TraceEntry, TraceTransactionRequest, TraceTransactionResponse, Transaction,
+    TransactionResources,
+};
+use reth_primitives::SealedBlockWithSenders;
+use std::{net::SocketAddr, pin::Pin, sync::Arc};
+use tonic::{Request, Response, Status};
+
+/// The protobuf messages and gRPC service generated from `proto/keth/v1/execution.proto`.
+#[allow(unreachable_pub, missing_debug_implementations, clippy::all)]
+pub mod proto {
+    tonic::include_proto!("keth.v1");
+}
+
+/// The number of trace entries returned per page when the request does not specify it.
+pub const DEFAULT_TRACE_PAGE_SIZE: u32 = 10_000;
+
+/// The maximum number of trace entries returned per page.
+pub const MAX_TRACE_PAGE_SIZE: u32 = 100_000;
+
+/// The number of recurring failures returned with the status of a proof.
+pub const STATUS_FAILURES_LIMIT: usize = 10;
+
+/// The stream of execution results returned by
+/// [`ExecutionService::stream_execution_results`].
+type ExecutionResultStream = Pin<Box<dyn Stream<Item = Result<ExecutionResult, Status>> + Send>>;
+
+/// The gRPC [`ExecutionService`] serving the execution results stored in the [`Database`], and
+/// the proven blocks stored in the [`KethStore`](crate::store::KethStore) of the instance.
+#[derive(Debug, Clone)]
+pub struct ExecutionGrpcService {
+    /// The SQLite database.
+    db: Database,
+    /// The store of the proofs and of the metadata of the proven blocks.
+    store: SharedStore,
+}
+
+impl ExecutionGrpcService {
+    /// Creates a new [`ExecutionGrpcService`], the proven blocks being stored in the database.
+    pub fn new(db: Database) -> Self {
+        Self { store: Arc::new(db.clone()), db }
+    }
+
+    /// Sets the store of the proven blocks.
+    pub fn with_store(mut self, store: SharedStore) -> Self {
+        self.store = store;
+        self
+    }
+}
+
+#[tonic::async_trait]
+impl ExecutionService for ExecutionGrpcService {
+    type StreamExecutionResultsStream = ExecutionResultStream;
+
+    async fn get_execution_result(
+        &self,
+        request: Request<GetExecutionResultRequest>,
+    ) -> Result<Response<ExecutionResult>, Status> {
+        let (db, number) = (self.db.clone(), request.into_inner().block_number);
+        let block = blocking(move || block(&db, number)).await?;
+        Ok(Response::new((&block).into()))
+    }
+
+    async fn stream_execution_results(
+        &self,
+        request: Request<StreamExecutionResultsRequest>,
+    ) -> Result<Response<Self::StreamExecutionResultsStream>, Status> {
+        let request = request.into_inner();
+
+        // Default to the latest executed block.
+        let to_block = match request.to_block {
+            Some(to_block) => to_block,
+            None => {
+                let db = self.db.clone();
+                match blocking(move || db.latest_block().map_err(internal)).await? {
+                    Some(block) => block.number,
+                    None => return Ok(Response::new(Box::pin(stream::empty()))),
+                }
+            }
+        };
+
+        // Blocks are loaded lazily, as the stream is consumed.
+        let db = self.db.clone();
+        let results = stream::iter(request.from_block..=to_block).then(move |number| {
+            let db = db.clone();
+            blocking(move || block(&db, number).map(|block| (&block).into()))
+        });
+
+        Ok(Response::new(Box::pin(results)))
+    }
+
+    async fn get_traces(
+        &self,
+        request: Request<GetTracesRequest>,
+    ) -> Result<Response<GetTracesResponse>, Status> {
+        let request = request.into_inner();
+
+        let (db, block_number) = (self.db.clone(), request.block_number);
+        let trace = blocking(move || {
+            if !db.has_execution_trace(block_number).map_err(internal)? {
+                return Err(Status::not_found(format!("no trace found for block {block_number}")));
+            }
+            Ok(db.execution_trace(block_number).map_err(internal)?.unwrap_or_default().0)
+        })
+        .await?;
+
+        let offset = parse_page_token(&request.page_token)?;
+        let (page, next_offset) = paginate(&trace, offset, request.page_size);
+
+        Ok(Response::new(GetTracesResponse {
+            entries: page
+                .iter()
+                .map(|entry| TraceEntry {
+                    pc: entry.pc as u64,
+                    ap: entry.ap as u64,
+                    fp: entry.fp as u64,
+                })
+                .collect(),
+            next_page_token: next_offset.map(|offset| offset.to_string()).unwrap_or_default(),
+            total_entries: trace.len() as u64,
+        }))
+    }
+
+    async fn get_proof_status(
+        &self,
+        request: Request<GetProofStatusRequest>,
+    ) -> Result<Response<ProofStatus>, Status> {
+        let (db, block_number) = (self.db.clone(), request.into_inner().block_number);
+
+        let status = blocking(move || {
+            // Deferred proving jobs are proving once exported, and proven once their proof is
+            // imported. The trace of a block is stored once its execution in the Cairo VM is
+            // complete.
+            let state = match db.proving_job_state(block_number).map_err(internal)? {
+                Some(JobState::Proven) => ProofState::Proven,
+                Some(JobState::Exported) => ProofState::Proving,
+                Some(JobState::Failed) => ProofState::Failed,
+                _ if db.has_execution_trace(block_number).map_err(internal)? => ProofState::Traced,
+                _ => ProofState::Pending,
+            };
+
+            let retry = db.proving_retry(block_number).map_err(internal)?.map(Into::into);
+            let recurring_failures = db
+                .recurring_failures(STATUS_FAILURES_LIMIT)
+                .map_err(internal)?
+                .into_iter()
+                .map(Into::into)
+                .collect();
+
+            Ok(ProofStatus { block_number, state: state.into(), retry, recurring_failures })
+        })
+        .await?;
+
+        Ok(Response::new(status))
+    }
+
+    async fn get_transaction_resources(
+        &self,
+        request: Request<GetTransactionResourcesRequest>,
+    ) -> Result<Response<GetTransactionResourcesResponse>, Status> {
+        let (db, block_number) = (self.db.clone(), request.into_inner().block_number);
+        let resources =
+            blocking(move || db.transaction_resources(block_number).map_err(internal)).await?;
+
+        Ok(Response::new(GetTransactionResourcesResponse {
+            block_number,
+            transactions: resources.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn get_proven_logs(
+        &self,
+        request: Request<GetProvenLogsRequest>,
+    ) -> Result<Response<GetProvenLogsResponse>, Status> {
+        let request = request.into_inner();
+        let filter = LogFilter {
+            from_block: request.from_block,
+            to_block: request.to_block,
+            address: (!request.address.is_empty())
+                .then(|| Address::try_from(request.address.as_slice()))
+                .transpose()
+                .map_err(|_| Status::invalid_argument("Invalid address"))?,
+            topic0: (!request.topic0.is_empty())
+                .then(|| B256::try_from(request.topic0.as_slice()))
+                .transpose()
+                .map_err(|_| Status::invalid_argument("Invalid topic"))?,
+        };
+        let db = self.db.clone();
+        let (logs, unindexed) = blocking(move || {
+            let unindexed =
+                db.unindexed_proven_blocks(filter.from_block, filter.to_block).map_err(internal)?;
+            Ok((db.proven_logs(&filter).map_err(internal)?, unindexed))
+        })
+        .await?;
+
+        // An empty answer must mean that no log matches, not that the logs were never decoded.
+        if !unindexed.is_empty() {
+            return Err(Status::failed_precondition(format!(
+                "The logs of the proven blocks {unindexed:?} are not indexed, their program does \
+                 not execute the transactions"
+            )));
+        }
+        let logs = logs.into_iter().map(Into::into);
+
+        Ok(Response::new(GetProvenLogsResponse { logs: logs.collect() }))
+    }
+
+    async fn get_chain_link(
+        &self,
+        request: Request<GetChainLinkRequest>,
+    ) -> Result<Response<ChainLink>, Status> {
+        let (store, block_number) = (self.store.clone(), request.into_inner().block_number);
+        let link = blocking(move || {
+            match block_number {
+                Some(number) => store.chain_link(number),
+                None => store.chain_head(),
+            }
+            .map_err(internal)
+        })
+        .await?;
+        let link = link.ok_or_else(|| Status::not_found("no chain link found"))?;
+
+        Ok(Response::new(link.into()))
+    }
+
+    async fn trace_transaction(
+        &self,
+        request: Request<TraceTransactionRequest>,
+    ) -> Result<Response<TraceTransactionResponse>, Status> {
+        let request = request.into_inner();
+        let tracer: Tracer =
+            request.tracer.parse().map_err(|err| Status::invalid_argument(format!("{err}")))?;
+        let options = TraceOptions::from_config(tracer, &request.tracer_config)
+            .map_err(|err| Status::invalid_argument(format!("Invalid tracer config: {err}")))?;
+
+        // The transaction is traced with the program which executed its block.
+        let (block_number, tx_index) = (request.block_number, request.tx_index);
+        let store = self.store.clone();
+        let program = blocking(move || store.block_program(block_number).map_err(internal))
+            .await?
+            .ok_or_else(|| Status::not_found(format!("no program found for block {block_number}")))?
+            .program;
+
+        // Decoding the trace of a block is CPU bound, it runs off the runtime.
+        let db = self.db.clone();
+        let trace = tokio::task::spawn_blocking(move || {
+            tracer::trace_transaction(&db, &program, block_number, tx_index, &options)
+        })
+        .await
+        .map_err(|err| Status::internal(err.to_string()))?
+        .map_err(|err| match err.downcast_ref::<AttributionError>() {
+            Some(AttributionError::MissingEntryPoint(_)) => {
+                Status::failed_precondition(err.to_string())
+            }
+            _ => internal(err),
+        })?;
+
+        Ok(Response::new(TraceTransactionResponse { result: trace.to_string() }))
+    }
+}
+
+impl From<chain::ChainLink> for ChainLink {
+    fn from(link: chain::ChainLink) -> Self {
+        Self {
+            block_number: link.block_number,
+            block_hash: link.block_hash.to_vec(),
+            post_state_root: link.post_state_root.to_vec(),
+            output_root: link.output_root.to_vec(),
+            anchor: link.anchor,
+            previous: link.previous.to_vec(),
+            hash: link.hash.to_vec(),
+        }
+    }
+}
+
+impl From<failures::RecurringFailure> for RecurringFailure {
+    fn from(failure: failures::RecurringFailure) -> Self {
+        Self {
+            fingerprint: failure.fingerprint,
+            summary: failure.summary,
+            count: failure.count,
+            first_block: failure.first_block,
+            last_block: failure.last_block,
+            first_seen: failure.first_seen,
+            last_seen: failure.last_seen,
+        }
+    }
+}
+
+impl From<retry::RetryState> for RetryStatus {
+    fn from(state: retry::RetryState) -> Self {
+        Self {
+            attempts: state.attempts,
+            failure_class: state.class.to_string(),
+            last_error: state.last_error,
+            worker: state.worker.as_str().to_string(),
+            next_attempt_at: state.next_attempt_at,
+        }
+    }
+}
+
+impl From<attribution::TransactionResources> for TransactionResources {
+    fn from(resources: attribution::TransactionResources) -> Self {
+        Self {
+            tx_index: resources.tx_index,
+            steps: resources.steps,
+            builtins: resources.builtins.into_iter().collect(),
+        }
+    }
+}
+
+impl From<IndexedLog> for ProvenLog {
+    fn from(log: IndexedLog) -> Self {
+        Self {
+            block_number: log.block_number,
+            tx_index: log.tx_index,
+            log_index: log.log_index,
+            address: log.log.address.to_vec(),
+            topics: log.log.topics().iter().map(|topic| topic.to_vec()).collect(),
+            data: log.log.data.data.to_vec(),
+        }
+    }
+}
+
+impl From<&SealedBlockWithSenders> for ExecutionResult {
+    fn from(block: &SealedBlockWithSenders) -> Self {
+        Self {
+            block_number: block.number,
+            block_hash: block.hash().to_vec(),
+            parent_hash: block.parent_hash.to_vec(),
+            state_root: block.state_root.to_vec(),
+            transactions_root: block.transactions_root.to_vec(),
+            receipts_root: block.receipts_root.to_vec(),
+            gas_used: block.gas_used,
+            timestamp: block.timestamp,
+            transactions: block
+                .body
+                .transactions
+                .iter()
+                .zip(&block.senders)
+                .map(|(transaction, sender)| Transaction {
+                    hash: transaction.hash().to_vec(),
+                    sender: sender.to_vec(),
+                    encoded: transaction.envelope_encoded().to_vec(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Serves the [`ExecutionService`] on the given address until the server is shut down.
+pub async fn serve(db: Database, store: SharedStore, addr: SocketAddr) -> eyre::Result<()> {
+    let service = ExecutionGrpcService::new(db).with_store(store);
+    tonic::transport::Server::builder()
+        .add_service(ExecutionServiceServer::new(service))
+        .serve(addr)
+        .await?;
+    Ok(())
+}
+
+/// Loads a block from the database, failing with [`Status::not_found`] if it is missing.
+fn block(db: &Database, number: u64) -> Result<SealedBlockWithSenders, Status> {
+    db.block(U256::from(number))
+        .map_err(internal)?
+        .ok_or_else(|| Status::not_found(format!("block {number} not found")))
+}
+
+/// Runs a blocking read of the database off the runtime, the SQLite connection of the
+/// [`Database`] and of the stores being guarded by a blocking mutex.
+async fn blocking<T, F>(f: F) -> Result<T, Status>
+where
+    F: FnOnce() -> Result<T, Status> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f).await.map_err(|err| Status::internal(err.to_string()))?
+}
+
+/// Parses a page token, the empty token being the first page.
+fn parse_page_token(token: &str) -> Result<usize, Status> {
+    if token.is_empty() {
+        return Ok(0);
+    }
+    token.parse().map_err(|_| Status::invalid_argument(format!("invalid page token: {token}")))
+}
+
+/// Returns the page of `items` starting at `offset`, and the offset of the next page if any.
+fn paginate<T>(items: &[T], offset: usize, page_size: u32) -> (&[T], Option<usize>) {
+    let page_size = match page_size {
+        0 => DEFAULT_TRACE_PAGE_SIZE,
+        page_size => page_size.min(MAX_TRACE_PAGE_SIZE),
+    } as usize;
+
+    let start = offset.min(items.len());
+    let end = start.saturating_add(page_size).min(items.len());
+    (&items[start..end], (end < items.len()).then_some(end))
+}
+
+/// Converts an internal error into a [`Status`], carrying its code when it is the error of a module
+/// of the taxonomy, see [`KethError::from_report`], and a [`Status::internal`] otherwise.
+fn internal(err: eyre::Report) -> Status {
+    match KethError::from_report(err) {
+        Ok(err) => err.into(),
+        Err(err) => Status::internal(err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        deferred::ProvingJob,
+        output::{ProgramOutput, ProofMetadata},
+        program::BlockProgram,
+        retry::RetryPolicy,
+        store::{KethStore, RedbStore},
+    };
+    use alloy_primitives::{Bytes, Log, LogData};
+    use reth_revm::db::BundleState;
+    use rusqlite::Connection;
+    use std::path::{Path, PathBuf};
+
+    fn database() -> Database {
+        Database::new(Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_paginate() {
+        let items = (0..25).collect::<Vec<_>>();
+
+        assert_eq!(paginate(&items, 0, 10), (&items[..10], Some(10)));
+        assert_eq!(paginate(&items, 20, 10), (&items[20..], None));
+        // Out of range offsets return an empty last page.
+        assert_eq!(paginate(&items, 30, 10), (&items[25..], None));
+        // A zero page size falls back to the default one.
+        assert_eq!(paginate(&items, 0, 0), (&items[..], None));
+    }
+
+    #[test]
+    fn test_parse_page_token() {
+        assert_eq!(parse_page_token("").unwrap(), 0);
+        assert_eq!(parse_page_token("42").unwrap(), 42);
+        assert_eq!(parse_page_token("abc").unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_get_execution_result() {
+        let db = database();
+        let block = SealedBlockWithSenders::default();
+        db.insert_block_with_bundle(&block, BundleState::default()).unwrap();
+
+        let service = ExecutionGrpcService::new(db);
+
+        let result = service
+            .get_execution_result(Request::new(GetExecutionResultRequest { block_number: 0 }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(result.block_hash, block.hash().to_vec());
+
+        let err = service
+            .get_execution_result(Request::new(GetExecutionResultRequest { block_number: 1 }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_get_proof_status_without_trace() {
+        let service = ExecutionGrpcService::new(database());
+
+        let status = service
+            .get_proof_status(Request::new(GetProofStatusRequest { block_number: 1 }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(status.state(), ProofState::Pending);
+    }
+
+    #[tokio::test]
+    async fn test_get_proof_status_deferred() {
+        let db = database();
+        db.enqueue_proving_job(&ProvingJob::new(1, PathBuf::from("os.json"))).unwrap();
+        db.set_proving_job_state(1, JobState::Exported).unwrap();
+        let service = ExecutionGrpcService::new(db.clone());
+
+        let status = service
+            .get_proof_status(Request::new(GetProofStatusRequest { block_number: 1 }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(status.state(), ProofState::Proving);
+
+        db.set_proving_job_state(1, JobState::Proven).unwrap();
+        let status = service
+            .get_proof_status(Request::new(GetProofStatusRequest { block_number: 1 }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(status.state(), ProofState::Proven);
+    }
+
+    #[tokio::test]
+    async fn test_get_proof_status_retries() {
+        let db = database();
+        db.enqueue_proving_job(&ProvingJob::new(1, PathBuf::from("os.json"))).unwrap();
+        let policy = RetryPolicy { max_attempts: 1, ..Default::default() };
+        retry::record_failure(&db, &policy, 1, "out of memory").unwrap();
+        let service = ExecutionGrpcService::new(db);
+
+        let status = service
+            .get_proof_status(Request::new(GetProofStatusRequest { block_number: 1 }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(status.state(), ProofState::Failed);
+        assert_eq!(
+            status.retry,
+            Some(RetryStatus {
+                attempts: 1,
+                failure_class: "out_of_memory".to_string(),
+                last_error: "out of memory".to_string(),
+                worker: "large".to_string(),
+                next_attempt_at: None,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_trace_transaction() {
+        let db = database();
+        let service = ExecutionGrpcService::new(db.clone());
+        let request = |tracer: &str| {
+            Request::new(TraceTransactionRequest {
+                block_number: 1,
+                tx_index: 0,
+                tracer: tracer.to_string(),
+                tracer_config: String::new(),
+            })
+        };
+
+        let err = service.trace_transaction(request("unknownTracer")).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        let err = service.trace_transaction(request("callTracer")).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+
+        // The OS does not execute the transactions, they cannot be traced.
+        db.insert_block_program(&BlockProgram {
+            block_number: 1,
+            fork: None,
+            program: Path::new(env!("CARGO_MANIFEST_DIR")).join("../../cairo/programs/os.json"),
+            program_hash: B256::ZERO,
+        })
+        .unwrap();
+        for tracer in ["", "callTracer"] {
+            let err = service.trace_transaction(request(tracer)).await.unwrap_err();
+            assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_chain_link() {
+        let store = RedbStore::in_memory().unwrap();
+        let service = ExecutionGrpcService::new(database()).with_store(Arc::new(store.clone()));
+
+        let err = service
+            .get_chain_link(Request::new(GetChainLinkRequest { block_number: None }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+
+        let output = ProgramOutput { block_number: 1, ..Default::default() };
+        let link = chain::ChainLink::anchor(&output);
+        store.insert_chain_link(&link).unwrap();
+
+        let head = service
+            .get_chain_link(Request::new(GetChainLinkRequest { block_number: None }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(head.block_number, 1);
+        assert_eq!(head.hash, link.hash.to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_get_proof_status_recurring_failures() {
+        let db = database();
+        let service = ExecutionGrpcService::new(db.clone());
+        for number in 1..=3 {
+            db.record_failure("a", "Unknown hint", number, 100).unwrap();
+        }
+        db.record_failure("b", "Out of memory", 4, 100).unwrap();
+
+        let status = service
+            .get_proof_status(Request::new(GetProofStatusRequest { block_number: 1 }))
+            .await
+            .unwrap()
+            .into_inner();
+        let counts: Vec<_> = status
+            .recurring_failures
+            .iter()
+            .map(|failure| (failure.fingerprint.as_str(), failure.count))
+            .collect();
+        assert_eq!(counts, vec![("a", 3), ("b", 1)]);
+        assert_eq!(status.recurring_failures[0].last_block, 3);
+    }
+
+    #[tokio::test]
+    async fn test_get_proven_logs() {
+        let db = database();
+        let log = |number, address| IndexedLog {
+            block_number: number,
+            tx_index: 0,
+            log_index: 0,
+            log: Log {
+                address,
+                data: LogData::new_unchecked(vec![B256::with_last_byte(1)], Bytes::new()),
+            },
+        };
+        db.insert_logs(1, &[log(1, Address::with_last_byte(1))]).unwrap();
+        db.insert_logs(2, &[log(2, Address::with_last_byte(2))]).unwrap();
+        let service = ExecutionGrpcService::new(db.clone());
+        let request = || GetProvenLogsRequest {
+            from_block: 0,
+            to_block: 10,
+            address: Address::with_last_byte(2).to_vec(),
+            topic0: B256::with_last_byte(1).to_vec(),
+        };
+
+        // Logs of blocks without proof are not returned.
+        let response = service.get_proven_logs(Request::new(request())).await.unwrap();
+        assert!(response.into_inner().logs.is_empty());
+
+        for number in [1, 2] {
+            let metadata = ProofMetadata { block_number: number, ..Default::default() };
+            db.insert_proof(&metadata, b"proof").unwrap();
+        }
+        let response = service.get_proven_logs(Request::new(request())).await.unwrap();
+        let logs = response.into_inner().logs;
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].block_number, 2);
+        assert_eq!(logs[0].topics, vec![B256::with_last_byte(1).to_vec()]);
+
+        // A proven block whose logs were never decoded fails the query instead of hiding them.
+        let metadata = ProofMetadata { block_number: 3, ..Default::default() };
+        db.insert_proof(&metadata, b"proof").unwrap();
+        let err = service.get_proven_logs(Request::new(request())).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+
+        db.insert_logs(3, &[]).unwrap();
+        let response = service.get_proven_logs(Request::new(request())).await.unwrap();
+        assert_eq!(response.into_inner().logs.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_transaction_resources() {
+        let db = database();
+        db.insert_transaction_resources(
+            1,
+            &[attribution::TransactionResources {
+                tx_index: 0,
+                steps: 1_000,
+                builtins: [("range_check".to_string(), 12)].into(),
+            }],
+        )
+        .unwrap();
+
+        let service = ExecutionGrpcService::new(db);
+
+        let response = service
+            .get_transaction_resources(Request::new(GetTransactionResourcesRequest {
+                block_number: 1,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.transactions.len(), 1);
+        assert_eq!(response.transactions[0].steps, 1_000);
+        assert_eq!(response.transactions[0].builtins["range_check"], 12);
+    }
+}
@@ -0,0 +1,352 @@
+//! Unified model of the exceptional halts of the Cairo and native executions.
+//!
+//! The Kakarot interpreter reports the outcome of a frame in the `reverted` member of its
+//! `model.EVM`, see [`ExecutionStatus::from_kakarot`], and the kind of an exceptional halt as its
+//! return data, an ASCII message prefixed with `Kakarot: `, while revm reports a [`HaltReason`].
+//! Both are mapped to a single [`EvmHalt`], so that the traces, the receipt status and the
+//! differential comparisons of the two executions agree on the halts, whatever their origin.
+
+use crate::serde::model::{EXCEPTIONAL_HALT, REVERTED};
+use reth_primitives::revm_primitives::{ExecutionResult, HaltReason, OutOfGasError};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The prefix of the error messages of the Kakarot interpreter.
+pub const KAKAROT_ERROR_PREFIX: &str = "Kakarot: ";
+
+/// An exceptional halt of the EVM, consuming all the gas of the frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvmHalt {
+    /// The frame ran out of gas.
+    OutOfGas,
+    /// The stack exceeded 1024 items.
+    StackOverflow,
+    /// An opcode popped more items than the stack holds.
+    StackUnderflow,
+    /// A jump to a destination which is not a `JUMPDEST`.
+    InvalidJump,
+    /// An undefined opcode, or the designated `INVALID` opcode.
+    InvalidOpcode,
+    /// A state modification in a static call.
+    StaticCallStateChange,
+    /// A read of the return data out of its bounds.
+    OutOfOffset,
+    /// The call depth exceeded 1024 frames.
+    CallTooDeep,
+    /// A contract creation at an address with code or nonce.
+    CreateCollision,
+    /// A deployed code or an init code exceeding its size limit.
+    CodeSizeLimit,
+    /// A deployed code starting with the `0xEF` byte.
+    InvalidCode,
+    /// The nonce of the sender overflowed.
+    NonceOverflow,
+    /// The balance of the sender does not cover the transferred value.
+    OutOfFunds,
+    /// A precompile failed.
+    PrecompileError,
+    /// Any other halt.
+    Other,
+}
+
+impl EvmHalt {
+    /// Returns the kind of an exceptional halt of the Kakarot interpreter, i.e. of an EVM whose
+    /// `reverted` is `Errors.EXCEPTIONAL_HALT`, from the error message of its return data.
+    ///
+    /// The message only tells the kind of the halt: [`EvmHalt::Other`] is returned for a message
+    /// which is not an error message of the interpreter.
+    pub fn from_kakarot_error(data: &[u8]) -> Self {
+        let Some(message) = std::str::from_utf8(data)
+            .ok()
+            .and_then(|message| message.strip_prefix(KAKAROT_ERROR_PREFIX))
+        else {
+            return Self::Other;
+        };
+        let message = message.to_lowercase();
+        let matches = |patterns: &[&str]| patterns.iter().any(|pattern| message.contains(pattern));
+
+        if matches(&["outofgas", "out of gas"]) {
+            Self::OutOfGas
+        } else if matches(&["stackoverflow"]) {
+            Self::StackOverflow
+        } else if matches(&["stackunderflow"]) {
+            Self::StackUnderflow
+        } else if matches(&["jump"]) {
+            Self::InvalidJump
+        } else if matches(&["statemodification", "static"]) {
+            Self::StaticCallStateChange
+        } else if matches(&["outofboundsread", "returndatacopy"]) {
+            Self::OutOfOffset
+        } else if matches(&["calldepth", "depth"]) {
+            Self::CallTooDeep
+        } else if matches(&["collision"]) {
+            Self::CreateCollision
+        } else if matches(&["codesize", "initcode"]) {
+            Self::CodeSizeLimit
+        } else if matches(&["invalidcode", "0xef"]) {
+            Self::InvalidCode
+        } else if matches(&["opcode"]) {
+            Self::InvalidOpcode
+        } else if matches(&["nonceoverflow", "nonce overflow"]) {
+            Self::NonceOverflow
+        } else if matches(&["balance", "funds"]) {
+            Self::OutOfFunds
+        } else if matches(&["precompile"]) {
+            Self::PrecompileError
+        } else {
+            Self::Other
+        }
+    }
+
+    /// Returns the revm [`HaltReason`] of the halt, `None` for [`EvmHalt::Other`] which has no
+    /// counterpart.
+    pub const fn halt_reason(&self) -> Option<HaltReason> {
+        Some(match self {
+            Self::OutOfGas => HaltReason::OutOfGas(OutOfGasError::Basic),
+            Self::StackOverflow => HaltReason::StackOverflow,
+            Self::StackUnderflow => HaltReason::StackUnderflow,
+            Self::InvalidJump => HaltReason::InvalidJump,
+            Self::InvalidOpcode => HaltReason::OpcodeNotFound,
+            Self::StaticCallStateChange => HaltReason::StateChangeDuringStaticCall,
+            Self::OutOfOffset => HaltReason::OutOfOffset,
+            Self::CallTooDeep => HaltReason::CallTooDeep,
+            Self::CreateCollision => HaltReason::CreateCollision,
+            Self::CodeSizeLimit => HaltReason::CreateContractSizeLimit,
+            Self::InvalidCode => HaltReason::CreateContractStartingWithEF,
+            Self::NonceOverflow => HaltReason::NonceOverflow,
+            Self::OutOfFunds => HaltReason::OutOfFunds,
+            Self::PrecompileError => HaltReason::PrecompileError,
+            Self::Other => return None,
+        })
+    }
+
+    /// Returns the name of the halt.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::OutOfGas => "out_of_gas",
+            Self::StackOverflow => "stack_overflow",
+            Self::StackUnderflow => "stack_underflow",
+            Self::InvalidJump => "invalid_jump",
+            Self::InvalidOpcode => "invalid_opcode",
+            Self::StaticCallStateChange => "static_call_state_change",
+            Self::OutOfOffset => "out_of_offset",
+            Self::CallTooDeep => "call_too_deep",
+            Self::CreateCollision => "create_collision",
+            Self::CodeSizeLimit => "code_size_limit",
+            Self::InvalidCode => "invalid_code",
+            Self::NonceOverflow => "nonce_overflow",
+            Self::OutOfFunds => "out_of_funds",
+            Self::PrecompileError => "precompile_error",
+            Self::Other => "other",
+        }
+    }
+
+    /// Returns the error message of the halt, as reported by geth.
+    pub const fn message(&self) -> &'static str {
+        match self {
+            Self::OutOfGas => "out of gas",
+            Self::StackOverflow => "stack limit reached 1024 (1023)",
+            Self::StackUnderflow => "stack underflow",
+            Self::InvalidJump => "invalid jump destination",
+            Self::InvalidOpcode => "invalid opcode",
+            Self::StaticCallStateChange => "write protection",
+            Self::OutOfOffset => "return data out of bounds",
+            Self::CallTooDeep => "max call depth exceeded",
+            Self::CreateCollision => "contract address collision",
+            Self::CodeSizeLimit => "max code size exceeded",
+            Self::InvalidCode => "invalid code: must not begin with 0xef",
+            Self::NonceOverflow => "nonce uint64 overflow",
+            Self::OutOfFunds => "insufficient balance for transfer",
+            Self::PrecompileError => "precompile failed",
+            Self::Other => "execution halted",
+        }
+    }
+}
+
+impl From<&HaltReason> for EvmHalt {
+    fn from(reason: &HaltReason) -> Self {
+        match reason {
+            HaltReason::OutOfGas(_) => Self::OutOfGas,
+            HaltReason::StackOverflow => Self::StackOverflow,
+            HaltReason::StackUnderflow => Self::StackUnderflow,
+            HaltReason::InvalidJump => Self::InvalidJump,
+            HaltReason::OpcodeNotFound | HaltReason::InvalidFEOpcode => Self::InvalidOpcode,
+            HaltReason::StateChangeDuringStaticCall | HaltReason::CallNotAllowedInsideStatic => {
+                Self::StaticCallStateChange
+            }
+            HaltReason::OutOfOffset => Self::OutOfOffset,
+            HaltReason::CallTooDeep => Self::CallTooDeep,
+            HaltReason::CreateCollision => Self::CreateCollision,
+            HaltReason::CreateContractSizeLimit | HaltReason::CreateInitCodeSizeLimit => {
+                Self::CodeSizeLimit
+            }
+            HaltReason::CreateContractStartingWithEF => Self::InvalidCode,
+            HaltReason::NonceOverflow => Self::NonceOverflow,
+            HaltReason::OutOfFunds => Self::OutOfFunds,
+            HaltReason::PrecompileError => Self::PrecompileError,
+            _ => Self::Other,
+        }
+    }
+}
+
+impl fmt::Display for EvmHalt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The outcome of the execution of a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", content = "halt", rename_all = "snake_case")]
+pub enum ExecutionStatus {
+    /// The transaction succeeded.
+    Success,
+    /// The transaction reverted, refunding the gas left.
+    Revert,
+    /// The transaction halted exceptionally.
+    Halt(EvmHalt),
+}
+
+impl ExecutionStatus {
+    /// Returns the status of a transaction executed by the Kakarot interpreter, from the
+    /// `reverted` code of its returned `model.EVM` and its return data, `None` for an unknown
+    /// code.
+    pub fn from_kakarot(reverted: u64, return_data: &[u8]) -> Option<Self> {
+        Some(match reverted {
+            0 => Self::Success,
+            REVERTED => Self::Revert,
+            EXCEPTIONAL_HALT => Self::Halt(EvmHalt::from_kakarot_error(return_data)),
+            _ => return None,
+        })
+    }
+
+    /// Returns the status of a transaction executed by revm.
+    pub fn from_result(result: &ExecutionResult) -> Self {
+        match result {
+            ExecutionResult::Success { .. } => Self::Success,
+            ExecutionResult::Revert { .. } => Self::Revert,
+            ExecutionResult::Halt { reason, .. } => Self::Halt(reason.into()),
+        }
+    }
+
+    /// Returns the status of the receipt of the transaction.
+    pub const fn receipt_status(&self) -> bool {
+        matches!(self, Self::Success)
+    }
+}
+
+/// A transaction whose outcome differs between the Cairo and the native executions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusDivergence {
+    /// The index of the transaction in the block.
+    pub tx_index: u32,
+    /// The status of the native execution.
+    pub expected: ExecutionStatus,
+    /// The status of the Cairo execution.
+    pub actual: ExecutionStatus,
+}
+
+/// Compares the statuses of the transactions of the Cairo execution against the native results,
+/// returning the diverging transactions.
+pub fn compare_statuses(
+    cairo: &[ExecutionStatus],
+    native: &[ExecutionResult],
+) -> Vec<StatusDivergence> {
+    cairo
+        .iter()
+        .zip(native)
+        .enumerate()
+        .filter_map(|(tx_index, (actual, result))| {
+            let expected = ExecutionStatus::from_result(result);
+            (*actual != expected).then_some(StatusDivergence {
+                tx_index: tx_index as u32,
+                expected,
+                actual: *actual,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::Bytes;
+
+    #[test]
+    fn test_from_kakarot_error() {
+        let halt = |message: &str| EvmHalt::from_kakarot_error(message.as_bytes());
+
+        assert_eq!(halt("Kakarot: outOfGas left=100, used=200"), EvmHalt::OutOfGas);
+        assert_eq!(halt("Kakarot: StackUnderflow"), EvmHalt::StackUnderflow);
+        assert_eq!(halt("Kakarot: invalidJumpDestError"), EvmHalt::InvalidJump);
+        assert_eq!(halt("Kakarot: StateModificationError"), EvmHalt::StaticCallStateChange);
+        assert_eq!(halt("Kakarot: UnknownOpcode"), EvmHalt::InvalidOpcode);
+        assert_eq!(halt("Kakarot: something else"), EvmHalt::Other);
+        // A message which is not an interpreter error does not tell the kind of the halt.
+        assert_eq!(halt("Not enough Ether provided."), EvmHalt::Other);
+        assert_eq!(EvmHalt::from_kakarot_error(&[0x08, 0xc3, 0x79, 0xa0, 0xff]), EvmHalt::Other);
+    }
+
+    #[test]
+    fn test_status_from_kakarot() {
+        // The status is read from the `reverted` code, whatever the return data.
+        let message = b"Kakarot: outOfGas left=0, used=50000";
+        assert_eq!(ExecutionStatus::from_kakarot(0, message), Some(ExecutionStatus::Success));
+        assert_eq!(ExecutionStatus::from_kakarot(REVERTED, message), Some(ExecutionStatus::Revert));
+        assert_eq!(
+            ExecutionStatus::from_kakarot(EXCEPTIONAL_HALT, message),
+            Some(ExecutionStatus::Halt(EvmHalt::OutOfGas))
+        );
+        assert_eq!(
+            ExecutionStatus::from_kakarot(EXCEPTIONAL_HALT, b""),
+            Some(ExecutionStatus::Halt(EvmHalt::Other))
+        );
+        assert_eq!(ExecutionStatus::from_kakarot(3, b""), None);
+    }
+
+    #[test]
+    fn test_from_halt_reason() {
+        assert_eq!(EvmHalt::from(&HaltReason::OutOfGas(OutOfGasError::Memory)), EvmHalt::OutOfGas);
+        assert_eq!(EvmHalt::from(&HaltReason::InvalidFEOpcode), EvmHalt::InvalidOpcode);
+        assert_eq!(
+            EvmHalt::from(&HaltReason::CallNotAllowedInsideStatic),
+            EvmHalt::StaticCallStateChange
+        );
+    }
+
+    #[test]
+    fn test_halt_reason() {
+        // The halts with a revm counterpart round trip.
+        for halt in [EvmHalt::OutOfGas, EvmHalt::InvalidOpcode, EvmHalt::CodeSizeLimit] {
+            assert_eq!(EvmHalt::from(&halt.halt_reason().unwrap()), halt);
+        }
+        assert_eq!(EvmHalt::Other.halt_reason(), None);
+    }
+
+    #[test]
+    fn test_compare_statuses() {
+        let native = [
+            ExecutionResult::Revert { gas_used: 30_000, output: Bytes::new() },
+            ExecutionResult::Halt { reason: HaltReason::StackOverflow, gas_used: 50_000 },
+        ];
+        let cairo = [
+            ExecutionStatus::from_kakarot(REVERTED, b"revert reason").unwrap(),
+            ExecutionStatus::from_kakarot(
+                EXCEPTIONAL_HALT,
+                b"Kakarot: outOfGas left=0, used=50000",
+            )
+            .unwrap(),
+        ];
+
+        assert!(!cairo[0].receipt_status());
+        assert_eq!(
+            compare_statuses(&cairo, &native),
+            vec![StatusDivergence {
+                tx_index: 1,
+                expected: ExecutionStatus::Halt(EvmHalt::StackOverflow),
+                actual: ExecutionStatus::Halt(EvmHalt::OutOfGas),
+            }]
+        );
+    }
+}
@@ -1,22 +1,82 @@
+use crate::{
+    input::{memory::InputWriter, program_input::ProgramInput, system::SystemCall},
+    precompute::{HintCache, RecoveryInput},
+    serde::{cache::ProgramLayoutCache, storage::STORAGE_PREIMAGES_SCOPE},
+    tuning::DICT_MANAGER_SCOPE,
+};
+use alloy_primitives::{Address, B256, U256};
 use cairo_vm::{
     hint_processor::{
         builtin_hint_processor::{
             builtin_hint_processor_definition::{BuiltinHintProcessor, HintFunc},
+            dict_manager::{DictManager, DictTracker},
+            hint_utils::{
+                get_integer_from_var_name, get_ptr_from_var_name, get_relocatable_from_var_name,
+                insert_value_from_var_name,
+            },
             memcpy_hint_utils::add_segment,
         },
-        hint_processor_definition::HintReference,
+        hint_processor_definition::{HintProcessorLogic, HintReference},
     },
     serde::deserialize_program::ApTracking,
-    types::exec_scope::ExecutionScopes,
-    vm::{errors::hint_errors::HintError, vm_core::VirtualMachine},
+    types::{
+        exec_scope::ExecutionScopes,
+        relocatable::{MaybeRelocatable, Relocatable},
+    },
+    vm::{
+        errors::{hint_errors::HintError, vm_errors::VirtualMachineError},
+        runners::cairo_runner::{ResourceTracker, RunResources},
+        vm_core::VirtualMachine,
+    },
     Felt252,
 };
-use std::{collections::HashMap, fmt, rc::Rc};
+use reth_provider::{AccountReader, StateProvider};
+use std::{any::Any, cell::RefCell, collections::HashMap, fmt, rc::Rc, sync::Arc};
+
+/// The code of the hint creating the dict manager, see `main` in `programs/os.cairo`.
+pub const DICT_MANAGER_HINT: &str = "dict_manager";
+
+/// The code of the hint writing the block of the program input to `ids.block`.
+pub const BLOCK_HINT: &str = "block";
+
+/// The code of the hint writing the pre-state of the program input to `ids.state`.
+pub const STATE_HINT: &str = "state";
+
+/// The code of the hint writing the chain id of the program input to `ids.chain_id`.
+pub const CHAIN_ID_HINT: &str = "chain_id";
+
+/// The code of the hint writing the system calls of the program input to `ids.system_calls`.
+pub const SYSTEM_CALLS_HINT: &str = "system_calls";
+
+/// The code of the hint recovering the signer of a message hash, see
+/// `ICairo1Helpers.recover_eth_address` in `src/interfaces/interfaces.cairo`.
+pub const RECOVER_ETH_ADDRESS_HINT: &str = "recover_eth_address";
+
+/// The code of the hint reading the nonce and the balance of `ids.address` from the host state to
+/// `ids.nonce` and `ids.balance`.
+pub const ACCOUNT_HINT: &str = "account";
+
+/// The code of the hint reading the storage slot `ids.key` of `ids.address` from the host state to
+/// `ids.value`.
+pub const STORAGE_HINT: &str = "storage";
+
+/// The code of the hint registering a copied dict, see `dict_copy` in `src/utils/dict.cairo`.
+pub const DICT_COPY_HINT: &str = "dict_copy";
+
+/// The code of the hint allocating a squashed dict, see `dict_squash` in `src/utils/dict.cairo`.
+pub const DICT_SQUASH_HINT: &str = "dict_squash";
+
+/// The code of the hint moving the tracker of a squashed dict to its end.
+pub const DICT_SQUASH_END_HINT: &str = "\
+# Update the DictTracker's current_ptr to point to the end of the squashed dict.
+__dict_manager.get_tracker(ids.squashed_dict_start).current_ptr = \\
+    ids.squashed_dict_end.address_";
 
 /// The type of a hint execution result.
 pub type HintExecutionResult = Result<(), HintError>;
 
-/// A wrapper around [`BuiltinHintProcessor`] to manage hint registration.
+/// The hint processor of the Kakarot programs, running the Kakarot hints registered in Rust on top
+/// of the hints of the Cairo common library.
 pub struct KakarotHintProcessor {
     /// The underlying [`BuiltinHintProcessor`].
     processor: BuiltinHintProcessor,
@@ -34,7 +94,12 @@ impl fmt::Debug for KakarotHintProcessor {
 
 impl Default for KakarotHintProcessor {
     fn default() -> Self {
-        Self::new_empty().with_hint(add_segment_hint())
+        Self::new_empty()
+            .with_hint(add_segment_hint())
+            .with_hint(dict_manager_hint())
+            .with_hint(dict_copy_hint())
+            .with_hint(dict_squash_hint())
+            .with_hint(dict_squash_end_hint())
     }
 }
 
@@ -52,11 +117,68 @@ impl KakarotHintProcessor {
         self
     }
 
-    /// Returns the underlying [`BuiltinHintProcessor`].
+    /// Registers the hints feeding a [`ProgramInput`] to the Kakarot program, whose struct layouts
+    /// are given.
     ///
-    /// This allows the processor to be used elsewhere.
-    pub fn build(self) -> BuiltinHintProcessor {
-        self.processor
+    /// The values written by the state hint and the signers of the transactions recovered by the
+    /// `recover_eth_address` hint are precomputed in parallel, see [`HintCache`].
+    pub fn with_program_input(
+        self,
+        layouts: Arc<ProgramLayoutCache>,
+        input: Arc<ProgramInput>,
+    ) -> Self {
+        let cache = Arc::new(HintCache::from_program_input(&input));
+        self.with_hint(block_hint(layouts.clone(), input.clone()))
+            .with_hint(state_hint(layouts, input.clone(), cache.clone()))
+            .with_hint(recover_eth_address_hint(cache))
+            .with_hint(chain_id_hint(input.chain_id))
+            .with_hint(system_calls_hint(input.system_calls.clone()))
+    }
+
+    /// Registers the hints reading the accounts and the storage from the given host state during
+    /// the run.
+    pub fn with_state_provider(self, state: Arc<dyn StateProvider>) -> Self {
+        self.with_hint(account_hint(state.clone())).with_hint(storage_hint(state))
+    }
+}
+
+impl HintProcessorLogic for KakarotHintProcessor {
+    fn compile_hint(
+        &self,
+        hint_code: &str,
+        ap_tracking_data: &ApTracking,
+        reference_ids: &HashMap<String, usize>,
+        references: &[HintReference],
+    ) -> Result<Box<dyn Any>, VirtualMachineError> {
+        self.processor.compile_hint(hint_code, ap_tracking_data, reference_ids, references)
+    }
+
+    fn execute_hint(
+        &mut self,
+        vm: &mut VirtualMachine,
+        exec_scopes: &mut ExecutionScopes,
+        hint_data: &Box<dyn Any>,
+        constants: &HashMap<String, Felt252>,
+    ) -> Result<(), HintError> {
+        self.processor.execute_hint(vm, exec_scopes, hint_data, constants)
+    }
+}
+
+impl ResourceTracker for KakarotHintProcessor {
+    fn consumed(&self) -> bool {
+        self.processor.consumed()
+    }
+
+    fn consume_step(&mut self) {
+        self.processor.consume_step()
+    }
+
+    fn get_n_steps(&self) -> Option<usize> {
+        self.processor.get_n_steps()
+    }
+
+    fn run_resources(&self) -> &RunResources {
+        self.processor.run_resources()
     }
 }
 
@@ -112,3 +234,568 @@ pub fn add_segment_hint() -> Hint {
         },
     )
 }
+
+/// Generates the hint creating the dict manager of the execution scopes, unless already created,
+/// e.g. pre-sized by the [`RunnerTuning`](crate::tuning::RunnerTuning).
+pub fn dict_manager_hint() -> Hint {
+    Hint::new(
+        String::from(DICT_MANAGER_HINT),
+        |_vm: &mut VirtualMachine,
+         exec_scopes: &mut ExecutionScopes,
+         _ids_data: &HashMap<String, HintReference>,
+         _ap_tracking: &ApTracking,
+         _constants: &HashMap<String, Felt252>|
+         -> HintExecutionResult {
+            if exec_scopes.get_dict_manager().is_err() {
+                exec_scopes
+                    .insert_value(DICT_MANAGER_SCOPE, Rc::new(RefCell::new(DictManager::new())));
+            }
+            Ok(())
+        },
+    )
+}
+
+/// Generates the hint writing the block of the program input to `ids.block`.
+pub fn block_hint(layouts: Arc<ProgramLayoutCache>, input: Arc<ProgramInput>) -> Hint {
+    Hint::new(
+        String::from(BLOCK_HINT),
+        move |vm: &mut VirtualMachine,
+              exec_scopes: &mut ExecutionScopes,
+              ids_data: &HashMap<String, HintReference>,
+              ap_tracking: &ApTracking,
+              _constants: &HashMap<String, Felt252>|
+              -> HintExecutionResult {
+            let dict_manager = exec_scopes.get_dict_manager()?;
+            let mut dict_manager = dict_manager.borrow_mut();
+            let block =
+                InputWriter::new(vm, &mut dict_manager, &layouts).write_block(&input.block)?;
+            insert_value_from_var_name("block", block, vm, ids_data, ap_tracking)
+        },
+    )
+}
+
+/// Generates the hint writing the pre-state of the program input to `ids.state`, with the values
+/// precomputed in the given [`HintCache`], and recording the preimages of its storage dict keys in
+/// the execution scopes.
+pub fn state_hint(
+    layouts: Arc<ProgramLayoutCache>,
+    input: Arc<ProgramInput>,
+    cache: Arc<HintCache>,
+) -> Hint {
+    Hint::new(
+        String::from(STATE_HINT),
+        move |vm: &mut VirtualMachine,
+              exec_scopes: &mut ExecutionScopes,
+              ids_data: &HashMap<String, HintReference>,
+              ap_tracking: &ApTracking,
+              _constants: &HashMap<String, Felt252>|
+              -> HintExecutionResult {
+            let dict_manager = exec_scopes.get_dict_manager()?;
+            let state = InputWriter::new(vm, &mut dict_manager.borrow_mut(), &layouts)
+                .write_state(&input.state, &cache)?;
+            // Record the slots hashed into the storage dict keys, to serialize the storage dicts.
+            exec_scopes.insert_value(STORAGE_PREIMAGES_SCOPE, cache.preimages());
+            insert_value_from_var_name("state", state, vm, ids_data, ap_tracking)
+        },
+    )
+}
+
+/// Generates the hint recovering the signer of `ids.msg_hash` from the `ids.r`, `ids.s` and
+/// `ids.y_parity` signature, writing it to `ids.address` and whether the signature is valid to
+/// `ids.success`.
+///
+/// The signers of the transactions are taken from the given [`HintCache`], the other recoveries,
+/// e.g. of the `ecrecover` precompile, being computed on the fly.
+pub fn recover_eth_address_hint(cache: Arc<HintCache>) -> Hint {
+    Hint::new(
+        String::from(RECOVER_ETH_ADDRESS_HINT),
+        move |vm: &mut VirtualMachine,
+              _exec_scopes: &mut ExecutionScopes,
+              ids_data: &HashMap<String, HintReference>,
+              ap_tracking: &ApTracking,
+              _constants: &HashMap<String, Felt252>|
+              -> HintExecutionResult {
+            let uint256 = |name: &str| {
+                read_uint256(vm, get_relocatable_from_var_name(name, vm, ids_data, ap_tracking)?)
+            };
+            let y_parity = get_integer_from_var_name("y_parity", vm, ids_data, ap_tracking)?;
+
+            let address = if y_parity <= Felt252::ONE {
+                cache.recover(&RecoveryInput {
+                    msg_hash: uint256("msg_hash")?.into(),
+                    r: uint256("r")?,
+                    s: uint256("s")?,
+                    y_parity: y_parity == Felt252::ONE,
+                })
+            } else {
+                None
+            };
+
+            let (success, address) = match address {
+                Some(address) => (Felt252::ONE, Felt252::from_bytes_be_slice(address.as_slice())),
+                None => (Felt252::ZERO, Felt252::ZERO),
+            };
+            insert_value_from_var_name("success", success, vm, ids_data, ap_tracking)?;
+            insert_value_from_var_name("address", address, vm, ids_data, ap_tracking)
+        },
+    )
+}
+
+/// Generates the hint reading the nonce and the balance of `ids.address` from the host state to
+/// `ids.nonce` and `ids.balance`, a missing account being empty.
+pub fn account_hint(state: Arc<dyn StateProvider>) -> Hint {
+    Hint::new(
+        String::from(ACCOUNT_HINT),
+        move |vm: &mut VirtualMachine,
+              _exec_scopes: &mut ExecutionScopes,
+              ids_data: &HashMap<String, HintReference>,
+              ap_tracking: &ApTracking,
+              _constants: &HashMap<String, Felt252>|
+              -> HintExecutionResult {
+            let address = address_from_var_name(vm, ids_data, ap_tracking)?;
+            let account = state
+                .basic_account(address)
+                .map_err(|err| HintError::CustomHint(err.to_string().into_boxed_str()))?
+                .unwrap_or_default();
+
+            insert_value_from_var_name(
+                "nonce",
+                Felt252::from(account.nonce),
+                vm,
+                ids_data,
+                ap_tracking,
+            )?;
+            let balance = get_relocatable_from_var_name("balance", vm, ids_data, ap_tracking)?;
+            write_uint256(vm, balance, account.balance)
+        },
+    )
+}
+
+/// Generates the hint reading the storage slot `ids.key` of `ids.address` from the host state to
+/// `ids.value`, a missing slot being zero.
+pub fn storage_hint(state: Arc<dyn StateProvider>) -> Hint {
+    Hint::new(
+        String::from(STORAGE_HINT),
+        move |vm: &mut VirtualMachine,
+              _exec_scopes: &mut ExecutionScopes,
+              ids_data: &HashMap<String, HintReference>,
+              ap_tracking: &ApTracking,
+              _constants: &HashMap<String, Felt252>|
+              -> HintExecutionResult {
+            let address = address_from_var_name(vm, ids_data, ap_tracking)?;
+            let key =
+                read_uint256(vm, get_relocatable_from_var_name("key", vm, ids_data, ap_tracking)?)?;
+            let value = state
+                .storage(address, B256::from(key))
+                .map_err(|err| HintError::CustomHint(err.to_string().into_boxed_str()))?
+                .unwrap_or_default();
+
+            let ptr = get_relocatable_from_var_name("value", vm, ids_data, ap_tracking)?;
+            write_uint256(vm, ptr, value)
+        },
+    )
+}
+
+/// Reads the address `ids.address`, keeping the low 20 bytes of the felt.
+fn address_from_var_name(
+    vm: &VirtualMachine,
+    ids_data: &HashMap<String, HintReference>,
+    ap_tracking: &ApTracking,
+) -> Result<Address, HintError> {
+    let address = get_integer_from_var_name("address", vm, ids_data, ap_tracking)?;
+    Ok(Address::from_slice(&address.to_bytes_be()[12..]))
+}
+
+/// Reads a `Uint256` from its low and high 128-bit limbs at `ptr`.
+fn read_uint256(vm: &VirtualMachine, ptr: Relocatable) -> Result<U256, HintError> {
+    let low = U256::from_be_bytes(vm.get_integer(ptr)?.to_bytes_be());
+    let high = U256::from_be_bytes(vm.get_integer((ptr + 1)?)?.to_bytes_be());
+    Ok((high << 128) | low)
+}
+
+/// Writes a `Uint256` as its low and high 128-bit limbs at `ptr`.
+fn write_uint256(vm: &mut VirtualMachine, ptr: Relocatable, value: U256) -> HintExecutionResult {
+    let bytes = value.to_be_bytes::<32>();
+    vm.insert_value(ptr, Felt252::from_bytes_be_slice(&bytes[16..]))?;
+    vm.insert_value((ptr + 1)?, Felt252::from_bytes_be_slice(&bytes[..16]))?;
+    Ok(())
+}
+
+/// Generates the hint writing the given chain id to `ids.chain_id`.
+pub fn chain_id_hint(chain_id: u64) -> Hint {
+    Hint::new(
+        String::from(CHAIN_ID_HINT),
+        move |vm: &mut VirtualMachine,
+              _exec_scopes: &mut ExecutionScopes,
+              ids_data: &HashMap<String, HintReference>,
+              ap_tracking: &ApTracking,
+              _constants: &HashMap<String, Felt252>|
+              -> HintExecutionResult {
+            insert_value_from_var_name(
+                "chain_id",
+                Felt252::from(chain_id),
+                vm,
+                ids_data,
+                ap_tracking,
+            )
+        },
+    )
+}
+
+/// Generates the hint writing the system calls the program must run before the transactions, as
+/// the addresses of their system contracts, to `ids.system_calls`, and their number to
+/// `ids.system_calls_len`.
+pub fn system_calls_hint(system_calls: Vec<SystemCall>) -> Hint {
+    Hint::new(
+        String::from(SYSTEM_CALLS_HINT),
+        move |vm: &mut VirtualMachine,
+              _exec_scopes: &mut ExecutionScopes,
+              ids_data: &HashMap<String, HintReference>,
+              ap_tracking: &ApTracking,
+              _constants: &HashMap<String, Felt252>|
+              -> HintExecutionResult {
+            let calls: Vec<MaybeRelocatable> = system_calls
+                .iter()
+                .map(|call| Felt252::from_bytes_be_slice(call.address().as_slice()).into())
+                .collect();
+            let base = vm.add_memory_segment();
+            vm.load_data(base, &calls)?;
+            insert_value_from_var_name("system_calls", base, vm, ids_data, ap_tracking)?;
+            insert_value_from_var_name(
+                "system_calls_len",
+                Felt252::from(calls.len()),
+                vm,
+                ids_data,
+                ap_tracking,
+            )
+        },
+    )
+}
+
+/// Generates the hint registering the copy of the dict at `ids.dict_start`, written from
+/// `ids.new_start` to `ids.new_end`, in the dict manager, with the same data.
+pub fn dict_copy_hint() -> Hint {
+    Hint::new(
+        String::from(DICT_COPY_HINT),
+        |vm: &mut VirtualMachine,
+         exec_scopes: &mut ExecutionScopes,
+         ids_data: &HashMap<String, HintReference>,
+         ap_tracking: &ApTracking,
+         _constants: &HashMap<String, Felt252>|
+         -> HintExecutionResult {
+            let dict_start = get_ptr_from_var_name("dict_start", vm, ids_data, ap_tracking)?;
+            let new_start = get_ptr_from_var_name("new_start", vm, ids_data, ap_tracking)?;
+            let new_end = get_ptr_from_var_name("new_end", vm, ids_data, ap_tracking)?;
+
+            let dict_manager = exec_scopes.get_dict_manager()?;
+            let mut dict_manager = dict_manager.borrow_mut();
+            let data = dict_manager
+                .trackers
+                .get(&dict_start.segment_index)
+                .ok_or(HintError::NoDictTracker(dict_start.segment_index))?
+                .data
+                .clone();
+            let mut tracker = DictTracker::new_empty(new_end);
+            tracker.data = data;
+            dict_manager.trackers.insert(new_start.segment_index, tracker);
+            Ok(())
+        },
+    )
+}
+
+/// Generates the hint allocating the segment of the squashed dict of the dict ending at
+/// `ids.dict_accesses_end`, tracked with the same data, and writing its base to `memory[ap]`.
+pub fn dict_squash_hint() -> Hint {
+    Hint::new(
+        String::from(DICT_SQUASH_HINT),
+        |vm: &mut VirtualMachine,
+         exec_scopes: &mut ExecutionScopes,
+         ids_data: &HashMap<String, HintReference>,
+         ap_tracking: &ApTracking,
+         _constants: &HashMap<String, Felt252>|
+         -> HintExecutionResult {
+            let dict_accesses_end =
+                get_ptr_from_var_name("dict_accesses_end", vm, ids_data, ap_tracking)?;
+
+            let dict_manager = exec_scopes.get_dict_manager()?;
+            let mut dict_manager = dict_manager.borrow_mut();
+            let data = dict_manager.get_tracker(dict_accesses_end)?.data.clone();
+            let base = vm.add_memory_segment();
+            if dict_manager.trackers.contains_key(&base.segment_index) {
+                return Err(HintError::CustomHint("The squashed dict is already tracked".into()));
+            }
+            let mut tracker = DictTracker::new_empty(base);
+            tracker.data = data;
+            dict_manager.trackers.insert(base.segment_index, tracker);
+
+            vm.insert_value(vm.get_ap(), base)?;
+            Ok(())
+        },
+    )
+}
+
+/// Generates the hint moving the tracker of the squashed dict at `ids.squashed_dict_start` to
+/// `ids.squashed_dict_end`.
+pub fn dict_squash_end_hint() -> Hint {
+    Hint::new(
+        String::from(DICT_SQUASH_END_HINT),
+        |vm: &mut VirtualMachine,
+         exec_scopes: &mut ExecutionScopes,
+         ids_data: &HashMap<String, HintReference>,
+         ap_tracking: &ApTracking,
+         _constants: &HashMap<String, Felt252>|
+         -> HintExecutionResult {
+            let start = get_ptr_from_var_name("squashed_dict_start", vm, ids_data, ap_tracking)?;
+            let end = get_ptr_from_var_name("squashed_dict_end", vm, ids_data, ap_tracking)?;
+
+            let dict_manager = exec_scopes.get_dict_manager()?;
+            dict_manager.borrow_mut().get_tracker_mut(start)?.current_ptr = end;
+            Ok(())
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        executor::{dry_run, execute, DryRun, ExecutionMode},
+        input::program_input::{AccountStateInput, BlockInput, HeaderInput},
+        limits::{ExecutionLimits, LimitedRun},
+        output::ProgramOutput,
+        serde::storage::StoragePreimages,
+        tuning::RunnerTuning,
+    };
+    use alloy_consensus::Header;
+    use alloy_primitives::{address, b256, bytes, keccak256, Address, B256, U256, U64};
+    use cairo_vm::{
+        types::{layout_name::LayoutName, program::Program},
+        vm::runners::cairo_runner::{CairoArg, CairoRunner},
+    };
+    use reth_provider::test_utils::{ExtendedAccount, MockEthProvider};
+    use std::collections::BTreeMap;
+
+    /// The compiled Kakarot OS.
+    const OS: &[u8] = include_bytes!("../../../cairo/programs/os.json");
+
+    fn header() -> Header {
+        Header {
+            number: 1,
+            parent_hash: B256::repeat_byte(0xaa),
+            state_root: B256::repeat_byte(0xbb),
+            transactions_root: B256::repeat_byte(0xcc),
+            receipts_root: B256::repeat_byte(0xdd),
+            gas_limit: 30_000_000,
+            gas_used: 21_000,
+            base_fee_per_gas: Some(7),
+            ..Default::default()
+        }
+    }
+
+    fn program_input() -> ProgramInput {
+        let account = AccountStateInput {
+            balance: U256::from(1_000_000),
+            // PUSH1 0x5b JUMPDEST STOP
+            code: bytes!("605b5b00"),
+            nonce: U64::from(1),
+            storage: BTreeMap::from([(U256::from(1), U256::from(42))]),
+        };
+        ProgramInput {
+            block: BlockInput {
+                block_header: HeaderInput::from(&header()),
+                transactions: Vec::new(),
+            },
+            state: BTreeMap::from([(
+                address!("0000000000000000000000000000000000001234"),
+                account,
+            )]),
+            chain_id: 1,
+            system_calls: Vec::new(),
+            block_hashes: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_run_os_with_program_input() {
+        let program = Program::from_bytes(OS, Some("main")).unwrap();
+        let layouts = Arc::new(ProgramLayoutCache::new(&program));
+        let mut hint_processor =
+            KakarotHintProcessor::default().with_program_input(layouts, Arc::new(program_input()));
+
+        let DryRun::Completed(report) = dry_run(
+            OS,
+            &mut hint_processor,
+            &ExecutionLimits::default(),
+            &RunnerTuning::default(),
+            &mut || false,
+        )
+        .unwrap() else {
+            panic!("The run of the OS did not complete");
+        };
+        assert!(report.steps > 0);
+        // The accounts dict and the storage, transient storage and jump destinations dicts of the
+        // account are tracked.
+        assert_eq!(report.profile.dicts, 4);
+
+        // The output is written from the header of the block.
+        let header = header();
+        assert_eq!(
+            report.output,
+            Some(ProgramOutput {
+                block_number: 1,
+                block_hash: header.hash_slow(),
+                parent_hash: header.parent_hash,
+                post_state_root: header.state_root,
+                transactions_root: header.transactions_root,
+                receipts_root: header.receipts_root,
+                gas_used: 21_000,
+            })
+        );
+    }
+
+    #[test]
+    fn test_run_os_records_storage_preimages() {
+        let program = Program::from_bytes(OS, Some("main")).unwrap();
+        let layouts = Arc::new(ProgramLayoutCache::new(&program));
+        let mut hint_processor =
+            KakarotHintProcessor::default().with_program_input(layouts, Arc::new(program_input()));
+
+        let LimitedRun::Completed(runner) = execute(
+            OS,
+            ExecutionMode::DryRun,
+            &mut hint_processor,
+            &ExecutionLimits::default(),
+            &RunnerTuning::default(),
+            &mut || false,
+        )
+        .unwrap() else {
+            panic!("The run of the OS did not complete");
+        };
+
+        let preimages = StoragePreimages::from_scopes(&runner.exec_scopes).unwrap();
+        assert_eq!(preimages.len(), 1);
+        assert_eq!(
+            preimages.get(&StoragePreimages::storage_key(U256::from(1))),
+            Some(U256::from(1))
+        );
+    }
+
+    #[test]
+    fn test_recover_eth_address_hint() {
+        let program = Program::from_bytes(OS, None).unwrap();
+        let entrypoint = program
+            .get_identifier("src.interfaces.interfaces.ICairo1Helpers.recover_eth_address")
+            .and_then(|identifier| identifier.pc)
+            .unwrap();
+        let mut hint_processor = KakarotHintProcessor::new_empty()
+            .with_hint(recover_eth_address_hint(Arc::new(HintCache::default())));
+
+        // Runs `recover_eth_address` and returns its `(success, address)`.
+        let mut recover = |input: &RecoveryInput, y_parity: u8| {
+            let mut runner = CairoRunner::new(&program, LayoutName::plain, false, false).unwrap();
+            runner.initialize_function_runner().unwrap();
+            let limbs = |word: U256| {
+                let bytes = word.to_be_bytes::<32>();
+                [
+                    Felt252::from_bytes_be_slice(&bytes[16..]),
+                    Felt252::from_bytes_be_slice(&bytes[..16]),
+                ]
+            };
+            let args: Vec<_> = [input.msg_hash.into(), input.r, input.s]
+                .into_iter()
+                .flat_map(limbs)
+                .chain([Felt252::from(y_parity)])
+                .map(|felt| CairoArg::Single(felt.into()))
+                .collect();
+            let args: Vec<_> = args.iter().collect();
+            runner.run_from_entrypoint(entrypoint, &args, true, None, &mut hint_processor).unwrap();
+            runner.vm.get_return_values(2).unwrap()
+        };
+
+        // The generator is a valid signature point.
+        let input = RecoveryInput {
+            msg_hash: keccak256("kakarot"),
+            r: U256::from_be_bytes(
+                b256!("79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").0,
+            ),
+            s: U256::from(1),
+            y_parity: true,
+        };
+        let signer = input.recover().unwrap();
+        assert_eq!(
+            recover(&input, 1),
+            vec![Felt252::ONE.into(), Felt252::from_bytes_be_slice(signer.as_slice()).into()]
+        );
+
+        // A parity out of range is not a valid signature.
+        assert_eq!(recover(&input, 2), vec![Felt252::ZERO.into(), Felt252::ZERO.into()]);
+    }
+
+    #[test]
+    fn test_state_provider_hints() {
+        let address = address!("f3de3c0d654fda23dad170f0f320a92172509127");
+        let state = MockEthProvider::default();
+        state.add_account(
+            address,
+            ExtendedAccount::new(3, U256::from(1) << 130)
+                .extend_storage([(B256::with_last_byte(1), U256::MAX)]),
+        );
+        let mut hint_processor =
+            KakarotHintProcessor::new_empty().with_state_provider(Arc::new(state));
+
+        // The ids `address`, `nonce`, `balance`, `key` and `value` are laid out from `fp`.
+        let references = [0, 1, 2, 4, 6].map(HintReference::new_simple);
+        let reference_ids = ["address", "nonce", "balance", "key", "value"]
+            .into_iter()
+            .enumerate()
+            .map(|(id, name)| (name.to_string(), id))
+            .collect::<HashMap<_, _>>();
+
+        let mut run = |address: Address| {
+            let mut vm = VirtualMachine::new(false);
+            vm.segments.add();
+            let fp = vm.segments.add();
+            vm.insert_value(fp, Felt252::from_bytes_be_slice(address.as_slice())).unwrap();
+            vm.insert_value((fp + 4).unwrap(), Felt252::ONE).unwrap();
+            vm.insert_value((fp + 5).unwrap(), Felt252::ZERO).unwrap();
+
+            for code in [ACCOUNT_HINT, STORAGE_HINT] {
+                let hint_data = hint_processor
+                    .compile_hint(code, &ApTracking::new(), &reference_ids, &references)
+                    .unwrap();
+                hint_processor
+                    .execute_hint(&mut vm, &mut ExecutionScopes::new(), &hint_data, &HashMap::new())
+                    .unwrap();
+            }
+            vm.get_integer_range((fp + 1).unwrap(), 7)
+                .unwrap()
+                .into_iter()
+                .map(|felt| felt.into_owned())
+                .collect::<Vec<_>>()
+        };
+
+        // `nonce`, `balance.low`, `balance.high`, `key.low`, `key.high`, `value.low`, `value.high`.
+        let limb = Felt252::from(u128::MAX);
+        assert_eq!(
+            run(address),
+            [3, 0, 4, 1, 0].map(Felt252::from).into_iter().chain([limb, limb]).collect::<Vec<_>>()
+        );
+
+        // A missing account is empty.
+        assert_eq!(run(Address::ZERO), [0, 0, 0, 1, 0, 0, 0].map(Felt252::from));
+    }
+
+    #[test]
+    fn test_run_os_without_program_input() {
+        let mut hint_processor = KakarotHintProcessor::default();
+        let err = dry_run(
+            OS,
+            &mut hint_processor,
+            &ExecutionLimits::default(),
+            &RunnerTuning::default(),
+            &mut || false,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains(&format!("Unknown Hint: {BLOCK_HINT}")), "{err}");
+    }
+}
@@ -0,0 +1,362 @@
+//! Collection of the state accessed by a block, to fetch its witness.
+//!
+//! Instead of over-fetching the pre-state a block might read, the block is run once through revm
+//! with an [`AccessRecorder`], which records every account, storage slot, bytecode and block hash
+//! accessed by its execution. The recorded [`StateAccesses`] are then fetched from an
+//! [`InputSource`] as the witness of the block: the accounts with their accessed slots, with the
+//! delegates of the delegated ones, and the accessed block hashes only, see
+//! [`BlockHashHistory::fetch_accessed`].
+//!
+//! The recording run must execute on the pre-state of the block, e.g. the historical state of its
+//! parent in the local node, see [`local_program_input`]. The accounts touched outside of the EVM
+//! execution, the block beneficiary and the withdrawal recipients, are recorded alongside, while
+//! the accounts of the system calls are requested by the [`SystemCallPolicy`].
+
+use super::{
+    delegation::fetch_with_delegates, history::BlockHashHistory, program_input::ProgramInput,
+    system::SystemCallPolicy, AccountInput, InputError, InputSource,
+};
+use crate::{calltracer::is_precompile, execution::configure_chain_block_env, exex::CHAIN_SPEC};
+use alloy_consensus::Header;
+use alloy_primitives::{Address, B256, U256};
+use reth_node_api::{ConfigureEvm, ConfigureEvmEnv};
+use reth_node_ethereum::EthEvmConfig;
+use reth_primitives::{
+    revm_primitives::{EVMError, ResultAndState},
+    SealedBlockWithSenders, TransactionSigned,
+};
+use reth_provider::StateProviderFactory;
+use reth_revm::{
+    database::StateProviderDatabase,
+    db::{states::bundle_state::BundleRetention, BundleState},
+    interpreter::{opcode, CallInputs, CallOutcome, CreateInputs, CreateOutcome, Interpreter},
+    Database as RevmDatabase, DatabaseCommit, EvmContext, Inspector, StateBuilder,
+};
+use reth_tracing::tracing::debug;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+/// The state accessed by the execution of a block.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StateAccesses {
+    /// The accessed accounts, with their accessed storage slots.
+    pub accounts: BTreeMap<Address, BTreeSet<B256>>,
+    /// The numbers of the blocks whose hash is read by `BLOCKHASH`.
+    pub block_hashes: BTreeSet<u64>,
+}
+
+impl StateAccesses {
+    /// Records the access of an account.
+    pub fn account(&mut self, address: Address) {
+        self.accounts.entry(address).or_default();
+    }
+
+    /// Records the access of a storage slot of an account.
+    pub fn slot(&mut self, address: Address, slot: U256) {
+        self.accounts.entry(address).or_default().insert(B256::from(slot));
+    }
+
+    /// Records the read of the hash of a block.
+    pub fn block_hash(&mut self, block_number: u64) {
+        self.block_hashes.insert(block_number);
+    }
+
+    /// Records the accounts and storage slots of witness requests, e.g. the
+    /// [`witness_requests`](super::system::SystemCallPolicy::witness_requests) of the system
+    /// calls of the block.
+    pub fn extend(&mut self, requests: impl IntoIterator<Item = (Address, Vec<B256>)>) {
+        for (address, slots) in requests {
+            self.accounts.entry(address).or_default().extend(slots);
+        }
+    }
+
+    /// Records the accounts touched by a transaction before its execution: its sender, its
+    /// recipient, its access list, and the authorities of its authorizations with their
+    /// delegates.
+    ///
+    /// The delegates are recorded as the delegations set within the block are not in the
+    /// pre-state, so that [`fetch_with_delegates`] does not see them.
+    pub fn record_transaction(&mut self, transaction: &TransactionSigned, sender: Address) {
+        self.account(sender);
+        if let Some(to) = transaction.to() {
+            self.account(to);
+        }
+        if let Some(access_list) = transaction.access_list() {
+            self.extend(access_list.iter().map(|item| (item.address, item.storage_keys.clone())));
+        }
+        for authorization in transaction.authorization_list().into_iter().flatten() {
+            if let Ok(authority) = authorization.recover_authority() {
+                self.account(authority);
+                let delegate = authorization.address;
+                if !delegate.is_zero() && !is_precompile(delegate) {
+                    self.account(delegate);
+                }
+            }
+        }
+    }
+
+    /// Records the accounts touched by a block outside of the execution of its transactions: its
+    /// beneficiary and the recipients of its withdrawals.
+    pub fn record_block(&mut self, block: &SealedBlockWithSenders) {
+        self.account(block.header.beneficiary);
+        for withdrawal in block.body.withdrawals.iter().flatten() {
+            self.account(withdrawal.address);
+        }
+    }
+
+    /// Returns the witness requests of the accessed accounts, by address.
+    pub fn requests(&self) -> Vec<(Address, Vec<B256>)> {
+        self.accounts
+            .iter()
+            .map(|(address, slots)| (*address, slots.iter().copied().collect()))
+            .collect()
+    }
+
+    /// Fetches the witness of the accesses of a block from an [`InputSource`]: the accessed
+    /// accounts of its pre-state, with the delegates of the delegated ones, and the history of
+    /// its accessed block hashes.
+    pub async fn fetch(
+        &self,
+        source: &impl InputSource,
+        header: &Header,
+    ) -> Result<(Vec<AccountInput>, BlockHashHistory), InputError> {
+        let accounts = match header.number.checked_sub(1) {
+            Some(parent) => fetch_with_delegates(source, parent, self.requests()).await?,
+            None => Vec::new(),
+        };
+        let history =
+            BlockHashHistory::fetch_accessed(source, header, self.block_hashes.iter().copied())
+                .await?;
+        Ok((accounts, history))
+    }
+}
+
+/// A revm [`Inspector`] recording the state accessed by the inspected execution.
+///
+/// The recorder aggregates the accesses of all the transactions it inspects, which are the
+/// accesses of the block when it inspects all of them.
+#[derive(Debug, Default, Clone)]
+pub struct AccessRecorder {
+    /// The recorded accesses.
+    accesses: StateAccesses,
+}
+
+impl AccessRecorder {
+    /// Returns the recorded accesses.
+    pub const fn accesses(&self) -> &StateAccesses {
+        &self.accesses
+    }
+
+    /// Returns the recorded accesses, consuming the recorder.
+    pub fn into_accesses(self) -> StateAccesses {
+        self.accesses
+    }
+}
+
+impl<DB: RevmDatabase> Inspector<DB> for AccessRecorder {
+    fn step(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        let Ok(top) = interp.stack().peek(0) else { return };
+        match interp.current_opcode() {
+            opcode::SLOAD | opcode::SSTORE => {
+                self.accesses.slot(interp.contract.target_address, top);
+            }
+            opcode::BALANCE | opcode::EXTCODESIZE | opcode::EXTCODECOPY | opcode::EXTCODEHASH => {
+                self.accesses.account(Address::from_word(B256::from(top)));
+            }
+            opcode::BLOCKHASH => {
+                if let Ok(block_number) = u64::try_from(top) {
+                    self.accesses.block_hash(block_number);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn call(
+        &mut self,
+        _context: &mut EvmContext<DB>,
+        inputs: &mut CallInputs,
+    ) -> Option<CallOutcome> {
+        self.accesses.account(inputs.target_address);
+        self.accesses.account(inputs.bytecode_address);
+        None
+    }
+
+    fn create_end(
+        &mut self,
+        _context: &mut EvmContext<DB>,
+        _inputs: &CreateInputs,
+        outcome: CreateOutcome,
+    ) -> CreateOutcome {
+        if let Some(address) = outcome.address {
+            self.accesses.account(address);
+        }
+        outcome
+    }
+
+    fn selfdestruct(&mut self, contract: Address, target: Address, _value: U256) {
+        self.accesses.account(contract);
+        self.accesses.account(target);
+    }
+}
+
+/// Runs a block of the given chain through revm on its pre-state with an [`AccessRecorder`],
+/// returning the state accessed by its execution.
+///
+/// The invalid transactions are skipped as in [`crate::execution::execute_transactions`], the
+/// accounts touched by their validation being recorded nonetheless.
+pub fn collect_accesses<DB>(
+    db: DB,
+    block: &SealedBlockWithSenders,
+    chain_id: u64,
+) -> eyre::Result<StateAccesses>
+where
+    DB: RevmDatabase,
+    DB::Error: Into<eyre::Report> + fmt::Display,
+{
+    Ok(replay_block(db, block, chain_id)?.0)
+}
+
+/// Runs a block of the given chain through revm on its pre-state with an [`AccessRecorder`],
+/// returning the state accessed by its execution and the [`BundleState`] of its changes.
+///
+/// The system calls and the withdrawals are not applied, the changes being those of the
+/// transactions, see [`collect_accesses`].
+pub fn replay_block<DB>(
+    db: DB,
+    block: &SealedBlockWithSenders,
+    chain_id: u64,
+) -> eyre::Result<(StateAccesses, BundleState)>
+where
+    DB: RevmDatabase,
+    DB::Error: Into<eyre::Report> + fmt::Display,
+{
+    let header = block.header.header();
+    let config = EthEvmConfig::new(CHAIN_SPEC.clone());
+    let state = StateBuilder::new_with_database(db).with_bundle_update().build();
+    let mut evm = config.evm_with_inspector(state, AccessRecorder::default());
+    configure_chain_block_env(&config, &mut evm, header, chain_id);
+
+    for (transaction, sender) in block.body.transactions.iter().zip(&block.senders) {
+        evm.context.external.accesses.record_transaction(transaction, *sender);
+        config.fill_tx_env(evm.tx_mut(), transaction, *sender);
+        let ResultAndState { state, .. } = match evm.transact() {
+            Ok(result) => result,
+            Err(EVMError::Transaction(err)) => {
+                debug!(%err, hash = %transaction.hash(), "Skipping invalid transaction");
+                continue;
+            }
+            Err(EVMError::Database(err)) => return Err(err.into()),
+            Err(err) => eyre::bail!("{err}"),
+        };
+        evm.db_mut().commit(state);
+    }
+
+    evm.db_mut().merge_transitions(BundleRetention::PlainState);
+    let bundle = evm.db_mut().take_bundle();
+    let mut accesses = std::mem::take(&mut evm.context.external).into_accesses();
+    accesses.record_block(block);
+    debug!(
+        number = header.number,
+        accounts = accesses.accounts.len(),
+        block_hashes = accesses.block_hashes.len(),
+        "Collected the state accesses of the block"
+    );
+    Ok((accesses, bundle))
+}
+
+/// Builds the [`ProgramInput`] of a block from the local node: the block is replayed on the
+/// historical state of its parent with an [`AccessRecorder`], and the accessed state is read from
+/// the same state as the witness of the block, along with the system contracts of the system
+/// calls handled by the given policy.
+///
+/// The witness is fetched from `source`, which serves the state of the local node, e.g. a
+/// [`LocalInputSource`](super::local::LocalInputSource) or its cache.
+pub async fn local_program_input<P>(
+    provider: &P,
+    source: &impl InputSource,
+    block: &SealedBlockWithSenders,
+    chain_id: u64,
+    system_calls: SystemCallPolicy,
+) -> eyre::Result<ProgramInput>
+where
+    P: StateProviderFactory,
+{
+    let header = block.header.header();
+    let parent = header
+        .number
+        .checked_sub(1)
+        .ok_or_else(|| eyre::eyre!("The genesis block has no pre-state"))?;
+
+    let state = StateProviderDatabase::new(provider.history_by_block_number(parent)?);
+    let mut accesses = collect_accesses(state, block, chain_id)?;
+    accesses.extend(system_calls.witness_requests(header));
+    let (accounts, history) = accesses.fetch(source, header).await?;
+
+    Ok(ProgramInput::builder(chain_id)
+        .block(block)?
+        .accounts(&accounts)
+        .system_calls(system_calls)
+        .accessed_block_hashes(history)
+        .build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{address, bytes, TxKind};
+    use reth_revm::{
+        db::{CacheDB, EmptyDB},
+        inspector_handle_register,
+        primitives::{AccountInfo, Bytecode},
+        Evm,
+    };
+
+    #[test]
+    fn test_record_accesses() {
+        let contract = address!("00000000000000000000000000000000000000c0");
+        let other = address!("00000000000000000000000000000000000000aa");
+        // SLOAD(5), BALANCE(0xaa), BLOCKHASH(10), CALL(0xaa), STOP.
+        let code = bytes!("6005545060aa3150600a40506000600060006000600060aa5af15000");
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(
+            contract,
+            AccountInfo { code: Some(Bytecode::new_raw(code)), ..Default::default() },
+        );
+
+        let mut evm = Evm::builder()
+            .with_db(db)
+            .with_external_context(AccessRecorder::default())
+            .append_handler_register(inspector_handle_register)
+            .modify_block_env(|block| block.number = U256::from(20))
+            .modify_tx_env(|tx| {
+                tx.transact_to = TxKind::Call(contract);
+                tx.gas_limit = 1_000_000;
+            })
+            .build();
+        evm.transact().unwrap();
+
+        let accesses = evm.context.external.into_accesses();
+        assert_eq!(accesses.accounts[&contract], BTreeSet::from([B256::from(U256::from(5))]));
+        assert!(accesses.accounts[&other].is_empty());
+        assert_eq!(accesses.block_hashes, BTreeSet::from([10]));
+    }
+
+    #[test]
+    fn test_requests() {
+        let mut accesses = StateAccesses::default();
+        accesses.slot(Address::repeat_byte(2), U256::from(1));
+        accesses.account(Address::repeat_byte(1));
+        accesses.extend([(Address::repeat_byte(2), vec![B256::ZERO, B256::from(U256::from(1))])]);
+
+        assert_eq!(
+            accesses.requests(),
+            vec![
+                (Address::repeat_byte(1), vec![]),
+                (Address::repeat_byte(2), vec![B256::ZERO, B256::from(U256::from(1))]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,429 @@
+use super::{AccountInput, InputError, InputSource};
+use alloy_primitives::{Address, Bytes, B256};
+use metrics::Label;
+use reth_execution_types::Chain;
+use reth_tracing::tracing::debug;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Mutex, MutexGuard},
+};
+
+/// The default number of blocks whose changed accounts are recorded by a [`WitnessCache`].
+pub const DEFAULT_CACHE_WINDOW: u64 = 64;
+
+/// The hits and misses of a [`WitnessCache`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// The number of accounts served from the cache.
+    pub hits: u64,
+    /// The number of accounts served from the cache with a proof fetched again, see
+    /// [`CacheLookup::StaleProof`].
+    pub proof_refreshes: u64,
+    /// The number of accounts fetched from the source.
+    pub misses: u64,
+}
+
+impl CacheStats {
+    /// Returns the ratio of the accounts served from the cache, `0` when nothing was requested.
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.proof_refreshes + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            (self.hits + self.proof_refreshes) as f64 / total as f64
+        }
+    }
+}
+
+/// The result of a lookup in a [`WitnessCache`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheLookup {
+    /// The account is cached with the requested slots for the requested block.
+    Hit(AccountInput),
+    /// The account is unchanged since it was fetched at an earlier block: its values and its
+    /// storage proofs are valid, but its account proof is against the state root of the earlier
+    /// block and must be fetched again.
+    StaleProof(AccountInput),
+    /// The account or one of the requested slots is not cached for the requested block.
+    Miss,
+}
+
+/// A cached account, with the block whose post-state it was fetched at.
+#[derive(Debug, Clone)]
+struct CachedAccount {
+    /// The block whose post-state the account was fetched at.
+    block_number: u64,
+    /// The account with its cached slots.
+    account: AccountInput,
+}
+
+/// A cache of the account witnesses shared across consecutive blocks.
+///
+/// Each cached account is valid for the post-state of the block it was fetched at, and of the
+/// next blocks until one of them changes it. The accounts changed by each block are recorded with
+/// [`WitnessCache::advance`], over a window of the last blocks: an account is served for a later
+/// block when the changes of all the blocks in between are recorded and none of them changes it.
+///
+/// The account proofs are built against the state root of a block, which is changed by any state
+/// change: an account with a proof served for a later block is returned as a
+/// [`CacheLookup::StaleProof`], for its account proof to be fetched again. Its storage proofs stay
+/// valid, its storage root being unchanged.
+#[derive(Debug, Clone)]
+pub struct WitnessCache {
+    /// The number of blocks whose changes are recorded.
+    window: u64,
+    /// The accounts changed by each recorded block.
+    changes: BTreeMap<u64, HashSet<Address>>,
+    /// The cached accounts.
+    accounts: HashMap<Address, CachedAccount>,
+    /// The hits and misses of the cache.
+    stats: CacheStats,
+}
+
+impl Default for WitnessCache {
+    fn default() -> Self {
+        Self::with_window(DEFAULT_CACHE_WINDOW)
+    }
+}
+
+impl WitnessCache {
+    /// Creates an empty cache recording the changes of the last `window` blocks.
+    pub fn with_window(window: u64) -> Self {
+        Self {
+            window,
+            changes: BTreeMap::new(),
+            accounts: HashMap::new(),
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Returns the last block whose changes are recorded.
+    pub fn head(&self) -> Option<u64> {
+        self.changes.keys().next_back().copied()
+    }
+
+    /// Returns the number of cached accounts.
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    /// Returns whether no account is cached.
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+
+    /// Returns the hits and misses of the cache.
+    pub const fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Looks up an account with the requested slots for the post-state of a block.
+    pub fn get(&mut self, block_number: u64, address: Address, slots: &[B256]) -> CacheLookup {
+        let lookup = match self.accounts.get(&address) {
+            Some(cached)
+                if slots.iter().all(|slot| cached.account.storage.contains_key(slot)) &&
+                    self.unchanged(&address, cached.block_number, block_number) =>
+            {
+                let mut account = cached.account.clone();
+                account.storage.retain(|slot, _| slots.contains(slot));
+                account.storage_proofs.retain(|slot, _| slots.contains(slot));
+                if cached.block_number == block_number || account.account_proof.is_empty() {
+                    CacheLookup::Hit(account)
+                } else {
+                    CacheLookup::StaleProof(account)
+                }
+            }
+            _ => CacheLookup::Miss,
+        };
+
+        match lookup {
+            CacheLookup::Hit(_) => self.stats.hits += 1,
+            CacheLookup::StaleProof(_) => self.stats.proof_refreshes += 1,
+            CacheLookup::Miss => self.stats.misses += 1,
+        }
+        lookup
+    }
+
+    /// Caches an account fetched for the post-state of a block.
+    ///
+    /// The slots of an account fetched for the cached block are merged with the cached ones, an
+    /// account fetched for a later block replacing the cached one.
+    pub fn insert(&mut self, block_number: u64, account: AccountInput) {
+        match self.accounts.get_mut(&account.address) {
+            Some(cached) if cached.block_number == block_number => {
+                cached.account.storage.extend(account.storage);
+                cached.account.storage_proofs.extend(account.storage_proofs);
+            }
+            Some(cached) if cached.block_number > block_number => {}
+            _ => {
+                self.accounts.insert(account.address, CachedAccount { block_number, account });
+            }
+        }
+    }
+
+    /// Moves an unchanged account to the post-state of a later block, with its account proof
+    /// against the state root of this block.
+    pub fn refresh_proof(
+        &mut self,
+        block_number: u64,
+        address: Address,
+        account_proof: Vec<Bytes>,
+    ) {
+        let Some(cached) = self.accounts.get(&address) else { return };
+        if cached.block_number < block_number &&
+            self.unchanged(&address, cached.block_number, block_number)
+        {
+            let cached = self.accounts.get_mut(&address).expect("account is cached");
+            cached.block_number = block_number;
+            cached.account.account_proof = account_proof;
+        }
+    }
+
+    /// Drops a cached account.
+    pub fn remove(&mut self, address: &Address) {
+        self.accounts.remove(address);
+    }
+
+    /// Records the accounts changed by a block, given by its state diff.
+    ///
+    /// The changes of the blocks out of the window are dropped, along with the accounts which can
+    /// no longer be served.
+    pub fn advance(&mut self, block_number: u64, changed: impl IntoIterator<Item = Address>) {
+        self.changes.insert(block_number, changed.into_iter().collect());
+        let oldest = block_number.saturating_sub(self.window);
+        self.changes.retain(|number, _| *number > oldest);
+
+        // An account is served for the blocks after it only with their recorded changes.
+        let first = self.changes.keys().next().copied().unwrap_or(block_number);
+        self.accounts.retain(|_, cached| cached.block_number + 1 >= first);
+    }
+
+    /// Rolls back the cache before a reverted block: the changes of the reverted blocks and the
+    /// accounts fetched for their post-states are dropped.
+    pub fn revert(&mut self, from: u64) {
+        self.changes.retain(|number, _| *number < from);
+        self.accounts.retain(|_, cached| cached.block_number < from);
+    }
+
+    /// Returns whether an account is unchanged between the post-states of two blocks: the changes
+    /// of all the blocks in between are recorded and none of them changes the account.
+    fn unchanged(&self, address: &Address, from: u64, to: u64) -> bool {
+        from <= to &&
+            (from + 1..=to).all(|number| {
+                self.changes.get(&number).is_some_and(|changed| !changed.contains(address))
+            })
+    }
+}
+
+/// An [`InputSource`] serving the accounts from a [`WitnessCache`], falling back on the wrapped
+/// source.
+///
+/// The hits, the proof refreshes and the misses are reported by the `kakarot_witness_cache_hits`,
+/// `kakarot_witness_cache_proof_refreshes` and `kakarot_witness_cache_misses` counters.
+#[derive(Debug)]
+pub struct CachedInputSource<S> {
+    /// The wrapped source.
+    source: S,
+    /// The cache of the accounts.
+    cache: Mutex<WitnessCache>,
+    /// The metrics labels of the cache.
+    labels: Vec<Label>,
+}
+
+impl<S: InputSource> CachedInputSource<S> {
+    /// Creates a new [`CachedInputSource`] with an empty cache.
+    pub fn new(source: S) -> Self {
+        Self::with_labels(source, Vec::new())
+    }
+
+    /// Creates a new [`CachedInputSource`] reporting its metrics with the given labels.
+    pub fn with_labels(source: S, labels: Vec<Label>) -> Self {
+        Self { source, cache: Mutex::new(WitnessCache::default()), labels }
+    }
+
+    /// Returns the wrapped source.
+    pub const fn source(&self) -> &S {
+        &self.source
+    }
+
+    /// Acquires a lock on the cache.
+    pub fn cache(&self) -> MutexGuard<'_, WitnessCache> {
+        self.cache.lock().expect("failed to acquire witness cache lock")
+    }
+
+    /// Records the accounts changed by a block, see [`WitnessCache::advance`].
+    pub fn advance(&self, block_number: u64, changed: impl IntoIterator<Item = Address>) {
+        self.cache().advance(block_number, changed);
+    }
+
+    /// Records the accounts changed by each block of a committed chain, read from the reverts of
+    /// its execution outcome, which list the accounts changed by each block.
+    pub fn commit_chain(&self, chain: &Chain) {
+        let outcome = chain.execution_outcome();
+        let mut cache = self.cache();
+        for (offset, reverts) in outcome.bundle.reverts.iter().enumerate() {
+            let changed = reverts.iter().map(|(address, _)| *address);
+            cache.advance(outcome.first_block() + offset as u64, changed);
+        }
+    }
+
+    /// Rolls back the cache before a reverted chain, see [`WitnessCache::revert`].
+    pub fn revert_chain(&self, chain: &Chain) {
+        self.cache().revert(chain.first().number);
+    }
+}
+
+impl<S: InputSource> InputSource for CachedInputSource<S> {
+    async fn account(
+        &self,
+        block_number: u64,
+        address: Address,
+        slots: Vec<B256>,
+    ) -> Result<AccountInput, InputError> {
+        // The lock is released before fetching from the source.
+        let lookup = self.cache().get(block_number, address, &slots);
+        match lookup {
+            CacheLookup::Hit(account) => {
+                metrics::counter!("kakarot_witness_cache_hits", self.labels.clone()).increment(1);
+                return Ok(account);
+            }
+            CacheLookup::StaleProof(mut account) => {
+                // Only the account proof is fetched, the storage of the account being unchanged.
+                let proven = self.source.account(block_number, address, Vec::new()).await?;
+                if proven.storage_root == account.storage_root {
+                    metrics::counter!("kakarot_witness_cache_proof_refreshes", self.labels.clone())
+                        .increment(1);
+                    self.cache().refresh_proof(block_number, address, proven.account_proof.clone());
+                    account.account_proof = proven.account_proof;
+                    return Ok(account);
+                }
+                debug!(%address, block_number, "Dropping a cached account with a stale storage root");
+                self.cache().remove(&address);
+            }
+            CacheLookup::Miss => {}
+        }
+        metrics::counter!("kakarot_witness_cache_misses", self.labels.clone()).increment(1);
+
+        let account = self.source.account(block_number, address, slots).await?;
+        self.cache().insert(block_number, account.clone());
+        Ok(account)
+    }
+
+    async fn block_hash(&self, block_number: u64) -> Result<B256, InputError> {
+        self.source.block_hash(block_number).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{bytes, U256};
+    use std::{
+        collections::BTreeMap,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    /// A source counting its reads, returning each slot with its own key as value, and an account
+    /// proof of the block number when `proven`.
+    #[derive(Debug, Default)]
+    struct CountingSource {
+        reads: AtomicUsize,
+        proven: bool,
+    }
+
+    impl InputSource for CountingSource {
+        async fn account(
+            &self,
+            block_number: u64,
+            address: Address,
+            slots: Vec<B256>,
+        ) -> Result<AccountInput, InputError> {
+            self.reads.fetch_add(1, Ordering::Relaxed);
+            let storage =
+                slots.into_iter().map(|slot| (slot, U256::from_be_bytes(slot.0))).collect();
+            let account_proof = if self.proven {
+                vec![Bytes::copy_from_slice(&block_number.to_be_bytes())]
+            } else {
+                Vec::new()
+            };
+            Ok(AccountInput { address, storage, account_proof, ..Default::default() })
+        }
+
+        async fn block_hash(&self, _block_number: u64) -> Result<B256, InputError> {
+            Ok(B256::ZERO)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cached_input_source() {
+        let source = CachedInputSource::new(CountingSource::default());
+        let (alice, bob) = (Address::with_last_byte(1), Address::with_last_byte(2));
+        let slot = B256::with_last_byte(7);
+
+        source.account(10, alice, vec![slot]).await.unwrap();
+        source.account(10, bob, vec![]).await.unwrap();
+        let account = source.account(10, alice, vec![slot]).await.unwrap();
+        assert_eq!(account.storage, BTreeMap::from([(slot, U256::from(7))]));
+        assert_eq!(source.source().reads.load(Ordering::Relaxed), 2);
+
+        // Block 11 changes alice only, bob is still served from the cache.
+        source.advance(11, [alice]);
+        source.account(11, bob, vec![]).await.unwrap();
+        source.account(11, alice, vec![slot]).await.unwrap();
+        assert_eq!(source.source().reads.load(Ordering::Relaxed), 3);
+        assert_eq!(source.cache().stats(), CacheStats { hits: 2, proof_refreshes: 0, misses: 3 });
+    }
+
+    #[tokio::test]
+    async fn test_cached_input_source_refreshes_proofs() {
+        let source = CachedInputSource::new(CountingSource { proven: true, ..Default::default() });
+        let address = Address::with_last_byte(1);
+        let slot = B256::with_last_byte(7);
+
+        source.account(10, address, vec![slot]).await.unwrap();
+        source.advance(11, []);
+
+        // The account proof is fetched again against the state root of block 11, the storage
+        // being served from the cache.
+        let account = source.account(11, address, vec![slot]).await.unwrap();
+        assert_eq!(account.account_proof, vec![bytes!("000000000000000b")]);
+        assert_eq!(account.storage, BTreeMap::from([(slot, U256::from(7))]));
+        assert_eq!(source.source().reads.load(Ordering::Relaxed), 2);
+
+        // The refreshed account is now cached for block 11.
+        let account = source.account(11, address, vec![slot]).await.unwrap();
+        assert_eq!(account.account_proof, vec![bytes!("000000000000000b")]);
+        assert_eq!(source.source().reads.load(Ordering::Relaxed), 2);
+        assert_eq!(source.cache().stats(), CacheStats { hits: 1, proof_refreshes: 1, misses: 1 });
+    }
+
+    #[test]
+    fn test_witness_cache_misses_unknown_slots_and_blocks() {
+        let mut cache = WitnessCache::with_window(4);
+        let address = Address::with_last_byte(1);
+        let slot = B256::with_last_byte(1);
+        cache.insert(5, AccountInput { address, ..Default::default() });
+
+        assert!(matches!(cache.get(5, address, &[]), CacheLookup::Hit(_)));
+        assert_eq!(cache.get(5, address, &[slot]), CacheLookup::Miss);
+        // The changes of block 6 are not recorded.
+        assert_eq!(cache.get(6, address, &[]), CacheLookup::Miss);
+        assert_eq!(cache.get(4, address, &[]), CacheLookup::Miss);
+
+        cache.advance(6, []);
+        assert!(matches!(cache.get(6, address, &[]), CacheLookup::Hit(_)));
+
+        // A reverted block drops the accounts fetched for its post-state and its changes.
+        cache.insert(6, AccountInput { address: Address::with_last_byte(2), ..Default::default() });
+        cache.revert(6);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.head(), None);
+        assert_eq!(cache.get(6, address, &[]), CacheLookup::Miss);
+
+        // The accounts fetched before the window are dropped.
+        cache.advance(10, []);
+        assert!(cache.is_empty());
+        assert_eq!(cache.head(), Some(10));
+        assert_eq!(cache.stats(), CacheStats { hits: 2, proof_refreshes: 0, misses: 4 });
+    }
+}
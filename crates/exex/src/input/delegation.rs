@@ -0,0 +1,233 @@
+//! Resolution of the EIP-7702 delegations of the accounts of the witness.
+//!
+//! The code of an account delegated by an EIP-7702 authorization is a delegation designator,
+//! `0xef0100 || delegate`, and calling the account executes the code of the delegate in the context
+//! of the account. The witness of a block must therefore hold the code of the delegates of its
+//! delegated accounts, fetched alongside them, and of the delegates set by the authorizations of
+//! its transactions, recorded with the
+//! [`StateAccesses`](super::access::StateAccesses::record_transaction) of the block.
+//!
+//! Delegations are not followed transitively: when the delegate is itself delegated, its designator
+//! is executed as code, and a delegation to a precompile executes empty code.
+
+use super::{program_input::AccountStateInput, AccountInput, InputError, InputSource};
+use crate::{
+    calltracer::is_precompile,
+    telemetry::{self, Stage},
+};
+use alloy_primitives::{keccak256, Address, Bytes, B256};
+use reth_primitives::TransactionSigned;
+use reth_tracing::tracing::Instrument;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use thiserror::Error;
+
+/// The prefix of an EIP-7702 delegation designator.
+pub const DELEGATION_PREFIX: [u8; 3] = [0xef, 0x01, 0x00];
+
+/// The length of an EIP-7702 delegation designator.
+pub const DELEGATION_CODE_LEN: usize = 23;
+
+/// Represents errors that can occur when resolving the delegations of the witness.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DelegationError {
+    /// Error variant indicating a delegated account whose delegate is missing from the witness.
+    #[error("Missing delegate {delegate} of the account {authority}")]
+    MissingDelegate {
+        /// The delegated account.
+        authority: Address,
+        /// The delegate of the account.
+        delegate: Address,
+    },
+}
+
+/// Returns the delegate of a code, `None` if the code is not a delegation designator.
+pub fn delegate(code: &[u8]) -> Option<Address> {
+    if code.len() != DELEGATION_CODE_LEN {
+        return None;
+    }
+    code.strip_prefix(&DELEGATION_PREFIX).map(Address::from_slice)
+}
+
+/// Returns the code of an account delegated to an address, empty for the zero address which
+/// clears the delegation.
+pub fn designator(delegate: Address) -> Bytes {
+    if delegate.is_zero() {
+        return Bytes::new();
+    }
+    [&DELEGATION_PREFIX[..], delegate.as_slice()].concat().into()
+}
+
+/// Returns the codes set by the authorizations of the transactions of a block, by hash, so that
+/// the delegated accounts of its state diff are reported with their designator.
+pub fn authorization_codes<'a>(
+    transactions: impl IntoIterator<Item = &'a TransactionSigned>,
+) -> HashMap<B256, Bytes> {
+    transactions
+        .into_iter()
+        .filter_map(|transaction| transaction.authorization_list())
+        .flatten()
+        .map(|authorization| designator(authorization.address))
+        .map(|code| (keccak256(&code), code))
+        .collect()
+}
+
+/// Returns the delegates of the fetched accounts which are missing from them, to be fetched
+/// without storage slots, as their storage is never read.
+pub fn missing_delegates(accounts: &[AccountInput]) -> Vec<Address> {
+    let fetched: BTreeSet<_> = accounts.iter().map(|account| account.address).collect();
+    let delegates: BTreeSet<_> = accounts
+        .iter()
+        .filter_map(|account| delegate(&account.code))
+        .filter(|address| !fetched.contains(address) && !is_precompile(*address))
+        .collect();
+    delegates.into_iter().collect()
+}
+
+/// Fetches a batch of accounts from an [`InputSource`], with the delegates of the delegated ones.
+pub async fn fetch_with_delegates(
+    source: &impl InputSource,
+    block_number: u64,
+    requests: Vec<(Address, Vec<B256>)>,
+) -> Result<Vec<AccountInput>, InputError> {
+    async {
+        let mut accounts = source.accounts(block_number, requests).await?;
+        let delegates = missing_delegates(&accounts);
+        if !delegates.is_empty() {
+            let requests = delegates.into_iter().map(|address| (address, Vec::new())).collect();
+            accounts.extend(source.accounts(block_number, requests).await?);
+        }
+        Ok(accounts)
+    }
+    .instrument(telemetry::stage_span(Stage::Witness, block_number))
+    .await
+}
+
+/// Returns the code executed when calling an account of the pre-state: the code of its delegate
+/// when it is delegated, its own code otherwise.
+pub fn executed_code(
+    state: &BTreeMap<Address, AccountStateInput>,
+    address: Address,
+) -> Result<Bytes, DelegationError> {
+    let code = state.get(&address).map(|account| account.code.clone()).unwrap_or_default();
+    let Some(target) = delegate(&code) else {
+        return Ok(code);
+    };
+    if is_precompile(target) {
+        return Ok(Bytes::new());
+    }
+    state
+        .get(&target)
+        .map(|account| account.code.clone())
+        .ok_or(DelegationError::MissingDelegate { authority: address, delegate: target })
+}
+
+/// Validates that the pre-state holds the delegate of each of its delegated accounts.
+pub fn validate_delegations(
+    state: &BTreeMap<Address, AccountStateInput>,
+) -> Result<(), DelegationError> {
+    for address in state.keys() {
+        executed_code(state, *address)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::address;
+
+    const ALICE: Address = address!("00000000000000000000000000000000000a11ce");
+    const BOB: Address = address!("0000000000000000000000000000000000000b0b");
+    const WALLET: Address = address!("000000000000000000000000000000000000c0de");
+
+    /// A source holding a wallet contract, and accounts delegated to it.
+    struct DelegationSource;
+
+    impl InputSource for DelegationSource {
+        async fn account(
+            &self,
+            _block_number: u64,
+            address: Address,
+            _slots: Vec<B256>,
+        ) -> Result<AccountInput, InputError> {
+            let code = match address {
+                ALICE => designator(WALLET),
+                // Bob delegates to Alice, herself delegated.
+                BOB => designator(ALICE),
+                WALLET => Bytes::from_static(&[0x60, 0x00]),
+                _ => Bytes::new(),
+            };
+            Ok(AccountInput { address, code, ..Default::default() })
+        }
+
+        async fn block_hash(&self, _block_number: u64) -> Result<B256, InputError> {
+            Ok(B256::ZERO)
+        }
+    }
+
+    fn pre_state(accounts: &[(Address, Bytes)]) -> BTreeMap<Address, AccountStateInput> {
+        accounts
+            .iter()
+            .map(|(address, code)| {
+                (*address, AccountStateInput { code: code.clone(), ..Default::default() })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_designator() {
+        let code = designator(WALLET);
+        assert_eq!(code.len(), DELEGATION_CODE_LEN);
+        assert_eq!(delegate(&code), Some(WALLET));
+        assert_eq!(designator(Address::ZERO), Bytes::new());
+
+        // A code starting with the prefix but of another length is not a designator.
+        assert_eq!(delegate(&code[..DELEGATION_CODE_LEN - 1]), None);
+        assert_eq!(delegate(&[&code[..], &[0x00]].concat()), None);
+        assert_eq!(delegate(&[0x60, 0x00]), None);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_with_delegates() {
+        let accounts =
+            fetch_with_delegates(&DelegationSource, 1, vec![(ALICE, vec![])]).await.unwrap();
+        let addresses: Vec<_> = accounts.iter().map(|account| account.address).collect();
+        assert_eq!(addresses, vec![ALICE, WALLET]);
+
+        // The delegations are resolved one level deep only: the delegate of Bob is Alice, whose
+        // designator is executed as code.
+        let accounts = fetch_with_delegates(&DelegationSource, 1, vec![(BOB, vec![B256::ZERO])])
+            .await
+            .unwrap();
+        let addresses: Vec<_> = accounts.iter().map(|account| account.address).collect();
+        assert_eq!(addresses, vec![BOB, ALICE]);
+    }
+
+    #[test]
+    fn test_executed_code() {
+        let precompile = address!("0000000000000000000000000000000000000001");
+        let state = pre_state(&[
+            (ALICE, designator(WALLET)),
+            (BOB, designator(ALICE)),
+            (WALLET, Bytes::from_static(&[0x60, 0x00])),
+            (precompile, Bytes::new()),
+            (WALLET.create(0), designator(precompile)),
+        ]);
+
+        assert_eq!(executed_code(&state, ALICE), Ok(Bytes::from_static(&[0x60, 0x00])));
+        assert_eq!(executed_code(&state, BOB), Ok(designator(WALLET)));
+        assert_eq!(executed_code(&state, WALLET.create(0)), Ok(Bytes::new()));
+        assert!(validate_delegations(&state).is_ok());
+
+        // A delegation to itself executes its own designator.
+        let looping = pre_state(&[(ALICE, designator(ALICE))]);
+        assert_eq!(executed_code(&looping, ALICE), Ok(designator(ALICE)));
+
+        let missing = pre_state(&[(ALICE, designator(WALLET))]);
+        assert_eq!(
+            validate_delegations(&missing),
+            Err(DelegationError::MissingDelegate { authority: ALICE, delegate: WALLET })
+        );
+        assert_eq!(executed_code(&missing, WALLET), Ok(Bytes::new()));
+    }
+}
@@ -0,0 +1,375 @@
+//! History of the block hashes read by the `BLOCKHASH` opcode.
+//!
+//! `BLOCKHASH` returns the hashes of the [`BLOCKHASH_WINDOW`] most recent ancestors of a block,
+//! which are not part of the state, so the witness of a block must supply them. Since Prague, the
+//! hashes are read from the ring buffer of the EIP-2935 history storage contract in the pre-state
+//! of the block, alongside their storage proofs. Before, or for the ancestors older than the
+//! activation of the contract, the ancestor hash chain is fetched from the input source.
+//!
+//! The parent hash of the block anchors the history: the hash of the parent must be the one of the
+//! header. The history is given to the program in `program_input["block_hashes"]`, and the
+//! `BLOCKHASH` reads of its execution, see [`blockhash_reads`], are validated against it when the
+//! block is compared with its native execution, see
+//! [`compare_block`](crate::divergence::compare_block).
+
+use super::{
+    system::{slot, HISTORY_SERVE_WINDOW, HISTORY_STORAGE_ADDRESS},
+    AccountInput, InputError, InputSource,
+};
+use crate::structlog::Step;
+use alloy_consensus::Header;
+use alloy_primitives::{Address, B256};
+use reth_revm::interpreter::opcode::BLOCKHASH;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, ops::Range};
+use thiserror::Error;
+
+/// The number of ancestors whose hash is served by the `BLOCKHASH` opcode.
+pub const BLOCKHASH_WINDOW: u64 = 256;
+
+/// Represents errors that can occur when building or validating the block hash history.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum HistoryError {
+    /// Error variant indicating a history built for another block.
+    #[error("History of block {actual}, expected block {expected}")]
+    BlockMismatch {
+        /// The number of the executed block.
+        expected: u64,
+        /// The number of the block of the history.
+        actual: u64,
+    },
+
+    /// Error variant indicating that the hash of the parent is not the parent hash of the header.
+    #[error("Parent hash {actual} of the history, expected {expected}")]
+    ParentMismatch {
+        /// The parent hash of the header.
+        expected: B256,
+        /// The hash of the parent in the history.
+        actual: B256,
+    },
+
+    /// Error variant indicating an ancestor of the window missing from the history.
+    #[error("Missing hash of block {0}")]
+    MissingHash(u64),
+
+    /// Error variant indicating a hash outside of the window of the block.
+    #[error("Block {0} is outside of the BLOCKHASH window")]
+    OutOfWindow(u64),
+
+    /// Error variant indicating a `BLOCKHASH` read returning another hash than the history.
+    #[error("BLOCKHASH({block_number}) read {actual}, expected {expected}")]
+    InvalidRead {
+        /// The number of the read block.
+        block_number: u64,
+        /// The hash of the block in the history.
+        expected: B256,
+        /// The hash returned by the read.
+        actual: B256,
+    },
+}
+
+/// The hashes of the ancestors of a block within the `BLOCKHASH` window.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct BlockHashHistory {
+    /// The number of the block executed with the history.
+    pub block_number: u64,
+    /// The hashes of the ancestors, by block number.
+    pub hashes: BTreeMap<u64, B256>,
+}
+
+impl BlockHashHistory {
+    /// Returns the numbers of the ancestors of a block within the `BLOCKHASH` window.
+    pub fn window(block_number: u64) -> Range<u64> {
+        block_number.saturating_sub(BLOCKHASH_WINDOW)..block_number
+    }
+
+    /// Returns the ancestors of a block stored in the history storage contract before its
+    /// execution, i.e. its window without its parent.
+    fn stored(block_number: u64) -> Range<u64> {
+        let window = Self::window(block_number);
+        window.start..window.end.saturating_sub(1)
+    }
+
+    /// Returns the account and the storage slots of the EIP-2935 history storage contract holding
+    /// the window of a block in its pre-state.
+    ///
+    /// The hash of the parent is written by the block itself, it is taken from the header instead.
+    pub fn history_request(header: &Header) -> (Address, Vec<B256>) {
+        let slots =
+            Self::stored(header.number).map(|number| slot(number % HISTORY_SERVE_WINDOW)).collect();
+        (HISTORY_STORAGE_ADDRESS, slots)
+    }
+
+    /// Builds the history of a block from the pre-state of the EIP-2935 history storage contract,
+    /// fetched with the [`history_request`](Self::history_request) of the block.
+    ///
+    /// The ancestors whose slot is empty, e.g. older than the activation of the contract, are
+    /// left out of the history.
+    pub fn from_history_storage(header: &Header, account: &AccountInput) -> Self {
+        let mut hashes: BTreeMap<_, _> = Self::stored(header.number)
+            .filter_map(|number| {
+                let value = account.storage.get(&slot(number % HISTORY_SERVE_WINDOW))?;
+                (!value.is_zero()).then(|| (number, B256::from(*value)))
+            })
+            .collect();
+        if let Some(parent) = header.number.checked_sub(1) {
+            hashes.insert(parent, header.parent_hash);
+        }
+        Self { block_number: header.number, hashes }
+    }
+
+    /// Fetches the history of a block from an [`InputSource`].
+    ///
+    /// With `history_storage`, the hashes are read from the EIP-2935 history storage contract,
+    /// the ancestors missing from its ring buffer being fetched one by one from the source.
+    pub async fn fetch(
+        source: &impl InputSource,
+        header: &Header,
+        history_storage: bool,
+    ) -> Result<Self, InputError> {
+        let mut history = if history_storage && header.number > 0 {
+            let (address, slots) = Self::history_request(header);
+            let account = source.account(header.number - 1, address, slots).await?;
+            Self::from_history_storage(header, &account)
+        } else {
+            Self::from_history_storage(header, &AccountInput::default())
+        };
+
+        for number in Self::window(header.number) {
+            if !history.hashes.contains_key(&number) {
+                history.hashes.insert(number, source.block_hash(number).await?);
+            }
+        }
+        Ok(history)
+    }
+
+    /// Fetches the hashes of the given ancestors of a block from an [`InputSource`], e.g. the
+    /// blocks read by `BLOCKHASH` recorded by an [`AccessRecorder`](super::access::AccessRecorder).
+    ///
+    /// The numbers outside of the window of the block, whose hash is zero, are left out, and the
+    /// hash of the parent is taken from the header.
+    pub async fn fetch_accessed(
+        source: &impl InputSource,
+        header: &Header,
+        block_numbers: impl IntoIterator<Item = u64>,
+    ) -> Result<Self, InputError> {
+        let mut history = Self::from_history_storage(header, &AccountInput::default());
+        let window = Self::window(header.number);
+        for number in block_numbers {
+            if window.contains(&number) && !history.hashes.contains_key(&number) {
+                history.hashes.insert(number, source.block_hash(number).await?);
+            }
+        }
+        Ok(history)
+    }
+
+    /// Validates the history against the header of its block: it must cover the whole window,
+    /// anchored by the parent hash of the header.
+    pub fn validate(&self, header: &Header) -> Result<(), HistoryError> {
+        self.validate_accessed(header)?;
+        let window = Self::window(self.block_number);
+        if let Some(number) = window.clone().find(|number| !self.hashes.contains_key(number)) {
+            return Err(HistoryError::MissingHash(number));
+        }
+        Ok(())
+    }
+
+    /// Validates a history fetched with [`fetch_accessed`](Self::fetch_accessed) against the
+    /// header of its block: it must be within the window, anchored by the parent hash of the
+    /// header, without covering the whole window.
+    pub fn validate_accessed(&self, header: &Header) -> Result<(), HistoryError> {
+        if self.block_number != header.number {
+            return Err(HistoryError::BlockMismatch {
+                expected: header.number,
+                actual: self.block_number,
+            });
+        }
+
+        let window = Self::window(self.block_number);
+        if let Some(number) = self.hashes.keys().find(|number| !window.contains(number)) {
+            return Err(HistoryError::OutOfWindow(*number));
+        }
+        if let Some(parent) = window.end.checked_sub(1) {
+            let actual =
+                self.hashes.get(&parent).copied().ok_or(HistoryError::MissingHash(parent))?;
+            if actual != header.parent_hash {
+                return Err(HistoryError::ParentMismatch { expected: header.parent_hash, actual });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the result of `BLOCKHASH(block_number)`, zero outside of the window.
+    pub fn block_hash(&self, block_number: u64) -> B256 {
+        self.hashes.get(&block_number).copied().unwrap_or_default()
+    }
+
+    /// Validates the `BLOCKHASH` reads of an execution of the block, as pairs of the read block
+    /// number and the returned hash.
+    pub fn validate_reads(
+        &self,
+        reads: impl IntoIterator<Item = (u64, B256)>,
+    ) -> Result<(), HistoryError> {
+        for (block_number, actual) in reads {
+            let expected = self.block_hash(block_number);
+            if actual != expected {
+                return Err(HistoryError::InvalidRead { block_number, expected, actual });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the `BLOCKHASH` reads of the steps of an execution, as pairs of the read block number
+/// and the returned hash: the number is the top of the stack of a `BLOCKHASH` step, and the hash
+/// the top of the stack of the next step of the same transaction.
+///
+/// Numbers which do not fit in a `u64` read as `u64::MAX`, outside of any window.
+pub fn blockhash_reads(steps: &[Step]) -> Vec<(u64, B256)> {
+    steps
+        .windows(2)
+        .filter(|pair| pair[0].op == BLOCKHASH && pair[0].error.is_none())
+        .filter(|pair| pair[0].tx_index == pair[1].tx_index)
+        .map(|pair| (pair[0].peek(0).saturating_to(), B256::from(pair[1].peek(0))))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::U256;
+
+    /// A source whose block hashes are their number, and whose history storage contract only
+    /// holds the blocks from 300 on.
+    struct HistorySource;
+
+    impl InputSource for HistorySource {
+        async fn account(
+            &self,
+            _block_number: u64,
+            address: Address,
+            slots: Vec<B256>,
+        ) -> Result<AccountInput, InputError> {
+            let storage = slots
+                .into_iter()
+                .map(|slot| {
+                    let index = U256::from_be_bytes(slot.0).to::<u64>();
+                    let value = if index >= 300 { U256::from(index) } else { U256::ZERO };
+                    (slot, value)
+                })
+                .collect();
+            Ok(AccountInput { address, storage, ..Default::default() })
+        }
+
+        async fn block_hash(&self, block_number: u64) -> Result<B256, InputError> {
+            Ok(B256::from(U256::from(block_number)))
+        }
+    }
+
+    fn header(number: u64) -> Header {
+        Header { number, parent_hash: B256::from(U256::from(number - 1)), ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_history() {
+        let header = header(400);
+        let history = BlockHashHistory::fetch(&HistorySource, &header, true).await.unwrap();
+
+        assert_eq!(history.hashes.len(), BLOCKHASH_WINDOW as usize);
+        assert_eq!(history.block_hash(144), B256::from(U256::from(144)));
+        assert_eq!(history.block_hash(399), header.parent_hash);
+        // The hashes outside of the window are zero.
+        assert_eq!(history.block_hash(143), B256::ZERO);
+        assert_eq!(history.block_hash(400), B256::ZERO);
+        assert!(history.validate(&header).is_ok());
+
+        let early = header(10);
+        let history = BlockHashHistory::fetch(&HistorySource, &early, false).await.unwrap();
+        assert_eq!(history.hashes.len(), 10);
+        assert!(history.validate(&early).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_accessed_history() {
+        let header = header(400);
+        let history = BlockHashHistory::fetch_accessed(&HistorySource, &header, [150, 10, 400])
+            .await
+            .unwrap();
+
+        // Only the accessed hashes within the window are fetched, alongside the parent hash.
+        assert_eq!(history.hashes.keys().copied().collect::<Vec<_>>(), vec![150, 399]);
+        assert!(history.validate_accessed(&header).is_ok());
+        assert_eq!(history.validate(&header), Err(HistoryError::MissingHash(144)));
+
+        let mut forked = history.clone();
+        forked.hashes.insert(399, B256::repeat_byte(1));
+        assert!(matches!(
+            forked.validate_accessed(&header),
+            Err(HistoryError::ParentMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_history_request() {
+        let (address, slots) = BlockHashHistory::history_request(&header(8200));
+        assert_eq!(address, HISTORY_STORAGE_ADDRESS);
+        assert_eq!(slots.len(), BLOCKHASH_WINDOW as usize);
+        // The ring buffer wraps around the serve window.
+        assert_eq!(slots.last(), Some(&slot(8198 % HISTORY_SERVE_WINDOW)));
+    }
+
+    #[tokio::test]
+    async fn test_validate_history() {
+        let header = header(300);
+        let history = BlockHashHistory::fetch(&HistorySource, &header, false).await.unwrap();
+
+        let mut forked = history.clone();
+        forked.hashes.insert(299, B256::repeat_byte(1));
+        assert_eq!(
+            forked.validate(&header),
+            Err(HistoryError::ParentMismatch {
+                expected: header.parent_hash,
+                actual: B256::repeat_byte(1)
+            })
+        );
+
+        let mut partial = history.clone();
+        partial.hashes.remove(&100);
+        assert_eq!(partial.validate(&header), Err(HistoryError::MissingHash(100)));
+
+        let mut extended = history.clone();
+        extended.hashes.insert(10, B256::ZERO);
+        assert_eq!(extended.validate(&header), Err(HistoryError::OutOfWindow(10)));
+
+        assert!(history
+            .validate_reads([(200, B256::from(U256::from(200))), (1, B256::ZERO)])
+            .is_ok());
+        assert_eq!(
+            history.validate_reads([(200, B256::ZERO)]),
+            Err(HistoryError::InvalidRead {
+                block_number: 200,
+                expected: B256::from(U256::from(200)),
+                actual: B256::ZERO
+            })
+        );
+    }
+
+    #[test]
+    fn test_blockhash_reads() {
+        let step = |tx_index, op, top: u64| Step {
+            tx_index,
+            op,
+            stack: vec![U256::from(7), U256::from(top)],
+            ..Default::default()
+        };
+        let steps = [
+            step(0, BLOCKHASH, 200),
+            step(0, 0x50, 0xaa),
+            // The last step of a transaction has no result in the same transaction.
+            step(0, BLOCKHASH, 201),
+            step(1, BLOCKHASH, 202),
+        ];
+
+        assert_eq!(blockhash_reads(&steps), vec![(200, B256::from(U256::from(0xaa)))]);
+    }
+}
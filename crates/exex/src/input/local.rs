@@ -0,0 +1,150 @@
+//! An [`InputSource`] reading the pre-state of the blocks from the database of the local node.
+
+use super::{
+    access::local_program_input, cache::CachedInputSource, program_input::ProgramInput,
+    system::SystemCallPolicy, AccountInput, InputError, InputSource,
+};
+use alloy_primitives::{Address, B256, KECCAK256_EMPTY};
+use reth_primitives::SealedBlockWithSenders;
+use reth_provider::{
+    AccountReader, BlockHashReader, BlockReader, StateProvider, StateProviderFactory,
+    TransactionVariant,
+};
+use std::collections::BTreeMap;
+
+/// A source of the blocks and their [`ProgramInput`]s, by number.
+pub trait BlockInputSource: Send + Sync {
+    /// Returns a block with the senders of its transactions.
+    fn block(&self, block_number: u64) -> eyre::Result<SealedBlockWithSenders>;
+
+    /// Builds the [`ProgramInput`] of a block: its header, its transactions and its witness, the
+    /// system calls of the block being handled by the given policy.
+    fn program_input(
+        &self,
+        block_number: u64,
+        chain_id: u64,
+        system_calls: SystemCallPolicy,
+    ) -> eyre::Result<ProgramInput>;
+}
+
+/// An [`InputSource`] reading the historical state of the local node, e.g. the provider of the
+/// node running the ExEx.
+///
+/// The local node is trusted: the accounts are read without their Merkle-Patricia proofs, and
+/// their storage root is left unset.
+#[derive(Debug, Clone)]
+pub struct LocalInputSource<P> {
+    /// The provider of the local node.
+    provider: P,
+}
+
+impl<P> LocalInputSource<P> {
+    /// Creates a new [`LocalInputSource`] reading from the given provider.
+    pub const fn new(provider: P) -> Self {
+        Self { provider }
+    }
+
+    /// Returns the provider of the local node.
+    pub const fn provider(&self) -> &P {
+        &self.provider
+    }
+}
+
+impl<P> InputSource for LocalInputSource<P>
+where
+    P: StateProviderFactory + BlockHashReader + Send + Sync,
+{
+    async fn account(
+        &self,
+        block_number: u64,
+        address: Address,
+        slots: Vec<B256>,
+    ) -> Result<AccountInput, InputError> {
+        let state = self.provider.history_by_block_number(block_number)?;
+        let account = state.basic_account(address)?.unwrap_or_default();
+        let code = match account.bytecode_hash {
+            Some(hash) if hash != KECCAK256_EMPTY => state
+                .bytecode_by_hash(hash)?
+                .map(|bytecode| bytecode.original_bytes())
+                .unwrap_or_default(),
+            _ => Default::default(),
+        };
+
+        let mut storage = BTreeMap::new();
+        for slot in slots {
+            storage.insert(slot, state.storage(address, slot)?.unwrap_or_default());
+        }
+
+        Ok(AccountInput {
+            address,
+            nonce: account.nonce,
+            balance: account.balance,
+            code_hash: account.bytecode_hash.unwrap_or(KECCAK256_EMPTY),
+            code,
+            storage,
+            ..Default::default()
+        })
+    }
+
+    async fn block_hash(&self, block_number: u64) -> Result<B256, InputError> {
+        self.provider.block_hash(block_number)?.ok_or(InputError::BlockNotFound(block_number))
+    }
+}
+
+/// The blocks are read from the local node, and their witness collected with
+/// [`local_program_input`].
+impl<P> BlockInputSource for LocalInputSource<P>
+where
+    P: BlockReader + StateProviderFactory + Clone + Send + Sync,
+{
+    fn block(&self, block_number: u64) -> eyre::Result<SealedBlockWithSenders> {
+        Ok(self
+            .provider
+            .sealed_block_with_senders(block_number.into(), TransactionVariant::WithHash)?
+            .ok_or(InputError::BlockNotFound(block_number))?)
+    }
+
+    fn program_input(
+        &self,
+        block_number: u64,
+        chain_id: u64,
+        system_calls: SystemCallPolicy,
+    ) -> eyre::Result<ProgramInput> {
+        let block = self.block(block_number)?;
+        // The local source does not await any I/O, its futures complete on their first poll.
+        futures::executor::block_on(local_program_input(
+            &self.provider,
+            self,
+            &block,
+            chain_id,
+            system_calls,
+        ))
+    }
+}
+
+/// The blocks are read from the local node, and the witness of each block is served from the
+/// accounts cached for the previous blocks, see [`CachedInputSource`].
+impl<P> BlockInputSource for CachedInputSource<LocalInputSource<P>>
+where
+    P: BlockReader + StateProviderFactory + Clone + Send + Sync,
+{
+    fn block(&self, block_number: u64) -> eyre::Result<SealedBlockWithSenders> {
+        self.source().block(block_number)
+    }
+
+    fn program_input(
+        &self,
+        block_number: u64,
+        chain_id: u64,
+        system_calls: SystemCallPolicy,
+    ) -> eyre::Result<ProgramInput> {
+        let block = self.block(block_number)?;
+        futures::executor::block_on(local_program_input(
+            self.source().provider(),
+            self,
+            &block,
+            chain_id,
+            system_calls,
+        ))
+    }
+}
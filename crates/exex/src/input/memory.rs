@@ -0,0 +1,458 @@
+//! Writing of the [`ProgramInput`] into the memory of the VM, by the `block` and `state` hints of
+//! the Kakarot program.
+//!
+//! The values are laid out as the `gen_arg` helper of the Cairo test suite does: nested structs
+//! and lists are written in new segments and referenced by pointer, `Uint256`s are split into
+//! their `low` and `high` 128 bits, and dicts are new segments tracked by the dict manager,
+//! referenced by both their `*_start` and current pointer members. Members are placed by name at
+//! the offsets of the program layouts, so that the same input feeds any build of the program.
+
+use super::program_input::{AccountStateInput, BlockInput, HeaderInput, TransactionInput};
+use crate::{
+    model::U128_BYTES_SIZE,
+    precompute::{HintCache, PrecomputedAccount},
+    serde::cache::{CachedStruct, ProgramLayoutCache},
+};
+use alloy_primitives::{Address, B256, U256};
+use cairo_vm::{
+    hint_processor::builtin_hint_processor::dict_manager::DictManager,
+    types::relocatable::{MaybeRelocatable, Relocatable},
+    vm::{errors::hint_errors::HintError, vm_core::VirtualMachine},
+    Felt252,
+};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::Arc,
+};
+
+/// The full name of the block struct.
+pub const BLOCK_STRUCT: &str = "src.model.model.Block";
+
+/// The full name of the block header struct.
+pub const HEADER_STRUCT: &str = "src.model.model.BlockHeader";
+
+/// The full name of the encoded transaction struct.
+pub const TRANSACTION_STRUCT: &str = "src.model.model.TransactionEncoded";
+
+/// The full name of the state struct.
+pub const STATE_STRUCT: &str = "src.model.model.State";
+
+/// The full name of the account struct.
+pub const ACCOUNT_STRUCT: &str = "src.model.model.Account";
+
+/// The `JUMPDEST` opcode.
+const JUMPDEST: u8 = 0x5b;
+
+/// The `PUSH1` opcode, followed by the `PUSH2` to `PUSH32` opcodes.
+const PUSH1: u8 = 0x60;
+
+/// The `PUSH32` opcode.
+const PUSH32: u8 = 0x7f;
+
+/// The value of a struct member, written according to the Cairo type of the member.
+#[derive(Debug, Clone)]
+enum Value {
+    /// A single felt.
+    Felt(Felt252),
+    /// A 256-bit word, an inline `Uint256` or a `Uint256*`.
+    Word(U256),
+    /// An optional felt, a `model.Option` or a felt defaulting to zero.
+    OptionalFelt(Option<Felt252>),
+    /// An optional 256-bit word, a `model.Option` whose value points to a `Uint256`, or an inline
+    /// `Uint256` defaulting to zero.
+    OptionalWord(Option<U256>),
+    /// A pointer to data written in a new segment.
+    Pointer(Relocatable),
+}
+
+/// Writes the [`ProgramInput`](super::program_input::ProgramInput) into the memory of a VM.
+pub struct InputWriter<'a> {
+    /// The VM whose memory is written.
+    vm: &'a mut VirtualMachine,
+    /// The dict manager tracking the written dicts.
+    dict_manager: &'a mut DictManager,
+    /// The struct layouts of the program.
+    layouts: &'a ProgramLayoutCache,
+}
+
+impl fmt::Debug for InputWriter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InputWriter")
+            .field("dicts", &self.dict_manager.trackers.len())
+            .field("layouts", &self.layouts.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> InputWriter<'a> {
+    /// Creates a new [`InputWriter`].
+    pub fn new(
+        vm: &'a mut VirtualMachine,
+        dict_manager: &'a mut DictManager,
+        layouts: &'a ProgramLayoutCache,
+    ) -> Self {
+        Self { vm, dict_manager, layouts }
+    }
+
+    /// Writes a block, returning the pointer to its `model.Block`.
+    pub fn write_block(&mut self, block: &BlockInput) -> Result<Relocatable, HintError> {
+        let header = self.write_header(&block.block_header)?;
+
+        let mut transactions = Vec::new();
+        for transaction in &block.transactions {
+            transactions.extend(self.transaction_cells(transaction)?);
+        }
+        let transactions = self.write_cells(&transactions)?;
+
+        self.write_struct(
+            BLOCK_STRUCT,
+            &[
+                (&["block_header"], Value::Pointer(header)),
+                (&["transactions_len"], Value::Felt(block.transactions.len().into())),
+                (&["transactions"], Value::Pointer(transactions)),
+            ],
+        )
+    }
+
+    /// Writes the pre-state accounts, returning the pointer to the `model.State`.
+    ///
+    /// The hashes and jump destinations of the accounts are taken from the [`HintCache`], and
+    /// computed inline for the accounts missing from it.
+    pub fn write_state(
+        &mut self,
+        state: &BTreeMap<Address, AccountStateInput>,
+        cache: &HintCache,
+    ) -> Result<Relocatable, HintError> {
+        let mut accounts = HashMap::with_capacity(state.len());
+        for (address, account) in state {
+            let precomputed = match cache.account(address) {
+                Some(precomputed) => Cow::Borrowed(precomputed),
+                None => Cow::Owned(PrecomputedAccount::compute(account)),
+            };
+            let key = Felt252::from_bytes_be_slice(address.as_slice());
+            accounts.insert(key.into(), self.write_account(account, &precomputed)?.into());
+        }
+        let accounts = self.write_dict(accounts)?;
+        let events = self.write_cells(&[])?;
+        let transfers = self.write_cells(&[])?;
+
+        self.write_struct(
+            STATE_STRUCT,
+            &[
+                (&["accounts_start", "accounts"], Value::Pointer(accounts)),
+                (&["events_len"], Value::Felt(Felt252::ZERO)),
+                (&["events"], Value::Pointer(events)),
+                (&["transfers_len"], Value::Felt(Felt252::ZERO)),
+                (&["transfers"], Value::Pointer(transfers)),
+            ],
+        )
+    }
+
+    /// Writes a block header, returning the pointer to its `model.BlockHeader`.
+    fn write_header(&mut self, header: &HeaderInput) -> Result<Relocatable, HintError> {
+        let bloom = header
+            .bloom
+            .as_slice()
+            .chunks(U128_BYTES_SIZE)
+            .map(|chunk| Felt252::from_bytes_be_slice(chunk).into())
+            .collect::<Vec<_>>();
+        let bloom_len = bloom.len();
+        let bloom = self.write_cells(&bloom)?;
+        let extra_data = self.write_bytes(&header.extra_data)?;
+        let word = |hash: B256| Value::Word(hash.into());
+        let felt = |value: u64| Value::Felt(value.into());
+
+        self.write_struct(
+            HEADER_STRUCT,
+            &[
+                (&["hash"], word(header.hash)),
+                (&["parent_hash"], word(header.parent_hash)),
+                (&["ommers_hash", "uncle_hash"], word(header.uncle_hash)),
+                (
+                    &["coinbase"],
+                    Value::Felt(Felt252::from_bytes_be_slice(header.coinbase.as_slice())),
+                ),
+                (&["state_root"], word(header.state_root)),
+                (&["transactions_root", "transactions_trie"], word(header.transactions_trie)),
+                (&["receipt_root", "receipt_trie"], word(header.receipt_trie)),
+                (
+                    &["withdrawals_root"],
+                    Value::OptionalWord(header.withdrawals_root.map(Into::into)),
+                ),
+                (&["bloom_len"], Value::Felt(bloom_len.into())),
+                (&["bloom"], Value::Pointer(bloom)),
+                (&["difficulty"], Value::Word(header.difficulty)),
+                (&["number"], felt(header.number.to())),
+                (&["gas_limit"], felt(header.gas_limit.to())),
+                (&["gas_used"], felt(header.gas_used.to())),
+                (&["timestamp"], felt(header.timestamp.to())),
+                (&["mix_hash"], word(header.mix_hash)),
+                (&["nonce"], Value::Felt(Felt252::from_bytes_be_slice(header.nonce.as_slice()))),
+                (
+                    &["base_fee_per_gas"],
+                    Value::OptionalFelt(header.base_fee_per_gas.map(|fee| fee.to::<u64>().into())),
+                ),
+                (
+                    &["blob_gas_used"],
+                    Value::OptionalFelt(header.blob_gas_used.map(|gas| gas.to::<u64>().into())),
+                ),
+                (
+                    &["excess_blob_gas"],
+                    Value::OptionalFelt(header.excess_blob_gas.map(|gas| gas.to::<u64>().into())),
+                ),
+                (
+                    &["parent_beacon_block_root"],
+                    Value::OptionalWord(header.parent_beacon_block_root.map(Into::into)),
+                ),
+                (&["requests_root"], Value::OptionalWord(header.requests_root.map(Into::into))),
+                (&["extra_data_len"], Value::Felt(header.extra_data.len().into())),
+                (&["extra_data"], Value::Pointer(extra_data)),
+            ],
+        )
+    }
+
+    /// Returns the cells of an encoded transaction, written inline in the transactions array.
+    fn transaction_cells(
+        &mut self,
+        transaction: &TransactionInput,
+    ) -> Result<Vec<MaybeRelocatable>, HintError> {
+        let rlp = self.write_bytes(&transaction.rlp)?;
+        let signature = transaction
+            .signature
+            .iter()
+            .map(|felt| Felt252::from(*felt).into())
+            .collect::<Vec<_>>();
+        let signature = self.write_cells(&signature)?;
+
+        self.struct_cells(
+            TRANSACTION_STRUCT,
+            &[
+                (&["rlp_len"], Value::Felt(transaction.rlp_len.into())),
+                (&["rlp"], Value::Pointer(rlp)),
+                (&["signature_len"], Value::Felt(transaction.signature_len.into())),
+                (&["signature"], Value::Pointer(signature)),
+                (
+                    &["sender"],
+                    Value::Felt(Felt252::from_bytes_be_slice(transaction.sender.as_slice())),
+                ),
+            ],
+        )
+    }
+
+    /// Writes an account, returning the pointer to its `model.Account`.
+    ///
+    /// The storage is keyed by `pedersen(slot.low, slot.high)` with pointers to the `Uint256`
+    /// values, and the valid jump destinations of the code are keyed by offset.
+    fn write_account(
+        &mut self,
+        account: &AccountStateInput,
+        precomputed: &PrecomputedAccount,
+    ) -> Result<Relocatable, HintError> {
+        let code = self.write_bytes(&account.code)?;
+        let code_hash = self.write_word(precomputed.code_hash.into())?;
+        let balance = self.write_word(account.balance)?;
+
+        let mut storage = HashMap::with_capacity(account.storage.len());
+        for (slot, value) in &account.storage {
+            let key = precomputed
+                .storage_keys
+                .get(slot)
+                .ok_or_else(|| custom_error(format!("Missing storage key of slot {slot}")))?;
+            storage.insert((*key).into(), self.write_word(*value)?.into());
+        }
+        let storage = self.write_dict(storage)?;
+        let transient_storage = self.write_dict(HashMap::new())?;
+        let jumpdests = precomputed
+            .valid_jumpdests
+            .iter()
+            .map(|offset| (Felt252::from(*offset).into(), Felt252::ONE.into()))
+            .collect();
+        let jumpdests = self.write_dict(jumpdests)?;
+
+        self.write_struct(
+            ACCOUNT_STRUCT,
+            &[
+                (&["code_len"], Value::Felt(account.code.len().into())),
+                (&["code"], Value::Pointer(code)),
+                (&["code_hash"], Value::Pointer(code_hash)),
+                (&["storage_start", "storage"], Value::Pointer(storage)),
+                (
+                    &["transient_storage_start", "transient_storage"],
+                    Value::Pointer(transient_storage),
+                ),
+                (&["valid_jumpdests_start", "valid_jumpdests"], Value::Pointer(jumpdests)),
+                (&["nonce"], Value::Felt(account.nonce.to::<u64>().into())),
+                (&["balance"], Value::Pointer(balance)),
+                (&["selfdestruct"], Value::Felt(Felt252::ZERO)),
+                (&["created"], Value::Felt(Felt252::ZERO)),
+            ],
+        )
+    }
+
+    /// Writes a struct in a new segment, returning its base.
+    fn write_struct(
+        &mut self,
+        name: &str,
+        members: &[(&[&str], Value)],
+    ) -> Result<Relocatable, HintError> {
+        let cells = self.struct_cells(name, members)?;
+        self.write_cells(&cells)
+    }
+
+    /// Returns the cells of a struct, each member of its layout being given by one of the names
+    /// of the `members`, e.g. its name in the different builds of the program.
+    fn struct_cells(
+        &mut self,
+        name: &str,
+        members: &[(&[&str], Value)],
+    ) -> Result<Vec<MaybeRelocatable>, HintError> {
+        let layout = self.layout(name)?;
+
+        let mut cells = Vec::new();
+        for (index, member) in layout.members.iter().enumerate() {
+            let Some((_, value)) =
+                members.iter().find(|(names, _)| names.contains(&member.name.as_str()))
+            else {
+                return Err(custom_error(format!(
+                    "Missing value of the member {name}.{}",
+                    member.name
+                )));
+            };
+
+            let value = self.member_cells(&member.cairo_type, value)?;
+            let end = member.offset + value.len();
+            if layout.members.get(index + 1).is_some_and(|next| next.offset < end) {
+                return Err(custom_error(format!(
+                    "The value of {name}.{} overflows its member",
+                    member.name
+                )));
+            }
+            if cells.len() < end {
+                cells.resize(end, Felt252::ZERO.into());
+            }
+            cells.splice(member.offset..end, value);
+        }
+        Ok(cells)
+    }
+
+    /// Returns the cells of a value written as a member of the given Cairo type.
+    fn member_cells(
+        &mut self,
+        cairo_type: &str,
+        value: &Value,
+    ) -> Result<Vec<MaybeRelocatable>, HintError> {
+        let word = |value: U256| {
+            let bytes = value.to_be_bytes::<{ U256::BYTES }>();
+            vec![
+                Felt252::from_bytes_be_slice(&bytes[U128_BYTES_SIZE..]).into(),
+                Felt252::from_bytes_be_slice(&bytes[..U128_BYTES_SIZE]).into(),
+            ]
+        };
+        let is_word = cairo_type.ends_with("Uint256");
+        let is_word_pointer = cairo_type.ends_with("Uint256*");
+        let is_option = cairo_type.ends_with("model.Option");
+
+        Ok(match value {
+            Value::Felt(felt) => vec![(*felt).into()],
+            Value::Pointer(pointer) => vec![(*pointer).into()],
+            Value::Word(value) if is_word => word(*value),
+            Value::Word(value) if is_word_pointer => vec![self.write_word(*value)?.into()],
+            Value::Word(value) => {
+                vec![Felt252::from_bytes_be_slice(&value.to_be_bytes::<32>()).into()]
+            }
+            Value::OptionalFelt(value) if is_option => {
+                vec![flag(value.is_some()).into(), value.unwrap_or_default().into()]
+            }
+            Value::OptionalFelt(value) => vec![value.unwrap_or_default().into()],
+            Value::OptionalWord(value) if is_option => {
+                let pointer = self.write_word(value.unwrap_or_default())?;
+                vec![flag(value.is_some()).into(), pointer.into()]
+            }
+            Value::OptionalWord(value) if is_word => word(value.unwrap_or_default()),
+            Value::OptionalWord(_) => {
+                return Err(custom_error(format!("Cannot write a word as a {cairo_type}")))
+            }
+        })
+    }
+
+    /// Writes a `Uint256` in a new segment, returning its base.
+    fn write_word(&mut self, value: U256) -> Result<Relocatable, HintError> {
+        let cells = self.member_cells("Uint256", &Value::Word(value))?;
+        self.write_cells(&cells)
+    }
+
+    /// Writes bytes in a new segment, one felt per byte, returning its base.
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<Relocatable, HintError> {
+        let cells = bytes.iter().map(|byte| Felt252::from(*byte).into()).collect::<Vec<_>>();
+        self.write_cells(&cells)
+    }
+
+    /// Writes cells in a new segment, returning its base.
+    fn write_cells(&mut self, cells: &[MaybeRelocatable]) -> Result<Relocatable, HintError> {
+        let base = self.vm.add_memory_segment();
+        self.vm.load_data(base, cells)?;
+        Ok(base)
+    }
+
+    /// Writes a dict defaulting to zero in a new segment tracked by the dict manager, returning
+    /// its base.
+    fn write_dict(
+        &mut self,
+        data: HashMap<MaybeRelocatable, MaybeRelocatable>,
+    ) -> Result<Relocatable, HintError> {
+        let base =
+            self.dict_manager.new_default_dict(self.vm, &Felt252::ZERO.into(), Some(data))?;
+        base.get_relocatable().ok_or_else(|| custom_error("The dict base is not a pointer".into()))
+    }
+
+    /// Returns the layout of the struct with the given full name.
+    fn layout(&self, name: &str) -> Result<Arc<CachedStruct>, HintError> {
+        self.layouts.resolve(name).map_err(|err| custom_error(err.to_string()))
+    }
+}
+
+/// Returns the offsets of the valid jump destinations of a bytecode, i.e. the `JUMPDEST`s which
+/// are not part of the data of a `PUSH`.
+pub fn valid_jumpdests(code: &[u8]) -> Vec<usize> {
+    let mut jumpdests = Vec::new();
+    let mut offset = 0;
+    while offset < code.len() {
+        let opcode = code[offset];
+        if opcode == JUMPDEST {
+            jumpdests.push(offset);
+        } else if (PUSH1..=PUSH32).contains(&opcode) {
+            offset += usize::from(opcode - PUSH1) + 1;
+        }
+        offset += 1;
+    }
+    jumpdests
+}
+
+/// Returns the felt of a boolean.
+const fn flag(value: bool) -> Felt252 {
+    if value {
+        Felt252::ONE
+    } else {
+        Felt252::ZERO
+    }
+}
+
+/// Returns a [`HintError::CustomHint`] with the given message.
+fn custom_error(message: String) -> HintError {
+    HintError::CustomHint(message.into_boxed_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_jumpdests() {
+        // JUMPDEST, PUSH1 0x5b, JUMPDEST, PUSH2 0x5b5b (truncated)
+        let code = [0x5b, 0x60, 0x5b, 0x5b, 0x61, 0x5b];
+        assert_eq!(valid_jumpdests(&code), vec![0, 3]);
+        assert!(valid_jumpdests(&[]).is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+pub mod access;
+pub mod cache;
+pub mod delegation;
+pub mod history;
+pub mod local;
+pub mod memory;
+pub mod program_input;
+pub mod provider;
+pub mod system;
+
+use alloy_primitives::{Address, Bytes, B256, U256};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, future::Future};
+use thiserror::Error;
+
+/// Represents errors that can occur while fetching the inputs required to run the Kakarot program.
+#[derive(Debug, Error)]
+pub enum InputError {
+    /// Error variant indicating a failure of the underlying transport (RPC endpoint).
+    #[error(transparent)]
+    Transport(#[from] alloy_transport::TransportError),
+
+    /// Error variant indicating a failure of the database of the local node.
+    #[error(transparent)]
+    Provider(#[from] reth_provider::ProviderError),
+
+    /// Error variant indicating that the requested block does not exist on the source.
+    #[error("Block {0} not found on the input source")]
+    BlockNotFound(u64),
+
+    /// Error variant indicating that the source returned a storage proof that does not match the
+    /// requested slots.
+    #[error("Expected {expected} storage proofs for account {address}, got {actual}")]
+    StorageProofMismatch {
+        /// The account for which the proofs were requested.
+        address: Address,
+        /// The number of requested storage slots.
+        expected: usize,
+        /// The number of storage proofs returned by the source.
+        actual: usize,
+    },
+}
+
+/// The state of an account required to execute a block, alongside the requested storage slots and
+/// the Merkle-Patricia proofs binding them to the state root.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct AccountInput {
+    /// The address of the account.
+    pub address: Address,
+    /// The nonce of the account.
+    pub nonce: u64,
+    /// The balance of the account.
+    pub balance: U256,
+    /// The hash of the account bytecode.
+    pub code_hash: B256,
+    /// The bytecode of the account.
+    pub code: Bytes,
+    /// The root of the account storage trie.
+    pub storage_root: B256,
+    /// The requested storage slots and their values.
+    pub storage: BTreeMap<B256, U256>,
+    /// The Merkle-Patricia proof of the account against the state root.
+    pub account_proof: Vec<Bytes>,
+    /// The Merkle-Patricia proofs of the storage slots against the storage root.
+    pub storage_proofs: BTreeMap<B256, Vec<Bytes>>,
+}
+
+/// A source of the pre-state required to build the Kakarot program inputs of a block.
+///
+/// All the methods take a block number and return the state **after** the execution of that block,
+/// which is the pre-state of the next block.
+pub trait InputSource: Send + Sync {
+    /// Fetches an account, its bytecode and the requested storage slots.
+    fn account(
+        &self,
+        block_number: u64,
+        address: Address,
+        slots: Vec<B256>,
+    ) -> impl Future<Output = Result<AccountInput, InputError>> + Send;
+
+    /// Fetches a batch of accounts with their requested storage slots.
+    ///
+    /// The default implementation fetches the accounts sequentially, sources should override it
+    /// when they are able to parallelize or batch the requests.
+    fn accounts(
+        &self,
+        block_number: u64,
+        requests: Vec<(Address, Vec<B256>)>,
+    ) -> impl Future<Output = Result<Vec<AccountInput>, InputError>> + Send {
+        async move {
+            let mut accounts = Vec::with_capacity(requests.len());
+            for (address, slots) in requests {
+                accounts.push(self.account(block_number, address, slots).await?);
+            }
+            Ok(accounts)
+        }
+    }
+
+    /// Fetches the hash of a block.
+    fn block_hash(
+        &self,
+        block_number: u64,
+    ) -> impl Future<Output = Result<B256, InputError>> + Send;
+}
@@ -0,0 +1,679 @@
+//! Typed builder of the `program_input` JSON consumed by the hints of the Kakarot program.
+//!
+//! The hints read the block with `program_input["block"]`, the pre-state with
+//! `program_input["state"]` and the chain id with `program_input["chain_id"]`. The system calls the
+//! program must run before the transactions, if any, are listed in `program_input["system_calls"]`,
+//! and the hashes served to `BLOCKHASH` in `program_input["block_hashes"]`. The layout of each
+//! entry follows the models of the Cairo test suite: block headers use the camelCase keys and hex
+//! quantities of the Ethereum test fixtures, transactions are given in their encoded form.
+
+use super::{
+    delegation::{validate_delegations, DelegationError},
+    history::{BlockHashHistory, HistoryError},
+    system::{SystemCall, SystemCallMode, SystemCallPolicy},
+    AccountInput,
+};
+use crate::telemetry::{self, Stage};
+use alloy_consensus::Header;
+use alloy_primitives::{keccak256, Address, Bloom, Bytes, B256, B64, U256, U64};
+use alloy_rlp::Encodable;
+use reth_primitives::{SealedBlockWithSenders, Signature, TransactionSigned};
+use reth_revm::{
+    db::{AccountState, CacheDB, DbAccount, EmptyDB},
+    primitives::{AccountInfo, Bytecode},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// The number of felts of an encoded signature: `[r.low, r.high, s.low, s.high, v]`.
+pub const SIGNATURE_LEN: usize = 5;
+
+/// Errors that can occur while building or validating a [`ProgramInput`].
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ProgramInputError {
+    /// Error variant indicating that no block was given to the builder.
+    #[error("Missing block")]
+    MissingBlock,
+
+    /// Error variant indicating that the number of senders does not match the number of
+    /// transactions.
+    #[error("Expected {transactions} transaction senders, got {senders}")]
+    SenderCountMismatch {
+        /// The number of transactions of the block.
+        transactions: usize,
+        /// The number of senders of the block.
+        senders: usize,
+    },
+
+    /// Error variant indicating that the pre-state lacks the account of a transaction sender.
+    #[error("Missing pre-state account of the sender {0}")]
+    MissingSenderAccount(Address),
+
+    /// Error variant indicating that the declared length of an encoded transaction field does not
+    /// match its content.
+    #[error("Transaction {index}: declared {field} length {declared}, actual {actual}")]
+    LengthMismatch {
+        /// The index of the transaction in the block.
+        index: usize,
+        /// The mismatching field.
+        field: &'static str,
+        /// The declared length.
+        declared: usize,
+        /// The actual length.
+        actual: usize,
+    },
+
+    /// Error variant indicating an invalid block hash history.
+    #[error(transparent)]
+    History(#[from] HistoryError),
+
+    /// Error variant indicating a delegated account whose delegate is missing from the pre-state.
+    #[error(transparent)]
+    Delegation(#[from] DelegationError),
+}
+
+/// The `program_input` of the Kakarot program.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgramInput {
+    /// The block to execute.
+    pub block: BlockInput,
+    /// The pre-state accounts touched by the block.
+    pub state: BTreeMap<Address, AccountStateInput>,
+    /// The chain id.
+    pub chain_id: u64,
+    /// The system calls modeled by the program, run before the transactions.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub system_calls: Vec<SystemCall>,
+    /// The hashes of the ancestors of the block served to `BLOCKHASH`, by block number.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub block_hashes: BTreeMap<u64, B256>,
+}
+
+/// The block entry of the [`ProgramInput`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockInput {
+    /// The header of the block.
+    pub block_header: HeaderInput,
+    /// The encoded transactions of the block.
+    pub transactions: Vec<TransactionInput>,
+}
+
+/// A block header, with the keys of the Ethereum test fixtures.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeaderInput {
+    /// The hash of the block.
+    pub hash: B256,
+    /// The hash of the parent block.
+    pub parent_hash: B256,
+    /// The hash of the ommers list.
+    pub uncle_hash: B256,
+    /// The beneficiary of the block.
+    pub coinbase: Address,
+    /// The state root after the execution of the block.
+    pub state_root: B256,
+    /// The root of the transactions trie.
+    pub transactions_trie: B256,
+    /// The root of the receipts trie.
+    pub receipt_trie: B256,
+    /// The root of the withdrawals trie.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub withdrawals_root: Option<B256>,
+    /// The logs bloom of the block.
+    pub bloom: Bloom,
+    /// The difficulty of the block.
+    pub difficulty: U256,
+    /// The number of the block.
+    pub number: U64,
+    /// The gas limit of the block.
+    pub gas_limit: U64,
+    /// The gas used by the block.
+    pub gas_used: U64,
+    /// The timestamp of the block.
+    pub timestamp: U64,
+    /// The mix hash, or previous RANDAO, of the block.
+    pub mix_hash: B256,
+    /// The nonce of the block.
+    pub nonce: B64,
+    /// The base fee per gas of the block.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_fee_per_gas: Option<U64>,
+    /// The blob gas used by the block.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blob_gas_used: Option<U64>,
+    /// The excess blob gas of the block.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub excess_blob_gas: Option<U64>,
+    /// The root of the parent beacon block.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_beacon_block_root: Option<B256>,
+    /// The root of the requests trie.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_root: Option<B256>,
+    /// The extra data of the block.
+    pub extra_data: Bytes,
+}
+
+impl From<&Header> for HeaderInput {
+    fn from(header: &Header) -> Self {
+        Self {
+            hash: header.hash_slow(),
+            parent_hash: header.parent_hash,
+            uncle_hash: header.ommers_hash,
+            coinbase: header.beneficiary,
+            state_root: header.state_root,
+            transactions_trie: header.transactions_root,
+            receipt_trie: header.receipts_root,
+            withdrawals_root: header.withdrawals_root,
+            bloom: header.logs_bloom,
+            difficulty: header.difficulty,
+            number: U64::from(header.number),
+            gas_limit: U64::from(header.gas_limit),
+            gas_used: U64::from(header.gas_used),
+            timestamp: U64::from(header.timestamp),
+            mix_hash: header.mix_hash,
+            nonce: header.nonce,
+            base_fee_per_gas: header.base_fee_per_gas.map(U64::from),
+            blob_gas_used: header.blob_gas_used.map(U64::from),
+            excess_blob_gas: header.excess_blob_gas.map(U64::from),
+            parent_beacon_block_root: header.parent_beacon_block_root,
+            requests_root: header.requests_root,
+            extra_data: header.extra_data.clone(),
+        }
+    }
+}
+
+/// An encoded transaction, as the `model.TransactionEncoded` struct.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionInput {
+    /// The length of the unsigned RLP encoding of the transaction.
+    pub rlp_len: usize,
+    /// The unsigned RLP encoding of the transaction.
+    pub rlp: Bytes,
+    /// The number of felts of the signature.
+    pub signature_len: usize,
+    /// The signature of the transaction, as `[r.low, r.high, s.low, s.high, v]`.
+    pub signature: Vec<u128>,
+    /// The sender of the transaction.
+    pub sender: Address,
+}
+
+impl TransactionInput {
+    /// Creates a new [`TransactionInput`] from a signed transaction and its sender.
+    pub fn new(transaction: &TransactionSigned, sender: Address) -> Self {
+        let mut rlp = Vec::new();
+        transaction.transaction.encode(&mut rlp);
+        let signature = encode_signature(&transaction.signature);
+
+        Self {
+            rlp_len: rlp.len(),
+            rlp: rlp.into(),
+            signature_len: signature.len(),
+            signature,
+            sender,
+        }
+    }
+}
+
+/// A pre-state account, with the keys of the Ethereum test fixtures.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct AccountStateInput {
+    /// The balance of the account.
+    pub balance: U256,
+    /// The bytecode of the account.
+    pub code: Bytes,
+    /// The nonce of the account.
+    pub nonce: U64,
+    /// The storage slots of the account.
+    pub storage: BTreeMap<U256, U256>,
+}
+
+impl AccountStateInput {
+    /// Returns whether the account is empty, i.e. missing from the pre-state.
+    pub fn is_empty(&self) -> bool {
+        self.balance.is_zero() &&
+            self.nonce.is_zero() &&
+            self.code.is_empty() &&
+            self.storage.is_empty()
+    }
+}
+
+impl From<&AccountInput> for AccountStateInput {
+    fn from(account: &AccountInput) -> Self {
+        Self {
+            balance: account.balance,
+            code: account.code.clone(),
+            nonce: U64::from(account.nonce),
+            storage: account
+                .storage
+                .iter()
+                .map(|(slot, value)| (U256::from_be_bytes(slot.0), *value))
+                .collect(),
+        }
+    }
+}
+
+impl ProgramInput {
+    /// Returns a new [`ProgramInputBuilder`].
+    pub fn builder(chain_id: u64) -> ProgramInputBuilder {
+        ProgramInputBuilder::new(chain_id)
+    }
+
+    /// Validates the consistency of the input.
+    ///
+    /// The declared lengths of the encoded transactions must match their content, and the
+    /// pre-state must hold the account of every transaction sender and the delegate of every
+    /// EIP-7702 delegated account.
+    pub fn validate(&self) -> Result<(), ProgramInputError> {
+        for (index, transaction) in self.block.transactions.iter().enumerate() {
+            for (field, declared, actual) in [
+                ("rlp", transaction.rlp_len, transaction.rlp.len()),
+                ("signature", transaction.signature_len, transaction.signature.len()),
+                ("signature", SIGNATURE_LEN, transaction.signature.len()),
+            ] {
+                if declared != actual {
+                    return Err(ProgramInputError::LengthMismatch {
+                        index,
+                        field,
+                        declared,
+                        actual,
+                    });
+                }
+            }
+
+            if !self.state.contains_key(&transaction.sender) {
+                return Err(ProgramInputError::MissingSenderAccount(transaction.sender));
+            }
+        }
+        validate_delegations(&self.state)?;
+        Ok(())
+    }
+
+    /// Returns the block hash history served to `BLOCKHASH`, against which the reads of the
+    /// execution of the block are validated.
+    pub fn block_hash_history(&self) -> BlockHashHistory {
+        BlockHashHistory {
+            block_number: self.block.block_header.number.to(),
+            hashes: self.block_hashes.clone(),
+        }
+    }
+
+    /// Returns the JSON value of the input, as consumed by the hints.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).expect("program input is always serializable")
+    }
+
+    /// Returns an in-memory revm database serving the witness of the input, to execute the block
+    /// natively on the same pre-state as the program: the non-empty accounts of the pre-state and
+    /// the block hashes, any other account reading as missing and any other slot as zero.
+    pub fn witness_db(&self) -> CacheDB<EmptyDB> {
+        let mut db = CacheDB::new(EmptyDB::default());
+        for (address, account) in self.state.iter().filter(|(_, account)| !account.is_empty()) {
+            let mut info = AccountInfo {
+                balance: account.balance,
+                nonce: account.nonce.to(),
+                code_hash: keccak256(&account.code),
+                code: Some(Bytecode::new_raw(account.code.clone())),
+            };
+            db.insert_contract(&mut info);
+            let storage = account.storage.iter().map(|(slot, value)| (*slot, *value)).collect();
+            db.accounts
+                .insert(*address, DbAccount { info, account_state: AccountState::None, storage });
+        }
+        db.block_hashes
+            .extend(self.block_hashes.iter().map(|(number, hash)| (U256::from(*number), *hash)));
+        db
+    }
+}
+
+/// A builder of [`ProgramInput`]s from reth models.
+#[derive(Debug, Clone, Default)]
+pub struct ProgramInputBuilder {
+    /// The chain id.
+    chain_id: u64,
+    /// The block to execute.
+    block: Option<BlockInput>,
+    /// The header of the block to execute.
+    header: Option<Header>,
+    /// The pre-state accounts.
+    state: BTreeMap<Address, AccountStateInput>,
+    /// The handling of the system calls of the block.
+    system_calls: SystemCallPolicy,
+    /// The block hash history of the block.
+    block_hashes: Option<BlockHashHistory>,
+    /// Whether the block hash history only holds the accessed hashes.
+    accessed_block_hashes: bool,
+}
+
+impl ProgramInputBuilder {
+    /// Creates a new [`ProgramInputBuilder`] for the given chain.
+    pub fn new(chain_id: u64) -> Self {
+        Self { chain_id, ..Default::default() }
+    }
+
+    /// Sets the block to execute.
+    pub fn block(mut self, block: &SealedBlockWithSenders) -> Result<Self, ProgramInputError> {
+        let transactions = &block.body.transactions;
+        if transactions.len() != block.senders.len() {
+            return Err(ProgramInputError::SenderCountMismatch {
+                transactions: transactions.len(),
+                senders: block.senders.len(),
+            });
+        }
+
+        let number = block.header.number;
+        let _span = telemetry::stage_span(Stage::Witness, number).entered();
+        self.block = Some(BlockInput {
+            block_header: HeaderInput::from(block.header.header()),
+            transactions: transactions
+                .iter()
+                .zip(&block.senders)
+                .enumerate()
+                .map(|(index, (transaction, sender))| {
+                    let _span =
+                        telemetry::transaction_span(number, index, transaction.hash()).entered();
+                    TransactionInput::new(transaction, *sender)
+                })
+                .collect(),
+        });
+        self.header = Some(block.header.header().clone());
+        Ok(self)
+    }
+
+    /// Adds a pre-state account.
+    pub fn account(mut self, address: Address, account: AccountStateInput) -> Self {
+        self.state.insert(address, account);
+        self
+    }
+
+    /// Adds the pre-state accounts fetched from an [`InputSource`](super::InputSource).
+    pub fn accounts<'a>(mut self, accounts: impl IntoIterator<Item = &'a AccountInput>) -> Self {
+        self.state.extend(accounts.into_iter().map(|account| (account.address, account.into())));
+        self
+    }
+
+    /// Sets the handling of the system calls of the block, none being handled by default.
+    pub fn system_calls(mut self, policy: SystemCallPolicy) -> Self {
+        self.system_calls = policy;
+        self
+    }
+
+    /// Sets the block hash history served to `BLOCKHASH`, none being served by default.
+    pub fn block_hashes(mut self, history: BlockHashHistory) -> Self {
+        self.block_hashes = Some(history);
+        self.accessed_block_hashes = false;
+        self
+    }
+
+    /// Sets the block hash history served to `BLOCKHASH` to the hashes accessed by the block,
+    /// fetched with [`BlockHashHistory::fetch_accessed`], which do not cover its whole window.
+    pub fn accessed_block_hashes(mut self, history: BlockHashHistory) -> Self {
+        self.block_hashes = Some(history);
+        self.accessed_block_hashes = true;
+        self
+    }
+
+    /// Builds and validates the [`ProgramInput`].
+    ///
+    /// The Rust-side system calls are applied to the pre-state, and the modeled ones are listed
+    /// for the program. The block hash history must cover the window of the block, or only its
+    /// accessed hashes, anchored by its parent hash.
+    pub fn build(mut self) -> Result<ProgramInput, ProgramInputError> {
+        let mut system_calls = Vec::new();
+        if let Some(header) = &self.header {
+            self.system_calls.inject(header, &mut self.state);
+            system_calls = self.system_calls.calls(header, SystemCallMode::Cairo);
+            match &self.block_hashes {
+                Some(history) if self.accessed_block_hashes => history.validate_accessed(header)?,
+                Some(history) => history.validate(header)?,
+                None => {}
+            }
+        }
+
+        let input = ProgramInput {
+            block: self.block.ok_or(ProgramInputError::MissingBlock)?,
+            state: self.state,
+            chain_id: self.chain_id,
+            system_calls,
+            block_hashes: self.block_hashes.map(|history| history.hashes).unwrap_or_default(),
+        };
+        input.validate()?;
+        Ok(input)
+    }
+}
+
+/// Encodes a signature as `[r.low, r.high, s.low, s.high, v]`.
+fn encode_signature(signature: &Signature) -> Vec<u128> {
+    let (r_low, r_high) = split_u256(signature.r());
+    let (s_low, s_high) = split_u256(signature.s());
+    vec![r_low, r_high, s_low, s_high, u128::from(signature.v().to_u64())]
+}
+
+/// Splits a [`U256`] into its low and high 128 bits.
+fn split_u256(value: U256) -> (u128, u128) {
+    let bytes = value.to_be_bytes::<32>();
+    let high = u128::from_be_bytes(bytes[..16].try_into().expect("16 bytes"));
+    let low = u128::from_be_bytes(bytes[16..].try_into().expect("16 bytes"));
+    (low, high)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::delegation::designator;
+    use alloy_primitives::{address, TxKind};
+    use reth_primitives::{BlockBody, SealedBlock, SealedHeader, Transaction, TxLegacy};
+
+    const SENDER: Address = address!("a94f5374fce5edbc8e2a8697c15331677e6ebf0b");
+
+    fn block() -> SealedBlockWithSenders {
+        let transaction = TransactionSigned::from_transaction_and_signature(
+            Transaction::Legacy(TxLegacy {
+                chain_id: Some(1),
+                nonce: 0,
+                gas_price: 10,
+                gas_limit: 1_000_000,
+                to: TxKind::Call(address!("000000000000000000000000000000000000c0de")),
+                value: U256::ZERO,
+                input: Bytes::from_static(&[0x01, 0x02]),
+            }),
+            Signature::from_rs_and_parity(U256::from(1), U256::MAX, 37u64).unwrap(),
+        );
+
+        SealedBlockWithSenders {
+            block: SealedBlock {
+                body: BlockBody { transactions: vec![transaction], ..Default::default() },
+                ..Default::default()
+            },
+            senders: vec![SENDER],
+        }
+    }
+
+    #[test]
+    fn test_build_program_input() {
+        let input = ProgramInput::builder(1)
+            .block(&block())
+            .unwrap()
+            .account(SENDER, AccountStateInput { balance: U256::from(1_000), ..Default::default() })
+            .build()
+            .unwrap();
+
+        let transaction = &input.block.transactions[0];
+        assert_eq!(transaction.rlp_len, transaction.rlp.len());
+        assert_eq!(transaction.signature, vec![1, 0, u128::MAX, u128::MAX, 37]);
+
+        let json = input.to_json();
+        assert_eq!(json["chain_id"], 1);
+        assert_eq!(json["block"]["blockHeader"]["number"], "0x0");
+        assert_eq!(json["block"]["blockHeader"]["uncleHash"], format!("{}", B256::ZERO));
+        assert!(json["block"]["blockHeader"].get("baseFeePerGas").is_none());
+        assert_eq!(json["block"]["transactions"][0]["sender"], format!("{SENDER:#x}"));
+        assert_eq!(json["state"][format!("{SENDER:#x}")]["balance"], "0x3e8");
+
+        // The JSON round-trips into the typed input.
+        assert_eq!(serde_json::from_value::<ProgramInput>(json).unwrap(), input);
+    }
+
+    #[test]
+    fn test_witness_db() {
+        use reth_revm::Database as _;
+
+        let contract = address!("000000000000000000000000000000000000c0de");
+        let input = ProgramInput::builder(1)
+            .block(&block())
+            .unwrap()
+            .account(SENDER, AccountStateInput { balance: U256::from(1_000), ..Default::default() })
+            .account(
+                contract,
+                AccountStateInput {
+                    code: Bytes::from_static(&[0x00]),
+                    nonce: U64::from(1),
+                    storage: BTreeMap::from([(U256::from(1), U256::from(2))]),
+                    ..Default::default()
+                },
+            )
+            .account(Address::ZERO, AccountStateInput::default())
+            .build()
+            .unwrap();
+
+        let mut db = input.witness_db();
+        assert_eq!(db.basic(SENDER).unwrap().unwrap().balance, U256::from(1_000));
+        let info = db.basic(contract).unwrap().unwrap();
+        assert_eq!(info.code_hash, keccak256([0x00]));
+        assert_eq!(
+            db.code_by_hash(info.code_hash).unwrap().original_bytes(),
+            Bytes::from_static(&[0x00])
+        );
+        assert_eq!(db.storage(contract, U256::from(1)).unwrap(), U256::from(2));
+        assert_eq!(db.storage(contract, U256::from(3)).unwrap(), U256::ZERO);
+        // The empty accounts of the witness are missing from the pre-state.
+        assert!(db.basic(Address::ZERO).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_build_program_input_system_calls() {
+        let mut block = block();
+        let header = Header { number: 1, ..Default::default() };
+        block.block.header = SealedHeader::new(header, B256::ZERO);
+        let policy = SystemCallPolicy {
+            beacon_root: SystemCallMode::Rust,
+            block_history: SystemCallMode::Cairo,
+        };
+
+        let input = ProgramInput::builder(1)
+            .block(&block)
+            .unwrap()
+            .account(SENDER, AccountStateInput::default())
+            .system_calls(policy)
+            .build()
+            .unwrap();
+
+        // The block has no parent beacon block root, only the history update applies.
+        assert_eq!(input.system_calls, vec![SystemCall::BlockHistory]);
+        assert_eq!(input.to_json()["system_calls"][0], "block_history");
+    }
+
+    #[test]
+    fn test_build_program_input_block_hashes() {
+        let mut block = block();
+        let header = Header { number: 2, parent_hash: B256::repeat_byte(2), ..Default::default() };
+        block.block.header = SealedHeader::new(header, B256::ZERO);
+        let hashes = BTreeMap::from([(0, B256::repeat_byte(1)), (1, B256::repeat_byte(2))]);
+
+        let input = ProgramInput::builder(1)
+            .block(&block)
+            .unwrap()
+            .account(SENDER, AccountStateInput::default())
+            .block_hashes(BlockHashHistory { block_number: 2, hashes: hashes.clone() })
+            .build()
+            .unwrap();
+        assert_eq!(input.block_hashes, hashes);
+        assert_eq!(input.to_json()["block_hashes"]["1"], format!("{}", B256::repeat_byte(2)));
+
+        // The history must be anchored by the parent hash of the block.
+        let forked = BTreeMap::from([(0, B256::repeat_byte(1)), (1, B256::repeat_byte(3))]);
+        let err = ProgramInput::builder(1)
+            .block(&block)
+            .unwrap()
+            .account(SENDER, AccountStateInput::default())
+            .block_hashes(BlockHashHistory { block_number: 2, hashes: forked })
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, ProgramInputError::History(HistoryError::ParentMismatch { .. })));
+
+        // The accessed hashes do not cover the whole window.
+        let accessed = BTreeMap::from([(1, B256::repeat_byte(2))]);
+        let input = ProgramInput::builder(1)
+            .block(&block)
+            .unwrap()
+            .account(SENDER, AccountStateInput::default())
+            .accessed_block_hashes(BlockHashHistory { block_number: 2, hashes: accessed.clone() })
+            .build()
+            .unwrap();
+        assert_eq!(input.block_hashes, accessed);
+    }
+
+    #[test]
+    fn test_build_program_input_errors() {
+        assert_eq!(ProgramInput::builder(1).build().unwrap_err(), ProgramInputError::MissingBlock);
+        assert_eq!(
+            ProgramInput::builder(1).block(&block()).unwrap().build().unwrap_err(),
+            ProgramInputError::MissingSenderAccount(SENDER)
+        );
+
+        let mut block = block();
+        block.senders.clear();
+        assert_eq!(
+            ProgramInput::builder(1).block(&block).unwrap_err(),
+            ProgramInputError::SenderCountMismatch { transactions: 1, senders: 0 }
+        );
+
+        // The delegate of a delegated account must be in the pre-state.
+        let wallet = address!("000000000000000000000000000000000000c0de");
+        let delegated = AccountStateInput { code: designator(wallet), ..Default::default() };
+        assert_eq!(
+            ProgramInput::builder(1)
+                .block(&block())
+                .unwrap()
+                .account(SENDER, delegated)
+                .build()
+                .unwrap_err(),
+            ProgramInputError::Delegation(DelegationError::MissingDelegate {
+                authority: SENDER,
+                delegate: wallet
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_lengths() {
+        let mut input = ProgramInput::builder(1)
+            .block(&block())
+            .unwrap()
+            .account(SENDER, AccountStateInput::default())
+            .build()
+            .unwrap();
+
+        input.block.transactions[0].rlp_len += 1;
+        assert!(matches!(
+            input.validate(),
+            Err(ProgramInputError::LengthMismatch { index: 0, field: "rlp", .. })
+        ));
+    }
+
+    #[test]
+    fn test_account_state_from_account_input() {
+        let account = AccountInput {
+            address: SENDER,
+            nonce: 2,
+            storage: BTreeMap::from([(B256::with_last_byte(1), U256::from(42))]),
+            ..Default::default()
+        };
+
+        let state = AccountStateInput::from(&account);
+        assert_eq!(state.nonce, U64::from(2));
+        assert_eq!(state.storage, BTreeMap::from([(U256::from(1), U256::from(42))]));
+    }
+}
@@ -0,0 +1,599 @@
+use super::{AccountInput, InputError, InputSource};
+use alloy_primitives::{Address, B256};
+use alloy_provider::Provider;
+use alloy_rpc_types_eth::{BlockId, BlockNumberOrTag};
+use alloy_transport::{RpcError, Transport, TransportError, TransportResult};
+use futures::{stream, StreamExt, TryStreamExt};
+use reth_tracing::tracing::debug;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    future::{Future, IntoFuture},
+    marker::PhantomData,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::Semaphore;
+
+/// The JSON-RPC error code returned by nodes which do not support the requested method.
+const METHOD_NOT_FOUND_CODE: i64 = -32601;
+
+/// The JSON-RPC error code commonly returned by providers when the rate limit is exceeded.
+const LIMIT_EXCEEDED_CODE: i64 = -32005;
+
+/// The retry policy applied to each request sent to the provider.
+///
+/// Requests failing because of the transport (connection reset, timeout, rate limiting) are retried
+/// with an exponential backoff, all other errors are returned immediately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryConfig {
+    /// The maximum number of retries for a single request.
+    pub max_retries: u32,
+    /// The backoff applied before the first retry.
+    pub initial_backoff: Duration,
+    /// The upper bound of the backoff between two retries.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Returns the backoff to apply before the retry number `attempt` (starting at 0).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff.saturating_mul(2u32.saturating_pow(attempt)).min(self.max_backoff)
+    }
+}
+
+/// The configuration of a [`ProviderInputSource`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProviderInputConfig {
+    /// The maximum number of storage slots requested in a single `eth_getProof` call.
+    ///
+    /// Public endpoints usually cap the number of keys per call, larger slot sets are split into
+    /// several calls.
+    pub max_slots_per_request: usize,
+    /// The maximum number of requests in flight at the same time, shared by all the requests of
+    /// the source, e.g. the storage batches of the accounts fetched concurrently.
+    pub max_concurrent_requests: usize,
+    /// The retry policy of each request.
+    pub retry: RetryConfig,
+}
+
+impl Default for ProviderInputConfig {
+    fn default() -> Self {
+        Self {
+            max_slots_per_request: 64,
+            max_concurrent_requests: 16,
+            retry: RetryConfig::default(),
+        }
+    }
+}
+
+/// The result of a `debug_accountRange` call.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountRange {
+    /// The state root of the dumped state.
+    pub root: B256,
+    /// The dumped accounts.
+    ///
+    /// Accounts are keyed by address when the node knows the preimage of the hashed key, by the
+    /// hashed key otherwise.
+    pub accounts: BTreeMap<String, serde_json::Value>,
+    /// The encoded key to resume the iteration from, if the range is incomplete.
+    #[serde(default)]
+    pub next: Option<String>,
+}
+
+impl AccountRange {
+    /// Returns the addresses of the dumped accounts, skipping the accounts keyed by hash.
+    pub fn addresses(&self) -> Vec<Address> {
+        self.accounts.keys().filter_map(|key| Address::from_str(key).ok()).collect()
+    }
+}
+
+/// An [`InputSource`] backed by any alloy [`Provider`].
+///
+/// This allows building the program inputs against a third-party RPC endpoint when no local reth
+/// datadir is available:
+/// - Accounts and storage are fetched with `eth_getProof`, storage slots being batched per account.
+/// - Bytecodes are fetched with `eth_getCode`.
+/// - The list of accounts of a state can be dumped with `debug_accountRange` on nodes exposing it.
+#[derive(Debug, Clone)]
+pub struct ProviderInputSource<P, T> {
+    /// The underlying provider.
+    provider: P,
+    /// The configuration of the source.
+    config: ProviderInputConfig,
+    /// The permits of the requests in flight, one being acquired by each attempt of a request.
+    permits: Arc<Semaphore>,
+    /// Marker for the transport of the provider.
+    _transport: PhantomData<fn() -> T>,
+}
+
+impl<P, T> ProviderInputSource<P, T>
+where
+    P: Provider<T>,
+    T: Transport + Clone,
+{
+    /// Creates a new [`ProviderInputSource`] with the default configuration.
+    pub fn new(provider: P) -> Self {
+        Self::with_config(provider, ProviderInputConfig::default())
+    }
+
+    /// Creates a new [`ProviderInputSource`] with the given configuration.
+    pub fn with_config(provider: P, config: ProviderInputConfig) -> Self {
+        let permits = Arc::new(Semaphore::new(config.max_concurrent_requests.max(1)));
+        Self { provider, config, permits, _transport: PhantomData }
+    }
+
+    /// Returns the underlying provider.
+    pub const fn provider(&self) -> &P {
+        &self.provider
+    }
+
+    /// Dumps a range of accounts of the state after the given block, starting at `start`.
+    ///
+    /// Returns `None` if the node does not expose the `debug_accountRange` method.
+    pub async fn account_range(
+        &self,
+        block_number: u64,
+        start: B256,
+        max_results: u64,
+    ) -> Result<Option<AccountRange>, InputError> {
+        // Parameters: block, start key, max results, no code, no storage, incompletes.
+        let params =
+            (BlockNumberOrTag::Number(block_number), start, max_results, true, true, false);
+
+        match self
+            .with_retries(|| {
+                self.provider
+                    .raw_request::<_, AccountRange>("debug_accountRange".into(), params.clone())
+            })
+            .await
+        {
+            Ok(range) => Ok(Some(range)),
+            // The node does not support the method, the caller has to fall back on another way of
+            // discovering the accounts.
+            Err(err) if err.as_error_resp().is_some_and(|e| e.code == METHOD_NOT_FOUND_CODE) => {
+                Ok(None)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Sends a request, retrying it according to the configured [`RetryConfig`].
+    ///
+    /// Each attempt waits for a permit of the source, so that at most
+    /// [`ProviderInputConfig::max_concurrent_requests`] requests are in flight, the backoffs
+    /// between the attempts not holding any.
+    async fn with_retries<F, Fut, R>(&self, mut request: F) -> TransportResult<R>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = TransportResult<R>>,
+    {
+        let mut attempt = 0;
+        loop {
+            let response = {
+                let _permit = self.permits.acquire().await.expect("the permits are never closed");
+                request().await
+            };
+            match response {
+                Ok(response) => return Ok(response),
+                Err(err) if attempt < self.config.retry.max_retries && is_retryable(&err) => {
+                    let backoff = self.config.retry.backoff(attempt);
+                    debug!(%err, attempt, ?backoff, "Retrying input request");
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl<P, T> InputSource for ProviderInputSource<P, T>
+where
+    P: Provider<T>,
+    T: Transport + Clone,
+{
+    async fn account(
+        &self,
+        block_number: u64,
+        address: Address,
+        slots: Vec<B256>,
+    ) -> Result<AccountInput, InputError> {
+        let block_id = BlockId::number(block_number);
+
+        // Split the storage slots in batches, always sending at least one request to retrieve the
+        // account proof.
+        let batches = if slots.is_empty() {
+            vec![vec![]]
+        } else {
+            slots.chunks(self.config.max_slots_per_request.max(1)).map(<[_]>::to_vec).collect()
+        };
+
+        // Fetch the proofs of all the batches concurrently, preserving their order. The requests in
+        // flight are bounded by the permits of the source, shared with the other accounts.
+        let proofs = stream::iter(batches)
+            .map(|batch| async move {
+                let proof = self
+                    .with_retries(|| {
+                        self.provider
+                            .get_proof(address, batch.clone())
+                            .block_id(block_id)
+                            .into_future()
+                    })
+                    .await?;
+                Ok::<_, InputError>((batch, proof))
+            })
+            .buffered(self.config.max_concurrent_requests.max(1))
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        // Fetch the bytecode of the account.
+        let code = self
+            .with_retries(|| self.provider.get_code_at(address).block_id(block_id).into_future())
+            .await?;
+
+        let mut input = AccountInput { address, code, ..Default::default() };
+        for (batch, proof) in proofs {
+            // Each storage proof is returned in the order of the requested slots.
+            if proof.storage_proof.len() != batch.len() {
+                return Err(InputError::StorageProofMismatch {
+                    address,
+                    expected: batch.len(),
+                    actual: proof.storage_proof.len(),
+                });
+            }
+
+            input.nonce = proof.nonce;
+            input.balance = proof.balance;
+            input.code_hash = proof.code_hash;
+            input.storage_root = proof.storage_hash;
+            input.account_proof = proof.account_proof;
+
+            for (slot, storage_proof) in batch.into_iter().zip(proof.storage_proof) {
+                input.storage.insert(slot, storage_proof.value);
+                input.storage_proofs.insert(slot, storage_proof.proof);
+            }
+        }
+
+        Ok(input)
+    }
+
+    async fn accounts(
+        &self,
+        block_number: u64,
+        requests: Vec<(Address, Vec<B256>)>,
+    ) -> Result<Vec<AccountInput>, InputError> {
+        stream::iter(requests)
+            .map(|(address, slots)| self.account(block_number, address, slots))
+            .buffered(self.config.max_concurrent_requests.max(1))
+            .try_collect()
+            .await
+    }
+
+    async fn block_hash(&self, block_number: u64) -> Result<B256, InputError> {
+        let block = self
+            .with_retries(|| {
+                self.provider.get_block_by_number(BlockNumberOrTag::Number(block_number), false)
+            })
+            .await?
+            .ok_or(InputError::BlockNotFound(block_number))?;
+
+        Ok(block.header.hash)
+    }
+}
+
+/// Returns whether a failed request should be retried.
+///
+/// Only transport failures and rate limiting errors are considered transient.
+fn is_retryable(err: &TransportError) -> bool {
+    matches!(err, RpcError::Transport(_)) ||
+        err.as_error_resp().is_some_and(|e| e.code == LIMIT_EXCEEDED_CODE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_json_rpc::{
+        ErrorPayload, RequestPacket, Response, ResponsePacket, ResponsePayload, SerializedRequest,
+    };
+    use alloy_provider::RootProvider;
+    use alloy_rpc_client::RpcClient;
+    use alloy_transport::{TransportErrorKind, TransportFut};
+    use serde_json::{json, value::RawValue, Value};
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        },
+        task::{Context, Poll},
+    };
+
+    const ACCOUNT: Address = Address::repeat_byte(0xc0);
+
+    /// The handler of the requests of a [`MockTransport`], returning the result of a request from
+    /// its method and parameters.
+    type Handler = dyn Fn(&str, Value) -> TransportResult<Value> + Send + Sync;
+
+    /// A transport answering the requests with a handler, and recording them.
+    #[derive(Clone)]
+    struct MockTransport {
+        /// The handler of the requests.
+        handler: Arc<Handler>,
+        /// The methods and parameters of the received requests.
+        requests: Arc<Mutex<Vec<(String, Value)>>>,
+        /// The number of requests in flight, and the maximum reached.
+        in_flight: Arc<(AtomicUsize, AtomicUsize)>,
+    }
+
+    impl MockTransport {
+        fn new(
+            handler: impl Fn(&str, Value) -> TransportResult<Value> + Send + Sync + 'static,
+        ) -> Self {
+            Self {
+                handler: Arc::new(handler),
+                requests: Default::default(),
+                in_flight: Default::default(),
+            }
+        }
+
+        /// Returns a source over the transport.
+        fn source(
+            &self,
+            config: ProviderInputConfig,
+        ) -> ProviderInputSource<RootProvider<Self>, Self> {
+            ProviderInputSource::with_config(
+                RootProvider::new(RpcClient::new(self.clone(), true)),
+                config,
+            )
+        }
+
+        /// Returns the parameters of the received requests of a method.
+        fn params(&self, method: &str) -> Vec<Value> {
+            let requests = self.requests.lock().unwrap();
+            requests.iter().filter(|(m, _)| m == method).map(|(_, params)| params.clone()).collect()
+        }
+
+        fn respond(&self, request: &SerializedRequest) -> TransportResult<Response> {
+            let params = request
+                .params()
+                .map(|params| serde_json::from_str(params.get()).unwrap())
+                .unwrap_or_default();
+            self.requests.lock().unwrap().push((request.method().to_string(), params.clone()));
+            let payload = match (self.handler)(request.method(), params) {
+                Ok(result) => {
+                    ResponsePayload::Success(RawValue::from_string(result.to_string()).unwrap())
+                }
+                Err(RpcError::ErrorResp(err)) => ResponsePayload::Failure(err),
+                Err(err) => return Err(err),
+            };
+            Ok(Response { id: request.id().clone(), payload })
+        }
+    }
+
+    impl tower::Service<RequestPacket> for MockTransport {
+        type Response = ResponsePacket;
+        type Error = TransportError;
+        type Future = TransportFut<'static>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: RequestPacket) -> Self::Future {
+            let transport = self.clone();
+            Box::pin(async move {
+                let (in_flight, max_in_flight) = &*transport.in_flight;
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(current, Ordering::SeqCst);
+                // Keep the request in flight long enough for the concurrent ones to overlap.
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+
+                match request {
+                    RequestPacket::Single(request) => {
+                        transport.respond(&request).map(ResponsePacket::Single)
+                    }
+                    RequestPacket::Batch(requests) => requests
+                        .iter()
+                        .map(|request| transport.respond(request))
+                        .collect::<Result<_, _>>()
+                        .map(ResponsePacket::Batch),
+                }
+            })
+        }
+    }
+
+    /// Answers `eth_getProof` with a proof of each requested slot, whose value is the slot, but
+    /// the last `missing` ones, and `eth_getCode` with a `STOP`.
+    fn node(missing: usize) -> impl Fn(&str, Value) -> TransportResult<Value> + Send + Sync {
+        move |method, params| match method {
+            "eth_getProof" => {
+                let slots: Vec<B256> = serde_json::from_value(params[1].clone()).unwrap();
+                let proofs: Vec<_> = slots
+                    .iter()
+                    .take(slots.len().saturating_sub(missing))
+                    .map(|slot| json!({ "key": slot, "value": slot, "proof": ["0x01"] }))
+                    .collect();
+                Ok(json!({
+                    "address": ACCOUNT,
+                    "balance": "0x64",
+                    "codeHash": B256::repeat_byte(0xcc),
+                    "nonce": "0x1",
+                    "storageHash": B256::repeat_byte(0x55),
+                    "accountProof": ["0x02"],
+                    "storageProof": proofs,
+                }))
+            }
+            "eth_getCode" => Ok(json!("0x00")),
+            _ => Err(TransportErrorKind::custom_str("unexpected method")),
+        }
+    }
+
+    fn config(max_slots_per_request: usize, max_concurrent_requests: usize) -> ProviderInputConfig {
+        ProviderInputConfig {
+            max_slots_per_request,
+            max_concurrent_requests,
+            retry: RetryConfig {
+                max_retries: 2,
+                initial_backoff: Duration::from_millis(1),
+                max_backoff: Duration::from_millis(1),
+            },
+        }
+    }
+
+    fn slots(count: u8) -> Vec<B256> {
+        (1..=count).map(B256::with_last_byte).collect()
+    }
+
+    #[tokio::test]
+    async fn test_account_splits_storage_batches() {
+        let transport = MockTransport::new(node(0));
+        let source = transport.source(config(2, 16));
+
+        let account = source.account(7, ACCOUNT, slots(5)).await.unwrap();
+
+        let batches: Vec<usize> = transport
+            .params("eth_getProof")
+            .iter()
+            .map(|params| params[1].as_array().unwrap().len())
+            .collect();
+        assert_eq!(batches, vec![2, 2, 1]);
+        assert!(transport.params("eth_getProof").iter().all(|params| params[2] == json!("0x7")));
+
+        assert_eq!(account.nonce, 1);
+        assert_eq!(account.storage_root, B256::repeat_byte(0x55));
+        assert_eq!(account.code.as_ref(), &[0x00]);
+        assert_eq!(account.storage.keys().copied().collect::<Vec<_>>(), slots(5));
+        assert!(account.storage.iter().all(|(slot, value)| B256::from(*value) == *slot));
+        assert_eq!(account.storage_proofs.len(), 5);
+
+        // An account without slots is still fetched with its proof.
+        source.account(7, ACCOUNT, Vec::new()).await.unwrap();
+        assert_eq!(transport.params("eth_getProof").last().unwrap()[1], json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_account_retries_transient_errors() {
+        let failures = Arc::new(AtomicUsize::new(0));
+        let node = node(0);
+        let failed = failures.clone();
+        let transport = MockTransport::new(move |method, params| {
+            if method == "eth_getProof" && failed.fetch_add(1, Ordering::SeqCst) < 2 {
+                return Err(TransportErrorKind::custom_str("connection reset"));
+            }
+            node(method, params)
+        });
+        let source = transport.source(config(64, 16));
+
+        let account = source.account(7, ACCOUNT, slots(1)).await.unwrap();
+        assert_eq!(account.storage.len(), 1);
+        assert_eq!(transport.params("eth_getProof").len(), 3);
+
+        // A request failing more than the retries allow is not retried again, nor are the
+        // non-transient errors.
+        failures.store(0, Ordering::SeqCst);
+        let source = transport.source(ProviderInputConfig {
+            retry: RetryConfig { max_retries: 1, ..config(64, 16).retry },
+            ..config(64, 16)
+        });
+        assert!(matches!(
+            source.account(7, ACCOUNT, slots(1)).await,
+            Err(InputError::Transport(RpcError::Transport(_)))
+        ));
+
+        let transport = MockTransport::new(|_, _| {
+            Err(RpcError::ErrorResp(ErrorPayload {
+                code: -32000,
+                message: "header not found".into(),
+                data: None,
+            }))
+        });
+        let source = transport.source(config(64, 16));
+        assert!(source.account(7, ACCOUNT, slots(1)).await.is_err());
+        assert_eq!(transport.params("eth_getProof").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_account_storage_proof_mismatch() {
+        let transport = MockTransport::new(node(1));
+        let source = transport.source(config(2, 16));
+
+        let err = source.account(7, ACCOUNT, slots(3)).await.unwrap_err();
+        assert!(matches!(
+            err,
+            InputError::StorageProofMismatch { address: ACCOUNT, expected: 2, actual: 1 }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_accounts_share_the_concurrency_limit() {
+        let transport = MockTransport::new(node(0));
+        let source = transport.source(config(1, 3));
+
+        let requests = (0..4).map(|i| (Address::with_last_byte(i), slots(4))).collect();
+        let accounts = source.accounts(7, requests).await.unwrap();
+        assert_eq!(accounts.len(), 4);
+
+        // The 4 accounts of 4 batches each never have more requests in flight than the limit.
+        assert_eq!(transport.params("eth_getProof").len(), 16);
+        assert!(transport.in_flight.1.load(Ordering::SeqCst) <= 3);
+    }
+
+    #[test]
+    fn test_retry_backoff_is_exponential() {
+        let config = RetryConfig {
+            max_retries: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(60),
+        };
+
+        assert_eq!(config.backoff(0), Duration::from_millis(100));
+        assert_eq!(config.backoff(1), Duration::from_millis(200));
+        assert_eq!(config.backoff(2), Duration::from_millis(400));
+        assert_eq!(config.backoff(5), Duration::from_millis(3200));
+    }
+
+    #[test]
+    fn test_retry_backoff_is_capped() {
+        let config = RetryConfig {
+            max_retries: 100,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+        };
+
+        assert_eq!(config.backoff(10), Duration::from_secs(5));
+        // Large attempts must not overflow.
+        assert_eq!(config.backoff(64), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_account_range_addresses_skip_hashed_keys() {
+        let range: AccountRange = serde_json::from_value(serde_json::json!({
+            "root": B256::ZERO,
+            "accounts": {
+                "0x000000000000000000000000000000000000c0de": { "nonce": 1 },
+                "pre(0x1234)": { "nonce": 0 }
+            },
+            "next": "AAAA"
+        }))
+        .unwrap();
+
+        assert_eq!(
+            range.addresses(),
+            vec![Address::from_str("0x000000000000000000000000000000000000c0de").unwrap()]
+        );
+        assert_eq!(range.next, Some("AAAA".to_string()));
+    }
+}
@@ -0,0 +1,312 @@
+//! Handling of the system calls run before the transactions of a block.
+//!
+//! Since Cancun, the EIP-4788 beacon roots contract stores the parent beacon block root of each
+//! block, and since Prague the EIP-2935 history storage contract stores the parent block hash.
+//! These writes are not transactions of the block, so they are either modeled by the Kakarot
+//! program, which is then told which calls to run, or applied Rust-side: their storage writes are
+//! injected in the pre-state of the witness, and stripped from the expected state diff of the
+//! block so that they do not show as divergences of the Cairo execution.
+//!
+//! The modeled calls are written for the program by the
+//! [`system_calls_hint`](crate::hints::system_calls_hint), and their writes are applied before the
+//! native execution of the transactions when comparing both executions, see
+//! [`compare_block`](crate::divergence::compare_block).
+
+use super::program_input::AccountStateInput;
+use crate::output::StateDiff;
+use alloy_consensus::Header;
+use alloy_primitives::{address, Address, B256, U256};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, str::FromStr};
+use thiserror::Error;
+
+/// The address of the EIP-4788 beacon roots contract.
+pub const BEACON_ROOTS_ADDRESS: Address = address!("000F3df6D732807Ef1319fB7B8bB8522d0Beac02");
+
+/// The length of the ring buffers of the EIP-4788 beacon roots contract.
+pub const BEACON_ROOTS_BUFFER_LENGTH: u64 = 8191;
+
+/// The address of the EIP-2935 history storage contract.
+pub const HISTORY_STORAGE_ADDRESS: Address = address!("0000F90827F1C53a10cb7A02335B175320002935");
+
+/// The number of block hashes served by the EIP-2935 history storage contract.
+pub const HISTORY_SERVE_WINDOW: u64 = 8191;
+
+/// Represents errors that can occur when configuring the system calls.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SystemCallError {
+    /// Error variant indicating an unknown system call mode.
+    #[error("Unknown system call mode '{0}', expected 'skip', 'cairo' or 'rust'")]
+    UnknownMode(String),
+}
+
+/// A system call run before the transactions of a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SystemCall {
+    /// The EIP-4788 update of the parent beacon block root.
+    BeaconRoot,
+    /// The EIP-2935 update of the parent block hash.
+    BlockHistory,
+}
+
+impl SystemCall {
+    /// Returns the address of the system contract updated by the call.
+    pub const fn address(&self) -> Address {
+        match self {
+            Self::BeaconRoot => BEACON_ROOTS_ADDRESS,
+            Self::BlockHistory => HISTORY_STORAGE_ADDRESS,
+        }
+    }
+
+    /// Returns the storage writes of the call for a block, `None` when the call does not apply to
+    /// the block, e.g. the genesis block or a header without parent beacon block root.
+    pub fn storage_writes(&self, header: &Header) -> Option<BTreeMap<B256, U256>> {
+        if header.number == 0 {
+            return None;
+        }
+
+        match self {
+            Self::BeaconRoot => {
+                let root = header.parent_beacon_block_root?;
+                let timestamp_index = header.timestamp % BEACON_ROOTS_BUFFER_LENGTH;
+                let root_index = timestamp_index + BEACON_ROOTS_BUFFER_LENGTH;
+                Some(BTreeMap::from([
+                    (slot(timestamp_index), U256::from(header.timestamp)),
+                    (slot(root_index), U256::from_be_bytes(root.0)),
+                ]))
+            }
+            Self::BlockHistory => {
+                let index = (header.number - 1) % HISTORY_SERVE_WINDOW;
+                Some(BTreeMap::from([(slot(index), U256::from_be_bytes(header.parent_hash.0))]))
+            }
+        }
+    }
+}
+
+impl fmt::Display for SystemCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BeaconRoot => write!(f, "beacon-root"),
+            Self::BlockHistory => write!(f, "block-history"),
+        }
+    }
+}
+
+/// How a system call is handled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SystemCallMode {
+    /// The call is ignored, e.g. on chains where it is not active.
+    #[default]
+    Skip,
+    /// The call is modeled by the Kakarot program, which is told to run it.
+    Cairo,
+    /// The call is applied Rust-side, its effect being injected in the witness.
+    Rust,
+}
+
+impl FromStr for SystemCallMode {
+    type Err = SystemCallError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(Self::Skip),
+            "cairo" => Ok(Self::Cairo),
+            "rust" => Ok(Self::Rust),
+            _ => Err(SystemCallError::UnknownMode(s.to_string())),
+        }
+    }
+}
+
+/// The handling of the system calls of a chain.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SystemCallPolicy {
+    /// The handling of the EIP-4788 beacon root update.
+    pub beacon_root: SystemCallMode,
+    /// The handling of the EIP-2935 block hash history update.
+    pub block_history: SystemCallMode,
+}
+
+impl SystemCallPolicy {
+    /// Returns the handling of a system call.
+    pub const fn mode(&self, call: SystemCall) -> SystemCallMode {
+        match call {
+            SystemCall::BeaconRoot => self.beacon_root,
+            SystemCall::BlockHistory => self.block_history,
+        }
+    }
+
+    /// Returns the system calls of a block handled in the given mode.
+    pub fn calls(&self, header: &Header, mode: SystemCallMode) -> Vec<SystemCall> {
+        [SystemCall::BeaconRoot, SystemCall::BlockHistory]
+            .into_iter()
+            .filter(|call| self.mode(*call) == mode && call.storage_writes(header).is_some())
+            .collect()
+    }
+
+    /// Returns the accounts and storage slots the witness of a block must hold for its modeled
+    /// and Rust-side system calls.
+    pub fn witness_requests(&self, header: &Header) -> Vec<(Address, Vec<B256>)> {
+        [SystemCallMode::Cairo, SystemCallMode::Rust]
+            .into_iter()
+            .flat_map(|mode| self.calls(header, mode))
+            .filter_map(|call| {
+                let writes = call.storage_writes(header)?;
+                Some((call.address(), writes.into_keys().collect()))
+            })
+            .collect()
+    }
+
+    /// Applies the Rust-side system calls of a block to its pre-state.
+    ///
+    /// The system contracts missing from the pre-state are left untouched, as the block does not
+    /// read them.
+    pub fn inject(&self, header: &Header, state: &mut BTreeMap<Address, AccountStateInput>) {
+        for call in self.calls(header, SystemCallMode::Rust) {
+            let (Some(account), Some(writes)) =
+                (state.get_mut(&call.address()), call.storage_writes(header))
+            else {
+                continue;
+            };
+            account.storage.extend(
+                writes.into_iter().map(|(slot, value)| (U256::from_be_bytes(slot.0), value)),
+            );
+        }
+    }
+
+    /// Removes the storage writes of the Rust-side system calls of a block from its state diff,
+    /// e.g. the diff of the native execution, leaving the changes expected from the Cairo
+    /// execution.
+    pub fn strip(&self, header: &Header, diff: &mut StateDiff) {
+        for call in self.calls(header, SystemCallMode::Rust) {
+            let (Some(account), Some(writes)) =
+                (diff.accounts.get_mut(&call.address()), call.storage_writes(header))
+            else {
+                continue;
+            };
+            account.storage.retain(|slot, value| writes.get(slot) != Some(value));
+
+            if account.storage.is_empty() &&
+                !account.destroyed &&
+                account.nonce.is_none() &&
+                account.balance.is_none() &&
+                account.code_hash.is_none()
+            {
+                diff.accounts.remove(&call.address());
+            }
+        }
+    }
+}
+
+/// Returns the storage written by the given system calls of a block, by address, e.g. the calls
+/// modeled by the program, which the native execution of its transactions does not run.
+pub fn system_call_storage(
+    header: &Header,
+    calls: &[SystemCall],
+) -> BTreeMap<Address, BTreeMap<U256, U256>> {
+    let mut storage: BTreeMap<_, BTreeMap<_, _>> = BTreeMap::new();
+    for call in calls {
+        let Some(writes) = call.storage_writes(header) else { continue };
+        storage
+            .entry(call.address())
+            .or_default()
+            .extend(writes.into_iter().map(|(slot, value)| (U256::from_be_bytes(slot.0), value)));
+    }
+    storage
+}
+
+/// Returns the storage slot of an index of a system contract.
+pub(crate) fn slot(index: u64) -> B256 {
+    B256::from(U256::from(index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::AccountDiff;
+
+    fn header() -> Header {
+        Header {
+            number: 10,
+            timestamp: 8192,
+            parent_hash: B256::repeat_byte(0xaa),
+            parent_beacon_block_root: Some(B256::repeat_byte(0xbb)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_storage_writes() {
+        let header = header();
+
+        assert_eq!(
+            SystemCall::BeaconRoot.storage_writes(&header),
+            Some(BTreeMap::from([
+                (slot(1), U256::from(8192)),
+                (slot(8192), U256::from_be_bytes([0xbb; 32])),
+            ]))
+        );
+        assert_eq!(
+            SystemCall::BlockHistory.storage_writes(&header),
+            Some(BTreeMap::from([(slot(9), U256::from_be_bytes([0xaa; 32]))]))
+        );
+
+        let genesis = Header { number: 0, ..header.clone() };
+        assert_eq!(SystemCall::BlockHistory.storage_writes(&genesis), None);
+        let pre_cancun = Header { parent_beacon_block_root: None, ..header };
+        assert_eq!(SystemCall::BeaconRoot.storage_writes(&pre_cancun), None);
+    }
+
+    #[test]
+    fn test_inject_and_strip() {
+        let header = header();
+        let policy = SystemCallPolicy {
+            beacon_root: SystemCallMode::Rust,
+            block_history: SystemCallMode::Cairo,
+        };
+        assert_eq!(policy.calls(&header, SystemCallMode::Cairo), vec![SystemCall::BlockHistory]);
+        assert_eq!(policy.witness_requests(&header).len(), 2);
+
+        let mut state = BTreeMap::from([(BEACON_ROOTS_ADDRESS, AccountStateInput::default())]);
+        policy.inject(&header, &mut state);
+        assert_eq!(state[&BEACON_ROOTS_ADDRESS].storage[&U256::from(1)], U256::from(8192));
+
+        let writes = SystemCall::BeaconRoot.storage_writes(&header).unwrap();
+        let history = SystemCall::BlockHistory.storage_writes(&header).unwrap();
+        let mut diff = StateDiff {
+            accounts: BTreeMap::from([
+                (BEACON_ROOTS_ADDRESS, AccountDiff { storage: writes, ..Default::default() }),
+                (HISTORY_STORAGE_ADDRESS, AccountDiff { storage: history, ..Default::default() }),
+            ]),
+        };
+        policy.strip(&header, &mut diff);
+
+        // Only the Rust-side call is stripped, the modeled one is expected from the Cairo run.
+        assert_eq!(diff.accounts.keys().collect::<Vec<_>>(), vec![&HISTORY_STORAGE_ADDRESS]);
+    }
+
+    #[test]
+    fn test_system_call_storage() {
+        let header = header();
+        let storage = system_call_storage(&header, &[SystemCall::BlockHistory]);
+
+        assert_eq!(
+            storage,
+            BTreeMap::from([(
+                HISTORY_STORAGE_ADDRESS,
+                BTreeMap::from([(U256::from(9), U256::from_be_bytes([0xaa; 32]))])
+            )])
+        );
+        let genesis = Header { number: 0, ..header };
+        assert!(system_call_storage(&genesis, &[SystemCall::BlockHistory]).is_empty());
+    }
+
+    #[test]
+    fn test_parse_system_call_mode() {
+        assert_eq!("rust".parse(), Ok(SystemCallMode::Rust));
+        assert_eq!(
+            "native".parse::<SystemCallMode>(),
+            Err(SystemCallError::UnknownMode("native".to_string()))
+        );
+    }
+}
@@ -0,0 +1,538 @@
+use crate::{
+    artifacts::LifecyclePolicy,
+    deferred::ProvingMode,
+    divergence::ReportFormat,
+    exex::{CHAIN_ID, DATABASE_PATH},
+    failures::DEFAULT_REPORT_INTERVAL,
+    input::system::SystemCallPolicy,
+    limits::ExecutionLimits,
+    policy::HintPolicy,
+    program::ProgramRegistry,
+    scheduler::SchedulerConfig,
+    tuning::TuningConfig,
+    verifier::VerifierRegistry,
+    watchdog::WatchdogConfig,
+};
+use metrics::Label;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+use thiserror::Error;
+
+/// The name of the instance used when a single Kakarot program is run.
+pub const DEFAULT_INSTANCE_NAME: &str = "default";
+
+/// The path of the Kakarot program run by the default instance.
+pub const DEFAULT_PROGRAM_PATH: &str = "../../cairo/programs/os.json";
+
+/// The directory of the artifacts of an instance, e.g. the memory dumps of interrupted runs.
+pub const ARTIFACTS_DIR: &str = "artifacts";
+
+/// The prefix of the instance keys configuring the capacity of a memory segment, followed by the
+/// name of the segment, e.g. `segment-capacity-execution`.
+pub const SEGMENT_CAPACITY_PREFIX: &str = "segment-capacity-";
+
+/// Represents errors that can occur when configuring the Kakarot instances.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum InstanceError {
+    /// Error variant indicating a malformed `key=value` pair.
+    #[error("Invalid instance entry '{0}', expected 'key=value'")]
+    InvalidEntry(String),
+
+    /// Error variant indicating an unsupported key.
+    #[error("Unknown instance key '{0}'")]
+    UnknownKey(String),
+
+    /// Error variant indicating a value that cannot be parsed.
+    #[error("Invalid value '{value}' for instance key '{key}'")]
+    InvalidValue {
+        /// The key of the value.
+        key: String,
+        /// The invalid value.
+        value: String,
+    },
+
+    /// Error variant indicating that a required key is missing.
+    #[error("Missing instance key '{0}'")]
+    MissingKey(&'static str),
+
+    /// Error variant indicating that an instance name is not usable as a directory name.
+    #[error("Invalid instance name '{0}', expected alphanumeric characters, '-' or '_'")]
+    InvalidName(String),
+
+    /// Error variant indicating that several instances share the same name.
+    #[error("Duplicate instance name '{0}'")]
+    DuplicateName(String),
+}
+
+/// The configuration of a Kakarot instance, i.e. a Kakarot program proving a chain.
+///
+/// Several instances can run in the same ExEx, e.g. a new program version in shadow mode alongside
+/// the production one. Each instance has its own database, queue and metrics labels.
+///
+/// Instances are parsed from comma separated `key=value` pairs, e.g.
+/// `name=shadow,program=os-v2.json,chain-id=1,start-block=100,max-steps=1000000,timeout-secs=60`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstanceConfig {
+    /// The name of the instance, used as database namespace and metrics label.
+    pub name: String,
+    /// The path of the compiled Kakarot program, proving the blocks out of the ranges of the
+    /// program registry.
+    pub program: PathBuf,
+    /// The path of the registry of the programs proving each range of blocks, e.g. each hardfork,
+    /// see [`ProgramRegistry`].
+    pub programs: Option<PathBuf>,
+    /// The chain ID of the chain proven by the instance, written in the program input of the
+    /// blocks and validated by their native replays.
+    pub chain_id: u64,
+    /// The first block processed by the instance, earlier blocks being skipped.
+    pub start_block: u64,
+    /// The limits of the execution of each block, unlimited by default.
+    pub limits: ExecutionLimits,
+    /// The configuration of the scheduling of the tip and backfill blocks.
+    pub scheduler: SchedulerConfig,
+    /// Whether the blocks are proven inline or enqueued for deferred proving, or only executed.
+    pub proving: ProvingMode,
+    /// The tuning of the Cairo runners, fixed or learned from the previous blocks.
+    pub tuning: TuningConfig,
+    /// The handling of the system calls of the chain, e.g. the EIP-4788 beacon root update.
+    pub system_calls: SystemCallPolicy,
+    /// The path of the registry of the verifier parameters the proofs are checked against before
+    /// their submission, see [`VerifierRegistry`].
+    pub verifier_params: Option<PathBuf>,
+    /// Whether each block is first run without trace nor proof mode, validating its output and
+    /// estimating its size before the proof-mode run, see [`crate::executor`].
+    pub dry_run: bool,
+    /// The format of the reports of the transactions diverging from their native execution, each
+    /// executed block being compared with it when set, see [`crate::divergence`].
+    pub differential: Option<ReportFormat>,
+    /// The path of the whitelist of the hints allowed to execute, all hints being allowed when
+    /// `None`, see [`HintPolicy`].
+    pub hint_policy: Option<PathBuf>,
+    /// The URL of the object storage the large artifacts are tiered to, e.g. `s3://bucket/keth`,
+    /// the artifacts being stored in the database when `None`, see [`crate::artifacts`].
+    pub artifact_store: Option<String>,
+    /// The path of the lifecycle rules of the tiered artifacts, see [`LifecyclePolicy`].
+    pub lifecycle: Option<PathBuf>,
+    /// The path of the redb file storing the proofs and the metadata of the proven blocks, which
+    /// are stored in the database when `None`, see [`crate::store`].
+    pub proof_store: Option<PathBuf>,
+    /// The minimum interval between two reports of the recurring failures of a fingerprint, see
+    /// [`crate::failures`].
+    pub failure_report_interval: Duration,
+    /// The path of the configuration of the watchdog of the stalled stages, the stages not being
+    /// watched when `None`, see [`WatchdogConfig`].
+    pub watchdog: Option<PathBuf>,
+    /// Whether the program input of each block is posted as EIP-4844 blobs, its KZG commitments
+    /// being computed and stored with the block, see [`crate::blob`].
+    pub blob_da: bool,
+}
+
+impl Default for InstanceConfig {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_INSTANCE_NAME.to_string(),
+            program: PathBuf::from(DEFAULT_PROGRAM_PATH),
+            programs: None,
+            chain_id: CHAIN_ID,
+            start_block: 0,
+            limits: ExecutionLimits::default(),
+            scheduler: SchedulerConfig::default(),
+            proving: ProvingMode::default(),
+            tuning: TuningConfig::default(),
+            system_calls: SystemCallPolicy::default(),
+            verifier_params: None,
+            dry_run: false,
+            differential: None,
+            hint_policy: None,
+            artifact_store: None,
+            lifecycle: None,
+            proof_store: None,
+            failure_report_interval: DEFAULT_REPORT_INTERVAL,
+            watchdog: None,
+            blob_da: false,
+        }
+    }
+}
+
+impl InstanceConfig {
+    /// Returns the path of the database of the instance, in its own directory of `data_dir`.
+    pub fn database_path(&self, data_dir: &Path) -> PathBuf {
+        data_dir.join(&self.name).join(DATABASE_PATH)
+    }
+
+    /// Returns the path of the artifacts directory of the instance, in its own directory of
+    /// `data_dir`.
+    pub fn artifacts_path(&self, data_dir: &Path) -> PathBuf {
+        data_dir.join(&self.name).join(ARTIFACTS_DIR)
+    }
+
+    /// Loads and validates the registry of the verifier parameters of the instance, if configured.
+    pub fn verifiers(&self) -> eyre::Result<Option<VerifierRegistry>> {
+        self.verifier_params.as_ref().map(VerifierRegistry::load).transpose()
+    }
+
+    /// Loads and validates the registry of the programs of the instance, if configured.
+    pub fn programs(&self) -> eyre::Result<Option<ProgramRegistry>> {
+        self.programs.as_ref().map(ProgramRegistry::load).transpose()
+    }
+
+    /// Loads the hint policy of the instance, if configured.
+    pub fn hint_policy(&self) -> eyre::Result<Option<HintPolicy>> {
+        self.hint_policy.as_ref().map(HintPolicy::load).transpose()
+    }
+
+    /// Loads the lifecycle rules of the tiered artifacts of the instance, if configured.
+    pub fn lifecycle(&self) -> eyre::Result<Option<LifecyclePolicy>> {
+        self.lifecycle.as_ref().map(LifecyclePolicy::load).transpose()
+    }
+
+    /// Loads the configuration of the watchdog of the instance, if configured.
+    pub fn watchdog(&self) -> eyre::Result<Option<WatchdogConfig>> {
+        self.watchdog.as_ref().map(WatchdogConfig::load).transpose()
+    }
+
+    /// Returns the metrics labels of the instance.
+    pub fn labels(&self) -> Vec<Label> {
+        vec![
+            Label::new("instance", self.name.clone()),
+            Label::new("chain_id", self.chain_id.to_string()),
+        ]
+    }
+
+    /// Returns whether the instance processes the given block.
+    pub const fn accepts(&self, block_number: u64) -> bool {
+        block_number >= self.start_block
+    }
+
+    /// Validates the configuration of several instances, whose names must be unique.
+    pub fn validate_all(instances: &[Self]) -> Result<(), InstanceError> {
+        let mut names = HashSet::new();
+        for instance in instances {
+            if !names.insert(instance.name.as_str()) {
+                return Err(InstanceError::DuplicateName(instance.name.clone()));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for InstanceConfig {
+    type Err = InstanceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut name = None;
+        let mut program = None;
+        let mut config = Self::default();
+
+        for entry in s.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (key, value) = entry
+                .split_once('=')
+                .ok_or_else(|| InstanceError::InvalidEntry(entry.to_string()))?;
+            let invalid_value =
+                || InstanceError::InvalidValue { key: key.to_string(), value: value.to_string() };
+
+            match key {
+                "name" => name = Some(value.to_string()),
+                "program" => program = Some(PathBuf::from(value)),
+                "programs" => config.programs = Some(PathBuf::from(value)),
+                "chain-id" => config.chain_id = value.parse().map_err(|_| invalid_value())?,
+                "start-block" => config.start_block = value.parse().map_err(|_| invalid_value())?,
+                "max-steps" => {
+                    config.limits.max_steps = Some(value.parse().map_err(|_| invalid_value())?);
+                }
+                "timeout-secs" => {
+                    let secs = value.parse().map_err(|_| invalid_value())?;
+                    config.limits.timeout = Some(Duration::from_secs(secs));
+                }
+                "tip-weight" => {
+                    config.scheduler.tip_weight = value.parse().map_err(|_| invalid_value())?;
+                }
+                "backfill-weight" => {
+                    config.scheduler.backfill_weight =
+                        value.parse().map_err(|_| invalid_value())?;
+                }
+                "max-tip-wait-secs" => {
+                    let secs = value.parse().map_err(|_| invalid_value())?;
+                    config.scheduler.max_tip_wait = Duration::from_secs(secs);
+                }
+                "proving" => config.proving = value.parse().map_err(|_| invalid_value())?,
+                "dict-pool" => {
+                    config.tuning.tuning.dict_pool = value.parse().map_err(|_| invalid_value())?;
+                }
+                "beacon-root" => {
+                    config.system_calls.beacon_root = value.parse().map_err(|_| invalid_value())?;
+                }
+                "block-history" => {
+                    config.system_calls.block_history =
+                        value.parse().map_err(|_| invalid_value())?;
+                }
+                "verifier-params" => config.verifier_params = Some(PathBuf::from(value)),
+                "dry-run" => config.dry_run = value.parse().map_err(|_| invalid_value())?,
+                "differential" => {
+                    config.differential = Some(value.parse().map_err(|_| invalid_value())?);
+                }
+                "hint-policy" => config.hint_policy = Some(PathBuf::from(value)),
+                "artifact-store" => config.artifact_store = Some(value.to_string()),
+                "lifecycle" => config.lifecycle = Some(PathBuf::from(value)),
+                "proof-store" => config.proof_store = Some(PathBuf::from(value)),
+                "failure-report-secs" => {
+                    let secs = value.parse().map_err(|_| invalid_value())?;
+                    config.failure_report_interval = Duration::from_secs(secs);
+                }
+                "watchdog" => config.watchdog = Some(PathBuf::from(value)),
+                "blob-da" => config.blob_da = value.parse().map_err(|_| invalid_value())?,
+                "auto-tune-blocks" => {
+                    let window = value.parse().map_err(|_| invalid_value())?;
+                    config.tuning.auto_tune_window = Some(window);
+                }
+                _ => {
+                    let segment = key
+                        .strip_prefix(SEGMENT_CAPACITY_PREFIX)
+                        .ok_or_else(|| InstanceError::UnknownKey(key.to_string()))?;
+                    let capacity = value.parse().map_err(|_| invalid_value())?;
+                    config.tuning.tuning.segments.insert(segment.to_string(), capacity);
+                }
+            }
+        }
+
+        config.name = name.ok_or(InstanceError::MissingKey("name"))?;
+        config.program = program.ok_or(InstanceError::MissingKey("program"))?;
+
+        // The name is used as a directory name, it must not escape the data directory.
+        if config.name.is_empty() ||
+            !config.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(InstanceError::InvalidName(config.name));
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{input::system::SystemCallMode, tuning::RunnerTuning};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_parse_instance_config() {
+        let config: InstanceConfig =
+            "name=shadow, program=os-v2.json,chain-id=7,start-block=100,max-steps=50,timeout-secs=2"
+                .parse()
+                .unwrap();
+
+        assert_eq!(
+            config,
+            InstanceConfig {
+                name: "shadow".to_string(),
+                program: PathBuf::from("os-v2.json"),
+                programs: None,
+                chain_id: 7,
+                start_block: 100,
+                limits: ExecutionLimits {
+                    max_steps: Some(50),
+                    timeout: Some(Duration::from_secs(2))
+                },
+                scheduler: SchedulerConfig::default(),
+                proving: ProvingMode::Inline,
+                tuning: TuningConfig::default(),
+                system_calls: SystemCallPolicy::default(),
+                verifier_params: None,
+                dry_run: false,
+                differential: None,
+                hint_policy: None,
+                artifact_store: None,
+                lifecycle: None,
+                proof_store: None,
+                failure_report_interval: DEFAULT_REPORT_INTERVAL,
+                watchdog: None,
+                blob_da: false,
+            }
+        );
+        assert!(!config.accepts(99));
+        assert!(config.accepts(100));
+        assert_eq!(
+            config.database_path(Path::new("/data")),
+            PathBuf::from("/data/shadow").join(DATABASE_PATH)
+        );
+        assert_eq!(
+            config.artifacts_path(Path::new("/data")),
+            PathBuf::from("/data/shadow").join(ARTIFACTS_DIR)
+        );
+    }
+
+    #[test]
+    fn test_parse_scheduler_config() {
+        let config: InstanceConfig =
+            "name=prod,program=os.json,tip-weight=8,backfill-weight=2,max-tip-wait-secs=10"
+                .parse()
+                .unwrap();
+
+        assert_eq!(
+            config.scheduler,
+            SchedulerConfig {
+                tip_weight: 8,
+                backfill_weight: 2,
+                max_tip_wait: Duration::from_secs(10)
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_tuning_config() {
+        let config: InstanceConfig =
+            "name=prod,program=os.json,dict-pool=64,segment-capacity-execution=1000000,\
+             auto-tune-blocks=100"
+                .parse()
+                .unwrap();
+
+        assert_eq!(
+            config.tuning,
+            TuningConfig {
+                tuning: RunnerTuning {
+                    dict_pool: 64,
+                    segments: BTreeMap::from([("execution".to_string(), 1_000_000)]),
+                },
+                auto_tune_window: Some(100)
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_system_call_policy() {
+        let config: InstanceConfig =
+            "name=prod,program=os.json,beacon-root=rust,block-history=cairo".parse().unwrap();
+
+        assert_eq!(
+            config.system_calls,
+            SystemCallPolicy {
+                beacon_root: SystemCallMode::Rust,
+                block_history: SystemCallMode::Cairo
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_verifier_params() {
+        let config: InstanceConfig =
+            "name=prod,program=os.json,verifier-params=verifiers.json".parse().unwrap();
+
+        assert_eq!(config.verifier_params, Some(PathBuf::from("verifiers.json")));
+    }
+
+    #[test]
+    fn test_parse_programs() {
+        let config: InstanceConfig =
+            "name=prod,program=os.json,programs=forks.json".parse().unwrap();
+        assert_eq!(config.programs, Some(PathBuf::from("forks.json")));
+    }
+
+    #[test]
+    fn test_parse_hint_policy() {
+        let config: InstanceConfig =
+            "name=prod,program=os.json,hint-policy=hints.json".parse().unwrap();
+        assert_eq!(config.hint_policy, Some(PathBuf::from("hints.json")));
+    }
+
+    #[test]
+    fn test_parse_artifact_store() {
+        let config: InstanceConfig =
+            "name=prod,program=os.json,artifact-store=s3://bucket/keth,lifecycle=lifecycle.json"
+                .parse()
+                .unwrap();
+        assert_eq!(config.artifact_store.as_deref(), Some("s3://bucket/keth"));
+        assert_eq!(config.lifecycle, Some(PathBuf::from("lifecycle.json")));
+    }
+
+    #[test]
+    fn test_parse_proof_store() {
+        let config: InstanceConfig =
+            "name=prod,program=os.json,proof-store=proofs.redb".parse().unwrap();
+        assert_eq!(config.proof_store, Some(PathBuf::from("proofs.redb")));
+    }
+
+    #[test]
+    fn test_parse_failure_report_interval() {
+        let config: InstanceConfig =
+            "name=prod,program=os.json,failure-report-secs=300".parse().unwrap();
+        assert_eq!(config.failure_report_interval, Duration::from_secs(300));
+    }
+
+    #[test]
+    fn test_parse_watchdog() {
+        let config: InstanceConfig =
+            "name=prod,program=os.json,watchdog=watchdog.json".parse().unwrap();
+        assert_eq!(config.watchdog, Some(PathBuf::from("watchdog.json")));
+    }
+
+    #[test]
+    fn test_parse_dry_run() {
+        let config: InstanceConfig = "name=prod,program=os.json,dry-run=true".parse().unwrap();
+        assert!(config.dry_run);
+        assert!(!"name=prod,program=os.json".parse::<InstanceConfig>().unwrap().dry_run);
+    }
+
+    #[test]
+    fn test_parse_blob_da() {
+        let config: InstanceConfig = "name=prod,program=os.json,blob-da=true".parse().unwrap();
+        assert!(config.blob_da);
+        assert!(matches!(
+            "name=prod,program=os.json,blob-da=yes".parse::<InstanceConfig>(),
+            Err(InstanceError::InvalidValue { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_differential() {
+        let config: InstanceConfig = "name=prod,program=os.json,differential=html".parse().unwrap();
+        assert_eq!(config.differential, Some(ReportFormat::Html));
+        assert!("name=prod,program=os.json,differential=pdf".parse::<InstanceConfig>().is_err());
+    }
+
+    #[test]
+    fn test_parse_instance_config_errors() {
+        assert_eq!(
+            "program=os.json".parse::<InstanceConfig>(),
+            Err(InstanceError::MissingKey("name"))
+        );
+        assert_eq!(
+            "name=../prod,program=os.json".parse::<InstanceConfig>(),
+            Err(InstanceError::InvalidName("../prod".to_string()))
+        );
+        assert_eq!(
+            "name=prod,program=os.json,chain-id=one".parse::<InstanceConfig>(),
+            Err(InstanceError::InvalidValue {
+                key: "chain-id".to_string(),
+                value: "one".to_string()
+            })
+        );
+        assert_eq!(
+            "name=prod,program=os.json,proving=later".parse::<InstanceConfig>(),
+            Err(InstanceError::InvalidValue {
+                key: "proving".to_string(),
+                value: "later".to_string()
+            })
+        );
+        assert_eq!(
+            "name=prod,layout=all".parse::<InstanceConfig>(),
+            Err(InstanceError::UnknownKey("layout".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_validate_instances() {
+        let prod = InstanceConfig { name: "prod".to_string(), ..Default::default() };
+        let shadow = InstanceConfig { name: "shadow".to_string(), ..Default::default() };
+
+        assert!(InstanceConfig::validate_all(&[prod.clone(), shadow]).is_ok());
+        assert_eq!(
+            InstanceConfig::validate_all(&[prod.clone(), prod]),
+            Err(InstanceError::DuplicateName("prod".to_string()))
+        );
+    }
+}
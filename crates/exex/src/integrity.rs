@@ -0,0 +1,289 @@
+//! Integrity of the persisted artifacts: content checksums and corruption detection.
+//!
+//! The sha256 checksum of every persisted artifact is recorded in the `artifact_checksum` table
+//! when it is written, over its uncompressed content so that the compression and the tiering of
+//! the artifacts do not change it. Artifacts are verified against their checksum when read, a
+//! mismatch failing with [`IntegrityError::Corrupted`]. Artifacts written before the checksums were
+//! recorded are not verified.
+//!
+//! [`fsck`] scans all the artifacts, in the database and in the object storage, reporting the
+//! corrupted and missing ones along with the orphaned objects of the storage. With
+//! [`FsckOptions::repair`], the affected blocks are scheduled for re-generation: their traces are
+//! removed and the blocks marked for retry, their corrupted proofs queued to be proven again, and
+//! the orphaned objects deleted.
+
+use crate::{
+    artifacts::{object_key, PIE_KIND},
+    compression::ArtifactKind,
+    db::Database,
+    deferred::JobState,
+};
+use alloy_primitives::B256;
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeSet, fmt};
+use thiserror::Error;
+
+/// Represents errors that can occur when verifying the integrity of the artifacts.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum IntegrityError {
+    /// Error variant indicating an artifact whose content does not match its checksum.
+    #[error("Artifact {artifact} is corrupted: expected checksum {expected}, found {actual}")]
+    Corrupted {
+        /// The corrupted artifact.
+        artifact: ArtifactRef,
+        /// The recorded checksum.
+        expected: B256,
+        /// The checksum of the content read.
+        actual: B256,
+    },
+}
+
+/// Returns the checksum of the content of an artifact.
+pub fn checksum(data: &[u8]) -> B256 {
+    B256::from(<[u8; 32]>::from(Sha256::digest(data)))
+}
+
+/// A reference to a persisted artifact of a block.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ArtifactRef {
+    /// The kind of the artifact, e.g. `trace` or `pie`.
+    pub kind: String,
+    /// The number of the block.
+    pub number: u64,
+    /// The name of the artifact, e.g. the column of its table or its file name.
+    pub name: String,
+}
+
+impl ArtifactRef {
+    /// Creates a reference to an artifact.
+    pub fn new(kind: &str, number: u64, name: &str) -> Self {
+        Self { kind: kind.to_string(), number, name: name.to_string() }
+    }
+
+    /// Returns the key of the artifact in the object storage.
+    pub fn key(&self) -> String {
+        object_key(&self.kind, self.number, &self.name)
+    }
+
+    /// Verifies some content of the artifact against its checksum, if recorded.
+    pub fn verify(&self, expected: Option<B256>, data: &[u8]) -> Result<(), IntegrityError> {
+        let Some(expected) = expected else { return Ok(()) };
+        let actual = checksum(data);
+        if actual != expected {
+            return Err(IntegrityError::Corrupted { artifact: self.clone(), expected, actual });
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ArtifactRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.key())
+    }
+}
+
+/// The options of [`fsck`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FsckOptions {
+    /// Whether to schedule the re-generation of the affected blocks and delete the orphaned
+    /// objects.
+    pub repair: bool,
+}
+
+/// The outcome of [`fsck`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FsckReport {
+    /// The number of artifacts checked.
+    pub checked: usize,
+    /// The artifacts whose content does not match their checksum, or cannot be decompressed.
+    pub corrupted: Vec<ArtifactRef>,
+    /// The artifacts recorded but missing, e.g. deleted from the object storage.
+    pub missing: Vec<ArtifactRef>,
+    /// The keys of the objects of the storage recorded by no artifact.
+    pub orphaned: Vec<String>,
+    /// The blocks scheduled for re-generation.
+    pub scheduled: Vec<u64>,
+}
+
+impl FsckReport {
+    /// Returns whether no problem was found.
+    pub fn is_clean(&self) -> bool {
+        self.corrupted.is_empty() && self.missing.is_empty() && self.orphaned.is_empty()
+    }
+}
+
+/// Scans the artifacts of the database and of its object storage, if any.
+pub fn fsck(db: &Database, options: &FsckOptions) -> eyre::Result<FsckReport> {
+    let mut report = FsckReport::default();
+
+    let artifacts = db.artifact_refs()?;
+    for artifact in &artifacts {
+        report.checked += 1;
+        let Some(stored) = db.raw_artifact(artifact)? else {
+            report.missing.push(artifact.clone());
+            continue;
+        };
+        let content = match stored_kind(artifact) {
+            Some(kind) => db.codec().unpack(kind, &stored).map(|(_, content)| content),
+            None => Ok(stored),
+        };
+        let valid = content.is_ok_and(|content| {
+            artifact.verify(db.artifact_checksum(artifact).ok().flatten(), &content).is_ok()
+        });
+        if !valid {
+            report.corrupted.push(artifact.clone());
+        }
+    }
+
+    if let Some(store) = db.artifact_store() {
+        let recorded: BTreeSet<_> = artifacts.iter().map(ArtifactRef::key).collect();
+        report.orphaned = store.list()?.into_iter().filter(|key| !recorded.contains(key)).collect();
+    }
+
+    if options.repair {
+        repair(db, &mut report)?;
+    }
+    Ok(report)
+}
+
+/// Returns the compressed kind of an artifact, `None` for the artifacts stored as is, e.g. PIEs.
+fn stored_kind(artifact: &ArtifactRef) -> Option<ArtifactKind> {
+    artifact.kind.parse().ok()
+}
+
+/// Schedules the re-generation of the blocks of the corrupted and missing artifacts, deleting
+/// their rows and tiered objects, and deletes the orphaned objects.
+fn repair(db: &Database, report: &mut FsckReport) -> eyre::Result<()> {
+    let affected: BTreeSet<_> = report.corrupted.iter().chain(&report.missing).collect();
+    let mut scheduled = BTreeSet::new();
+    for artifact in affected {
+        match stored_kind(artifact) {
+            // Proofs are proven again by their job, if any.
+            Some(ArtifactKind::Proof) => {
+                if db.proving_job_state(artifact.number)?.is_some() {
+                    db.delete_proof(artifact.number)?;
+                    db.set_proving_job_state(artifact.number, JobState::Queued)?;
+                    scheduled.insert(artifact.number);
+                }
+            }
+            // Traces are executed again, with the limits of a pending retry if any.
+            Some(ArtifactKind::Trace | ArtifactKind::Memory | ArtifactKind::AirInput) => {
+                let limits = db.retry_limits(artifact.number)?.unwrap_or_default();
+                db.delete_execution_trace(artifact.number)?;
+                db.mark_for_retry(artifact.number, &limits)?;
+                scheduled.insert(artifact.number);
+            }
+            // PIEs are exported again by their job, if any.
+            None if artifact.kind == PIE_KIND => {
+                if db.proving_job_state(artifact.number)?.is_some() {
+                    db.delete_tiered_artifact(artifact)?;
+                    db.set_proving_job_state(artifact.number, JobState::Queued)?;
+                    scheduled.insert(artifact.number);
+                }
+            }
+            // Memory dumps are diagnostics of past runs, only reported.
+            _ => {}
+        }
+    }
+    report.scheduled = scheduled.into_iter().collect();
+
+    if let Some(store) = db.artifact_store() {
+        for key in &report.orphaned {
+            store.delete(key)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{artifacts::ArtifactStore, deferred::ProvingJob, output::ProofMetadata};
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_verify_checksum() {
+        let artifact = ArtifactRef::new("trace", 42, "execution");
+        assert_eq!(artifact.to_string(), "trace/42/execution");
+
+        let expected = checksum(b"trace");
+        assert_eq!(artifact.verify(Some(expected), b"trace"), Ok(()));
+        assert_eq!(artifact.verify(None, b"corrupted"), Ok(()));
+        assert_eq!(
+            artifact.verify(Some(expected), b"corrupted"),
+            Err(IntegrityError::Corrupted {
+                artifact: artifact.clone(),
+                expected,
+                actual: checksum(b"corrupted")
+            })
+        );
+    }
+
+    #[test]
+    fn test_verify_on_read() {
+        let db = Database::new(Connection::open_in_memory().unwrap()).unwrap();
+        let metadata = ProofMetadata { block_number: 1, ..Default::default() };
+        db.insert_proof(&metadata, b"proof").unwrap();
+        assert_eq!(db.proof(1).unwrap().map(|(_, proof)| proof), Some(b"proof".to_vec()));
+
+        db.lock()
+            .unwrap()
+            .execute("UPDATE proof SET proof = ? WHERE number = ?", (b"corrupted".as_slice(), "1"))
+            .unwrap();
+        let err = db.proof(1).unwrap_err();
+        let Some(IntegrityError::Corrupted { artifact, .. }) = err.downcast_ref() else {
+            panic!("expected a corrupted proof, got {err}")
+        };
+        assert_eq!(artifact.to_string(), "proof/1/proof");
+
+        let report = fsck(&db, &FsckOptions::default()).unwrap();
+        assert_eq!(report.corrupted, vec![ArtifactRef::new("proof", 1, "proof")]);
+    }
+
+    #[test]
+    fn test_fsck() {
+        let store = ArtifactStore::open("memory://", None).unwrap();
+        let db = Database::new(Connection::open_in_memory().unwrap())
+            .unwrap()
+            .with_artifact_store(store.clone());
+        db.store_artifact(PIE_KIND, 1, "1.pie.zip", b"pie".to_vec()).unwrap();
+        db.store_artifact(PIE_KIND, 2, "2.pie.zip", b"pie".to_vec()).unwrap();
+        store.put(&object_key(PIE_KIND, 2, "2.pie.zip"), b"corrupted".to_vec()).unwrap();
+        store.delete(&object_key(PIE_KIND, 1, "1.pie.zip")).unwrap();
+        store.put("pie/3/3.pie.zip", b"orphan".to_vec()).unwrap();
+
+        let report = fsck(&db, &FsckOptions { repair: true }).unwrap();
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.missing, vec![ArtifactRef::new(PIE_KIND, 1, "1.pie.zip")]);
+        assert_eq!(report.corrupted, vec![ArtifactRef::new(PIE_KIND, 2, "2.pie.zip")]);
+        assert_eq!(report.orphaned, vec!["pie/3/3.pie.zip".to_string()]);
+        assert!(!report.is_clean());
+
+        // The orphaned objects are deleted by the repair
+        assert_eq!(store.get("pie/3/3.pie.zip").unwrap(), None);
+    }
+
+    #[test]
+    fn test_fsck_repair_deletes_artifacts() {
+        let store = ArtifactStore::open("memory://", None).unwrap();
+        let db = Database::new(Connection::open_in_memory().unwrap())
+            .unwrap()
+            .with_artifact_store(store.clone());
+        let pie = ArtifactRef::new(PIE_KIND, 2, "2.pie.zip");
+        db.enqueue_proving_job(&ProvingJob::new(2, "os.json".into())).unwrap();
+        db.set_proving_job_state(2, JobState::Exported).unwrap();
+        db.store_artifact(PIE_KIND, 2, "2.pie.zip", b"pie".to_vec()).unwrap();
+        store.put(&pie.key(), b"corrupted".to_vec()).unwrap();
+
+        let report = fsck(&db, &FsckOptions { repair: true }).unwrap();
+        assert_eq!(report.corrupted, vec![pie.clone()]);
+        assert_eq!(report.scheduled, vec![2]);
+
+        // The corrupted PIE is deleted with its row, to be exported again by its job.
+        assert_eq!(db.proving_job_state(2).unwrap(), Some(JobState::Queued));
+        assert_eq!(db.artifact_refs().unwrap(), vec![]);
+        assert_eq!(db.artifact_checksum(&pie).unwrap(), None);
+        assert_eq!(store.get(&pie.key()).unwrap(), None);
+        assert!(fsck(&db, &FsckOptions::default()).unwrap().is_clean());
+    }
+}
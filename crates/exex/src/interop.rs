@@ -0,0 +1,266 @@
+use crate::model::{ConversionError, KethMaybeRelocatable, KethTransactionEncoded};
+use alloy_primitives::{Address, U256};
+use cairo_vm::Felt252;
+use once_cell::sync::Lazy;
+use reth_primitives::TransactionSigned;
+use serde::{Deserialize, Serialize};
+use starknet_types_core::{
+    felt::NonZeroFelt,
+    hash::{Pedersen, StarkHash},
+};
+use thiserror::Error;
+
+/// The number of bytes packed in a single felt of the Starknet calldata.
+///
+/// A felt holds at most 251 bits, so 31 full bytes is the largest whole number of bytes that fits.
+pub const BYTES_PER_FELT: usize = 31;
+
+/// The prefix of the Starknet contract address computation, the `STARKNET_CONTRACT_ADDRESS` short
+/// string.
+static CONTRACT_ADDRESS_PREFIX: Lazy<Felt252> =
+    Lazy::new(|| Felt252::from_bytes_be_slice(b"STARKNET_CONTRACT_ADDRESS"));
+
+/// The upper bound of Starknet contract addresses, `2**251 - 256`.
+static L2_ADDRESS_UPPER_BOUND: Lazy<Felt252> = Lazy::new(|| {
+    Felt252::from_hex_unchecked("0x7ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff00")
+});
+
+/// Represents errors that can occur when converting keth models into the kakarot-rpc wire types.
+#[derive(Debug, Error)]
+pub enum InteropError {
+    /// Error variant indicating that a field expected to hold an integer holds a relocatable.
+    #[error("Expected an integer for field '{0}', found a relocatable")]
+    NotAnInteger(&'static str),
+
+    /// Error variant indicating that a field expected to hold a byte holds a larger value.
+    #[error("Expected a byte for field '{0}', found {1}")]
+    NotAByte(&'static str, Felt252),
+
+    /// Error variant indicating that the maximum fee of a transaction overflows.
+    #[error(
+        "Maximum fee overflows for gas limit {gas_limit} and max fee per gas {max_fee_per_gas}"
+    )]
+    FeeOverflow {
+        /// The gas limit of the transaction.
+        gas_limit: u64,
+        /// The maximum fee per gas of the transaction.
+        max_fee_per_gas: u128,
+    },
+
+    /// Error variant indicating a failure to convert the transaction into the keth model.
+    #[error(transparent)]
+    Conversion(#[from] ConversionError),
+}
+
+/// The mapping between EVM addresses and the Starknet addresses of their Kakarot accounts.
+///
+/// Every EVM account is deployed by the Kakarot contract as a Starknet contract, using the EVM
+/// address as salt and `(kakarot_address, evm_address)` as constructor calldata. This is the same
+/// computation as the one performed by the kakarot-rpc gateway.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StarknetAddressMapper {
+    /// The Starknet address of the Kakarot contract (the deployer of all accounts).
+    pub kakarot_address: Felt252,
+    /// The class hash of the (uninitialized) Kakarot account contract.
+    pub account_class_hash: Felt252,
+}
+
+impl StarknetAddressMapper {
+    /// Creates a new [`StarknetAddressMapper`].
+    pub const fn new(kakarot_address: Felt252, account_class_hash: Felt252) -> Self {
+        Self { kakarot_address, account_class_hash }
+    }
+
+    /// Computes the Starknet address of the Kakarot account of an EVM address.
+    pub fn starknet_address(&self, evm_address: Address) -> Felt252 {
+        // The EVM address is used both as the salt and in the constructor calldata.
+        let salt = Felt252::from_bytes_be_slice(evm_address.as_slice());
+        let constructor_calldata_hash = Pedersen::hash_array(&[self.kakarot_address, salt]);
+
+        // h(prefix, deployer, salt, class_hash, h(calldata)) mod (2**251 - 256)
+        Pedersen::hash_array(&[
+            *CONTRACT_ADDRESS_PREFIX,
+            self.kakarot_address,
+            salt,
+            self.account_class_hash,
+            constructor_calldata_hash,
+        ])
+        .mod_floor(
+            &NonZeroFelt::try_from(*L2_ADDRESS_UPPER_BOUND).expect("upper bound is not zero"),
+        )
+    }
+
+    /// Converts an encoded transaction of the keth model into the Starknet transaction sent by
+    /// kakarot-rpc for it.
+    pub fn to_starknet_transaction(
+        &self,
+        transaction: &KethTransactionEncoded,
+    ) -> Result<StarknetTransaction, InteropError> {
+        // The sender is stored as a single felt holding the EVM address.
+        let sender = to_felt(transaction.sender(), "sender")?;
+        let sender = Address::from_slice(&sender.to_bytes_be()[32 - Address::len_bytes()..]);
+
+        // In the keth model, the RLP encoding is stored with one byte per felt.
+        let rlp = transaction
+            .rlp()
+            .data()
+            .iter()
+            .map(|byte| to_byte(byte, "rlp"))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // The signature is already stored as `[r.low, r.high, s.low, s.high, v]`.
+        let signature = transaction
+            .signature()
+            .data()
+            .iter()
+            .map(|felt| to_felt(felt, "signature").copied())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(StarknetTransaction {
+            sender_address: self.starknet_address(sender),
+            calldata: pack_bytes(&rlp),
+            signature,
+        })
+    }
+
+    /// Converts a signed EVM transaction into the Starknet transaction sent by kakarot-rpc for it.
+    pub fn to_starknet_transaction_signed(
+        &self,
+        transaction: TransactionSigned,
+    ) -> Result<StarknetTransaction, InteropError> {
+        self.to_starknet_transaction(&transaction.try_into()?)
+    }
+}
+
+/// The Starknet transaction wrapping an EVM transaction, as built by the kakarot-rpc gateway.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct StarknetTransaction {
+    /// The Starknet address of the Kakarot account of the EVM sender.
+    pub sender_address: Felt252,
+    /// The calldata, holding the length in bytes of the unsigned RLP-encoded EVM transaction
+    /// followed by its bytes packed by chunks of [`BYTES_PER_FELT`].
+    pub calldata: Vec<Felt252>,
+    /// The signature of the EVM transaction, as `[r.low, r.high, s.low, s.high, v]`.
+    pub signature: Vec<Felt252>,
+}
+
+/// Packs a byte array into felts, prefixed by its length in bytes.
+///
+/// Bytes are packed by chunks of [`BYTES_PER_FELT`] in big-endian order, the last chunk holding
+/// the remaining bytes.
+pub fn pack_bytes(bytes: &[u8]) -> Vec<Felt252> {
+    std::iter::once(Felt252::from(bytes.len()))
+        .chain(bytes.chunks(BYTES_PER_FELT).map(Felt252::from_bytes_be_slice))
+        .collect()
+}
+
+/// Computes the maximum fee of a transaction, in wei, as charged by the Kakarot account on
+/// Starknet.
+pub fn max_fee(gas_limit: u64, max_fee_per_gas: u128) -> Result<u128, InteropError> {
+    u128::from(gas_limit)
+        .checked_mul(max_fee_per_gas)
+        .ok_or(InteropError::FeeOverflow { gas_limit, max_fee_per_gas })
+}
+
+/// Splits an amount of wei into the `(low, high)` felts of a Starknet `Uint256`.
+pub fn u256_to_felts(value: U256) -> [Felt252; 2] {
+    let bytes = value.to_be_bytes::<{ U256::BYTES }>();
+    [Felt252::from_bytes_be_slice(&bytes[16..]), Felt252::from_bytes_be_slice(&bytes[..16])]
+}
+
+/// Returns the integer held by a [`KethMaybeRelocatable`].
+fn to_felt<'a>(
+    value: &'a KethMaybeRelocatable,
+    field: &'static str,
+) -> Result<&'a Felt252, InteropError> {
+    value.as_felt().ok_or(InteropError::NotAnInteger(field))
+}
+
+/// Returns the byte held by a [`KethMaybeRelocatable`].
+fn to_byte(value: &KethMaybeRelocatable, field: &'static str) -> Result<u8, InteropError> {
+    let felt = to_felt(value, field)?;
+    let bytes = felt.to_bytes_be();
+    if bytes[..31].iter().any(|byte| *byte != 0) {
+        return Err(InteropError::NotAByte(field, *felt));
+    }
+    Ok(bytes[31])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_rlp::Encodable;
+    use arbitrary::{Arbitrary, Unstructured};
+    use proptest::prelude::*;
+
+    fn mapper() -> StarknetAddressMapper {
+        StarknetAddressMapper::new(Felt252::from(0x1234u64), Felt252::from(0x5678u64))
+    }
+
+    #[test]
+    fn test_pack_bytes_empty() {
+        assert_eq!(pack_bytes(&[]), vec![Felt252::ZERO]);
+    }
+
+    #[test]
+    fn test_pack_bytes_chunks() {
+        // 31 bytes fit in a single felt, the 32nd byte goes in a second one.
+        let bytes = (1..=32u8).collect::<Vec<_>>();
+        let packed = pack_bytes(&bytes);
+
+        assert_eq!(packed.len(), 3);
+        assert_eq!(packed[0], Felt252::from(32u64));
+        assert_eq!(packed[1], Felt252::from_bytes_be_slice(&bytes[..31]));
+        assert_eq!(packed[2], Felt252::from(32u64));
+    }
+
+    #[test]
+    fn test_starknet_address_is_deterministic_and_bounded() {
+        let mapper = mapper();
+        let address = Address::new([0xab; 20]);
+
+        let starknet_address = mapper.starknet_address(address);
+        assert_eq!(starknet_address, mapper.starknet_address(address));
+        assert_ne!(starknet_address, mapper.starknet_address(Address::ZERO));
+        assert!(starknet_address.to_biguint() < L2_ADDRESS_UPPER_BOUND.to_biguint());
+    }
+
+    #[test]
+    fn test_max_fee_overflow() {
+        assert_eq!(max_fee(21_000, 10).unwrap(), 210_000);
+        assert!(matches!(max_fee(u64::MAX, u128::MAX), Err(InteropError::FeeOverflow { .. })));
+    }
+
+    #[test]
+    fn test_u256_to_felts() {
+        let value = U256::from(u128::MAX) + U256::from(2);
+        assert_eq!(u256_to_felts(value), [Felt252::ONE, Felt252::ONE]);
+    }
+
+    proptest! {
+        #[test]
+        fn test_signed_transaction_to_starknet_transaction(raw_bytes in any::<[u8; 1000]>()) {
+            let mut unstructured = Unstructured::new(&raw_bytes);
+
+            // Generate an arbitrary signed transaction
+            let tx = TransactionSigned::arbitrary(&mut unstructured)
+                .expect("Failed to generate arbitrary transaction");
+
+            let starknet_tx = mapper().to_starknet_transaction_signed(tx.clone()).unwrap();
+
+            // The calldata holds the packed RLP encoding of the unsigned transaction
+            let mut buffer = Vec::new();
+            tx.transaction.encode(&mut buffer);
+            prop_assert_eq!(starknet_tx.calldata, pack_bytes(&buffer));
+
+            // The sender is mapped to its Starknet address
+            prop_assert_eq!(
+                starknet_tx.sender_address,
+                mapper().starknet_address(tx.recover_signer().unwrap())
+            );
+
+            // The signature is made of 5 felts
+            prop_assert_eq!(starknet_tx.signature.len(), 5);
+        }
+    }
+}
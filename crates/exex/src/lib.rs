@@ -1,6 +1,59 @@
+pub mod air;
+pub mod analytics;
+pub mod artifacts;
+pub mod attribution;
+pub mod benchmark;
+pub mod blob;
+pub mod calltracer;
+pub mod campaign;
+pub mod chain;
+pub mod checkpoint;
+pub mod commitment;
+pub mod compression;
 pub mod db;
+pub mod deferred;
+pub mod divergence;
+pub mod envelope;
+pub mod error;
+pub mod events;
 pub mod execution;
+pub mod executor;
 pub mod exex;
+pub mod fact;
+pub mod failures;
+pub mod grpc;
+pub mod halt;
 pub mod hints;
+pub mod input;
+pub mod instance;
+pub mod integrity;
+pub mod interop;
+pub mod light_client;
+pub mod limits;
 pub mod model;
+pub mod otlp;
+pub mod output;
+pub mod policy;
+pub mod precompute;
+pub mod prestate;
+pub mod profiler;
+pub mod program;
+pub mod quorum;
+pub mod receipts;
+pub mod refund;
+pub mod retry;
+pub mod revert;
+pub mod rlp;
+pub mod runner;
+pub mod scheduler;
 pub mod serde;
+pub mod sharp;
+pub mod solidity;
+pub mod ssz;
+pub mod store;
+pub mod structlog;
+pub mod telemetry;
+pub mod tracer;
+pub mod tuning;
+pub mod verifier;
+pub mod watchdog;
@@ -0,0 +1,496 @@
+//! Light-client proof bundles of the proven blocks.
+//!
+//! A [`LightClientBundle`] lets a light client verify specific state values after a block without
+//! trusting the node which exported it. It holds, for a proven block:
+//! - The validity proof of the execution of the block, with its [`ProofMetadata`].
+//! - The [`ProgramOutput`] of the block, committed to by the `output_root` of the metadata and by
+//!   the fact of the proof, `keccak256(program_hash || keccak256(output))`, see [`fact_hash`].
+//! - The Merkle-Patricia proofs of the requested accounts and storage slots, fetched with
+//!   `eth_getProof` semantics, against the post-state root of the output.
+//!
+//! Once the fact is checked against a fact registry or the proof against its verifier, the state
+//! values are checked with [`LightClientBundle::verify`], which binds them to the post-state root
+//! of the output. That root echoes the header the block was proven with and is not checked by the
+//! execution yet, see [`ProgramOutput`], so the client must trust the header it comes from.
+//!
+//! The bundles are exported in a directory holding a `manifest.json` [`LightClientManifest`] and a
+//! `{number}.light.json` file per block.
+
+use crate::{
+    fact::fact_hash,
+    input::{AccountInput, InputSource},
+    output::{ProgramOutput, ProofMetadata},
+    ssz::Ssz,
+    store::KethStore,
+};
+use alloy_primitives::{keccak256, Address, Bytes, B256, KECCAK256_EMPTY, U256};
+use alloy_rlp::Encodable;
+use alloy_trie::{proof::verify_proof, Nibbles, EMPTY_ROOT_HASH};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path, str::FromStr};
+use thiserror::Error;
+
+/// The file name of the manifest of exported light-client bundles.
+pub const LIGHT_CLIENT_MANIFEST: &str = "manifest.json";
+
+/// The version of the light-client bundle format, bumped on breaking changes.
+pub const LIGHT_CLIENT_VERSION: u32 = 1;
+
+/// Represents errors that can occur when building or verifying a light-client bundle.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum LightClientError {
+    /// Error variant indicating an invalid state proof request.
+    #[error("Invalid state proof request '{0}', expected 'address[:slot,...]'")]
+    InvalidRequest(String),
+
+    /// Error variant indicating a bundle of an unsupported version.
+    #[error("Unsupported light-client bundle version {0}")]
+    UnsupportedVersion(u32),
+
+    /// Error variant indicating an output of another block than the proof.
+    #[error("Bundle of block {expected} holds data of block {actual}")]
+    BlockMismatch {
+        /// The number of the block of the bundle.
+        expected: u64,
+        /// The number of the block of the mismatching data.
+        actual: u64,
+    },
+
+    /// Error variant indicating an output which is not the one committed to by the proof.
+    #[error("Output root {actual}, expected {expected}")]
+    OutputRootMismatch {
+        /// The output root of the proof metadata.
+        expected: B256,
+        /// The hash tree root of the output of the bundle.
+        actual: B256,
+    },
+
+    /// Error variant indicating a fact which is not the one of the program and output.
+    #[error("Fact {actual}, expected {expected}")]
+    FactMismatch {
+        /// The fact of the program hash and output.
+        expected: B256,
+        /// The fact of the bundle.
+        actual: B256,
+    },
+
+    /// Error variant indicating an account proof which does not verify against the post-state
+    /// root.
+    #[error("Invalid proof of account {0}")]
+    InvalidAccountProof(Address),
+
+    /// Error variant indicating a storage proof which does not verify against the storage root
+    /// of its account.
+    #[error("Invalid proof of slot {slot} of account {address}")]
+    InvalidStorageProof {
+        /// The account of the slot.
+        address: Address,
+        /// The storage slot.
+        slot: B256,
+    },
+}
+
+/// The accounts and storage slots to prove, e.g. parsed from `0xabc..:0x01,0x02`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateProofRequest {
+    /// The address of the account.
+    pub address: Address,
+    /// The storage slots of the account.
+    pub slots: Vec<B256>,
+}
+
+impl FromStr for StateProofRequest {
+    type Err = LightClientError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || LightClientError::InvalidRequest(s.to_string());
+        let (address, slots) = s.split_once(':').unwrap_or((s, ""));
+        let address = address.parse().map_err(|_| invalid())?;
+        let slots = slots
+            .split(',')
+            .filter(|slot| !slot.is_empty())
+            .map(|slot| slot.parse::<U256>().map(B256::from).map_err(|_| invalid()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { address, slots })
+    }
+}
+
+/// The light-client bundle of a proven block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LightClientBundle {
+    /// The version of the bundle format.
+    pub version: u32,
+    /// The metadata of the validity proof, holding the output root.
+    pub metadata: ProofMetadata,
+    /// The validity proof of the block.
+    pub proof: Bytes,
+    /// The output of the block, holding the post-state root of its header.
+    pub output: ProgramOutput,
+    /// The fact of the proof, as registered on L1.
+    pub fact: B256,
+    /// The requested accounts and storage slots, with their proofs against the post-state root.
+    pub accounts: Vec<AccountInput>,
+}
+
+impl LightClientBundle {
+    /// Creates the bundle of a proven block.
+    pub fn new(
+        metadata: ProofMetadata,
+        proof: Vec<u8>,
+        output: ProgramOutput,
+        accounts: Vec<AccountInput>,
+    ) -> Self {
+        let fact = fact_hash(metadata.program_hash, &output.to_felts());
+        Self {
+            version: LIGHT_CLIENT_VERSION,
+            metadata,
+            proof: proof.into(),
+            output,
+            fact,
+            accounts,
+        }
+    }
+
+    /// Returns the number of the block of the bundle.
+    pub const fn block_number(&self) -> u64 {
+        self.metadata.block_number
+    }
+
+    /// Returns the file name of the bundle, e.g. `100.light.json`.
+    pub fn file_name(&self) -> String {
+        format!("{}.light.json", self.block_number())
+    }
+
+    /// Verifies that the output is the one committed to by the proof, and that the state values
+    /// of the accounts are proven against its post-state root, as echoed from the header.
+    ///
+    /// The validity proof itself, or its fact, must be checked against its verifier.
+    pub fn verify(&self) -> Result<(), LightClientError> {
+        if self.version != LIGHT_CLIENT_VERSION {
+            return Err(LightClientError::UnsupportedVersion(self.version));
+        }
+        if self.output.block_number != self.block_number() {
+            return Err(LightClientError::BlockMismatch {
+                expected: self.block_number(),
+                actual: self.output.block_number,
+            });
+        }
+
+        let output_root = self.output.hash_tree_root();
+        if output_root != self.metadata.output_root {
+            return Err(LightClientError::OutputRootMismatch {
+                expected: self.metadata.output_root,
+                actual: output_root,
+            });
+        }
+        let fact = fact_hash(self.metadata.program_hash, &self.output.to_felts());
+        if fact != self.fact {
+            return Err(LightClientError::FactMismatch { expected: fact, actual: self.fact });
+        }
+
+        for account in &self.accounts {
+            verify_account(self.output.post_state_root, account)?;
+        }
+        Ok(())
+    }
+}
+
+/// Verifies the proofs of an account and of its storage slots against a state root.
+pub fn verify_account(state_root: B256, account: &AccountInput) -> Result<(), LightClientError> {
+    let address = account.address;
+    verify_proof(
+        state_root,
+        Nibbles::unpack(keccak256(address)),
+        encode_account(account),
+        &account.account_proof,
+    )
+    .map_err(|_| LightClientError::InvalidAccountProof(address))?;
+
+    for (slot, value) in &account.storage {
+        let invalid = || LightClientError::InvalidStorageProof { address, slot: *slot };
+        let proof = account.storage_proofs.get(slot).ok_or_else(invalid)?;
+        let value = (!value.is_zero()).then(|| alloy_rlp::encode(value));
+        verify_proof(account.storage_root, Nibbles::unpack(keccak256(slot)), value, proof)
+            .map_err(|_| invalid())?;
+    }
+    Ok(())
+}
+
+/// Returns the RLP encoding of an account in the state trie, `None` for an empty account which
+/// is absent from the trie.
+fn encode_account(account: &AccountInput) -> Option<Vec<u8>> {
+    let storage_root =
+        if account.storage_root.is_zero() { EMPTY_ROOT_HASH } else { account.storage_root };
+    let code_hash = if account.code_hash.is_zero() { KECCAK256_EMPTY } else { account.code_hash };
+    if account.nonce == 0 &&
+        account.balance.is_zero() &&
+        storage_root == EMPTY_ROOT_HASH &&
+        code_hash == KECCAK256_EMPTY
+    {
+        return None;
+    }
+
+    let payload_length = account.nonce.length() +
+        account.balance.length() +
+        storage_root.length() +
+        code_hash.length();
+    let mut out = Vec::with_capacity(payload_length + 3);
+    alloy_rlp::Header { list: true, payload_length }.encode(&mut out);
+    account.nonce.encode(&mut out);
+    account.balance.encode(&mut out);
+    storage_root.encode(&mut out);
+    code_hash.encode(&mut out);
+    Some(out)
+}
+
+/// An exported light-client bundle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LightClientEntry {
+    /// The number of the block.
+    pub block_number: u64,
+    /// The post-state root of the output of the block.
+    pub post_state_root: B256,
+    /// The file name of the bundle, relative to the manifest directory.
+    pub bundle: String,
+}
+
+/// The manifest of exported light-client bundles.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LightClientManifest {
+    /// The version of the bundle format.
+    pub version: u32,
+    /// The exported bundles, ordered by block number.
+    pub entries: Vec<LightClientEntry>,
+}
+
+/// Builds the light-client bundle of a proven block, fetching the proofs of the requested state
+/// after the block from an [`InputSource`]. Returns `None` if the block is not proven.
+pub async fn build_bundle(
+    db: &(impl KethStore + ?Sized),
+    source: &impl InputSource,
+    block_number: u64,
+    requests: &[StateProofRequest],
+) -> eyre::Result<Option<LightClientBundle>> {
+    let Some((metadata, proof)) = db.proof(block_number)? else { return Ok(None) };
+    let output = db
+        .program_output(block_number)?
+        .ok_or_else(|| eyre::eyre!("No program output found for block {block_number}"))?;
+
+    let requests = requests.iter().map(|request| (request.address, request.slots.clone()));
+    let accounts = source.accounts(block_number, requests.collect()).await?;
+    let bundle = LightClientBundle::new(metadata, proof, output, accounts);
+
+    // The bundle is only exported if it verifies, e.g. the source serves the state of the output.
+    bundle.verify()?;
+    Ok(Some(bundle))
+}
+
+/// Exports the light-client bundles of the proven blocks in `[from_block, to_block]` in `dir`,
+/// with the proofs of the requested state after each block.
+pub async fn export_bundles(
+    db: &(impl KethStore + ?Sized),
+    source: &impl InputSource,
+    dir: &Path,
+    from_block: u64,
+    to_block: u64,
+    requests: &[StateProofRequest],
+) -> eyre::Result<LightClientManifest> {
+    fs::create_dir_all(dir)?;
+
+    let mut entries = Vec::new();
+    for block_number in db.proven_blocks(from_block, to_block)? {
+        let Some(bundle) = build_bundle(db, source, block_number, requests).await? else {
+            continue;
+        };
+        let file_name = bundle.file_name();
+        fs::write(dir.join(&file_name), serde_json::to_vec_pretty(&bundle)?)?;
+        entries.push(LightClientEntry {
+            block_number,
+            post_state_root: bundle.output.post_state_root,
+            bundle: file_name,
+        });
+    }
+
+    let manifest = LightClientManifest { version: LIGHT_CLIENT_VERSION, entries };
+    fs::write(dir.join(LIGHT_CLIENT_MANIFEST), serde_json::to_vec_pretty(&manifest)?)?;
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::Database,
+        deferred::{
+            export_bundle, import_proofs, ProofEntry, ProofManifest, ProvingJob, PROOF_MANIFEST,
+        },
+        input::{
+            program_input::{BlockInput, HeaderInput, ProgramInput},
+            InputError,
+        },
+        retry::RetryPolicy,
+    };
+    use alloy_consensus::Header;
+    use alloy_trie::nodes::LeafNode;
+    use rusqlite::Connection;
+    use std::collections::BTreeMap;
+
+    /// An input source serving a single account, with its proofs.
+    struct AccountSource(AccountInput);
+
+    impl InputSource for AccountSource {
+        async fn account(
+            &self,
+            _block_number: u64,
+            address: Address,
+            _slots: Vec<B256>,
+        ) -> Result<AccountInput, InputError> {
+            assert_eq!(address, self.0.address);
+            Ok(self.0.clone())
+        }
+
+        async fn block_hash(&self, block_number: u64) -> Result<B256, InputError> {
+            Err(InputError::BlockNotFound(block_number))
+        }
+    }
+
+    /// Returns the root of a trie holding a single leaf, and the proof of the leaf.
+    fn single_leaf_trie(key: &[u8], value: Vec<u8>) -> (B256, Vec<Bytes>) {
+        let leaf = alloy_rlp::encode(LeafNode::new(Nibbles::unpack(keccak256(key)), value));
+        (keccak256(&leaf), vec![leaf.into()])
+    }
+
+    fn account() -> AccountInput {
+        let slot = B256::from(U256::from(1));
+        let (storage_root, storage_proof) =
+            single_leaf_trie(slot.as_slice(), alloy_rlp::encode(U256::from(42)));
+        AccountInput {
+            address: Address::repeat_byte(1),
+            nonce: 1,
+            balance: U256::from(100),
+            code_hash: KECCAK256_EMPTY,
+            storage_root,
+            storage: BTreeMap::from([(slot, U256::from(42))]),
+            storage_proofs: BTreeMap::from([(slot, storage_proof)]),
+            ..Default::default()
+        }
+    }
+
+    fn bundle() -> LightClientBundle {
+        let mut account = account();
+        let (state_root, account_proof) =
+            single_leaf_trie(account.address.as_slice(), encode_account(&account).unwrap());
+        account.account_proof = account_proof;
+
+        let output =
+            ProgramOutput { block_number: 7, post_state_root: state_root, ..Default::default() };
+        let metadata = ProofMetadata {
+            block_number: 7,
+            output_root: output.hash_tree_root(),
+            ..Default::default()
+        };
+        LightClientBundle::new(metadata, vec![1, 2, 3], output, vec![account])
+    }
+
+    #[test]
+    fn test_verify_bundle() {
+        let bundle = bundle();
+        assert_eq!(bundle.verify(), Ok(()));
+        assert_eq!(bundle.file_name(), "7.light.json");
+
+        let mut tampered = bundle.clone();
+        tampered.accounts[0].balance = U256::from(101);
+        assert_eq!(
+            tampered.verify(),
+            Err(LightClientError::InvalidAccountProof(Address::repeat_byte(1)))
+        );
+
+        let mut tampered = bundle.clone();
+        tampered.accounts[0].storage.insert(B256::from(U256::from(1)), U256::from(43));
+        assert!(matches!(tampered.verify(), Err(LightClientError::InvalidStorageProof { .. })));
+
+        let mut tampered = bundle.clone();
+        tampered.output.post_state_root = B256::repeat_byte(1);
+        assert!(matches!(tampered.verify(), Err(LightClientError::OutputRootMismatch { .. })));
+
+        let mut tampered = bundle;
+        tampered.fact = B256::ZERO;
+        assert!(matches!(tampered.verify(), Err(LightClientError::FactMismatch { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_build_bundle_deferred() {
+        let mut account = account();
+        let (state_root, account_proof) =
+            single_leaf_trie(account.address.as_slice(), encode_account(&account).unwrap());
+        account.account_proof = account_proof;
+
+        // The block is enqueued in deferred mode with its program input, then exported.
+        let db = Database::new(Connection::open_in_memory().unwrap()).unwrap();
+        let header = Header {
+            number: 7,
+            state_root,
+            gas_limit: 30_000_000,
+            base_fee_per_gas: Some(7),
+            ..Default::default()
+        };
+        let input = ProgramInput {
+            block: BlockInput {
+                block_header: HeaderInput::from(&header),
+                transactions: Vec::new(),
+            },
+            state: BTreeMap::new(),
+            chain_id: 1,
+            system_calls: Vec::new(),
+            block_hashes: BTreeMap::new(),
+        };
+        let program = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../cairo/programs/os.json");
+        db.insert_program_input(7, &input).unwrap();
+        db.enqueue_proving_job(&ProvingJob::new(7, program)).unwrap();
+        let bundle_dir = tempfile::tempdir().unwrap();
+        export_bundle(&db, &db, bundle_dir.path(), 1).unwrap();
+
+        // The proof of the exported run commits to its recorded output.
+        let output = db.program_output(7).unwrap().unwrap();
+        assert_eq!(output.post_state_root, state_root);
+        let metadata = ProofMetadata {
+            block_number: 7,
+            output_root: output.hash_tree_root(),
+            ..Default::default()
+        };
+        let entry = ProofEntry { metadata, proof: "7.proof".to_string(), fact: None };
+        let manifest = ProofManifest { proofs: vec![entry], failures: vec![] };
+        let proofs_dir = tempfile::tempdir().unwrap();
+        fs::write(proofs_dir.path().join(PROOF_MANIFEST), serde_json::to_vec(&manifest).unwrap())
+            .unwrap();
+        fs::write(proofs_dir.path().join("7.proof"), b"proof").unwrap();
+        import_proofs(&db, &db, proofs_dir.path(), None, None, &RetryPolicy::default()).unwrap();
+
+        let request = StateProofRequest {
+            address: account.address,
+            slots: account.storage.keys().copied().collect(),
+        };
+        let bundle =
+            build_bundle(&db, &AccountSource(account), 7, &[request]).await.unwrap().unwrap();
+        assert_eq!(bundle.output, output);
+        assert_eq!(bundle.proof, Bytes::from_static(b"proof"));
+        assert_eq!(bundle.verify(), Ok(()));
+    }
+
+    #[test]
+    fn test_parse_request() {
+        let request: StateProofRequest =
+            "0x0101010101010101010101010101010101010101:0x01,2".parse().unwrap();
+        assert_eq!(request.address, Address::repeat_byte(1));
+        assert_eq!(request.slots, vec![B256::from(U256::from(1)), B256::from(U256::from(2))]);
+
+        let request: StateProofRequest =
+            "0x0101010101010101010101010101010101010101".parse().unwrap();
+        assert!(request.slots.is_empty());
+
+        assert!(matches!(
+            "0x01:0x01".parse::<StateProofRequest>(),
+            Err(LightClientError::InvalidRequest(_))
+        ));
+    }
+}
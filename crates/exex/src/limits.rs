@@ -0,0 +1,416 @@
+use crate::{
+    attribution::felt_to_usize,
+    runner::register_builtins,
+    serde::{dump::MemoryDump, KakarotSerde},
+    tuning::RunnerTuning,
+};
+use cairo_vm::{
+    cairo_run::CairoRunConfig,
+    hint_processor::hint_processor_definition::HintProcessor,
+    types::{program::Program, relocatable::Relocatable},
+    vm::{
+        errors::{cairo_run_errors::CairoRunError, vm_errors::VirtualMachineError},
+        runners::{builtin_runner::BuiltinRunner, cairo_runner::CairoRunner},
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// The number of steps run between two checks of the limits.
+pub const CHECK_INTERVAL_STEPS: usize = 100_000;
+
+/// The maximum number of retries of a block exceeding its limits, each retry doubling them.
+pub const MAX_LIMIT_RETRIES: u32 = 3;
+
+/// The maximum number of frames walked when capturing the call stack.
+const MAX_FRAMES: usize = 256;
+
+/// The full name of the Cairo function executing a single EVM opcode.
+pub const EXEC_OPCODE_FUNCTION: &str = "src.interpreter.Interpreter.exec_opcode";
+
+/// The full name of the `model.EVM` struct.
+const EVM_STRUCT: &str = "src.model.model.EVM";
+
+/// The full name of the `model.Message` struct.
+const MESSAGE_STRUCT: &str = "src.model.model.Message";
+
+/// The limits of the execution of a block in the Cairo VM.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionLimits {
+    /// The maximum number of Cairo steps, unlimited when `None`.
+    pub max_steps: Option<usize>,
+    /// The maximum wall-clock duration of the execution, unlimited when `None`.
+    pub timeout: Option<Duration>,
+}
+
+impl ExecutionLimits {
+    /// Returns the limits doubled, used to retry the blocks that exceeded them, or `None` once the
+    /// configured `base` limits were doubled [`MAX_LIMIT_RETRIES`] times.
+    pub fn relaxed(&self, base: &Self) -> Option<Self> {
+        let factor = 1 << MAX_LIMIT_RETRIES;
+        let relaxed = Self {
+            max_steps: self.max_steps.map(|steps| steps.saturating_mul(2)),
+            timeout: self.timeout.map(|timeout| timeout.saturating_mul(2)),
+        };
+        let steps_within = match (relaxed.max_steps, base.max_steps) {
+            (Some(steps), Some(base)) => steps <= base.saturating_mul(factor as usize),
+            _ => true,
+        };
+        let timeout_within = match (relaxed.timeout, base.timeout) {
+            (Some(timeout), Some(base)) => timeout <= base.saturating_mul(factor),
+            _ => true,
+        };
+        (steps_within && timeout_within).then_some(relaxed)
+    }
+
+    /// Returns the limit exceeded after running `steps` steps during `elapsed`, if any.
+    pub fn exceeded(&self, steps: usize, elapsed: Duration) -> Option<ExceededLimit> {
+        if self.max_steps.is_some_and(|max_steps| steps >= max_steps) {
+            return Some(ExceededLimit::Steps);
+        }
+        if self.timeout.is_some_and(|timeout| elapsed >= timeout) {
+            return Some(ExceededLimit::WallClock);
+        }
+        None
+    }
+}
+
+/// A limit of [`ExecutionLimits`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExceededLimit {
+    /// The maximum number of steps.
+    Steps,
+    /// The wall-clock timeout.
+    WallClock,
+}
+
+/// The diagnostics of an execution interrupted by its limits.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostics {
+    /// The exceeded limit.
+    pub limit: ExceededLimit,
+    /// The number of steps run.
+    pub steps: usize,
+    /// The wall-clock duration of the execution.
+    pub elapsed: Duration,
+    /// The pc when the execution was interrupted.
+    pub pc: Relocatable,
+    /// The Cairo call stack, from the innermost frame, as function names.
+    pub frames: Vec<String>,
+    /// The last EVM opcode being executed, if any.
+    pub last_opcode: Option<u8>,
+}
+
+/// An execution interrupted by its limits.
+#[derive(Debug, Clone)]
+pub struct PartialRun {
+    /// The diagnostics of the interruption.
+    pub diagnostics: Diagnostics,
+    /// The memory written before the interruption.
+    pub dump: MemoryDump,
+}
+
+/// The outcome of [`run_with_limits`].
+#[allow(missing_debug_implementations, clippy::large_enum_variant)]
+pub enum LimitedRun {
+    /// The execution completed, the runner is ended and relocated as by
+    /// [`cairo_vm::cairo_run::cairo_run`].
+    Completed(CairoRunner),
+    /// The execution was interrupted by its limits.
+    Interrupted(Box<PartialRun>),
+    /// The execution was preempted, e.g. by a job of higher priority, and must be run again.
+    Preempted,
+}
+
+/// Runs a program as [`cairo_vm::cairo_run::cairo_run`] does, interrupting it when it exceeds the
+/// given limits.
+///
+/// The limits are checked every [`CHECK_INTERVAL_STEPS`] steps, so that the wall-clock timeout can
+/// be exceeded by the duration of a check interval. `preempt` is called at the same interval, the
+/// execution being abandoned without capture when it returns `true`.
+///
+/// The pools of the runner are pre-sized by `tuning` once it is initialized.
+pub fn run_with_limits(
+    program: &[u8],
+    config: &CairoRunConfig<'_>,
+    hint_processor: &mut dyn HintProcessor,
+    limits: &ExecutionLimits,
+    tuning: &RunnerTuning,
+    preempt: &mut dyn FnMut() -> bool,
+) -> Result<LimitedRun, CairoRunError> {
+    let program = Program::from_bytes(program, Some(config.entrypoint))?;
+    let (mut runner, end) = initialize_runner(&program, config, &[])?;
+    tuning.apply(&mut runner, config);
+    run_until_end(runner, end, config, hint_processor, limits, preempt)
+}
+
+/// Creates and initializes the runner of a program, with the given builtin runners registered as
+/// by [`register_builtins`], returning it with the pc at which its execution ends.
+pub(crate) fn initialize_runner(
+    program: &Program,
+    config: &CairoRunConfig<'_>,
+    builtins: &[BuiltinRunner],
+) -> Result<(CairoRunner, Relocatable), CairoRunError> {
+    let allow_missing_builtins = config.allow_missing_builtins.unwrap_or(config.proof_mode);
+
+    let mut runner =
+        CairoRunner::new(program, config.layout, config.proof_mode, config.trace_enabled)?;
+    let end = runner.initialize(allow_missing_builtins)?;
+    register_builtins(&mut runner, builtins);
+    Ok((runner, end))
+}
+
+/// Runs an initialized runner until `end` as [`run_with_limits`], the limits applying to the
+/// steps run by this call only.
+pub(crate) fn run_until_end(
+    mut runner: CairoRunner,
+    end: Relocatable,
+    config: &CairoRunConfig<'_>,
+    hint_processor: &mut dyn HintProcessor,
+    limits: &ExecutionLimits,
+    preempt: &mut dyn FnMut() -> bool,
+) -> Result<LimitedRun, CairoRunError> {
+    let allow_missing_builtins = config.allow_missing_builtins.unwrap_or(config.proof_mode);
+    let start = Instant::now();
+    let mut steps = 0;
+    while runner.vm.get_pc() != end {
+        if steps > 0 && preempt() {
+            return Ok(LimitedRun::Preempted);
+        }
+        if let Some(limit) = limits.exceeded(steps, start.elapsed()) {
+            let partial = PartialRun::capture(runner, limit, steps, start.elapsed());
+            return Ok(LimitedRun::Interrupted(Box::new(partial)));
+        }
+
+        let remaining = limits.max_steps.map_or(usize::MAX, |max_steps| max_steps - steps);
+        let chunk = remaining.min(CHECK_INTERVAL_STEPS);
+        match runner.run_for_steps(chunk, hint_processor) {
+            Ok(()) => steps += chunk,
+            Err(VirtualMachineError::EndOfProgram(remaining)) => {
+                steps += chunk - remaining;
+                break;
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    // The step following the end of the program, run by `cairo_run` in proof mode.
+    if config.proof_mode {
+        runner.run_for_steps(1, hint_processor)?;
+    }
+    runner.end_run(config.disable_trace_padding, false, hint_processor)?;
+    runner.vm.verify_auto_deductions()?;
+    runner.read_return_values(allow_missing_builtins)?;
+    if config.proof_mode {
+        runner.finalize_segments()?;
+    }
+    runner.relocate(config.relocate_mem)?;
+
+    Ok(LimitedRun::Completed(runner))
+}
+
+impl PartialRun {
+    /// Captures the diagnostics and the memory of an interrupted runner.
+    fn capture(runner: CairoRunner, limit: ExceededLimit, steps: usize, elapsed: Duration) -> Self {
+        let program = runner.get_program();
+        let frames = call_stack(&runner);
+        let evm = frames
+            .iter()
+            .find(|(name, _)| name == EXEC_OPCODE_FUNCTION)
+            .and_then(|(_, fp)| runner.vm.get_relocatable((*fp - 3).ok()?).ok());
+        let last_opcode = evm.and_then(|evm| last_opcode(&runner, program, evm));
+
+        let diagnostics = Diagnostics {
+            limit,
+            steps,
+            elapsed,
+            pc: runner.vm.get_pc(),
+            frames: frames.into_iter().map(|(name, _)| name).collect(),
+            last_opcode,
+        };
+
+        // The memory is annotated from the EVM being executed, if any.
+        let mut serde = KakarotSerde::new(runner);
+        let roots: Vec<_> = evm.map(|evm| (evm, EVM_STRUCT)).into_iter().collect();
+        let dump =
+            serde.dump_memory(&roots).or_else(|_| serde.dump_memory(&[])).unwrap_or_default();
+
+        Self { diagnostics, dump }
+    }
+}
+
+/// Returns the Cairo call stack of the runner, from the innermost frame, as the function names
+/// and frame pointers of the frames.
+///
+/// The stack is walked through the `[fp - 2]` (caller frame pointer) and `[fp - 1]` (return pc)
+/// cells of each frame.
+fn call_stack(runner: &CairoRunner) -> Vec<(String, Relocatable)> {
+    let program = runner.get_program();
+    let mut functions: Vec<_> = program
+        .iter_identifiers()
+        .filter(|(_, identifier)| identifier.type_.as_deref() == Some("function"))
+        .filter_map(|(name, identifier)| Some((identifier.pc?, name.to_string())))
+        .collect();
+    functions.sort();
+
+    let function_at = |pc: Relocatable| {
+        let index = functions.partition_point(|(start, _)| *start <= pc.offset);
+        index.checked_sub(1).map_or_else(|| pc.to_string(), |index| functions[index].1.clone())
+    };
+
+    let mut frames = Vec::new();
+    let (mut pc, mut fp) = (runner.vm.get_pc(), runner.vm.get_fp());
+    while frames.len() < MAX_FRAMES {
+        frames.push((function_at(pc), fp));
+
+        let caller = (fp - 2).ok().and_then(|address| runner.vm.get_relocatable(address).ok());
+        let return_pc = (fp - 1).ok().and_then(|address| runner.vm.get_relocatable(address).ok());
+        match (caller, return_pc) {
+            (Some(caller), Some(return_pc)) if caller < fp => (pc, fp) = (return_pc, caller),
+            _ => break,
+        }
+    }
+    frames
+}
+
+/// Returns the opcode at the program counter of the given `model.EVM`.
+fn last_opcode(runner: &CairoRunner, program: &Program, evm: Relocatable) -> Option<u8> {
+    let offset = |struct_name: &str, member: &str| {
+        let identifier = program.get_identifier(struct_name)?;
+        identifier.members.as_ref()?.get(member).map(|member| member.offset)
+    };
+    let read_felt = |address: Relocatable| {
+        runner.vm.get_integer(address).ok().and_then(|value| felt_to_usize(&value))
+    };
+
+    let message = runner.vm.get_relocatable((evm + offset(EVM_STRUCT, "message")?).ok()?).ok()?;
+    let program_counter = read_felt((evm + offset(EVM_STRUCT, "program_counter")?).ok()?)?;
+    let bytecode =
+        runner.vm.get_relocatable((message + offset(MESSAGE_STRUCT, "bytecode")?).ok()?).ok()?;
+    read_felt((bytecode + program_counter).ok()?).and_then(|opcode| u8::try_from(opcode).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hints::KakarotHintProcessor;
+    use cairo_vm::types::layout_name::LayoutName;
+
+    fn config() -> CairoRunConfig<'static> {
+        CairoRunConfig { layout: LayoutName::all_cairo, ..Default::default() }
+    }
+
+    #[test]
+    fn test_exceeded_limits() {
+        let limits = ExecutionLimits { max_steps: Some(10), timeout: Some(Duration::from_secs(1)) };
+
+        assert_eq!(limits.exceeded(9, Duration::ZERO), None);
+        assert_eq!(limits.exceeded(10, Duration::ZERO), Some(ExceededLimit::Steps));
+        assert_eq!(limits.exceeded(0, Duration::from_secs(2)), Some(ExceededLimit::WallClock));
+        assert_eq!(ExecutionLimits::default().exceeded(usize::MAX, Duration::MAX), None);
+        assert_eq!(
+            limits.relaxed(&limits),
+            Some(ExecutionLimits { max_steps: Some(20), timeout: Some(Duration::from_secs(2)) })
+        );
+    }
+
+    #[test]
+    fn test_relaxed_limits_capped() {
+        let base = ExecutionLimits { max_steps: Some(10), timeout: None };
+
+        let mut limits = base;
+        for _ in 0..MAX_LIMIT_RETRIES {
+            limits = limits.relaxed(&base).unwrap();
+        }
+        assert_eq!(limits.max_steps, Some(80));
+        assert_eq!(limits.relaxed(&base), None);
+        assert_eq!(ExecutionLimits::default().relaxed(&base), Some(ExecutionLimits::default()));
+    }
+
+    #[test]
+    fn test_run_with_limits_interrupted() {
+        let program = include_bytes!("../testdata/keccak_add_uint256.json");
+        let mut hint_processor = KakarotHintProcessor::default();
+        let limits = ExecutionLimits { max_steps: Some(5), timeout: None };
+        let tuning = RunnerTuning::default();
+
+        let result =
+            run_with_limits(program, &config(), &mut hint_processor, &limits, &tuning, &mut || {
+                false
+            });
+        let LimitedRun::Interrupted(partial) = result.unwrap() else {
+            panic!("Expected an interrupted run");
+        };
+
+        assert_eq!(partial.diagnostics.limit, ExceededLimit::Steps);
+        assert_eq!(partial.diagnostics.steps, 5);
+        assert!(!partial.diagnostics.frames.is_empty());
+        assert_eq!(partial.diagnostics.last_opcode, None);
+        assert!(!partial.dump.segments.is_empty());
+    }
+
+    #[test]
+    fn test_run_with_limits_completed() {
+        let program = include_bytes!("../testdata/keccak_add_uint256.json");
+        let mut hint_processor = KakarotHintProcessor::default();
+
+        let result = run_with_limits(
+            program,
+            &config(),
+            &mut hint_processor,
+            &ExecutionLimits::default(),
+            &RunnerTuning::default(),
+            &mut || false,
+        )
+        .unwrap();
+
+        assert!(matches!(result, LimitedRun::Completed(_)));
+    }
+
+    #[test]
+    fn test_run_with_limits_proof_mode() {
+        let program = include_bytes!("../testdata/keccak_add_uint256.json");
+        let config = CairoRunConfig { proof_mode: true, ..config() };
+
+        let mut hint_processor = KakarotHintProcessor::default();
+        let expected =
+            cairo_vm::cairo_run::cairo_run(program, &config, &mut hint_processor).unwrap();
+        let mut hint_processor = KakarotHintProcessor::default();
+        let result = run_with_limits(
+            program,
+            &config,
+            &mut hint_processor,
+            &ExecutionLimits::default(),
+            &RunnerTuning::default(),
+            &mut || false,
+        )
+        .unwrap();
+
+        // The run is padded as by `cairo_run`, from the step following the end of the program.
+        let LimitedRun::Completed(runner) = result else { panic!("Expected a completed run") };
+        assert_eq!(
+            runner.get_execution_resources().unwrap(),
+            expected.get_execution_resources().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_run_with_limits_preempted() {
+        let program = include_bytes!("../testdata/keccak_add_uint256.json");
+        let mut hint_processor = KakarotHintProcessor::default();
+        let limits = ExecutionLimits { max_steps: Some(10), timeout: None };
+        let tuning = RunnerTuning::default();
+
+        // A chunk of steps always runs before the first preemption check.
+        let mut checks = 0;
+        let result =
+            run_with_limits(program, &config(), &mut hint_processor, &limits, &tuning, &mut || {
+                checks += 1;
+                true
+            })
+            .unwrap();
+
+        assert!(matches!(result, LimitedRun::Preempted));
+        assert_eq!(checks, 1);
+    }
+}
@@ -1,5 +1,5 @@
 use alloy_consensus::Header;
-use alloy_primitives::{Address, Bloom, Bytes, B256, B64, U256};
+use alloy_primitives::{Address, Bloom, Bytes, SignatureError, B256, B64, U256};
 use alloy_rlp::Encodable;
 use cairo_vm::{types::relocatable::MaybeRelocatable, Felt252};
 use reth_primitives::{Signature, Transaction, TransactionSigned, TransactionSignedEcRecovered};
@@ -16,6 +16,36 @@ pub enum ConversionError {
     /// Error indicating the failure to recover the signer from the transaction.
     #[error("Failed to recover signer from transaction")]
     TransactionSigner,
+
+    /// Error indicating that an integer was expected but a relocatable was found.
+    #[error("Expected an integer, found a relocatable")]
+    NotAnInteger,
+
+    /// Error indicating that a value does not fit in the target Ethereum type.
+    #[error("Value {value} does not fit in {size} bytes")]
+    ValueOverflow {
+        /// The overflowing value.
+        value: Felt252,
+        /// The size in bytes of the target type.
+        size: usize,
+    },
+
+    /// Error indicating that the `is_some` flag of an option is neither `0` nor `1`.
+    #[error("Invalid option flag {0}, expected 0 or 1")]
+    InvalidOptionFlag(u8),
+
+    /// Error indicating that a pointer does not hold the expected number of elements.
+    #[error("Expected {expected} elements, found {actual}")]
+    InvalidLength {
+        /// The expected number of elements.
+        expected: usize,
+        /// The actual number of elements.
+        actual: usize,
+    },
+
+    /// Error indicating that the signature components are invalid.
+    #[error(transparent)]
+    Signature(#[from] SignatureError),
 }
 
 /// A custom wrapper around [`MaybeRelocatable`] for the Keth execution environment.
@@ -95,6 +125,24 @@ impl KethMaybeRelocatable {
     pub fn from_bytes_be_slice(bytes: &[u8]) -> Self {
         Felt252::from_bytes_be_slice(bytes).into()
     }
+
+    /// Returns the underlying [`Felt252`] if the value is an integer, `None` if it is a relocatable
+    /// address.
+    pub fn as_felt(&self) -> Option<&Felt252> {
+        self.0.get_int_ref()
+    }
+
+    /// Returns the `N` lower bytes of the integer in big-endian order.
+    ///
+    /// Fails if the value is a relocatable or if the integer does not fit in `N` bytes.
+    fn to_be_bytes_checked<const N: usize>(&self) -> Result<[u8; N], ConversionError> {
+        let felt = self.as_felt().ok_or(ConversionError::NotAnInteger)?;
+        let bytes = felt.to_bytes_be();
+        if bytes[..bytes.len() - N].iter().any(|byte| *byte != 0) {
+            return Err(ConversionError::ValueOverflow { value: *felt, size: N });
+        }
+        Ok(bytes[bytes.len() - N..].try_into().expect("slice has N bytes"))
+    }
 }
 
 impl From<Felt252> for KethMaybeRelocatable {
@@ -133,6 +181,36 @@ impl From<Address> for KethMaybeRelocatable {
     }
 }
 
+impl From<&KethMaybeRelocatable> for MaybeRelocatable {
+    fn from(value: &KethMaybeRelocatable) -> Self {
+        value.0.clone()
+    }
+}
+
+impl TryFrom<&KethMaybeRelocatable> for u8 {
+    type Error = ConversionError;
+
+    fn try_from(value: &KethMaybeRelocatable) -> Result<Self, Self::Error> {
+        Ok(Self::from_be_bytes(value.to_be_bytes_checked()?))
+    }
+}
+
+impl TryFrom<&KethMaybeRelocatable> for u64 {
+    type Error = ConversionError;
+
+    fn try_from(value: &KethMaybeRelocatable) -> Result<Self, Self::Error> {
+        Ok(Self::from_be_bytes(value.to_be_bytes_checked()?))
+    }
+}
+
+impl TryFrom<&KethMaybeRelocatable> for Address {
+    type Error = ConversionError;
+
+    fn try_from(value: &KethMaybeRelocatable) -> Result<Self, Self::Error> {
+        Ok(Self::from(value.to_be_bytes_checked::<20>()?))
+    }
+}
+
 /// [`KethOption`] is a custom representation of a Rust [`Option<T>`] type where `T` can be
 /// any type, such as [`KethMaybeRelocatable`] or [`KethU256`].
 ///
@@ -184,6 +262,33 @@ where
     }
 }
 
+impl<T> KethOption<T> {
+    /// Returns the value of the option if its `is_some` flag is set.
+    fn try_as_option(&self) -> Result<Option<&T>, ConversionError> {
+        match u8::try_from(&self.is_some)? {
+            0 => Ok(None),
+            1 => Ok(Some(&self.value)),
+            flag => Err(ConversionError::InvalidOptionFlag(flag)),
+        }
+    }
+}
+
+impl TryFrom<&KethOption<KethMaybeRelocatable>> for Option<u64> {
+    type Error = ConversionError;
+
+    fn try_from(value: &KethOption<KethMaybeRelocatable>) -> Result<Self, Self::Error> {
+        value.try_as_option()?.map(u64::try_from).transpose()
+    }
+}
+
+impl TryFrom<&KethOption<KethU256>> for Option<B256> {
+    type Error = ConversionError;
+
+    fn try_from(value: &KethOption<KethU256>) -> Result<Self, Self::Error> {
+        value.try_as_option()?.map(B256::try_from).transpose()
+    }
+}
+
 /// [`KethU256`] represents a 256-bit unsigned integer used within the Keth model.
 ///
 /// This struct is designed to encapsulate two components of a 256-bit number:
@@ -225,6 +330,24 @@ impl From<B256> for KethU256 {
     }
 }
 
+impl TryFrom<&KethU256> for B256 {
+    type Error = ConversionError;
+
+    fn try_from(value: &KethU256) -> Result<Self, Self::Error> {
+        let high = value.high.to_be_bytes_checked::<U128_BYTES_SIZE>()?;
+        let low = value.low.to_be_bytes_checked::<U128_BYTES_SIZE>()?;
+        Ok(Self::from_slice(&[high, low].concat()))
+    }
+}
+
+impl TryFrom<&KethU256> for U256 {
+    type Error = ConversionError;
+
+    fn try_from(value: &KethU256) -> Result<Self, Self::Error> {
+        Ok(B256::try_from(value)?.into())
+    }
+}
+
 impl From<U256> for KethU256 {
     fn from(value: U256) -> Self {
         Self {
@@ -266,6 +389,23 @@ impl Default for KethPointer {
     }
 }
 
+impl KethPointer {
+    /// Returns the length of the pointed data.
+    pub const fn length(&self) -> &KethMaybeRelocatable {
+        &self.len
+    }
+
+    /// Returns the pointed data.
+    pub fn data(&self) -> &[KethMaybeRelocatable] {
+        &self.data
+    }
+
+    /// Returns the size of the underlying Cairo struct.
+    pub const fn type_size(&self) -> usize {
+        self.type_size
+    }
+}
+
 impl From<Bloom> for KethPointer {
     /// Converts a [`Bloom`] filter into a [`KethPointer`] structure.
     ///
@@ -297,6 +437,26 @@ impl From<Bloom> for KethPointer {
     }
 }
 
+impl TryFrom<&KethPointer> for Bloom {
+    type Error = ConversionError;
+
+    /// Converts a [`KethPointer`] holding 16 chunks of [`U128_BYTES_SIZE`] bytes back into a
+    /// [`Bloom`] filter.
+    fn try_from(value: &KethPointer) -> Result<Self, Self::Error> {
+        let expected = Self::len_bytes() / U128_BYTES_SIZE;
+        if value.data.len() != expected {
+            return Err(ConversionError::InvalidLength { expected, actual: value.data.len() });
+        }
+
+        let bytes = value
+            .data
+            .iter()
+            .map(KethMaybeRelocatable::to_be_bytes_checked::<U128_BYTES_SIZE>)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::from_slice(&bytes.concat()))
+    }
+}
+
 impl From<Bytes> for KethPointer {
     /// Converts a [`Bytes`] object into a [`KethPointer`] structure.
     ///
@@ -335,6 +495,20 @@ impl From<Bytes> for KethPointer {
     }
 }
 
+impl TryFrom<&KethPointer> for Bytes {
+    type Error = ConversionError;
+
+    /// Converts a [`KethPointer`] holding one byte per felt back into [`Bytes`].
+    fn try_from(value: &KethPointer) -> Result<Self, Self::Error> {
+        let len = u64::try_from(&value.len)? as usize;
+        if value.data.len() != len {
+            return Err(ConversionError::InvalidLength { expected: len, actual: value.data.len() });
+        }
+
+        value.data.iter().map(u8::try_from).collect()
+    }
+}
+
 impl From<Signature> for KethPointer {
     /// Converts a [`Signature`] into a [`KethPointer`].
     ///
@@ -383,6 +557,22 @@ impl From<Signature> for KethPointer {
     }
 }
 
+impl TryFrom<&KethPointer> for Signature {
+    type Error = ConversionError;
+
+    /// Converts a [`KethPointer`] holding `[r.low, r.high, s.low, s.high, v]` back into a
+    /// [`Signature`].
+    fn try_from(value: &KethPointer) -> Result<Self, Self::Error> {
+        let [r_low, r_high, s_low, s_high, v] = value.data.as_slice() else {
+            return Err(ConversionError::InvalidLength { expected: 5, actual: value.data.len() });
+        };
+
+        let r = U256::try_from(&KethU256 { low: r_low.clone(), high: r_high.clone() })?;
+        let s = U256::try_from(&KethU256 { low: s_low.clone(), high: s_high.clone() })?;
+        Ok(Self::from_rs_and_parity(r, s, u64::try_from(v)?)?)
+    }
+}
+
 impl From<Transaction> for KethPointer {
     /// Converts a [`Transaction`] into a [`KethPointer`].
     ///
@@ -500,6 +690,38 @@ impl From<Header> for KethBlockHeader {
     }
 }
 
+impl TryFrom<&KethBlockHeader> for Header {
+    type Error = ConversionError;
+
+    /// Reconstructs the [`Header`] from a [`KethBlockHeader`], typically read back from the Cairo
+    /// memory.
+    fn try_from(value: &KethBlockHeader) -> Result<Self, Self::Error> {
+        Ok(Self {
+            parent_hash: (&value.parent_hash).try_into()?,
+            ommers_hash: (&value.ommers_hash).try_into()?,
+            beneficiary: (&value.coinbase).try_into()?,
+            state_root: (&value.state_root).try_into()?,
+            transactions_root: (&value.transactions_root).try_into()?,
+            receipts_root: (&value.receipt_root).try_into()?,
+            withdrawals_root: (&value.withdrawals_root).try_into()?,
+            logs_bloom: (&value.bloom).try_into()?,
+            difficulty: (&value.difficulty).try_into()?,
+            number: (&value.number).try_into()?,
+            gas_limit: (&value.gas_limit).try_into()?,
+            gas_used: (&value.gas_used).try_into()?,
+            timestamp: (&value.timestamp).try_into()?,
+            mix_hash: (&value.mix_hash).try_into()?,
+            nonce: u64::try_from(&value.nonce)?.into(),
+            base_fee_per_gas: (&value.base_fee_per_gas).try_into()?,
+            blob_gas_used: (&value.blob_gas_used).try_into()?,
+            excess_blob_gas: (&value.excess_blob_gas).try_into()?,
+            parent_beacon_block_root: (&value.parent_beacon_block_root).try_into()?,
+            requests_root: (&value.requests_root).try_into()?,
+            extra_data: (&value.extra_data).try_into()?,
+        })
+    }
+}
+
 /// [`KethTransactionEncoded`] represents an encoded Ethereum transaction.
 ///
 /// This struct holds three components of a transaction:
@@ -518,6 +740,23 @@ pub struct KethTransactionEncoded {
     sender: KethMaybeRelocatable,
 }
 
+impl KethTransactionEncoded {
+    /// Returns the RLP encoding of the unsigned transaction.
+    pub const fn rlp(&self) -> &KethPointer {
+        &self.rlp
+    }
+
+    /// Returns the signature of the transaction.
+    pub const fn signature(&self) -> &KethPointer {
+        &self.signature
+    }
+
+    /// Returns the address of the sender.
+    pub const fn sender(&self) -> &KethMaybeRelocatable {
+        &self.sender
+    }
+}
+
 impl TryFrom<TransactionSigned> for KethTransactionEncoded {
     type Error = ConversionError;
 
@@ -836,6 +1075,22 @@ mod tests {
             prop_assert_eq!(final_header, original_header);
         }
 
+        #[test]
+        fn test_header_try_from_keth_block_header(raw_bytes in any::<[u8; 1000]>()) {
+            let mut unstructured = Unstructured::new(&raw_bytes);
+
+            // Generate an arbitrary Header using Arbitrary
+            let original_header = Header::arbitrary(&mut unstructured).expect("Failed to generate arbitrary Header");
+
+            // Convert it into a KethBlockHeader and reconstruct it
+            let keth_header: KethBlockHeader = original_header.clone().into();
+            let reconstructed = Header::try_from(&keth_header).unwrap();
+
+            // The reconstructed header hashes to the original one
+            prop_assert_eq!(reconstructed.hash_slow(), original_header.hash_slow());
+            prop_assert_eq!(reconstructed, original_header);
+        }
+
         #[test]
         fn test_transaction_to_rlp_encoded(raw_bytes in any::<[u8; 1000]>()) {
             let mut unstructured = Unstructured::new(&raw_bytes);
@@ -1075,4 +1330,42 @@ mod tests {
         assert_eq!(keth_pointer.type_size, 1);
         assert_eq!(keth_pointer.data.len(), 16);
     }
+
+    #[test]
+    fn test_keth_option_invalid_flag() {
+        let option = KethOption { is_some: 2u8.into(), value: KethMaybeRelocatable::zero() };
+        assert!(matches!(
+            Option::<u64>::try_from(&option),
+            Err(ConversionError::InvalidOptionFlag(2))
+        ));
+    }
+
+    #[test]
+    fn test_keth_u256_limb_overflow() {
+        // A limb must hold at most 128 bits.
+        let value = KethU256 {
+            low: KethMaybeRelocatable::from_bytes_be_slice(&[0xff; 17]),
+            high: KethMaybeRelocatable::zero(),
+        };
+        assert!(matches!(
+            U256::try_from(&value),
+            Err(ConversionError::ValueOverflow { size: U128_BYTES_SIZE, .. })
+        ));
+    }
+
+    #[test]
+    fn test_bytes_try_from_keth_pointer_length_mismatch() {
+        let pointer = KethPointer { len: 2usize.into(), data: vec![1u8.into()], type_size: 1 };
+        assert!(matches!(
+            Bytes::try_from(&pointer),
+            Err(ConversionError::InvalidLength { expected: 2, actual: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_signature_try_from_keth_pointer_roundtrip() {
+        let signature = Signature::from_rs_and_parity(U256::from(1), U256::from(2), 27u64).unwrap();
+        let pointer = KethPointer::from(signature);
+        assert_eq!(Signature::try_from(&pointer).unwrap(), signature);
+    }
 }
@@ -0,0 +1,452 @@
+//! Export of the metrics and the tracing spans of the pipeline with OpenTelemetry (OTLP).
+//!
+//! The pipeline records its metrics with the [`metrics`] facade and its spans with `tracing`, see
+//! [`crate::telemetry`]. With an [`OtlpConfig`], they are exported over gRPC to OTLP collectors:
+//! - the metrics by an [`OtlpRecorder`], installed as the global recorder of the facade, which
+//!   forwards them to an OpenTelemetry meter exported periodically;
+//! - the spans by the [`Tracer`] of [`Otlp::tracer`], to be layered on the subscriber with
+//!   `tracing-opentelemetry`.
+//!
+//! The exported data carries the resource attributes of the config, along with the ones of the
+//! `OTEL_RESOURCE_ATTRIBUTES` environment variable. The exporters run on their own runtime, so that
+//! they can be installed before the runtime of the node, and are flushed when [`Otlp`] is dropped.
+
+use metrics::{
+    Counter, CounterFn, Gauge, GaugeFn, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+    SharedString, Unit,
+};
+use opentelemetry::{
+    metrics::{Meter, MeterProvider as _},
+    trace::TracerProvider as _,
+    KeyValue,
+};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{
+    metrics::SdkMeterProvider,
+    runtime,
+    trace::{Config, Tracer, TracerProvider},
+    Resource,
+};
+use reth_tracing::tracing::warn;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use thiserror::Error;
+use tokio::runtime::Runtime;
+
+/// The default name of the service in the resource attributes.
+pub const SERVICE_NAME: &str = "keth";
+
+/// The name of the instrumentation scope of the metrics and spans.
+const SCOPE: &str = "kakarot-exex";
+
+/// Represents errors that can occur when exporting the telemetry with OTLP.
+#[derive(Debug, Error)]
+pub enum OtlpError {
+    /// Error variant indicating an invalid resource attribute, expected as `key=value`.
+    #[error("Invalid resource attribute '{0}', expected 'key=value'")]
+    InvalidAttribute(String),
+
+    /// Error variant indicating a failure of the trace exporter.
+    #[error(transparent)]
+    Trace(#[from] opentelemetry::trace::TraceError),
+
+    /// Error variant indicating a failure of the metrics exporter.
+    #[error(transparent)]
+    Metrics(#[from] opentelemetry::metrics::MetricsError),
+
+    /// Error variant indicating that a metrics recorder is already installed.
+    #[error("A metrics recorder is already installed")]
+    RecorderInstalled,
+
+    /// Error variant indicating a failure to start the runtime of the exporters.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Parses a resource attribute from `key=value`.
+pub fn parse_attribute(s: &str) -> Result<(String, String), OtlpError> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(OtlpError::InvalidAttribute(s.to_string())),
+    }
+}
+
+/// The configuration of the OTLP exporters, each one being disabled without an endpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OtlpConfig {
+    /// The endpoint of the collector of the spans, e.g. `http://localhost:4317`.
+    pub traces_endpoint: Option<String>,
+    /// The endpoint of the collector of the metrics.
+    pub metrics_endpoint: Option<String>,
+    /// The interval between two exports of the metrics.
+    pub metrics_interval: Duration,
+    /// The name of the service in the resource attributes.
+    pub service_name: String,
+    /// The additional resource attributes, e.g. `deployment.environment=staging`.
+    pub attributes: Vec<(String, String)>,
+}
+
+impl Default for OtlpConfig {
+    fn default() -> Self {
+        Self {
+            traces_endpoint: None,
+            metrics_endpoint: None,
+            metrics_interval: Duration::from_secs(60),
+            service_name: SERVICE_NAME.to_string(),
+            attributes: Vec::new(),
+        }
+    }
+}
+
+impl OtlpConfig {
+    /// Returns whether an exporter is enabled.
+    pub const fn is_enabled(&self) -> bool {
+        self.traces_endpoint.is_some() || self.metrics_endpoint.is_some()
+    }
+
+    /// Returns the resource of the exported data.
+    pub fn resource(&self) -> Resource {
+        let attributes = std::iter::once(KeyValue::new("service.name", self.service_name.clone()))
+            .chain(
+                self.attributes
+                    .iter()
+                    .map(|(key, value)| KeyValue::new(key.clone(), value.clone())),
+            );
+        Resource::default().merge(&Resource::new(attributes))
+    }
+}
+
+/// The installed OTLP exporters, flushed and shut down when dropped.
+#[derive(Debug)]
+pub struct Otlp {
+    /// The provider of the tracer of the spans, if exported.
+    tracer_provider: Option<TracerProvider>,
+    /// The provider of the meter of the metrics, if exported.
+    meter_provider: Option<SdkMeterProvider>,
+    /// The runtime of the exporters.
+    runtime: Option<Runtime>,
+}
+
+impl Otlp {
+    /// Installs the exporters of the config, the metrics recorder being installed globally.
+    pub fn install(config: &OtlpConfig) -> Result<Self, OtlpError> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("otlp-exporter")
+            .enable_all()
+            .build()?;
+        // The batch exporters are spawned on the runtime they are built in.
+        let guard = runtime.enter();
+        let resource = config.resource();
+
+        let tracer_provider = config
+            .traces_endpoint
+            .as_ref()
+            .map(|endpoint| {
+                opentelemetry_otlp::new_pipeline()
+                    .tracing()
+                    .with_exporter(
+                        opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint),
+                    )
+                    .with_trace_config(Config::default().with_resource(resource.clone()))
+                    .install_batch(runtime::Tokio)
+            })
+            .transpose()?;
+
+        let meter_provider = match &config.metrics_endpoint {
+            Some(endpoint) => {
+                let provider = opentelemetry_otlp::new_pipeline()
+                    .metrics(runtime::Tokio)
+                    .with_exporter(
+                        opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint),
+                    )
+                    .with_resource(resource)
+                    .with_period(config.metrics_interval)
+                    .build()?;
+                metrics::set_global_recorder(OtlpRecorder::new(provider.meter(SCOPE)))
+                    .map_err(|_| OtlpError::RecorderInstalled)?;
+                Some(provider)
+            }
+            None => None,
+        };
+
+        drop(guard);
+        Ok(Self { tracer_provider, meter_provider, runtime: Some(runtime) })
+    }
+
+    /// Returns the tracer exporting the spans, if exported.
+    pub fn tracer(&self) -> Option<Tracer> {
+        self.tracer_provider.as_ref().map(|provider| provider.tracer(SCOPE))
+    }
+}
+
+impl Drop for Otlp {
+    fn drop(&mut self) {
+        if let Some(provider) = self.tracer_provider.take() {
+            for result in provider.force_flush() {
+                if let Err(err) = result {
+                    warn!(target: "kkrt::otlp", %err, "Failed to flush the spans");
+                }
+            }
+        }
+        if let Some(provider) = self.meter_provider.take() {
+            if let Err(err) = provider.shutdown() {
+                warn!(target: "kkrt::otlp", %err, "Failed to shut down the metrics exporter");
+            }
+        }
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// A recorder of the [`metrics`] facade forwarding the metrics to an OpenTelemetry meter.
+///
+/// The counters and histograms are mapped to their OpenTelemetry counterparts, the gauges to
+/// gauges recording their value once updated. The metrics are registered once by key, with the
+/// description and unit they were described with, if any.
+#[derive(Debug)]
+pub struct OtlpRecorder {
+    /// The meter of the instruments.
+    meter: Meter,
+    /// The descriptions and units of the metrics, by name.
+    descriptions: Mutex<HashMap<String, (SharedString, Option<Unit>)>>,
+    /// The registered counters, by key.
+    counters: Mutex<HashMap<Key, Arc<OtlpCounter>>>,
+    /// The registered gauges, by key.
+    gauges: Mutex<HashMap<Key, Arc<OtlpGauge>>>,
+    /// The registered histograms, by key.
+    histograms: Mutex<HashMap<Key, Arc<OtlpHistogram>>>,
+}
+
+impl OtlpRecorder {
+    /// Creates a recorder forwarding the metrics to the given meter.
+    pub fn new(meter: Meter) -> Self {
+        Self {
+            meter,
+            descriptions: Mutex::default(),
+            counters: Mutex::default(),
+            gauges: Mutex::default(),
+            histograms: Mutex::default(),
+        }
+    }
+
+    /// Records the description of a metric, applied when it is registered.
+    fn describe(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.descriptions
+            .lock()
+            .expect("failed to acquire descriptions lock")
+            .insert(key.as_str().to_string(), (description, unit));
+    }
+
+    /// Returns the description and unit of a metric, if described.
+    fn description(&self, key: &Key) -> (String, String) {
+        let descriptions = self.descriptions.lock().expect("failed to acquire descriptions lock");
+        match descriptions.get(key.name()) {
+            Some((description, unit)) => (
+                description.to_string(),
+                unit.map(|unit| unit.as_canonical_label().to_string()).unwrap_or_default(),
+            ),
+            None => Default::default(),
+        }
+    }
+}
+
+/// Returns the attributes of the labels of a metric.
+fn attributes(key: &Key) -> Vec<KeyValue> {
+    key.labels()
+        .map(|label| KeyValue::new(label.key().to_string(), label.value().to_string()))
+        .collect()
+}
+
+impl Recorder for OtlpRecorder {
+    fn describe_counter(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.describe(key, unit, description);
+    }
+
+    fn describe_gauge(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.describe(key, unit, description);
+    }
+
+    fn describe_histogram(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.describe(key, unit, description);
+    }
+
+    fn register_counter(&self, key: &Key, _metadata: &Metadata<'_>) -> Counter {
+        let mut counters = self.counters.lock().expect("failed to acquire counters lock");
+        let counter = counters.entry(key.clone()).or_insert_with(|| {
+            let (description, unit) = self.description(key);
+            let counter = self
+                .meter
+                .u64_counter(key.name().to_string())
+                .with_description(description)
+                .with_unit(unit)
+                .init();
+            Arc::new(OtlpCounter { counter, attributes: attributes(key), total: AtomicU64::new(0) })
+        });
+        Counter::from_arc(counter.clone())
+    }
+
+    fn register_gauge(&self, key: &Key, _metadata: &Metadata<'_>) -> Gauge {
+        let mut gauges = self.gauges.lock().expect("failed to acquire gauges lock");
+        let gauge = gauges.entry(key.clone()).or_insert_with(|| {
+            let (description, unit) = self.description(key);
+            let gauge = self
+                .meter
+                .f64_gauge(key.name().to_string())
+                .with_description(description)
+                .with_unit(unit)
+                .init();
+            Arc::new(OtlpGauge { gauge, attributes: attributes(key), value: AtomicU64::new(0) })
+        });
+        Gauge::from_arc(gauge.clone())
+    }
+
+    fn register_histogram(&self, key: &Key, _metadata: &Metadata<'_>) -> Histogram {
+        let mut histograms = self.histograms.lock().expect("failed to acquire histograms lock");
+        let histogram = histograms.entry(key.clone()).or_insert_with(|| {
+            let (description, unit) = self.description(key);
+            let histogram = self
+                .meter
+                .f64_histogram(key.name().to_string())
+                .with_description(description)
+                .with_unit(unit)
+                .init();
+            Arc::new(OtlpHistogram { histogram, attributes: attributes(key) })
+        });
+        Histogram::from_arc(histogram.clone())
+    }
+}
+
+/// A counter forwarded to an OpenTelemetry counter.
+#[derive(Debug)]
+struct OtlpCounter {
+    /// The OpenTelemetry counter.
+    counter: opentelemetry::metrics::Counter<u64>,
+    /// The attributes of the labels of the counter.
+    attributes: Vec<KeyValue>,
+    /// The total of the counter, to forward the absolute values as increments.
+    total: AtomicU64,
+}
+
+impl CounterFn for OtlpCounter {
+    fn increment(&self, value: u64) {
+        self.total.fetch_add(value, Ordering::Relaxed);
+        self.counter.add(value, &self.attributes);
+    }
+
+    fn absolute(&self, value: u64) {
+        let previous = self.total.fetch_max(value, Ordering::Relaxed);
+        if value > previous {
+            self.counter.add(value - previous, &self.attributes);
+        }
+    }
+}
+
+/// A gauge forwarded to an OpenTelemetry gauge.
+#[derive(Debug)]
+struct OtlpGauge {
+    /// The OpenTelemetry gauge.
+    gauge: opentelemetry::metrics::Gauge<f64>,
+    /// The attributes of the labels of the gauge.
+    attributes: Vec<KeyValue>,
+    /// The bits of the current value of the gauge, to forward the increments as values.
+    value: AtomicU64,
+}
+
+impl OtlpGauge {
+    /// Updates the value of the gauge and records it.
+    fn update(&self, f: impl Fn(f64) -> f64) {
+        let mut value = 0.0;
+        let _ = self.value.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+            value = f(f64::from_bits(bits));
+            Some(value.to_bits())
+        });
+        self.gauge.record(value, &self.attributes);
+    }
+}
+
+impl GaugeFn for OtlpGauge {
+    fn increment(&self, value: f64) {
+        self.update(|current| current + value);
+    }
+
+    fn decrement(&self, value: f64) {
+        self.update(|current| current - value);
+    }
+
+    fn set(&self, value: f64) {
+        self.update(|_| value);
+    }
+}
+
+/// A histogram forwarded to an OpenTelemetry histogram.
+#[derive(Debug)]
+struct OtlpHistogram {
+    /// The OpenTelemetry histogram.
+    histogram: opentelemetry::metrics::Histogram<f64>,
+    /// The attributes of the labels of the histogram.
+    attributes: Vec<KeyValue>,
+}
+
+impl HistogramFn for OtlpHistogram {
+    fn record(&self, value: f64) {
+        self.histogram.record(value, &self.attributes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::Value;
+
+    #[test]
+    fn test_parse_attribute() {
+        assert_eq!(
+            parse_attribute("deployment.environment = staging").unwrap(),
+            ("deployment.environment".to_string(), "staging".to_string())
+        );
+        assert!(matches!(parse_attribute("staging"), Err(OtlpError::InvalidAttribute(_))));
+        assert!(matches!(parse_attribute("=staging"), Err(OtlpError::InvalidAttribute(_))));
+    }
+
+    #[test]
+    fn test_resource() {
+        let config = OtlpConfig {
+            attributes: vec![("deployment.environment".to_string(), "staging".to_string())],
+            ..Default::default()
+        };
+        assert!(!config.is_enabled());
+
+        let resource = config.resource();
+        assert_eq!(resource.get("service.name".into()), Some(Value::from(SERVICE_NAME)));
+        assert_eq!(resource.get("deployment.environment".into()), Some(Value::from("staging")));
+    }
+
+    #[test]
+    fn test_recorder() {
+        let provider = SdkMeterProvider::default();
+        let recorder = OtlpRecorder::new(provider.meter(SCOPE));
+        let key = Key::from_parts("kakarot_exex_blocks_processed", vec![]);
+        let metadata = Metadata::new(module_path!(), metrics::Level::INFO, None);
+
+        let counter = recorder.register_counter(&key, &metadata);
+        counter.increment(2);
+        counter.absolute(5);
+        assert_eq!(recorder.counters.lock().unwrap()[&key].total.load(Ordering::Relaxed), 5);
+
+        // The metrics are registered once by key
+        recorder.register_gauge(&key, &metadata).increment(1.5);
+        recorder.register_gauge(&key, &metadata).decrement(0.5);
+        let value = recorder.gauges.lock().unwrap()[&key].value.load(Ordering::Relaxed);
+        assert_eq!(f64::from_bits(value), 1.0);
+    }
+}
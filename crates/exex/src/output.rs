@@ -0,0 +1,544 @@
+use crate::{
+    input::program_input::HeaderInput,
+    model::U128_BYTES_SIZE,
+    ssz::{append_container, container_fixed_size, container_root, ByteList, List, Ssz},
+};
+use alloy_primitives::{Address, B256, U256};
+use cairo_vm::{
+    types::relocatable::Relocatable,
+    vm::{
+        errors::memory_errors::MemoryError,
+        runners::{builtin_runner::BuiltinRunner, cairo_runner::CairoRunner},
+    },
+    Felt252,
+};
+use reth_revm::db::BundleState;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// The maximum number of accounts of a [`StateDiff`] in its SSZ representation.
+pub const MAX_DIFF_ACCOUNTS: usize = 1 << 20;
+
+/// The maximum number of storage slots of an [`AccountDiff`] in its SSZ representation.
+pub const MAX_DIFF_STORAGE_SLOTS: usize = 1 << 24;
+
+/// The maximum length in bytes of the strings of the [`ProofMetadata`] in its SSZ representation.
+pub const MAX_METADATA_STRING_LENGTH: usize = 256;
+
+/// Represents errors that can occur when decoding the output of the Kakarot program.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum OutputError {
+    /// Error variant indicating that the output segment does not have the expected length.
+    #[error("Expected {expected} felts in the program output, found {actual}")]
+    InvalidLength {
+        /// The expected number of felts.
+        expected: usize,
+        /// The number of felts found in the output segment.
+        actual: usize,
+    },
+
+    /// Error variant indicating that a felt of the output does not fit in the expected type.
+    #[error("Output field '{field}' overflows: {value}")]
+    Overflow {
+        /// The name of the overflowing field.
+        field: &'static str,
+        /// The overflowing value.
+        value: Felt252,
+    },
+}
+
+/// The public output of the Kakarot program for a block, as written to the output segment by the
+/// `main` function of `os.cairo` from the header of the executed block.
+///
+/// The output is an unverified echo of the header given as input: the OS neither recomputes the
+/// block hash nor compares the state root after the execution with the header yet. A proof thus
+/// commits to the header it was run with, not to the validity of these fields.
+///
+/// Hashes are written as `Uint256` (low and high 128-bit limbs), so the output segment has the
+/// following layout:
+///
+/// | Offset | Field                            |
+/// |--------|----------------------------------|
+/// | 0      | `block_number`                   |
+/// | 1-2    | `block_hash` (low, high)         |
+/// | 3-4    | `parent_hash` (low, high)        |
+/// | 5-6    | `post_state_root` (low, high)    |
+/// | 7-8    | `transactions_root` (low, high)  |
+/// | 9-10   | `receipts_root` (low, high)      |
+/// | 11     | `gas_used`                       |
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct ProgramOutput {
+    /// The number of the executed block.
+    pub block_number: u64,
+    /// The hash of the executed block.
+    pub block_hash: B256,
+    /// The hash of the parent block.
+    pub parent_hash: B256,
+    /// The state root after the execution of the block.
+    pub post_state_root: B256,
+    /// The root of the transactions trie of the block.
+    pub transactions_root: B256,
+    /// The root of the receipts trie of the block.
+    pub receipts_root: B256,
+    /// The total gas used by the block.
+    pub gas_used: u64,
+}
+
+impl ProgramOutput {
+    /// The number of felts of the output segment.
+    pub const SIZE: usize = 12;
+
+    /// Decodes the program output from the felts of the output segment.
+    pub fn from_felts(felts: &[Felt252]) -> Result<Self, OutputError> {
+        if felts.len() != Self::SIZE {
+            return Err(OutputError::InvalidLength { expected: Self::SIZE, actual: felts.len() });
+        }
+
+        Ok(Self {
+            block_number: felt_to_u64(&felts[0], "block_number")?,
+            block_hash: felts_to_b256(&felts[1], &felts[2], "block_hash")?,
+            parent_hash: felts_to_b256(&felts[3], &felts[4], "parent_hash")?,
+            post_state_root: felts_to_b256(&felts[5], &felts[6], "post_state_root")?,
+            transactions_root: felts_to_b256(&felts[7], &felts[8], "transactions_root")?,
+            receipts_root: felts_to_b256(&felts[9], &felts[10], "receipts_root")?,
+            gas_used: felt_to_u64(&felts[11], "gas_used")?,
+        })
+    }
+
+    /// Encodes the program output into the felts of the output segment.
+    pub fn to_felts(&self) -> Vec<Felt252> {
+        let mut felts = Vec::with_capacity(Self::SIZE);
+        felts.push(self.block_number.into());
+        for hash in [
+            self.block_hash,
+            self.parent_hash,
+            self.post_state_root,
+            self.transactions_root,
+            self.receipts_root,
+        ] {
+            felts.push(Felt252::from_bytes_be_slice(&hash.0[U128_BYTES_SIZE..]));
+            felts.push(Felt252::from_bytes_be_slice(&hash.0[..U128_BYTES_SIZE]));
+        }
+        felts.push(self.gas_used.into());
+        felts
+    }
+}
+
+/// The output expected from the execution of a block, read from its header.
+impl From<&HeaderInput> for ProgramOutput {
+    fn from(header: &HeaderInput) -> Self {
+        Self {
+            block_number: header.number.to(),
+            block_hash: header.hash,
+            parent_hash: header.parent_hash,
+            post_state_root: header.state_root,
+            transactions_root: header.transactions_trie,
+            receipts_root: header.receipt_trie,
+            gas_used: header.gas_used.to(),
+        }
+    }
+}
+
+/// Reads the felts written to the output segment of an ended runner, `None` when the program has no
+/// output builtin.
+///
+/// Fails if the size of the segment is not computed yet or the segment holds non-integer values.
+pub fn read_output(runner: &CairoRunner) -> Result<Option<Vec<Felt252>>, MemoryError> {
+    let Some(output) = runner
+        .vm
+        .get_builtin_runners()
+        .iter()
+        .find(|builtin| matches!(builtin, BuiltinRunner::Output(_)))
+    else {
+        return Ok(None);
+    };
+    let base = output.base();
+    let size = runner
+        .vm
+        .segments
+        .get_segment_used_size(base)
+        .ok_or(MemoryError::MissingSegmentUsedSizes)?;
+    let felts = runner.vm.get_integer_range(Relocatable::from((base as isize, 0)), size)?;
+    Ok(Some(felts.into_iter().map(|felt| *felt).collect()))
+}
+
+/// The changes applied by a block to a single account.
+///
+/// Only the modified fields are set, unchanged fields are left to `None`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct AccountDiff {
+    /// Whether the account was destroyed by the block.
+    pub destroyed: bool,
+    /// The new nonce of the account.
+    pub nonce: Option<u64>,
+    /// The new balance of the account.
+    pub balance: Option<U256>,
+    /// The new code hash of the account.
+    pub code_hash: Option<B256>,
+    /// The new values of the modified storage slots.
+    pub storage: BTreeMap<B256, U256>,
+}
+
+/// The state changes applied by a block, keyed by account.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct StateDiff {
+    /// The changes of each modified account.
+    pub accounts: BTreeMap<Address, AccountDiff>,
+}
+
+impl From<&BundleState> for StateDiff {
+    fn from(bundle: &BundleState) -> Self {
+        let mut accounts = BTreeMap::new();
+
+        for (address, account) in &bundle.state {
+            let mut diff = AccountDiff::default();
+
+            match (&account.original_info, &account.info) {
+                // The account was destroyed during the block.
+                (_, None) => diff.destroyed = true,
+                // The account was created or modified: record the fields which changed.
+                (original, Some(info)) => {
+                    let original = original.clone().unwrap_or_default();
+                    diff.nonce = (original.nonce != info.nonce).then_some(info.nonce);
+                    diff.balance = (original.balance != info.balance).then_some(info.balance);
+                    diff.code_hash =
+                        (original.code_hash != info.code_hash).then_some(info.code_hash);
+                }
+            }
+
+            // Record the storage slots whose value changed.
+            diff.storage = account
+                .storage
+                .iter()
+                .filter(|(_, slot)| slot.is_changed())
+                .map(|(key, slot)| (B256::from(*key), slot.present_value))
+                .collect();
+
+            if diff != AccountDiff::default() {
+                accounts.insert(*address, diff);
+            }
+        }
+
+        Self { accounts }
+    }
+}
+
+/// The metadata of a proof of a block execution.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ProofMetadata {
+    /// The number of the proven block.
+    pub block_number: u64,
+    /// The hash of the Kakarot program used to execute the block.
+    pub program_hash: B256,
+    /// The hash tree root of the [`ProgramOutput`] of the block.
+    pub output_root: B256,
+    /// The name of the Cairo layout used to generate the proof.
+    pub layout: String,
+    /// The name and version of the prover which generated the proof.
+    pub prover: String,
+    /// The UNIX timestamp, in seconds, at which the proof was generated.
+    pub created_at: u64,
+    /// The identifier of the job of the proving service which generated the proof, e.g. a SHARP
+    /// job key, `None` for local provers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+}
+
+impl ProgramOutput {
+    fn ssz_fields(&self) -> [&dyn Ssz; 7] {
+        [
+            &self.block_number,
+            &self.block_hash,
+            &self.parent_hash,
+            &self.post_state_root,
+            &self.transactions_root,
+            &self.receipts_root,
+            &self.gas_used,
+        ]
+    }
+}
+
+/// `ProgramOutput` is encoded as the container of its fields, in declaration order.
+impl Ssz for ProgramOutput {
+    fn fixed_size(&self) -> Option<usize> {
+        container_fixed_size(&self.ssz_fields())
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        append_container(&self.ssz_fields(), buf);
+    }
+
+    fn hash_tree_root(&self) -> B256 {
+        container_root(&self.ssz_fields())
+    }
+}
+
+/// The SSZ representation of a modified storage slot.
+#[derive(Debug, Clone, Copy)]
+struct SszStorageSlot {
+    key: B256,
+    value: U256,
+}
+
+impl Ssz for SszStorageSlot {
+    fn fixed_size(&self) -> Option<usize> {
+        container_fixed_size(&[&self.key, &self.value])
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        append_container(&[&self.key, &self.value], buf);
+    }
+
+    fn hash_tree_root(&self) -> B256 {
+        container_root(&[&self.key, &self.value])
+    }
+}
+
+/// The SSZ representation of an [`AccountDiff`], alongside the address of the account.
+///
+/// ```text
+/// class AccountDiff(Container):
+///     address: Bytes20
+///     destroyed: boolean
+///     nonce: List[uint64, 1]
+///     balance: List[uint256, 1]
+///     code_hash: List[Bytes32, 1]
+///     storage: List[StorageSlot, MAX_DIFF_STORAGE_SLOTS]
+/// ```
+#[derive(Debug)]
+struct SszAccountDiff<'a> {
+    address: &'a Address,
+    diff: &'a AccountDiff,
+    storage: Vec<SszStorageSlot>,
+}
+
+impl<'a> SszAccountDiff<'a> {
+    fn new(address: &'a Address, diff: &'a AccountDiff) -> Self {
+        let storage =
+            diff.storage.iter().map(|(key, value)| SszStorageSlot { key: *key, value: *value });
+        Self { address, diff, storage: storage.collect() }
+    }
+
+    fn encode<R>(&self, f: impl FnOnce(&[&dyn Ssz]) -> R) -> R {
+        f(&[
+            self.address,
+            &self.diff.destroyed,
+            &List::new(self.diff.nonce.as_slice(), 1),
+            &List::new(self.diff.balance.as_slice(), 1),
+            &List::new(self.diff.code_hash.as_slice(), 1),
+            &List::new(&self.storage, MAX_DIFF_STORAGE_SLOTS),
+        ])
+    }
+}
+
+impl Ssz for SszAccountDiff<'_> {
+    fn fixed_size(&self) -> Option<usize> {
+        None
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        self.encode(|fields| append_container(fields, buf));
+    }
+
+    fn hash_tree_root(&self) -> B256 {
+        self.encode(container_root)
+    }
+}
+
+/// `StateDiff` is encoded as the container
+///
+/// ```text
+/// class StateDiff(Container):
+///     accounts: List[AccountDiff, MAX_DIFF_ACCOUNTS]
+/// ```
+///
+/// with accounts sorted by address and storage slots sorted by key. Unchanged fields of an
+/// account are encoded as empty lists.
+impl StateDiff {
+    fn encode<R>(&self, f: impl FnOnce(&[&dyn Ssz]) -> R) -> R {
+        let accounts = self
+            .accounts
+            .iter()
+            .map(|(address, diff)| SszAccountDiff::new(address, diff))
+            .collect::<Vec<_>>();
+        f(&[&List::new(&accounts, MAX_DIFF_ACCOUNTS)])
+    }
+}
+
+impl Ssz for StateDiff {
+    fn fixed_size(&self) -> Option<usize> {
+        None
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        self.encode(|fields| append_container(fields, buf));
+    }
+
+    fn hash_tree_root(&self) -> B256 {
+        self.encode(container_root)
+    }
+}
+
+/// `ProofMetadata` is encoded as the container of its fields, in declaration order, strings being
+/// encoded as `List[byte, MAX_METADATA_STRING_LENGTH]`. The `job_id` is operational data and is not
+/// encoded.
+impl ProofMetadata {
+    fn encode<R>(&self, f: impl FnOnce(&[&dyn Ssz]) -> R) -> R {
+        f(&[
+            &self.block_number,
+            &self.program_hash,
+            &self.output_root,
+            &ByteList::new(self.layout.as_bytes(), MAX_METADATA_STRING_LENGTH),
+            &ByteList::new(self.prover.as_bytes(), MAX_METADATA_STRING_LENGTH),
+            &self.created_at,
+        ])
+    }
+}
+
+impl Ssz for ProofMetadata {
+    fn fixed_size(&self) -> Option<usize> {
+        None
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        self.encode(|fields| append_container(fields, buf));
+    }
+
+    fn hash_tree_root(&self) -> B256 {
+        self.encode(container_root)
+    }
+}
+
+/// Converts a felt into a `u64`.
+fn felt_to_u64(felt: &Felt252, field: &'static str) -> Result<u64, OutputError> {
+    let bytes = felt.to_bytes_be();
+    if bytes[..24].iter().any(|byte| *byte != 0) {
+        return Err(OutputError::Overflow { field, value: *felt });
+    }
+    Ok(u64::from_be_bytes(bytes[24..].try_into().expect("slice has 8 bytes")))
+}
+
+/// Converts the low and high 128-bit limbs of a `Uint256` into a [`B256`].
+fn felts_to_b256(low: &Felt252, high: &Felt252, field: &'static str) -> Result<B256, OutputError> {
+    let mut bytes = [0u8; 32];
+    for (felt, range) in [(high, 0..U128_BYTES_SIZE), (low, U128_BYTES_SIZE..32)] {
+        let limb = felt.to_bytes_be();
+        if limb[..U128_BYTES_SIZE].iter().any(|byte| *byte != 0) {
+            return Err(OutputError::Overflow { field, value: *felt });
+        }
+        bytes[range].copy_from_slice(&limb[U128_BYTES_SIZE..]);
+    }
+    Ok(B256::from(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn test_program_output_felts_roundtrip(
+            block_number in any::<u64>(),
+            hashes in any::<[B256; 5]>(),
+            gas_used in any::<u64>()
+        ) {
+            let output = ProgramOutput {
+                block_number,
+                block_hash: hashes[0],
+                parent_hash: hashes[1],
+                post_state_root: hashes[2],
+                transactions_root: hashes[3],
+                receipts_root: hashes[4],
+                gas_used,
+            };
+
+            let felts = output.to_felts();
+            prop_assert_eq!(felts.len(), ProgramOutput::SIZE);
+            prop_assert_eq!(ProgramOutput::from_felts(&felts).unwrap(), output);
+        }
+    }
+
+    #[test]
+    fn test_program_output_ssz() {
+        let output = ProgramOutput {
+            block_number: 1,
+            block_hash: B256::repeat_byte(1),
+            gas_used: 21_000,
+            ..Default::default()
+        };
+
+        // 2 `uint64` and 5 `Bytes32`.
+        let bytes = output.ssz_bytes();
+        assert_eq!(output.fixed_size(), Some(2 * 8 + 5 * 32));
+        assert_eq!(bytes.len(), 2 * 8 + 5 * 32);
+        assert_eq!(&bytes[..8], &1u64.to_le_bytes());
+        assert_eq!(&bytes[8..40], B256::repeat_byte(1).as_slice());
+
+        // 7 fields are merkleized in a tree of depth 3.
+        let mut other = output;
+        other.gas_used += 1;
+        assert_ne!(output.hash_tree_root(), other.hash_tree_root());
+    }
+
+    #[test]
+    fn test_state_diff_ssz() {
+        let empty = StateDiff::default();
+        // A single offset pointing right after itself.
+        assert_eq!(empty.ssz_bytes(), vec![4, 0, 0, 0]);
+
+        let mut diff = StateDiff::default();
+        diff.accounts.insert(
+            Address::repeat_byte(0xaa),
+            AccountDiff {
+                nonce: Some(1),
+                storage: BTreeMap::from([(B256::ZERO, U256::from(2))]),
+                ..Default::default()
+            },
+        );
+
+        let bytes = diff.ssz_bytes();
+        // Container offset, list offset, then the account diff: address, destroyed flag and 4
+        // offsets, the nonce and the storage slot.
+        assert_eq!(bytes.len(), 4 + 4 + (20 + 1 + 4 * 4) + 8 + 64);
+        assert_eq!(&bytes[8..28], Address::repeat_byte(0xaa).as_slice());
+        assert_ne!(diff.hash_tree_root(), empty.hash_tree_root());
+    }
+
+    #[test]
+    fn test_proof_metadata_ssz() {
+        let metadata = ProofMetadata {
+            block_number: 1,
+            layout: "all_cairo".to_string(),
+            prover: "stone".to_string(),
+            ..Default::default()
+        };
+
+        let bytes = metadata.ssz_bytes();
+        // Fixed part: 2 `uint64`, 2 `Bytes32` and 2 offsets.
+        let fixed_len = 2 * 8 + 2 * 32 + 2 * 4;
+        assert_eq!(bytes.len(), fixed_len + "all_cairo".len() + "stone".len());
+        assert_eq!(&bytes[fixed_len..], b"all_cairostone");
+    }
+
+    #[test]
+    fn test_program_output_invalid_length() {
+        assert_eq!(
+            ProgramOutput::from_felts(&[Felt252::ZERO; 3]),
+            Err(OutputError::InvalidLength { expected: ProgramOutput::SIZE, actual: 3 })
+        );
+    }
+
+    #[test]
+    fn test_program_output_limb_overflow() {
+        let mut felts = ProgramOutput::default().to_felts();
+        // A limb larger than 128 bits is not a valid `Uint256` limb.
+        felts[2] = Felt252::from(u128::MAX) + Felt252::ONE;
+
+        assert!(matches!(
+            ProgramOutput::from_felts(&felts),
+            Err(OutputError::Overflow { field: "block_hash", .. })
+        ));
+    }
+}
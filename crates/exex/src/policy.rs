@@ -0,0 +1,320 @@
+//! The security policy of the hints executed by the Cairo runs.
+//!
+//! A [`HintPolicy`] is a whitelist of the hints allowed to execute, pinned by the keccak hash of
+//! their code, so that a third-party or newly compiled program cannot run arbitrary hints. The
+//! hints of a program are checked against the policy before its run, and the
+//! [`PolicyHintProcessor`] enforces it during the run, rejecting the hints out of the whitelist
+//! and recording a [`HintAudit`] of the hints executed.
+
+use crate::hints::KakarotHintProcessor;
+use alloy_primitives::{keccak256, B256};
+use cairo_vm::{
+    hint_processor::{
+        builtin_hint_processor::builtin_hint_processor_definition::HintProcessorData,
+        hint_processor_definition::{HintProcessorLogic, HintReference},
+    },
+    serde::deserialize_program::ApTracking,
+    types::exec_scope::ExecutionScopes,
+    vm::{
+        errors::{hint_errors::HintError, vm_errors::VirtualMachineError},
+        runners::cairo_runner::{ResourceTracker, RunResources},
+        vm_core::VirtualMachine,
+    },
+    Felt252,
+};
+use serde::Deserialize;
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::Path,
+};
+use thiserror::Error;
+
+/// Represents errors that can occur when enforcing a hint policy.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PolicyError {
+    /// Error variant indicating a hint out of the whitelist of the policy.
+    #[error("Hint {hash} is not allowed by the policy: {code}")]
+    Rejected {
+        /// The hash of the code of the hint.
+        hash: B256,
+        /// The code of the hint.
+        code: String,
+    },
+
+    /// Error variant indicating a program whose hints cannot be read.
+    #[error("Invalid program: {0}")]
+    InvalidProgram(String),
+}
+
+/// Returns the hash pinning the code of a hint.
+pub fn hint_hash(code: &str) -> B256 {
+    keccak256(code.as_bytes())
+}
+
+/// A whitelist of the hints allowed to execute, by hash of their code.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HintPolicy {
+    /// The hashes of the allowed hints.
+    allowed: HashSet<B256>,
+}
+
+/// The hints of a compiled program, as serialized by the Cairo compiler.
+#[derive(Debug, Deserialize)]
+struct ProgramHints {
+    /// The hints of the program, by pc.
+    #[serde(default)]
+    hints: HashMap<String, Vec<ProgramHint>>,
+}
+
+/// A hint of a compiled program.
+#[derive(Debug, Deserialize)]
+struct ProgramHint {
+    /// The code of the hint.
+    code: String,
+}
+
+impl HintPolicy {
+    /// Creates a policy allowing the hints of the given hashes.
+    pub fn from_hashes(hashes: impl IntoIterator<Item = B256>) -> Self {
+        Self { allowed: hashes.into_iter().collect() }
+    }
+
+    /// Creates a policy allowing the hints of the given codes.
+    pub fn from_codes<'a>(codes: impl IntoIterator<Item = &'a str>) -> Self {
+        Self::from_hashes(codes.into_iter().map(hint_hash))
+    }
+
+    /// Loads a policy from a JSON file holding the list of the allowed hashes.
+    pub fn load(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let hashes: Vec<B256> = serde_json::from_slice(&fs::read(path)?)?;
+        Ok(Self::from_hashes(hashes))
+    }
+
+    /// Returns the number of allowed hints.
+    pub fn len(&self) -> usize {
+        self.allowed.len()
+    }
+
+    /// Returns whether no hint is allowed.
+    pub fn is_empty(&self) -> bool {
+        self.allowed.is_empty()
+    }
+
+    /// Returns whether a hint hash is allowed.
+    pub fn allows(&self, hash: &B256) -> bool {
+        self.allowed.contains(hash)
+    }
+
+    /// Checks the hints of a compiled program against the policy before its run, failing with
+    /// the first rejected hint, by pc.
+    pub fn check_program(&self, program: &[u8]) -> Result<(), PolicyError> {
+        let program: ProgramHints = serde_json::from_slice(program)
+            .map_err(|err| PolicyError::InvalidProgram(err.to_string()))?;
+        let mut hints: Vec<_> = program
+            .hints
+            .into_iter()
+            .map(|(pc, hints)| (pc.parse::<usize>().unwrap_or(usize::MAX), hints))
+            .collect();
+        hints.sort_by_key(|(pc, _)| *pc);
+
+        for ProgramHint { code } in hints.into_iter().flat_map(|(_, hints)| hints) {
+            let hash = hint_hash(&code);
+            if !self.allows(&hash) {
+                return Err(PolicyError::Rejected { hash, code });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The usage of a hint during a run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HintUsage {
+    /// The code of the hint.
+    pub code: String,
+    /// The number of executions of the hint.
+    pub executions: usize,
+}
+
+/// The audit trail of the hints of a run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HintAudit {
+    /// The usage of the executed hints, by hash.
+    pub executed: BTreeMap<B256, HintUsage>,
+    /// The rejected hints, by hash.
+    pub rejected: BTreeMap<B256, String>,
+}
+
+impl HintAudit {
+    /// Returns the total number of hint executions.
+    pub fn executions(&self) -> usize {
+        self.executed.values().map(|usage| usage.executions).sum()
+    }
+}
+
+/// The compiled data of a hint, with its hash and whether it is allowed.
+struct PolicyHintData {
+    /// The hash of the code of the hint.
+    hash: B256,
+    /// Whether the policy allows the hint.
+    allowed: bool,
+    /// The compiled data of the underlying processor.
+    data: Box<dyn Any>,
+}
+
+/// A hint processor enforcing a [`HintPolicy`] on top of a [`KakarotHintProcessor`] and auditing
+/// the hints executed, all of them being allowed without policy.
+#[allow(missing_debug_implementations)]
+pub struct PolicyHintProcessor<'a> {
+    /// The underlying processor.
+    processor: KakarotHintProcessor,
+    /// The enforced policy, if any.
+    policy: Option<&'a HintPolicy>,
+    /// The audit trail of the run.
+    audit: HintAudit,
+}
+
+impl<'a> PolicyHintProcessor<'a> {
+    /// Creates a processor enforcing the given policy, if any.
+    pub fn new(processor: KakarotHintProcessor, policy: Option<&'a HintPolicy>) -> Self {
+        Self { processor, policy, audit: HintAudit::default() }
+    }
+
+    /// Returns the audit trail of the hints executed so far.
+    pub const fn audit(&self) -> &HintAudit {
+        &self.audit
+    }
+}
+
+impl HintProcessorLogic for PolicyHintProcessor<'_> {
+    fn compile_hint(
+        &self,
+        hint_code: &str,
+        ap_tracking_data: &ApTracking,
+        reference_ids: &HashMap<String, usize>,
+        references: &[HintReference],
+    ) -> Result<Box<dyn Any>, VirtualMachineError> {
+        let hash = hint_hash(hint_code);
+        let data =
+            self.processor.compile_hint(hint_code, ap_tracking_data, reference_ids, references)?;
+        let allowed = self.policy.is_none_or(|policy| policy.allows(&hash));
+        Ok(Box::new(PolicyHintData { hash, allowed, data }))
+    }
+
+    fn execute_hint(
+        &mut self,
+        vm: &mut VirtualMachine,
+        exec_scopes: &mut ExecutionScopes,
+        hint_data: &Box<dyn Any>,
+        constants: &HashMap<String, Felt252>,
+    ) -> Result<(), HintError> {
+        let PolicyHintData { hash, allowed, data } =
+            hint_data.downcast_ref::<PolicyHintData>().ok_or(HintError::WrongHintData)?;
+        let code = || {
+            data.downcast_ref::<HintProcessorData>()
+                .map(|data| data.code.clone())
+                .unwrap_or_default()
+        };
+
+        if !allowed {
+            let code = code();
+            self.audit.rejected.insert(*hash, code.clone());
+            return Err(HintError::UnknownHint(code.into_boxed_str()));
+        }
+        self.audit
+            .executed
+            .entry(*hash)
+            .or_insert_with(|| HintUsage { code: code(), executions: 0 })
+            .executions += 1;
+        self.processor.execute_hint(vm, exec_scopes, data, constants)
+    }
+}
+
+impl ResourceTracker for PolicyHintProcessor<'_> {
+    fn consumed(&self) -> bool {
+        self.processor.consumed()
+    }
+
+    fn consume_step(&mut self) {
+        self.processor.consume_step()
+    }
+
+    fn get_n_steps(&self) -> Option<usize> {
+        self.processor.get_n_steps()
+    }
+
+    fn run_resources(&self) -> &RunResources {
+        self.processor.run_resources()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        executor::{execute, ExecutionMode},
+        limits::LimitedRun,
+        tuning::RunnerTuning,
+    };
+    use std::collections::BTreeSet;
+
+    const PROGRAM: &[u8] = include_bytes!("../testdata/keccak_add_uint256.json");
+
+    fn codes() -> BTreeSet<String> {
+        let program: ProgramHints = serde_json::from_slice(PROGRAM).unwrap();
+        program.hints.into_values().flatten().map(|hint| hint.code).collect()
+    }
+
+    fn run(policy: Option<&HintPolicy>) -> (bool, HintAudit) {
+        let mut hint_processor = PolicyHintProcessor::new(KakarotHintProcessor::default(), policy);
+        let run = execute(
+            PROGRAM,
+            ExecutionMode::DryRun,
+            &mut hint_processor,
+            &Default::default(),
+            &RunnerTuning::default(),
+            &mut || false,
+        );
+        (matches!(run, Ok(LimitedRun::Completed(_))), hint_processor.audit().clone())
+    }
+
+    #[test]
+    fn test_hint_policy() {
+        let codes = codes();
+        let policy = HintPolicy::from_codes(codes.iter().map(String::as_str));
+        assert_eq!(policy.check_program(PROGRAM), Ok(()));
+
+        let (completed, audit) = run(Some(&policy));
+        assert!(completed);
+        assert!(audit.rejected.is_empty());
+        assert!(audit.executions() > 0);
+        assert_eq!(run(None).1, audit);
+
+        // A policy missing a hint of the program rejects it.
+        let partial = HintPolicy::from_codes(codes.iter().skip(1).map(String::as_str));
+        assert!(matches!(
+            partial.check_program(PROGRAM),
+            Err(PolicyError::Rejected { code, .. }) if !partial.allows(&hint_hash(&code))
+        ));
+        assert_eq!(HintPolicy::default().check_program(b"{}"), Ok(()));
+
+        let (completed, audit) = run(Some(&HintPolicy::default()));
+        assert!(!completed);
+        assert_eq!(audit.rejected.len(), 1);
+        assert_eq!(audit.executions(), 0);
+    }
+
+    #[test]
+    fn test_load_hint_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hints.json");
+        let hash = hint_hash("memory[ap] = to_felt_or_relocatable(segments.add())");
+        fs::write(&path, serde_json::to_vec(&[hash]).unwrap()).unwrap();
+
+        let policy = HintPolicy::load(&path).unwrap();
+        assert_eq!(policy.len(), 1);
+        assert!(policy.allows(&hash));
+    }
+}
@@ -0,0 +1,310 @@
+//! Parallel precomputation of the values written by the hints of the Kakarot program.
+//!
+//! The keccak and signature recovery work dominates the hint time of real blocks:
+//! - The `state` hint hashes the code of each account of the pre-state, scans it for the valid jump
+//!   destinations and hashes each storage slot into its `pedersen(slot.low, slot.high)` dict key.
+//! - The `recover_eth_address` hint recovers the signer of each transaction, validated by
+//!   `Transaction.validate`, from the keccak hash of its unsigned encoding and its signature.
+//!
+//! These values only depend on the [`ProgramInput`], so they are computed in a batch on the rayon
+//! pool when the hints are registered, see
+//! [`KakarotHintProcessor::with_program_input`](crate::hints::KakarotHintProcessor::with_program_input),
+//! and the [`state_hint`](crate::hints::state_hint) and
+//! [`recover_eth_address_hint`](crate::hints::recover_eth_address_hint) only write them, the
+//! recoveries requested by the `ecrecover` precompile being computed when missed.
+//!
+//! The cache is keyed by address and by recovery input, the recoveries being collected in the
+//! order of the transactions, so that the written memory, and hence the execution, does not depend
+//! on the scheduling of the pool.
+
+use crate::{
+    input::{
+        memory::valid_jumpdests,
+        program_input::{AccountStateInput, ProgramInput, TransactionInput},
+    },
+    serde::storage::StoragePreimages,
+};
+use alloy_primitives::{keccak256, Address, Signature, B256, U256};
+use cairo_vm::Felt252;
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+
+/// The values of an account of the pre-state written by the `state` hint.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrecomputedAccount {
+    /// The keccak hash of the code.
+    pub code_hash: B256,
+    /// The offsets of the valid jump destinations of the code.
+    pub valid_jumpdests: Vec<usize>,
+    /// The storage dict keys, by slot.
+    pub storage_keys: BTreeMap<U256, Felt252>,
+}
+
+impl PrecomputedAccount {
+    /// Computes the values of an account.
+    pub fn compute(account: &AccountStateInput) -> Self {
+        Self {
+            code_hash: keccak256(&account.code),
+            valid_jumpdests: valid_jumpdests(&account.code),
+            storage_keys: account
+                .storage
+                .keys()
+                .map(|slot| (*slot, StoragePreimages::storage_key(*slot)))
+                .collect(),
+        }
+    }
+}
+
+/// The inputs of a signature recovery of the `recover_eth_address` hint, see
+/// `ICairo1Helpers.recover_eth_address`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RecoveryInput {
+    /// The signed message hash.
+    pub msg_hash: B256,
+    /// The `r` value of the signature.
+    pub r: U256,
+    /// The `s` value of the signature.
+    pub s: U256,
+    /// The parity of the `y` coordinate of the signature point.
+    pub y_parity: bool,
+}
+
+impl RecoveryInput {
+    /// Returns the recovery validating the signature of a transaction, as computed by
+    /// `Transaction.validate` from the keccak hash of its unsigned encoding and its
+    /// `[r.low, r.high, s.low, s.high, v]` signature.
+    ///
+    /// Returns `None` if the signature is malformed, the transaction being rejected by the program.
+    pub fn from_transaction(transaction: &TransactionInput, chain_id: u64) -> Option<Self> {
+        let &[r_low, r_high, s_low, s_high, v] = transaction.signature.as_slice() else {
+            return None;
+        };
+        let word = |low: u128, high: u128| (U256::from(high) << 128) | U256::from(low);
+
+        // Legacy transactions are encoded as a list, and carry the chain id in `v` after EIP-155.
+        let legacy = transaction.rlp.first().is_some_and(|prefix| *prefix >= 0xc0);
+        let y_parity = match (legacy, v) {
+            (true, 27 | 28) => v - 27,
+            (true, _) => v.checked_sub(u128::from(chain_id) * 2 + 35)?,
+            (false, _) => v,
+        };
+
+        Some(Self {
+            msg_hash: keccak256(&transaction.rlp),
+            r: word(r_low, r_high),
+            s: word(s_low, s_high),
+            y_parity: match y_parity {
+                0 => false,
+                1 => true,
+                _ => return None,
+            },
+        })
+    }
+
+    /// Recovers the address of the signer, `None` if the signature is invalid.
+    pub fn recover(&self) -> Option<Address> {
+        Signature::from_rs_and_parity(self.r, self.s, u64::from(self.y_parity))
+            .ok()?
+            .recover_address_from_prehash(&self.msg_hash)
+            .ok()
+    }
+}
+
+/// The precomputed values of the accounts of a pre-state and of the signature recoveries of the
+/// transactions of a block.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HintCache {
+    /// The precomputed accounts, by address.
+    accounts: HashMap<Address, PrecomputedAccount>,
+    /// The recovered signers, `None` for the invalid signatures, by recovery input.
+    recoveries: HashMap<RecoveryInput, Option<Address>>,
+}
+
+impl HintCache {
+    /// Computes the values of the pre-state and of the transactions of a program input in
+    /// parallel.
+    pub fn from_program_input(input: &ProgramInput) -> Self {
+        let (accounts, recoveries) = rayon::join(
+            || Self::from_state(&input.state).accounts,
+            || Self::from_transactions(&input.block.transactions, input.chain_id).recoveries,
+        );
+        Self { accounts, recoveries }
+    }
+
+    /// Computes the values of the accounts of a pre-state in parallel.
+    pub fn from_state(state: &BTreeMap<Address, AccountStateInput>) -> Self {
+        let accounts = state
+            .par_iter()
+            .map(|(address, account)| (*address, PrecomputedAccount::compute(account)))
+            .collect();
+        Self { accounts, ..Default::default() }
+    }
+
+    /// Computes the signature recoveries of the transactions of a block in parallel, each
+    /// transaction hashing its unsigned encoding and recovering its signer in the same task.
+    pub fn from_transactions(transactions: &[TransactionInput], chain_id: u64) -> Self {
+        let recoveries: Vec<_> = transactions
+            .par_iter()
+            .filter_map(|transaction| RecoveryInput::from_transaction(transaction, chain_id))
+            .map(|input| (input, input.recover()))
+            .collect();
+        // The recoveries are collected in the order of the transactions.
+        Self { recoveries: recoveries.into_iter().collect(), ..Default::default() }
+    }
+
+    /// Returns the precomputed values of an account, if cached.
+    pub fn account(&self, address: &Address) -> Option<&PrecomputedAccount> {
+        self.accounts.get(address)
+    }
+
+    /// Returns the recovered signer of a signature, `None` if the signature is invalid, or computes
+    /// it if not cached, e.g. for the recoveries of the `ecrecover` precompile.
+    pub fn recover(&self, input: &RecoveryInput) -> Option<Address> {
+        self.recoveries.get(input).copied().unwrap_or_else(|| input.recover())
+    }
+
+    /// Returns the preimages of the storage dict keys of the cached accounts.
+    pub fn preimages(&self) -> StoragePreimages {
+        let mut preimages = StoragePreimages::default();
+        for account in self.accounts.values() {
+            for (slot, key) in &account.storage_keys {
+                preimages.record(*key, *slot);
+            }
+        }
+        preimages
+    }
+
+    /// Returns the number of cached accounts.
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    /// Returns whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_consensus::TxEip1559;
+    use alloy_primitives::{address, bytes, hex, TxKind, U64};
+    use reth_primitives::{Transaction, TransactionSigned};
+
+    /// A mainnet transaction <https://etherscan.io/tx/0xc3099e296bc0eaa6d3a5e0f46fcc4a9bb2f42fb4668a17dd926d75ca651509f0>.
+    fn transaction() -> TransactionSigned {
+        TransactionSigned::from_transaction_and_signature(
+            Transaction::Eip1559(TxEip1559 {
+                chain_id: 1,
+                nonce: 0,
+                gas_limit: 0x3173e,
+                max_fee_per_gas: 0x2a9860004,
+                max_priority_fee_per_gas: 0x4903a597,
+                to: TxKind::Call(address!("f3de3c0d654fda23dad170f0f320a92172509127")),
+                value: U256::from(0xb1a2bc2ec50000u64),
+                access_list: Default::default(),
+                input: hex!("9871efa4000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000b1a2bc2ec50000000000000000000000000000000000000000000000000009f7051a01fa559ee400000000000000000000000000000000000000000000000000000000000000800000000000000000000000000000000000000000000000000000000000000001b0000000000000003b6d0340cab7ab9f1a9add91380a0e8fae700b65f320e667").to_vec().into(),
+            }),
+            Signature::from_rs_and_parity(
+                U256::from_be_bytes(hex!(
+                    "e74ec6b1365234a0ebe63f8e238d2318b28d1d2c58ada3a153ad364497dac715"
+                )),
+                U256::from_be_bytes(hex!(
+                    "7306a7cab3679ead15daee428d2481b1b92a5dc2303adfe4b3bbbb4713be74af"
+                )),
+                false,
+            )
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_hint_cache_from_transactions() {
+        let transaction = transaction();
+        let sender = transaction.recover_signer().unwrap();
+        let input = TransactionInput::new(&transaction, sender);
+
+        let recovery = RecoveryInput::from_transaction(&input, 1).unwrap();
+        assert_eq!(recovery.msg_hash, transaction.signature_hash());
+        assert_eq!(recovery.recover(), Some(sender));
+
+        // The recoveries of the transactions are cached, the other ones being computed on a miss.
+        let mut invalid = input.clone();
+        invalid.signature[4] = 2;
+        let cache = HintCache::from_transactions(&[input.clone(), invalid, input], 1);
+        assert_eq!(cache.recoveries.len(), 1);
+        assert_eq!(cache.recover(&recovery), Some(sender));
+
+        let flipped = RecoveryInput { y_parity: !recovery.y_parity, ..recovery };
+        assert_ne!(cache.recover(&flipped), Some(sender));
+    }
+
+    #[test]
+    fn test_recovery_input_y_parity() {
+        let legacy = |v: u128| TransactionInput {
+            rlp_len: 1,
+            rlp: bytes!("c0"),
+            signature_len: 5,
+            signature: vec![1, 0, 2, 0, v],
+            sender: Address::ZERO,
+        };
+        let y_parity = |input: &TransactionInput, chain_id| {
+            RecoveryInput::from_transaction(input, chain_id).map(|recovery| recovery.y_parity)
+        };
+
+        // Pre-EIP-155 and EIP-155 legacy transactions.
+        assert_eq!(y_parity(&legacy(27), 1), Some(false));
+        assert_eq!(y_parity(&legacy(28), 1), Some(true));
+        assert_eq!(y_parity(&legacy(37), 1), Some(false));
+        assert_eq!(y_parity(&legacy(38), 1), Some(true));
+        assert_eq!(y_parity(&legacy(37), 2), None);
+
+        // Typed transactions carry the parity.
+        let typed = TransactionInput { rlp: bytes!("02c0"), ..legacy(1) };
+        assert_eq!(y_parity(&typed, 1), Some(true));
+        assert_eq!(
+            y_parity(&TransactionInput { signature: vec![1, 0, 2, 0, 27], ..typed }, 1),
+            None
+        );
+
+        // The signature must have 5 felts.
+        assert_eq!(
+            y_parity(&TransactionInput { signature: vec![1, 0, 2, 0], ..legacy(27) }, 1),
+            None
+        );
+    }
+
+    #[test]
+    fn test_hint_cache_from_state() {
+        let account = AccountStateInput {
+            balance: U256::ZERO,
+            // PUSH1 0x5b JUMPDEST STOP
+            code: bytes!("605b5b00"),
+            nonce: U64::ZERO,
+            storage: BTreeMap::from([(U256::from(1), U256::from(42))]),
+        };
+        let state: BTreeMap<_, _> =
+            (0..16u8).map(|byte| (Address::repeat_byte(byte), account.clone())).collect();
+
+        let cache = HintCache::from_state(&state);
+        assert_eq!(cache.len(), state.len());
+
+        let precomputed = cache.account(&Address::repeat_byte(1)).unwrap();
+        assert_eq!(precomputed, &PrecomputedAccount::compute(&account));
+        assert_eq!(precomputed.code_hash, keccak256(&account.code));
+        assert_eq!(precomputed.valid_jumpdests, vec![2]);
+        assert_eq!(
+            precomputed.storage_keys[&U256::from(1)],
+            StoragePreimages::storage_key(U256::from(1))
+        );
+        assert!(cache.account(&Address::repeat_byte(0xff)).is_none());
+
+        let preimages = cache.preimages();
+        assert_eq!(preimages.len(), 1);
+        assert_eq!(
+            preimages.get(&StoragePreimages::storage_key(U256::from(1))),
+            Some(U256::from(1))
+        );
+    }
+}
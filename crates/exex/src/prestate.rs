@@ -0,0 +1,343 @@
+//! Geth-compatible `prestateTracer` traces derived from the witness of a block.
+//!
+//! The pre-state accounts of the stored [`ProgramInput`](crate::input::program_input::ProgramInput)
+//! of a block are the exact views of the accounts it touches before its execution. The block is
+//! replayed natively on this witness with
+//! an [`AccessRecorder`](crate::input::access::AccessRecorder), see [`replay_block`], which gives
+//! the accounts and slots touched by the transactions for the default mode, and the [`StateDiff`]
+//! and the deployed codes of the block for the `diffMode`.
+//!
+//! The traces are block-level: the accounts touched by any transaction of the block are reported.
+
+use crate::{
+    db::Database,
+    input::{
+        access::{replay_block, StateAccesses},
+        program_input::AccountStateInput,
+    },
+    output::{AccountDiff, StateDiff},
+};
+use alloy_primitives::{keccak256, Address, Bytes, B256, U256};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// The options of the geth `prestateTracer`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PrestateTracerConfig {
+    /// Whether the pre-state and the post-state of the modified accounts are reported, instead of
+    /// the pre-state of the touched accounts.
+    pub diff_mode: bool,
+    /// Whether the code is omitted from the accounts.
+    pub disable_code: bool,
+    /// Whether the storage is omitted from the accounts.
+    pub disable_storage: bool,
+}
+
+/// An account of a prestate trace, its empty fields being omitted.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrestateAccount {
+    /// The balance of the account.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balance: Option<U256>,
+    /// The nonce of the account.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<u64>,
+    /// The code of the account.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<Bytes>,
+    /// The storage slots of the account.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub storage: BTreeMap<B256, B256>,
+}
+
+impl PrestateAccount {
+    /// Returns whether the account has no field.
+    pub fn is_empty(&self) -> bool {
+        self.balance.is_none() &&
+            self.nonce.is_none() &&
+            self.code.is_none() &&
+            self.storage.is_empty()
+    }
+}
+
+/// A trace of the `prestateTracer`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PrestateTrace {
+    /// The pre-state of the touched accounts.
+    Prestate(BTreeMap<Address, PrestateAccount>),
+    /// The pre-state and the post-state of the modified accounts, in `diffMode`.
+    Diff {
+        /// The pre-state of the modified accounts, with their modified storage slots only.
+        pre: BTreeMap<Address, PrestateAccount>,
+        /// The modified fields of the accounts which still exist after the block.
+        post: BTreeMap<Address, PrestateAccount>,
+    },
+}
+
+impl PrestateTrace {
+    /// Derives the trace of a block from the pre-state accounts of its witness, the state accessed
+    /// by its transactions and its state diff.
+    ///
+    /// In the default mode, the accessed accounts are reported with their accessed slots only. The
+    /// code of the accounts deploying or changing their code in the block is looked up in
+    /// `codes` by hash, or in the witness, and omitted from the post-state when missing from both.
+    /// The EIP-7702 delegated accounts are resolved with the
+    /// [`authorization_codes`](crate::input::delegation::authorization_codes) of the block.
+    pub fn new(
+        witness: &BTreeMap<Address, AccountStateInput>,
+        accesses: &StateAccesses,
+        diff: &StateDiff,
+        codes: &HashMap<B256, Bytes>,
+        config: &PrestateTracerConfig,
+    ) -> Self {
+        if !config.diff_mode {
+            let accounts = accesses
+                .accounts
+                .iter()
+                .map(|(address, slots)| {
+                    let account = witness.get(address).cloned().unwrap_or_default();
+                    (*address, pre_account(&account, |slot| slots.contains(slot), config))
+                })
+                .collect();
+            return Self::Prestate(accounts);
+        }
+
+        let mut codes = codes.clone();
+        codes.extend(
+            witness.values().map(|account| (keccak256(&account.code), account.code.clone())),
+        );
+
+        let mut pre = BTreeMap::new();
+        let mut post = BTreeMap::new();
+        for (address, account_diff) in &diff.accounts {
+            let original = witness.get(address);
+            if let Some(original) = original.filter(|account| !account.is_empty()) {
+                let modified = |slot: &B256| account_diff.storage.contains_key(slot);
+                pre.insert(*address, pre_account(original, modified, config));
+            }
+            if account_diff.destroyed {
+                continue;
+            }
+
+            let account = post_account(original, account_diff, &codes, config);
+            if !account.is_empty() {
+                post.insert(*address, account);
+            }
+        }
+        Self::Diff { pre, post }
+    }
+
+    /// Returns the JSON value of the trace, as returned by geth.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).expect("prestate trace is always serializable")
+    }
+}
+
+/// Traces a stored block by replaying it on the witness of its stored
+/// [`ProgramInput`](crate::input::program_input::ProgramInput).
+pub fn trace_prestate(
+    db: &Database,
+    block_number: u64,
+    config: &PrestateTracerConfig,
+) -> eyre::Result<PrestateTrace> {
+    let block = db
+        .block(U256::from(block_number))?
+        .ok_or_else(|| eyre::eyre!("Block {block_number} not found"))?;
+    let input = db
+        .program_input(block_number)?
+        .ok_or_else(|| eyre::eyre!("No program input recorded for block {block_number}"))?;
+
+    let (accesses, bundle) = replay_block(input.witness_db(), &block, input.chain_id)?;
+    let codes =
+        bundle.contracts.iter().map(|(hash, code)| (*hash, code.original_bytes())).collect();
+    Ok(PrestateTrace::new(&input.state, &accesses, &StateDiff::from(&bundle), &codes, config))
+}
+
+/// Returns the pre-state view of an account, restricted to the storage slots selected by `slots`.
+fn pre_account(
+    account: &AccountStateInput,
+    slots: impl Fn(&B256) -> bool,
+    config: &PrestateTracerConfig,
+) -> PrestateAccount {
+    let storage = if config.disable_storage {
+        BTreeMap::new()
+    } else {
+        account
+            .storage
+            .iter()
+            .map(|(key, value)| (B256::from(*key), B256::from(*value)))
+            .filter(|(key, _)| slots(key))
+            .collect()
+    };
+
+    PrestateAccount {
+        balance: Some(account.balance),
+        nonce: Some(account.nonce.to::<u64>()).filter(|nonce| *nonce != 0),
+        code: (!config.disable_code && !account.code.is_empty()).then(|| account.code.clone()),
+        storage,
+    }
+}
+
+/// Returns the post-state view of an account: the fields modified by its diff, the storage slots
+/// cleared by the block being omitted as geth does.
+fn post_account(
+    original: Option<&AccountStateInput>,
+    diff: &AccountDiff,
+    codes: &HashMap<B256, Bytes>,
+    config: &PrestateTracerConfig,
+) -> PrestateAccount {
+    let storage = if config.disable_storage {
+        BTreeMap::new()
+    } else {
+        diff.storage
+            .iter()
+            .filter(|(key, value)| {
+                let previous =
+                    original.and_then(|account| account.storage.get(&U256::from_be_bytes(key.0)));
+                previous != Some(*value) && !value.is_zero()
+            })
+            .map(|(key, value)| (*key, B256::from(*value)))
+            .collect()
+    };
+    let code = diff
+        .code_hash
+        .filter(|_| !config.disable_code)
+        .and_then(|hash| codes.get(&hash).cloned())
+        .filter(|code| !code.is_empty());
+
+    PrestateAccount { balance: diff.balance, nonce: diff.nonce, code, storage }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{address, U64};
+
+    const SENDER: Address = address!("00000000000000000000000000000000000000aa");
+    const CONTRACT: Address = address!("00000000000000000000000000000000000000bb");
+    const CREATED: Address = address!("00000000000000000000000000000000000000cc");
+
+    fn witness() -> BTreeMap<Address, AccountStateInput> {
+        BTreeMap::from([
+            (
+                SENDER,
+                AccountStateInput {
+                    balance: U256::from(1_000),
+                    nonce: U64::from(1),
+                    ..Default::default()
+                },
+            ),
+            (
+                CONTRACT,
+                AccountStateInput {
+                    balance: U256::ZERO,
+                    code: Bytes::from_static(&[0x60, 0x00]),
+                    nonce: U64::from(1),
+                    storage: BTreeMap::from([
+                        (U256::from(1), U256::from(10)),
+                        (U256::from(2), U256::from(20)),
+                    ]),
+                },
+            ),
+            (CREATED, AccountStateInput::default()),
+        ])
+    }
+
+    fn accesses() -> StateAccesses {
+        let mut accesses = StateAccesses::default();
+        accesses.account(SENDER);
+        accesses.slot(CONTRACT, U256::from(1));
+        accesses
+    }
+
+    fn diff() -> StateDiff {
+        let slot = |value: u64| B256::from(U256::from(value));
+        StateDiff {
+            accounts: BTreeMap::from([
+                (
+                    SENDER,
+                    AccountDiff {
+                        nonce: Some(2),
+                        balance: Some(U256::from(900)),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    CONTRACT,
+                    AccountDiff {
+                        storage: BTreeMap::from([(slot(1), U256::from(11)), (slot(2), U256::ZERO)]),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    CREATED,
+                    AccountDiff {
+                        nonce: Some(1),
+                        code_hash: Some(keccak256([0xfe])),
+                        ..Default::default()
+                    },
+                ),
+            ]),
+        }
+    }
+
+    #[test]
+    fn test_prestate() {
+        let trace = PrestateTrace::new(
+            &witness(),
+            &accesses(),
+            &diff(),
+            &HashMap::new(),
+            &PrestateTracerConfig::default(),
+        );
+        let PrestateTrace::Prestate(accounts) = &trace else { panic!("expected a prestate") };
+
+        // Only the accessed accounts are reported, with their accessed slots.
+        assert_eq!(accounts.keys().collect::<Vec<_>>(), vec![&SENDER, &CONTRACT]);
+        assert_eq!(
+            accounts[&CONTRACT].storage,
+            BTreeMap::from([(B256::from(U256::from(1)), B256::from(U256::from(10)))])
+        );
+        assert_eq!(accounts[&CONTRACT].code, Some(Bytes::from_static(&[0x60, 0x00])));
+
+        let json = trace.to_json();
+        assert_eq!(json[SENDER.to_string().to_lowercase()]["balance"], "0x3e8");
+        assert_eq!(json[SENDER.to_string().to_lowercase()]["nonce"], 1);
+    }
+
+    #[test]
+    fn test_prestate_diff_mode() {
+        let codes = HashMap::from([(keccak256([0xfe]), Bytes::from_static(&[0xfe]))]);
+        let config = PrestateTracerConfig { diff_mode: true, ..Default::default() };
+        let PrestateTrace::Diff { pre, post } =
+            PrestateTrace::new(&witness(), &accesses(), &diff(), &codes, &config)
+        else {
+            panic!("expected a diff")
+        };
+
+        // The created account is missing from the pre-state.
+        assert_eq!(pre.keys().collect::<Vec<_>>(), vec![&SENDER, &CONTRACT]);
+        assert_eq!(pre[&CONTRACT].storage.len(), 2);
+
+        assert_eq!(post[&SENDER].nonce, Some(2));
+        assert_eq!(post[&SENDER].code, None);
+        // The cleared slot is omitted from the post-state.
+        assert_eq!(
+            post[&CONTRACT].storage,
+            BTreeMap::from([(B256::from(U256::from(1)), B256::from(U256::from(11)))])
+        );
+        assert_eq!(post[&CREATED].code, Some(Bytes::from_static(&[0xfe])));
+
+        let config =
+            PrestateTracerConfig { diff_mode: true, disable_code: true, disable_storage: true };
+        let PrestateTrace::Diff { post, .. } =
+            PrestateTrace::new(&witness(), &accesses(), &diff(), &codes, &config)
+        else {
+            panic!("expected a diff")
+        };
+        assert!(!post.contains_key(&CONTRACT));
+        assert_eq!(post[&CREATED].code, None);
+    }
+}
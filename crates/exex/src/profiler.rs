@@ -0,0 +1,296 @@
+//! Profiles of the Cairo steps of an execution, attributed to the Cairo functions.
+//!
+//! The call stack of each step is rebuilt from the relocated trace: a step whose frame pointer is
+//! the one of a frame of the stack returns to that frame, any other frame pointer enters a new
+//! frame, named after the function of its first pc in the identifiers of the program. The steps
+//! are aggregated by call stack into a [`StepProfile`], which is written in the folded-stack
+//! format of the flamegraph tools or as a pprof protobuf.
+
+use crate::{attribution::PROGRAM_BASE, db::Database};
+use cairo_vm::{types::program::Program, vm::trace::trace_entry::RelocatedTraceEntry};
+use prost::Message;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write as _,
+    fs,
+    path::Path,
+    str::FromStr,
+};
+use thiserror::Error;
+
+/// The protobuf messages generated from `proto/perftools/profiles/profile.proto`.
+#[allow(unreachable_pub, missing_debug_implementations, clippy::all)]
+pub mod proto {
+    tonic::include_proto!("perftools.profiles");
+}
+
+/// Represents errors that can occur when writing a profile.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ProfilerError {
+    /// Error variant indicating an unknown profile format.
+    #[error("Unknown profile format '{0}', expected 'folded' or 'pprof'")]
+    UnknownFormat(String),
+}
+
+/// The output format of a profile.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProfileFormat {
+    /// One line per call stack, the functions separated by `;` and followed by the steps, as read
+    /// by `flamegraph.pl` and `inferno`.
+    #[default]
+    Folded,
+    /// A pprof protobuf, as read by `go tool pprof`.
+    Pprof,
+}
+
+impl FromStr for ProfileFormat {
+    type Err = ProfilerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "folded" => Ok(Self::Folded),
+            "pprof" => Ok(Self::Pprof),
+            _ => Err(ProfilerError::UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+/// The functions of a program, sorted by relocated pc.
+#[derive(Debug, Clone, Default)]
+struct Functions(Vec<(usize, String)>);
+
+impl Functions {
+    /// Collects the functions of the identifiers of a program.
+    fn from_program(program: &Program) -> Self {
+        let mut functions: Vec<_> = program
+            .iter_identifiers()
+            .filter(|(_, identifier)| identifier.type_.as_deref() == Some("function"))
+            .filter_map(|(name, identifier)| Some((identifier.pc? + PROGRAM_BASE, name.clone())))
+            .collect();
+        functions.sort();
+        Self(functions)
+    }
+
+    /// Returns the name of the function containing a relocated pc, the pc itself if none.
+    fn at(&self, pc: usize) -> String {
+        let index = self.0.partition_point(|(start, _)| *start <= pc);
+        index.checked_sub(1).map_or_else(|| format!("pc:{pc}"), |index| self.0[index].1.clone())
+    }
+}
+
+/// The steps of an execution, by call stack.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StepProfile {
+    /// The number of steps run in each call stack, the outermost function first.
+    pub stacks: BTreeMap<Vec<String>, usize>,
+}
+
+impl StepProfile {
+    /// Aggregates the steps of a relocated trace of a program by call stack.
+    pub fn from_trace(program: &Program, trace: &[RelocatedTraceEntry]) -> Self {
+        let functions = Functions::from_program(program);
+
+        // The frame pointer and the function of each frame of the current call stack.
+        let mut frames: Vec<(usize, String)> = Vec::new();
+        let mut stacks = BTreeMap::new();
+        let mut steps = 0;
+        for entry in trace {
+            let depth = match frames.iter().rposition(|(fp, _)| *fp == entry.fp) {
+                Some(index) => index + 1,
+                None => frames.len() + 1,
+            };
+            if depth != frames.len() {
+                flush(&mut stacks, &frames, steps);
+                steps = 0;
+                frames.truncate(depth);
+                if frames.len() < depth {
+                    frames.push((entry.fp, functions.at(entry.pc)));
+                }
+            }
+            steps += 1;
+        }
+        flush(&mut stacks, &frames, steps);
+
+        Self { stacks }
+    }
+
+    /// Returns the total number of steps.
+    pub fn steps(&self) -> usize {
+        self.stacks.values().sum()
+    }
+
+    /// Returns the steps run in the body of each function, excluding its callees.
+    pub fn self_steps(&self) -> BTreeMap<String, usize> {
+        let mut functions = BTreeMap::new();
+        for (stack, steps) in &self.stacks {
+            if let Some(function) = stack.last() {
+                *functions.entry(function.clone()).or_default() += steps;
+            }
+        }
+        functions
+    }
+
+    /// Returns the profile in the folded-stack format.
+    pub fn folded(&self) -> String {
+        let mut folded = String::new();
+        for (stack, steps) in &self.stacks {
+            let _ = writeln!(folded, "{} {steps}", stack.join(";"));
+        }
+        folded
+    }
+
+    /// Returns the profile as a pprof profile, with one sample of the steps per call stack.
+    pub fn pprof(&self) -> proto::Profile {
+        let mut strings = vec![String::new()];
+        let mut indexes = HashMap::new();
+        let mut intern = |value: &str| {
+            *indexes.entry(value.to_string()).or_insert_with(|| {
+                strings.push(value.to_string());
+                strings.len() as i64 - 1
+            })
+        };
+
+        // Each function has a single location, sharing its identifier.
+        let mut ids = HashMap::new();
+        let mut functions = Vec::new();
+        let mut samples = Vec::new();
+        for (stack, steps) in &self.stacks {
+            let mut location_id = Vec::with_capacity(stack.len());
+            for function in stack.iter().rev() {
+                let id = *ids.entry(function.as_str()).or_insert_with(|| {
+                    let id = functions.len() as u64 + 1;
+                    let name = intern(function);
+                    functions.push(proto::Function {
+                        id,
+                        name,
+                        system_name: name,
+                        ..Default::default()
+                    });
+                    id
+                });
+                location_id.push(id);
+            }
+            samples.push(proto::Sample {
+                location_id,
+                value: vec![*steps as i64],
+                ..Default::default()
+            });
+        }
+
+        let locations = functions
+            .iter()
+            .map(|function| proto::Location {
+                id: function.id,
+                line: vec![proto::Line { function_id: function.id, line: 0, column: 0 }],
+                ..Default::default()
+            })
+            .collect();
+        let (steps, count) = (intern("steps"), intern("count"));
+
+        proto::Profile {
+            sample_type: vec![proto::ValueType { r#type: steps, unit: count }],
+            period_type: Some(proto::ValueType { r#type: steps, unit: count }),
+            period: 1,
+            sample: samples,
+            location: locations,
+            function: functions,
+            string_table: strings,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the profile encoded in the given format.
+    pub fn encode(&self, format: ProfileFormat) -> Vec<u8> {
+        match format {
+            ProfileFormat::Folded => self.folded().into_bytes(),
+            ProfileFormat::Pprof => self.pprof().encode_to_vec(),
+        }
+    }
+}
+
+/// Adds the steps run in a call stack to the profile.
+fn flush(stacks: &mut BTreeMap<Vec<String>, usize>, frames: &[(usize, String)], steps: usize) {
+    if steps > 0 {
+        let stack = frames.iter().map(|(_, function)| function.clone()).collect();
+        *stacks.entry(stack).or_default() += steps;
+    }
+}
+
+/// Profiles the stored execution of a block, writing the profile in the given format to `output`.
+pub fn profile_block(
+    db: &Database,
+    program: &Path,
+    block_number: u64,
+    format: ProfileFormat,
+    output: &Path,
+) -> eyre::Result<StepProfile> {
+    let (trace, _) = db
+        .execution_trace(block_number)?
+        .ok_or_else(|| eyre::eyre!("No trace found for block {block_number}"))?;
+    let program = Program::from_bytes(&fs::read(program)?, Some("main"))?;
+
+    let profile = StepProfile::from_trace(&program, &trace);
+    fs::write(output, profile.encode(format))?;
+    Ok(profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        executor::{execute, ExecutionMode},
+        hints::KakarotHintProcessor,
+        limits::LimitedRun,
+        tuning::RunnerTuning,
+    };
+
+    #[test]
+    fn test_profile_formats() {
+        assert_eq!("pprof".parse(), Ok(ProfileFormat::Pprof));
+        assert_eq!(
+            "svg".parse::<ProfileFormat>(),
+            Err(ProfilerError::UnknownFormat("svg".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_step_profile() {
+        let mut hint_processor = KakarotHintProcessor::default();
+        let run = execute(
+            include_bytes!("../testdata/keccak_add_uint256.json"),
+            ExecutionMode::Proof,
+            &mut hint_processor,
+            &Default::default(),
+            &RunnerTuning::default(),
+            &mut || false,
+        )
+        .unwrap();
+        let LimitedRun::Completed(runner) = run else { panic!("Expected a completed run") };
+        let trace = runner.relocated_trace.clone().unwrap();
+
+        let profile = StepProfile::from_trace(runner.get_program(), &trace);
+        assert_eq!(profile.steps(), trace.len());
+        assert_eq!(profile.self_steps().values().sum::<usize>(), trace.len());
+
+        // The callees of `main` are profiled below it.
+        let keccak = "starkware.cairo.common.keccak_utils.keccak_utils.keccak_add_uint256";
+        assert!(profile.stacks.keys().any(|stack| {
+            let main = stack.iter().position(|function| function == "__main__.main");
+            let callee = stack.iter().position(|function| function == keccak);
+            matches!((main, callee), (Some(main), Some(callee)) if main < callee)
+        }));
+
+        let folded = profile.folded();
+        assert_eq!(folded.lines().count(), profile.stacks.len());
+
+        let pprof =
+            proto::Profile::decode(profile.encode(ProfileFormat::Pprof).as_slice()).unwrap();
+        assert_eq!(pprof, profile.pprof());
+        assert_eq!(pprof.sample.len(), profile.stacks.len());
+        assert_eq!(
+            pprof.sample.iter().map(|sample| sample.value[0]).sum::<i64>(),
+            trace.len() as i64
+        );
+        assert_eq!(pprof.string_table[0], "");
+    }
+}
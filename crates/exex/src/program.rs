@@ -0,0 +1,325 @@
+//! The compiled Kakarot program loaded by the instances, and its canonical hash.
+//!
+//! The program hash is the hash computed by the bootloader over the stripped program, which is the
+//! hash committed in the facts of the proofs and in the configuration of the on-chain verifiers.
+//! It identifies the program the artifacts and the proofs of a block were produced with.
+//!
+//! A chain whose rules changed over time, e.g. at each hardfork, is proven by a different program
+//! for each range of blocks. The [`ProgramRegistry`] maps the ranges to their programs, so that the
+//! program of each block is selected automatically, and the hash of the program selected for a
+//! block is recorded alongside its artifacts as a [`BlockProgram`].
+
+use alloy_primitives::B256;
+use cairo_vm::{program_hash::compute_program_hash_chain, types::program::Program};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// The version of the bootloader hashing the programs.
+pub const BOOTLOADER_VERSION: usize = 0;
+
+/// The entrypoint of the Kakarot program.
+pub const ENTRYPOINT: &str = "main";
+
+/// Represents errors that can occur when loading or hashing a program.
+#[derive(Debug, Error)]
+pub enum ProgramError {
+    /// Error variant indicating a failure to read the program.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// Error variant indicating an invalid compiled program.
+    #[error("Invalid program: {0}")]
+    Parse(String),
+
+    /// Error variant indicating that the program hash cannot be computed.
+    #[error("Failed to compute the program hash: {0}")]
+    Hash(String),
+
+    /// Error variant indicating a registry entry ending before its first block.
+    #[error("Invalid block range {from_block}..={to_block} of the program of fork '{fork}'")]
+    InvalidRange {
+        /// The fork of the entry.
+        fork: String,
+        /// The first block of the entry.
+        from_block: u64,
+        /// The last block of the entry.
+        to_block: u64,
+    },
+
+    /// Error variant indicating registry entries whose block ranges overlap.
+    #[error("The block ranges of the programs of forks '{fork}' and '{other}' overlap")]
+    OverlappingRanges {
+        /// The fork of the first entry.
+        fork: String,
+        /// The fork of the overlapping entry.
+        other: String,
+    },
+
+    /// Error variant indicating a program whose hash is not the one pinned in the registry.
+    #[error("Program hash {actual} of fork '{fork}', expected {expected}")]
+    HashMismatch {
+        /// The fork of the program.
+        fork: String,
+        /// The hash pinned in the registry.
+        expected: B256,
+        /// The hash of the program.
+        actual: B256,
+    },
+}
+
+/// A compiled Kakarot program, as read and as parsed.
+#[derive(Debug, Clone)]
+pub struct KakarotProgram {
+    /// The compiled program.
+    bytes: Vec<u8>,
+    /// The parsed program.
+    program: Program,
+}
+
+impl KakarotProgram {
+    /// Parses a compiled program.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, ProgramError> {
+        let program = Program::from_bytes(&bytes, Some(ENTRYPOINT))
+            .map_err(|err| ProgramError::Parse(err.to_string()))?;
+        Ok(Self { bytes, program })
+    }
+
+    /// Loads a compiled program from a file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ProgramError> {
+        Self::from_bytes(fs::read(path)?)
+    }
+
+    /// Returns the compiled program.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the parsed program.
+    pub const fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /// Returns the canonical hash of the program, as hashed by the bootloader.
+    pub fn program_hash(&self) -> Result<B256, ProgramError> {
+        program_hash(&self.program)
+    }
+}
+
+/// Returns the canonical hash of a parsed program, as hashed by the bootloader.
+pub fn program_hash(program: &Program) -> Result<B256, ProgramError> {
+    let stripped =
+        program.get_stripped_program().map_err(|err| ProgramError::Hash(err.to_string()))?;
+    let hash = compute_program_hash_chain(&stripped, BOOTLOADER_VERSION)
+        .map_err(|err| ProgramError::Hash(err.to_string()))?;
+    Ok(B256::from(hash.to_bytes_be()))
+}
+
+/// The program proving a range of blocks, e.g. the blocks of a hardfork.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgramEntry {
+    /// The name of the fork of the range.
+    pub fork: String,
+    /// The first block of the range.
+    pub from_block: u64,
+    /// The last block of the range, included, the range being open when `None`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_block: Option<u64>,
+    /// The path of the compiled program, relative to the registry file.
+    pub program: PathBuf,
+    /// The expected hash of the program, checked when the program is loaded if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub program_hash: Option<B256>,
+}
+
+impl ProgramEntry {
+    /// Returns whether the entry covers the given block.
+    pub fn contains(&self, block_number: u64) -> bool {
+        block_number >= self.from_block && self.to_block.is_none_or(|to| block_number <= to)
+    }
+
+    /// Loads the program of the entry, checking its pinned hash if any. Returns the program and
+    /// its hash.
+    pub fn load(&self) -> Result<(KakarotProgram, B256), ProgramError> {
+        let program = KakarotProgram::load(&self.program)?;
+        let hash = program.program_hash()?;
+        match self.program_hash {
+            Some(expected) if expected != hash => {
+                Err(ProgramError::HashMismatch { fork: self.fork.clone(), expected, actual: hash })
+            }
+            _ => Ok((program, hash)),
+        }
+    }
+}
+
+/// The programs proving the ranges of blocks of a chain, ordered by block.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProgramRegistry {
+    /// The entries, ordered by first block.
+    entries: Vec<ProgramEntry>,
+}
+
+impl ProgramRegistry {
+    /// Creates a registry from its entries, validating that their ranges do not overlap.
+    pub fn from_entries(mut entries: Vec<ProgramEntry>) -> Result<Self, ProgramError> {
+        entries.sort_by_key(|entry| entry.from_block);
+        for entry in &entries {
+            if let Some(to_block) = entry.to_block.filter(|to| *to < entry.from_block) {
+                return Err(ProgramError::InvalidRange {
+                    fork: entry.fork.clone(),
+                    from_block: entry.from_block,
+                    to_block,
+                });
+            }
+        }
+        for pair in entries.windows(2) {
+            if pair[0].contains(pair[1].from_block) {
+                return Err(ProgramError::OverlappingRanges {
+                    fork: pair[0].fork.clone(),
+                    other: pair[1].fork.clone(),
+                });
+            }
+        }
+        Ok(Self { entries })
+    }
+
+    /// Loads and validates the registry from a JSON file holding a list of [`ProgramEntry`], the
+    /// relative paths of the programs being resolved against the directory of the file.
+    pub fn load(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let path = path.as_ref();
+        let mut entries: Vec<ProgramEntry> = serde_json::from_slice(&fs::read(path)?)?;
+        if let Some(dir) = path.parent() {
+            for entry in &mut entries {
+                entry.program = dir.join(&entry.program);
+            }
+        }
+        Ok(Self::from_entries(entries)?)
+    }
+
+    /// Returns the entries of the registry, ordered by first block.
+    pub fn entries(&self) -> &[ProgramEntry] {
+        &self.entries
+    }
+
+    /// Returns the entry of the program proving the given block, if any.
+    pub fn select(&self, block_number: u64) -> Option<&ProgramEntry> {
+        let index = self.entries.partition_point(|entry| entry.from_block <= block_number);
+        index
+            .checked_sub(1)
+            .map(|index| &self.entries[index])
+            .filter(|entry| entry.contains(block_number))
+    }
+}
+
+/// The program selected for a block, recorded alongside its artifacts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockProgram {
+    /// The number of the block.
+    pub block_number: u64,
+    /// The fork of the registry entry of the program, `None` for the default program of the
+    /// instance.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fork: Option<String>,
+    /// The path of the program.
+    pub program: PathBuf,
+    /// The canonical hash of the program.
+    pub program_hash: B256,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(fork: &str, from_block: u64, to_block: Option<u64>) -> ProgramEntry {
+        ProgramEntry {
+            fork: fork.to_string(),
+            from_block,
+            to_block,
+            program: PathBuf::from(format!("{fork}.json")),
+            program_hash: None,
+        }
+    }
+
+    #[test]
+    fn test_program_hash() {
+        let bytes = include_bytes!("../testdata/keccak_add_uint256.json").to_vec();
+        let program = KakarotProgram::from_bytes(bytes.clone()).unwrap();
+        assert_eq!(program.bytes(), bytes.as_slice());
+
+        // The hash is deterministic.
+        let hash = program.program_hash().unwrap();
+        assert_ne!(hash, B256::ZERO);
+        assert_eq!(
+            KakarotProgram::from_bytes(bytes.clone()).unwrap().program_hash().unwrap(),
+            hash
+        );
+
+        // The hash only commits to the stripped program, i.e. to its bytecode, builtins and main,
+        // and not to its hints.
+        let rehash = |edit: &dyn Fn(&mut serde_json::Value)| {
+            let mut json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            edit(&mut json);
+            let program = KakarotProgram::from_bytes(serde_json::to_vec(&json).unwrap()).unwrap();
+            program.program_hash().unwrap()
+        };
+        assert_eq!(rehash(&|json| json["hints"] = serde_json::json!({})), hash);
+        assert_ne!(rehash(&|json| json["data"][1] = "0x4".into()), hash);
+        assert_ne!(rehash(&|json| json["builtins"] = serde_json::json!([])), hash);
+
+        assert!(matches!(KakarotProgram::from_bytes(b"{}".to_vec()), Err(ProgramError::Parse(_))));
+    }
+
+    #[test]
+    fn test_select_program() {
+        let registry = ProgramRegistry::from_entries(vec![
+            entry("cancun", 100, None),
+            entry("frontier", 0, Some(49)),
+            entry("london", 60, Some(99)),
+        ])
+        .unwrap();
+        assert_eq!(registry.entries()[0].fork, "frontier");
+
+        let fork = |block| registry.select(block).map(|entry| entry.fork.as_str());
+        assert_eq!(fork(0), Some("frontier"));
+        assert_eq!(fork(49), Some("frontier"));
+        // The blocks between the ranges have no program.
+        assert_eq!(fork(55), None);
+        assert_eq!(fork(60), Some("london"));
+        assert_eq!(fork(99), Some("london"));
+        assert_eq!(fork(100), Some("cancun"));
+        assert_eq!(fork(u64::MAX), Some("cancun"));
+    }
+
+    #[test]
+    fn test_invalid_registry() {
+        assert!(matches!(
+            ProgramRegistry::from_entries(vec![entry("london", 60, Some(59))]),
+            Err(ProgramError::InvalidRange { from_block: 60, to_block: 59, .. })
+        ));
+        let err = ProgramRegistry::from_entries(vec![
+            entry("london", 60, Some(100)),
+            entry("cancun", 100, None),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The block ranges of the programs of forks 'london' and 'cancun' overlap"
+        );
+    }
+
+    #[test]
+    fn test_load_pinned_program() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata");
+        let mut entry = entry("cancun", 0, None);
+        entry.program = dir.join("keccak_add_uint256.json");
+        let (_, hash) = entry.load().unwrap();
+
+        entry.program_hash = Some(hash);
+        assert!(entry.load().is_ok());
+        entry.program_hash = Some(B256::ZERO);
+        assert!(matches!(entry.load(), Err(ProgramError::HashMismatch { .. })));
+    }
+}
@@ -0,0 +1,248 @@
+//! Redundant proving of the blocks by several provers, with a quorum on their results.
+//!
+//! While the provers are not trusted yet, each block can be proven by several independent prover
+//! backends. The result of each prover is recorded with the program hash and the output root of
+//! the run it proved, and the fact it claims if any. The block is only marked as proven once all
+//! the configured provers proved the same program and output, and claimed the same fact. Diverging
+//! results point to a bug in one of the provers: they are reported by the
+//! `kakarot_prover_divergences` counter and the block stays unproven until investigated.
+
+use crate::{db::Database, deferred::JobState, output::ProofMetadata, store::KethStore};
+use alloy_primitives::B256;
+use reth_tracing::tracing::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use thiserror::Error;
+
+/// The minimum number of provers of a quorum.
+pub const MIN_QUORUM_PROVERS: usize = 2;
+
+/// Represents errors that can occur when proving with a quorum of provers.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum QuorumError {
+    /// Error variant indicating a quorum with too few distinct provers.
+    #[error("A quorum requires at least {MIN_QUORUM_PROVERS} distinct provers, got {0}")]
+    TooFewProvers(usize),
+
+    /// Error variant indicating a result of a prover outside of the quorum.
+    #[error("Prover '{0}' is not part of the quorum")]
+    UnknownProver(String),
+}
+
+/// The provers which must agree on the result of a block for it to be proven.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuorumConfig {
+    /// The names of the provers, as recorded in the metadata of their proofs.
+    provers: BTreeSet<String>,
+}
+
+impl QuorumConfig {
+    /// Creates a quorum of the given provers, at least [`MIN_QUORUM_PROVERS`] distinct ones.
+    pub fn new(provers: impl IntoIterator<Item = String>) -> Result<Self, QuorumError> {
+        let provers: BTreeSet<_> = provers.into_iter().collect();
+        if provers.len() < MIN_QUORUM_PROVERS {
+            return Err(QuorumError::TooFewProvers(provers.len()));
+        }
+        Ok(Self { provers })
+    }
+
+    /// Returns the provers of the quorum.
+    pub const fn provers(&self) -> &BTreeSet<String> {
+        &self.provers
+    }
+}
+
+/// The result of a prover for a block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProverResult {
+    /// The metadata of the proof, holding the block number, the prover and the program hash and
+    /// output root of the proven run.
+    pub metadata: ProofMetadata,
+    /// The fact claimed by the prover, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fact: Option<B256>,
+}
+
+/// The state of the quorum of a block after the recording of a result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuorumOutcome {
+    /// Some provers did not report their result yet.
+    Pending {
+        /// The provers which reported their result.
+        received: usize,
+        /// The provers of the quorum.
+        required: usize,
+    },
+    /// All the provers agree, the block is proven with the agreed output root.
+    Agreed(B256),
+    /// The provers reported different results, by prover.
+    Diverged(BTreeMap<String, ProverResult>),
+}
+
+/// Records the result of a prover for a block, marking the block as proven once all the provers
+/// of the quorum agree on its result.
+///
+/// The recorded proof of an agreed block is the one of the first prover of the quorum, in
+/// lexicographic order, inserted in `store`. A divergence is reported on each recorded result
+/// until the diverging result is replaced.
+pub fn record_result(
+    db: &Database,
+    store: &dyn KethStore,
+    quorum: &QuorumConfig,
+    result: &ProverResult,
+    proof: &[u8],
+) -> eyre::Result<QuorumOutcome> {
+    let block_number = result.metadata.block_number;
+    if !quorum.provers.contains(&result.metadata.prover) {
+        return Err(QuorumError::UnknownProver(result.metadata.prover.clone()).into());
+    }
+    db.insert_prover_result(result, proof)?;
+
+    let results: BTreeMap<_, _> = db
+        .prover_results(block_number)?
+        .into_iter()
+        .filter(|result| quorum.provers.contains(&result.metadata.prover))
+        .map(|result| (result.metadata.prover.clone(), result))
+        .collect();
+
+    // The provers must prove the same program and output, and claim the same fact if any.
+    let runs: BTreeSet<_> = results
+        .values()
+        .map(|result| (result.metadata.program_hash, result.metadata.output_root))
+        .collect();
+    let facts: BTreeSet<_> = results.values().filter_map(|result| result.fact).collect();
+    if runs.len() > 1 || facts.len() > 1 {
+        let output_roots: BTreeMap<_, _> =
+            results.iter().map(|(prover, result)| (prover, result.metadata.output_root)).collect();
+        warn!(
+            target: "kkrt::quorum",
+            number = block_number,
+            output_roots = ?output_roots,
+            "Provers diverged"
+        );
+        metrics::counter!("kakarot_prover_divergences").increment(1);
+        return Ok(QuorumOutcome::Diverged(results));
+    }
+
+    if results.len() < quorum.provers.len() {
+        return Ok(QuorumOutcome::Pending {
+            received: results.len(),
+            required: quorum.provers.len(),
+        });
+    }
+
+    let prover = quorum.provers.first().expect("non empty quorum");
+    let (metadata, proof) = db
+        .prover_proof(block_number, prover)?
+        .ok_or_else(|| QuorumError::UnknownProver(prover.clone()))?;
+    store.insert_proof(&metadata, &proof)?;
+    db.set_proving_job_state(block_number, JobState::Proven)?;
+    Ok(QuorumOutcome::Agreed(result.metadata.output_root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deferred::ProvingJob;
+    use rusqlite::Connection;
+
+    fn setup_db() -> Database {
+        let db = Database::new(Connection::open_in_memory().unwrap()).unwrap();
+        db.enqueue_proving_job(&ProvingJob::new(1, "program.json".into())).unwrap();
+        db
+    }
+
+    fn quorum() -> QuorumConfig {
+        QuorumConfig::new(["stone".to_string(), "sharp".to_string()]).unwrap()
+    }
+
+    fn result(prover: &str, output_root: u8) -> ProverResult {
+        ProverResult {
+            metadata: ProofMetadata {
+                block_number: 1,
+                output_root: B256::with_last_byte(output_root),
+                prover: prover.to_string(),
+                ..Default::default()
+            },
+            fact: None,
+        }
+    }
+
+    #[test]
+    fn test_quorum_agreement() {
+        let db = setup_db();
+        let quorum = quorum();
+
+        assert_eq!(
+            record_result(&db, &db, &quorum, &result("stone", 1), b"stone").unwrap(),
+            QuorumOutcome::Pending { received: 1, required: 2 }
+        );
+        assert_eq!(db.proving_job_state(1).unwrap(), Some(JobState::Queued));
+
+        assert_eq!(
+            record_result(&db, &db, &quorum, &result("sharp", 1), b"").unwrap(),
+            QuorumOutcome::Agreed(B256::with_last_byte(1))
+        );
+        assert_eq!(db.proving_job_state(1).unwrap(), Some(JobState::Proven));
+        // The proof of the first prover in lexicographic order is recorded.
+        assert_eq!(db.proof(1).unwrap().map(|(metadata, _)| metadata.prover), Some("sharp".into()));
+    }
+
+    #[test]
+    fn test_quorum_divergence() {
+        let db = setup_db();
+        let quorum = quorum();
+
+        record_result(&db, &db, &quorum, &result("stone", 1), b"stone").unwrap();
+        let outcome = record_result(&db, &db, &quorum, &result("sharp", 2), b"").unwrap();
+
+        assert_eq!(
+            outcome,
+            QuorumOutcome::Diverged(BTreeMap::from([
+                ("sharp".to_string(), result("sharp", 2)),
+                ("stone".to_string(), result("stone", 1)),
+            ]))
+        );
+        assert_eq!(db.proving_job_state(1).unwrap(), Some(JobState::Queued));
+        assert!(db.proof(1).unwrap().is_none());
+
+        // The diverging result is replaced once the prover is fixed.
+        assert_eq!(
+            record_result(&db, &db, &quorum, &result("sharp", 1), b"").unwrap(),
+            QuorumOutcome::Agreed(B256::with_last_byte(1))
+        );
+    }
+
+    #[test]
+    fn test_quorum_facts() {
+        let db = setup_db();
+        let quorum = quorum();
+        let claim = |prover, fact| ProverResult {
+            fact: Some(B256::with_last_byte(fact)),
+            ..result(prover, 1)
+        };
+
+        // The same output with different claimed facts diverges.
+        record_result(&db, &db, &quorum, &claim("stone", 1), b"stone").unwrap();
+        let outcome = record_result(&db, &db, &quorum, &claim("sharp", 2), b"").unwrap();
+        assert!(matches!(outcome, QuorumOutcome::Diverged(_)));
+
+        // A prover without claimed fact agrees on the proven output.
+        assert_eq!(
+            record_result(&db, &db, &quorum, &result("sharp", 1), b"").unwrap(),
+            QuorumOutcome::Agreed(B256::with_last_byte(1))
+        );
+    }
+
+    #[test]
+    fn test_invalid_quorum() {
+        assert_eq!(
+            QuorumConfig::new(["stone".to_string(), "stone".to_string()]),
+            Err(QuorumError::TooFewProvers(1))
+        );
+
+        let err = record_result(&setup_db(), &setup_db(), &quorum(), &result("other", 1), b"")
+            .unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&QuorumError::UnknownProver("other".to_string())));
+    }
+}
@@ -0,0 +1,677 @@
+//! Reconstruction of the receipts of the Cairo execution of a block, and their verification against
+//! the execution outcome of reth.
+//!
+//! The results of the transactions are serialized from the `model.EVM` and `model.State` returned
+//! by each invocation of [`EXECUTE_FUNCTION`], see
+//! [`KakarotSerde::serialize_evm`](crate::serde::KakarotSerde::serialize_evm), and turned into reth
+//! [`Receipt`]s and an [`ExecutionOutcome`]. [`CairoOutcome::verify_against`] compares them field
+//! by field with the outcome of the native execution, and [`CairoOutcome::verify_header`] compares
+//! them and the output of the program with the header of the block, each divergence being reported
+//! as a [`Mismatch`] naming the transaction and the field it concerns.
+
+use crate::{
+    attribution::{felt_to_usize, invocations, AttributionError, FrameSpec, EXECUTE_FUNCTION},
+    halt::ExecutionStatus,
+    input::program_input::{HeaderInput, TransactionInput},
+    output::ProgramOutput,
+    rlp,
+    serde::{
+        builder::BuilderError, cache::ProgramLayoutCache, relocated::RelocatedMemory, CairoType,
+        KakarotSerde, KakarotSerdeError,
+    },
+};
+use alloy_primitives::{Address, Bloom, B256};
+use cairo_vm::{
+    types::{program::Program, relocatable::Relocatable},
+    vm::trace::trace_entry::RelocatedTraceEntry,
+};
+use reth_execution_types::ExecutionOutcome;
+use reth_primitives::{
+    revm_primitives::{ExecutionResult, Output},
+    Receipt, Receipts, TxType,
+};
+use reth_revm::db::BundleState;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Debug};
+use thiserror::Error;
+
+/// Represents errors that can occur when reading the results of the transactions of a block.
+#[derive(Debug, Error)]
+pub enum ReceiptError {
+    /// Error variant indicating that the frame of [`EXECUTE_FUNCTION`] cannot be laid out.
+    #[error(transparent)]
+    Attribution(#[from] AttributionError),
+
+    /// Error variant indicating that the serializer of the program cannot be built.
+    #[error(transparent)]
+    Builder(#[from] BuilderError),
+
+    /// Error variant indicating that a returned struct cannot be serialized.
+    #[error(transparent)]
+    Serde(#[from] KakarotSerdeError),
+
+    /// Error variant indicating that a memory cell does not hold the expected value.
+    #[error("Invalid value at relocated address {0}")]
+    InvalidValue(usize),
+}
+
+/// The layout of the frame of [`EXECUTE_FUNCTION`], from which the result of each transaction is
+/// read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecuteLayout {
+    /// The frame of the function.
+    pub spec: FrameSpec,
+    /// The offset of the `gas_limit` argument among the explicit arguments.
+    pub gas_limit: usize,
+    /// The offset of the returned `model.EVM*` among the explicit return values.
+    pub evm: usize,
+    /// The offset of the returned `model.State*` among the explicit return values.
+    pub state: usize,
+}
+
+impl ExecuteLayout {
+    /// Builds the layout of the frame from the identifiers of the program.
+    pub fn from_program(program: &Program) -> Result<Self, AttributionError> {
+        let spec = FrameSpec::execute(program)?;
+        let missing =
+            |name: &str| AttributionError::MissingIdentifier(format!("{EXECUTE_FUNCTION}.{name}"));
+
+        let gas_limit = program
+            .get_identifier(&format!("{EXECUTE_FUNCTION}.Args"))
+            .and_then(|args| args.members.as_ref()?.get("gas_limit"))
+            .map(|member| member.offset)
+            .ok_or_else(|| missing("Args.gas_limit"))?;
+
+        let return_type = program
+            .get_identifier(&format!("{EXECUTE_FUNCTION}.Return"))
+            .and_then(|identifier| identifier.cairo_type.clone())
+            .ok_or_else(|| missing("Return"))?;
+        let CairoType::Tuple { members, .. } = CairoType::parse(&return_type) else {
+            return Err(missing("Return"));
+        };
+        let layouts = ProgramLayoutCache::new(program);
+        let (mut evm, mut state, mut position) = (None, None, 0);
+        for member in &members {
+            match pointee(&member.typ) {
+                Some("EVM") => evm = evm.or(Some(position)),
+                Some("State") => state = state.or(Some(position)),
+                _ => {}
+            }
+            position += layouts
+                .type_size(&member.typ)
+                .map_err(|_| AttributionError::UnknownSize(return_type.clone()))?;
+        }
+
+        Ok(Self {
+            spec,
+            gas_limit,
+            evm: evm.ok_or_else(|| missing("Return.evm"))?,
+            state: state.ok_or_else(|| missing("Return.state"))?,
+        })
+    }
+}
+
+/// An invocation of [`EXECUTE_FUNCTION`] which returned, executing a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Invocation {
+    /// The gas limit of the transaction.
+    pub gas_limit: u64,
+    /// The returned `model.EVM`.
+    pub evm: Relocatable,
+    /// The returned `model.State`, after the transaction.
+    pub state: Relocatable,
+}
+
+impl ExecuteLayout {
+    /// Returns the invocations of [`EXECUTE_FUNCTION`] in a relocated trace, one per transaction,
+    /// reading the relocated memory with `read`.
+    ///
+    /// The last invocation of an interrupted trace never returns and is skipped.
+    pub fn invocations(
+        &self,
+        trace: &[RelocatedTraceEntry],
+        read: impl Fn(usize) -> Result<usize, ReceiptError>,
+    ) -> Result<Vec<Invocation>, ReceiptError> {
+        let spec = &self.spec;
+        let mut returned = Vec::new();
+        for (start, end) in invocations(spec, trace) {
+            let Some(exit) = trace.get(end) else { break };
+            let fp = trace[start].fp;
+            let args_start =
+                fp.checked_sub(2 + spec.args_size).ok_or(ReceiptError::InvalidValue(fp))?;
+            let returns_start =
+                exit.ap.checked_sub(spec.return_size).ok_or(ReceiptError::InvalidValue(exit.ap))?;
+
+            returned.push(Invocation {
+                gas_limit: read(args_start + self.gas_limit)? as u64,
+                evm: RelocatedMemory::address(read(returns_start + self.evm)?),
+                state: RelocatedMemory::address(read(returns_start + self.state)?),
+            });
+        }
+        Ok(returned)
+    }
+}
+
+/// Returns the name of the struct a pointer type points to.
+fn pointee(cairo_type: &CairoType) -> Option<&str> {
+    let CairoType::Pointer { pointee, .. } = cairo_type else { return None };
+    let CairoType::Struct { scope, .. } = pointee.as_ref() else { return None };
+    scope.path.last().map(String::as_str)
+}
+
+/// Returns the type of a transaction from its unsigned RLP encoding: typed transactions start with
+/// their type, legacy ones with the header of a list.
+fn tx_type(transaction: &TransactionInput) -> TxType {
+    match transaction.rlp.first() {
+        Some(&byte) if byte < 0x7f => TxType::try_from(byte).unwrap_or_default(),
+        _ => TxType::Legacy,
+    }
+}
+
+/// The field of a [`Mismatch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "field", content = "index", rename_all = "snake_case")]
+pub enum MismatchField {
+    /// The number of transactions of the block.
+    TransactionCount,
+    /// The type of a transaction.
+    TxType,
+    /// The status of a transaction.
+    Status,
+    /// The gas used by a transaction.
+    GasUsed,
+    /// The gas used by a transaction and the preceding ones.
+    CumulativeGasUsed,
+    /// The number of logs of a transaction.
+    LogCount,
+    /// A log of a transaction, by index in the transaction.
+    Log(usize),
+    /// A contract created by a transaction, missing from the native state.
+    CreatedContract,
+    /// The logs bloom of the block.
+    LogsBloom,
+    /// The gas used by the block, summed over its receipts.
+    BlockGasUsed,
+    /// The gas used by the block, as written to the output of the program.
+    OutputGasUsed,
+    /// The root of the receipts trie of the block.
+    ReceiptsRoot,
+    /// The state root after the execution of the block.
+    StateRoot,
+}
+
+impl fmt::Display for MismatchField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TransactionCount => f.write_str("transaction count"),
+            Self::TxType => f.write_str("transaction type"),
+            Self::Status => f.write_str("status"),
+            Self::GasUsed => f.write_str("gas used"),
+            Self::CumulativeGasUsed => f.write_str("cumulative gas used"),
+            Self::LogCount => f.write_str("log count"),
+            Self::Log(index) => write!(f, "log {index}"),
+            Self::CreatedContract => f.write_str("created contract"),
+            Self::LogsBloom => f.write_str("logs bloom"),
+            Self::BlockGasUsed => f.write_str("block gas used"),
+            Self::OutputGasUsed => f.write_str("output gas used"),
+            Self::ReceiptsRoot => f.write_str("receipts root"),
+            Self::StateRoot => f.write_str("state root"),
+        }
+    }
+}
+
+/// A field of the Cairo execution of a block differing from the native execution.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mismatch {
+    /// The number of the block.
+    pub block_number: u64,
+    /// The index of the transaction in the block, `None` for the fields of the block.
+    pub tx_index: Option<usize>,
+    /// The differing field.
+    pub field: MismatchField,
+    /// The value of the native execution.
+    pub expected: String,
+    /// The value of the Cairo execution.
+    pub actual: String,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Block {}", self.block_number)?;
+        if let Some(tx_index) = self.tx_index {
+            write!(f, ", transaction {tx_index}")?;
+        }
+        write!(f, ": {} mismatch, expected {}, got {}", self.field, self.expected, self.actual)
+    }
+}
+
+/// The result of a transaction of the Cairo execution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CairoTransaction {
+    /// The type of the transaction.
+    pub tx_type: TxType,
+    /// The result of the transaction, as serialized from the returned EVM.
+    pub result: ExecutionResult,
+}
+
+impl CairoTransaction {
+    /// Returns the address of the contract created by the transaction, if any.
+    pub fn created_contract(&self) -> Option<Address> {
+        match &self.result {
+            ExecutionResult::Success { output: Output::Create(_, address), .. } => *address,
+            _ => None,
+        }
+    }
+}
+
+/// The results of the transactions of the Cairo execution of a block.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CairoOutcome {
+    /// The number of the block.
+    pub block_number: u64,
+    /// The results of the transactions, in order.
+    pub transactions: Vec<CairoTransaction>,
+}
+
+impl CairoOutcome {
+    /// Creates a new [`CairoOutcome`] of a block without transactions.
+    pub const fn new(block_number: u64) -> Self {
+        Self { block_number, transactions: Vec::new() }
+    }
+
+    /// Reads the results of the transactions of a block from the relocated trace and memory of its
+    /// Cairo execution, one per returned invocation of [`EXECUTE_FUNCTION`].
+    ///
+    /// The gas used is read from the `gas_limit` argument and the returned `model.EVM`. The types
+    /// of the transactions are read from the program input of the block.
+    pub fn from_trace(
+        program: &[u8],
+        layout: &ExecuteLayout,
+        block_number: u64,
+        transactions: &[TransactionInput],
+        trace: &[RelocatedTraceEntry],
+        memory: RelocatedMemory,
+    ) -> Result<Self, ReceiptError> {
+        let serde =
+            KakarotSerde::builder().program_bytes(program).relocated_memory(memory).build()?;
+        let read = |address: usize| {
+            serde
+                .read_felt(RelocatedMemory::address(address))
+                .ok()
+                .as_ref()
+                .and_then(felt_to_usize)
+                .ok_or(ReceiptError::InvalidValue(address))
+        };
+
+        let mut outcome = Self::new(block_number);
+        for (tx_index, invocation) in layout.invocations(trace, read)?.into_iter().enumerate() {
+            let tx_type = transactions.get(tx_index).map(tx_type).unwrap_or_default();
+            let roots = [(invocation.evm, "model.EVM"), (invocation.state, "model.State")];
+            let result = serde
+                .serialize_evm(invocation.evm, invocation.state, invocation.gas_limit)
+                .map_err(|err| serde.locate_error(err, &roots))?;
+            outcome.push(tx_type, result);
+        }
+        Ok(outcome)
+    }
+
+    /// Adds the result of the next transaction of the block.
+    pub fn push(&mut self, tx_type: TxType, result: ExecutionResult) {
+        self.transactions.push(CairoTransaction { tx_type, result });
+    }
+
+    /// Returns the receipts of the transactions.
+    pub fn receipts(&self) -> Vec<Receipt> {
+        let mut cumulative_gas_used = 0;
+        self.transactions
+            .iter()
+            .map(|transaction| {
+                cumulative_gas_used += transaction.result.gas_used();
+                Receipt {
+                    tx_type: transaction.tx_type,
+                    success: transaction.result.is_success(),
+                    cumulative_gas_used,
+                    logs: transaction.result.logs().to_vec(),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the logs bloom of the block.
+    pub fn logs_bloom(&self) -> Bloom {
+        let mut bloom = Bloom::ZERO;
+        for log in self.transactions.iter().flat_map(|transaction| transaction.result.logs()) {
+            bloom.accrue_log(log);
+        }
+        bloom
+    }
+
+    /// Returns the gas used by the block.
+    pub fn gas_used(&self) -> u64 {
+        self.transactions.iter().map(|transaction| transaction.result.gas_used()).sum()
+    }
+
+    /// Returns the root of the receipts trie of the block.
+    pub fn receipts_root(&self) -> B256 {
+        rlp::receipts_root(&self.receipts())
+    }
+
+    /// Returns the execution outcome of the block, with the given state changes.
+    pub fn to_execution_outcome(&self, bundle: BundleState) -> ExecutionOutcome {
+        let receipts = self.receipts().into_iter().map(Some).collect();
+        ExecutionOutcome {
+            bundle,
+            receipts: Receipts { receipt_vec: vec![receipts] },
+            first_block: self.block_number,
+            ..Default::default()
+        }
+    }
+
+    /// Verifies the receipts against the execution outcome of the native execution of the block,
+    /// returning the mismatches.
+    ///
+    /// The pruned receipts of the outcome are not verified. The contracts created by the
+    /// transactions must be part of the state changes of the outcome.
+    pub fn verify_against(&self, outcome: &ExecutionOutcome) -> Vec<Mismatch> {
+        let mut mismatches = Vec::new();
+        let native = outcome.receipts_by_block(self.block_number);
+        if native.len() != self.transactions.len() {
+            mismatches.push(self.mismatch(
+                None,
+                MismatchField::TransactionCount,
+                native.len(),
+                self.transactions.len(),
+            ));
+        }
+
+        // The gas used by a native transaction is unknown after a pruned receipt.
+        let mut previous_gas_used = Some(0);
+        for (tx_index, (expected, actual)) in native.iter().zip(self.receipts()).enumerate() {
+            let Some(expected) = expected else {
+                previous_gas_used = None;
+                continue;
+            };
+            let expected_gas_used = previous_gas_used
+                .map(|previous| expected.cumulative_gas_used.saturating_sub(previous));
+            mismatches.extend(self.verify_receipt(tx_index, expected, &actual, expected_gas_used));
+            previous_gas_used = Some(expected.cumulative_gas_used);
+
+            let transaction = &self.transactions[tx_index];
+            if let Some(address) = transaction.created_contract().filter(|address| {
+                outcome.bundle.account(address).and_then(|account| account.info.as_ref()).is_none()
+            }) {
+                mismatches.push(self.mismatch(
+                    Some(tx_index),
+                    MismatchField::CreatedContract,
+                    None::<Address>,
+                    Some(address),
+                ));
+            }
+        }
+
+        // The logs bloom of the outcome misses the logs of the pruned receipts.
+        let pruned = native.iter().any(Option::is_none);
+        if let Some(expected) = outcome.block_logs_bloom(self.block_number).filter(|_| !pruned) {
+            let actual = self.logs_bloom();
+            if expected != actual {
+                mismatches.push(self.mismatch(None, MismatchField::LogsBloom, expected, actual));
+            }
+        }
+        mismatches
+    }
+
+    /// Verifies the output of the program and the receipts against the header of the block,
+    /// returning the mismatches.
+    pub fn verify_header(&self, output: &ProgramOutput, header: &HeaderInput) -> Vec<Mismatch> {
+        let mut mismatches = Vec::new();
+        let mut verify = |field, expected: &dyn Debug, actual: &dyn Debug, equal: bool| {
+            if !equal {
+                mismatches.push(self.mismatch(None, field, expected, actual));
+            }
+        };
+
+        let gas_used = header.gas_used.to::<u64>();
+        verify(
+            MismatchField::StateRoot,
+            &header.state_root,
+            &output.post_state_root,
+            header.state_root == output.post_state_root,
+        );
+        let receipts_root = self.receipts_root();
+        verify(
+            MismatchField::ReceiptsRoot,
+            &header.receipt_trie,
+            &receipts_root,
+            header.receipt_trie == receipts_root,
+        );
+        verify(
+            MismatchField::OutputGasUsed,
+            &gas_used,
+            &output.gas_used,
+            gas_used == output.gas_used,
+        );
+        verify(
+            MismatchField::BlockGasUsed,
+            &gas_used,
+            &self.gas_used(),
+            gas_used == self.gas_used(),
+        );
+        let logs_bloom = self.logs_bloom();
+        verify(MismatchField::LogsBloom, &header.bloom, &logs_bloom, header.bloom == logs_bloom);
+        mismatches
+    }
+
+    /// Verifies a receipt against the native one, given the gas used by the native transaction
+    /// when known.
+    fn verify_receipt(
+        &self,
+        tx_index: usize,
+        expected: &Receipt,
+        actual: &Receipt,
+        expected_gas_used: Option<u64>,
+    ) -> Vec<Mismatch> {
+        let mut mismatches = Vec::new();
+        let mut verify = |field, expected: &dyn Debug, actual: &dyn Debug, equal: bool| {
+            if !equal {
+                mismatches.push(self.mismatch(Some(tx_index), field, expected, actual));
+            }
+        };
+
+        let result = &self.transactions[tx_index].result;
+        verify(
+            MismatchField::TxType,
+            &expected.tx_type,
+            &actual.tx_type,
+            expected.tx_type == actual.tx_type,
+        );
+        verify(
+            MismatchField::Status,
+            &expected.success,
+            &ExecutionStatus::from_result(result),
+            expected.success == actual.success,
+        );
+        if let Some(expected_gas_used) = expected_gas_used {
+            verify(
+                MismatchField::GasUsed,
+                &expected_gas_used,
+                &result.gas_used(),
+                expected_gas_used == result.gas_used(),
+            );
+        }
+        verify(
+            MismatchField::CumulativeGasUsed,
+            &expected.cumulative_gas_used,
+            &actual.cumulative_gas_used,
+            expected.cumulative_gas_used == actual.cumulative_gas_used,
+        );
+        verify(
+            MismatchField::LogCount,
+            &expected.logs.len(),
+            &actual.logs.len(),
+            expected.logs.len() == actual.logs.len(),
+        );
+        for (index, (expected, actual)) in expected.logs.iter().zip(&actual.logs).enumerate() {
+            verify(MismatchField::Log(index), expected, actual, expected == actual);
+        }
+        mismatches
+    }
+
+    /// Returns a mismatch of a field of the block.
+    fn mismatch(
+        &self,
+        tx_index: Option<usize>,
+        field: MismatchField,
+        expected: impl Debug,
+        actual: impl Debug,
+    ) -> Mismatch {
+        Mismatch {
+            block_number: self.block_number,
+            tx_index,
+            field,
+            expected: format!("{expected:?}"),
+            actual: format!("{actual:?}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{Bytes, Log, LogData, U64};
+    use reth_primitives::revm_primitives::SuccessReason;
+
+    fn log(topic: u8) -> Log {
+        Log {
+            address: Address::with_last_byte(1),
+            data: LogData::new_unchecked(vec![B256::with_last_byte(topic)], Bytes::new()),
+        }
+    }
+
+    fn success(gas_used: u64, logs: Vec<Log>) -> ExecutionResult {
+        ExecutionResult::Success {
+            reason: SuccessReason::Stop,
+            gas_used,
+            gas_refunded: 0,
+            logs,
+            output: Output::Call(Bytes::new()),
+        }
+    }
+
+    fn cairo_outcome() -> CairoOutcome {
+        let mut cairo = CairoOutcome::new(10);
+        cairo.push(TxType::Eip1559, success(21_000, vec![log(1)]));
+        cairo.push(
+            TxType::Legacy,
+            ExecutionResult::Revert { gas_used: 30_000, output: Bytes::new() },
+        );
+        cairo
+    }
+
+    #[test]
+    fn test_receipts() {
+        let cairo = cairo_outcome();
+        let receipts = cairo.receipts();
+        assert_eq!(
+            receipts.iter().map(|receipt| receipt.cumulative_gas_used).collect::<Vec<_>>(),
+            vec![21_000, 51_000]
+        );
+        assert!(receipts[0].success && !receipts[1].success);
+        assert_eq!(cairo.gas_used(), 51_000);
+
+        let outcome = cairo.to_execution_outcome(BundleState::default());
+        assert_eq!(outcome.block_logs_bloom(10), Some(cairo.logs_bloom()));
+        assert!(cairo.verify_against(&outcome).is_empty());
+    }
+
+    #[test]
+    fn test_verify_against() {
+        let mut native = cairo_outcome().receipts();
+        native[0].logs = vec![log(2)];
+        native[1].cumulative_gas_used = 50_000;
+        let outcome = ExecutionOutcome {
+            receipts: Receipts { receipt_vec: vec![native.into_iter().map(Some).collect()] },
+            first_block: 10,
+            ..Default::default()
+        };
+
+        let mismatches = cairo_outcome().verify_against(&outcome);
+        assert_eq!(
+            mismatches
+                .iter()
+                .map(|mismatch| (mismatch.tx_index, mismatch.field))
+                .collect::<Vec<_>>(),
+            vec![
+                (Some(0), MismatchField::Log(0)),
+                (Some(1), MismatchField::GasUsed),
+                (Some(1), MismatchField::CumulativeGasUsed),
+                (None, MismatchField::LogsBloom),
+            ]
+        );
+        assert_eq!(
+            mismatches[1].to_string(),
+            "Block 10, transaction 1: gas used mismatch, expected 29000, got 30000"
+        );
+    }
+
+    #[test]
+    fn test_verify_header() {
+        let cairo = cairo_outcome();
+        let mut header = HeaderInput::from(&reth_primitives::Header {
+            number: 10,
+            gas_used: 51_000,
+            receipts_root: cairo.receipts_root(),
+            logs_bloom: cairo.logs_bloom(),
+            ..Default::default()
+        });
+        let output = ProgramOutput::from(&header);
+        assert!(cairo.verify_header(&output, &header).is_empty());
+
+        // The receipts root is computed from the receipts, and the gas used of the output and of
+        // the receipts are reported separately.
+        header.receipt_trie = B256::repeat_byte(1);
+        header.gas_used = U64::from(50_000);
+        let fields: Vec<_> = cairo
+            .verify_header(&ProgramOutput { gas_used: 50_000, ..output }, &header)
+            .into_iter()
+            .map(|mismatch| mismatch.field)
+            .collect();
+        assert_eq!(fields, vec![MismatchField::ReceiptsRoot, MismatchField::BlockGasUsed]);
+        let fields: Vec<_> = cairo
+            .verify_header(&output, &header)
+            .into_iter()
+            .map(|mismatch| mismatch.field)
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                MismatchField::ReceiptsRoot,
+                MismatchField::OutputGasUsed,
+                MismatchField::BlockGasUsed
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tx_type() {
+        let transaction = |rlp: &[u8]| TransactionInput {
+            rlp_len: rlp.len(),
+            rlp: Bytes::copy_from_slice(rlp),
+            signature_len: 0,
+            signature: Vec::new(),
+            sender: Address::ZERO,
+        };
+        assert_eq!(tx_type(&transaction(&[0x02, 0xc0])), TxType::Eip1559);
+        assert_eq!(tx_type(&transaction(&[0xc0])), TxType::Legacy);
+    }
+
+    #[test]
+    fn test_verify_against_pruned() {
+        let native = cairo_outcome().receipts();
+        let outcome = ExecutionOutcome {
+            receipts: Receipts { receipt_vec: vec![vec![None, Some(native[1].clone())]] },
+            first_block: 10,
+            ..Default::default()
+        };
+        assert!(cairo_outcome().verify_against(&outcome).is_empty());
+    }
+}
@@ -0,0 +1,369 @@
+//! Extraction and validation of the gas refund accounting of the Cairo execution.
+//!
+//! The refund counter of each transaction is read from the EVM returned by the Kakarot function
+//! executing it, alongside its gas limit and the gas left. The EIP-3529 refund cap is applied
+//! Rust-side to derive the gas used by each transaction, which is compared against the receipts of
+//! the block. A mismatch which a capped refund of the Cairo execution gas can explain is classified
+//! as a refund accounting divergence, the others as execution gas divergences.
+
+use crate::attribution::{felt_to_usize, invocations, AttributionError, FrameSpec};
+use cairo_vm::{types::program::Program, vm::trace::trace_entry::RelocatedTraceEntry, Felt252};
+use reth_primitives::Receipt;
+use reth_tracing::tracing::warn;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use thiserror::Error;
+
+/// The full name of the Cairo struct of the EVM returned by the execution of a transaction.
+pub const EVM_STRUCT: &str = "src.model.model.EVM";
+
+/// The maximum refund quotient since EIP-3529: at most a fifth of the gas used is refunded.
+pub const MAX_REFUND_QUOTIENT: u64 = 5;
+
+/// The maximum refund quotient before EIP-3529.
+pub const LEGACY_MAX_REFUND_QUOTIENT: u64 = 2;
+
+/// Represents errors that can occur when extracting the refund counters of an execution.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum RefundError {
+    /// Error variant indicating that the frame of the executing function cannot be built.
+    #[error(transparent)]
+    Attribution(#[from] AttributionError),
+
+    /// Error variant indicating that a member is missing from a Cairo struct.
+    #[error("Member '{member}' not found in '{scope}'")]
+    MissingMember {
+        /// The full name of the struct.
+        scope: String,
+        /// The name of the member.
+        member: String,
+    },
+
+    /// Error variant indicating a frame whose arguments or return values would start before the
+    /// memory.
+    #[error("Invalid frame at relocated address {0}")]
+    InvalidFrame(usize),
+
+    /// Error variant indicating that a value cannot be read from the memory.
+    #[error("Value '{name}' not found at address {address}")]
+    MissingValue {
+        /// The name of the value.
+        name: String,
+        /// The relocated address of the value.
+        address: usize,
+    },
+}
+
+/// The layout of the values holding the refund accounting of a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefundSpec {
+    /// The frame of the function executing a transaction.
+    pub frame: FrameSpec,
+    /// The offset of the gas limit in the explicit arguments.
+    pub gas_limit_offset: usize,
+    /// The offset of the EVM pointer in the explicit return values.
+    pub evm_offset: usize,
+    /// The offset of the gas left in the EVM struct.
+    pub gas_left_offset: usize,
+    /// The offset of the refund counter in the EVM struct.
+    pub gas_refund_offset: usize,
+}
+
+impl RefundSpec {
+    /// Builds the layout from the identifiers of the program, the EVM being the first return value
+    /// of the function.
+    pub fn from_program(program: &Program, function: &str) -> Result<Self, RefundError> {
+        let member = |scope: &str, member: &str| {
+            program
+                .get_identifier(scope)
+                .and_then(|identifier| identifier.members.as_ref()?.get(member))
+                .map(|member| member.offset)
+                .ok_or_else(|| RefundError::MissingMember {
+                    scope: scope.to_string(),
+                    member: member.to_string(),
+                })
+        };
+
+        Ok(Self {
+            frame: FrameSpec::from_program(program, function)?,
+            gas_limit_offset: member(&format!("{function}.Args"), "gas_limit")?,
+            evm_offset: 0,
+            gas_left_offset: member(EVM_STRUCT, "gas_left")?,
+            gas_refund_offset: member(EVM_STRUCT, "gas_refund")?,
+        })
+    }
+}
+
+/// The refund accounting of a transaction in the Cairo execution.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RefundCounters {
+    /// The index of the transaction in the block.
+    pub tx_index: u32,
+    /// The gas limit of the transaction.
+    pub gas_limit: u64,
+    /// The gas left at the end of the execution, before the refund.
+    pub gas_left: u64,
+    /// The refund counter at the end of the execution, before the cap.
+    pub gas_refund: u64,
+}
+
+impl RefundCounters {
+    /// Returns the gas used by the execution, before the refund.
+    pub const fn execution_gas(&self) -> u64 {
+        self.gas_limit.saturating_sub(self.gas_left)
+    }
+
+    /// Returns the refund applied to the transaction, capped to a fraction of its execution gas.
+    pub fn capped_refund(&self, quotient: u64) -> u64 {
+        self.gas_refund.min(self.execution_gas() / quotient)
+    }
+
+    /// Returns the gas used by the transaction, after the capped refund.
+    pub fn gas_used(&self, quotient: u64) -> u64 {
+        self.execution_gas() - self.capped_refund(quotient)
+    }
+}
+
+/// Reads the refund counters of the transactions executed in a relocated trace.
+///
+/// The transactions which never return, i.e. whose execution failed, are skipped.
+pub fn extract_refunds(
+    spec: &RefundSpec,
+    trace: &[RelocatedTraceEntry],
+    memory: &[Felt252],
+) -> Result<Vec<RefundCounters>, RefundError> {
+    let read = |name: &str, address: usize| {
+        memory
+            .get(address)
+            .and_then(felt_to_usize)
+            .map(|value| value as u64)
+            .ok_or_else(|| RefundError::MissingValue { name: name.to_string(), address })
+    };
+
+    let frame = &spec.frame;
+    let mut counters = Vec::new();
+    for (tx_index, (start, end)) in invocations(frame, trace).into_iter().enumerate() {
+        let Some(exit) = trace.get(end) else { continue };
+        let fp = trace[start].fp;
+        let args_start =
+            fp.checked_sub(2 + frame.args_size).ok_or(RefundError::InvalidFrame(fp))?;
+        let returns_start =
+            exit.ap.checked_sub(frame.return_size).ok_or(RefundError::InvalidFrame(exit.ap))?;
+
+        let evm = read("evm", returns_start + spec.evm_offset)? as usize;
+        counters.push(RefundCounters {
+            tx_index: tx_index as u32,
+            gas_limit: read("gas_limit", args_start + spec.gas_limit_offset)?,
+            gas_left: read("gas_left", evm + spec.gas_left_offset)?,
+            gas_refund: read("gas_refund", evm + spec.gas_refund_offset)?,
+        });
+    }
+    Ok(counters)
+}
+
+/// The class of a gas divergence between the Cairo execution and the receipts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GasDivergenceKind {
+    /// The execution gas matches the receipt up to a capped refund: the refund accounting
+    /// diverged.
+    RefundAccounting,
+    /// The execution gas itself diverged.
+    Execution,
+}
+
+impl GasDivergenceKind {
+    /// Returns the name of the class.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::RefundAccounting => "refund_accounting",
+            Self::Execution => "execution",
+        }
+    }
+}
+
+impl fmt::Display for GasDivergenceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A transaction whose gas used in the Cairo execution does not match its receipt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GasDivergence {
+    /// The index of the transaction in the block.
+    pub tx_index: u32,
+    /// The gas used according to the receipt.
+    pub expected: u64,
+    /// The gas used by the Cairo execution, after the capped refund.
+    pub actual: u64,
+    /// The class of the divergence.
+    pub kind: GasDivergenceKind,
+}
+
+/// Compares the gas used by the transactions of the Cairo execution against the receipts of the
+/// block, returning the diverging transactions.
+pub fn validate_gas_used(
+    counters: &[RefundCounters],
+    receipts: &[Receipt],
+    quotient: u64,
+) -> Vec<GasDivergence> {
+    let mut previous = 0;
+    let receipt_gas: Vec<_> = receipts
+        .iter()
+        .map(|receipt| {
+            let gas = receipt.cumulative_gas_used.saturating_sub(previous);
+            previous = receipt.cumulative_gas_used;
+            gas
+        })
+        .collect();
+
+    counters
+        .iter()
+        .filter_map(|counters| {
+            let expected = *receipt_gas.get(counters.tx_index as usize)?;
+            let actual = counters.gas_used(quotient);
+            if actual == expected {
+                return None;
+            }
+
+            // The receipt is consistent with the execution gas of the Cairo run and a capped
+            // refund: only the refund counter or its cap diverged.
+            let execution = counters.execution_gas();
+            let kind = if expected <= execution && execution - expected <= execution / quotient {
+                GasDivergenceKind::RefundAccounting
+            } else {
+                GasDivergenceKind::Execution
+            };
+            Some(GasDivergence { tx_index: counters.tx_index, expected, actual, kind })
+        })
+        .collect()
+}
+
+/// Reports the gas divergences of a block.
+pub fn report_divergences(block_number: u64, divergences: &[GasDivergence]) {
+    for divergence in divergences {
+        warn!(
+            target: "kkrt::refund",
+            number = block_number,
+            tx_index = divergence.tx_index,
+            expected = divergence.expected,
+            actual = divergence.actual,
+            kind = %divergence.kind,
+            "Gas used diverged from the receipt"
+        );
+        metrics::counter!("kakarot_gas_divergences", "kind" => divergence.kind.as_str())
+            .increment(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(pc: usize, ap: usize, fp: usize) -> RelocatedTraceEntry {
+        RelocatedTraceEntry { pc, ap, fp }
+    }
+
+    fn receipt(cumulative_gas_used: u64) -> Receipt {
+        Receipt { cumulative_gas_used, ..Default::default() }
+    }
+
+    #[test]
+    fn test_extract_refunds() {
+        // A function taking the gas limit as its single argument and returning the EVM, whose
+        // gas left and refund counter are its first two members.
+        let spec = RefundSpec {
+            frame: FrameSpec {
+                pc: 10,
+                builtin_ptrs: vec![],
+                implicit_args_size: 0,
+                args_size: 1,
+                return_size: 1,
+            },
+            gas_limit_offset: 0,
+            evm_offset: 0,
+            gas_left_offset: 0,
+            gas_refund_offset: 1,
+        };
+
+        // The invocation is entered with `fp = 100`, its argument being at 97, and returns with
+        // `ap = 110`, the EVM pointer being at 109. The second invocation never returns.
+        let trace = vec![
+            entry(1, 98, 98),
+            entry(10, 100, 100),
+            entry(11, 108, 100),
+            entry(2, 110, 98),
+            entry(10, 120, 120),
+        ];
+        let mut memory = vec![Felt252::ZERO; 130];
+        memory[97] = Felt252::from(100_000);
+        memory[109] = Felt252::from(120);
+        memory[120] = Felt252::from(40_000);
+        memory[121] = Felt252::from(24_000);
+
+        let counters = extract_refunds(&spec, &trace, &memory).unwrap();
+        assert_eq!(
+            counters,
+            vec![RefundCounters {
+                tx_index: 0,
+                gas_limit: 100_000,
+                gas_left: 40_000,
+                gas_refund: 24_000
+            }]
+        );
+
+        // The refund is capped to a fifth of the execution gas.
+        assert_eq!(counters[0].capped_refund(MAX_REFUND_QUOTIENT), 12_000);
+        assert_eq!(counters[0].gas_used(MAX_REFUND_QUOTIENT), 48_000);
+        assert_eq!(counters[0].gas_used(LEGACY_MAX_REFUND_QUOTIENT), 36_000);
+    }
+
+    #[test]
+    fn test_validate_gas_used() {
+        let counters = [
+            RefundCounters { tx_index: 0, gas_limit: 50_000, gas_left: 29_000, gas_refund: 0 },
+            RefundCounters {
+                tx_index: 1,
+                gas_limit: 100_000,
+                gas_left: 40_000,
+                gas_refund: 24_000,
+            },
+            RefundCounters { tx_index: 2, gas_limit: 50_000, gas_left: 0, gas_refund: 0 },
+        ];
+        // The second transaction was refunded 4800 natively, the third one used 21000 gas.
+        let receipts = [receipt(21_000), receipt(76_200), receipt(97_200)];
+
+        assert_eq!(
+            validate_gas_used(&counters, &receipts, MAX_REFUND_QUOTIENT),
+            vec![
+                GasDivergence {
+                    tx_index: 1,
+                    expected: 55_200,
+                    actual: 48_000,
+                    kind: GasDivergenceKind::RefundAccounting
+                },
+                GasDivergence {
+                    tx_index: 2,
+                    expected: 21_000,
+                    actual: 50_000,
+                    kind: GasDivergenceKind::Execution
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_refund_spec_missing_function() {
+        let program_content = include_bytes!("../testdata/keccak_add_uint256.json");
+        let program = Program::from_bytes(program_content, Some("main")).unwrap();
+
+        assert_eq!(
+            RefundSpec::from_program(&program, "__main__.main"),
+            Err(RefundError::MissingMember {
+                scope: "__main__.main.Args".to_string(),
+                member: "gas_limit".to_string()
+            })
+        );
+    }
+}
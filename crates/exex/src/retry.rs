@@ -0,0 +1,286 @@
+//! Retry policy of the failed proving jobs.
+//!
+//! A failed proving job is classified from its error, and the [`RetryPolicy`] decides whether it
+//! is queued again and when: the delay between the attempts grows exponentially up to a maximum,
+//! and a job whose prover ran out of memory is rerouted to a large worker. A job which exhausted
+//! its attempts, or whose failure cannot be fixed by a retry, is marked as failed.
+//!
+//! The [`RetryState`] of each job is persisted, so that the attempts survive restarts and are
+//! reported by the proof status RPC.
+
+use crate::{db::Database, deferred::JobState};
+use reth_tracing::tracing::warn;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The class of the failure of a proving job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureClass {
+    /// The prover ran out of memory.
+    OutOfMemory,
+    /// The prover timed out.
+    Timeout,
+    /// The job is invalid, e.g. its Cairo PIE was rejected, and fails on each attempt.
+    Invalid,
+    /// Any other failure, e.g. a network error, assumed transient.
+    Transient,
+}
+
+impl FailureClass {
+    /// Classifies a failure from its error message.
+    pub fn classify(error: &str) -> Self {
+        let error = error.to_lowercase();
+        let matches = |patterns: &[&str]| patterns.iter().any(|pattern| error.contains(pattern));
+        // `oom` is matched as a word, not to classify e.g. a "room" or "bloom" error.
+        let oom = error
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .any(|word| matches!(word, "oom" | "oomkilled"));
+        if oom || matches(&["out of memory", "cannot allocate", "memory allocation"]) {
+            Self::OutOfMemory
+        } else if matches(&["timeout", "timed out", "deadline"]) {
+            Self::Timeout
+        } else if matches(&["invalid", "rejected"]) {
+            Self::Invalid
+        } else {
+            Self::Transient
+        }
+    }
+
+    /// Returns the name of the class.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::OutOfMemory => "out_of_memory",
+            Self::Timeout => "timeout",
+            Self::Invalid => "invalid",
+            Self::Transient => "transient",
+        }
+    }
+}
+
+impl fmt::Display for FailureClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The size of the worker a proving job is routed to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkerSize {
+    /// A worker of the default size.
+    #[default]
+    Standard,
+    /// A worker with more memory, for the jobs which ran out of memory.
+    Large,
+}
+
+impl WorkerSize {
+    /// Returns the name of the size.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Standard => "standard",
+            Self::Large => "large",
+        }
+    }
+}
+
+/// The policy of the retries of the failed proving jobs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The delay before the first retry.
+    pub initial_backoff: Duration,
+    /// The maximum delay between two attempts.
+    pub max_backoff: Duration,
+    /// The maximum number of attempts of a job, including the first one.
+    pub max_attempts: u32,
+    /// Whether the jobs which ran out of memory are rerouted to a large worker.
+    pub reroute_out_of_memory: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(60),
+            max_backoff: Duration::from_secs(3600),
+            max_attempts: 5,
+            reroute_out_of_memory: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the delay before the retry following the given number of failed attempts.
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+
+    /// Returns the retry state of a job after a failure at `now`, from its previous state.
+    pub fn on_failure(
+        &self,
+        previous: Option<&RetryState>,
+        block_number: u64,
+        error: &str,
+        now: u64,
+    ) -> RetryState {
+        let attempts = previous.map_or(0, |state| state.attempts) + 1;
+        let class = FailureClass::classify(error);
+        let worker = match class {
+            FailureClass::OutOfMemory if self.reroute_out_of_memory => WorkerSize::Large,
+            _ => previous.map(|state| state.worker).unwrap_or_default(),
+        };
+        let exhausted = class == FailureClass::Invalid || attempts >= self.max_attempts;
+
+        RetryState {
+            block_number,
+            attempts,
+            class,
+            last_error: error.to_string(),
+            worker,
+            next_attempt_at: (!exhausted).then(|| now + self.backoff(attempts).as_secs()),
+        }
+    }
+}
+
+/// The retry state of a proving job.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryState {
+    /// The number of the block.
+    pub block_number: u64,
+    /// The number of failed attempts.
+    pub attempts: u32,
+    /// The class of the last failure.
+    pub class: FailureClass,
+    /// The error of the last failure.
+    pub last_error: String,
+    /// The size of the worker the next attempt is routed to.
+    pub worker: WorkerSize,
+    /// The UNIX timestamp, in seconds, from which the job can be attempted again, `None` once the
+    /// job failed for good.
+    pub next_attempt_at: Option<u64>,
+}
+
+impl RetryState {
+    /// Returns whether the job will not be attempted again.
+    pub const fn is_exhausted(&self) -> bool {
+        self.next_attempt_at.is_none()
+    }
+
+    /// Returns the state of the job after the failure: failed once exhausted, queued otherwise.
+    pub const fn job_state(&self) -> JobState {
+        if self.is_exhausted() {
+            JobState::Failed
+        } else {
+            JobState::Queued
+        }
+    }
+}
+
+/// Records the failure of the proving job of a block, queuing it again or marking it as failed
+/// according to the policy.
+pub fn record_failure(
+    db: &Database,
+    policy: &RetryPolicy,
+    block_number: u64,
+    error: &str,
+) -> eyre::Result<RetryState> {
+    let previous = db.proving_retry(block_number)?;
+    let state = policy.on_failure(previous.as_ref(), block_number, error, now());
+    db.insert_proving_retry(&state)?;
+    db.set_proving_job_state(block_number, state.job_state())?;
+    report_failure(&state);
+    Ok(state)
+}
+
+/// Logs and counts a recorded failure of a proving job.
+pub(crate) fn report_failure(state: &RetryState) {
+    if state.is_exhausted() {
+        warn!(
+            target: "kkrt::retry",
+            number = state.block_number,
+            attempts = state.attempts,
+            class = %state.class,
+            error = %state.last_error,
+            "Proving job failed"
+        );
+        metrics::counter!("kakarot_proving_jobs_failed").increment(1);
+    } else {
+        metrics::counter!("kakarot_proving_jobs_retried").increment(1);
+    }
+}
+
+/// Returns the current UNIX timestamp, in seconds.
+pub(crate) fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deferred::ProvingJob;
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_classify_failures() {
+        assert_eq!(FailureClass::classify("Worker OOM killed"), FailureClass::OutOfMemory);
+        assert_eq!(FailureClass::classify("Reason: OOMKilled"), FailureClass::OutOfMemory);
+        assert_eq!(FailureClass::classify("no room left in the queue"), FailureClass::Transient);
+        assert_eq!(FailureClass::classify("request timed out"), FailureClass::Timeout);
+        assert_eq!(FailureClass::classify("SHARP job INVALID"), FailureClass::Invalid);
+        assert_eq!(FailureClass::classify("connection reset"), FailureClass::Transient);
+    }
+
+    #[test]
+    fn test_exponential_backoff() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(60),
+            ..Default::default()
+        };
+
+        assert_eq!(policy.backoff(1), Duration::from_secs(10));
+        assert_eq!(policy.backoff(2), Duration::from_secs(20));
+        assert_eq!(policy.backoff(3), Duration::from_secs(40));
+        assert_eq!(policy.backoff(4), Duration::from_secs(60));
+        assert_eq!(policy.backoff(100), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_on_failure() {
+        let policy = RetryPolicy { max_attempts: 2, ..Default::default() };
+
+        let first = policy.on_failure(None, 1, "out of memory", 1000);
+        assert_eq!(first.attempts, 1);
+        assert_eq!(first.worker, WorkerSize::Large);
+        assert_eq!(first.next_attempt_at, Some(1060));
+
+        // The job stays on the large worker, and fails once its attempts are exhausted.
+        let second = policy.on_failure(Some(&first), 1, "connection reset", 2000);
+        assert_eq!(second.worker, WorkerSize::Large);
+        assert!(second.is_exhausted());
+
+        assert!(policy.on_failure(None, 1, "invalid PIE", 1000).is_exhausted());
+    }
+
+    #[test]
+    fn test_record_failure() {
+        let db = Database::new(Connection::open_in_memory().unwrap()).unwrap();
+        db.enqueue_proving_job(&ProvingJob::new(1, "program.json".into())).unwrap();
+        db.set_proving_job_state(1, JobState::Exported).unwrap();
+        let policy = RetryPolicy { max_attempts: 2, ..Default::default() };
+
+        record_failure(&db, &policy, 1, "timeout").unwrap();
+        assert_eq!(db.proving_job_state(1).unwrap(), Some(JobState::Queued));
+        // The job is not due before its backoff elapsed.
+        assert!(db.due_proving_jobs(now(), 10).unwrap().is_empty());
+        assert_eq!(db.due_proving_jobs(now() + 3600, 10).unwrap().len(), 1);
+
+        record_failure(&db, &policy, 1, "timeout").unwrap();
+        assert_eq!(db.proving_job_state(1).unwrap(), Some(JobState::Failed));
+        assert_eq!(db.proving_retry(1).unwrap().map(|state| state.attempts), Some(2));
+    }
+}
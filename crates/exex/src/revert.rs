@@ -0,0 +1,261 @@
+//! Decoding of the revert reasons of the transactions of the Cairo execution.
+//!
+//! The output of a reverted transaction, the return data of its `model.EVM` when `reverted` is
+//! `Errors.REVERT`, is decoded as the Solidity `Error(string)` and `Panic(uint256)` errors, or kept
+//! as a custom error with its selector. The reasons are reported as geth and reth do, with the
+//! `execution reverted` message and the JSON-RPC error code `3`, the raw output being kept as the
+//! data of the error, so that client libraries decode them unchanged.
+//!
+//! The `callTracer` decodes the reason of each reverted frame from its steps, and the divergence
+//! reports compare the reverts of the Cairo execution with the native ones, see
+//! [`RevertDivergence`](crate::divergence::RevertDivergence).
+
+use crate::receipts::CairoTransaction;
+use alloy_primitives::{Bytes, FixedBytes, U256};
+use reth_primitives::revm_primitives::ExecutionResult;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The selector of the Solidity `Error(string)` error.
+pub const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// The selector of the Solidity `Panic(uint256)` error.
+pub const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// The JSON-RPC error code of a reverted execution.
+pub const REVERT_ERROR_CODE: i32 = 3;
+
+/// The message of a reverted execution, followed by its reason when decoded.
+pub const REVERT_MESSAGE: &str = "execution reverted";
+
+/// The decoded reason of a revert.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RevertReason {
+    /// A revert without output.
+    Empty,
+    /// A Solidity `Error(string)`, raised by `require` and `revert` with a message.
+    Error {
+        /// The message of the error.
+        message: String,
+    },
+    /// A Solidity `Panic(uint256)`, raised by failed assertions and arithmetic errors.
+    Panic {
+        /// The panic code.
+        code: U256,
+    },
+    /// A custom error, or an output which is not ABI encoded.
+    Custom {
+        /// The selector of the error, `None` for outputs shorter than a selector.
+        selector: Option<FixedBytes<4>>,
+    },
+}
+
+impl RevertReason {
+    /// Decodes the reason of a revert from its output.
+    pub fn decode(output: &[u8]) -> Self {
+        if output.is_empty() {
+            return Self::Empty;
+        }
+        let Some((selector, data)) = output.split_first_chunk::<4>() else {
+            return Self::Custom { selector: None };
+        };
+
+        match *selector {
+            ERROR_SELECTOR => match decode_string(data) {
+                Some(message) => Self::Error { message },
+                None => Self::Custom { selector: Some(selector.into()) },
+            },
+            PANIC_SELECTOR if data.len() == 32 => Self::Panic { code: U256::from_be_slice(data) },
+            _ => Self::Custom { selector: Some(selector.into()) },
+        }
+    }
+
+    /// Returns the human readable reason, `None` for empty and custom reverts.
+    pub fn reason(&self) -> Option<String> {
+        match self {
+            Self::Error { message } => Some(message.clone()),
+            Self::Panic { code } => Some(format!("panic: {} (0x{code:02x})", panic_reason(*code))),
+            Self::Empty | Self::Custom { .. } => None,
+        }
+    }
+}
+
+/// A reverted execution, with its decoded reason and its raw output.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Revert {
+    /// The decoded reason.
+    pub reason: RevertReason,
+    /// The raw output of the execution.
+    pub data: Bytes,
+}
+
+impl Revert {
+    /// Decodes a revert from the output of the execution.
+    pub fn new(data: Bytes) -> Self {
+        Self { reason: RevertReason::decode(&data), data }
+    }
+
+    /// Returns the revert of a transaction of the Cairo execution, serialized from its returned
+    /// `model.EVM`, `None` if the transaction succeeded or halted.
+    pub fn from_cairo(transaction: &CairoTransaction) -> Option<Self> {
+        Self::from_result(&transaction.result)
+    }
+
+    /// Returns the revert of a native execution, `None` if the execution succeeded or halted.
+    pub fn from_result(result: &ExecutionResult) -> Option<Self> {
+        match result {
+            ExecutionResult::Revert { output, .. } => Some(Self::new(output.clone())),
+            _ => None,
+        }
+    }
+
+    /// Returns the JSON-RPC error of the revert.
+    pub fn to_rpc_error(&self) -> RevertRpcError {
+        RevertRpcError {
+            code: REVERT_ERROR_CODE,
+            message: self.to_string(),
+            data: (!self.data.is_empty()).then(|| self.data.clone()),
+        }
+    }
+}
+
+impl fmt::Display for Revert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason.reason() {
+            Some(reason) => write!(f, "{REVERT_MESSAGE}: {reason}"),
+            None => f.write_str(REVERT_MESSAGE),
+        }
+    }
+}
+
+/// The JSON-RPC error object of a reverted execution, as returned by geth and reth.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RevertRpcError {
+    /// The error code, always [`REVERT_ERROR_CODE`].
+    pub code: i32,
+    /// The error message, with the decoded reason.
+    pub message: String,
+    /// The raw output of the execution.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Bytes>,
+}
+
+/// Returns the description of a Solidity panic code.
+fn panic_reason(code: U256) -> &'static str {
+    match code.saturating_to::<u64>() {
+        0x00 => "generic/unspecified error",
+        0x01 => "assertion failed",
+        0x11 => "arithmetic underflow or overflow",
+        0x12 => "division or modulo by zero",
+        0x21 => "failed to convert value into enum type",
+        0x22 => "storage byte array incorrectly encoded",
+        0x31 => "called `.pop()` on an empty array",
+        0x32 => "array out-of-bounds access",
+        0x41 => "out of memory",
+        0x51 => "called an invalid internal function",
+        _ => "unknown panic code",
+    }
+}
+
+/// Decodes the ABI encoding of a single `string` argument.
+fn decode_string(data: &[u8]) -> Option<String> {
+    let word = |index: usize| {
+        let word = data.get(index..index.checked_add(32)?)?;
+        usize::try_from(U256::from_be_slice(word)).ok()
+    };
+
+    let offset = word(0)?;
+    let length = word(offset)?;
+    let start = offset.checked_add(32)?;
+    let bytes = data.get(start..start.checked_add(length)?)?;
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::receipts::CairoOutcome;
+    use alloy_primitives::hex;
+    use reth_primitives::{revm_primitives::HaltReason, TxType};
+
+    #[test]
+    fn test_decode_error_string() {
+        // `revert("Not enough Ether provided.")`
+        let data = Bytes::from(hex!(
+            "08c379a0"
+            "0000000000000000000000000000000000000000000000000000000000000020"
+            "000000000000000000000000000000000000000000000000000000000000001a"
+            "4e6f7420656e6f7567682045746865722070726f76696465642e000000000000"
+        ));
+        let revert = Revert::new(data.clone());
+
+        assert_eq!(
+            revert.reason,
+            RevertReason::Error { message: "Not enough Ether provided.".to_string() }
+        );
+        assert_eq!(
+            revert.to_rpc_error(),
+            RevertRpcError {
+                code: 3,
+                message: "execution reverted: Not enough Ether provided.".to_string(),
+                data: Some(data),
+            }
+        );
+    }
+
+    #[test]
+    fn test_decode_panic() {
+        let mut data = PANIC_SELECTOR.to_vec();
+        data.extend_from_slice(&U256::from(0x11).to_be_bytes::<32>());
+        let revert = Revert::new(data.into());
+
+        assert_eq!(revert.reason, RevertReason::Panic { code: U256::from(0x11) });
+        assert_eq!(
+            revert.to_string(),
+            "execution reverted: panic: arithmetic underflow or overflow (0x11)"
+        );
+    }
+
+    #[test]
+    fn test_decode_custom_and_empty() {
+        // `error InsufficientBalance(uint256)` with its argument.
+        let mut data = hex!("cf479181").to_vec();
+        data.extend_from_slice(&[0; 32]);
+        let revert = Revert::new(data.into());
+        assert_eq!(revert.reason, RevertReason::Custom { selector: Some(hex!("cf479181").into()) });
+        assert_eq!(revert.to_string(), REVERT_MESSAGE);
+
+        // A truncated `Error(string)` is kept as a custom error.
+        assert_eq!(
+            RevertReason::decode(&ERROR_SELECTOR),
+            RevertReason::Custom { selector: Some(ERROR_SELECTOR.into()) }
+        );
+        assert_eq!(RevertReason::decode(&[0xfe]), RevertReason::Custom { selector: None });
+
+        let empty = Revert::new(Bytes::new());
+        assert_eq!(empty.reason, RevertReason::Empty);
+        assert_eq!(empty.to_rpc_error().data, None);
+    }
+
+    #[test]
+    fn test_revert_from_cairo() {
+        let mut outcome = CairoOutcome::new(1);
+        let mut data = PANIC_SELECTOR.to_vec();
+        data.extend_from_slice(&U256::from(0x01).to_be_bytes::<32>());
+        outcome.push(
+            TxType::Eip1559,
+            ExecutionResult::Revert { gas_used: 21_000, output: data.into() },
+        );
+        outcome.push(
+            TxType::Legacy,
+            ExecutionResult::Halt { reason: HaltReason::OutOfFunds, gas_used: 21_000 },
+        );
+
+        assert_eq!(
+            Revert::from_cairo(&outcome.transactions[0]).map(|revert| revert.reason),
+            Some(RevertReason::Panic { code: U256::from(0x01) })
+        );
+        assert_eq!(Revert::from_cairo(&outcome.transactions[1]), None);
+    }
+}
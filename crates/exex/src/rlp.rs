@@ -0,0 +1,392 @@
+use crate::model::{ConversionError, KethBlockHeader, KethTransactionEncoded};
+use alloy_consensus::Header;
+use alloy_primitives::{Bytes, B256};
+use alloy_rlp::{Decodable, Encodable, Header as RlpHeader, EMPTY_LIST_CODE, EMPTY_STRING_CODE};
+use alloy_trie::root::ordered_trie_root_with_encoder;
+use reth_primitives::{logs_bloom, Receipt, Signature, TransactionSigned, TxType};
+use std::fmt;
+use thiserror::Error;
+
+/// The number of fields of a legacy transaction, without the EIP-155 replay protection fields.
+const LEGACY_FIELDS: usize = 6;
+
+/// The number of fields of an EIP-155 legacy transaction signing payload (`chain_id, 0, 0` being
+/// appended to the legacy fields).
+const EIP155_LEGACY_FIELDS: usize = 9;
+
+/// The commitments of a block header checked against the reconstructed payloads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Commitment {
+    /// The hash of the block header.
+    BlockHash,
+    /// The root of the transactions trie.
+    TransactionsRoot,
+    /// The root of the receipts trie.
+    ReceiptsRoot,
+}
+
+impl fmt::Display for Commitment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BlockHash => write!(f, "block hash"),
+            Self::TransactionsRoot => write!(f, "transactions root"),
+            Self::ReceiptsRoot => write!(f, "receipts root"),
+        }
+    }
+}
+
+/// Represents errors that can occur when rebuilding the RLP payloads of a block.
+#[derive(Debug, Error)]
+pub enum RlpError {
+    /// Error variant indicating a failure to convert a keth model back into its Ethereum type.
+    #[error(transparent)]
+    Conversion(#[from] ConversionError),
+
+    /// Error variant indicating an invalid RLP encoding.
+    #[error(transparent)]
+    Rlp(#[from] alloy_rlp::Error),
+
+    /// Error variant indicating that the unsigned transaction is not an RLP list.
+    #[error("Expected the transaction fields to be encoded as an RLP list")]
+    ExpectedList,
+
+    /// Error variant indicating that a legacy transaction has an unexpected number of fields.
+    #[error("Unexpected number of fields in legacy transaction: {0}")]
+    LegacyFieldCount(usize),
+
+    /// Error variant indicating an EIP-155 signing payload whose chain id is not followed by two
+    /// empty fields.
+    #[error("Expected the EIP-155 chain id to be followed by two empty fields")]
+    InvalidEip155Fields,
+
+    /// Error variant indicating a chain id whose EIP-155 `v` does not fit in a `u64`.
+    #[error("EIP-155 v overflows for chain id {0}")]
+    ChainIdOverflow(u64),
+
+    /// Error variant indicating that a rebuilt payload does not match the header commitment.
+    #[error("{commitment} mismatch: expected {expected}, computed {actual}")]
+    CommitmentMismatch {
+        /// The mismatching commitment.
+        commitment: Commitment,
+        /// The commitment of the canonical header.
+        expected: B256,
+        /// The commitment computed from the rebuilt payloads.
+        actual: B256,
+    },
+}
+
+/// A block rebuilt from the keth models read back from the Cairo memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReconstructedBlock {
+    /// The block header.
+    pub header: Header,
+    /// The EIP-2718 encoding of each signed transaction of the block.
+    pub transactions: Vec<Bytes>,
+}
+
+impl ReconstructedBlock {
+    /// Rebuilds a block from its keth header and encoded transactions.
+    pub fn from_keth(
+        header: &KethBlockHeader,
+        transactions: &[KethTransactionEncoded],
+    ) -> Result<Self, RlpError> {
+        Ok(Self {
+            header: header.try_into()?,
+            transactions: transactions
+                .iter()
+                .map(|transaction| reconstruct_transaction(transaction).map(Into::into))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Returns the hash of the rebuilt header.
+    pub fn hash(&self) -> B256 {
+        self.header.hash_slow()
+    }
+
+    /// Decodes the rebuilt transactions.
+    pub fn decode_transactions(&self) -> Result<Vec<TransactionSigned>, RlpError> {
+        self.transactions.iter().map(|transaction| decode_transaction(transaction)).collect()
+    }
+
+    /// Verifies that the rebuilt payloads re-hash to the canonical header commitments:
+    /// - the hash of the rebuilt header matches the canonical block hash,
+    /// - the root of the rebuilt transactions matches the header transactions root,
+    /// - the root of the given receipts matches the header receipts root.
+    pub fn verify(&self, block_hash: B256, receipts: &[Receipt]) -> Result<(), RlpError> {
+        check(Commitment::BlockHash, block_hash, self.hash())?;
+        check(
+            Commitment::TransactionsRoot,
+            self.header.transactions_root,
+            transactions_root(&self.transactions),
+        )?;
+        check(Commitment::ReceiptsRoot, self.header.receipts_root, receipts_root(receipts))
+    }
+}
+
+/// Encodes a block header.
+pub fn encode_header(header: &Header) -> Vec<u8> {
+    alloy_rlp::encode(header)
+}
+
+/// Decodes a block header.
+pub fn decode_header(mut bytes: &[u8]) -> Result<Header, RlpError> {
+    Ok(Header::decode(&mut bytes)?)
+}
+
+/// Rebuilds the EIP-2718 encoding of a signed transaction from its keth model.
+///
+/// The keth model holds the unsigned transaction (the signing payload) and the signature, the
+/// signature fields are appended to the transaction fields to get the signed encoding.
+pub fn reconstruct_transaction(transaction: &KethTransactionEncoded) -> Result<Vec<u8>, RlpError> {
+    let unsigned = Bytes::try_from(transaction.rlp())?;
+    let signature = Signature::try_from(transaction.signature())?;
+    encode_signed_transaction(&unsigned, &signature)
+}
+
+/// Builds the EIP-2718 encoding of a signed transaction from its signing payload and signature.
+///
+/// - Typed transactions are encoded as `type || rlp([fields..., y_parity, r, s])`.
+/// - Legacy transactions are encoded as `rlp([fields..., v, r, s])`, where the EIP-155 fields
+///   `chain_id, 0, 0` of the signing payload are replaced by the signature and `v` is derived from
+///   the chain id.
+pub fn encode_signed_transaction(
+    unsigned: &[u8],
+    signature: &Signature,
+) -> Result<Vec<u8>, RlpError> {
+    // Typed transactions start with their type, legacy ones directly with the list header.
+    let (tx_type, mut buf) = match unsigned.first() {
+        Some(&tx_type) if tx_type < EMPTY_LIST_CODE => (Some(tx_type), &unsigned[1..]),
+        _ => (None, unsigned),
+    };
+
+    let header = RlpHeader::decode(&mut buf)?;
+    if !header.list {
+        return Err(RlpError::ExpectedList);
+    }
+    if buf.len() < header.payload_length {
+        return Err(alloy_rlp::Error::InputTooShort.into());
+    }
+    let items = split_list_items(&buf[..header.payload_length])?;
+
+    let y_parity = signature.v().y_parity();
+    let mut payload = Vec::with_capacity(header.payload_length);
+    match tx_type {
+        Some(_) => {
+            items.iter().for_each(|item| payload.extend_from_slice(item));
+            y_parity.encode(&mut payload);
+        }
+        None => {
+            let (fields, chain_id) = match items.len() {
+                LEGACY_FIELDS => (&items[..], None),
+                EIP155_LEGACY_FIELDS => {
+                    if items[LEGACY_FIELDS + 1..].iter().any(|item| *item != [EMPTY_STRING_CODE]) {
+                        return Err(RlpError::InvalidEip155Fields);
+                    }
+                    (&items[..LEGACY_FIELDS], Some(u64::decode(&mut &*items[LEGACY_FIELDS])?))
+                }
+                count => return Err(RlpError::LegacyFieldCount(count)),
+            };
+            fields.iter().for_each(|item| payload.extend_from_slice(item));
+
+            // v = 27 + y_parity for pre-EIP-155 transactions, 35 + 2 * chain_id + y_parity
+            // otherwise.
+            let v = match chain_id {
+                Some(chain_id) => chain_id
+                    .checked_mul(2)
+                    .and_then(|v| v.checked_add(35 + u64::from(y_parity)))
+                    .ok_or(RlpError::ChainIdOverflow(chain_id))?,
+                None => 27 + u64::from(y_parity),
+            };
+            v.encode(&mut payload);
+        }
+    }
+    signature.r().encode(&mut payload);
+    signature.s().encode(&mut payload);
+
+    let mut out = Vec::new();
+    out.extend(tx_type);
+    RlpHeader { list: true, payload_length: payload.len() }.encode(&mut out);
+    out.extend_from_slice(&payload);
+    Ok(out)
+}
+
+/// Decodes the EIP-2718 encoding of a signed transaction.
+pub fn decode_transaction(mut bytes: &[u8]) -> Result<TransactionSigned, RlpError> {
+    Ok(TransactionSigned::decode_enveloped(&mut bytes)?)
+}
+
+/// Encodes a receipt as stored in the receipts trie.
+///
+/// Receipts of typed transactions are encoded as `type || rlp([status, cumulative_gas, bloom,
+/// logs])`, receipts of legacy transactions as `rlp([status, cumulative_gas, bloom, logs])`.
+pub fn encode_receipt(receipt: &Receipt, out: &mut Vec<u8>) {
+    let bloom = logs_bloom(receipt.logs.iter());
+    let payload_length = receipt.success.length() +
+        receipt.cumulative_gas_used.length() +
+        bloom.length() +
+        receipt.logs.length();
+
+    if receipt.tx_type != TxType::Legacy {
+        out.push(u8::from(receipt.tx_type));
+    }
+    RlpHeader { list: true, payload_length }.encode(out);
+    receipt.success.encode(out);
+    receipt.cumulative_gas_used.encode(out);
+    bloom.encode(out);
+    receipt.logs.encode(out);
+}
+
+/// Computes the transactions root of a block from the EIP-2718 encoding of its transactions.
+pub fn transactions_root<T: AsRef<[u8]>>(transactions: &[T]) -> B256 {
+    ordered_trie_root_with_encoder(transactions, |transaction, buf| {
+        buf.extend_from_slice(transaction.as_ref())
+    })
+}
+
+/// Computes the receipts root of a block.
+pub fn receipts_root(receipts: &[Receipt]) -> B256 {
+    ordered_trie_root_with_encoder(receipts, encode_receipt)
+}
+
+/// Splits the payload of an RLP list into the raw encoding of each of its items.
+fn split_list_items(mut payload: &[u8]) -> Result<Vec<&[u8]>, RlpError> {
+    let mut items = Vec::new();
+    while !payload.is_empty() {
+        let item = payload;
+        let header = RlpHeader::decode(&mut payload)?;
+        let item_length = item.len() - payload.len() + header.payload_length;
+        if item.len() < item_length {
+            return Err(alloy_rlp::Error::InputTooShort.into());
+        }
+        items.push(&item[..item_length]);
+        payload = &item[item_length..];
+    }
+    Ok(items)
+}
+
+/// Checks that a computed commitment matches the expected one.
+fn check(commitment: Commitment, expected: B256, actual: B256) -> Result<(), RlpError> {
+    if expected != actual {
+        return Err(RlpError::CommitmentMismatch { commitment, expected, actual });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::U256;
+    use arbitrary::{Arbitrary, Unstructured};
+    use proptest::prelude::*;
+    use reth_primitives::proofs::{calculate_receipt_root_no_memo, calculate_transaction_root};
+
+    proptest! {
+        #[test]
+        fn test_reconstruct_transaction_matches_hash(raw_bytes in any::<[u8; 1000]>()) {
+            let mut unstructured = Unstructured::new(&raw_bytes);
+
+            // Generate an arbitrary signed transaction
+            let tx = TransactionSigned::arbitrary(&mut unstructured)
+                .expect("Failed to generate arbitrary transaction");
+
+            // Rebuild the signed encoding from the keth model
+            let keth_tx = KethTransactionEncoded::try_from(tx.clone()).unwrap();
+            let encoded = reconstruct_transaction(&keth_tx).unwrap();
+
+            // The rebuilt encoding hashes to the transaction hash and decodes to the transaction
+            prop_assert_eq!(alloy_primitives::keccak256(&encoded), tx.hash());
+            prop_assert_eq!(decode_transaction(&encoded).unwrap(), tx.clone());
+
+            // The transactions root matches the one computed by reth
+            prop_assert_eq!(transactions_root(&[encoded]), calculate_transaction_root(&[tx]));
+        }
+
+        #[test]
+        fn test_receipts_root(raw_bytes in any::<[u8; 1000]>()) {
+            let mut unstructured = Unstructured::new(&raw_bytes);
+
+            // Generate arbitrary receipts
+            let receipts = Vec::<Receipt>::arbitrary(&mut unstructured)
+                .expect("Failed to generate arbitrary receipts");
+
+            prop_assert_eq!(
+                receipts_root(&receipts),
+                calculate_receipt_root_no_memo(&receipts.iter().collect::<Vec<_>>())
+            );
+        }
+
+        #[test]
+        fn test_header_rlp_roundtrip(raw_bytes in any::<[u8; 1000]>()) {
+            let mut unstructured = Unstructured::new(&raw_bytes);
+
+            // Generate an arbitrary header and convert it into the keth model
+            let header = Header::arbitrary(&mut unstructured)
+                .expect("Failed to generate arbitrary Header");
+            let keth_header = KethBlockHeader::from(header.clone());
+
+            let block = ReconstructedBlock::from_keth(&keth_header, &[]).unwrap();
+            prop_assert_eq!(decode_header(&encode_header(&block.header)).unwrap(), header.clone());
+            prop_assert_eq!(block.hash(), header.hash_slow());
+        }
+    }
+
+    #[test]
+    fn test_verify_detects_mismatching_commitments() {
+        let header = Header {
+            transactions_root: alloy_trie::EMPTY_ROOT_HASH,
+            receipts_root: alloy_trie::EMPTY_ROOT_HASH,
+            ..Default::default()
+        };
+        let block = ReconstructedBlock { header: header.clone(), transactions: vec![] };
+
+        // An empty block verifies against its own hash.
+        block.verify(header.hash_slow(), &[]).unwrap();
+
+        // A wrong block hash is detected.
+        assert!(matches!(
+            block.verify(B256::ZERO, &[]),
+            Err(RlpError::CommitmentMismatch { commitment: Commitment::BlockHash, .. })
+        ));
+
+        // Receipts not matching the header are detected.
+        assert!(matches!(
+            block.verify(header.hash_slow(), &[Receipt::default()]),
+            Err(RlpError::CommitmentMismatch { commitment: Commitment::ReceiptsRoot, .. })
+        ));
+    }
+
+    #[test]
+    fn test_encode_signed_transaction_rejects_strings() {
+        let signature = Signature::from_rs_and_parity(U256::from(1), U256::from(1), 27u64).unwrap();
+        assert!(matches!(
+            encode_signed_transaction(&[0x83, 1, 2, 3], &signature),
+            Err(RlpError::ExpectedList)
+        ));
+    }
+
+    #[test]
+    fn test_encode_signed_transaction_validates_eip155_fields() {
+        let signature = Signature::from_rs_and_parity(U256::from(1), U256::from(1), 27u64).unwrap();
+        let legacy = |chain_id: u64, trailing: [u64; 2]| {
+            let mut payload = Vec::new();
+            [0u64; LEGACY_FIELDS].iter().for_each(|field| field.encode(&mut payload));
+            chain_id.encode(&mut payload);
+            trailing.iter().for_each(|field| field.encode(&mut payload));
+            let mut out = Vec::new();
+            RlpHeader { list: true, payload_length: payload.len() }.encode(&mut out);
+            out.extend_from_slice(&payload);
+            out
+        };
+
+        encode_signed_transaction(&legacy(1, [0, 0]), &signature).unwrap();
+        assert!(matches!(
+            encode_signed_transaction(&legacy(1, [0, 1]), &signature),
+            Err(RlpError::InvalidEip155Fields)
+        ));
+        assert!(matches!(
+            encode_signed_transaction(&legacy(u64::MAX / 2, [0, 0]), &signature),
+            Err(RlpError::ChainIdOverflow(chain_id)) if chain_id == u64::MAX / 2
+        ));
+    }
+}
@@ -0,0 +1,493 @@
+//! A Cairo runner of the Kakarot program whose VM state can be snapshotted and restored.
+//!
+//! A [`VmSnapshot`] holds the written memory, the registers and the dictionaries of the hints of a
+//! runner. Restoring a snapshot replaces the runner by a runner of the same parsed program in the
+//! snapshotted state, so that several speculative executions, e.g. `eth_call` with state
+//! overrides, run on top of a shared warm base state without parsing the program nor running the
+//! steps of the base state again: one after the other, a runner run to its end being restored
+//! again, or side by side on the runners [forked](KakarotRunner::fork) from the base state.
+//!
+//! The range-check bounds and the trace of the steps before the snapshot are not restored, and
+//! the execution scopes of the hints other than the dictionary manager neither: snapshots are
+//! taken outside of any hint scope.
+//!
+//! Additional builtin runners, e.g. an experimental variant of a builtin of the layout, are
+//! registered with [`KakarotRunner::with_builtins`] rather than by patching the construction of
+//! the runner. They are validated against the builtins of the program and of the layout: a
+//! registered builtin replaces the builtin of the layout with the same name once the runner is
+//! initialized, on the segment allocated to it, and in proof mode, the memory layout of the prover
+//! being fixed by the layout, it must keep the ratio of the layout.
+
+use crate::{
+    limits::{initialize_runner, run_until_end, ExecutionLimits, LimitedRun},
+    serde::dump::MemoryDump,
+    tuning::DICT_MANAGER_SCOPE,
+};
+use cairo_vm::{
+    cairo_run::CairoRunConfig,
+    hint_processor::{
+        builtin_hint_processor::dict_manager::{DictManager, DictTracker, Dictionary},
+        hint_processor_definition::HintProcessor,
+    },
+    types::{
+        builtin_name::BuiltinName,
+        exec_scope::ExecutionScopes,
+        layout_name::LayoutName,
+        program::Program,
+        relocatable::{MaybeRelocatable, Relocatable},
+    },
+    vm::{
+        errors::{cairo_run_errors::CairoRunError, vm_errors::VirtualMachineError},
+        runners::{builtin_runner::BuiltinRunner, cairo_runner::CairoRunner},
+        vm_core::VirtualMachine,
+        vm_memory::memory_segments::MemorySegmentManager,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt,
+    rc::Rc,
+};
+use thiserror::Error;
+
+/// Represents errors that can occur when registering builtin runners.
+#[derive(Debug, Error)]
+pub enum BuiltinError {
+    /// Error variant indicating a failure of the initialization of the runner.
+    #[error(transparent)]
+    Run(#[from] CairoRunError),
+
+    /// Error variant indicating a builtin registered twice.
+    #[error("Builtin {} registered twice", .0.to_str())]
+    Duplicate(BuiltinName),
+
+    /// Error variant indicating a builtin not used by the program.
+    #[error("Builtin {} not used by the program", .0.to_str())]
+    NotInProgram(BuiltinName),
+
+    /// Error variant indicating a builtin not allocated by the layout.
+    #[error("Builtin {} not allocated by the layout {}", .builtin.to_str(), .layout.to_str())]
+    NotInLayout {
+        /// The registered builtin.
+        builtin: BuiltinName,
+        /// The layout of the run.
+        layout: LayoutName,
+    },
+
+    /// Error variant indicating a proof-mode builtin with another ratio than the layout.
+    #[error("Builtin {} has ratio {actual:?}, the layout expects {expected:?}", .builtin.to_str())]
+    RatioMismatch {
+        /// The registered builtin.
+        builtin: BuiltinName,
+        /// The ratio of the builtin in the layout.
+        expected: Option<u32>,
+        /// The ratio of the registered builtin.
+        actual: Option<u32>,
+    },
+}
+
+/// Replaces the builtin runners of an initialized runner by the registered runners of the same
+/// name, in place to preserve the order of the layout.
+///
+/// A registered runner takes over the segment of the runner it replaces, whose base is already
+/// part of the initial stack of the program, and the validation rule of that segment.
+pub(crate) fn register_builtins(runner: &mut CairoRunner, builtins: &[BuiltinRunner]) {
+    for builtin in builtins {
+        let Some(replaced) =
+            runner.vm.builtin_runners.iter_mut().find(|runner| runner.name() == builtin.name())
+        else {
+            continue;
+        };
+
+        // Allocate the segment of the replaced runner to the registered runner.
+        let mut segments = MemorySegmentManager::new();
+        while segments.num_segments() < replaced.base() {
+            segments.add();
+        }
+        let mut builtin = builtin.clone();
+        builtin.initialize_segments(&mut segments);
+        if let BuiltinRunner::Mod(builtin) = &mut builtin {
+            builtin.initialize_zero_segment(&mut runner.vm.segments);
+        }
+        builtin.add_validation_rule(&mut runner.vm.segments.memory);
+        *replaced = builtin;
+    }
+}
+
+/// Validates builtin runners to register on the runners of a program.
+fn validate_builtins(
+    program: &Program,
+    config: &CairoRunConfig<'_>,
+    builtins: &[BuiltinRunner],
+) -> Result<(), BuiltinError> {
+    let mut runner =
+        CairoRunner::new(program, config.layout, config.proof_mode, config.trace_enabled)?;
+    runner
+        .initialize_builtins(config.allow_missing_builtins.unwrap_or(config.proof_mode))
+        .map_err(CairoRunError::from)?;
+
+    let mut names = HashSet::new();
+    for builtin in builtins {
+        let name = builtin.name();
+        if !names.insert(name) {
+            return Err(BuiltinError::Duplicate(name));
+        }
+        if !program.iter_builtins().any(|used| *used == name) {
+            return Err(BuiltinError::NotInProgram(name));
+        }
+
+        let layout = runner
+            .vm
+            .builtin_runners
+            .iter()
+            .find(|runner| runner.name() == name)
+            .ok_or(BuiltinError::NotInLayout { builtin: name, layout: config.layout })?;
+        if config.proof_mode && layout.ratio() != builtin.ratio() {
+            return Err(BuiltinError::RatioMismatch {
+                builtin: name,
+                expected: layout.ratio(),
+                actual: builtin.ratio(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// The state of a dictionary of the hints.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DictSnapshot {
+    /// The segment of the dictionary.
+    pub segment: isize,
+    /// The pointer to the next access of the dictionary.
+    pub current_ptr: Relocatable,
+    /// The default value of the dictionary, `None` for a dictionary without default.
+    pub default_value: Option<MaybeRelocatable>,
+    /// The entries of the dictionary, ordered by key.
+    pub entries: Vec<(MaybeRelocatable, MaybeRelocatable)>,
+}
+
+impl DictSnapshot {
+    /// Captures the state of a dictionary tracker.
+    fn new((segment, tracker): (&isize, &DictTracker)) -> Self {
+        let (dict, default_value) = match &tracker.data {
+            Dictionary::SimpleDictionary(dict) => (dict, None),
+            Dictionary::DefaultDictionary { dict, default_value } => {
+                (dict, Some(default_value.clone()))
+            }
+        };
+        let mut entries: Vec<_> =
+            dict.iter().map(|(key, value)| (key.clone(), value.clone())).collect();
+        entries.sort();
+
+        Self { segment: *segment, current_ptr: tracker.current_ptr, default_value, entries }
+    }
+
+    /// Returns the dictionary tracker of the state.
+    fn tracker(&self) -> DictTracker {
+        let base = Relocatable::from((self.segment, 0));
+        let dict: HashMap<_, _> = self.entries.iter().cloned().collect();
+        let mut tracker = match &self.default_value {
+            Some(default_value) => DictTracker::new_default_dict(base, default_value, Some(dict)),
+            None => DictTracker::new_with_initial(base, dict),
+        };
+        tracker.current_ptr = self.current_ptr;
+        tracker
+    }
+}
+
+/// The state of the VM of a runner.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VmSnapshot {
+    /// The program counter.
+    pub pc: Relocatable,
+    /// The allocation pointer, as an offset in the execution segment.
+    pub ap: usize,
+    /// The frame pointer, as an offset in the execution segment.
+    pub fp: usize,
+    /// The written memory.
+    pub memory: MemoryDump,
+    /// The dictionaries of the hints, ordered by segment.
+    pub dicts: Vec<DictSnapshot>,
+}
+
+/// A Cairo runner of a parsed program, initialized and not yet ended.
+pub struct KakarotRunner {
+    /// The parsed program, shared by the restored runners.
+    program: Program,
+    /// The configuration of the run.
+    config: CairoRunConfig<'static>,
+    /// The builtin runners registered on top of the builtins of the layout.
+    builtins: Vec<BuiltinRunner>,
+    /// The underlying runner.
+    runner: CairoRunner,
+    /// The pc at which the execution ends.
+    end: Relocatable,
+}
+
+impl fmt::Debug for KakarotRunner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KakarotRunner")
+            .field("pc", &self.runner.vm.get_pc())
+            .field("end", &self.end)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KakarotRunner {
+    /// Parses a compiled program and initializes its runner.
+    pub fn new(program: &[u8], config: CairoRunConfig<'static>) -> Result<Self, CairoRunError> {
+        let program = Program::from_bytes(program, Some(config.entrypoint))?;
+        let (runner, end) = initialize_runner(&program, &config, &[])?;
+        Ok(Self { program, config, builtins: Vec::new(), runner, end })
+    }
+
+    /// Parses a compiled program and initializes its runner with additional builtin runners,
+    /// replacing the builtins of the layout with the same name.
+    pub fn with_builtins(
+        program: &[u8],
+        config: CairoRunConfig<'static>,
+        builtins: Vec<BuiltinRunner>,
+    ) -> Result<Self, BuiltinError> {
+        let program =
+            Program::from_bytes(program, Some(config.entrypoint)).map_err(CairoRunError::from)?;
+        validate_builtins(&program, &config, &builtins)?;
+        let (runner, end) = initialize_runner(&program, &config, &builtins)?;
+        Ok(Self { program, config, builtins, runner, end })
+    }
+
+    /// Returns the VM of the runner.
+    pub const fn vm(&self) -> &VirtualMachine {
+        &self.runner.vm
+    }
+
+    /// Returns the execution scopes of the hints, e.g. to install a pre-sized dict manager.
+    pub fn exec_scopes_mut(&mut self) -> &mut ExecutionScopes {
+        &mut self.runner.exec_scopes
+    }
+
+    /// Returns whether the execution reached its end.
+    pub fn is_ended(&self) -> bool {
+        self.runner.vm.get_pc() == self.end
+    }
+
+    /// Runs the given number of steps, failing with [`VirtualMachineError::EndOfProgram`] when the
+    /// end is reached first.
+    pub fn run_for_steps(
+        &mut self,
+        steps: usize,
+        hint_processor: &mut dyn HintProcessor,
+    ) -> Result<(), VirtualMachineError> {
+        self.runner.run_for_steps(steps, hint_processor)
+    }
+
+    /// Runs the program to its end, as [`run_with_limits`](crate::limits::run_with_limits), the
+    /// limits applying to the steps run by this call only.
+    ///
+    /// The ended runner is moved out of the wrapper, left in the state of a new runner, e.g. to
+    /// restore the base state of the next speculative execution.
+    pub fn run_to_end(
+        &mut self,
+        hint_processor: &mut dyn HintProcessor,
+        limits: &ExecutionLimits,
+        preempt: &mut dyn FnMut() -> bool,
+    ) -> Result<LimitedRun, CairoRunError> {
+        let (runner, end) = initialize_runner(&self.program, &self.config, &self.builtins)?;
+        let runner = std::mem::replace(&mut self.runner, runner);
+        let end = std::mem::replace(&mut self.end, end);
+        run_until_end(runner, end, &self.config, hint_processor, limits, preempt)
+    }
+
+    /// Returns a runner of the same program in the state of a snapshot, without parsing the
+    /// program again, e.g. to run several speculative executions from a base state side by side.
+    pub fn fork(&self, snapshot: &VmSnapshot) -> Result<Self, CairoRunError> {
+        let (runner, end) = self.restored(snapshot)?;
+        let config = CairoRunConfig {
+            entrypoint: self.config.entrypoint,
+            trace_enabled: self.config.trace_enabled,
+            relocate_mem: self.config.relocate_mem,
+            layout: self.config.layout,
+            proof_mode: self.config.proof_mode,
+            secure_run: self.config.secure_run,
+            disable_trace_padding: self.config.disable_trace_padding,
+            allow_missing_builtins: self.config.allow_missing_builtins,
+        };
+        Ok(Self {
+            program: self.program.clone(),
+            config,
+            builtins: self.builtins.clone(),
+            runner,
+            end,
+        })
+    }
+
+    /// Captures the state of the VM.
+    pub fn snapshot(&mut self) -> VmSnapshot {
+        let mut dicts: Vec<_> = self
+            .runner
+            .exec_scopes
+            .get_dict_manager()
+            .map(|manager| manager.borrow().trackers.iter().map(DictSnapshot::new).collect())
+            .unwrap_or_default();
+        dicts.sort_by_key(|dict| dict.segment);
+
+        // The sizes of the segments are cached by the capture of the memory, they must be
+        // computed again at the end of the run.
+        let vm = &mut self.runner.vm;
+        let memory = MemoryDump::capture(vm);
+        vm.segments.segment_used_sizes = None;
+
+        VmSnapshot {
+            pc: vm.get_pc(),
+            ap: vm.get_ap().offset,
+            fp: vm.get_fp().offset,
+            memory,
+            dicts,
+        }
+    }
+
+    /// Restores a snapshot of a runner of the same program, discarding the current state.
+    pub fn restore(&mut self, snapshot: &VmSnapshot) -> Result<(), CairoRunError> {
+        (self.runner, self.end) = self.restored(snapshot)?;
+        Ok(())
+    }
+
+    /// Returns a new runner of the program in the state of a snapshot, with the pc at which its
+    /// execution ends.
+    fn restored(&self, snapshot: &VmSnapshot) -> Result<(CairoRunner, Relocatable), CairoRunError> {
+        let (mut runner, end) = initialize_runner(&self.program, &self.config, &self.builtins)?;
+
+        // The cells written by the initialization are written again with the same value.
+        while runner.vm.segments.num_segments() < snapshot.memory.segments.len() {
+            runner.vm.segments.add();
+        }
+        for segment in &snapshot.memory.segments {
+            for cell in &segment.cells {
+                let address = Relocatable::from((segment.index, cell.offset));
+                runner.vm.insert_value(address, cell.value.clone())?;
+            }
+        }
+
+        runner.vm.set_pc(snapshot.pc);
+        runner.vm.set_ap(snapshot.ap);
+        runner.vm.set_fp(snapshot.fp);
+
+        if !snapshot.dicts.is_empty() {
+            let mut manager = DictManager::new();
+            for dict in &snapshot.dicts {
+                manager.trackers.insert(dict.segment, dict.tracker());
+            }
+            runner.exec_scopes.insert_value(DICT_MANAGER_SCOPE, Rc::new(RefCell::new(manager)));
+        }
+
+        Ok((runner, end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{executor::ExecutionMode, hints::KakarotHintProcessor, tuning::RunProfile};
+    use cairo_vm::vm::runners::builtin_runner::{BitwiseBuiltinRunner, KeccakBuiltinRunner};
+
+    const PROGRAM: &[u8] = include_bytes!("../testdata/keccak_add_uint256.json");
+
+    fn completed(run: LimitedRun) -> CairoRunner {
+        let LimitedRun::Completed(runner) = run else { panic!("Expected a completed run") };
+        runner
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let config = ExecutionMode::DryRun.run_config();
+        let mut hint_processor = KakarotHintProcessor::default();
+        let mut runner = KakarotRunner::new(PROGRAM, config).unwrap();
+        runner.run_for_steps(20, &mut hint_processor).unwrap();
+        let base = runner.snapshot();
+        assert_eq!(base.pc, runner.vm().get_pc());
+
+        let full = completed(
+            runner
+                .run_to_end(&mut hint_processor, &ExecutionLimits::default(), &mut || false)
+                .unwrap(),
+        );
+
+        // Several executions run on top of the same base state.
+        for _ in 0..2 {
+            runner.restore(&base).unwrap();
+            assert_eq!(runner.snapshot(), base);
+            runner.run_for_steps(5, &mut hint_processor).unwrap();
+            assert!(!runner.is_ended());
+        }
+
+        // The runner run to its end is restored again, and forks run side by side.
+        for _ in 0..2 {
+            runner.restore(&base).unwrap();
+            let resumed = completed(
+                runner
+                    .run_to_end(&mut hint_processor, &ExecutionLimits::default(), &mut || false)
+                    .unwrap(),
+            );
+            assert_eq!(RunProfile::from_runner(&resumed), RunProfile::from_runner(&full));
+        }
+        let mut forks = [runner.fork(&base).unwrap(), runner.fork(&base).unwrap()];
+        forks[0].run_for_steps(5, &mut hint_processor).unwrap();
+        assert_eq!(forks[1].snapshot(), base);
+        for fork in &mut forks {
+            let resumed = completed(
+                fork.run_to_end(&mut hint_processor, &ExecutionLimits::default(), &mut || false)
+                    .unwrap(),
+            );
+            assert_eq!(RunProfile::from_runner(&resumed), RunProfile::from_runner(&full));
+        }
+    }
+
+    #[test]
+    fn test_with_builtins() {
+        let bitwise = |ratio| BuiltinRunner::Bitwise(BitwiseBuiltinRunner::new(ratio, true));
+        let mut hint_processor = KakarotHintProcessor::default();
+
+        // A variant of a builtin of the program is run in place of the builtin of the layout.
+        let bitwise_runners = |runner: &KakarotRunner| -> Vec<_> {
+            runner
+                .vm()
+                .get_builtin_runners()
+                .iter()
+                .filter(|runner| runner.name() == BuiltinName::bitwise)
+                .map(|runner| (runner.base(), runner.ratio()))
+                .collect()
+        };
+        let layout = KakarotRunner::new(PROGRAM, ExecutionMode::DryRun.run_config()).unwrap();
+        let config = ExecutionMode::DryRun.run_config();
+        let mut runner =
+            KakarotRunner::with_builtins(PROGRAM, config, vec![bitwise(Some(1))]).unwrap();
+        // The registered builtin runs on the segment allocated to the builtin of the layout.
+        let [(base, ratio)] = bitwise_runners(&layout)[..] else { panic!("Bitwise not in layout") };
+        assert_ne!(ratio, Some(1));
+        assert_eq!(bitwise_runners(&runner), vec![(base, Some(1))]);
+        completed(
+            runner
+                .run_to_end(&mut hint_processor, &ExecutionLimits::default(), &mut || false)
+                .unwrap(),
+        );
+
+        let with_builtins = |mode: ExecutionMode, builtins| {
+            KakarotRunner::with_builtins(PROGRAM, mode.run_config(), builtins)
+        };
+        let keccak = BuiltinRunner::Keccak(KeccakBuiltinRunner::new(Some(2048), true));
+        assert!(matches!(
+            with_builtins(ExecutionMode::DryRun, vec![keccak]),
+            Err(BuiltinError::NotInProgram(BuiltinName::keccak))
+        ));
+        assert!(matches!(
+            with_builtins(ExecutionMode::DryRun, vec![bitwise(None), bitwise(None)]),
+            Err(BuiltinError::Duplicate(BuiltinName::bitwise))
+        ));
+        // The proof-mode builtins keep the ratios of the layout.
+        assert!(matches!(
+            with_builtins(ExecutionMode::Proof, vec![bitwise(Some(1))]),
+            Err(BuiltinError::RatioMismatch { builtin: BuiltinName::bitwise, .. })
+        ));
+    }
+}
@@ -0,0 +1,232 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// The lane of a block execution job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Lane {
+    /// The tip of the chain, proved with priority.
+    Tip,
+    /// Historical blocks and retries, filling the idle capacity.
+    Backfill,
+}
+
+impl fmt::Display for Lane {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tip => write!(f, "tip"),
+            Self::Backfill => write!(f, "backfill"),
+        }
+    }
+}
+
+/// The configuration of the [`Scheduler`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchedulerConfig {
+    /// The number of consecutive tip jobs served before a backfill job, when both are pending.
+    pub tip_weight: u32,
+    /// The number of consecutive backfill jobs served before a tip job, when both are pending.
+    pub backfill_weight: u32,
+    /// The maximum wait of a tip job, running backfill jobs being preempted past it.
+    pub max_tip_wait: Duration,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self { tip_weight: 4, backfill_weight: 1, max_tip_wait: Duration::from_secs(30) }
+    }
+}
+
+/// A block execution job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Job {
+    /// The number of the block.
+    pub number: u64,
+    /// The lane of the job.
+    pub lane: Lane,
+    /// When the job was scheduled.
+    pub scheduled_at: Instant,
+}
+
+/// A two-lane scheduler of block execution jobs.
+///
+/// Both lanes are served in a weighted round robin, a lane being skipped when it is empty so that
+/// backfill jobs use the capacity left idle by the tip. A block is scheduled at most once, a
+/// backfill job being promoted to the tip lane when the block is scheduled again as tip.
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    /// The configuration of the scheduler.
+    config: SchedulerConfig,
+    /// The pending tip jobs, oldest first.
+    tip: VecDeque<Job>,
+    /// The pending backfill jobs, oldest first.
+    backfill: VecDeque<Job>,
+    /// The lane currently served, with the number of jobs it served in a row.
+    current: (Lane, u32),
+}
+
+impl Scheduler {
+    /// Creates a new empty [`Scheduler`].
+    pub fn new(config: SchedulerConfig) -> Self {
+        Self { config, tip: VecDeque::new(), backfill: VecDeque::new(), current: (Lane::Tip, 0) }
+    }
+
+    /// Schedules a block in the given lane, returning whether it was not already scheduled.
+    pub fn push(&mut self, number: u64, lane: Lane) -> bool {
+        if self.tip.iter().any(|job| job.number == number) {
+            return false;
+        }
+        if let Some(index) = self.backfill.iter().position(|job| job.number == number) {
+            if lane == Lane::Backfill {
+                return false;
+            }
+            self.backfill.remove(index);
+        }
+
+        let job = Job { number, lane, scheduled_at: Instant::now() };
+        self.lane_mut(lane).push_back(job);
+        true
+    }
+
+    /// Schedules a preempted job again, ahead of the other jobs of its lane.
+    pub fn requeue(&mut self, job: Job) {
+        self.lane_mut(job.lane).push_front(job);
+    }
+
+    /// Cancels the pending jobs of the blocks from the given number onwards, returning the number
+    /// of cancelled jobs.
+    pub fn cancel_from(&mut self, number: u64) -> usize {
+        let pending = self.tip.len() + self.backfill.len();
+        self.tip.retain(|job| job.number < number);
+        self.backfill.retain(|job| job.number < number);
+        pending - self.tip.len() - self.backfill.len()
+    }
+
+    /// Returns the next job to run.
+    pub fn pop(&mut self) -> Option<Job> {
+        let (lane, served) = self.current;
+        let other = match lane {
+            Lane::Tip => Lane::Backfill,
+            Lane::Backfill => Lane::Tip,
+        };
+
+        // Switch lanes once the current one used its weight, or when it has no pending job.
+        let lane = if (served >= self.weight(lane) && self.len(other) > 0) || self.len(lane) == 0 {
+            self.current = (other, 0);
+            other
+        } else {
+            lane
+        };
+
+        let job = self.lane_mut(lane).pop_front()?;
+        self.current.1 += 1;
+        Some(job)
+    }
+
+    /// Returns whether the oldest tip job waited longer than the configured maximum at `now`.
+    pub fn tip_starving(&self, now: Instant) -> bool {
+        self.tip.front().is_some_and(|job| {
+            now.saturating_duration_since(job.scheduled_at) >= self.config.max_tip_wait
+        })
+    }
+
+    /// Returns the number of pending jobs of a lane.
+    pub fn len(&self, lane: Lane) -> usize {
+        match lane {
+            Lane::Tip => self.tip.len(),
+            Lane::Backfill => self.backfill.len(),
+        }
+    }
+
+    /// Returns whether no job is pending.
+    pub fn is_empty(&self) -> bool {
+        self.tip.is_empty() && self.backfill.is_empty()
+    }
+
+    /// Returns the weight of a lane, at least `1`.
+    fn weight(&self, lane: Lane) -> u32 {
+        match lane {
+            Lane::Tip => self.config.tip_weight.max(1),
+            Lane::Backfill => self.config.backfill_weight.max(1),
+        }
+    }
+
+    /// Returns the pending jobs of a lane.
+    fn lane_mut(&mut self, lane: Lane) -> &mut VecDeque<Job> {
+        match lane {
+            Lane::Tip => &mut self.tip,
+            Lane::Backfill => &mut self.backfill,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pop_all(scheduler: &mut Scheduler) -> Vec<u64> {
+        std::iter::from_fn(|| scheduler.pop()).map(|job| job.number).collect()
+    }
+
+    #[test]
+    fn test_weighted_round_robin() {
+        let config = SchedulerConfig { tip_weight: 2, backfill_weight: 1, ..Default::default() };
+        let mut scheduler = Scheduler::new(config);
+        for number in 1..=3 {
+            scheduler.push(number, Lane::Backfill);
+        }
+        for number in 10..=14 {
+            scheduler.push(number, Lane::Tip);
+        }
+
+        assert_eq!(pop_all(&mut scheduler), vec![10, 11, 1, 12, 13, 2, 14, 3]);
+        assert!(scheduler.is_empty());
+    }
+
+    #[test]
+    fn test_push_deduplicates_and_promotes() {
+        let mut scheduler = Scheduler::new(SchedulerConfig::default());
+
+        assert!(scheduler.push(1, Lane::Backfill));
+        assert!(!scheduler.push(1, Lane::Backfill));
+        assert!(scheduler.push(1, Lane::Tip));
+        assert!(!scheduler.push(1, Lane::Backfill));
+
+        assert_eq!(scheduler.len(Lane::Tip), 1);
+        assert_eq!(scheduler.len(Lane::Backfill), 0);
+    }
+
+    #[test]
+    fn test_cancel_from() {
+        let mut scheduler = Scheduler::new(SchedulerConfig::default());
+        for number in 1..=4 {
+            scheduler.push(number, Lane::Backfill);
+        }
+        scheduler.push(5, Lane::Tip);
+
+        assert_eq!(scheduler.cancel_from(3), 3);
+        assert_eq!(scheduler.cancel_from(3), 0);
+        assert_eq!(pop_all(&mut scheduler), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_tip_starving_and_requeue() {
+        let config = SchedulerConfig { max_tip_wait: Duration::from_secs(5), ..Default::default() };
+        let mut scheduler = Scheduler::new(config);
+        scheduler.push(1, Lane::Backfill);
+        scheduler.push(2, Lane::Backfill);
+        let job = scheduler.pop().unwrap();
+        scheduler.push(10, Lane::Tip);
+
+        let now = Instant::now();
+        assert!(!scheduler.tip_starving(now));
+        assert!(scheduler.tip_starving(now + Duration::from_secs(5)));
+
+        // The preempted backfill job runs again before the other backfill jobs.
+        scheduler.requeue(job);
+        assert_eq!(pop_all(&mut scheduler), vec![10, 1, 2]);
+    }
+}
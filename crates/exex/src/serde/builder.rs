@@ -0,0 +1,295 @@
+//! A builder of [`KakarotSerde`], setting up the runner of its program.
+//!
+//! The program is given as compiled bytes, as the path of a compiled program or as a loaded
+//! [`KakarotProgram`]. The runner is created with the given layout, proof mode and entrypoint, and
+//! is run to its end when a hint processor is given, so that the values written by the run can be
+//! deserialized. Given a [`RelocatedMemory`] instead, the runner is only used for the identifiers
+//! of the program, the values being read from the relocated memory.
+
+use super::{relocated::RelocatedMemory, KakarotSerde};
+use crate::{
+    limits::{initialize_runner, run_until_end, ExecutionLimits, LimitedRun},
+    program::{KakarotProgram, ProgramError, ENTRYPOINT},
+};
+use cairo_vm::{
+    cairo_run::CairoRunConfig,
+    hint_processor::hint_processor_definition::HintProcessor,
+    types::{layout_name::LayoutName, program::Program},
+    vm::{errors::cairo_run_errors::CairoRunError, runners::cairo_runner::CairoRunner},
+};
+use std::{fmt, fs, path::PathBuf};
+use thiserror::Error;
+
+/// Represents errors that can occur when building a [`KakarotSerde`].
+#[derive(Debug, Error)]
+pub enum BuilderError {
+    /// Error variant indicating that no program source was given.
+    #[error("No program source given")]
+    MissingProgram,
+
+    /// Error variant indicating that several program sources were given.
+    #[error("{0} program sources given, expected one")]
+    ConflictingPrograms(usize),
+
+    /// Error variant indicating a proof-mode run from another entrypoint than `main`.
+    #[error("Proof-mode runs start from '{ENTRYPOINT}', not '{0}'")]
+    ProofModeEntrypoint(String),
+
+    /// Error variant indicating a loaded program with another entrypoint than `main`.
+    #[error("Loaded programs are parsed with the '{ENTRYPOINT}' entrypoint, not '{0}'")]
+    LoadedEntrypoint(String),
+
+    /// Error variant indicating a relocated memory given along with a hint processor.
+    #[error("A relocated memory can't be read from a run")]
+    RelocatedRun,
+
+    /// Error variant indicating a run that did not complete.
+    #[error("The run of the program did not complete")]
+    IncompleteRun,
+
+    /// Error variant indicating a failure to load or parse the program.
+    #[error(transparent)]
+    Program(#[from] ProgramError),
+
+    /// Error variant indicating a failure to create or run the runner.
+    #[error(transparent)]
+    Run(#[from] CairoRunError),
+}
+
+/// The source of the program of a [`KakarotSerde`].
+#[derive(Debug, Clone)]
+pub enum ProgramSource {
+    /// A compiled program.
+    Bytes(Vec<u8>),
+    /// The path of a compiled program.
+    Path(PathBuf),
+    /// A loaded program.
+    Loaded(KakarotProgram),
+}
+
+/// A builder of [`KakarotSerde`], see [`KakarotSerde::builder`].
+pub struct KakarotSerdeBuilder {
+    /// The program sources given, exactly one being expected.
+    sources: Vec<ProgramSource>,
+    /// The layout of the runner.
+    layout: LayoutName,
+    /// Whether the runner is in proof mode.
+    proof_mode: bool,
+    /// The entrypoint of the program.
+    entrypoint: String,
+    /// The hint processor running the program to its end, the runner being only created when
+    /// `None`.
+    hint_processor: Option<Box<dyn HintProcessor>>,
+    /// The relocated memory to read in place of the memory of the runner.
+    relocated: Option<RelocatedMemory>,
+}
+
+impl fmt::Debug for KakarotSerdeBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KakarotSerdeBuilder")
+            .field("sources", &self.sources.len())
+            .field("layout", &self.layout)
+            .field("proof_mode", &self.proof_mode)
+            .field("entrypoint", &self.entrypoint)
+            .field("run", &self.hint_processor.is_some())
+            .field("relocated", &self.relocated.as_ref().map(RelocatedMemory::len))
+            .finish()
+    }
+}
+
+impl Default for KakarotSerdeBuilder {
+    fn default() -> Self {
+        Self {
+            sources: Vec::new(),
+            layout: LayoutName::plain,
+            proof_mode: false,
+            entrypoint: ENTRYPOINT.to_string(),
+            hint_processor: None,
+            relocated: None,
+        }
+    }
+}
+
+impl KakarotSerde {
+    /// Returns a builder of a [`KakarotSerde`], by default for a runner of the `main` entrypoint
+    /// in the plain layout, neither in proof mode nor run.
+    pub fn builder() -> KakarotSerdeBuilder {
+        KakarotSerdeBuilder::default()
+    }
+}
+
+impl KakarotSerdeBuilder {
+    /// Sets the source of the program.
+    pub fn program(mut self, source: ProgramSource) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    /// Sets the program to a compiled program.
+    pub fn program_bytes(self, bytes: impl Into<Vec<u8>>) -> Self {
+        self.program(ProgramSource::Bytes(bytes.into()))
+    }
+
+    /// Sets the program to the compiled program at a path.
+    pub fn program_path(self, path: impl Into<PathBuf>) -> Self {
+        self.program(ProgramSource::Path(path.into()))
+    }
+
+    /// Sets the program to a loaded program.
+    pub fn loaded_program(self, program: KakarotProgram) -> Self {
+        self.program(ProgramSource::Loaded(program))
+    }
+
+    /// Sets the layout of the runner.
+    pub const fn layout(mut self, layout: LayoutName) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Sets whether the runner is in proof mode, the trace being enabled in proof mode.
+    pub const fn proof_mode(mut self, proof_mode: bool) -> Self {
+        self.proof_mode = proof_mode;
+        self
+    }
+
+    /// Sets the entrypoint of the program.
+    pub fn entrypoint(mut self, entrypoint: impl Into<String>) -> Self {
+        self.entrypoint = entrypoint.into();
+        self
+    }
+
+    /// Sets the hint processor running the program to its end when building.
+    pub fn hint_processor(mut self, hint_processor: impl HintProcessor + 'static) -> Self {
+        self.hint_processor = Some(Box::new(hint_processor));
+        self
+    }
+
+    /// Sets the relocated memory to read, e.g. persisted after a run, instead of running the
+    /// program.
+    pub fn relocated_memory(mut self, memory: RelocatedMemory) -> Self {
+        self.relocated = Some(memory);
+        self
+    }
+
+    /// Builds the [`KakarotSerde`], validating the configuration.
+    pub fn build(mut self) -> Result<KakarotSerde, BuilderError> {
+        let source = match self.sources.len() {
+            0 => return Err(BuilderError::MissingProgram),
+            1 => self.sources.remove(0),
+            count => return Err(BuilderError::ConflictingPrograms(count)),
+        };
+        if self.relocated.is_some() && self.hint_processor.is_some() {
+            return Err(BuilderError::RelocatedRun);
+        }
+        if self.proof_mode && self.entrypoint != ENTRYPOINT {
+            return Err(BuilderError::ProofModeEntrypoint(self.entrypoint));
+        }
+
+        let parse = |bytes: &[u8]| {
+            Program::from_bytes(bytes, Some(&self.entrypoint))
+                .map_err(|err| ProgramError::Parse(err.to_string()))
+        };
+        let program = match source {
+            ProgramSource::Bytes(bytes) => parse(&bytes)?,
+            ProgramSource::Path(path) => parse(&fs::read(path).map_err(ProgramError::from)?)?,
+            ProgramSource::Loaded(program) => {
+                if self.entrypoint != ENTRYPOINT {
+                    return Err(BuilderError::LoadedEntrypoint(self.entrypoint));
+                }
+                program.program().clone()
+            }
+        };
+
+        let runner = match self.hint_processor {
+            Some(mut hint_processor) => {
+                let config = CairoRunConfig {
+                    entrypoint: &self.entrypoint,
+                    layout: self.layout,
+                    proof_mode: self.proof_mode,
+                    trace_enabled: self.proof_mode,
+                    relocate_mem: self.proof_mode,
+                    ..Default::default()
+                };
+                let (runner, end) = initialize_runner(&program, &config, &[])?;
+                let limits = ExecutionLimits::default();
+                let run = run_until_end(
+                    runner,
+                    end,
+                    &config,
+                    &mut *hint_processor,
+                    &limits,
+                    &mut || false,
+                )?;
+                match run {
+                    LimitedRun::Completed(runner) => runner,
+                    LimitedRun::Interrupted(_) | LimitedRun::Preempted => {
+                        return Err(BuilderError::IncompleteRun)
+                    }
+                }
+            }
+            None => CairoRunner::new(&program, self.layout, self.proof_mode, self.proof_mode)?,
+        };
+        Ok(KakarotSerde { relocated: self.relocated, ..KakarotSerde::new(runner) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hints::KakarotHintProcessor;
+
+    const PROGRAM: &[u8] = include_bytes!("../../testdata/keccak_add_uint256.json");
+
+    #[test]
+    fn test_build_kakarot_serde() {
+        let kakarot_serde = KakarotSerde::builder().program_bytes(PROGRAM).build().unwrap();
+        assert!(kakarot_serde.get_identifier("main", Some("function".to_string())).is_ok());
+
+        // The program is run to its end with a hint processor.
+        let kakarot_serde = KakarotSerde::builder()
+            .loaded_program(KakarotProgram::from_bytes(PROGRAM.to_vec()).unwrap())
+            .layout(LayoutName::all_cairo)
+            .proof_mode(true)
+            .hint_processor(KakarotHintProcessor::default())
+            .build()
+            .unwrap();
+        assert!(kakarot_serde.runner.relocated_trace.is_some());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("program.json");
+        fs::write(&path, PROGRAM).unwrap();
+        assert!(KakarotSerde::builder().program_path(path).build().is_ok());
+    }
+
+    #[test]
+    fn test_build_kakarot_serde_errors() {
+        assert!(matches!(KakarotSerde::builder().build(), Err(BuilderError::MissingProgram)));
+        assert!(matches!(
+            KakarotSerde::builder().program_bytes(PROGRAM).program_path("os.json").build(),
+            Err(BuilderError::ConflictingPrograms(2))
+        ));
+        assert!(matches!(
+            KakarotSerde::builder().program_bytes(PROGRAM).proof_mode(true).entrypoint("f").build(),
+            Err(BuilderError::ProofModeEntrypoint(entrypoint)) if entrypoint == "f"
+        ));
+        assert!(matches!(
+            KakarotSerde::builder()
+                .loaded_program(KakarotProgram::from_bytes(PROGRAM.to_vec()).unwrap())
+                .entrypoint("f")
+                .build(),
+            Err(BuilderError::LoadedEntrypoint(_))
+        ));
+        assert!(matches!(
+            KakarotSerde::builder()
+                .program_bytes(PROGRAM)
+                .relocated_memory(RelocatedMemory::default())
+                .hint_processor(KakarotHintProcessor::default())
+                .build(),
+            Err(BuilderError::RelocatedRun)
+        ));
+        assert!(matches!(
+            KakarotSerde::builder().program_path("missing.json").build(),
+            Err(BuilderError::Program(ProgramError::Io(_)))
+        ));
+    }
+}
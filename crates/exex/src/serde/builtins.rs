@@ -0,0 +1,269 @@
+//! Typed builtin pointers of the implicit arguments.
+//!
+//! The implicit arguments of a Cairo function thread the builtin pointers through the program. A
+//! builtin pointer wired to the wrong builtin only fails when the trace is proven, so the typed
+//! pointers returned by [`KakarotSerde::serialize_implicit_args`] are checked to point into the
+//! segment of their builtin when they are read.
+
+use super::{KakarotSerde, KakarotSerdeError};
+use cairo_vm::types::{
+    builtin_name::BuiltinName,
+    relocatable::{MaybeRelocatable, Relocatable},
+};
+use std::collections::HashMap;
+
+/// A pointer into the segment of a builtin.
+pub trait BuiltinPointer: Sized {
+    /// The builtin the pointer points into.
+    const BUILTIN: BuiltinName;
+
+    /// The name of the implicit argument holding the pointer.
+    const MEMBER: &'static str;
+
+    /// Wraps an address, which is expected to be in the segment of the builtin.
+    fn from_address(address: Relocatable) -> Self;
+
+    /// Returns the address pointed to.
+    fn address(&self) -> Relocatable;
+}
+
+/// A pointer into the range check builtin segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RangeCheckPtr(Relocatable);
+
+impl BuiltinPointer for RangeCheckPtr {
+    const BUILTIN: BuiltinName = BuiltinName::range_check;
+    const MEMBER: &'static str = "range_check_ptr";
+
+    fn from_address(address: Relocatable) -> Self {
+        Self(address)
+    }
+
+    fn address(&self) -> Relocatable {
+        self.0
+    }
+}
+
+/// A pointer into the bitwise builtin segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BitwisePtr(Relocatable);
+
+impl BuiltinPointer for BitwisePtr {
+    const BUILTIN: BuiltinName = BuiltinName::bitwise;
+    const MEMBER: &'static str = "bitwise_ptr";
+
+    fn from_address(address: Relocatable) -> Self {
+        Self(address)
+    }
+
+    fn address(&self) -> Relocatable {
+        self.0
+    }
+}
+
+/// A pointer into the keccak builtin segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeccakPtr(Relocatable);
+
+impl BuiltinPointer for KeccakPtr {
+    const BUILTIN: BuiltinName = BuiltinName::keccak;
+    const MEMBER: &'static str = "keccak_ptr";
+
+    fn from_address(address: Relocatable) -> Self {
+        Self(address)
+    }
+
+    fn address(&self) -> Relocatable {
+        self.0
+    }
+}
+
+/// A pointer into the poseidon builtin segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PoseidonPtr(Relocatable);
+
+impl BuiltinPointer for PoseidonPtr {
+    const BUILTIN: BuiltinName = BuiltinName::poseidon;
+    const MEMBER: &'static str = "poseidon_ptr";
+
+    fn from_address(address: Relocatable) -> Self {
+        Self(address)
+    }
+
+    fn address(&self) -> Relocatable {
+        self.0
+    }
+}
+
+/// The serialized implicit arguments of a function.
+///
+/// The builtin pointers are `None` when the function does not take them or when they are null.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImplicitArgs {
+    /// The range check builtin pointer.
+    pub range_check_ptr: Option<RangeCheckPtr>,
+    /// The bitwise builtin pointer.
+    pub bitwise_ptr: Option<BitwisePtr>,
+    /// The keccak builtin pointer.
+    pub keccak_ptr: Option<KeccakPtr>,
+    /// The poseidon builtin pointer.
+    pub poseidon_ptr: Option<PoseidonPtr>,
+    /// The other implicit arguments, e.g. `output_ptr`, as serialized by
+    /// [`KakarotSerde::serialize_pointers`].
+    pub others: HashMap<String, Option<MaybeRelocatable>>,
+}
+
+impl KakarotSerde {
+    /// Serializes the implicit arguments struct at `ptr`, checking that each builtin pointer
+    /// points into the segment of its builtin.
+    pub fn serialize_implicit_args(
+        &self,
+        struct_name: &str,
+        ptr: Relocatable,
+    ) -> Result<ImplicitArgs, KakarotSerdeError> {
+        let mut others = self.serialize_pointers(struct_name, ptr)?;
+        Ok(ImplicitArgs {
+            range_check_ptr: self.builtin_pointer(&mut others)?,
+            bitwise_ptr: self.builtin_pointer(&mut others)?,
+            keccak_ptr: self.builtin_pointer(&mut others)?,
+            poseidon_ptr: self.builtin_pointer(&mut others)?,
+            others,
+        })
+    }
+
+    /// Returns the index of the segment of a builtin.
+    pub fn builtin_segment(&self, builtin: BuiltinName) -> Result<usize, KakarotSerdeError> {
+        self.runner
+            .vm
+            .get_builtin_runners()
+            .iter()
+            .find(|runner| runner.name() == builtin)
+            .map(|runner| runner.base())
+            .ok_or(KakarotSerdeError::MissingBuiltin(builtin))
+    }
+
+    /// Takes the builtin pointer `P` out of the serialized members, validating its segment.
+    fn builtin_pointer<P: BuiltinPointer>(
+        &self,
+        members: &mut HashMap<String, Option<MaybeRelocatable>>,
+    ) -> Result<Option<P>, KakarotSerdeError> {
+        let Some(value) = members.remove(P::MEMBER).flatten() else { return Ok(None) };
+
+        let segment = self.builtin_segment(P::BUILTIN)?;
+        match value {
+            MaybeRelocatable::RelocatableValue(address)
+                if address.segment_index == segment as isize =>
+            {
+                Ok(Some(P::from_address(address)))
+            }
+            value => Err(KakarotSerdeError::WrongBuiltinSegment {
+                member: P::MEMBER.to_string(),
+                builtin: P::BUILTIN,
+                segment,
+                value,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cairo_vm::{types::layout_name::LayoutName, Felt252};
+
+    /// Returns a serializer whose runner has the `output`, `range_check` and `bitwise` builtin
+    /// segments initialized, at the indexes 2, 3 and 4.
+    fn setup_kakarot_serde() -> KakarotSerde {
+        let mut kakarot_serde = KakarotSerde::builder()
+            .program_bytes(include_bytes!("../../testdata/keccak_add_uint256.json"))
+            .layout(LayoutName::all_cairo)
+            .build()
+            .unwrap();
+        kakarot_serde.runner.initialize_builtins(false).unwrap();
+        kakarot_serde.runner.initialize_segments(None);
+        kakarot_serde
+    }
+
+    /// Writes `main.ImplicitArgs` with the given members to memory.
+    fn implicit_args(
+        kakarot_serde: &mut KakarotSerde,
+        members: &[MaybeRelocatable],
+    ) -> Relocatable {
+        kakarot_serde.runner.vm.gen_arg(&members.to_vec()).unwrap().get_relocatable().unwrap()
+    }
+
+    #[test]
+    fn test_serialize_implicit_args() {
+        let mut kakarot_serde = setup_kakarot_serde();
+        let output = Relocatable::from((2, 0));
+        let range_check = Relocatable::from((3, 4));
+        let bitwise = Relocatable::from((4, 10));
+        let args =
+            implicit_args(&mut kakarot_serde, &[output.into(), range_check.into(), bitwise.into()]);
+
+        let result = kakarot_serde.serialize_implicit_args("main.ImplicitArgs", args).unwrap();
+
+        assert_eq!(
+            result,
+            ImplicitArgs {
+                range_check_ptr: Some(RangeCheckPtr(range_check)),
+                bitwise_ptr: Some(BitwisePtr(bitwise)),
+                keccak_ptr: None,
+                poseidon_ptr: None,
+                others: HashMap::from_iter([("output_ptr".to_string(), Some(output.into()))]),
+            }
+        );
+        assert_eq!(result.bitwise_ptr.unwrap().address(), bitwise);
+    }
+
+    #[test]
+    fn test_serialize_implicit_args_wrong_segment() {
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        // The bitwise pointer is wired to the range check segment.
+        let range_check = Relocatable::from((3, 0));
+        let args = implicit_args(
+            &mut kakarot_serde,
+            &[Felt252::ZERO.into(), range_check.into(), range_check.into()],
+        );
+        let result = kakarot_serde.serialize_implicit_args("main.ImplicitArgs", args);
+        match result {
+            Err(KakarotSerdeError::WrongBuiltinSegment { member, builtin, segment, value }) => {
+                assert_eq!(member, "bitwise_ptr");
+                assert_eq!(builtin, BuiltinName::bitwise);
+                assert_eq!(segment, 4);
+                assert_eq!(value, range_check.into());
+            }
+            _ => panic!("Expected KakarotSerdeError::WrongBuiltinSegment, but got: {:?}", result),
+        }
+
+        // The range check pointer holds a felt.
+        let args = implicit_args(
+            &mut kakarot_serde,
+            &[Felt252::ZERO.into(), Felt252::from(12).into(), Relocatable::from((4, 0)).into()],
+        );
+        assert!(matches!(
+            kakarot_serde.serialize_implicit_args("main.ImplicitArgs", args),
+            Err(KakarotSerdeError::WrongBuiltinSegment { builtin: BuiltinName::range_check, .. })
+        ));
+    }
+
+    #[test]
+    fn test_serialize_implicit_args_missing_builtin() {
+        // The builtins of the runner are not initialized.
+        let mut kakarot_serde = KakarotSerde::builder()
+            .program_bytes(include_bytes!("../../testdata/keccak_add_uint256.json"))
+            .build()
+            .unwrap();
+        let segment = kakarot_serde.runner.vm.add_memory_segment();
+        let args = implicit_args(
+            &mut kakarot_serde,
+            &[Felt252::ZERO.into(), segment.into(), Felt252::ZERO.into()],
+        );
+
+        assert!(matches!(
+            kakarot_serde.serialize_implicit_args("main.ImplicitArgs", args),
+            Err(KakarotSerdeError::MissingBuiltin(BuiltinName::range_check))
+        ));
+    }
+}
@@ -0,0 +1,221 @@
+//! Struct layouts precomputed from the program identifiers.
+//!
+//! Resolving a struct through [`KakarotSerde::get_identifier`] scans every identifier of the
+//! program and clones its members, which dominates the cost of serializing the thousands of
+//! structs of a block. The [`ProgramLayoutCache`] is built once per serializer and holds the
+//! members of every struct, ordered by offset with their parsed [`CairoType`], shared behind an
+//! [`Arc`].
+//!
+//! [`KakarotSerde::get_identifier`]: super::KakarotSerde::get_identifier
+
+use super::{CairoType, KakarotSerdeError, MemberType, ScopedName};
+use cairo_vm::types::program::Program;
+use std::{collections::HashMap, sync::Arc};
+
+/// A struct member, as cached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedMember {
+    /// The name of the member.
+    pub name: String,
+    /// The offset of the member from the start of the struct.
+    pub offset: usize,
+    /// The Cairo type of the member, as written in the identifiers.
+    pub cairo_type: String,
+    /// The parsed Cairo type of the member.
+    pub typ: CairoType,
+    /// Whether the member is a pointer.
+    pub pointer: bool,
+}
+
+/// A struct, as cached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedStruct {
+    /// The full name of the struct.
+    pub name: String,
+    /// The members of the struct, ordered by offset.
+    pub members: Vec<CachedMember>,
+}
+
+impl CachedStruct {
+    /// Returns the member with the given name.
+    pub fn member(&self, name: &str) -> Option<&CachedMember> {
+        self.members.iter().find(|member| member.name == name)
+    }
+
+    /// Returns the number of cells spanned by the first cell of each member, i.e. the cells to
+    /// fetch to read all the members.
+    pub fn span(&self) -> usize {
+        self.members.last().map_or(0, |member| member.offset + 1)
+    }
+}
+
+/// The layouts of the structs of a program, by full name.
+#[derive(Debug, Clone, Default)]
+pub struct ProgramLayoutCache {
+    /// The structs, by full name.
+    structs: HashMap<String, Arc<CachedStruct>>,
+    /// The full names of the structs, by last scope segment.
+    by_last_segment: HashMap<String, Vec<String>>,
+}
+
+impl ProgramLayoutCache {
+    /// Builds the cache of all the structs of a program.
+    pub fn new(program: &Program) -> Self {
+        let mut cache = Self::default();
+        for (name, identifier) in program.iter_identifiers() {
+            if identifier.type_.as_deref() != Some("struct") {
+                continue;
+            }
+
+            let mut members = identifier
+                .members
+                .iter()
+                .flatten()
+                .map(|(member_name, member)| CachedMember {
+                    name: member_name.clone(),
+                    offset: member.offset,
+                    cairo_type: member.cairo_type.clone(),
+                    typ: CairoType::parse(&member.cairo_type),
+                    pointer: matches!(
+                        MemberType::parse(&member.cairo_type),
+                        MemberType::Pointer(_)
+                    ),
+                })
+                .collect::<Vec<_>>();
+            members.sort_by_key(|member| member.offset);
+
+            let full_name = identifier.full_name.clone().unwrap_or_else(|| name.to_string());
+            cache
+                .by_last_segment
+                .entry(last_segment(name).to_string())
+                .or_default()
+                .push(name.to_string());
+            cache
+                .structs
+                .insert(name.to_string(), Arc::new(CachedStruct { name: full_name, members }));
+        }
+        cache
+    }
+
+    /// Returns the struct with the given full name.
+    pub fn get(&self, full_name: &str) -> Option<&Arc<CachedStruct>> {
+        self.structs.get(full_name)
+    }
+
+    /// Resolves a struct by name.
+    ///
+    /// Full names are looked up directly. Other names are matched as in
+    /// [`KakarotSerde::get_identifier`]: the struct must be unique among the structs whose full
+    /// name contains the name and ends with the same segment.
+    ///
+    /// [`KakarotSerde::get_identifier`]: super::KakarotSerde::get_identifier
+    pub fn resolve(&self, name: &str) -> Result<Arc<CachedStruct>, KakarotSerdeError> {
+        if let Some(cached) = self.structs.get(name) {
+            return Ok(cached.clone());
+        }
+
+        let candidates = self
+            .by_last_segment
+            .get(last_segment(name))
+            .into_iter()
+            .flatten()
+            .filter(|full_name| full_name.contains(name))
+            .collect::<Vec<_>>();
+        match candidates.as_slice() {
+            [] => Err(KakarotSerdeError::IdentifierNotFound {
+                struct_name: name.to_string(),
+                expected_type: Some("struct".to_string()),
+            }),
+            [full_name] => Ok(self.structs[*full_name].clone()),
+            candidates => Err(KakarotSerdeError::MultipleIdentifiersFound {
+                struct_name: name.to_string(),
+                expected_type: Some("struct".to_string()),
+                count: candidates.len(),
+            }),
+        }
+    }
+
+    /// Returns the number of memory cells of a value of the given [`CairoType`].
+    pub fn type_size(&self, cairo_type: &CairoType) -> Result<usize, KakarotSerdeError> {
+        match cairo_type {
+            CairoType::Felt { .. } | CairoType::Pointer { .. } => Ok(1),
+            CairoType::Tuple { members, .. } => {
+                members.iter().try_fold(0, |size, member| Ok(size + self.type_size(&member.typ)?))
+            }
+            CairoType::Struct { scope, .. } => {
+                let layout = self.resolve(&scope.path.join(ScopedName::SEPARATOR))?;
+                layout.members.iter().try_fold(0, |size, member| {
+                    Ok(size.max(member.offset + self.type_size(&member.typ)?))
+                })
+            }
+        }
+    }
+
+    /// Returns the number of cached structs.
+    pub fn len(&self) -> usize {
+        self.structs.len()
+    }
+
+    /// Returns whether no struct is cached.
+    pub fn is_empty(&self) -> bool {
+        self.structs.is_empty()
+    }
+}
+
+/// Returns the last segment of a scoped name.
+fn last_segment(name: &str) -> &str {
+    name.rsplit(ScopedName::SEPARATOR).next().unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serde::KakarotSerde;
+
+    fn setup_kakarot_serde() -> KakarotSerde {
+        KakarotSerde::builder()
+            .program_bytes(include_bytes!("../../testdata/keccak_add_uint256.json"))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_resolve_struct() {
+        let kakarot_serde = setup_kakarot_serde();
+        let cache = ProgramLayoutCache::new(kakarot_serde.runner.get_program());
+        assert_eq!(cache.len(), 24);
+
+        let cached = cache.resolve("EcOpBuiltin").unwrap();
+        assert_eq!(cached.name, "starkware.cairo.common.cairo_builtins.EcOpBuiltin");
+        assert_eq!(
+            cached.members.iter().map(|member| member.offset).collect::<Vec<_>>(),
+            vec![0, 2, 4, 5]
+        );
+        assert_eq!(cached.span(), 6);
+        assert!(Arc::ptr_eq(&cached, cache.get(&cached.name).unwrap()));
+
+        let cached = cache.resolve("main.ImplicitArgs").unwrap();
+        assert!(cached.member("bitwise_ptr").unwrap().pointer);
+        assert!(!cached.member("range_check_ptr").unwrap().pointer);
+    }
+
+    #[test]
+    fn test_resolve_struct_errors() {
+        let kakarot_serde = setup_kakarot_serde();
+        let cache = ProgramLayoutCache::new(kakarot_serde.runner.get_program());
+
+        assert!(matches!(
+            cache.resolve("Missing"),
+            Err(KakarotSerdeError::IdentifierNotFound { .. })
+        ));
+        assert!(matches!(
+            cache.resolve("ImplicitArgs"),
+            Err(KakarotSerdeError::MultipleIdentifiersFound { .. })
+        ));
+        // Partial names match whole segments only at the end of the name.
+        assert!(matches!(
+            cache.resolve("Uint25"),
+            Err(KakarotSerdeError::IdentifierNotFound { .. })
+        ));
+    }
+}
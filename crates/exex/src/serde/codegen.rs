@@ -0,0 +1,321 @@
+//! Generation of Rust type definitions from the identifiers of a compiled Cairo program.
+//!
+//! Each selected Cairo struct becomes a Rust struct with one field per member, along with a
+//! [`CairoSerde`](super::CairoSerde) implementation reading it from the VM memory. Members are
+//! mapped as follows:
+//! - `felt` members are read as [`Felt252`](cairo_vm::Felt252)s.
+//! - Pointer members are read as `Option<Relocatable>`, `None` being the null pointer.
+//! - Struct members are read as the generated type when the struct is selected too.
+//! - Any other member is read as an array of `MaybeRelocatable` of the size of the member.
+
+use super::{
+    cache::{CachedMember, CachedStruct, ProgramLayoutCache},
+    CairoType, KakarotSerdeError, MemberType, ScopedName,
+};
+use cairo_vm::types::{errors::program_errors::ProgramError, program::Program};
+use std::{collections::HashMap, fmt::Write, path::Path, sync::Arc};
+use thiserror::Error;
+
+/// The Rust keywords which can't be used as raw field names.
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+    "mut", "pub", "ref", "return", "static", "struct", "trait", "true", "type", "unsafe", "use",
+    "where", "while", "yield",
+];
+
+/// Errors that can occur while generating the Rust type definitions.
+#[derive(Debug, Error)]
+pub enum CodegenError {
+    /// Error variant indicating that the program could not be loaded.
+    #[error(transparent)]
+    Program(#[from] ProgramError),
+
+    /// Error variant indicating that a member type refers to an unknown struct.
+    #[error("Unknown struct '{0}'")]
+    UnknownStruct(String),
+
+    /// Error variant indicating that the size of a member type could not be resolved.
+    #[error(transparent)]
+    Layout(#[from] KakarotSerdeError),
+}
+
+/// The options of the code generation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodegenOptions {
+    /// The scopes of the structs to generate, e.g. `src.model.model`. All the structs are
+    /// generated when empty.
+    pub scopes: Vec<String>,
+    /// The path of the [`serde`](super) module in the generated code.
+    pub serde_path: String,
+}
+
+impl Default for CodegenOptions {
+    fn default() -> Self {
+        Self { scopes: Vec::new(), serde_path: "kakarot_exex::serde".to_string() }
+    }
+}
+
+/// Generates the Rust type definitions of the structs of the compiled program at `path`.
+pub fn generate_from_file(
+    path: impl AsRef<Path>,
+    options: &CodegenOptions,
+) -> Result<String, CodegenError> {
+    generate(&Program::from_file(path.as_ref(), None)?, options)
+}
+
+/// Generates the Rust type definitions of the structs of the program.
+///
+/// Structs without members are skipped.
+pub fn generate(program: &Program, options: &CodegenOptions) -> Result<String, CodegenError> {
+    // Structs without members, such as the arguments of functions without arguments, are skipped.
+    let mut selected = program
+        .iter_identifiers()
+        .filter(|(_, identifier)| identifier.type_.as_deref() == Some("struct"))
+        .filter(|(_, identifier)| identifier.members.as_ref().is_some_and(|m| !m.is_empty()))
+        .map(|(name, _)| name)
+        .filter(|name| {
+            options.scopes.is_empty() ||
+                options.scopes.iter().any(|scope| {
+                    name.strip_prefix(scope.as_str())
+                        .is_some_and(|rest| rest.starts_with(ScopedName::SEPARATOR))
+                })
+        })
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    selected.sort();
+
+    let generator =
+        Generator { rust_names: rust_names(&selected), layouts: ProgramLayoutCache::new(program) };
+
+    let mut out = String::new();
+    writeln!(out, "//! Generated from the program identifiers, do not edit.").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "#[allow(unused_imports)]").unwrap();
+    writeln!(
+        out,
+        "use cairo_vm::{{types::relocatable::{{MaybeRelocatable, Relocatable}}, Felt252}};"
+    )
+    .unwrap();
+    writeln!(out, "use {}::{{CairoSerde, KakarotSerde, KakarotSerdeError}};", options.serde_path)
+        .unwrap();
+
+    for name in &selected {
+        writeln!(out).unwrap();
+        generator.write_struct(&mut out, name)?;
+    }
+
+    Ok(out)
+}
+
+/// The Rust type of a struct member.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FieldType {
+    /// A felt.
+    Felt,
+    /// A pointer.
+    Pointer,
+    /// A generated struct, with its Rust name.
+    Struct(String),
+    /// An array of cells of the given size.
+    Cells(usize),
+}
+
+/// The state of the code generation.
+#[derive(Debug)]
+struct Generator {
+    /// The Rust names of the generated structs, by full name.
+    rust_names: HashMap<String, String>,
+    /// The layouts of all the structs of the program.
+    layouts: ProgramLayoutCache,
+}
+
+impl Generator {
+    /// Writes the definition and the [`CairoSerde`](super::CairoSerde) implementation of a struct.
+    fn write_struct(&self, out: &mut String, name: &str) -> Result<(), CodegenError> {
+        let rust_name = &self.rust_names[name];
+        let fields = self
+            .layout(name)?
+            .members
+            .iter()
+            .map(|member| {
+                Ok((
+                    field_name(&member.name),
+                    member.offset,
+                    member.cairo_type.clone(),
+                    self.field_type(member)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, CodegenError>>()?;
+
+        writeln!(out, "/// `{name}`.").unwrap();
+        writeln!(out, "#[derive(Debug, Clone, PartialEq, Eq)]").unwrap();
+        writeln!(out, "pub struct {rust_name} {{").unwrap();
+        for (field, offset, cairo_type, field_type) in &fields {
+            let rust_type = match field_type {
+                FieldType::Felt => "Felt252".to_string(),
+                FieldType::Pointer => "Option<Relocatable>".to_string(),
+                FieldType::Struct(rust_name) => rust_name.clone(),
+                FieldType::Cells(size) => format!("[MaybeRelocatable; {size}]"),
+            };
+            writeln!(out, "    /// `{cairo_type}` at offset {offset}.").unwrap();
+            writeln!(out, "    pub {field}: {rust_type},").unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+        writeln!(out).unwrap();
+
+        writeln!(out, "impl CairoSerde for {rust_name} {{").unwrap();
+        writeln!(out, "    const STRUCT_NAME: &'static str = \"{name}\";").unwrap();
+        let size = self.layouts.type_size(&CairoType::struct_type(name, None))?;
+        writeln!(out, "    const SIZE: usize = {size};").unwrap();
+        writeln!(out).unwrap();
+        writeln!(out, "    fn from_cairo(").unwrap();
+        writeln!(out, "        serde: &KakarotSerde,").unwrap();
+        writeln!(out, "        ptr: Relocatable,").unwrap();
+        writeln!(out, "    ) -> Result<Self, KakarotSerdeError> {{").unwrap();
+        writeln!(out, "        Ok(Self {{").unwrap();
+        for (field, offset, _, field_type) in &fields {
+            let read = match field_type {
+                FieldType::Felt => format!("serde.read_felt((ptr + {offset}usize)?)?"),
+                FieldType::Pointer => format!("serde.read_pointer((ptr + {offset}usize)?)?"),
+                FieldType::Struct(rust_name) => {
+                    format!("{rust_name}::from_cairo(serde, (ptr + {offset}usize)?)?")
+                }
+                FieldType::Cells(size) => {
+                    format!("serde.read_cells::<{size}>((ptr + {offset}usize)?)?")
+                }
+            };
+            writeln!(out, "            {field}: {read},").unwrap();
+        }
+        writeln!(out, "        }})").unwrap();
+        writeln!(out, "    }}").unwrap();
+        writeln!(out, "}}").unwrap();
+
+        Ok(())
+    }
+
+    /// Returns the Rust type of a struct member.
+    fn field_type(&self, member: &CachedMember) -> Result<FieldType, CodegenError> {
+        Ok(match MemberType::parse(&member.cairo_type) {
+            MemberType::Felt => FieldType::Felt,
+            MemberType::Pointer(_) => FieldType::Pointer,
+            MemberType::Struct(name) if self.rust_names.contains_key(name) => {
+                FieldType::Struct(self.rust_names[name].clone())
+            }
+            _ => FieldType::Cells(self.layouts.type_size(&member.typ)?),
+        })
+    }
+
+    /// Returns the layout of a struct, its members being ordered by offset.
+    fn layout(&self, name: &str) -> Result<Arc<CachedStruct>, CodegenError> {
+        self.layouts.get(name).cloned().ok_or_else(|| CodegenError::UnknownStruct(name.to_string()))
+    }
+}
+
+/// Returns the Rust names of the given structs.
+///
+/// Structs are named after the last segment of their full name, the structs sharing the same last
+/// segment being named after their whole full name.
+fn rust_names(names: &[String]) -> HashMap<String, String> {
+    let mut counts = HashMap::<&str, usize>::new();
+    for name in names {
+        *counts.entry(last_segment(name)).or_default() += 1;
+    }
+
+    names
+        .iter()
+        .map(|name| {
+            let rust_name = if counts[last_segment(name)] == 1 {
+                upper_camel_case(last_segment(name))
+            } else {
+                name.split(ScopedName::SEPARATOR).map(upper_camel_case).collect()
+            };
+            (name.clone(), rust_name)
+        })
+        .collect()
+}
+
+/// Returns the last segment of a full name.
+fn last_segment(name: &str) -> &str {
+    name.rsplit(ScopedName::SEPARATOR).next().unwrap_or(name)
+}
+
+/// Converts a name segment to `UpperCamelCase`, e.g. `__main__` to `Main`.
+fn upper_camel_case(segment: &str) -> String {
+    segment
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Returns the Rust field name of a member, escaping keywords.
+fn field_name(member: &str) -> String {
+    if RUST_KEYWORDS.contains(&member) {
+        format!("r#{member}")
+    } else {
+        member.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program() -> Program {
+        let program_content = include_bytes!("../../testdata/keccak_add_uint256.json");
+        Program::from_bytes(program_content, Some("main")).unwrap()
+    }
+
+    fn options(scopes: &[&str]) -> CodegenOptions {
+        CodegenOptions {
+            scopes: scopes.iter().map(ToString::to_string).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_generate_struct() {
+        let code = generate(&program(), &options(&["starkware.cairo.common.uint256"])).unwrap();
+
+        // The argument structs of the functions of the scope are generated too, those sharing the
+        // same name being named after their full name.
+        assert_eq!(code, include_str!("../../testdata/codegen/uint256.rs"));
+    }
+
+    #[test]
+    fn test_generate_nested_and_foreign_structs() {
+        let code =
+            generate(&program(), &options(&["starkware.cairo.common.cairo_builtins"])).unwrap();
+
+        // `UInt384` is generated and read as such, `KeccakBuiltinState` and `EcPoint` are not and
+        // are read as cells.
+        assert_eq!(code, include_str!("../../testdata/codegen/cairo_builtins.rs"));
+    }
+
+    #[test]
+    fn test_generate_disambiguates_names() {
+        let code = generate(&program(), &CodegenOptions::default()).unwrap();
+
+        assert!(code.contains("pub struct MainMainImplicitArgs {"));
+        assert!(code.contains("pub struct StarkwareCairoCommonAllocAllocImplicitArgs {"));
+        assert!(code.contains("pub struct BitwiseBuiltin {"));
+        // Structs without members are skipped.
+        assert!(!code.contains("pub struct MainMainArgs {"));
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(upper_camel_case("__main__"), "Main");
+        assert_eq!(upper_camel_case("keccak_add_uint256"), "KeccakAddUint256");
+        assert_eq!(upper_camel_case("Uint256"), "Uint256");
+        assert_eq!(field_name("type"), "r#type");
+        assert_eq!(field_name("nonce"), "nonce");
+    }
+}
@@ -0,0 +1,298 @@
+//! Diffing of the Cairo VM memory between two runs.
+//!
+//! Two runs of the same block are expected to write the exact same memory. A [`MemoryDiff`]
+//! lists the cells that differ, which can be annotated with the [`SymbolIndex`] of either run,
+//! and [`SerializedValue::diff`] compares typed values, e.g. two `model.State`s, member by member.
+
+use super::{symbols::SymbolIndex, KakarotSerdeError, SerializedValue};
+use cairo_vm::{
+    types::relocatable::{MaybeRelocatable, Relocatable},
+    vm::runners::cairo_runner::CairoRunner,
+};
+use std::collections::BTreeSet;
+
+/// The change of a memory cell between two runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CellChange {
+    /// The cell is only written in the first run.
+    Removed(MaybeRelocatable),
+    /// The cell is only written in the second run.
+    Added(MaybeRelocatable),
+    /// The cell is written with different values.
+    Changed {
+        /// The value of the first run.
+        left: MaybeRelocatable,
+        /// The value of the second run.
+        right: MaybeRelocatable,
+    },
+}
+
+/// A memory cell differing between two runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CellDiff {
+    /// The address of the cell.
+    pub address: Relocatable,
+    /// The change of the cell.
+    pub change: CellChange,
+    /// The symbol containing the cell, e.g. `model.Account.nonce`, when annotated.
+    pub symbol: Option<String>,
+}
+
+/// The memory cells differing between two runs, ordered by address.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryDiff {
+    /// The differing cells.
+    pub cells: Vec<CellDiff>,
+}
+
+impl MemoryDiff {
+    /// Returns whether both memories are identical.
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Returns the indexes of the segments holding differing cells.
+    pub fn segments(&self) -> BTreeSet<isize> {
+        self.cells.iter().map(|cell| cell.address.segment_index).collect()
+    }
+
+    /// Annotates the differing cells with the symbols of the index.
+    pub fn annotate(&mut self, index: &SymbolIndex) {
+        for cell in &mut self.cells {
+            cell.symbol = index.lookup(cell.address).map(|location| location.to_string());
+        }
+    }
+}
+
+/// Diffs the memory of two runs.
+///
+/// The runs must have ended, so that the used sizes of their segments are computed.
+pub fn diff_memory(
+    runner_a: &CairoRunner,
+    runner_b: &CairoRunner,
+) -> Result<MemoryDiff, KakarotSerdeError> {
+    let segments = runner_a.vm.segments.num_segments().max(runner_b.vm.segments.num_segments());
+
+    let mut cells = Vec::new();
+    for index in 0..segments {
+        let size = used_size(runner_a, index)?.max(used_size(runner_b, index)?);
+        for offset in 0..size {
+            let address = Relocatable::from((index as isize, offset));
+            let change = match (runner_a.vm.get_maybe(&address), runner_b.vm.get_maybe(&address)) {
+                (Some(left), Some(right)) if left != right => CellChange::Changed { left, right },
+                (Some(left), None) => CellChange::Removed(left),
+                (None, Some(right)) => CellChange::Added(right),
+                _ => continue,
+            };
+            cells.push(CellDiff { address, change, symbol: None });
+        }
+    }
+
+    Ok(MemoryDiff { cells })
+}
+
+/// Returns the used size of a segment, `0` when the runner does not have this segment.
+fn used_size(runner: &CairoRunner, index: usize) -> Result<usize, KakarotSerdeError> {
+    if index >= runner.vm.segments.num_segments() {
+        return Ok(0);
+    }
+    runner
+        .vm
+        .segments
+        .get_segment_used_size(index)
+        .ok_or(KakarotSerdeError::SegmentSizesNotComputed { segment_index: index })
+}
+
+/// A value differing between two serialized values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueDiff {
+    /// The path of the value, e.g. `accounts.nonce`, empty for the root value.
+    pub path: String,
+    /// The value on the left side, `None` if absent.
+    pub left: Option<SerializedValue>,
+    /// The value on the right side, `None` if absent.
+    pub right: Option<SerializedValue>,
+}
+
+impl SerializedValue {
+    /// Diffs two serialized values member by member.
+    ///
+    /// Pointers are compared through their pointees when serialized, so that values allocated
+    /// at different addresses compare equal.
+    pub fn diff(&self, other: &Self) -> Vec<ValueDiff> {
+        let mut diffs = Vec::new();
+        diff_values(String::new(), Some(self), Some(other), &mut diffs);
+        diffs
+    }
+}
+
+/// Appends the differences between two values to `diffs`.
+fn diff_values(
+    path: String,
+    left: Option<&SerializedValue>,
+    right: Option<&SerializedValue>,
+    diffs: &mut Vec<ValueDiff>,
+) {
+    match (left, right) {
+        (
+            Some(SerializedValue::Pointer { pointee: Some(left), .. }),
+            Some(SerializedValue::Pointer { pointee: Some(right), .. }),
+        ) => diff_values(path, Some(left), Some(right), diffs),
+        (
+            Some(SerializedValue::Struct { name: left_name, members: left }),
+            Some(SerializedValue::Struct { name: right_name, members: right }),
+        ) if left_name == right_name => {
+            let names: BTreeSet<_> = left.keys().chain(right.keys()).collect();
+            for name in names {
+                diff_values(child_path(&path, name), left.get(name), right.get(name), diffs);
+            }
+        }
+        (Some(SerializedValue::Tuple(left)), Some(SerializedValue::Tuple(right)))
+            if left.len() == right.len() =>
+        {
+            for (index, ((name, left), (_, right))) in left.iter().zip(right).enumerate() {
+                let name = name.clone().unwrap_or_else(|| index.to_string());
+                diff_values(child_path(&path, &name), Some(left), Some(right), diffs);
+            }
+        }
+        (left, right) if left != right => {
+            diffs.push(ValueDiff { path, left: left.cloned(), right: right.cloned() })
+        }
+        _ => {}
+    }
+}
+
+/// Returns the path of a member of the value at `path`.
+fn child_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{path}.{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serde::KakarotSerde;
+    use cairo_vm::{
+        types::{layout_name::LayoutName, program::Program},
+        Felt252,
+    };
+    use std::collections::BTreeMap;
+
+    fn setup_runner(values: &[u64]) -> CairoRunner {
+        let program_content = include_bytes!("../../testdata/keccak_add_uint256.json");
+        let program = Program::from_bytes(program_content, Some("main")).unwrap();
+        let mut runner = CairoRunner::new(&program, LayoutName::plain, false, false).unwrap();
+        let base = runner.vm.add_memory_segment();
+        let values: Vec<MaybeRelocatable> =
+            values.iter().map(|value| Felt252::from(*value).into()).collect();
+        runner.vm.load_data(base, &values).unwrap();
+        runner.vm.segments.compute_effective_sizes();
+        runner
+    }
+
+    #[test]
+    fn test_diff_memory() {
+        let runner_a = setup_runner(&[1, 2, 3]);
+        let runner_b = setup_runner(&[1, 5]);
+
+        let diff = diff_memory(&runner_a, &runner_b).unwrap();
+
+        assert_eq!(
+            diff.cells,
+            vec![
+                CellDiff {
+                    address: Relocatable::from((0, 1)),
+                    change: CellChange::Changed {
+                        left: Felt252::from(2).into(),
+                        right: Felt252::from(5).into()
+                    },
+                    symbol: None,
+                },
+                CellDiff {
+                    address: Relocatable::from((0, 2)),
+                    change: CellChange::Removed(Felt252::from(3).into()),
+                    symbol: None,
+                },
+            ]
+        );
+        assert_eq!(diff.segments(), BTreeSet::from([0]));
+        assert!(diff_memory(&runner_a, &runner_a).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_diff_memory_annotated() {
+        let runner_a = setup_runner(&[1, 2]);
+        let runner_b = setup_runner(&[1, 3]);
+
+        let mut diff = diff_memory(&runner_a, &runner_b).unwrap();
+        let kakarot_serde = KakarotSerde::new(runner_a);
+        let index = kakarot_serde.symbol_index(&[(Relocatable::from((0, 0)), "Uint256")]).unwrap();
+        diff.annotate(&index);
+
+        assert_eq!(
+            diff.cells[0].symbol.as_deref(),
+            Some("starkware.cairo.common.uint256.Uint256.high")
+        );
+    }
+
+    #[test]
+    fn test_diff_memory_sizes_not_computed() {
+        let program_content = include_bytes!("../../testdata/keccak_add_uint256.json");
+        let program = Program::from_bytes(program_content, Some("main")).unwrap();
+        let mut runner = CairoRunner::new(&program, LayoutName::plain, false, false).unwrap();
+        runner.vm.add_memory_segment();
+
+        let result = diff_memory(&runner, &runner);
+
+        assert!(matches!(
+            result,
+            Err(KakarotSerdeError::SegmentSizesNotComputed { segment_index: 0 })
+        ));
+    }
+
+    #[test]
+    fn test_serialized_value_diff() {
+        let uint256 = |low: u64, address: (isize, usize)| SerializedValue::Pointer {
+            address: Relocatable::from(address),
+            pointee: Some(Box::new(SerializedValue::Struct {
+                name: "Uint256".to_string(),
+                members: BTreeMap::from([
+                    ("low".to_string(), SerializedValue::Felt(Felt252::from(low).into())),
+                    ("high".to_string(), SerializedValue::Felt(Felt252::ZERO.into())),
+                ]),
+            })),
+        };
+        let account = |balance, nonce: Option<u64>| {
+            let mut members = BTreeMap::from([("balance".to_string(), balance)]);
+            if let Some(nonce) = nonce {
+                let nonce = SerializedValue::Felt(Felt252::from(nonce).into());
+                members.insert("nonce".to_string(), nonce);
+            }
+            SerializedValue::Struct { name: "model.Account".to_string(), members }
+        };
+
+        // Pointees allocated at different addresses compare equal.
+        let left = account(uint256(1, (1, 0)), Some(1));
+        assert!(left.diff(&account(uint256(1, (2, 0)), Some(1))).is_empty());
+
+        let right = account(uint256(2, (2, 0)), None);
+        assert_eq!(
+            left.diff(&right),
+            vec![
+                ValueDiff {
+                    path: "balance.low".to_string(),
+                    left: Some(SerializedValue::Felt(Felt252::from(1).into())),
+                    right: Some(SerializedValue::Felt(Felt252::from(2).into())),
+                },
+                ValueDiff {
+                    path: "nonce".to_string(),
+                    left: Some(SerializedValue::Felt(Felt252::from(1).into())),
+                    right: None,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,343 @@
+//! Typed, annotated dumps of the Cairo VM memory.
+//!
+//! A [`MemoryDump`] holds every written cell of the VM memory, each cell being annotated with the
+//! struct member it belongs to when it is reachable from one of the typed roots given to
+//! [`KakarotSerde::dump_memory`]. Cells of the program segment are annotated with the source
+//! location of their instruction when the program was compiled with debug info.
+//!
+//! Dumps are stored as zstd compressed JSON, so that they can be inspected offline, with the
+//! dictionary of the dumps of the [`Codec`] when one was trained.
+
+use super::{KakarotSerde, KakarotSerdeError, MemberType};
+use crate::compression::{ArtifactKind, Codec};
+use cairo_vm::{
+    types::relocatable::{MaybeRelocatable, Relocatable},
+    vm::vm_core::VirtualMachine,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+/// The version of the dump format, bumped on breaking changes.
+pub const MEMORY_DUMP_VERSION: u32 = 1;
+
+/// The annotation of a memory cell.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CellAnnotation {
+    /// The path of the cell, e.g. `model.Account.balance.low`, or the source location of the
+    /// instruction for the cells of the program segment.
+    pub path: String,
+    /// The Cairo type of the cell, e.g. `felt` or `model.Account*`.
+    pub cairo_type: String,
+}
+
+/// A written memory cell.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CellDump {
+    /// The offset of the cell in its segment.
+    pub offset: usize,
+    /// The value of the cell.
+    pub value: MaybeRelocatable,
+    /// The annotation of the cell, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotation: Option<CellAnnotation>,
+}
+
+/// The written cells of a memory segment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SegmentDump {
+    /// The index of the segment.
+    pub index: isize,
+    /// The size of the segment, including the holes.
+    pub size: usize,
+    /// The written cells of the segment, ordered by offset.
+    pub cells: Vec<CellDump>,
+}
+
+/// A typed, annotated dump of the Cairo VM memory.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryDump {
+    /// The version of the dump format.
+    pub version: u32,
+    /// The dumped segments, ordered by index.
+    pub segments: Vec<SegmentDump>,
+}
+
+impl MemoryDump {
+    /// Returns the dumped cell at the given address, `None` if it was not written.
+    pub fn cell(&self, address: Relocatable) -> Option<&CellDump> {
+        let segment =
+            self.segments.iter().find(|segment| segment.index == address.segment_index)?;
+        segment
+            .cells
+            .binary_search_by_key(&address.offset, |cell| cell.offset)
+            .ok()
+            .map(|index| &segment.cells[index])
+    }
+
+    /// Returns an iterator over the annotated cells and their address.
+    pub fn annotated_cells(&self) -> impl Iterator<Item = (Relocatable, &CellAnnotation)> {
+        self.segments.iter().flat_map(|segment| {
+            segment.cells.iter().filter_map(|cell| {
+                let address = Relocatable::from((segment.index, cell.offset));
+                cell.annotation.as_ref().map(|annotation| (address, annotation))
+            })
+        })
+    }
+
+    /// Dumps all the written cells of the VM memory, without annotation.
+    pub fn capture(vm: &mut VirtualMachine) -> Self {
+        let sizes = vm.segments.compute_effective_sizes().clone();
+        let segments = sizes
+            .into_iter()
+            .enumerate()
+            .map(|(index, size)| {
+                let index = index as isize;
+                let cells = (0..size)
+                    .filter_map(|offset| {
+                        let value = vm.get_maybe(&Relocatable::from((index, offset)))?;
+                        Some(CellDump { offset, value, annotation: None })
+                    })
+                    .collect();
+                SegmentDump { index, size, cells }
+            })
+            .collect();
+
+        Self { version: MEMORY_DUMP_VERSION, segments }
+    }
+
+    /// Writes the compressed dump.
+    pub fn write_compressed<W: Write>(&self, writer: W, codec: &Codec) -> io::Result<()> {
+        let mut encoder = codec.encoder(ArtifactKind::Dump, writer)?;
+        serde_json::to_writer(&mut encoder, self)?;
+        encoder.finish()?.flush()
+    }
+
+    /// Reads a compressed dump, decompressing it on the fly.
+    pub fn read_compressed<R: BufRead>(reader: R, codec: &Codec) -> io::Result<Self> {
+        let dump: Self = serde_json::from_reader(codec.decoder(ArtifactKind::Dump, reader)?)?;
+        if dump.version != MEMORY_DUMP_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported memory dump version {}", dump.version),
+            ));
+        }
+        Ok(dump)
+    }
+
+    /// Saves the compressed dump to a file.
+    pub fn save(&self, path: impl AsRef<Path>, codec: &Codec) -> io::Result<()> {
+        self.write_compressed(BufWriter::new(File::create(path)?), codec)
+    }
+
+    /// Loads a compressed dump from a file.
+    pub fn load(path: impl AsRef<Path>, codec: &Codec) -> io::Result<Self> {
+        Self::read_compressed(BufReader::new(File::open(path)?), codec)
+    }
+}
+
+impl KakarotSerde {
+    /// Dumps all the written cells of the VM memory.
+    ///
+    /// Each root is the address of a value of the given Cairo type, e.g. `model.State*` or
+    /// `Uint256`. The cells of the value are annotated with the struct member they belong to, and
+    /// pointers to structs are followed so that all the cells reachable from the roots are
+    /// annotated.
+    pub fn dump_memory(
+        &mut self,
+        roots: &[(Relocatable, &str)],
+    ) -> Result<MemoryDump, KakarotSerdeError> {
+        let mut annotations = HashMap::new();
+        let mut visited = HashSet::new();
+        for (address, cairo_type) in roots {
+            self.annotate(*address, cairo_type, None, &mut annotations, &mut visited)?;
+        }
+        self.annotate_code(&mut annotations);
+
+        let mut dump = MemoryDump::capture(&mut self.runner.vm);
+        for segment in &mut dump.segments {
+            for cell in &mut segment.cells {
+                let address = Relocatable::from((segment.index, cell.offset));
+                cell.annotation = annotations.remove(&address);
+            }
+        }
+        Ok(dump)
+    }
+
+    /// Annotates the cells of the value of type `cairo_type` at `address`.
+    ///
+    /// The path of inline members extends the path of their parent, while the path of the values
+    /// behind a pointer starts over from the name of their struct.
+    fn annotate(
+        &self,
+        address: Relocatable,
+        cairo_type: &str,
+        path: Option<String>,
+        annotations: &mut HashMap<Relocatable, CellAnnotation>,
+        visited: &mut HashSet<(Relocatable, String)>,
+    ) -> Result<(), KakarotSerdeError> {
+        // Shared and cyclic values are only annotated once.
+        if !visited.insert((address, cairo_type.to_string())) {
+            return Ok(());
+        }
+
+        match MemberType::parse(cairo_type) {
+            MemberType::Struct(name) => {
+                let layout = self.struct_members(name)?;
+                let path = path.unwrap_or_else(|| layout.name.clone());
+                for member in &layout.members {
+                    self.annotate(
+                        (address + member.offset)?,
+                        &member.cairo_type,
+                        Some(format!("{path}.{}", member.name)),
+                        annotations,
+                        visited,
+                    )?;
+                }
+            }
+            member_type => {
+                annotations.entry(address).or_insert_with(|| CellAnnotation {
+                    path: path.unwrap_or_else(|| cairo_type.to_string()),
+                    cairo_type: cairo_type.to_string(),
+                });
+
+                // Only pointers to structs are followed, as the length of felt arrays is unknown.
+                let MemberType::Pointer(pointee) = member_type else { return Ok(()) };
+                if let (MemberType::Struct(_), Some(MaybeRelocatable::RelocatableValue(target))) =
+                    (MemberType::parse(pointee), self.get_maybe(address, true))
+                {
+                    self.annotate(target, pointee, None, annotations, visited)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Annotates the cells of the program segment with the source location of their instruction.
+    fn annotate_code(&self, annotations: &mut HashMap<Relocatable, CellAnnotation>) {
+        let Some(program_base) = self.runner.get_program_base() else { return };
+        let Some(locations) = self.runner.get_program().get_relocated_instruction_locations(&[0])
+        else {
+            return;
+        };
+
+        for (pc, location) in locations {
+            let Ok(address) = program_base + pc else { continue };
+            let location = location.inst;
+            annotations.entry(address).or_insert_with(|| CellAnnotation {
+                path: format!(
+                    "{}:{}:{}",
+                    location.input_file.filename, location.start_line, location.start_col
+                ),
+                cairo_type: "codeoffset".to_string(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cairo_vm::Felt252;
+
+    fn setup_kakarot_serde() -> KakarotSerde {
+        KakarotSerde::builder()
+            .program_bytes(include_bytes!("../../testdata/keccak_add_uint256.json"))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_dump_memory_annotates_struct_members() {
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        // Write a `Uint256` in a new segment.
+        let base = kakarot_serde.runner.vm.add_memory_segment();
+        kakarot_serde
+            .runner
+            .vm
+            .load_data(base, &[Felt252::from(1).into(), Felt252::from(2).into()])
+            .unwrap();
+
+        let dump = kakarot_serde.dump_memory(&[(base, "Uint256")]).unwrap();
+
+        assert_eq!(dump.version, MEMORY_DUMP_VERSION);
+        assert_eq!(
+            dump.cell(base).unwrap().annotation,
+            Some(CellAnnotation {
+                path: "starkware.cairo.common.uint256.Uint256.low".to_string(),
+                cairo_type: "felt".to_string()
+            })
+        );
+        assert_eq!(
+            dump.cell((base + 1usize).unwrap()).unwrap().annotation.as_ref().unwrap().path,
+            "starkware.cairo.common.uint256.Uint256.high"
+        );
+        assert_eq!(dump.cell((base + 2usize).unwrap()), None);
+    }
+
+    #[test]
+    fn test_dump_memory_follows_struct_pointers() {
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        // `main.ImplicitArgs` whose `bitwise_ptr` points to a `BitwiseBuiltin`.
+        let args = kakarot_serde.runner.vm.add_memory_segment();
+        let bitwise = kakarot_serde.runner.vm.add_memory_segment();
+        kakarot_serde
+            .runner
+            .vm
+            .load_data(args, &[Felt252::ZERO.into(), Felt252::from(7).into(), bitwise.into()])
+            .unwrap();
+        kakarot_serde.runner.vm.load_data(bitwise, &[Felt252::from(3).into()]).unwrap();
+
+        let dump = kakarot_serde.dump_memory(&[(args, "main.ImplicitArgs")]).unwrap();
+
+        let annotation = dump.cell((args + 2usize).unwrap()).unwrap().annotation.clone().unwrap();
+        assert_eq!(annotation.path, "__main__.main.ImplicitArgs.bitwise_ptr");
+        assert_eq!(annotation.cairo_type, "starkware.cairo.common.cairo_builtins.BitwiseBuiltin*");
+        assert_eq!(
+            dump.cell(bitwise).unwrap().annotation.as_ref().unwrap().path,
+            "starkware.cairo.common.cairo_builtins.BitwiseBuiltin.x"
+        );
+        // The null `output_ptr` is annotated but not followed.
+        assert_eq!(
+            dump.cell(args).unwrap().annotation.as_ref().unwrap().path,
+            "__main__.main.ImplicitArgs.output_ptr"
+        );
+        assert_eq!(dump.annotated_cells().count(), 4);
+    }
+
+    #[test]
+    fn test_memory_dump_compressed_roundtrip() {
+        let mut kakarot_serde = setup_kakarot_serde();
+        let base = kakarot_serde.runner.vm.add_memory_segment();
+        kakarot_serde
+            .runner
+            .vm
+            .load_data(base, &[Felt252::from(1).into(), Felt252::from(2).into()])
+            .unwrap();
+        let dump = kakarot_serde.dump_memory(&[(base, "Uint256")]).unwrap();
+
+        let codec = Codec::default();
+        let mut buf = Vec::new();
+        dump.write_compressed(&mut buf, &codec).unwrap();
+
+        assert_eq!(MemoryDump::read_compressed(buf.as_slice(), &codec).unwrap(), dump);
+    }
+
+    #[test]
+    fn test_memory_dump_unsupported_version() {
+        let dump = MemoryDump { version: MEMORY_DUMP_VERSION + 1, segments: vec![] };
+        let codec = Codec::default();
+        let mut buf = Vec::new();
+        dump.write_compressed(&mut buf, &codec).unwrap();
+
+        let err = MemoryDump::read_compressed(buf.as_slice(), &codec).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
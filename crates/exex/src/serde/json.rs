@@ -0,0 +1,135 @@
+//! JSON representation of the values serialized by [`KakarotSerde::serialize_by_type`].
+//!
+//! [`SerializedValue`] implements [`Serialize`], so that the value trees of arbitrary Cairo types
+//! can be emitted by RPC endpoints and tools without a hand-rolled Rust model:
+//! - Felts are hex strings, relocatables `segment:offset` strings.
+//! - Null pointers are `null`.
+//! - Pointers are `{"address": .., "pointee": ..}` objects, felt pointees being omitted.
+//! - Tuples are arrays, or objects when all their items are named.
+//! - Structs are objects of their written members.
+//! - References are `{"reference": ..}` objects.
+//!
+//! [`KakarotSerde::serialize_by_type`]: super::KakarotSerde::serialize_by_type
+
+use super::SerializedValue;
+use cairo_vm::types::relocatable::MaybeRelocatable;
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+
+/// Returns the JSON string of a felt or a relocatable.
+fn cell_string(value: &MaybeRelocatable) -> String {
+    match value {
+        MaybeRelocatable::Int(felt) => felt.to_hex_string(),
+        MaybeRelocatable::RelocatableValue(address) => address.to_string(),
+    }
+}
+
+impl Serialize for SerializedValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Felt(value) => serializer.serialize_str(&cell_string(value)),
+            Self::Null => serializer.serialize_none(),
+            Self::Pointer { address, pointee } => {
+                let mut map = serializer.serialize_map(Some(1 + usize::from(pointee.is_some())))?;
+                map.serialize_entry("address", &address.to_string())?;
+                if let Some(pointee) = pointee {
+                    map.serialize_entry("pointee", pointee)?;
+                }
+                map.end()
+            }
+            Self::Tuple(items)
+                if items.iter().all(|(name, _)| name.is_some()) && !items.is_empty() =>
+            {
+                let mut map = serializer.serialize_map(Some(items.len()))?;
+                for (name, value) in items {
+                    map.serialize_entry(name.as_deref().unwrap_or_default(), value)?;
+                }
+                map.end()
+            }
+            Self::Tuple(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for (_, value) in items {
+                    seq.serialize_element(value)?;
+                }
+                seq.end()
+            }
+            Self::Struct { members, .. } => members.serialize(serializer),
+            Self::Reference(address) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("reference", &address.to_string())?;
+                map.end()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::serde::{CairoType, KakarotSerde};
+    use cairo_vm::{types::relocatable::MaybeRelocatable, Felt252};
+    use serde_json::json;
+
+    fn setup_kakarot_serde() -> KakarotSerde {
+        KakarotSerde::builder()
+            .program_bytes(include_bytes!("../../testdata/keccak_add_uint256.json"))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_serialize_nested_to_json() {
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        // An `EcOpBuiltin**`: a pointer to a pointer to a struct embedding `EcPoint`s by value.
+        let base = kakarot_serde.runner.vm.add_memory_segment();
+        let pointer = kakarot_serde.runner.vm.add_memory_segment();
+        let ec_op = kakarot_serde.runner.vm.add_memory_segment();
+        kakarot_serde.runner.vm.load_data(base, &[pointer.into()]).unwrap();
+        kakarot_serde.runner.vm.load_data(pointer, &[ec_op.into()]).unwrap();
+        let values: Vec<MaybeRelocatable> =
+            (1..=7).map(|value| Felt252::from(value).into()).collect();
+        kakarot_serde.runner.vm.load_data(ec_op, &values).unwrap();
+
+        let value =
+            kakarot_serde.serialize_by_type(&CairoType::parse("EcOpBuiltin**"), base).unwrap();
+        assert_eq!(
+            serde_json::to_value(&value).unwrap(),
+            json!({
+                "address": "1:0",
+                "pointee": {
+                    "address": "2:0",
+                    "pointee": {
+                        "p": {"x": "0x1", "y": "0x2"},
+                        "q": {"x": "0x3", "y": "0x4"},
+                        "m": "0x5",
+                        "r": {"x": "0x6", "y": "0x7"},
+                    },
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_serialize_tuples_to_json() {
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        let base = kakarot_serde.runner.vm.add_memory_segment();
+        let values: Vec<MaybeRelocatable> =
+            [0, 2, 3].into_iter().map(|v| Felt252::from(v).into()).collect();
+        kakarot_serde.runner.vm.load_data(base, &values).unwrap();
+
+        let value =
+            kakarot_serde.serialize_by_type(&CairoType::parse("(felt*, Uint256)"), base).unwrap();
+        assert_eq!(
+            serde_json::to_value(&value).unwrap(),
+            json!([null, {"low": "0x2", "high": "0x3"}])
+        );
+
+        let value = kakarot_serde
+            .serialize_by_type(&CairoType::parse("(a: felt, b: Uint256)"), base)
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&value).unwrap(),
+            json!({"a": "0x0", "b": {"low": "0x2", "high": "0x3"}})
+        );
+    }
+}
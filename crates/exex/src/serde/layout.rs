@@ -0,0 +1,225 @@
+//! Introspection of the memory layout of the Cairo structs.
+//!
+//! A [`StructLayout`] describes the size of a struct and the offset, size and type of each of its
+//! members, with the full names of the structs they embed or point to. It lets code generation,
+//! startup validation of the Rust models and tooling such as the memory inspector work on any
+//! struct of the program without hardcoding its layout.
+
+use super::{KakarotSerde, KakarotSerdeError, MemberType, ScopedName};
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+/// The kind of a struct member.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum MemberKind {
+    /// A felt.
+    Felt,
+    /// A pointer, with the full name of the pointed struct when the pointee is a struct.
+    Pointer {
+        /// The full name of the pointed struct.
+        pointee: Option<String>,
+    },
+    /// A struct embedded in the member cells, with its full name.
+    Struct {
+        /// The full name of the embedded struct.
+        name: String,
+    },
+    /// A tuple.
+    Tuple,
+}
+
+/// The layout of a struct member.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MemberLayout {
+    /// The name of the member.
+    pub name: String,
+    /// The offset of the member from the start of the struct.
+    pub offset: usize,
+    /// The number of cells of the member.
+    pub size: usize,
+    /// The Cairo type of the member, e.g. `felt*`.
+    pub cairo_type: String,
+    /// The kind of the member.
+    pub kind: MemberKind,
+}
+
+/// The layout of a struct.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StructLayout {
+    /// The full name of the struct.
+    pub name: String,
+    /// The number of cells of the struct.
+    pub size: usize,
+    /// The members of the struct, ordered by offset.
+    pub members: Vec<MemberLayout>,
+}
+
+impl StructLayout {
+    /// Returns the layout of the member with the given name.
+    pub fn member(&self, name: &str) -> Option<&MemberLayout> {
+        self.members.iter().find(|member| member.name == name)
+    }
+
+    /// Returns the full names of the structs embedded in or pointed to by the members.
+    pub fn references(&self) -> BTreeSet<&str> {
+        self.members
+            .iter()
+            .filter_map(|member| match &member.kind {
+                MemberKind::Pointer { pointee } => pointee.as_deref(),
+                MemberKind::Struct { name } => Some(name.as_str()),
+                MemberKind::Felt | MemberKind::Tuple => None,
+            })
+            .collect()
+    }
+}
+
+impl KakarotSerde {
+    /// Describes the layout of the struct with the given name.
+    ///
+    /// The name is resolved as in [`KakarotSerde::get_identifier`], e.g. `Uint256` resolves to
+    /// `starkware.cairo.common.uint256.Uint256`.
+    pub fn describe_struct(&self, scope: &str) -> Result<StructLayout, KakarotSerdeError> {
+        let layout = self.struct_members(scope)?;
+
+        let mut layouts = Vec::with_capacity(layout.members.len());
+        for member in &layout.members {
+            let kind = match MemberType::parse(&member.cairo_type) {
+                MemberType::Felt => MemberKind::Felt,
+                MemberType::Pointer(pointee) => MemberKind::Pointer {
+                    pointee: match MemberType::parse(pointee) {
+                        MemberType::Struct(pointee) => {
+                            Some(self.struct_members(pointee)?.name.clone())
+                        }
+                        _ => None,
+                    },
+                },
+                MemberType::Struct(nested) => {
+                    MemberKind::Struct { name: self.struct_members(nested)?.name.clone() }
+                }
+                MemberType::Tuple => MemberKind::Tuple,
+            };
+            layouts.push(MemberLayout {
+                name: member.name.clone(),
+                offset: member.offset,
+                size: self.type_size(&member.typ)?,
+                cairo_type: member.cairo_type.clone(),
+                kind,
+            });
+        }
+
+        let size = layouts.iter().map(|member| member.offset + member.size).max().unwrap_or(0);
+        Ok(StructLayout { name: layout.name.clone(), size, members: layouts })
+    }
+
+    /// Lists the full names of the structs of the program in the scope `prefix`, sorted.
+    ///
+    /// All the structs are listed when the scope is empty.
+    pub fn list_structs(&self, prefix: &str) -> Vec<String> {
+        let mut names = self
+            .runner
+            .get_program()
+            .iter_identifiers()
+            .filter(|(_, identifier)| identifier.type_.as_deref() == Some("struct"))
+            .map(|(name, _)| name)
+            .filter(|name| {
+                prefix.is_empty() ||
+                    *name == prefix ||
+                    name.strip_prefix(prefix)
+                        .is_some_and(|rest| rest.starts_with(ScopedName::SEPARATOR))
+            })
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_kakarot_serde() -> KakarotSerde {
+        KakarotSerde::builder()
+            .program_bytes(include_bytes!("../../testdata/keccak_add_uint256.json"))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_describe_struct() {
+        let kakarot_serde = setup_kakarot_serde();
+
+        let layout = kakarot_serde.describe_struct("EcOpBuiltin").unwrap();
+        assert_eq!(layout.name, "starkware.cairo.common.cairo_builtins.EcOpBuiltin");
+        assert_eq!(layout.size, 7);
+        assert_eq!(
+            layout.members.iter().map(|member| member.name.as_str()).collect::<Vec<_>>(),
+            vec!["p", "q", "m", "r"]
+        );
+        assert_eq!(
+            layout.member("r"),
+            Some(&MemberLayout {
+                name: "r".to_string(),
+                offset: 5,
+                size: 2,
+                cairo_type: "starkware.cairo.common.ec_point.EcPoint".to_string(),
+                kind: MemberKind::Struct {
+                    name: "starkware.cairo.common.ec_point.EcPoint".to_string()
+                },
+            })
+        );
+        assert_eq!(layout.member("m").unwrap().kind, MemberKind::Felt);
+        assert_eq!(
+            layout.references(),
+            BTreeSet::from(["starkware.cairo.common.ec_point.EcPoint"])
+        );
+    }
+
+    #[test]
+    fn test_describe_struct_pointers() {
+        let kakarot_serde = setup_kakarot_serde();
+
+        let layout = kakarot_serde.describe_struct("main.ImplicitArgs").unwrap();
+        assert_eq!(layout.name, "__main__.main.ImplicitArgs");
+        assert_eq!(layout.size, 3);
+        assert_eq!(
+            layout.member("output_ptr").unwrap().kind,
+            MemberKind::Pointer { pointee: None }
+        );
+        assert_eq!(
+            layout.member("bitwise_ptr").unwrap().kind,
+            MemberKind::Pointer {
+                pointee: Some("starkware.cairo.common.cairo_builtins.BitwiseBuiltin".to_string())
+            }
+        );
+
+        // Structs without members are empty.
+        let layout = kakarot_serde.describe_struct("main.Args").unwrap();
+        assert_eq!((layout.size, layout.members.len()), (0, 0));
+
+        assert!(matches!(
+            kakarot_serde.describe_struct("Missing"),
+            Err(KakarotSerdeError::IdentifierNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_list_structs() {
+        let kakarot_serde = setup_kakarot_serde();
+
+        assert_eq!(
+            kakarot_serde.list_structs("starkware.cairo.common.uint256"),
+            vec![
+                "starkware.cairo.common.uint256.Uint256",
+                "starkware.cairo.common.uint256.uint256_reverse_endian.Args",
+                "starkware.cairo.common.uint256.uint256_reverse_endian.ImplicitArgs",
+                "starkware.cairo.common.uint256.word_reverse_endian.Args",
+                "starkware.cairo.common.uint256.word_reverse_endian.ImplicitArgs",
+            ]
+        );
+        // Prefixes are matched on whole scopes.
+        assert!(kakarot_serde.list_structs("starkware.cairo.common.uint").is_empty());
+        assert_eq!(kakarot_serde.list_structs("").len(), 24);
+    }
+}
@@ -0,0 +1,1867 @@
+pub mod builder;
+pub mod builtins;
+pub mod cache;
+pub mod codegen;
+pub mod diff;
+pub mod dump;
+pub mod json;
+pub mod layout;
+pub mod model;
+pub mod null;
+pub mod relocated;
+pub mod storage;
+pub mod symbols;
+pub mod writer;
+
+use crate::{
+    attribution::felt_to_usize,
+    model::{ConversionError, U128_BYTES_SIZE},
+    rlp::RlpError,
+};
+use alloy_primitives::U256;
+use cache::{CachedMember, CachedStruct, ProgramLayoutCache};
+use cairo_vm::{
+    serde::deserialize_program::{Identifier, Location},
+    types::{
+        builtin_name::BuiltinName,
+        errors::math_errors::MathError,
+        relocatable::{MaybeRelocatable, Relocatable},
+    },
+    vm::{errors::memory_errors::MemoryError, runners::cairo_runner::CairoRunner},
+    Felt252,
+};
+use null::{MemberValue, NullPointerRegistry};
+use relocated::{RelocatedMemory, RELOCATED_SEGMENT};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+};
+use thiserror::Error;
+
+/// Represents errors that can occur during the serialization and deserialization processes between
+/// Cairo VM programs and Rust representations.
+#[derive(Debug, Error)]
+pub enum KakarotSerdeError {
+    /// Error variant indicating that no identifier matching the specified name was found.
+    #[error("Expected one struct named '{struct_name}', found 0 matches. Expected type: {expected_type:?}")]
+    IdentifierNotFound {
+        /// The name of the struct that was not found.
+        struct_name: String,
+        /// The expected type of the struct (if applicable).
+        expected_type: Option<String>,
+    },
+
+    /// Error variant indicating that multiple identifiers matching the specified name were found.
+    #[error("Expected one struct named '{struct_name}', found {count} matches. Expected type: {expected_type:?}")]
+    MultipleIdentifiersFound {
+        /// The name of the struct for which multiple identifiers were found.
+        struct_name: String,
+        /// The expected type of the struct (if applicable).
+        expected_type: Option<String>,
+        /// The number of matching identifiers found.
+        count: usize,
+    },
+
+    /// Error variant indicating a Math error in CairoVM operations
+    #[error(transparent)]
+    CairoVmMath(#[from] MathError),
+
+    /// Error variant indicating a memory error in CairoVM operations
+    #[error(transparent)]
+    CairoVmMemory(#[from] MemoryError),
+
+    /// Error variant indicating that a required field was not found during serialization.
+    #[error("Missing required field '{field}' in serialization process.")]
+    MissingField {
+        /// The name of the missing field.
+        field: String,
+    },
+
+    /// Error variant indicating that a struct has no member with the given name.
+    #[error("Struct '{struct_name}' has no member named '{member}'.")]
+    UnknownMember {
+        /// The name of the struct.
+        struct_name: String,
+        /// The name of the unknown member.
+        member: String,
+    },
+
+    /// Error variant indicating that no value was written at the given address.
+    #[error("No value written at {address}.")]
+    MissingValue {
+        /// The address of the missing value.
+        address: Relocatable,
+    },
+
+    /// Error variant indicating that a pointer chain loops back to one of its pointees.
+    #[error("Pointer cycle detected: {cycle:?}.")]
+    PointerCycle {
+        /// The addresses of the pointees forming the cycle.
+        cycle: Vec<Relocatable>,
+    },
+
+    /// Error variant indicating that a felt was expected but a relocatable was found.
+    #[error("Expected a felt at {address}, found a relocatable.")]
+    ExpectedFelt {
+        /// The address of the value.
+        address: Relocatable,
+    },
+
+    /// Error variant indicating that a pointer holds a non-null felt.
+    #[error("Invalid pointer at {address}: expected a relocatable or 0, found {value}.")]
+    InvalidPointer {
+        /// The address of the pointer.
+        address: Relocatable,
+        /// The value found at the address.
+        value: Felt252,
+    },
+
+    /// Error variant indicating that the slot hashed into a storage dict key is unknown.
+    #[error("Missing preimage of storage key {key}.")]
+    MissingPreimage {
+        /// The storage dict key.
+        key: Felt252,
+    },
+
+    /// Error variant indicating that the used size of a segment is not computed yet.
+    #[error("Used size of segment {segment_index} not computed, the run must be ended.")]
+    SegmentSizesNotComputed {
+        /// The index of the segment.
+        segment_index: usize,
+    },
+
+    /// Error variant indicating that an accounts dict key is not an EVM address.
+    #[error("Invalid account address {key}.")]
+    InvalidAddress {
+        /// The accounts dict key.
+        key: Felt252,
+    },
+
+    /// Error variant indicating that the runner has no runner for a builtin.
+    #[error("Builtin {} not initialized in the runner.", .0.to_str())]
+    MissingBuiltin(BuiltinName),
+
+    /// Error variant indicating that a builtin pointer does not point into its builtin segment.
+    #[error(
+        "Member '{member}' should point into the {} segment {segment}, found {value}.",
+        .builtin.to_str()
+    )]
+    WrongBuiltinSegment {
+        /// The name of the member holding the pointer.
+        member: String,
+        /// The builtin the pointer should point into.
+        builtin: BuiltinName,
+        /// The index of the builtin segment.
+        segment: usize,
+        /// The value of the pointer.
+        value: MaybeRelocatable,
+    },
+
+    /// Error variant indicating that a value written to a struct member does not have its size.
+    #[error("Member '{member}' of '{struct_name}' has {expected} cells, found {actual}.")]
+    MemberSizeMismatch {
+        /// The name of the struct.
+        struct_name: String,
+        /// The name of the member.
+        member: String,
+        /// The number of cells of the member.
+        expected: usize,
+        /// The number of cells of the written value.
+        actual: usize,
+    },
+
+    /// Error variant indicating a write to a serializer reading a relocated memory.
+    #[error("Values cannot be written to a relocated memory.")]
+    RelocatedWrite,
+
+    /// Error variant indicating an unknown `reverted` code of a `model.EVM`.
+    #[error("Invalid reverted code {code}, expected 0, Errors.REVERT or Errors.EXCEPTIONAL_HALT.")]
+    InvalidRevertedCode {
+        /// The `reverted` code.
+        code: u64,
+    },
+
+    /// Error variant indicating an exceptional halt whose error message has no known reason.
+    #[error("Unknown exceptional halt: {message}.")]
+    UnknownHalt {
+        /// The error message of the halt, lossily decoded.
+        message: String,
+    },
+
+    /// Error variant indicating an error at an address, located in the value containing it, see
+    /// [`KakarotSerde::locate_error`].
+    #[error("Read failed at {location}: {error}")]
+    Located {
+        /// The symbol containing the address, e.g. `model.Account.storage + 3`.
+        location: String,
+        /// The located error.
+        error: Box<KakarotSerdeError>,
+    },
+
+    /// Error variant indicating that a value does not convert to its Rust type.
+    #[error(transparent)]
+    Conversion(#[from] ConversionError),
+
+    /// Error variant indicating that a serialized transaction does not decode.
+    #[error(transparent)]
+    Rlp(#[from] RlpError),
+}
+
+/// Represents the types used in Cairo, including felt types, pointers, tuples, and structs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CairoType {
+    /// A felt type, optionally associated with a location.
+    Felt { location: Option<Location> },
+
+    /// A pointer type that points to another [`CairoType`], with an optional location.
+    Pointer { pointee: Box<CairoType>, location: Option<Location> },
+
+    /// A tuple type that consists of multiple tuple items.
+    Tuple { members: Vec<TupleItem>, has_trailing_comma: bool, location: Option<Location> },
+
+    /// A struct type defined by its scope and an optional location.
+    Struct { scope: ScopedName, location: Option<Location> },
+}
+
+impl CairoType {
+    /// Creates a new [`CairoType::Struct`] with the specified scope and optional location.
+    pub fn struct_type(scope: &str, location: Option<Location>) -> Self {
+        Self::Struct { scope: ScopedName::from_string(scope), location }
+    }
+
+    /// Creates a new [`CairoType::Felt`] with an optional location.
+    pub fn felt_type(location: Option<Location>) -> Self {
+        Self::Felt { location }
+    }
+
+    /// Creates a new [`CairoType::Pointer`] that points to a specified [`CairoType`].
+    pub fn pointer_type(pointee: CairoType, location: Option<Location>) -> Self {
+        Self::Pointer { pointee: Box::new(pointee), location }
+    }
+
+    /// Creates a new [`CairoType::Tuple`] from a vector of [`TupleItem`]s.
+    pub fn tuple_from_members(
+        members: Vec<TupleItem>,
+        has_trailing_comma: bool,
+        location: Option<Location>,
+    ) -> Self {
+        Self::Tuple { members, has_trailing_comma, location }
+    }
+
+    /// Parses a type annotation, e.g. `felt`, `model.Account*` or `(a: felt, b: Uint256)`.
+    ///
+    /// The parsed type holds no location.
+    pub fn parse(cairo_type: &str) -> Self {
+        match MemberType::parse(cairo_type) {
+            MemberType::Felt => Self::felt_type(None),
+            MemberType::Pointer(pointee) => Self::pointer_type(Self::parse(pointee), None),
+            MemberType::Struct(name) => Self::struct_type(name, None),
+            MemberType::Tuple => {
+                let (items, has_trailing_comma) = tuple_items(cairo_type);
+                let members = items
+                    .into_iter()
+                    .map(|(name, typ)| {
+                        TupleItem::new(name.map(String::from), Self::parse(typ), None)
+                    })
+                    .collect();
+                Self::tuple_from_members(members, has_trailing_comma, None)
+            }
+        }
+    }
+}
+
+/// Represents an item in a tuple, consisting of an optional name, type, and location.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TupleItem {
+    /// An optional string representing the name of the tuple item.
+    pub name: Option<String>,
+
+    /// The [`CairoType`] of the tuple item.
+    pub typ: CairoType,
+
+    /// An optional location associated with the tuple item.
+    pub location: Option<Location>,
+}
+
+impl TupleItem {
+    /// Creates a new [`TupleItem`] with an optional name, Cairo type, and location.
+    pub fn new(name: Option<String>, typ: CairoType, location: Option<Location>) -> Self {
+        Self { name, typ, location }
+    }
+}
+
+/// Represents a scoped name composed of a series of identifiers forming a path.
+///
+/// Example: `starkware.cairo.common.uint256.Uint256`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ScopedName {
+    /// A vector of strings representing the components of the scoped name.
+    ///
+    /// Each element in the vector corresponds to a segment of the name, separated by
+    /// a dot (`.`).
+    ///
+    /// The first element is the top-level namespace, and subsequent elements represent
+    /// sub-namespaces or types. This structure allows for easy manipulation and representation
+    /// of names in a hierarchical format.
+    pub path: Vec<String>,
+}
+
+impl ScopedName {
+    /// Separator for the scope path.
+    const SEPARATOR: &'static str = ".";
+
+    /// Creates a [`ScopedName`] from a dot-separated string.
+    pub fn from_string(scope: &str) -> Self {
+        let path = if scope.is_empty() {
+            vec![]
+        } else {
+            scope.split(Self::SEPARATOR).map(String::from).collect()
+        };
+        Self { path }
+    }
+}
+
+/// A Cairo type, as found in the `cairo_type` of the struct members.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MemberType<'a> {
+    /// A felt, or a code offset.
+    Felt,
+    /// A pointer to the given type.
+    Pointer(&'a str),
+    /// A struct with the given name.
+    Struct(&'a str),
+    /// A tuple, which is not walked.
+    Tuple,
+}
+
+impl<'a> MemberType<'a> {
+    /// Parses a member type.
+    fn parse(cairo_type: &'a str) -> Self {
+        let cairo_type = cairo_type.trim();
+        if let Some(pointee) = cairo_type.strip_suffix('*') {
+            Self::Pointer(pointee.trim())
+        } else if cairo_type == "felt" || cairo_type == "codeoffset" {
+            Self::Felt
+        } else if cairo_type.starts_with('(') {
+            Self::Tuple
+        } else {
+            Self::Struct(cairo_type)
+        }
+    }
+}
+
+/// Splits a tuple type into its optionally named items, and whether it has a trailing comma.
+fn tuple_items(cairo_type: &str) -> (Vec<(Option<&str>, &str)>, bool) {
+    let cairo_type = cairo_type.trim();
+    let inner = cairo_type
+        .strip_prefix('(')
+        .and_then(|inner| inner.strip_suffix(')'))
+        .unwrap_or(cairo_type)
+        .trim();
+    let has_trailing_comma = inner.ends_with(',');
+
+    let mut items = Vec::new();
+    let (mut depth, mut start) = (0usize, 0);
+    for (i, c) in inner.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                items.push(&inner[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&inner[start..]);
+
+    let items = items
+        .into_iter()
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| match item.split_once(':') {
+            // Named items are `name: type`, nested tuples never start with a name.
+            Some((name, typ)) if !name.contains('(') => (Some(name.trim()), typ.trim()),
+            _ => (None, item),
+        })
+        .collect();
+
+    (items, has_trailing_comma)
+}
+
+/// A value serialized from the VM memory according to its [`CairoType`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SerializedValue {
+    /// A felt, or a relocatable stored in a felt.
+    Felt(MaybeRelocatable),
+
+    /// A null pointer.
+    Null,
+
+    /// A pointer, along with its serialized pointee.
+    ///
+    /// Felt pointees are not serialized, as they usually are arrays of unknown length.
+    Pointer { address: Relocatable, pointee: Option<Box<SerializedValue>> },
+
+    /// A tuple, made of its optionally named items.
+    Tuple(Vec<(Option<String>, SerializedValue)>),
+
+    /// A struct, made of its full name and its written members.
+    Struct { name: String, members: BTreeMap<String, SerializedValue> },
+
+    /// A reference to a pointee already serialized elsewhere in the value.
+    ///
+    /// Only emitted when [`SerializeOptions::shared_as_references`] is set.
+    Reference(Relocatable),
+}
+
+/// The options of [`KakarotSerde::serialize_by_type_with`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SerializeOptions {
+    /// Whether pointees reachable through several pointers are serialized once, the other
+    /// occurrences being emitted as [`SerializedValue::Reference`]s, rather than copied.
+    pub shared_as_references: bool,
+}
+
+/// The state of a recursive serialization.
+#[derive(Debug)]
+struct SerializeContext {
+    /// The options of the serialization.
+    options: SerializeOptions,
+    /// The addresses of the pointees being serialized, from the outermost one.
+    chain: Vec<Relocatable>,
+    /// The addresses of the pointees already serialized.
+    serialized: HashSet<Relocatable>,
+}
+
+impl SerializeContext {
+    /// Creates a new [`SerializeContext`].
+    fn new(options: SerializeOptions) -> Self {
+        Self { options, chain: Vec::new(), serialized: HashSet::new() }
+    }
+}
+
+/// A Rust type which can be read from the memory of a Cairo struct.
+///
+/// Implementations are usually generated from the program identifiers, see [`codegen`]. Values are
+/// written to memory the other way with [`writer::CairoWrite`].
+pub trait CairoSerde: Sized {
+    /// The full name of the Cairo struct.
+    const STRUCT_NAME: &'static str;
+
+    /// The number of memory cells of the Cairo struct.
+    const SIZE: usize;
+
+    /// Reads the value of the struct at `ptr`.
+    fn from_cairo(serde: &KakarotSerde, ptr: Relocatable) -> Result<Self, KakarotSerdeError>;
+}
+
+/// A structure representing the Kakarot serialization and deserialization context for Cairo
+/// programs.
+///
+/// This struct encapsulates the components required to serialize and deserialize
+/// Kakarot programs, including:
+/// - The Cairo runner responsible for executing the program
+#[allow(missing_debug_implementations)]
+pub struct KakarotSerde {
+    /// The Cairo runner used to execute Kakarot programs.
+    ///
+    /// This runner interacts with the Cairo virtual machine, providing the necessary
+    /// infrastructure for running and managing the execution of Cairo programs.
+    /// It is responsible for handling program execution flow, managing state, and
+    /// providing access to program identifiers.
+    runner: CairoRunner,
+
+    /// The null-pointer semantics of the struct members.
+    null_pointers: NullPointerRegistry,
+
+    /// The relocated memory read in place of the memory of the runner, if any.
+    relocated: Option<RelocatedMemory>,
+
+    /// The layouts of the structs of the program.
+    layouts: ProgramLayoutCache,
+}
+
+impl KakarotSerde {
+    /// Creates a new [`KakarotSerde`] for the given runner.
+    pub fn new(runner: CairoRunner) -> Self {
+        let layouts = ProgramLayoutCache::new(runner.get_program());
+        Self { runner, null_pointers: NullPointerRegistry::default(), relocated: None, layouts }
+    }
+
+    /// Returns whether the serializer reads a relocated memory rather than the memory of its
+    /// runner, see [`relocated`].
+    pub const fn is_relocated(&self) -> bool {
+        self.relocated.is_some()
+    }
+
+    /// Returns the layouts of the structs of the program.
+    pub const fn layouts(&self) -> &ProgramLayoutCache {
+        &self.layouts
+    }
+
+    /// Returns the null-pointer semantics of the struct members, to configure them.
+    pub fn null_pointers_mut(&mut self) -> &mut NullPointerRegistry {
+        &mut self.null_pointers
+    }
+
+    /// Retrieves a unique identifier from the Cairo program based on the specified struct name and
+    /// expected type.
+    ///
+    /// This function searches for identifiers that match the provided struct name and type within
+    /// the Cairo program's identifier mappings. It returns an error if no identifiers or
+    /// multiple identifiers are found.
+    pub fn get_identifier(
+        &self,
+        struct_name: &str,
+        expected_type: Option<String>,
+    ) -> Result<Identifier, KakarotSerdeError> {
+        // Retrieve identifiers from the program and filter them based on the struct name and
+        // expected type
+        let identifiers = self
+            .runner
+            .get_program()
+            .iter_identifiers()
+            .filter(|(key, value)| {
+                key.contains(struct_name) &&
+                    key.split('.').last() == struct_name.split('.').last() &&
+                    value.type_ == expected_type
+            })
+            .map(|(_, value)| value)
+            .collect::<Vec<_>>();
+
+        // Match on the number of found identifiers
+        match identifiers.len() {
+            // No identifiers found
+            0 => Err(KakarotSerdeError::IdentifierNotFound {
+                struct_name: struct_name.to_string(),
+                expected_type,
+            }),
+            // Exactly one identifier found, return it
+            1 => Ok(identifiers[0].clone()),
+            // More than one identifier found
+            count => Err(KakarotSerdeError::MultipleIdentifiersFound {
+                struct_name: struct_name.to_string(),
+                expected_type,
+                count,
+            }),
+        }
+    }
+
+    /// Serializes a pointer to a Hashmap by resolving its members from memory.
+    ///
+    /// We provide:
+    /// - The name of the struct whose pointer is being serialized.
+    /// - The memory location (pointer) of the struct.
+    ///
+    /// We expect:
+    /// - A map of member names to their corresponding values (or `None` if the pointer is null).
+    ///
+    /// Members absent from memory are not part of the map, see
+    /// [`KakarotSerde::serialize_members`] to tell them apart from null pointers.
+    pub fn serialize_pointers(
+        &self,
+        struct_name: &str,
+        ptr: Relocatable,
+    ) -> Result<HashMap<String, Option<MaybeRelocatable>>, KakarotSerdeError> {
+        Ok(self
+            .serialize_members(struct_name, ptr)?
+            .into_iter()
+            .filter_map(|(name, value)| value.into_option().map(|value| (name, value)))
+            .collect())
+    }
+
+    /// Serializes all the members of a struct, absent members included.
+    ///
+    /// Null pointers are detected according to the [`NullPointerRegistry`] of the serializer.
+    pub fn serialize_members(
+        &self,
+        struct_name: &str,
+        ptr: Relocatable,
+    ) -> Result<HashMap<String, MemberValue>, KakarotSerdeError> {
+        // Fetch the struct layout by name.
+        let layout = self.layouts.resolve(struct_name)?;
+
+        // Fetch the cells of all the members at once.
+        let cells = self.get_range(ptr, layout.span())?;
+
+        // Resolve the value of each member from its cell.
+        let mut output = HashMap::with_capacity(layout.members.len());
+        for member in &layout.members {
+            let value = self.member_value(&layout.name, member, cells[member.offset].clone());
+            output.insert(member.name.clone(), value);
+        }
+
+        Ok(output)
+    }
+
+    /// Serializes the selected members of a struct to a Hashmap.
+    ///
+    /// This is the partial counterpart of [`KakarotSerde::serialize_pointers`]: only the cells
+    /// between the first and the last selected members are read, at once, which avoids decoding
+    /// every member of wide structs on hot paths. Unwritten members are absent from the output, as
+    /// in [`KakarotSerde::serialize_pointers`].
+    pub fn serialize_fields(
+        &self,
+        struct_name: &str,
+        ptr: Relocatable,
+        fields: &[&str],
+    ) -> Result<HashMap<String, Option<MaybeRelocatable>>, KakarotSerdeError> {
+        // Fetch the struct layout by name.
+        let layout = self.layouts.resolve(struct_name)?;
+        let members = fields
+            .iter()
+            .map(|field| {
+                layout.member(field).ok_or_else(|| KakarotSerdeError::UnknownMember {
+                    struct_name: struct_name.to_string(),
+                    member: field.to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Fetch the cells spanning the selected members at once.
+        let Some(first) = members.iter().map(|member| member.offset).min() else {
+            return Ok(HashMap::new());
+        };
+        let last = members.iter().map(|member| member.offset).max().unwrap_or(first);
+        let cells = self.get_range((ptr + first)?, last - first + 1)?;
+
+        let mut output = HashMap::with_capacity(members.len());
+        for member in members {
+            let cell = cells[member.offset - first].clone();
+            if let Some(value) = self.member_value(&layout.name, member, cell).into_option() {
+                output.insert(member.name.clone(), value);
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// Returns the value of a struct member from the cell read at its offset, detecting null
+    /// pointers according to the [`NullPointerRegistry`].
+    fn member_value(
+        &self,
+        struct_name: &str,
+        member: &CachedMember,
+        cell: Option<MaybeRelocatable>,
+    ) -> MemberValue {
+        let Some(mut value) = cell else { return MemberValue::Absent };
+        if member.pointer {
+            value = self.relocated_address(value);
+        }
+
+        // We return `Null` for cases such as `parent=cast(0, model.Parent*)`
+        if self.null_pointers.policy(struct_name, &member.name).is_null(&value, &member.cairo_type)
+        {
+            MemberValue::Null
+        } else {
+            MemberValue::Value(value)
+        }
+    }
+
+    /// Serializes a Cairo VM `Uint256` structure (with `low` and `high` fields) into a Rust
+    /// [`U256`] value.
+    ///
+    /// This function retrieves the `Uint256` struct from memory, extracts its `low` and `high`
+    /// values, converts them into a big-endian byte representation, and combines them into a
+    /// single [`U256`].
+    pub fn serialize_uint256(&self, ptr: Relocatable) -> Result<U256, KakarotSerdeError> {
+        // Fetches the `Uint256` structure from memory.
+        let raw = self.serialize_pointers("Uint256", ptr)?;
+
+        // Retrieves the `low` field from the deserialized struct, ensuring it's a valid integer.
+        let low = match raw.get("low") {
+            Some(Some(MaybeRelocatable::Int(value))) => value,
+            _ => return Err(KakarotSerdeError::MissingField { field: "low".to_string() }),
+        };
+
+        // Retrieves the `high` field from the deserialized struct, ensuring it's a valid integer.
+        let high = match raw.get("high") {
+            Some(Some(MaybeRelocatable::Int(value))) => value,
+            _ => return Err(KakarotSerdeError::MissingField { field: "high".to_string() }),
+        };
+
+        // Converts the `low` and `high` values into big-endian byte arrays.
+        let high_bytes = high.to_bytes_be();
+        let low_bytes = low.to_bytes_be();
+
+        // Concatenates the last 16 bytes (128 bits) of the `high` and `low` byte arrays.
+        //
+        // This forms a 256-bit number, where:
+        // - The `high` bytes make up the most significant 128 bits
+        // - The `low` bytes make up the least significant 128 bits.
+        let bytes = [&high_bytes[U128_BYTES_SIZE..], &low_bytes[U128_BYTES_SIZE..]].concat();
+
+        // Creates a `U256` value from the concatenated big-endian byte array.
+        Ok(U256::from_be_slice(&bytes))
+    }
+
+    /// Serializes the value of the given [`CairoType`] at `ptr`.
+    ///
+    /// This is the entry point for callers that only know the type of a value, e.g. from a type
+    /// annotation parsed with [`CairoType::parse`]:
+    /// - Felts are read as is.
+    /// - Pointers are chased, null pointers being serialized as [`SerializedValue::Null`].
+    /// - Tuple items are serialized one after the other.
+    /// - Struct members are looked up in the program identifiers, unwritten members are skipped.
+    ///
+    /// Pointer cycles are reported as [`KakarotSerdeError::PointerCycle`], and shared pointees are
+    /// copied. See [`KakarotSerde::serialize_by_type_with`] to emit them as references instead.
+    pub fn serialize_by_type(
+        &self,
+        cairo_type: &CairoType,
+        ptr: Relocatable,
+    ) -> Result<SerializedValue, KakarotSerdeError> {
+        self.serialize_by_type_with(cairo_type, ptr, SerializeOptions::default())
+    }
+
+    /// Serializes the value of the given [`CairoType`] at `ptr` with the given options.
+    pub fn serialize_by_type_with(
+        &self,
+        cairo_type: &CairoType,
+        ptr: Relocatable,
+        options: SerializeOptions,
+    ) -> Result<SerializedValue, KakarotSerdeError> {
+        self.serialize_value(cairo_type, ptr, &mut SerializeContext::new(options))
+    }
+
+    /// Recursively serializes the value of the given [`CairoType`] at `ptr`.
+    fn serialize_value(
+        &self,
+        cairo_type: &CairoType,
+        ptr: Relocatable,
+        ctx: &mut SerializeContext,
+    ) -> Result<SerializedValue, KakarotSerdeError> {
+        match cairo_type {
+            CairoType::Felt { .. } => Ok(SerializedValue::Felt(self.read(ptr)?)),
+            CairoType::Pointer { pointee, .. } => match self.read_address(ptr)? {
+                MaybeRelocatable::Int(value) if value == Felt252::ZERO => Ok(SerializedValue::Null),
+                MaybeRelocatable::Int(value) => {
+                    Err(KakarotSerdeError::InvalidPointer { address: ptr, value })
+                }
+                MaybeRelocatable::RelocatableValue(address) => {
+                    let pointee = match pointee.as_ref() {
+                        CairoType::Felt { .. } => None,
+                        pointee => Some(Box::new(self.serialize_pointee(pointee, address, ctx)?)),
+                    };
+                    Ok(SerializedValue::Pointer { address, pointee })
+                }
+            },
+            CairoType::Tuple { members, .. } => {
+                let mut offset = 0;
+                let mut items = Vec::with_capacity(members.len());
+                for member in members {
+                    let value = self.serialize_value(&member.typ, (ptr + offset)?, ctx)?;
+                    offset += self.type_size(&member.typ)?;
+                    items.push((member.name.clone(), value));
+                }
+                Ok(SerializedValue::Tuple(items))
+            }
+            CairoType::Struct { scope, .. } => {
+                let layout = self.struct_members(&scope.path.join(ScopedName::SEPARATOR))?;
+                let mut values = BTreeMap::new();
+                for member in &layout.members {
+                    match self.serialize_value(&member.typ, (ptr + member.offset)?, ctx) {
+                        Ok(value) => {
+                            values.insert(member.name.clone(), value);
+                        }
+                        Err(KakarotSerdeError::MissingValue { .. }) => {}
+                        Err(err) => return Err(err),
+                    }
+                }
+                Ok(SerializedValue::Struct { name: layout.name.clone(), members: values })
+            }
+        }
+    }
+
+    /// Serializes the pointee at `address`, detecting cycles and shared pointees.
+    fn serialize_pointee(
+        &self,
+        pointee: &CairoType,
+        address: Relocatable,
+        ctx: &mut SerializeContext,
+    ) -> Result<SerializedValue, KakarotSerdeError> {
+        // A pointee being serialized higher in the pointer chain is a cycle.
+        if let Some(start) = ctx.chain.iter().position(|chased| *chased == address) {
+            return Err(KakarotSerdeError::PointerCycle { cycle: ctx.chain[start..].to_vec() });
+        }
+        if ctx.options.shared_as_references && ctx.serialized.contains(&address) {
+            return Ok(SerializedValue::Reference(address));
+        }
+
+        ctx.chain.push(address);
+        let value = self.serialize_value(pointee, address, ctx);
+        ctx.chain.pop();
+
+        ctx.serialized.insert(address);
+        value
+    }
+
+    /// Returns the number of memory cells of a value of the given [`CairoType`].
+    pub fn type_size(&self, cairo_type: &CairoType) -> Result<usize, KakarotSerdeError> {
+        self.layouts.type_size(cairo_type)
+    }
+
+    /// Returns the value written at the given address, if any.
+    ///
+    /// Relocated memories only hold felts: the non-zero felts read as a `pointer` are converted to
+    /// the absolute address they hold.
+    fn get_maybe(&self, address: Relocatable, pointer: bool) -> Option<MaybeRelocatable> {
+        let Some(memory) = &self.relocated else { return self.runner.vm.get_maybe(&address) };
+        if address.segment_index != RELOCATED_SEGMENT {
+            return None;
+        }
+
+        let value = MaybeRelocatable::Int(memory.get(address.offset)?);
+        Some(if pointer { self.relocated_address(value) } else { value })
+    }
+
+    /// Returns the values written in the `size` cells starting at the given address, felts of a
+    /// relocated memory being left as is.
+    fn get_range(
+        &self,
+        address: Relocatable,
+        size: usize,
+    ) -> Result<Vec<Option<MaybeRelocatable>>, KakarotSerdeError> {
+        if self.relocated.is_none() {
+            let cells = self.runner.vm.get_range(address, size);
+            return Ok(cells.into_iter().map(|cell| cell.map(Cow::into_owned)).collect());
+        }
+        (0..size).map(|offset| Ok(self.get_maybe((address + offset)?, false))).collect()
+    }
+
+    /// Converts a non-zero felt read as a pointer from a relocated memory into the absolute
+    /// address it holds.
+    fn relocated_address(&self, value: MaybeRelocatable) -> MaybeRelocatable {
+        match value {
+            MaybeRelocatable::Int(felt) if self.relocated.is_some() => match felt_to_usize(&felt) {
+                Some(target) if target != 0 => {
+                    MaybeRelocatable::RelocatableValue(RelocatedMemory::address(target))
+                }
+                _ => value,
+            },
+            value => value,
+        }
+    }
+
+    /// Reads the value written at the given address.
+    fn read(&self, address: Relocatable) -> Result<MaybeRelocatable, KakarotSerdeError> {
+        self.get_maybe(address, false).ok_or(KakarotSerdeError::MissingValue { address })
+    }
+
+    /// Reads the pointer written at the given address, as a relocatable unless null.
+    fn read_address(&self, address: Relocatable) -> Result<MaybeRelocatable, KakarotSerdeError> {
+        self.get_maybe(address, true).ok_or(KakarotSerdeError::MissingValue { address })
+    }
+
+    /// Reads the felt written at the given address.
+    pub fn read_felt(&self, address: Relocatable) -> Result<Felt252, KakarotSerdeError> {
+        match self.read(address)? {
+            MaybeRelocatable::Int(value) => Ok(value),
+            MaybeRelocatable::RelocatableValue(_) => {
+                Err(KakarotSerdeError::ExpectedFelt { address })
+            }
+        }
+    }
+
+    /// Reads the pointer written at the given address, `None` being the null pointer.
+    pub fn read_pointer(
+        &self,
+        address: Relocatable,
+    ) -> Result<Option<Relocatable>, KakarotSerdeError> {
+        match self.read_address(address)? {
+            MaybeRelocatable::RelocatableValue(pointer) => Ok(Some(pointer)),
+            MaybeRelocatable::Int(value) if value == Felt252::ZERO => Ok(None),
+            MaybeRelocatable::Int(value) => {
+                Err(KakarotSerdeError::InvalidPointer { address, value })
+            }
+        }
+    }
+
+    /// Reads the `N` cells starting at the given address.
+    pub fn read_cells<const N: usize>(
+        &self,
+        address: Relocatable,
+    ) -> Result<[MaybeRelocatable; N], KakarotSerdeError> {
+        let mut cells = Vec::with_capacity(N);
+        for offset in 0..N {
+            cells.push(self.read((address + offset)?)?);
+        }
+        Ok(cells.try_into().expect("N cells were read"))
+    }
+
+    /// Returns the layout of a struct, its members being ordered by offset.
+    ///
+    /// Member types use full names, which are looked up directly before falling back to the
+    /// partial name matching of [`KakarotSerde::get_identifier`].
+    fn struct_members(&self, name: &str) -> Result<Arc<CachedStruct>, KakarotSerdeError> {
+        self.layouts.resolve(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cairo_vm::serde::deserialize_program::InputFile;
+    use std::str::FromStr;
+
+    fn setup_kakarot_serde() -> KakarotSerde {
+        KakarotSerde::builder()
+            .program_bytes(include_bytes!("../../testdata/keccak_add_uint256.json"))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_program_identifier_valid() {
+        // Setup the KakarotSerde instance
+        let kakarot_serde = setup_kakarot_serde();
+
+        // Check if the identifier "main" with expected type "function" is correctly retrieved
+        assert_eq!(
+            kakarot_serde.get_identifier("main", Some("function".to_string())).unwrap(),
+            Identifier {
+                pc: Some(96),
+                type_: Some("function".to_string()),
+                value: None,
+                full_name: None,
+                members: None,
+                cairo_type: None
+            }
+        );
+
+        // Check if the identifier "__temp0" with expected type "reference" is correctly retrieved
+        assert_eq!(
+            kakarot_serde.get_identifier("__temp0", Some("reference".to_string())).unwrap(),
+            Identifier {
+                pc: None,
+                type_: Some("reference".to_string()),
+                value: None,
+                full_name: Some(
+                    "starkware.cairo.common.uint256.word_reverse_endian.__temp0".to_string()
+                ),
+                members: None,
+                cairo_type: Some("felt".to_string())
+            }
+        );
+    }
+
+    #[test]
+    fn test_non_existent_identifier() {
+        // Setup the KakarotSerde instance
+        let kakarot_serde = setup_kakarot_serde();
+
+        // Test for a non-existent identifier
+        let result =
+            kakarot_serde.get_identifier("non_existent_struct", Some("function".to_string()));
+
+        // Check if the error is valid and validate its parameters
+        if let Err(KakarotSerdeError::IdentifierNotFound { struct_name, expected_type }) = result {
+            assert_eq!(struct_name, "non_existent_struct");
+            assert_eq!(expected_type, Some("function".to_string()));
+        } else {
+            panic!("Expected KakarotSerdeError::IdentifierNotFound");
+        }
+    }
+
+    #[test]
+    fn test_incorrect_identifier_usage() {
+        // Setup the KakarotSerde instance
+        let kakarot_serde = setup_kakarot_serde();
+
+        // Test for an identifier used incorrectly (not the last segment of the full name)
+        let result = kakarot_serde.get_identifier("check_range", Some("struct".to_string()));
+
+        // Check if the error is valid and validate its parameters
+        if let Err(KakarotSerdeError::IdentifierNotFound { struct_name, expected_type }) = result {
+            assert_eq!(struct_name, "check_range");
+            assert_eq!(expected_type, Some("struct".to_string()));
+        } else {
+            panic!("Expected KakarotSerdeError::IdentifierNotFound");
+        }
+    }
+
+    #[test]
+    fn test_valid_identifier_incorrect_type() {
+        // Setup the KakarotSerde instance
+        let kakarot_serde = setup_kakarot_serde();
+
+        // Test for a valid identifier but with an incorrect type
+        let result = kakarot_serde.get_identifier("main", Some("struct".to_string()));
+
+        // Check if the error is valid and validate its parameters
+        if let Err(KakarotSerdeError::IdentifierNotFound { struct_name, expected_type }) = result {
+            assert_eq!(struct_name, "main");
+            assert_eq!(expected_type, Some("struct".to_string()));
+        } else {
+            panic!("Expected KakarotSerdeError::IdentifierNotFound");
+        }
+    }
+
+    #[test]
+    fn test_identifier_with_multiple_matches() {
+        // Setup the KakarotSerde instance
+        let kakarot_serde = setup_kakarot_serde();
+
+        // Test for an identifier with multiple matches
+        let result = kakarot_serde.get_identifier("ImplicitArgs", Some("struct".to_string()));
+
+        // Check if the error is valid and validate its parameters
+        if let Err(KakarotSerdeError::MultipleIdentifiersFound {
+            struct_name,
+            expected_type,
+            count,
+        }) = result
+        {
+            assert_eq!(struct_name, "ImplicitArgs");
+            assert_eq!(expected_type, Some("struct".to_string()));
+            assert_eq!(count, 6);
+        } else {
+            panic!("Expected KakarotSerdeError::MultipleIdentifiersFound");
+        }
+    }
+
+    #[test]
+    fn test_serialize_pointer_not_struct() {
+        // Setup the KakarotSerde instance
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        // Add a new memory segment to the virtual machine (VM).
+        let base = kakarot_serde.runner.vm.add_memory_segment();
+
+        // Attempt to serialize pointer with "main", expecting an IdentifierNotFound error.
+        let result = kakarot_serde.serialize_pointers("main", base);
+
+        // Assert that the result is an error with the expected struct name and type.
+        match result {
+            Err(KakarotSerdeError::IdentifierNotFound { struct_name, expected_type }) => {
+                assert_eq!(struct_name, "main".to_string());
+                assert_eq!(expected_type, Some("struct".to_string()));
+            }
+            _ => panic!("Expected KakarotSerdeError::IdentifierNotFound, but got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_serialize_pointer_empty() {
+        // Setup the KakarotSerde instance
+        let kakarot_serde = setup_kakarot_serde();
+
+        // Serialize the pointers of the "ImplicitArgs" struct but without any memory segment.
+        let result = kakarot_serde
+            .serialize_pointers("main.ImplicitArgs", Relocatable::default())
+            .expect("failed to serialize pointers");
+
+        // The result should be an empty HashMap since there is no memory segment.
+        assert!(result.is_empty(),);
+    }
+
+    #[test]
+    fn test_serialize_pointer_valid() {
+        // Setup the KakarotSerde instance
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        // Setup
+        let output_ptr = Felt252::ZERO;
+        let range_check_ptr = kakarot_serde.runner.vm.add_memory_segment();
+        let bitwise_ptr = kakarot_serde.runner.vm.add_memory_segment();
+
+        // Insert values in memory
+        let base = kakarot_serde
+            .runner
+            .vm
+            .gen_arg(&vec![
+                MaybeRelocatable::Int(output_ptr),
+                MaybeRelocatable::RelocatableValue(range_check_ptr),
+                MaybeRelocatable::RelocatableValue(bitwise_ptr),
+            ])
+            .unwrap()
+            .get_relocatable()
+            .unwrap();
+
+        // Serialize the pointers of the "ImplicitArgs" struct using the new memory segment.
+        let result = kakarot_serde
+            .serialize_pointers("main.ImplicitArgs", base)
+            .expect("failed to serialize pointers");
+
+        // Assert that the result matches the expected serialized struct members.
+        assert_eq!(
+            result,
+            HashMap::from_iter([
+                ("output_ptr".to_string(), None),
+                (
+                    "range_check_ptr".to_string(),
+                    Some(MaybeRelocatable::RelocatableValue(range_check_ptr))
+                ),
+                ("bitwise_ptr".to_string(), Some(MaybeRelocatable::RelocatableValue(bitwise_ptr))),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_serialize_fields_selection() {
+        // Setup the KakarotSerde instance
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        // Setup
+        let bitwise_ptr = kakarot_serde.runner.vm.add_memory_segment();
+
+        // Insert values in memory
+        let base = kakarot_serde
+            .runner
+            .vm
+            .gen_arg(&vec![
+                MaybeRelocatable::Int(Felt252::ZERO),
+                MaybeRelocatable::Int(Felt252::from(12)),
+                MaybeRelocatable::RelocatableValue(bitwise_ptr),
+            ])
+            .unwrap()
+            .get_relocatable()
+            .unwrap();
+
+        // Only the selected members are serialized.
+        let result = kakarot_serde
+            .serialize_fields("main.ImplicitArgs", base, &["output_ptr", "bitwise_ptr"])
+            .expect("failed to serialize fields");
+
+        assert_eq!(
+            result,
+            HashMap::from_iter([
+                ("output_ptr".to_string(), None),
+                ("bitwise_ptr".to_string(), Some(MaybeRelocatable::RelocatableValue(bitwise_ptr))),
+            ])
+        );
+
+        // A single member is read from its own cell, an empty selection reads nothing.
+        assert_eq!(
+            kakarot_serde
+                .serialize_fields("main.ImplicitArgs", base, &["range_check_ptr"])
+                .unwrap(),
+            HashMap::from_iter([(
+                "range_check_ptr".to_string(),
+                Some(MaybeRelocatable::Int(Felt252::from(12)))
+            )])
+        );
+        assert!(kakarot_serde.serialize_fields("main.ImplicitArgs", base, &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_serialize_fields_unknown_member() {
+        // Setup the KakarotSerde instance
+        let mut kakarot_serde = setup_kakarot_serde();
+        let base = kakarot_serde.runner.vm.add_memory_segment();
+
+        // Selecting a member that does not exist fails.
+        let result = kakarot_serde.serialize_fields("main.ImplicitArgs", base, &["nonce"]);
+
+        if let Err(KakarotSerdeError::UnknownMember { struct_name, member }) = result {
+            assert_eq!(struct_name, "main.ImplicitArgs");
+            assert_eq!(member, "nonce");
+        } else {
+            panic!("Expected KakarotSerdeError::UnknownMember");
+        }
+    }
+
+    #[test]
+    fn test_serialize_fields_unwritten_member() {
+        // Setup the KakarotSerde instance
+        let mut kakarot_serde = setup_kakarot_serde();
+        let base = kakarot_serde.runner.vm.add_memory_segment();
+
+        // Unwritten members are absent from the output.
+        let result =
+            kakarot_serde.serialize_fields("main.ImplicitArgs", base, &["range_check_ptr"]);
+
+        assert_eq!(result.unwrap(), HashMap::new());
+    }
+
+    #[test]
+    fn test_serialize_members_null_and_absent() {
+        // Setup the KakarotSerde instance
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        // Only `output_ptr` and `range_check_ptr` are written, both set to zero.
+        let base = kakarot_serde.runner.vm.add_memory_segment();
+        kakarot_serde
+            .runner
+            .vm
+            .load_data(base, &[Felt252::ZERO.into(), Felt252::ZERO.into()])
+            .unwrap();
+
+        let result = kakarot_serde.serialize_members("main.ImplicitArgs", base).unwrap();
+
+        assert_eq!(
+            result,
+            HashMap::from_iter([
+                ("output_ptr".to_string(), MemberValue::Null),
+                ("range_check_ptr".to_string(), MemberValue::Value(Felt252::ZERO.into())),
+                ("bitwise_ptr".to_string(), MemberValue::Absent),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_serialize_members_null_policies() {
+        // Setup the KakarotSerde instance
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        let base = kakarot_serde.runner.vm.add_memory_segment();
+        kakarot_serde
+            .runner
+            .vm
+            .load_data(base, &[Felt252::ZERO.into(), Felt252::ZERO.into(), Felt252::ZERO.into()])
+            .unwrap();
+
+        // Zero is null in the felt member, and kept as a value in the `bitwise_ptr` member.
+        kakarot_serde
+            .null_pointers_mut()
+            .set_struct("__main__.main.ImplicitArgs", null::NullPolicy::Zero)
+            .set_member("__main__.main.ImplicitArgs", "bitwise_ptr", null::NullPolicy::Never);
+
+        let result = kakarot_serde.serialize_pointers("main.ImplicitArgs", base).unwrap();
+
+        assert_eq!(
+            result,
+            HashMap::from_iter([
+                ("output_ptr".to_string(), None),
+                ("range_check_ptr".to_string(), None),
+                ("bitwise_ptr".to_string(), Some(Felt252::ZERO.into())),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_serialize_null_no_pointer() {
+        // Setup the KakarotSerde instance
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        // Setup
+        let output_ptr = Relocatable { segment_index: 10, offset: 11 };
+        let range_check_ptr = Felt252::ZERO;
+        let bitwise_ptr = Felt252::from(55);
+
+        // Insert values in memory
+        let base = kakarot_serde
+            .runner
+            .vm
+            .gen_arg(&vec![
+                MaybeRelocatable::RelocatableValue(output_ptr),
+                MaybeRelocatable::Int(range_check_ptr),
+                MaybeRelocatable::Int(bitwise_ptr),
+            ])
+            .unwrap()
+            .get_relocatable()
+            .unwrap();
+
+        // Serialize the pointers of the "ImplicitArgs" struct using the new memory segment.
+        let result = kakarot_serde
+            .serialize_pointers("main.ImplicitArgs", base)
+            .expect("failed to serialize pointers");
+
+        // Assert that the result matches the expected serialized struct members.
+        assert_eq!(
+            result,
+            HashMap::from_iter([
+                ("output_ptr".to_string(), Some(MaybeRelocatable::RelocatableValue(output_ptr))),
+                // Not a pointer so that we shouldn't have a `None`
+                ("range_check_ptr".to_string(), Some(MaybeRelocatable::Int(range_check_ptr))),
+                ("bitwise_ptr".to_string(), Some(MaybeRelocatable::Int(bitwise_ptr))),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_serialize_uint256_0() {
+        // Setup the KakarotSerde instance
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        // U256 to be serialized
+        let x = U256::ZERO;
+
+        // Setup with the high and low parts of the U256
+        let low =
+            Felt252::from_bytes_be_slice(&x.to_be_bytes::<{ U256::BYTES }>()[U128_BYTES_SIZE..]);
+        let high =
+            Felt252::from_bytes_be_slice(&x.to_be_bytes::<{ U256::BYTES }>()[0..U128_BYTES_SIZE]);
+
+        // Insert values in memory
+        let base = kakarot_serde
+            .runner
+            .vm
+            .gen_arg(&vec![MaybeRelocatable::Int(low), MaybeRelocatable::Int(high)])
+            .unwrap()
+            .get_relocatable()
+            .unwrap();
+
+        // Serialize the Uint256 struct using the new memory segment.
+        let result = kakarot_serde.serialize_uint256(base).expect("failed to serialize pointers");
+
+        // Assert that the result is 0.
+        assert_eq!(result, U256::ZERO);
+    }
+
+    #[test]
+    fn test_serialize_uint256_valid() {
+        // Setup the KakarotSerde instance
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        // U256 to be serialized
+        let x =
+            U256::from_str("0x52f8f61201b2b11a78d6e866abc9c3db2ae8631fa656bfe5cb53668255367afb")
+                .unwrap();
+
+        // Setup with the high and low parts of the U256
+        let low =
+            Felt252::from_bytes_be_slice(&x.to_be_bytes::<{ U256::BYTES }>()[U128_BYTES_SIZE..]);
+        let high =
+            Felt252::from_bytes_be_slice(&x.to_be_bytes::<{ U256::BYTES }>()[0..U128_BYTES_SIZE]);
+
+        // Insert values in memory
+        let base = kakarot_serde
+            .runner
+            .vm
+            .gen_arg(&vec![MaybeRelocatable::Int(low), MaybeRelocatable::Int(high)])
+            .unwrap()
+            .get_relocatable()
+            .unwrap();
+
+        // Serialize the Uint256 struct using the new memory segment.
+        let result = kakarot_serde.serialize_uint256(base).expect("failed to serialize pointers");
+
+        // Assert that the result matches the expected U256 value.
+        assert_eq!(result, x);
+    }
+
+    #[test]
+    fn test_serialize_uint256_not_int_high() {
+        // Setup the KakarotSerde instance
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        // U256 to be serialized
+        let x = U256::MAX;
+
+        // Setup with the high and low parts of the U256
+        let low =
+            Felt252::from_bytes_be_slice(&x.to_be_bytes::<{ U256::BYTES }>()[U128_BYTES_SIZE..]);
+        // High is not an Int to trigger the error
+        let high = Relocatable { segment_index: 10, offset: 11 };
+
+        // Insert values in memory
+        let base = kakarot_serde
+            .runner
+            .vm
+            .gen_arg(&vec![MaybeRelocatable::Int(low), MaybeRelocatable::RelocatableValue(high)])
+            .unwrap()
+            .get_relocatable()
+            .unwrap();
+
+        // Try to serialize the Uint256 struct using the new memory segment.
+        let result = kakarot_serde.serialize_uint256(base);
+
+        // Assert that the result is an error with the expected missing field.
+        match result {
+            Err(KakarotSerdeError::MissingField { field }) => {
+                assert_eq!(field, "high");
+            }
+            _ => panic!("Expected a missing field error, but got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_serialize_uint256_not_int_low() {
+        // Setup the KakarotSerde instance
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        // U256 to be serialized
+        let x = U256::MAX;
+
+        // Low is not an Int to trigger the error
+        let low = Relocatable { segment_index: 10, offset: 11 };
+        let high =
+            Felt252::from_bytes_be_slice(&x.to_be_bytes::<{ U256::BYTES }>()[0..U128_BYTES_SIZE]);
+
+        // Insert values in memory
+        let base = kakarot_serde
+            .runner
+            .vm
+            .gen_arg(&vec![MaybeRelocatable::RelocatableValue(low), MaybeRelocatable::Int(high)])
+            .unwrap()
+            .get_relocatable()
+            .unwrap();
+
+        // Try to serialize the Uint256 struct using the new memory segment.
+        let result = kakarot_serde.serialize_uint256(base);
+
+        // Assert that the result is an error with the expected missing field.
+        match result {
+            Err(KakarotSerdeError::MissingField { field }) => {
+                assert_eq!(field, "low");
+            }
+            _ => panic!("Expected a missing field error, but got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_cairo_type_struct_type() {
+        // A dummy scope name for the struct type.
+        let scope_name = "starkware.cairo.common.uint256.Uint256";
+
+        // Create a Cairo type for the struct.
+        let cairo_type = CairoType::struct_type(scope_name, None);
+
+        // Assert that the Cairo type is a struct with the correct scope name.
+        assert_eq!(
+            cairo_type,
+            CairoType::Struct {
+                scope: ScopedName {
+                    path: vec![
+                        "starkware".to_string(),
+                        "cairo".to_string(),
+                        "common".to_string(),
+                        "uint256".to_string(),
+                        "Uint256".to_string()
+                    ]
+                },
+                location: None
+            }
+        );
+
+        // Test with a dummy location
+        let location = Some(Location {
+            end_line: 100,
+            end_col: 454,
+            input_file: InputFile { filename: "test.cairo".to_string() },
+            parent_location: None,
+            start_line: 34,
+            start_col: 234,
+        });
+        let cairo_type_with_location = CairoType::struct_type(scope_name, location.clone());
+        assert_eq!(
+            cairo_type_with_location,
+            CairoType::Struct {
+                scope: ScopedName {
+                    path: vec![
+                        "starkware".to_string(),
+                        "cairo".to_string(),
+                        "common".to_string(),
+                        "uint256".to_string(),
+                        "Uint256".to_string()
+                    ]
+                },
+                location
+            }
+        );
+    }
+
+    #[test]
+    fn test_cairo_type_felt() {
+        // Create a Cairo type for a Felt.
+        let cairo_type = CairoType::felt_type(None);
+
+        // Assert that the Cairo type is a Felt with the correct location.
+        assert_eq!(cairo_type, CairoType::Felt { location: None });
+
+        // Test with a dummy location
+        let location = Some(Location {
+            end_line: 100,
+            end_col: 454,
+            input_file: InputFile { filename: "test.cairo".to_string() },
+            parent_location: None,
+            start_line: 34,
+            start_col: 234,
+        });
+        let cairo_type_with_location = CairoType::felt_type(location.clone());
+        assert_eq!(cairo_type_with_location, CairoType::Felt { location });
+    }
+
+    #[test]
+    fn test_cairo_type_pointer() {
+        // Create a Cairo type for a Pointer.
+        let pointee_type = CairoType::felt_type(None);
+        let cairo_type = CairoType::pointer_type(pointee_type.clone(), None);
+
+        // Assert that the Cairo type is a Pointer with the correct pointee type.
+        assert_eq!(
+            cairo_type,
+            CairoType::Pointer { pointee: Box::new(pointee_type), location: None }
+        );
+
+        // Test with a dummy location
+        let location = Some(Location {
+            end_line: 100,
+            end_col: 454,
+            input_file: InputFile { filename: "test.cairo".to_string() },
+            parent_location: None,
+            start_line: 34,
+            start_col: 234,
+        });
+        let cairo_type_with_location =
+            CairoType::pointer_type(CairoType::felt_type(None), location.clone());
+        assert_eq!(
+            cairo_type_with_location,
+            CairoType::Pointer { pointee: Box::new(CairoType::Felt { location: None }), location }
+        );
+    }
+
+    #[test]
+    fn test_cairo_type_tuple() {
+        // Create Cairo types for Tuple members.
+        let member1 = TupleItem::new(Some("a".to_string()), CairoType::felt_type(None), None);
+        let member2 = TupleItem::new(
+            Some("b".to_string()),
+            CairoType::pointer_type(CairoType::felt_type(None), None),
+            None,
+        );
+
+        // Create a Cairo type for a Tuple.
+        let cairo_type =
+            CairoType::tuple_from_members(vec![member1.clone(), member2.clone()], true, None);
+
+        // Assert that the Cairo type is a Tuple with the correct members and trailing comma flag.
+        assert_eq!(
+            cairo_type,
+            CairoType::Tuple {
+                members: vec![member1, member2],
+                has_trailing_comma: true,
+                location: None
+            }
+        );
+
+        // Test with a dummy location
+        let location = Some(Location {
+            end_line: 100,
+            end_col: 454,
+            input_file: InputFile { filename: "test.cairo".to_string() },
+            parent_location: None,
+            start_line: 34,
+            start_col: 234,
+        });
+        let cairo_type_with_location = CairoType::tuple_from_members(
+            vec![TupleItem::new(None, CairoType::felt_type(None), None)],
+            false,
+            location.clone(),
+        );
+        assert_eq!(
+            cairo_type_with_location,
+            CairoType::Tuple {
+                members: vec![TupleItem::new(None, CairoType::felt_type(None), None)],
+                has_trailing_comma: false,
+                location
+            }
+        );
+    }
+
+    #[test]
+    fn test_cairo_type_parse() {
+        assert_eq!(CairoType::parse("felt"), CairoType::felt_type(None));
+        assert_eq!(
+            CairoType::parse("model.Account**"),
+            CairoType::pointer_type(
+                CairoType::pointer_type(CairoType::struct_type("model.Account", None), None),
+                None
+            )
+        );
+        assert_eq!(
+            CairoType::parse("(a: felt, b: (felt*, Uint256))"),
+            CairoType::tuple_from_members(
+                vec![
+                    TupleItem::new(Some("a".to_string()), CairoType::felt_type(None), None),
+                    TupleItem::new(
+                        Some("b".to_string()),
+                        CairoType::tuple_from_members(
+                            vec![
+                                TupleItem::new(
+                                    None,
+                                    CairoType::pointer_type(CairoType::felt_type(None), None),
+                                    None
+                                ),
+                                TupleItem::new(None, CairoType::struct_type("Uint256", None), None),
+                            ],
+                            false,
+                            None
+                        ),
+                        None
+                    ),
+                ],
+                false,
+                None
+            )
+        );
+        assert_eq!(
+            CairoType::parse("(felt,)"),
+            CairoType::tuple_from_members(
+                vec![TupleItem::new(None, CairoType::felt_type(None), None)],
+                true,
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn test_type_size() {
+        let kakarot_serde = setup_kakarot_serde();
+
+        assert_eq!(kakarot_serde.type_size(&CairoType::parse("felt*")).unwrap(), 1);
+        assert_eq!(kakarot_serde.type_size(&CairoType::parse("Uint256")).unwrap(), 2);
+        assert_eq!(kakarot_serde.type_size(&CairoType::parse("(felt, Uint256)")).unwrap(), 3);
+        assert_eq!(kakarot_serde.type_size(&CairoType::parse("BitwiseBuiltin")).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_serialize_by_type_struct_and_pointers() {
+        // Setup the KakarotSerde instance
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        // `main.ImplicitArgs` with a null `output_ptr` and a `bitwise_ptr` to a partially written
+        // `BitwiseBuiltin`.
+        let args = kakarot_serde.runner.vm.add_memory_segment();
+        let bitwise = kakarot_serde.runner.vm.add_memory_segment();
+        kakarot_serde
+            .runner
+            .vm
+            .load_data(args, &[Felt252::ZERO.into(), Felt252::from(7).into(), bitwise.into()])
+            .unwrap();
+        kakarot_serde
+            .runner
+            .vm
+            .load_data(bitwise, &[Felt252::from(1).into(), Felt252::from(2).into()])
+            .unwrap();
+
+        let value =
+            kakarot_serde.serialize_by_type(&CairoType::parse("main.ImplicitArgs"), args).unwrap();
+
+        let bitwise_value = SerializedValue::Struct {
+            name: "starkware.cairo.common.cairo_builtins.BitwiseBuiltin".to_string(),
+            members: BTreeMap::from([
+                ("x".to_string(), SerializedValue::Felt(Felt252::from(1).into())),
+                ("y".to_string(), SerializedValue::Felt(Felt252::from(2).into())),
+            ]),
+        };
+        assert_eq!(
+            value,
+            SerializedValue::Struct {
+                name: "__main__.main.ImplicitArgs".to_string(),
+                members: BTreeMap::from([
+                    ("output_ptr".to_string(), SerializedValue::Null),
+                    ("range_check_ptr".to_string(), SerializedValue::Felt(Felt252::from(7).into())),
+                    (
+                        "bitwise_ptr".to_string(),
+                        SerializedValue::Pointer {
+                            address: bitwise,
+                            pointee: Some(Box::new(bitwise_value))
+                        }
+                    ),
+                ]),
+            }
+        );
+    }
+
+    #[test]
+    fn test_serialize_by_type_tuple() {
+        // Setup the KakarotSerde instance
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        // A `(felt, Uint256)` tuple.
+        let base = kakarot_serde.runner.vm.add_memory_segment();
+        kakarot_serde
+            .runner
+            .vm
+            .load_data(
+                base,
+                &[Felt252::from(1).into(), Felt252::from(2).into(), Felt252::from(3).into()],
+            )
+            .unwrap();
+
+        let value = kakarot_serde
+            .serialize_by_type(&CairoType::parse("(a: felt, b: Uint256)"), base)
+            .unwrap();
+
+        let SerializedValue::Tuple(items) = value else { panic!("Expected a tuple") };
+        assert_eq!(items[0].0.as_deref(), Some("a"));
+        assert_eq!(items[0].1, SerializedValue::Felt(Felt252::from(1).into()));
+        let (name, SerializedValue::Struct { members, .. }) = &items[1] else {
+            panic!("Expected a struct")
+        };
+        assert_eq!(name.as_deref(), Some("b"));
+        assert_eq!(members["low"], SerializedValue::Felt(Felt252::from(2).into()));
+        assert_eq!(members["high"], SerializedValue::Felt(Felt252::from(3).into()));
+    }
+
+    #[test]
+    fn test_serialize_by_type_errors() {
+        // Setup the KakarotSerde instance
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        let base = kakarot_serde.runner.vm.add_memory_segment();
+        kakarot_serde.runner.vm.load_data(base, &[Felt252::from(42).into()]).unwrap();
+
+        // A non-null felt is not a valid pointer.
+        let pointer = CairoType::parse("felt*");
+        assert!(matches!(
+            kakarot_serde.serialize_by_type(&pointer, base),
+            Err(KakarotSerdeError::InvalidPointer { address, value })
+                if address == base && value == Felt252::from(42)
+        ));
+
+        // Unwritten cells are missing values.
+        let next = (base + 1usize).unwrap();
+        assert!(matches!(
+            kakarot_serde.serialize_by_type(&CairoType::felt_type(None), next),
+            Err(KakarotSerdeError::MissingValue { address }) if address == next
+        ));
+    }
+
+    #[test]
+    fn test_serialize_by_type_shared_pointees() {
+        // Setup the KakarotSerde instance
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        // Two pointers to the same `BitwiseBuiltin`.
+        let base = kakarot_serde.runner.vm.add_memory_segment();
+        let bitwise = kakarot_serde.runner.vm.add_memory_segment();
+        kakarot_serde.runner.vm.load_data(base, &[bitwise.into(), bitwise.into()]).unwrap();
+        kakarot_serde.runner.vm.load_data(bitwise, &[Felt252::from(1).into()]).unwrap();
+        let cairo_type = CairoType::parse("(BitwiseBuiltin*, BitwiseBuiltin*)");
+
+        // By default, the shared pointee is copied.
+        let SerializedValue::Tuple(items) =
+            kakarot_serde.serialize_by_type(&cairo_type, base).unwrap()
+        else {
+            panic!("Expected a tuple")
+        };
+        assert_eq!(items[0].1, items[1].1);
+
+        // The second occurrence is emitted as a reference.
+        let options = SerializeOptions { shared_as_references: true };
+        let SerializedValue::Tuple(items) =
+            kakarot_serde.serialize_by_type_with(&cairo_type, base, options).unwrap()
+        else {
+            panic!("Expected a tuple")
+        };
+        assert!(matches!(
+            &items[0].1,
+            SerializedValue::Pointer { pointee: Some(pointee), .. }
+                if matches!(pointee.as_ref(), SerializedValue::Struct { .. })
+        ));
+        assert_eq!(
+            items[1].1,
+            SerializedValue::Pointer {
+                address: bitwise,
+                pointee: Some(Box::new(SerializedValue::Reference(bitwise)))
+            }
+        );
+    }
+
+    #[test]
+    fn test_serialize_by_type_pointer_cycle() {
+        let mut kakarot_serde = KakarotSerde::builder()
+            .program_bytes(include_bytes!("../../../../cairo/programs/os.json"))
+            .build()
+            .unwrap();
+        let vm = &mut kakarot_serde.runner.vm;
+
+        // A call frame whose parent EVM runs the frame itself: the `model.Message` points to its
+        // `model.Parent`, whose `model.EVM` points back to the message.
+        let root = vm.add_memory_segment();
+        let message = vm.add_memory_segment();
+        let parent = vm.add_memory_segment();
+        let evm = vm.add_memory_segment();
+        vm.load_data(root, &[message.into()]).unwrap();
+        vm.load_data((message + 8usize).unwrap(), &[parent.into()]).unwrap();
+        vm.load_data(parent, &[evm.into()]).unwrap();
+        vm.load_data(evm, &[message.into()]).unwrap();
+
+        let cairo_type = CairoType::parse("src.model.model.Message*");
+        assert!(matches!(
+            kakarot_serde.serialize_by_type(&cairo_type, root),
+            Err(KakarotSerdeError::PointerCycle { cycle }) if cycle == vec![message, parent, evm]
+        ));
+        // The cycle is detected whatever the entry point.
+        let cairo_type = CairoType::parse("src.model.model.EVM*");
+        let entry = kakarot_serde.runner.vm.add_memory_segment();
+        kakarot_serde.runner.vm.load_data(entry, &[evm.into()]).unwrap();
+        assert!(matches!(
+            kakarot_serde.serialize_by_type(&cairo_type, entry),
+            Err(KakarotSerdeError::PointerCycle { cycle }) if cycle == vec![evm, message, parent]
+        ));
+
+        // Two sibling frames sharing their parent, whose EVM runs the message of the caller, do
+        // not form a cycle.
+        let vm = &mut kakarot_serde.runner.vm;
+        let (root, first, second) =
+            (vm.add_memory_segment(), vm.add_memory_segment(), vm.add_memory_segment());
+        let (parent, evm, caller) =
+            (vm.add_memory_segment(), vm.add_memory_segment(), vm.add_memory_segment());
+        vm.load_data(root, &[first.into(), second.into()]).unwrap();
+        vm.load_data((first + 8usize).unwrap(), &[parent.into()]).unwrap();
+        vm.load_data((second + 8usize).unwrap(), &[parent.into()]).unwrap();
+        vm.load_data(parent, &[evm.into()]).unwrap();
+        vm.load_data(evm, &[caller.into()]).unwrap();
+
+        let cairo_type = CairoType::parse("(src.model.model.Message*, src.model.model.Message*)");
+        let SerializedValue::Tuple(copied) =
+            kakarot_serde.serialize_by_type(&cairo_type, root).unwrap()
+        else {
+            panic!("Expected a tuple")
+        };
+        assert!(
+            matches!(&copied[1].1, SerializedValue::Pointer { address, .. } if *address == second)
+        );
+
+        // The shared parent is serialized once.
+        let options = SerializeOptions { shared_as_references: true };
+        let SerializedValue::Tuple(referenced) =
+            kakarot_serde.serialize_by_type_with(&cairo_type, root, options).unwrap()
+        else {
+            panic!("Expected a tuple")
+        };
+        assert_eq!(referenced[0].1, copied[0].1);
+        let SerializedValue::Pointer { pointee: Some(message), .. } = &referenced[1].1 else {
+            panic!("Expected a pointer")
+        };
+        let SerializedValue::Struct { members, .. } = message.as_ref() else {
+            panic!("Expected a struct")
+        };
+        assert_eq!(
+            members["parent"],
+            SerializedValue::Pointer {
+                address: parent,
+                pointee: Some(Box::new(SerializedValue::Reference(parent)))
+            }
+        );
+    }
+
+    #[test]
+    fn test_read_typed_cells() {
+        // Setup the KakarotSerde instance
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        let base = kakarot_serde.runner.vm.add_memory_segment();
+        let pointer = kakarot_serde.runner.vm.add_memory_segment();
+        kakarot_serde
+            .runner
+            .vm
+            .load_data(base, &[Felt252::from(5).into(), pointer.into(), Felt252::ZERO.into()])
+            .unwrap();
+
+        assert_eq!(kakarot_serde.read_felt(base).unwrap(), Felt252::from(5));
+        assert_eq!(kakarot_serde.read_pointer((base + 1usize).unwrap()).unwrap(), Some(pointer));
+        assert_eq!(kakarot_serde.read_pointer((base + 2usize).unwrap()).unwrap(), None);
+        assert_eq!(
+            kakarot_serde.read_cells::<2>(base).unwrap(),
+            [Felt252::from(5).into(), pointer.into()]
+        );
+
+        // A relocatable is not a felt.
+        assert!(matches!(
+            kakarot_serde.read_felt((base + 1usize).unwrap()),
+            Err(KakarotSerdeError::ExpectedFelt { .. })
+        ));
+        // Reading past the written cells fails.
+        assert!(matches!(
+            kakarot_serde.read_cells::<4>(base),
+            Err(KakarotSerdeError::MissingValue { .. })
+        ));
+    }
+}
@@ -0,0 +1,494 @@
+//! Typed serialization of the Kakarot `model.*` structs.
+//!
+//! The serializers read the structs through the program identifiers and return the matching
+//! Rust types, so that consumers of the ExEx don't have to know their memory layout:
+//! - `model.Account` into an [`Account`], with its code and storage.
+//! - `model.TransactionEncoded` into a [`TransactionSignedEcRecovered`].
+//! - `model.Block` into a [`Block`].
+//! - `model.EVM` into a revm [`ExecutionResult`].
+//! - `model.State` into a [`State`], walking the accounts dict.
+
+use super::{storage::StoragePreimages, KakarotSerde, KakarotSerdeError};
+use crate::{
+    halt::ExecutionStatus,
+    model::{ConversionError, KethMaybeRelocatable, U128_BYTES_SIZE},
+    rlp::{decode_transaction, encode_signed_transaction},
+};
+use alloy_consensus::Header;
+use alloy_primitives::{Address, Bloom, Bytes, Log, LogData, B256, U256};
+use cairo_vm::{
+    types::relocatable::{MaybeRelocatable, Relocatable},
+    Felt252,
+};
+use reth_primitives::{
+    revm_primitives::{ExecutionResult, Output, SuccessReason},
+    Signature, TransactionSignedEcRecovered,
+};
+use std::collections::HashMap;
+
+/// The `reverted` code of an EVM reverted by the `REVERT` opcode, see `Errors.REVERT`.
+pub const REVERTED: u64 = 1;
+
+/// The `reverted` code of an EVM stopped by an exceptional halt, see `Errors.EXCEPTIONAL_HALT`.
+pub const EXCEPTIONAL_HALT: u64 = 2;
+
+/// The number of felts of a serialized signature: `[r.low, r.high, s.low, s.high, v]`.
+const SIGNATURE_LEN: usize = 5;
+
+/// An EVM account, as read from a `model.Account`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Account {
+    /// The bytecode of the account.
+    pub code: Bytes,
+    /// The hash of the bytecode, if computed.
+    pub code_hash: Option<B256>,
+    /// The nonce of the account.
+    pub nonce: u64,
+    /// The balance of the account, zero when not loaded.
+    pub balance: U256,
+    /// The storage slots written during the execution.
+    pub storage: HashMap<U256, U256>,
+    /// Whether the account was self-destructed.
+    pub selfdestruct: bool,
+    /// Whether the account was created during the transaction.
+    pub created: bool,
+}
+
+/// A native token transfer, as read from a `model.Transfer`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transfer {
+    /// The Starknet address of the sender.
+    pub sender: Felt252,
+    /// The Starknet address of the recipient.
+    pub recipient: Felt252,
+    /// The amount transferred.
+    pub amount: U256,
+}
+
+/// The state of an execution, as read from a `model.State`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct State {
+    /// The accounts touched by the execution, by address.
+    pub accounts: HashMap<Address, Account>,
+    /// The logs emitted, in order.
+    pub logs: Vec<Log>,
+    /// The native token transfers, in order.
+    pub transfers: Vec<Transfer>,
+}
+
+/// A block, as read from a `model.Block`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    /// The header of the block.
+    pub header: Header,
+    /// The transactions of the block, with their sender.
+    pub transactions: Vec<TransactionSignedEcRecovered>,
+}
+
+/// A view over the members of a struct in memory.
+struct StructView<'a> {
+    /// The serializer reading the memory.
+    serde: &'a KakarotSerde,
+    /// The address of each member, by name.
+    members: HashMap<String, Relocatable>,
+}
+
+impl<'a> StructView<'a> {
+    /// Creates a view over the struct with the given name at `ptr`.
+    fn new(
+        serde: &'a KakarotSerde,
+        name: &str,
+        ptr: Relocatable,
+    ) -> Result<Self, KakarotSerdeError> {
+        let layout = serde.struct_members(name)?;
+        let members = layout
+            .members
+            .iter()
+            .map(|member| Ok((member.name.clone(), (ptr + member.offset)?)))
+            .collect::<Result<_, KakarotSerdeError>>()?;
+        Ok(Self { serde, members })
+    }
+
+    /// Returns the address of a member.
+    fn address(&self, name: &str) -> Result<Relocatable, KakarotSerdeError> {
+        self.members
+            .get(name)
+            .copied()
+            .ok_or_else(|| KakarotSerdeError::MissingField { field: name.to_string() })
+    }
+
+    /// Reads a felt member.
+    fn felt(&self, name: &str) -> Result<Felt252, KakarotSerdeError> {
+        self.serde.read_felt(self.address(name)?)
+    }
+
+    /// Reads a felt member holding an integer of at most 64 bits.
+    fn u64(&self, name: &str) -> Result<u64, KakarotSerdeError> {
+        Ok(u64::try_from(&KethMaybeRelocatable::from(self.felt(name)?))?)
+    }
+
+    /// Reads a felt member holding an EVM address.
+    fn evm_address(&self, name: &str) -> Result<Address, KakarotSerdeError> {
+        Ok(Address::try_from(&KethMaybeRelocatable::from(self.felt(name)?))?)
+    }
+
+    /// Reads a felt member holding a boolean, any non-zero value being `true`.
+    fn bool(&self, name: &str) -> Result<bool, KakarotSerdeError> {
+        Ok(self.felt(name)? != Felt252::ZERO)
+    }
+
+    /// Reads a `Uint256` member.
+    fn uint256(&self, name: &str) -> Result<U256, KakarotSerdeError> {
+        self.serde.serialize_uint256(self.address(name)?)
+    }
+
+    /// Reads a pointer member, `None` being the null pointer.
+    fn pointer(&self, name: &str) -> Result<Option<Relocatable>, KakarotSerdeError> {
+        self.serde.read_pointer(self.address(name)?)
+    }
+
+    /// Reads a pointer member which must not be null.
+    fn non_null(&self, name: &str) -> Result<Relocatable, KakarotSerdeError> {
+        self.pointer(name)?
+            .ok_or_else(|| KakarotSerdeError::MissingField { field: name.to_string() })
+    }
+
+    /// Reads a `Uint256*` member, `None` being the null pointer.
+    fn uint256_pointer(&self, name: &str) -> Result<Option<U256>, KakarotSerdeError> {
+        self.pointer(name)?.map(|ptr| self.serde.serialize_uint256(ptr)).transpose()
+    }
+
+    /// Reads the `len` felts pointed to by the `data` member.
+    fn felts(&self, len: &str, data: &str) -> Result<Vec<Felt252>, KakarotSerdeError> {
+        let len = self.u64(len)? as usize;
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+
+        let data = self.non_null(data)?;
+        (0..len).map(|offset| self.serde.read_felt((data + offset)?)).collect()
+    }
+
+    /// Reads the `len` bytes, one per felt, pointed to by the `data` member.
+    fn bytes(&self, len: &str, data: &str) -> Result<Bytes, KakarotSerdeError> {
+        self.felts(len, data)?
+            .iter()
+            .map(|felt| Ok(u8::try_from(&KethMaybeRelocatable::from(*felt))?))
+            .collect()
+    }
+
+    /// Reads a `model.Option` member holding an integer of at most 64 bits.
+    fn option_u64(&self, name: &str) -> Result<Option<u64>, KakarotSerdeError> {
+        let option = StructView::new(self.serde, "model.Option", self.address(name)?)?;
+        if !option.bool("is_some")? {
+            return Ok(None);
+        }
+        option.u64("value").map(Some)
+    }
+
+    /// Reads a `model.Option` member holding a hash, either as a pointer to a `Uint256` or as a
+    /// felt.
+    fn option_b256(&self, name: &str) -> Result<Option<B256>, KakarotSerdeError> {
+        let option = StructView::new(self.serde, "model.Option", self.address(name)?)?;
+        if !option.bool("is_some")? {
+            return Ok(None);
+        }
+        match self.serde.read_address(option.address("value")?)? {
+            MaybeRelocatable::RelocatableValue(ptr) => {
+                Ok(Some(self.serde.serialize_uint256(ptr)?.into()))
+            }
+            MaybeRelocatable::Int(value) => Ok(Some(value.to_bytes_be().into())),
+        }
+    }
+}
+
+impl KakarotSerde {
+    /// Serializes a `model.Account`, resolving its storage slots with the given preimages.
+    pub fn serialize_account(
+        &self,
+        ptr: Relocatable,
+        preimages: &StoragePreimages,
+    ) -> Result<Account, KakarotSerdeError> {
+        let account = StructView::new(self, "model.Account", ptr)?;
+        Ok(Account {
+            code: account.bytes("code_len", "code")?,
+            code_hash: account.uint256_pointer("code_hash")?.map(Into::into),
+            nonce: account.u64("nonce")?,
+            balance: account.uint256_pointer("balance")?.unwrap_or_default(),
+            storage: self.serialize_account_storage(ptr, preimages)?,
+            selfdestruct: account.bool("selfdestruct")?,
+            created: account.bool("created")?,
+        })
+    }
+
+    /// Serializes a `model.TransactionEncoded` into the signed transaction and its sender.
+    pub fn serialize_transaction(
+        &self,
+        ptr: Relocatable,
+    ) -> Result<TransactionSignedEcRecovered, KakarotSerdeError> {
+        let transaction = StructView::new(self, "model.TransactionEncoded", ptr)?;
+
+        let unsigned = transaction.bytes("rlp_len", "rlp")?;
+        let signature = transaction.felts("signature_len", "signature")?;
+        let [r_low, r_high, s_low, s_high, v] = signature.as_slice() else {
+            return Err(ConversionError::InvalidLength {
+                expected: SIGNATURE_LEN,
+                actual: signature.len(),
+            }
+            .into());
+        };
+        let signature = Signature::from_rs_and_parity(
+            uint256(r_low, r_high),
+            uint256(s_low, s_high),
+            u64::try_from(&KethMaybeRelocatable::from(*v))?,
+        )
+        .map_err(ConversionError::from)?;
+
+        let signed = decode_transaction(&encode_signed_transaction(&unsigned, &signature)?)?;
+        Ok(TransactionSignedEcRecovered::from_signed_transaction(
+            signed,
+            transaction.evm_address("sender")?,
+        ))
+    }
+
+    /// Serializes a `model.Block` into its header and transactions.
+    pub fn serialize_block(&self, ptr: Relocatable) -> Result<Block, KakarotSerdeError> {
+        let block = StructView::new(self, "model.Block", ptr)?;
+
+        let transactions_len = block.u64("transactions_len")? as usize;
+        let mut transactions = Vec::with_capacity(transactions_len);
+        if transactions_len > 0 {
+            let size = self.describe_struct("model.TransactionEncoded")?.size;
+            let base = block.non_null("transactions")?;
+            for index in 0..transactions_len {
+                transactions.push(self.serialize_transaction((base + index * size)?)?);
+            }
+        }
+
+        Ok(Block {
+            header: self.serialize_block_header(block.non_null("block_header")?)?,
+            transactions,
+        })
+    }
+
+    /// Serializes a `model.BlockHeader`.
+    pub fn serialize_block_header(&self, ptr: Relocatable) -> Result<Header, KakarotSerdeError> {
+        let header = StructView::new(self, "model.BlockHeader", ptr)?;
+
+        let bloom = header.non_null("bloom")?;
+        let mut logs_bloom = Bloom::default();
+        for (index, chunk) in logs_bloom.0.chunks_mut(U128_BYTES_SIZE).enumerate() {
+            let value = self.read_felt((bloom + index)?)?.to_bytes_be();
+            chunk.copy_from_slice(&value[value.len() - U128_BYTES_SIZE..]);
+        }
+
+        Ok(Header {
+            parent_hash: header.uint256("parent_hash")?.into(),
+            ommers_hash: header.uint256("ommers_hash")?.into(),
+            beneficiary: header.evm_address("coinbase")?,
+            state_root: header.uint256("state_root")?.into(),
+            transactions_root: header.uint256("transactions_root")?.into(),
+            receipts_root: header.uint256("receipt_root")?.into(),
+            withdrawals_root: header.option_b256("withdrawals_root")?,
+            logs_bloom,
+            difficulty: header.uint256("difficulty")?,
+            number: header.u64("number")?,
+            gas_limit: header.u64("gas_limit")?,
+            gas_used: header.u64("gas_used")?,
+            timestamp: header.u64("timestamp")?,
+            mix_hash: header.uint256("mix_hash")?.into(),
+            nonce: header.u64("nonce")?.into(),
+            base_fee_per_gas: header.option_u64("base_fee_per_gas")?,
+            blob_gas_used: header.option_u64("blob_gas_used")?,
+            excess_blob_gas: header.option_u64("excess_blob_gas")?,
+            parent_beacon_block_root: header.option_b256("parent_beacon_block_root")?,
+            requests_root: header.option_b256("requests_root")?,
+            extra_data: header.bytes("extra_data_len", "extra_data")?,
+        })
+    }
+
+    /// Serializes a `model.EVM` at the end of a transaction into its execution result.
+    ///
+    /// The gas used is derived from the gas limit of the transaction and the gas left, minus the
+    /// refund capped to a fifth of the gas used as in `Interpreter.execute`, and the logs are read
+    /// from the `model.State` of the transaction. The status is read from the `reverted` code, and
+    /// the reason of an exceptional halt from the error message written to the return data.
+    pub fn serialize_evm(
+        &self,
+        evm_ptr: Relocatable,
+        state_ptr: Relocatable,
+        gas_limit: u64,
+    ) -> Result<ExecutionResult, KakarotSerdeError> {
+        let evm = StructView::new(self, "model.EVM", evm_ptr)?;
+        let message = StructView::new(self, "model.Message", evm.non_null("message")?)?;
+
+        let output = evm.bytes("return_data_len", "return_data")?;
+        let required_gas = gas_limit.saturating_sub(evm.u64("gas_left")?);
+        let gas_refunded = evm.u64("gas_refund")?.min(required_gas / 5);
+        let gas_used = required_gas - gas_refunded;
+
+        let code = evm.u64("reverted")?;
+        let status = ExecutionStatus::from_kakarot(code, &output)
+            .ok_or(KakarotSerdeError::InvalidRevertedCode { code })?;
+        Ok(match status {
+            ExecutionStatus::Success => ExecutionResult::Success {
+                reason: if output.is_empty() { SuccessReason::Stop } else { SuccessReason::Return },
+                gas_used,
+                gas_refunded,
+                logs: self.serialize_logs(state_ptr)?,
+                output: if message.bool("is_create")? {
+                    Output::Create(output, Some(message.evm_address("address")?))
+                } else {
+                    Output::Call(output)
+                },
+            },
+            ExecutionStatus::Revert => ExecutionResult::Revert { gas_used, output },
+            ExecutionStatus::Halt(halt) => {
+                let reason = halt.halt_reason().ok_or_else(|| KakarotSerdeError::UnknownHalt {
+                    message: String::from_utf8_lossy(&output).into_owned(),
+                })?;
+                ExecutionResult::Halt { reason, gas_used }
+            }
+        })
+    }
+
+    /// Serializes a `model.State`, walking its accounts dict.
+    ///
+    /// Accounts set to a null pointer are skipped.
+    pub fn serialize_state(
+        &self,
+        ptr: Relocatable,
+        preimages: &StoragePreimages,
+    ) -> Result<State, KakarotSerdeError> {
+        let state = StructView::new(self, "model.State", ptr)?;
+
+        let mut accounts = HashMap::new();
+        let (start, end) = (state.non_null("accounts_start")?, state.non_null("accounts")?);
+        for (key, value) in self.dict_entries(start, end)? {
+            let address = Address::try_from(&KethMaybeRelocatable::from(key))
+                .map_err(|_| KakarotSerdeError::InvalidAddress { key })?;
+            let Some(account) = self.dict_pointer(value, start)? else { continue };
+            accounts.insert(address, self.serialize_account(account, preimages)?);
+        }
+
+        let transfers_len = state.u64("transfers_len")? as usize;
+        let mut transfers = Vec::with_capacity(transfers_len);
+        if transfers_len > 0 {
+            let size = self.describe_struct("model.Transfer")?.size;
+            let base = state.non_null("transfers")?;
+            for index in 0..transfers_len {
+                let transfer = StructView::new(self, "model.Transfer", (base + index * size)?)?;
+                transfers.push(Transfer {
+                    sender: transfer.felt("sender")?,
+                    recipient: transfer.felt("recipient")?,
+                    amount: transfer.uint256("amount")?,
+                });
+            }
+        }
+
+        Ok(State { accounts, logs: self.serialize_logs(ptr)?, transfers })
+    }
+
+    /// Serializes the events of a `model.State` into logs, in order.
+    pub fn serialize_logs(&self, state_ptr: Relocatable) -> Result<Vec<Log>, KakarotSerdeError> {
+        let state = StructView::new(self, "model.State", state_ptr)?;
+
+        let events_len = state.u64("events_len")? as usize;
+        let mut logs = Vec::with_capacity(events_len);
+        if events_len > 0 {
+            let size = self.describe_struct("model.Event")?.size;
+            let base = state.non_null("events")?;
+            for index in 0..events_len {
+                logs.push(self.serialize_event((base + index * size)?)?);
+            }
+        }
+        Ok(logs)
+    }
+
+    /// Serializes a `model.Event` into a log.
+    ///
+    /// The `topics` hold the address of the emitting contract followed by the topics as `Uint256`
+    /// (low, high) pairs, `topics_len` counting felts.
+    pub fn serialize_event(&self, ptr: Relocatable) -> Result<Log, KakarotSerdeError> {
+        let event = StructView::new(self, "model.Event", ptr)?;
+
+        let topics = event.felts("topics_len", "topics")?;
+        let Some((address, topics)) = topics.split_first() else {
+            return Err(KakarotSerdeError::MissingField { field: "topics".to_string() });
+        };
+        let address = Address::try_from(&KethMaybeRelocatable::from(*address))?;
+        let topics = topics.chunks_exact(2).map(|topic| uint256(&topic[0], &topic[1]).into());
+
+        Ok(Log {
+            address,
+            data: LogData::new_unchecked(topics.collect(), event.bytes("data_len", "data")?),
+        })
+    }
+}
+
+/// Combines the `low` and `high` felts of a `Uint256`.
+fn uint256(low: &Felt252, high: &Felt252) -> U256 {
+    let low = low.to_bytes_be();
+    let high = high.to_bytes_be();
+    U256::from_be_slice(&[&high[U128_BYTES_SIZE..], &low[U128_BYTES_SIZE..]].concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_kakarot_serde() -> KakarotSerde {
+        KakarotSerde::builder()
+            .program_bytes(include_bytes!("../../testdata/keccak_add_uint256.json"))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_struct_view() {
+        let mut kakarot_serde = setup_kakarot_serde();
+        let bitwise = kakarot_serde.write(&U256::from(3)).unwrap();
+        let ptr = kakarot_serde
+            .write_struct(
+                "main.ImplicitArgs",
+                &[
+                    ("output_ptr", &None::<u64>),
+                    ("range_check_ptr", &Felt252::from(12)),
+                    ("bitwise_ptr", &bitwise),
+                ],
+            )
+            .unwrap();
+
+        let view = StructView::new(&kakarot_serde, "main.ImplicitArgs", ptr).unwrap();
+        assert_eq!(view.u64("range_check_ptr").unwrap(), 12);
+        assert!(view.bool("range_check_ptr").unwrap());
+        assert_eq!(view.pointer("output_ptr").unwrap(), None);
+        assert_eq!(view.uint256_pointer("bitwise_ptr").unwrap(), Some(U256::from(3)));
+        assert_eq!(view.uint256_pointer("output_ptr").unwrap(), None);
+        assert!(matches!(
+            view.non_null("output_ptr"),
+            Err(KakarotSerdeError::MissingField { field }) if field == "output_ptr"
+        ));
+    }
+
+    #[test]
+    fn test_struct_view_bytes() {
+        let mut kakarot_serde = setup_kakarot_serde();
+        let data = kakarot_serde
+            .write_cells(&[0xabu8, 0xcd, 0xef].map(|byte| Felt252::from(byte).into()))
+            .unwrap();
+        // A `Uint256` standing for a `(len, felt*)` pair.
+        let ptr =
+            kakarot_serde.write_struct("Uint256", &[("low", &3u64), ("high", &data)]).unwrap();
+
+        let view = StructView::new(&kakarot_serde, "Uint256", ptr).unwrap();
+        assert_eq!(view.bytes("low", "high").unwrap(), Bytes::from(vec![0xab, 0xcd, 0xef]));
+        assert!(matches!(view.evm_address("high"), Err(KakarotSerdeError::ExpectedFelt { .. })));
+    }
+
+    #[test]
+    fn test_uint256() {
+        let value = uint256(&Felt252::from(2), &Felt252::from(1));
+        assert_eq!(value, (U256::from(1) << 128) + U256::from(2));
+    }
+}
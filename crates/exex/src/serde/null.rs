@@ -0,0 +1,143 @@
+//! Null-pointer semantics of the serialized struct members.
+//!
+//! By default, a `0` stored in a pointer member (e.g. `parent=cast(0, model.Parent*)`) is a null
+//! pointer. Some structs give a different meaning to zero casts, or store nullable values in felt
+//! members: the [`NullPointerRegistry`] overrides the default per struct or per member.
+
+use cairo_vm::{types::relocatable::MaybeRelocatable, Felt252};
+use std::collections::HashMap;
+
+/// When a `0` stored in a struct member is a null pointer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum NullPolicy {
+    /// `0` is null in pointer members only, i.e. members whose type ends with `*`.
+    #[default]
+    PointerZero,
+    /// `0` is null whatever the type of the member.
+    Zero,
+    /// `0` is never null, it is always kept as a value.
+    Never,
+}
+
+impl NullPolicy {
+    /// Returns whether the value of a member of the given Cairo type is null.
+    pub fn is_null(&self, value: &MaybeRelocatable, cairo_type: &str) -> bool {
+        let is_zero = *value == MaybeRelocatable::Int(Felt252::ZERO);
+        match self {
+            Self::PointerZero => is_zero && cairo_type.trim_end().ends_with('*'),
+            Self::Zero => is_zero,
+            Self::Never => false,
+        }
+    }
+}
+
+/// The value of a struct member read from memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemberValue {
+    /// The value written in memory.
+    Value(MaybeRelocatable),
+    /// A null pointer, according to the [`NullPolicy`] of the member.
+    Null,
+    /// The member is not written in memory.
+    Absent,
+}
+
+impl MemberValue {
+    /// Returns the value, if written and not null.
+    pub const fn value(&self) -> Option<&MaybeRelocatable> {
+        match self {
+            Self::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the member is a null pointer.
+    pub const fn is_null(&self) -> bool {
+        matches!(self, Self::Null)
+    }
+
+    /// Returns whether the member is not written in memory.
+    pub const fn is_absent(&self) -> bool {
+        matches!(self, Self::Absent)
+    }
+
+    /// Converts the member into the `Option<Option<_>>` form, `None` being an absent member and
+    /// `Some(None)` a null pointer.
+    pub fn into_option(self) -> Option<Option<MaybeRelocatable>> {
+        match self {
+            Self::Value(value) => Some(Some(value)),
+            Self::Null => Some(None),
+            Self::Absent => None,
+        }
+    }
+}
+
+/// A registry of the [`NullPolicy`] of the struct members.
+///
+/// Structs are identified by their full name, e.g. `src.model.model.Account`. A member policy
+/// takes precedence over the policy of its struct, which takes precedence over the default
+/// [`NullPolicy::PointerZero`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NullPointerRegistry {
+    /// The policies of the structs, by full name.
+    structs: HashMap<String, NullPolicy>,
+    /// The policies of the members, by struct full name and member name.
+    members: HashMap<(String, String), NullPolicy>,
+}
+
+impl NullPointerRegistry {
+    /// Sets the policy of all the members of a struct.
+    pub fn set_struct(&mut self, struct_name: &str, policy: NullPolicy) -> &mut Self {
+        self.structs.insert(struct_name.to_string(), policy);
+        self
+    }
+
+    /// Sets the policy of a single member of a struct.
+    pub fn set_member(&mut self, struct_name: &str, member: &str, policy: NullPolicy) -> &mut Self {
+        self.members.insert((struct_name.to_string(), member.to_string()), policy);
+        self
+    }
+
+    /// Returns the policy of a struct member.
+    pub fn policy(&self, struct_name: &str, member: &str) -> NullPolicy {
+        self.members
+            .get(&(struct_name.to_string(), member.to_string()))
+            .or_else(|| self.structs.get(struct_name))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cairo_vm::types::relocatable::Relocatable;
+
+    #[test]
+    fn test_null_policy() {
+        let zero = MaybeRelocatable::Int(Felt252::ZERO);
+        let one = MaybeRelocatable::Int(Felt252::ONE);
+        let offset_zero = MaybeRelocatable::RelocatableValue(Relocatable::from((1, 0)));
+
+        assert!(NullPolicy::PointerZero.is_null(&zero, "model.Parent*"));
+        assert!(!NullPolicy::PointerZero.is_null(&zero, "felt"));
+        assert!(!NullPolicy::PointerZero.is_null(&offset_zero, "felt*"));
+        assert!(NullPolicy::Zero.is_null(&zero, "felt"));
+        assert!(!NullPolicy::Zero.is_null(&one, "felt"));
+        assert!(!NullPolicy::Never.is_null(&zero, "felt*"));
+    }
+
+    #[test]
+    fn test_registry_precedence() {
+        let mut registry = NullPointerRegistry::default();
+        registry.set_struct("model.Account", NullPolicy::Never).set_member(
+            "model.Account",
+            "code",
+            NullPolicy::Zero,
+        );
+
+        assert_eq!(registry.policy("model.Account", "code"), NullPolicy::Zero);
+        assert_eq!(registry.policy("model.Account", "storage"), NullPolicy::Never);
+        assert_eq!(registry.policy("model.State", "accounts"), NullPolicy::PointerZero);
+    }
+}
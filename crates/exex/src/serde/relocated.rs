@@ -0,0 +1,169 @@
+//! Relocated memories, as persisted after a run.
+//!
+//! Once relocated, the segments of a run are laid out one after the other in a flat memory of
+//! felts, the relocatable values being replaced by their absolute address. A [`KakarotSerde`]
+//! built over a [`RelocatedMemory`] decodes such memories with the identifiers of the program,
+//! without re-running the VM: addresses are the absolute addresses, represented as offsets in the
+//! [`RELOCATED_SEGMENT`], and the non-zero felts read as pointers are followed as absolute
+//! addresses.
+//!
+//! [`KakarotSerde`]: super::KakarotSerde
+
+use cairo_vm::{types::relocatable::Relocatable, vm::runners::cairo_runner::CairoRunner, Felt252};
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+};
+
+/// The segment index of the absolute addresses of a relocated memory.
+pub const RELOCATED_SEGMENT: isize = 0;
+
+/// The size of an encoded memory cell: its address in 64-bit little endian followed by its value
+/// in 256-bit little endian, as written by [`crate::air::write_memory`].
+const CELL_BYTES: usize = 8 + 32;
+
+/// A relocated memory, indexed by absolute address.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RelocatedMemory {
+    /// The value of each cell, `None` when not written.
+    cells: Vec<Option<Felt252>>,
+}
+
+impl RelocatedMemory {
+    /// Creates a [`RelocatedMemory`] from its cells, indexed by absolute address.
+    pub fn from_cells(cells: Vec<Option<Felt252>>) -> Self {
+        Self { cells }
+    }
+
+    /// Returns the relocated memory of an ended runner.
+    pub fn from_runner(runner: &CairoRunner) -> Self {
+        Self::from_cells(runner.relocated_memory.clone())
+    }
+
+    /// Reads a memory encoded as by [`crate::air::write_memory`].
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        if bytes.len() % CELL_BYTES != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("truncated memory cell, {} trailing bytes", bytes.len() % CELL_BYTES),
+            ));
+        }
+
+        let mut cells = Vec::new();
+        for cell in bytes.chunks_exact(CELL_BYTES) {
+            let (address, value) = cell.split_at(8);
+            let address = u64::from_le_bytes(address.try_into().expect("8 bytes")) as usize;
+            let value = Felt252::from_bytes_le(value.try_into().expect("32 bytes"));
+            if cells.len() <= address {
+                cells.resize(address + 1, None);
+            }
+            cells[address] = Some(value);
+        }
+        Ok(Self { cells })
+    }
+
+    /// Loads a memory file encoded as by [`crate::air::write_memory`].
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Returns the value at an absolute address, if written.
+    pub fn get(&self, address: usize) -> Option<Felt252> {
+        self.cells.get(address).copied().flatten()
+    }
+
+    /// Returns the number of addressable cells, written or not.
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Returns whether the memory has no cell.
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Returns the [`Relocatable`] representing an absolute address.
+    pub fn address(address: usize) -> Relocatable {
+        Relocatable::from((RELOCATED_SEGMENT, address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        air::write_memory,
+        serde::{CairoType, KakarotSerde, SerializedValue},
+    };
+    use cairo_vm::types::relocatable::MaybeRelocatable;
+
+    #[test]
+    fn test_read_relocated_memory() {
+        let cells = vec![None, Some(Felt252::from(7)), None, Some(Felt252::MAX)];
+        let mut bytes = Vec::new();
+        write_memory(&cells, &mut bytes).unwrap();
+
+        let memory = RelocatedMemory::read(bytes.as_slice()).unwrap();
+        assert_eq!(memory, RelocatedMemory::from_cells(cells));
+        assert_eq!(memory.get(1), Some(Felt252::from(7)));
+        assert_eq!(memory.get(2), None);
+        assert_eq!(memory.get(10), None);
+
+        let err = RelocatedMemory::read(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_serialize_relocated_memory() {
+        // `main.ImplicitArgs` at 10, with a null `output_ptr`, a `range_check_ptr` and a
+        // `bitwise_ptr` to a `BitwiseBuiltin` at 20.
+        let mut cells = vec![None; 25];
+        cells[10] = Some(Felt252::ZERO);
+        cells[11] = Some(Felt252::from(30));
+        cells[12] = Some(Felt252::from(20));
+        for (offset, value) in [3, 5, 1, 6, 7].into_iter().enumerate() {
+            cells[20 + offset] = Some(Felt252::from(value));
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memory.bin");
+        write_memory(&cells, File::create(&path).unwrap()).unwrap();
+
+        let kakarot_serde = KakarotSerde::builder()
+            .program_bytes(include_bytes!("../../testdata/keccak_add_uint256.json"))
+            .relocated_memory(RelocatedMemory::load(&path).unwrap())
+            .build()
+            .unwrap();
+        assert!(kakarot_serde.is_relocated());
+
+        let args = RelocatedMemory::address(10);
+        let members = kakarot_serde.serialize_pointers("main.ImplicitArgs", args).unwrap();
+        assert_eq!(members["output_ptr"], None);
+        assert_eq!(members["range_check_ptr"], Some(MaybeRelocatable::Int(Felt252::from(30))));
+        assert_eq!(
+            members["bitwise_ptr"],
+            Some(MaybeRelocatable::RelocatableValue(RelocatedMemory::address(20)))
+        );
+
+        let bitwise = kakarot_serde
+            .read_pointer((args + 2usize).unwrap())
+            .unwrap()
+            .expect("non-null bitwise pointer");
+        assert_eq!(kakarot_serde.read_felt((bitwise + 3usize).unwrap()).unwrap(), 6.into());
+
+        // Pointers are followed as absolute addresses.
+        let value =
+            kakarot_serde.serialize_by_type(&CairoType::parse("main.ImplicitArgs"), args).unwrap();
+        let SerializedValue::Struct { members, .. } = value else {
+            panic!("Expected a struct");
+        };
+        assert!(matches!(
+            &members["bitwise_ptr"],
+            SerializedValue::Pointer { address, pointee: Some(_) }
+                if *address == RelocatedMemory::address(20)
+        ));
+    }
+}
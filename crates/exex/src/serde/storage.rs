@@ -0,0 +1,288 @@
+//! Serialization of the storage dicts into per-account slot maps.
+//!
+//! The Cairo program keys the accounts dict by EVM address, and the storage dict of each account
+//! by `pedersen(slot.low, slot.high)`. Slots cannot be recovered from these keys, so the
+//! preimages tracked while running the hints are used to rebuild slot maps comparable with the
+//! reth state: the `state` hint records the preimages of the slots of the pre-state in the
+//! execution scopes of the run, see [`STORAGE_PREIMAGES_SCOPE`].
+
+use super::{KakarotSerde, KakarotSerdeError};
+use crate::model::U128_BYTES_SIZE;
+use alloy_primitives::{Address, U256};
+use cairo_vm::{
+    types::{
+        exec_scope::ExecutionScopes,
+        relocatable::{MaybeRelocatable, Relocatable},
+    },
+    Felt252,
+};
+use starknet_types_core::hash::{Pedersen, StarkHash};
+use std::collections::HashMap;
+
+/// The name of the execution scope variable holding the [`StoragePreimages`] recorded by the
+/// hints.
+pub const STORAGE_PREIMAGES_SCOPE: &str = "__storage_preimages";
+
+/// The size of a `DictAccess` entry: `key`, `prev_value` and `new_value`.
+const DICT_ACCESS_SIZE: usize = 3;
+
+/// The storage slots hashed into storage dict keys, by key.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StoragePreimages {
+    /// The slots, by storage dict key.
+    slots: HashMap<Felt252, U256>,
+}
+
+impl StoragePreimages {
+    /// Returns the storage dict key of a slot, i.e. `pedersen(slot.low, slot.high)`.
+    pub fn storage_key(slot: U256) -> Felt252 {
+        let bytes = slot.to_be_bytes::<{ U256::BYTES }>();
+        let low = Felt252::from_bytes_be_slice(&bytes[U128_BYTES_SIZE..]);
+        let high = Felt252::from_bytes_be_slice(&bytes[..U128_BYTES_SIZE]);
+        Pedersen::hash(&low, &high)
+    }
+
+    /// Tracks a slot, returning its storage dict key.
+    pub fn insert(&mut self, slot: U256) -> Felt252 {
+        let key = Self::storage_key(slot);
+        self.slots.insert(key, slot);
+        key
+    }
+
+    /// Tracks a preimage whose key was already computed, e.g. by a hint.
+    pub fn record(&mut self, key: Felt252, slot: U256) {
+        self.slots.insert(key, slot);
+    }
+
+    /// Returns the preimages recorded by the hints of a run in its execution scopes, if any.
+    pub fn from_scopes(scopes: &ExecutionScopes) -> Option<&Self> {
+        scopes.get_ref(STORAGE_PREIMAGES_SCOPE).ok()
+    }
+
+    /// Returns the slot hashed into the given key, if tracked.
+    pub fn get(&self, key: &Felt252) -> Option<U256> {
+        self.slots.get(key).copied()
+    }
+
+    /// Returns the number of tracked preimages.
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns whether no preimage is tracked.
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+}
+
+impl FromIterator<U256> for StoragePreimages {
+    fn from_iter<T: IntoIterator<Item = U256>>(iter: T) -> Self {
+        let mut preimages = Self::default();
+        iter.into_iter().for_each(|slot| {
+            preimages.insert(slot);
+        });
+        preimages
+    }
+}
+
+impl KakarotSerde {
+    /// Serializes the storage of all the accounts of a `model.State`.
+    ///
+    /// Accounts set to a null pointer are skipped, and accounts without storage writes map to an
+    /// empty slot map.
+    pub fn serialize_state_storage(
+        &self,
+        state_ptr: Relocatable,
+        preimages: &StoragePreimages,
+    ) -> Result<HashMap<Address, HashMap<U256, U256>>, KakarotSerdeError> {
+        let (start, end) = self.dict_bounds("model.State", state_ptr, "accounts")?;
+
+        let mut output = HashMap::new();
+        for (key, value) in self.dict_entries(start, end)? {
+            let bytes = key.to_bytes_be();
+            if bytes[..12].iter().any(|byte| *byte != 0) {
+                return Err(KakarotSerdeError::InvalidAddress { key });
+            }
+            let Some(account_ptr) = self.dict_pointer(value, start)? else { continue };
+
+            let storage = self.serialize_account_storage(account_ptr, preimages)?;
+            output.insert(Address::from_slice(&bytes[12..]), storage);
+        }
+
+        Ok(output)
+    }
+
+    /// Serializes the storage of a `model.Account`.
+    pub fn serialize_account_storage(
+        &self,
+        account_ptr: Relocatable,
+        preimages: &StoragePreimages,
+    ) -> Result<HashMap<U256, U256>, KakarotSerdeError> {
+        let (start, end) = self.dict_bounds("model.Account", account_ptr, "storage")?;
+        self.serialize_storage_dict(start, end, preimages)
+    }
+
+    /// Serializes the storage dict between `start` and `end` into a slot map.
+    ///
+    /// Each dict value is a pointer to a `Uint256`, the null pointer being the default zero
+    /// value. The last write of each key wins.
+    pub fn serialize_storage_dict(
+        &self,
+        start: Relocatable,
+        end: Relocatable,
+        preimages: &StoragePreimages,
+    ) -> Result<HashMap<U256, U256>, KakarotSerdeError> {
+        self.dict_entries(start, end)?
+            .into_iter()
+            .map(|(key, value)| {
+                let slot = preimages.get(&key).ok_or(KakarotSerdeError::MissingPreimage { key })?;
+                let value = match self.dict_pointer(value, start)? {
+                    Some(value_ptr) => self.serialize_uint256(value_ptr)?,
+                    None => U256::ZERO,
+                };
+                Ok((slot, value))
+            })
+            .collect()
+    }
+
+    /// Returns the `{name}_start` and `{name}` dict pointers of a struct.
+    fn dict_bounds(
+        &self,
+        struct_name: &str,
+        ptr: Relocatable,
+        name: &str,
+    ) -> Result<(Relocatable, Relocatable), KakarotSerdeError> {
+        let start_name = format!("{name}_start");
+        let fields = self.serialize_fields(struct_name, ptr, &[&start_name, name])?;
+        let pointer = |field: &str| match fields.get(field) {
+            Some(Some(MaybeRelocatable::RelocatableValue(pointer))) => Ok(*pointer),
+            _ => Err(KakarotSerdeError::MissingField { field: field.to_string() }),
+        };
+        Ok((pointer(&start_name)?, pointer(name)?))
+    }
+
+    /// Returns the last `new_value` of each key of the dict between `start` and `end`.
+    pub(super) fn dict_entries(
+        &self,
+        start: Relocatable,
+        end: Relocatable,
+    ) -> Result<HashMap<Felt252, MaybeRelocatable>, KakarotSerdeError> {
+        let len = (end - start)?;
+
+        let mut entries = HashMap::new();
+        for offset in (0..len).step_by(DICT_ACCESS_SIZE) {
+            let access = (start + offset)?;
+            let key = self.read_felt(access)?;
+            let new_value = self.read((access + 2usize)?)?;
+            entries.insert(key, new_value);
+        }
+
+        Ok(entries)
+    }
+
+    /// Interprets a dict value as a pointer, `0` being the default null pointer.
+    pub(super) fn dict_pointer(
+        &self,
+        value: MaybeRelocatable,
+        dict: Relocatable,
+    ) -> Result<Option<Relocatable>, KakarotSerdeError> {
+        match value {
+            MaybeRelocatable::RelocatableValue(pointer) => Ok(Some(pointer)),
+            MaybeRelocatable::Int(value) if value == Felt252::ZERO => Ok(None),
+            MaybeRelocatable::Int(value) => {
+                Err(KakarotSerdeError::InvalidPointer { address: dict, value })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_kakarot_serde() -> KakarotSerde {
+        KakarotSerde::builder()
+            .program_bytes(include_bytes!("../../testdata/keccak_add_uint256.json"))
+            .build()
+            .unwrap()
+    }
+
+    /// Writes the `Uint256` value and returns a pointer to it.
+    fn write_uint256(kakarot_serde: &mut KakarotSerde, value: u64) -> MaybeRelocatable {
+        let ptr = kakarot_serde.runner.vm.add_memory_segment();
+        kakarot_serde
+            .runner
+            .vm
+            .load_data(ptr, &[Felt252::from(value).into(), Felt252::ZERO.into()])
+            .unwrap();
+        ptr.into()
+    }
+
+    #[test]
+    fn test_storage_key() {
+        let mut preimages = StoragePreimages::default();
+        let key = preimages.insert(U256::from(1));
+
+        assert_eq!(key, Pedersen::hash(&Felt252::ONE, &Felt252::ZERO));
+        assert_eq!(preimages.get(&key), Some(U256::from(1)));
+        assert_eq!(preimages.get(&Felt252::ONE), None);
+    }
+
+    #[test]
+    fn test_serialize_storage_dict() {
+        let mut kakarot_serde = setup_kakarot_serde();
+        let preimages: StoragePreimages = [U256::from(1), U256::MAX].into_iter().collect();
+        let key_one = StoragePreimages::storage_key(U256::from(1));
+        let key_max = StoragePreimages::storage_key(U256::MAX);
+
+        // Slot 1 is written twice, slot `U256::MAX` is read with its default value.
+        let first = write_uint256(&mut kakarot_serde, 7);
+        let second = write_uint256(&mut kakarot_serde, 8);
+        let dict = kakarot_serde.runner.vm.add_memory_segment();
+        let end = kakarot_serde
+            .runner
+            .vm
+            .load_data(
+                dict,
+                &[
+                    key_one.into(),
+                    Felt252::ZERO.into(),
+                    first.clone(),
+                    key_max.into(),
+                    Felt252::ZERO.into(),
+                    Felt252::ZERO.into(),
+                    key_one.into(),
+                    first,
+                    second,
+                ],
+            )
+            .unwrap();
+
+        let storage = kakarot_serde.serialize_storage_dict(dict, end, &preimages).unwrap();
+
+        assert_eq!(
+            storage,
+            HashMap::from_iter([(U256::from(1), U256::from(8)), (U256::MAX, U256::ZERO)])
+        );
+    }
+
+    #[test]
+    fn test_serialize_storage_dict_missing_preimage() {
+        let mut kakarot_serde = setup_kakarot_serde();
+        let key = StoragePreimages::storage_key(U256::from(2));
+
+        let dict = kakarot_serde.runner.vm.add_memory_segment();
+        let end = kakarot_serde
+            .runner
+            .vm
+            .load_data(dict, &[key.into(), Felt252::ZERO.into(), Felt252::ZERO.into()])
+            .unwrap();
+
+        let result = kakarot_serde.serialize_storage_dict(dict, end, &StoragePreimages::default());
+
+        match result {
+            Err(KakarotSerdeError::MissingPreimage { key: missing }) => assert_eq!(missing, key),
+            _ => panic!("Expected a missing preimage error, but got: {:?}", result),
+        }
+    }
+}
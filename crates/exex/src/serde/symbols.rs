@@ -0,0 +1,311 @@
+//! Reverse symbol lookup, from a memory address to the struct member containing it.
+//!
+//! A [`SymbolIndex`] is built by walking the values reachable from typed roots, recording the
+//! memory range of every struct and member, and the segments allocated for the arrays they point
+//! to. It is used to describe addresses in error messages, e.g. `model.Account.storage + 3`
+//! instead of `5:3`, see [`KakarotSerde::locate_error`].
+
+use super::{CairoType, KakarotSerde, KakarotSerdeError, MemberType};
+use cairo_vm::types::relocatable::{MaybeRelocatable, Relocatable};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+};
+
+/// A known memory range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    /// The path of the value, e.g. `model.Account.balance`.
+    pub path: String,
+    /// The Cairo type of the value.
+    pub cairo_type: String,
+    /// The address of the first cell of the value.
+    pub start: Relocatable,
+    /// The number of cells of the value, `None` for arrays of unknown length, which span until the
+    /// end of their segment.
+    pub size: Option<usize>,
+}
+
+impl Symbol {
+    /// Returns whether the symbol contains the address.
+    pub fn contains(&self, address: Relocatable) -> bool {
+        address.segment_index == self.start.segment_index &&
+            address.offset >= self.start.offset &&
+            self.size.is_none_or(|size| address.offset < self.start.offset + size)
+    }
+}
+
+/// The location of an address relative to the symbol containing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SymbolLocation<'a> {
+    /// The symbol containing the address.
+    pub symbol: &'a Symbol,
+    /// The offset of the address from the start of the symbol.
+    pub offset: usize,
+}
+
+impl fmt::Display for SymbolLocation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.offset {
+            0 => write!(f, "{}", self.symbol.path),
+            offset => write!(f, "{} + {offset}", self.symbol.path),
+        }
+    }
+}
+
+/// An index of the known memory ranges, by segment.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolIndex {
+    /// The symbols of each segment, ordered by start offset.
+    segments: BTreeMap<isize, Vec<Symbol>>,
+}
+
+impl SymbolIndex {
+    /// Inserts a symbol in the index, e.g. a segment allocated by a hint.
+    pub fn insert(&mut self, symbol: Symbol) {
+        let symbols = self.segments.entry(symbol.start.segment_index).or_default();
+        let index = symbols.partition_point(|other| other.start.offset <= symbol.start.offset);
+        symbols.insert(index, symbol);
+    }
+
+    /// Returns the number of symbols in the index.
+    pub fn len(&self) -> usize {
+        self.segments.values().map(Vec::len).sum()
+    }
+
+    /// Returns whether the index is empty.
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Returns the most specific symbol containing the address, if any.
+    ///
+    /// The smallest containing symbol wins, bounded symbols being more specific than arrays of
+    /// unknown length. Ties are broken in favor of the symbol starting last.
+    pub fn lookup(&self, address: Relocatable) -> Option<SymbolLocation<'_>> {
+        let symbols = self.segments.get(&address.segment_index)?;
+        let candidates = &symbols[..symbols.partition_point(|s| s.start.offset <= address.offset)];
+
+        candidates
+            .iter()
+            .enumerate()
+            .filter(|(_, symbol)| symbol.contains(address))
+            .min_by_key(|(index, symbol)| (symbol.size.unwrap_or(usize::MAX), usize::MAX - index))
+            .map(|(_, symbol)| SymbolLocation {
+                symbol,
+                offset: address.offset - symbol.start.offset,
+            })
+    }
+
+    /// Describes the address, falling back to its bare `segment:offset` form when unknown.
+    pub fn describe(&self, address: Relocatable) -> String {
+        self.lookup(address).map_or_else(|| address.to_string(), |location| location.to_string())
+    }
+}
+
+impl KakarotSerdeError {
+    /// Returns the address the error occurred at, if any.
+    pub const fn address(&self) -> Option<Relocatable> {
+        match self {
+            Self::MissingValue { address } |
+            Self::ExpectedFelt { address } |
+            Self::InvalidPointer { address, .. } => Some(*address),
+            _ => None,
+        }
+    }
+}
+
+impl KakarotSerde {
+    /// Locates the address of an error in the values reachable from the given typed roots, see
+    /// [`Self::symbol_index`], the error being returned as is when its address is unknown.
+    pub fn locate_error(
+        &self,
+        error: KakarotSerdeError,
+        roots: &[(Relocatable, &str)],
+    ) -> KakarotSerdeError {
+        let Some(address) = error.address() else { return error };
+        // The index is only used to describe the error, failing to build it is not an error.
+        let location = self
+            .symbol_index(roots)
+            .ok()
+            .and_then(|index| index.lookup(address).map(|location| location.to_string()));
+        match location {
+            Some(location) => KakarotSerdeError::Located { location, error: Box::new(error) },
+            None => error,
+        }
+    }
+
+    /// Builds the [`SymbolIndex`] of the values reachable from the given typed roots.
+    ///
+    /// Each root is the address of a value of the given Cairo type, e.g. `model.State`. Pointers
+    /// to structs are followed, and the segments pointed to by felt pointers are recorded as
+    /// arrays of unknown length.
+    pub fn symbol_index(
+        &self,
+        roots: &[(Relocatable, &str)],
+    ) -> Result<SymbolIndex, KakarotSerdeError> {
+        let mut index = SymbolIndex::default();
+        let mut visited = HashSet::new();
+        for (address, cairo_type) in roots {
+            self.index_value(*address, cairo_type, None, &mut index, &mut visited)?;
+        }
+        Ok(index)
+    }
+
+    /// Records the symbols of the value of type `cairo_type` at `address`.
+    fn index_value(
+        &self,
+        address: Relocatable,
+        cairo_type: &str,
+        path: Option<String>,
+        index: &mut SymbolIndex,
+        visited: &mut HashSet<(Relocatable, String)>,
+    ) -> Result<(), KakarotSerdeError> {
+        // Shared and cyclic values are only indexed once.
+        if !visited.insert((address, cairo_type.to_string())) {
+            return Ok(());
+        }
+
+        let member_type = MemberType::parse(cairo_type);
+        let layout = match member_type {
+            MemberType::Struct(name) => Some(self.struct_members(name)?),
+            _ => None,
+        };
+        let full_name = layout.as_ref().map(|layout| layout.name.clone());
+        let path = path.or_else(|| full_name.clone()).unwrap_or_else(|| cairo_type.to_string());
+
+        index.insert(Symbol {
+            path: path.clone(),
+            cairo_type: full_name.unwrap_or_else(|| cairo_type.to_string()),
+            start: address,
+            size: Some(self.type_size(&CairoType::parse(cairo_type))?),
+        });
+
+        for member in layout.iter().flat_map(|layout| &layout.members) {
+            self.index_value(
+                (address + member.offset)?,
+                &member.cairo_type,
+                Some(format!("{path}.{}", member.name)),
+                index,
+                visited,
+            )?;
+        }
+
+        let MemberType::Pointer(pointee) = member_type else { return Ok(()) };
+        let Some(MaybeRelocatable::RelocatableValue(target)) = self.get_maybe(address, true) else {
+            return Ok(());
+        };
+
+        match MemberType::parse(pointee) {
+            MemberType::Struct(_) => self.index_value(target, pointee, None, index, visited)?,
+            // The length of the other arrays is unknown, they span until the end of the segment.
+            _ => index.insert(Symbol {
+                path,
+                cairo_type: pointee.to_string(),
+                start: target,
+                size: None,
+            }),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cairo_vm::Felt252;
+
+    fn setup_kakarot_serde() -> KakarotSerde {
+        KakarotSerde::builder()
+            .program_bytes(include_bytes!("../../testdata/keccak_add_uint256.json"))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_symbol_index_lookup() {
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        // `main.ImplicitArgs` whose `output_ptr` points to an array and whose `bitwise_ptr` points
+        // to a `BitwiseBuiltin`.
+        let args = kakarot_serde.runner.vm.add_memory_segment();
+        let output = kakarot_serde.runner.vm.add_memory_segment();
+        let bitwise = kakarot_serde.runner.vm.add_memory_segment();
+        kakarot_serde
+            .runner
+            .vm
+            .load_data(args, &[output.into(), Felt252::from(7).into(), bitwise.into()])
+            .unwrap();
+
+        let index = kakarot_serde.symbol_index(&[(args, "main.ImplicitArgs")]).unwrap();
+
+        // The root struct, its 3 members, the output array, the bitwise struct and its 5 members.
+        assert_eq!(index.len(), 11);
+        assert_eq!(index.describe(args), "__main__.main.ImplicitArgs.output_ptr");
+        assert_eq!(
+            index.describe((args + 1usize).unwrap()),
+            "__main__.main.ImplicitArgs.range_check_ptr"
+        );
+        assert_eq!(
+            index.describe((output + 3usize).unwrap()),
+            "__main__.main.ImplicitArgs.output_ptr + 3"
+        );
+        assert_eq!(
+            index.describe((bitwise + 3usize).unwrap()),
+            "starkware.cairo.common.cairo_builtins.BitwiseBuiltin.x_xor_y"
+        );
+        // Past the end of the bitwise struct.
+        assert_eq!(index.describe((bitwise + 5usize).unwrap()), "2:5");
+        assert_eq!(index.describe((args + 3usize).unwrap()), "0:3");
+    }
+
+    #[test]
+    fn test_locate_error() {
+        let mut kakarot_serde = setup_kakarot_serde();
+        let args = kakarot_serde.runner.vm.add_memory_segment();
+        let roots = [(args, "main.ImplicitArgs")];
+
+        let address = (args + 1usize).unwrap();
+        let error = kakarot_serde
+            .locate_error(KakarotSerdeError::MissingValue { address }, &roots)
+            .to_string();
+        assert_eq!(
+            error,
+            format!(
+                "Read failed at __main__.main.ImplicitArgs.range_check_ptr: No value written at \
+                 {address}."
+            )
+        );
+
+        // Errors without address, or at an unknown one, are returned as is.
+        let unknown = Relocatable::from((args.segment_index, 3));
+        assert!(matches!(
+            kakarot_serde
+                .locate_error(KakarotSerdeError::MissingValue { address: unknown }, &roots),
+            KakarotSerdeError::MissingValue { .. }
+        ));
+        assert!(matches!(
+            kakarot_serde.locate_error(KakarotSerdeError::RelocatedWrite, &roots),
+            KakarotSerdeError::RelocatedWrite
+        ));
+    }
+
+    #[test]
+    fn test_symbol_index_struct_offset() {
+        let kakarot_serde = setup_kakarot_serde();
+        let address = Relocatable::from((0, 10));
+
+        let index = kakarot_serde.symbol_index(&[(address, "keccak_add_uint256.Args")]).unwrap();
+
+        // `Args` is made of a `Uint256` and a felt.
+        let location = index.lookup(address).unwrap();
+        assert_eq!(location.symbol.size, Some(1));
+        assert_eq!(
+            location.to_string(),
+            "starkware.cairo.common.keccak_utils.keccak_utils.keccak_add_uint256.Args.num.low"
+        );
+        let root = index.segments[&0].first().unwrap();
+        assert_eq!(root.size, Some(3));
+    }
+}
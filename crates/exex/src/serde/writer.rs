@@ -0,0 +1,425 @@
+//! Writing of Rust values into the memory of the runner, to feed program inputs.
+//!
+//! A [`CairoWrite`] value flattens itself into the cells of its Cairo type, writing the data it
+//! points to in new segments:
+//! - Integers and addresses are a single felt.
+//! - [`U256`] and [`B256`] are a `Uint256`, split into its `low` and `high` 128 bits.
+//! - Byte arrays and lists are a `len` felt followed by a pointer to their items, one felt per
+//!   byte.
+//! - [`Option`]s are a pointer to their value, null when `None`.
+//!
+//! [`KakarotSerde::write`] writes a value in a new segment and returns its base, to be passed as
+//! an entrypoint argument, and [`KakarotSerde::write_struct`] writes the members of a struct at
+//! their offsets, e.g. for the account structs.
+
+use super::{KakarotSerde, KakarotSerdeError};
+use crate::model::{KethMaybeRelocatable, KethPointer, KethTransactionEncoded, U128_BYTES_SIZE};
+use alloy_primitives::{Address, Bloom, Bytes, B256, U256};
+use cairo_vm::{
+    types::relocatable::{MaybeRelocatable, Relocatable},
+    Felt252,
+};
+use reth_primitives::{Signature, Transaction, TransactionSignedEcRecovered};
+
+/// A Rust value which can be written to the memory of a Cairo program.
+pub trait CairoWrite {
+    /// Returns the cells of the value, writing the data it points to in new segments.
+    fn to_cairo(
+        &self,
+        serde: &mut KakarotSerde,
+    ) -> Result<Vec<MaybeRelocatable>, KakarotSerdeError>;
+}
+
+impl KakarotSerde {
+    /// Writes a value in a new segment, returning the base of the segment.
+    pub fn write<T: CairoWrite + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<Relocatable, KakarotSerdeError> {
+        let cells = value.to_cairo(self)?;
+        self.write_cells(&cells)
+    }
+
+    /// Writes cells in a new segment, returning the base of the segment.
+    pub fn write_cells(
+        &mut self,
+        cells: &[MaybeRelocatable],
+    ) -> Result<Relocatable, KakarotSerdeError> {
+        if self.is_relocated() {
+            return Err(KakarotSerdeError::RelocatedWrite);
+        }
+
+        let base = self.runner.vm.add_memory_segment();
+        self.runner.vm.load_data(base, cells)?;
+        Ok(base)
+    }
+
+    /// Writes the struct with the given name in a new segment, returning the base of the segment.
+    ///
+    /// Each member of the struct must be given exactly once, with a value of the size of the
+    /// member, and is written at its offset.
+    pub fn write_struct(
+        &mut self,
+        name: &str,
+        members: &[(&str, &dyn CairoWrite)],
+    ) -> Result<Relocatable, KakarotSerdeError> {
+        let cells = self.struct_cells(name, members)?;
+        self.write_cells(&cells)
+    }
+
+    /// Returns the cells of the struct with the given name, see [`KakarotSerde::write_struct`].
+    pub fn struct_cells(
+        &mut self,
+        name: &str,
+        members: &[(&str, &dyn CairoWrite)],
+    ) -> Result<Vec<MaybeRelocatable>, KakarotSerdeError> {
+        let layout = self.struct_members(name)?;
+
+        if let Some((member, _)) =
+            members.iter().find(|(member, _)| layout.member(member).is_none())
+        {
+            return Err(KakarotSerdeError::UnknownMember {
+                struct_name: layout.name.clone(),
+                member: member.to_string(),
+            });
+        }
+
+        let mut cells = Vec::new();
+        for member in &layout.members {
+            let Some((_, value)) = members.iter().find(|(name, _)| *name == member.name) else {
+                return Err(KakarotSerdeError::MissingField { field: member.name.clone() });
+            };
+
+            let expected = self.type_size(&member.typ)?;
+            let value = value.to_cairo(self)?;
+            if value.len() != expected {
+                return Err(KakarotSerdeError::MemberSizeMismatch {
+                    struct_name: layout.name.clone(),
+                    member: member.name.clone(),
+                    expected,
+                    actual: value.len(),
+                });
+            }
+
+            if cells.len() < member.offset + expected {
+                cells.resize(member.offset + expected, Felt252::ZERO.into());
+            }
+            cells.splice(member.offset..member.offset + expected, value);
+        }
+        Ok(cells)
+    }
+}
+
+impl CairoWrite for MaybeRelocatable {
+    fn to_cairo(&self, _: &mut KakarotSerde) -> Result<Vec<MaybeRelocatable>, KakarotSerdeError> {
+        Ok(vec![self.clone()])
+    }
+}
+
+impl CairoWrite for Felt252 {
+    fn to_cairo(&self, _: &mut KakarotSerde) -> Result<Vec<MaybeRelocatable>, KakarotSerdeError> {
+        Ok(vec![(*self).into()])
+    }
+}
+
+impl CairoWrite for Relocatable {
+    fn to_cairo(&self, _: &mut KakarotSerde) -> Result<Vec<MaybeRelocatable>, KakarotSerdeError> {
+        Ok(vec![(*self).into()])
+    }
+}
+
+impl CairoWrite for u64 {
+    fn to_cairo(&self, _: &mut KakarotSerde) -> Result<Vec<MaybeRelocatable>, KakarotSerdeError> {
+        Ok(vec![Felt252::from(*self).into()])
+    }
+}
+
+impl CairoWrite for usize {
+    fn to_cairo(&self, _: &mut KakarotSerde) -> Result<Vec<MaybeRelocatable>, KakarotSerdeError> {
+        Ok(vec![Felt252::from(*self).into()])
+    }
+}
+
+impl CairoWrite for bool {
+    fn to_cairo(&self, _: &mut KakarotSerde) -> Result<Vec<MaybeRelocatable>, KakarotSerdeError> {
+        Ok(vec![Felt252::from(*self).into()])
+    }
+}
+
+impl CairoWrite for Address {
+    fn to_cairo(&self, _: &mut KakarotSerde) -> Result<Vec<MaybeRelocatable>, KakarotSerdeError> {
+        Ok(vec![Felt252::from_bytes_be_slice(self.as_slice()).into()])
+    }
+}
+
+impl CairoWrite for B256 {
+    /// Writes the 32 bytes as a `Uint256`.
+    fn to_cairo(&self, _: &mut KakarotSerde) -> Result<Vec<MaybeRelocatable>, KakarotSerdeError> {
+        let (high, low) = self.0.split_at(U128_BYTES_SIZE);
+        Ok(vec![
+            Felt252::from_bytes_be_slice(low).into(),
+            Felt252::from_bytes_be_slice(high).into(),
+        ])
+    }
+}
+
+impl CairoWrite for U256 {
+    fn to_cairo(
+        &self,
+        serde: &mut KakarotSerde,
+    ) -> Result<Vec<MaybeRelocatable>, KakarotSerdeError> {
+        B256::from(*self).to_cairo(serde)
+    }
+}
+
+impl CairoWrite for [u8] {
+    /// Writes the bytes as a `len` felt followed by a `felt*` holding one byte per felt.
+    fn to_cairo(
+        &self,
+        serde: &mut KakarotSerde,
+    ) -> Result<Vec<MaybeRelocatable>, KakarotSerdeError> {
+        let bytes = self.iter().map(|byte| Felt252::from(*byte).into()).collect::<Vec<_>>();
+        Ok(vec![Felt252::from(self.len()).into(), serde.write_cells(&bytes)?.into()])
+    }
+}
+
+impl CairoWrite for Bytes {
+    fn to_cairo(
+        &self,
+        serde: &mut KakarotSerde,
+    ) -> Result<Vec<MaybeRelocatable>, KakarotSerdeError> {
+        self[..].to_cairo(serde)
+    }
+}
+
+impl CairoWrite for Bloom {
+    /// Writes the bloom filter as a `felt*` to its 16-byte chunks, the length being implicit.
+    fn to_cairo(
+        &self,
+        serde: &mut KakarotSerde,
+    ) -> Result<Vec<MaybeRelocatable>, KakarotSerdeError> {
+        let chunks = self
+            .0
+            .chunks(U128_BYTES_SIZE)
+            .map(|chunk| Felt252::from_bytes_be_slice(chunk).into())
+            .collect::<Vec<_>>();
+        Ok(vec![serde.write_cells(&chunks)?.into()])
+    }
+}
+
+impl<T: CairoWrite> CairoWrite for Option<T> {
+    /// Writes the value as a pointer to it, null when `None`.
+    fn to_cairo(
+        &self,
+        serde: &mut KakarotSerde,
+    ) -> Result<Vec<MaybeRelocatable>, KakarotSerdeError> {
+        match self {
+            Some(value) => Ok(vec![serde.write(value)?.into()]),
+            None => Ok(vec![Felt252::ZERO.into()]),
+        }
+    }
+}
+
+impl<T: CairoWrite> CairoWrite for [T] {
+    /// Writes the items as a `len` felt followed by a pointer to the items, laid out one after
+    /// the other.
+    fn to_cairo(
+        &self,
+        serde: &mut KakarotSerde,
+    ) -> Result<Vec<MaybeRelocatable>, KakarotSerdeError> {
+        let mut items = Vec::new();
+        for item in self {
+            items.extend(item.to_cairo(serde)?);
+        }
+        Ok(vec![Felt252::from(self.len()).into(), serde.write_cells(&items)?.into()])
+    }
+}
+
+impl<T: CairoWrite> CairoWrite for Vec<T> {
+    fn to_cairo(
+        &self,
+        serde: &mut KakarotSerde,
+    ) -> Result<Vec<MaybeRelocatable>, KakarotSerdeError> {
+        self.as_slice().to_cairo(serde)
+    }
+}
+
+impl CairoWrite for KethMaybeRelocatable {
+    fn to_cairo(&self, _: &mut KakarotSerde) -> Result<Vec<MaybeRelocatable>, KakarotSerdeError> {
+        Ok(vec![self.into()])
+    }
+}
+
+impl CairoWrite for KethPointer {
+    /// Writes the data as a `len` felt followed by a pointer to the data.
+    fn to_cairo(
+        &self,
+        serde: &mut KakarotSerde,
+    ) -> Result<Vec<MaybeRelocatable>, KakarotSerdeError> {
+        let data = self.data().iter().map(MaybeRelocatable::from).collect::<Vec<_>>();
+        Ok(vec![self.length().into(), serde.write_cells(&data)?.into()])
+    }
+}
+
+impl CairoWrite for Signature {
+    /// Writes the signature as `[r.low, r.high, s.low, s.high, v]` felts, see [`KethPointer`].
+    fn to_cairo(
+        &self,
+        serde: &mut KakarotSerde,
+    ) -> Result<Vec<MaybeRelocatable>, KakarotSerdeError> {
+        KethPointer::from(*self).to_cairo(serde)
+    }
+}
+
+impl CairoWrite for Transaction {
+    /// Writes the RLP encoding of the unsigned transaction, one byte per felt.
+    fn to_cairo(
+        &self,
+        serde: &mut KakarotSerde,
+    ) -> Result<Vec<MaybeRelocatable>, KakarotSerdeError> {
+        KethPointer::from(self.clone()).to_cairo(serde)
+    }
+}
+
+impl CairoWrite for KethTransactionEncoded {
+    /// Writes the transaction as a `model.TransactionEncoded`.
+    fn to_cairo(
+        &self,
+        serde: &mut KakarotSerde,
+    ) -> Result<Vec<MaybeRelocatable>, KakarotSerdeError> {
+        let mut cells = self.rlp().to_cairo(serde)?;
+        cells.extend(self.signature().to_cairo(serde)?);
+        cells.extend(self.sender().to_cairo(serde)?);
+        Ok(cells)
+    }
+}
+
+impl CairoWrite for TransactionSignedEcRecovered {
+    /// Writes the transaction as a `model.TransactionEncoded`.
+    fn to_cairo(
+        &self,
+        serde: &mut KakarotSerde,
+    ) -> Result<Vec<MaybeRelocatable>, KakarotSerdeError> {
+        KethTransactionEncoded::from(self.clone()).to_cairo(serde)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_kakarot_serde() -> KakarotSerde {
+        KakarotSerde::builder()
+            .program_bytes(include_bytes!("../../testdata/keccak_add_uint256.json"))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_write_uint256() {
+        let mut kakarot_serde = setup_kakarot_serde();
+        let value = U256::from_str_radix("0123456789abcdef0123456789abcdef00", 16).unwrap();
+
+        let ptr = kakarot_serde.write(&value).unwrap();
+
+        assert_eq!(kakarot_serde.serialize_uint256(ptr).unwrap(), value);
+        let [low, high] = kakarot_serde.read_cells(ptr).unwrap();
+        assert_eq!(high, Felt252::from(0x01u8).into());
+        assert_eq!(low, Felt252::from(0x23456789abcdef0123456789abcdef00u128).into());
+    }
+
+    #[test]
+    fn test_write_bytes_and_options() {
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        let ptr = kakarot_serde.write(&Bytes::from(vec![0xde, 0xad, 0xbe])).unwrap();
+        assert_eq!(kakarot_serde.read_felt(ptr).unwrap(), Felt252::from(3));
+        let data = kakarot_serde.read_pointer((ptr + 1usize).unwrap()).unwrap().unwrap();
+        assert_eq!(
+            kakarot_serde.read_cells::<3>(data).unwrap(),
+            [0xdeu8, 0xad, 0xbe].map(|byte| Felt252::from(byte).into())
+        );
+
+        let ptr = kakarot_serde.write(&None::<U256>).unwrap();
+        assert_eq!(kakarot_serde.read_pointer(ptr).unwrap(), None);
+
+        let ptr = kakarot_serde.write(&Some(U256::from(7))).unwrap();
+        let value = kakarot_serde.read_pointer(ptr).unwrap().unwrap();
+        assert_eq!(kakarot_serde.serialize_uint256(value).unwrap(), U256::from(7));
+    }
+
+    #[test]
+    fn test_write_list() {
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        let ptr = kakarot_serde.write(&vec![U256::from(1), U256::from(2)]).unwrap();
+        assert_eq!(kakarot_serde.read_felt(ptr).unwrap(), Felt252::from(2));
+        let items = kakarot_serde.read_pointer((ptr + 1usize).unwrap()).unwrap().unwrap();
+        assert_eq!(
+            kakarot_serde.serialize_uint256((items + 2usize).unwrap()).unwrap(),
+            U256::from(2)
+        );
+    }
+
+    #[test]
+    fn test_write_struct() {
+        let mut kakarot_serde = setup_kakarot_serde();
+        let bitwise = kakarot_serde.runner.vm.add_memory_segment();
+
+        // Members are written at their offsets, whatever the order they are given in.
+        let ptr = kakarot_serde
+            .write_struct(
+                "main.ImplicitArgs",
+                &[
+                    ("bitwise_ptr", &bitwise),
+                    ("output_ptr", &None::<u64>),
+                    ("range_check_ptr", &Felt252::from(12)),
+                ],
+            )
+            .unwrap();
+
+        let members = kakarot_serde.serialize_pointers("main.ImplicitArgs", ptr).unwrap();
+        assert_eq!(members["output_ptr"], None);
+        assert_eq!(members["range_check_ptr"], Some(Felt252::from(12).into()));
+        assert_eq!(members["bitwise_ptr"], Some(bitwise.into()));
+
+        // Embedded structs take the cells of their value.
+        let ptr = kakarot_serde
+            .write_struct(
+                "EcOpBuiltin",
+                &[
+                    ("p", &U256::from(1)),
+                    ("q", &U256::from(2)),
+                    ("m", &3u64),
+                    ("r", &U256::from(4)),
+                ],
+            )
+            .unwrap();
+        assert_eq!(kakarot_serde.read_felt((ptr + 4usize).unwrap()).unwrap(), Felt252::from(3));
+        assert_eq!(kakarot_serde.read_felt((ptr + 5usize).unwrap()).unwrap(), Felt252::from(4));
+    }
+
+    #[test]
+    fn test_write_struct_invalid_members() {
+        let mut kakarot_serde = setup_kakarot_serde();
+
+        let result = kakarot_serde.write_struct("Uint256", &[("low", &1u64)]);
+        assert!(
+            matches!(result, Err(KakarotSerdeError::MissingField { field }) if field == "high")
+        );
+
+        let result =
+            kakarot_serde.write_struct("Uint256", &[("low", &1u64), ("high", &U256::from(1))]);
+        assert!(matches!(
+            result,
+            Err(KakarotSerdeError::MemberSizeMismatch { expected: 1, actual: 2, .. })
+        ));
+
+        let result = kakarot_serde
+            .write_struct("Uint256", &[("low", &1u64), ("high", &2u64), ("middle", &3u64)]);
+        assert!(matches!(
+            result,
+            Err(KakarotSerdeError::UnknownMember { member, .. }) if member == "middle"
+        ));
+    }
+}
@@ -0,0 +1,323 @@
+//! Managed proving of the blocks with SHARP, the shared prover of StarkWare.
+//!
+//! The queued proving jobs of an instance in deferred mode are run to their Cairo PIE, which is
+//! submitted to the SHARP gateway. Submitted jobs are polled until SHARP processes them: the proof
+//! of the block is then recorded with the SHARP job key in its metadata, and its fact, computed
+//! from the program hash and output of the run, can be checked on the L1 fact registry.
+//!
+//! SHARP does not return the proof itself, which is verified on-chain, so the recorded proofs
+//! are empty.
+
+use crate::{
+    artifacts::PIE_KIND,
+    db::Database,
+    deferred::{self, JobState},
+    fact::fact_hash,
+    output::{ProgramOutput, ProofMetadata},
+    program,
+    quorum::{self, ProverResult, QuorumConfig, QuorumOutcome},
+    retry::{self, RetryPolicy},
+    ssz::Ssz,
+    store::KethStore,
+    telemetry::{self, Stage},
+    verifier::VerifierRegistry,
+};
+use alloy_primitives::B256;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reth_tracing::tracing::{warn, Instrument};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
+use std::{fmt, fs, path::Path, str::FromStr};
+use thiserror::Error;
+
+/// The name of the prover recorded in the metadata of the SHARP proofs.
+pub const SHARP_PROVER: &str = "sharp";
+
+/// The layout of the runs submitted to SHARP.
+pub const SHARP_LAYOUT: &str = "all_cairo";
+
+/// Represents errors that can occur when proving with SHARP.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SharpError {
+    /// Error variant indicating that the gateway rejected a job.
+    #[error("SHARP rejected the job of block {block_number}: {reason}")]
+    Rejected {
+        /// The number of the block.
+        block_number: u64,
+        /// The response of the gateway.
+        reason: String,
+    },
+
+    /// Error variant indicating an unknown SHARP job status.
+    #[error("Unknown SHARP job status '{0}'")]
+    UnknownStatus(String),
+
+    /// Error variant indicating that the program hash of a run cannot be computed.
+    #[error("Failed to compute the program hash: {0}")]
+    ProgramHash(String),
+}
+
+/// The status of a SHARP job, as returned by the gateway.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SharpStatus {
+    /// The job was not created yet.
+    NotCreated,
+    /// The job is being proven.
+    InProgress,
+    /// The proof of the job was generated and verified.
+    Processed,
+    /// The fact of the job was registered on-chain.
+    Onchain,
+    /// The job failed.
+    Failed,
+    /// The Cairo PIE of the job is invalid.
+    Invalid,
+    /// The gateway does not know the job.
+    Unknown,
+}
+
+impl SharpStatus {
+    /// Returns whether the job was proven.
+    pub const fn is_proven(&self) -> bool {
+        matches!(self, Self::Processed | Self::Onchain)
+    }
+
+    /// Returns whether the job failed and must be submitted again.
+    pub const fn is_failed(&self) -> bool {
+        matches!(self, Self::Failed | Self::Invalid | Self::Unknown)
+    }
+
+    /// Returns the name of the status, as returned by the gateway and stored in the database.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::NotCreated => "NOT_CREATED",
+            Self::InProgress => "IN_PROGRESS",
+            Self::Processed => "PROCESSED",
+            Self::Onchain => "ONCHAIN",
+            Self::Failed => "FAILED",
+            Self::Invalid => "INVALID",
+            Self::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+impl fmt::Display for SharpStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SharpStatus {
+    type Err = SharpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(json!(s)).map_err(|_| SharpError::UnknownStatus(s.to_string()))
+    }
+}
+
+/// A block submitted to SHARP.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SharpJob {
+    /// The number of the block.
+    pub block_number: u64,
+    /// The key of the SHARP job.
+    pub job_key: String,
+    /// The hash of the Kakarot program, as hashed by the bootloader.
+    pub program_hash: B256,
+    /// The hash tree root of the [`ProgramOutput`] of the block.
+    pub output_root: B256,
+    /// The fact registered once the job is proven.
+    pub fact: B256,
+    /// The UNIX timestamp, in seconds, at which the job was submitted.
+    pub submitted_at: u64,
+}
+
+/// The response of the gateway to a job submission.
+#[derive(Debug, Deserialize)]
+struct AddJobResponse {
+    /// The key of the created job.
+    cairo_job_key: Option<String>,
+}
+
+/// The response of the gateway to a status request.
+#[derive(Debug, Deserialize)]
+struct StatusResponse {
+    /// The status of the job.
+    status: SharpStatus,
+}
+
+/// A client of the SHARP gateway.
+#[derive(Debug, Clone)]
+pub struct SharpClient {
+    /// The HTTP client.
+    http: reqwest::Client,
+    /// The URL of the gateway.
+    gateway_url: String,
+}
+
+impl SharpClient {
+    /// Creates a new [`SharpClient`] for the gateway at the given URL.
+    pub fn new(gateway_url: impl Into<String>) -> Self {
+        Self { http: reqwest::Client::new(), gateway_url: gateway_url.into() }
+    }
+
+    /// Submits a zipped Cairo PIE, returning the key of the created job, `None` if the gateway
+    /// did not create it.
+    pub async fn add_job(&self, pie: &[u8]) -> eyre::Result<Option<String>> {
+        let request =
+            json!({ "action": "add_job", "request": { "cairo_pie": STANDARD.encode(pie) } });
+        let response: AddJobResponse = self.post(&request).await?;
+        Ok(response.cairo_job_key)
+    }
+
+    /// Returns the status of a job.
+    pub async fn status(&self, job_key: &str) -> eyre::Result<SharpStatus> {
+        let request = json!({ "action": "get_status", "request": { "cairo_job_key": job_key } });
+        let response: StatusResponse = self.post(&request).await?;
+        Ok(response.status)
+    }
+
+    /// Sends a request to the gateway.
+    async fn post<R: DeserializeOwned>(&self, body: &serde_json::Value) -> eyre::Result<R> {
+        let response = self.http.post(&self.gateway_url).json(body).send().await?;
+        Ok(response.error_for_status()?.json().await?)
+    }
+}
+
+/// Submits at most `limit` queued proving jobs to SHARP, marking them as exported.
+///
+/// The Cairo PIEs are written to `work_dir` before their submission, and the outputs of their runs
+/// are recorded in the store. When a registry of verifier parameters is given, jobs whose program
+/// is not registered for SHARP with its layout are rejected before their submission. Jobs whose
+/// retry backoff did not elapse are left queued.
+pub async fn submit_jobs(
+    db: &Database,
+    store: &dyn KethStore,
+    client: &SharpClient,
+    work_dir: &Path,
+    limit: usize,
+    verifiers: Option<&VerifierRegistry>,
+) -> eyre::Result<Vec<SharpJob>> {
+    fs::create_dir_all(work_dir)?;
+
+    let mut submitted = Vec::new();
+    for job in db.due_proving_jobs(retry::now(), limit)? {
+        let block_number = job.block_number;
+        let pie = deferred::pie_file_name(block_number);
+        let path = work_dir.join(&pie);
+        let runner = deferred::write_pie(&job, &deferred::job_input(db, block_number)?, &path)?;
+
+        let program_hash = program::program_hash(runner.get_program())
+            .map_err(|err| SharpError::ProgramHash(err.to_string()))?;
+        if let Some(verifiers) = verifiers {
+            verifiers.check(program_hash, SHARP_PROVER, SHARP_LAYOUT)?;
+        }
+        let output = deferred::record_output(store, block_number, &runner)?;
+
+        let span = telemetry::stage_span(Stage::Submit, block_number);
+        let job_key =
+            client.add_job(&fs::read(&path)?).instrument(span.clone()).await?.ok_or_else(|| {
+                SharpError::Rejected { block_number, reason: "no job key".to_string() }
+            })?;
+        span.record("job_id", job_key.as_str());
+        db.store_artifact(PIE_KIND, block_number, &pie, fs::read(&path)?)?;
+        let sharp_job = SharpJob {
+            block_number,
+            job_key,
+            program_hash,
+            output_root: output.hash_tree_root(),
+            fact: fact_hash(program_hash, &output.to_felts()),
+            submitted_at: retry::now(),
+        };
+
+        db.insert_sharp_job(&sharp_job)?;
+        db.set_proving_job_state(block_number, JobState::Exported)?;
+        submitted.push(sharp_job);
+    }
+
+    Ok(submitted)
+}
+
+/// Polls the status of the submitted jobs, recording the proofs of the processed ones in the store.
+///
+/// Failed jobs are queued again according to the retry policy, to be submitted in a new SHARP job.
+/// With a quorum, SHARP is one of its provers and a processed job only proves its block once all
+/// the provers agree on its fact. Returns the numbers of the blocks proven since the last poll.
+pub async fn poll_jobs(
+    db: &Database,
+    store: &dyn KethStore,
+    client: &SharpClient,
+    quorum: Option<&QuorumConfig>,
+    policy: &RetryPolicy,
+) -> eyre::Result<Vec<u64>> {
+    let mut proven = Vec::new();
+    for job in db.sharp_jobs(SharpStatus::InProgress)? {
+        let status = client.status(&job.job_key).await?;
+        db.set_sharp_job_status(job.block_number, status)?;
+
+        if status.is_proven() {
+            let metadata = ProofMetadata {
+                block_number: job.block_number,
+                program_hash: job.program_hash,
+                output_root: job.output_root,
+                layout: SHARP_LAYOUT.to_string(),
+                prover: SHARP_PROVER.to_string(),
+                created_at: retry::now(),
+                job_id: Some(job.job_key),
+            };
+            match quorum {
+                Some(quorum) => {
+                    let result = ProverResult { metadata, fact: Some(job.fact) };
+                    if !matches!(
+                        quorum::record_result(db, store, quorum, &result, &[])?,
+                        QuorumOutcome::Agreed(_)
+                    ) {
+                        continue;
+                    }
+                }
+                None => {
+                    store.insert_proof(&metadata, &[])?;
+                    db.set_proving_job_state(job.block_number, JobState::Proven)?;
+                }
+            }
+            proven.push(job.block_number);
+        } else if status.is_failed() {
+            warn!(
+                target: "kkrt::sharp",
+                number = job.block_number,
+                job_key = job.job_key,
+                %status,
+                "SHARP job failed"
+            );
+            retry::record_failure(db, policy, job.block_number, &format!("SHARP job {status}"))?;
+        }
+    }
+
+    Ok(proven)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sharp_status() {
+        assert_eq!("ONCHAIN".parse(), Ok(SharpStatus::Onchain));
+        assert_eq!(SharpStatus::InProgress.to_string(), "IN_PROGRESS");
+        assert!(SharpStatus::Processed.is_proven());
+        assert!(SharpStatus::Invalid.is_failed());
+        assert!(!SharpStatus::NotCreated.is_failed());
+        assert_eq!(
+            "DONE".parse::<SharpStatus>(),
+            Err(SharpError::UnknownStatus("DONE".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_status_response() {
+        let response: StatusResponse =
+            serde_json::from_str(r#"{"status":"IN_PROGRESS","validation_done":false}"#).unwrap();
+        assert_eq!(response.status, SharpStatus::InProgress);
+    }
+}
@@ -0,0 +1,727 @@
+//! Serialization of the proofs for the STARK verifier contracts deployed on the EVM.
+//!
+//! The EVM verifier cannot check a whole proof in a single transaction, so the proof is split: the
+//! Merkle and FRI decommitments are registered first as statements of their own contracts, and
+//! the main proof, stripped from them, is then verified by the statement verifier. The split
+//! relies on the annotations written by the prover in annotated mode, each describing the bytes of
+//! the proof it covers, e.g.
+//! `P->V[0:32]: /cpu air/STARK/Original/Commit on Trace: Commitment: Hash(0x...)`.
+//!
+//! Each decommitment is registered with the calldata of its statement contract:
+//! `verifyMerkle(merkleView, initialMerkleQueue, height, expectedRoot)` for the trace and
+//! composition commitments, and `verifyFRI(proof, friQueue, evaluationPoint, friStepSize,
+//! expectedRoot)` for the FRI layers. The authentication nodes of a decommitment are the
+//! `P->V ... Decommitment: For node {index}` annotations of the proof, while its queue is read from
+//! the values the verifier computes, annotated in the extra annotations:
+//! - `V->P: .../Decommitment: For node {index}: Hash(0x...)` for the leaves of a Merkle tree,
+//! - `V->P: .../FRI/Decommitment/Layer {n}: Row {index}: Field Elements(0x{value}, 0x{inverse})`
+//!   for the queries of a FRI layer, with the inverse of their evaluation point,
+//! - `V->P: .../FRI/Commitment/Layer {n}: Evaluation point: Field Element(0x...)` for the
+//!   evaluation point of a FRI layer.
+//!
+//! The expected root of a decommitment is the `Commitment` of the same tree, e.g.
+//! `.../Original/Commit on Trace` for `.../Original/Decommitment`, and
+//! `.../FRI/Commitment/Layer 1` for `.../FRI/Decommitment/Layer 1`.
+//!
+//! Before paying for an L1 transaction, the verification of the main proof can be simulated with
+//! an `eth_call` on the verifier.
+
+use alloy_primitives::{hex, keccak256, Address, Bytes, U256};
+use alloy_provider::Provider;
+use alloy_rpc_types_eth::TransactionRequest;
+use alloy_transport::{RpcError, Transport, TransportResult};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, ops::Range};
+use thiserror::Error;
+
+/// The signature of the function verifying the main proof on the statement verifier.
+pub const VERIFY_PROOF_SIGNATURE: &str =
+    "verifyProofAndRegister(uint256[],uint256[],uint256[],uint256[],uint256)";
+
+/// The signature of the function registering a Merkle decommitment on the Merkle statement
+/// contract.
+pub const VERIFY_MERKLE_SIGNATURE: &str = "verifyMerkle(uint256[],uint256[],uint256,uint256)";
+
+/// The signature of the function registering a FRI layer on the FRI statement contract.
+pub const VERIFY_FRI_SIGNATURE: &str = "verifyFRI(uint256[],uint256[],uint256,uint256,uint256)";
+
+/// The size of a word of the proof, in bytes.
+const WORD_SIZE: usize = 32;
+
+/// The path segment of the annotations of the decommitments, registered as separate statements.
+const DECOMMITMENT: &str = "/Decommitment";
+
+/// The path segment of the annotations of the FRI layers.
+const FRI: &str = "/FRI/";
+
+/// The label of the interaction elements, drawn by the verifier.
+const INTERACTION_ELEMENT: &str = "Interaction element";
+
+/// The label of the commitments, holding the root of their tree.
+const COMMITMENT: &str = "Commitment";
+
+/// The label prefix of the nodes of a Merkle decommitment.
+const NODE: &str = "For node ";
+
+/// The label prefix of the queries of a FRI layer.
+const ROW: &str = "Row ";
+
+/// The label of the evaluation point of a FRI layer.
+const EVALUATION_POINT: &str = "Evaluation point";
+
+/// Represents errors that can occur when serializing a proof for the EVM verifier.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SolidityError {
+    /// Error variant indicating a malformed annotation.
+    #[error("Invalid annotation '{0}'")]
+    InvalidAnnotation(String),
+
+    /// Error variant indicating that the proof is not valid hex.
+    #[error("Invalid proof hex")]
+    InvalidProofHex,
+
+    /// Error variant indicating a decommitment without the commitment of its tree.
+    #[error("Missing commitment of the decommitment {0}")]
+    MissingCommitment(String),
+
+    /// Error variant indicating a FRI layer without evaluation point.
+    #[error("Missing evaluation point of the FRI layer {0}")]
+    MissingEvaluationPoint(String),
+
+    /// Error variant indicating a FRI layer missing from the FRI steps of the proof parameters.
+    #[error("Missing FRI step of the layer {0}")]
+    MissingFriStep(String),
+
+    /// Error variant indicating a decommitment whose queue is empty.
+    #[error("Empty queue of the decommitment {0}")]
+    EmptyQueue(String),
+
+    /// Error variant indicating that an annotation covers bytes outside of the proof or not
+    /// aligned to its words.
+    #[error("Annotation range {start}..{end} does not match the words of the proof")]
+    InvalidRange {
+        /// The first byte of the range.
+        start: usize,
+        /// The end of the range.
+        end: usize,
+    },
+}
+
+/// The direction of an annotated message of the proof transcript.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// A message of the prover, written in the proof.
+    ProverToVerifier,
+    /// A random value drawn by the verifier, not written in the proof.
+    VerifierToProver,
+}
+
+/// An annotation of the proof transcript.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    /// The direction of the message.
+    pub direction: Direction,
+    /// The bytes of the proof holding the message, for the messages of the prover.
+    pub range: Option<Range<usize>>,
+    /// The path of the message in the protocol, e.g. `/cpu air/STARK/FRI/Commitment/Layer 1`.
+    pub path: String,
+    /// The label of the message, e.g. `Commitment` or `Interaction element #0`.
+    pub label: String,
+    /// The values of the message, e.g. the hash of a commitment.
+    pub values: Vec<U256>,
+}
+
+impl Annotation {
+    /// Parses an annotation, of the form `{direction}[{start}:{end}]: {path}: {label}: {values}`,
+    /// the range only being present for the messages of the prover.
+    pub fn parse(line: &str) -> Result<Self, SolidityError> {
+        let invalid = || SolidityError::InvalidAnnotation(line.to_string());
+
+        let (head, rest) = line.split_once(": ").ok_or_else(invalid)?;
+        let (direction, range) = match head.split_once('[') {
+            Some((direction, range)) => {
+                let (start, end) = range
+                    .strip_suffix(']')
+                    .and_then(|range| range.split_once(':'))
+                    .ok_or_else(invalid)?;
+                let start = start.parse().map_err(|_| invalid())?;
+                let end = end.parse().map_err(|_| invalid())?;
+                (direction, Some(start..end))
+            }
+            None => (head, None),
+        };
+        let direction = match direction {
+            "P->V" => Direction::ProverToVerifier,
+            "V->P" => Direction::VerifierToProver,
+            _ => return Err(invalid()),
+        };
+
+        let (path, message) = rest.split_once(": ").ok_or_else(invalid)?;
+        let (label, values) = message.rsplit_once(": ").unwrap_or(("", message));
+        let values = values
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|token| token.starts_with("0x"))
+            .map(|token| token.parse().map_err(|_| invalid()))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { direction, range, path: path.to_string(), label: label.to_string(), values })
+    }
+}
+
+/// The FRI parameters of a proof.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FriParameters {
+    /// The number of layers folded by each FRI step, as log2.
+    pub fri_step_list: Vec<u64>,
+    /// The degree bound of the last FRI layer.
+    pub last_layer_degree_bound: u64,
+    /// The number of queries.
+    pub n_queries: u64,
+    /// The number of bits of the proof of work.
+    pub proof_of_work_bits: u64,
+}
+
+/// The STARK parameters of a proof.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StarkParameters {
+    /// The blowup factor of the evaluation domain, as log2.
+    pub log_n_cosets: u64,
+    /// The FRI parameters.
+    pub fri: FriParameters,
+}
+
+/// The parameters of a proof, as written by the prover.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofParameters {
+    /// The STARK parameters.
+    pub stark: StarkParameters,
+}
+
+impl ProofParameters {
+    /// Returns the parameters as expected by the verifier: the number of queries, the blowup
+    /// factor, the proof of work bits, the degree bound of the last FRI layer (all as log2 where
+    /// applicable), and the FRI steps preceded by their number.
+    pub fn to_words(&self) -> Vec<U256> {
+        let fri = &self.stark.fri;
+        let mut words = vec![
+            U256::from(fri.n_queries),
+            U256::from(self.stark.log_n_cosets),
+            U256::from(fri.proof_of_work_bits),
+            U256::from(fri.last_layer_degree_bound.max(1).ilog2()),
+            U256::from(fri.fri_step_list.len()),
+        ];
+        words.extend(fri.fri_step_list.iter().copied().map(U256::from));
+        words
+    }
+}
+
+/// A proof written by the prover in annotated mode.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnnotatedProof {
+    /// The parameters of the proof.
+    pub proof_parameters: ProofParameters,
+    /// The proof, as hex.
+    pub proof_hex: String,
+    /// The annotations of the proof transcript.
+    pub annotations: Vec<String>,
+    /// The annotations of the values the verifier computes from the transcript, e.g. the FRI
+    /// queries.
+    #[serde(default)]
+    pub extra_annotations: Vec<String>,
+}
+
+/// The kind of a statement registered before the main proof.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatementKind {
+    /// The decommitment of a trace or composition commitment.
+    Merkle {
+        /// The height of the tree.
+        height: u32,
+    },
+    /// The decommitment of a FRI layer.
+    Fri {
+        /// The point the layer is evaluated at.
+        evaluation_point: U256,
+        /// The number of layers folded by the FRI step, as log2.
+        step_size: u64,
+    },
+}
+
+/// The words of the proof registered as a statement before the main proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement {
+    /// The kind of the statement.
+    pub kind: StatementKind,
+    /// The path of the decommitment, e.g. `/cpu air/STARK/FRI/Decommitment/Layer 1`.
+    pub path: String,
+    /// The words of the decommitment, in proof order.
+    pub words: Vec<U256>,
+    /// The queue of the decommitment: the `(node, hash)` pairs of the leaves of a Merkle tree, or
+    /// the `(query, value, inverse of the evaluation point)` triplets of a FRI layer.
+    pub queue: Vec<U256>,
+    /// The root of the tree, as committed in the proof.
+    pub root: U256,
+}
+
+impl Statement {
+    /// Encodes the call registering the statement on its statement contract.
+    pub fn encode_verify(&self) -> Bytes {
+        match self.kind {
+            StatementKind::Merkle { height } => encode_call(
+                VERIFY_MERKLE_SIGNATURE,
+                &[&self.words, &self.queue],
+                &[U256::from(height), self.root],
+            ),
+            StatementKind::Fri { evaluation_point, step_size } => {
+                // The FRI queue is terminated by a zero.
+                let queue: Vec<_> = self.queue.iter().copied().chain([U256::ZERO]).collect();
+                encode_call(
+                    VERIFY_FRI_SIGNATURE,
+                    &[&self.words, &queue],
+                    &[evaluation_point, U256::from(step_size), self.root],
+                )
+            }
+        }
+    }
+}
+
+/// The annotations of a decommitment, collected before their commitment is known.
+#[derive(Debug, Default)]
+struct Decommitment {
+    /// The path of the decommitment.
+    path: String,
+    /// The authentication words.
+    words: Vec<U256>,
+    /// The queue of the decommitment.
+    queue: Vec<U256>,
+}
+
+/// A proof split for the EVM verifier.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitProof {
+    /// The parameters of the proof, as expected by the verifier.
+    pub proof_params: Vec<U256>,
+    /// The words of the main proof, without the decommitments.
+    pub main_proof: Vec<U256>,
+    /// The decommitments, registered as statements before the main proof, in proof order.
+    pub statements: Vec<Statement>,
+    /// The interaction elements drawn by the verifier, appended to the auxiliary input of the
+    /// Cairo verifier.
+    pub interaction_elements: Vec<U256>,
+}
+
+impl SplitProof {
+    /// Splits an annotated proof.
+    pub fn from_annotated(proof: &AnnotatedProof) -> Result<Self, SolidityError> {
+        let bytes = hex::decode(&proof.proof_hex).map_err(|_| SolidityError::InvalidProofHex)?;
+        let annotations = proof
+            .annotations
+            .iter()
+            .chain(&proof.extra_annotations)
+            .map(|line| Annotation::parse(line))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut main_proof = Vec::new();
+        let mut decommitments: Vec<Decommitment> = Vec::new();
+        let mut commitments = HashMap::new();
+        let mut evaluation_points = HashMap::new();
+        let mut interaction_elements = Vec::new();
+        for annotation in annotations {
+            let Some(range) = annotation.range.clone() else {
+                if annotation.label.starts_with(INTERACTION_ELEMENT) {
+                    interaction_elements.extend(annotation.values);
+                } else if annotation.label == EVALUATION_POINT {
+                    let point = annotation.values.first().copied().ok_or_else(|| {
+                        SolidityError::InvalidAnnotation(annotation.label.clone())
+                    })?;
+                    evaluation_points.insert(tree_path(&annotation.path), point);
+                } else if let Some(index) = annotation.path.find(DECOMMITMENT) {
+                    let queue = queue_entry(&annotation)?;
+                    decommitment(&mut decommitments, &annotation.path, index).queue.extend(queue);
+                }
+                continue;
+            };
+            let words = words(&bytes, range)?;
+
+            // Decommitments are grouped by the path of their commitment, e.g. by FRI layer.
+            let Some(index) = annotation.path.find(DECOMMITMENT) else {
+                if annotation.label == COMMITMENT {
+                    if let Some(root) = words.first() {
+                        commitments.insert(tree_path(&annotation.path), *root);
+                    }
+                }
+                main_proof.extend(words);
+                continue;
+            };
+            decommitment(&mut decommitments, &annotation.path, index).words.extend(words);
+        }
+
+        let statements = decommitments
+            .into_iter()
+            .map(|Decommitment { path, words, queue }| {
+                if queue.is_empty() {
+                    return Err(SolidityError::EmptyQueue(path));
+                }
+                let tree = tree_path(&path);
+                let root = *commitments
+                    .get(&tree)
+                    .ok_or_else(|| SolidityError::MissingCommitment(path.clone()))?;
+                let kind = if path.contains(FRI) {
+                    let evaluation_point = *evaluation_points
+                        .get(&tree)
+                        .ok_or_else(|| SolidityError::MissingEvaluationPoint(path.clone()))?;
+                    let step_size = fri_layer(&path)
+                        .and_then(|layer| proof.proof_parameters.stark.fri.fri_step_list.get(layer))
+                        .copied()
+                        .ok_or_else(|| SolidityError::MissingFriStep(path.clone()))?;
+                    StatementKind::Fri { evaluation_point, step_size }
+                } else {
+                    // The leaves are all at the height of the tree, their node index being
+                    // `2^height + row`.
+                    StatementKind::Merkle { height: queue[0].bit_len().saturating_sub(1) as u32 }
+                };
+                Ok(Statement { kind, path, words, queue, root })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            proof_params: proof.proof_parameters.to_words(),
+            main_proof,
+            statements,
+            interaction_elements,
+        })
+    }
+
+    /// Returns the auxiliary input of the Cairo verifier: the public input of the run, followed by
+    /// the interaction elements.
+    pub fn cairo_aux_input(&self, public_input: &[U256]) -> Vec<U256> {
+        public_input.iter().chain(&self.interaction_elements).copied().collect()
+    }
+
+    /// Encodes the call verifying the main proof and registering its fact on the statement
+    /// verifier.
+    pub fn encode_verify_proof(
+        &self,
+        task_metadata: &[U256],
+        public_input: &[U256],
+        cairo_verifier_id: U256,
+    ) -> Bytes {
+        encode_call(
+            VERIFY_PROOF_SIGNATURE,
+            &[
+                &self.proof_params,
+                &self.main_proof,
+                task_metadata,
+                &self.cairo_aux_input(public_input),
+            ],
+            &[cairo_verifier_id],
+        )
+    }
+}
+
+/// The outcome of the simulation of a verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Simulation {
+    /// The verification succeeded.
+    Verified,
+    /// The verification reverted, with the error of the node.
+    Reverted(String),
+}
+
+/// Simulates a call to a verifier with an `eth_call`, before paying for its transaction.
+///
+/// A revert of the verifier is returned as [`Simulation::Reverted`], while transport errors are
+/// returned as errors.
+pub async fn simulate<P, T>(
+    provider: &P,
+    verifier: Address,
+    calldata: Bytes,
+) -> TransportResult<Simulation>
+where
+    P: Provider<T>,
+    T: Transport + Clone,
+{
+    let request = TransactionRequest::default().to(verifier).input(calldata.into());
+    match provider.call(&request).await {
+        Ok(_) => Ok(Simulation::Verified),
+        Err(RpcError::ErrorResp(payload)) => Ok(Simulation::Reverted(payload.message.to_string())),
+        Err(err) => Err(err),
+    }
+}
+
+/// Encodes a call to a function taking the given `uint256[]` arrays followed by the given `uint256`
+/// words.
+fn encode_call(signature: &str, arrays: &[&[U256]], words: &[U256]) -> Bytes {
+    let mut calldata = keccak256(signature)[..4].to_vec();
+    // The head holds the offsets of the arrays, then the static words.
+    let mut offset = (arrays.len() + words.len()) * WORD_SIZE;
+    for array in arrays {
+        calldata.extend_from_slice(&U256::from(offset).to_be_bytes::<WORD_SIZE>());
+        offset += (array.len() + 1) * WORD_SIZE;
+    }
+    for word in words {
+        calldata.extend_from_slice(&word.to_be_bytes::<WORD_SIZE>());
+    }
+    for array in arrays {
+        calldata.extend_from_slice(&U256::from(array.len()).to_be_bytes::<WORD_SIZE>());
+        for word in *array {
+            calldata.extend_from_slice(&word.to_be_bytes::<WORD_SIZE>());
+        }
+    }
+    calldata.into()
+}
+
+/// Returns the decommitment of an annotation at `path`, whose `Decommitment` segment starts at
+/// `index`, appending it on its first annotation so that the decommitments stay in proof order.
+fn decommitment<'a>(
+    decommitments: &'a mut Vec<Decommitment>,
+    path: &str,
+    index: usize,
+) -> &'a mut Decommitment {
+    let path = decommitment_path(path, index);
+    let position = match decommitments.iter().position(|decommitment| decommitment.path == path) {
+        Some(position) => position,
+        None => {
+            decommitments.push(Decommitment { path, ..Default::default() });
+            decommitments.len() - 1
+        }
+    };
+    &mut decommitments[position]
+}
+
+/// Returns the entry of the queue of a decommitment annotated by the verifier: the node and hash
+/// of a Merkle leaf, or the query, value and inverse of the evaluation point of a FRI query.
+fn queue_entry(annotation: &Annotation) -> Result<Vec<U256>, SolidityError> {
+    let invalid = || SolidityError::InvalidAnnotation(annotation.label.clone());
+    let (index, values) = match annotation.label.strip_prefix(NODE) {
+        Some(node) => (node, 1),
+        None => (annotation.label.strip_prefix(ROW).ok_or_else(invalid)?, 2),
+    };
+    if annotation.values.len() != values {
+        return Err(invalid());
+    }
+    let index: U256 = index.parse().map_err(|_| invalid())?;
+    Ok([index].into_iter().chain(annotation.values.iter().copied()).collect())
+}
+
+/// Returns the path identifying the tree of a commitment or decommitment annotation, i.e. its path
+/// without its `Commit...` or `Decommitment` segment.
+fn tree_path(path: &str) -> String {
+    path.split('/')
+        .filter(|segment| *segment != "Decommitment" && !segment.starts_with("Commit"))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Returns the index of the FRI layer of a path, e.g. 1 for `.../FRI/Decommitment/Layer 1`.
+fn fri_layer(path: &str) -> Option<usize> {
+    path.rsplit('/').next()?.strip_prefix("Layer ")?.parse().ok()
+}
+
+/// Returns the words of the proof in the given byte range.
+fn words(bytes: &[u8], range: Range<usize>) -> Result<Vec<U256>, SolidityError> {
+    let invalid = || SolidityError::InvalidRange { start: range.start, end: range.end };
+    if range.start % WORD_SIZE != 0 || range.end % WORD_SIZE != 0 {
+        return Err(invalid());
+    }
+    let bytes = bytes.get(range.clone()).ok_or_else(invalid)?;
+    Ok(bytes.chunks_exact(WORD_SIZE).map(U256::from_be_slice).collect())
+}
+
+/// Returns the path of the commitment of a decommitment annotation: up to the `Decommitment`
+/// segment, with the following layer segment for the FRI layers.
+fn decommitment_path(path: &str, index: usize) -> String {
+    let end = index + DECOMMITMENT.len();
+    let layer = path[end..]
+        .strip_prefix('/')
+        .filter(|rest| rest.starts_with("Layer"))
+        .map(|rest| rest.split('/').next().unwrap_or_default().len() + 1)
+        .unwrap_or_default();
+    path[..end + layer].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(byte: u8) -> String {
+        format!("{byte:064x}")
+    }
+
+    #[test]
+    fn test_parse_annotation() {
+        let annotation = Annotation::parse(
+            "P->V[32:96]: /cpu air/STARK/Out Of Domain Sampling: OODS values: \
+             Field Elements(0x1, 0x2)",
+        )
+        .unwrap();
+        assert_eq!(annotation.direction, Direction::ProverToVerifier);
+        assert_eq!(annotation.range, Some(32..96));
+        assert_eq!(annotation.path, "/cpu air/STARK/Out Of Domain Sampling");
+        assert_eq!(annotation.label, "OODS values");
+        assert_eq!(annotation.values, vec![U256::from(1), U256::from(2)]);
+
+        let annotation = Annotation::parse(
+            "V->P: /cpu air/STARK/Interaction: Interaction element #0: Field Element(0xab)",
+        )
+        .unwrap();
+        assert_eq!(annotation.direction, Direction::VerifierToProver);
+        assert_eq!(annotation.range, None);
+        assert_eq!(annotation.values, vec![U256::from(0xab)]);
+
+        assert!(Annotation::parse("X->Y: /path: label: Hash(0x1)").is_err());
+        assert!(Annotation::parse("P->V[1]: /path: label: Hash(0x1)").is_err());
+    }
+
+    #[test]
+    fn test_split_proof() {
+        let proof = AnnotatedProof {
+            proof_parameters: ProofParameters {
+                stark: StarkParameters {
+                    log_n_cosets: 2,
+                    fri: FriParameters {
+                        fri_step_list: vec![0, 4],
+                        last_layer_degree_bound: 64,
+                        n_queries: 18,
+                        proof_of_work_bits: 24,
+                    },
+                },
+            },
+            proof_hex: (1..=5).map(word).collect(),
+            annotations: vec![
+                "P->V[0:32]: /cpu air/STARK/Original/Commit on Trace: Commitment: Hash(0x1)"
+                    .to_string(),
+                "P->V[32:64]: /cpu air/STARK/FRI/Commitment/Layer 1: Commitment: Hash(0x5)"
+                    .to_string(),
+                "P->V[64:96]: /cpu air/STARK/Original/Decommitment: For node 3: Hash(0x2)"
+                    .to_string(),
+                "P->V[96:128]: /cpu air/STARK/FRI/Decommitment/Layer 1: For node 5: Hash(0x3)"
+                    .to_string(),
+                "P->V[128:160]: /cpu air/STARK/FRI/Decommitment/Layer 1: For node 7: Hash(0x4)"
+                    .to_string(),
+            ],
+            extra_annotations: vec![
+                "V->P: /cpu air/STARK/Interaction: Interaction element #0: Field Element(0x9)"
+                    .to_string(),
+                "V->P: /cpu air/STARK/FRI/Commitment/Layer 1: Evaluation point: \
+                 Field Element(0xe)"
+                    .to_string(),
+                "V->P: /cpu air/STARK/Original/Decommitment: For node 8: Hash(0xa)".to_string(),
+                "V->P: /cpu air/STARK/FRI/Decommitment/Layer 1: Row 4: \
+                 Field Elements(0xb, 0xc)"
+                    .to_string(),
+            ],
+        };
+
+        let split = SplitProof::from_annotated(&proof).unwrap();
+
+        assert_eq!(split.proof_params, [18, 2, 24, 6, 2, 0, 4].map(U256::from).to_vec());
+        assert_eq!(split.main_proof, vec![U256::from(1), U256::from(5)]);
+        assert_eq!(
+            split.statements,
+            vec![
+                Statement {
+                    kind: StatementKind::Merkle { height: 3 },
+                    path: "/cpu air/STARK/Original/Decommitment".to_string(),
+                    words: vec![U256::from(2)],
+                    queue: vec![U256::from(8), U256::from(0xa)],
+                    root: U256::from(1),
+                },
+                Statement {
+                    kind: StatementKind::Fri { evaluation_point: U256::from(0xe), step_size: 4 },
+                    path: "/cpu air/STARK/FRI/Decommitment/Layer 1".to_string(),
+                    words: vec![U256::from(3), U256::from(4)],
+                    queue: vec![U256::from(4), U256::from(0xb), U256::from(0xc)],
+                    root: U256::from(5),
+                },
+            ]
+        );
+        assert_eq!(split.cairo_aux_input(&[U256::from(7)]), vec![U256::from(7), U256::from(9)]);
+
+        let calldata = split.statements[0].encode_verify();
+        assert_eq!(calldata[..4], keccak256(VERIFY_MERKLE_SIGNATURE)[..4]);
+        let words = calldata[4..].chunks(WORD_SIZE).map(U256::from_be_slice).collect::<Vec<_>>();
+        assert_eq!(words, [128, 192, 3, 1, 1, 2, 2, 8, 0xa].map(U256::from).to_vec());
+
+        let calldata = split.statements[1].encode_verify();
+        assert_eq!(calldata[..4], keccak256(VERIFY_FRI_SIGNATURE)[..4]);
+        let words = calldata[4..].chunks(WORD_SIZE).map(U256::from_be_slice).collect::<Vec<_>>();
+        assert_eq!(
+            words,
+            [160, 256, 0xe, 4, 5, 2, 3, 4, 4, 4, 0xb, 0xc, 0].map(U256::from).to_vec()
+        );
+    }
+
+    #[test]
+    fn test_split_proof_missing_commitment() {
+        let proof = AnnotatedProof {
+            proof_parameters: ProofParameters {
+                stark: StarkParameters {
+                    log_n_cosets: 2,
+                    fri: FriParameters {
+                        fri_step_list: vec![],
+                        last_layer_degree_bound: 1,
+                        n_queries: 1,
+                        proof_of_work_bits: 0,
+                    },
+                },
+            },
+            proof_hex: word(1),
+            annotations: vec![
+                "P->V[0:32]: /cpu air/STARK/Original/Decommitment: For node 3: Hash(0x1)"
+                    .to_string(),
+            ],
+            extra_annotations: vec![
+                "V->P: /cpu air/STARK/Original/Decommitment: For node 2: Hash(0x2)".to_string(),
+            ],
+        };
+
+        assert_eq!(
+            SplitProof::from_annotated(&proof),
+            Err(SolidityError::MissingCommitment(
+                "/cpu air/STARK/Original/Decommitment".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_encode_verify_proof() {
+        let split = SplitProof {
+            proof_params: vec![U256::from(1)],
+            main_proof: vec![U256::from(2), U256::from(3)],
+            statements: vec![],
+            interaction_elements: vec![],
+        };
+
+        let calldata = split.encode_verify_proof(&[], &[U256::from(4)], U256::from(5));
+
+        assert_eq!(calldata[..4], keccak256(VERIFY_PROOF_SIGNATURE)[..4]);
+        let words = calldata[4..].chunks(WORD_SIZE).map(U256::from_be_slice).collect::<Vec<_>>();
+        assert_eq!(words, [160, 224, 320, 352, 5, 1, 1, 2, 2, 3, 0, 1, 4].map(U256::from).to_vec());
+    }
+
+    #[test]
+    fn test_split_proof_invalid_range() {
+        let proof = AnnotatedProof {
+            proof_parameters: ProofParameters {
+                stark: StarkParameters {
+                    log_n_cosets: 2,
+                    fri: FriParameters {
+                        fri_step_list: vec![],
+                        last_layer_degree_bound: 1,
+                        n_queries: 1,
+                        proof_of_work_bits: 0,
+                    },
+                },
+            },
+            proof_hex: word(1),
+            annotations: vec!["P->V[0:64]: /cpu air/STARK: Commitment: Hash(0x1)".to_string()],
+            extra_annotations: vec![],
+        };
+
+        assert_eq!(
+            SplitProof::from_annotated(&proof),
+            Err(SolidityError::InvalidRange { start: 0, end: 64 })
+        );
+    }
+}
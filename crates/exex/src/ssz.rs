@@ -0,0 +1,335 @@
+//! Minimal [SSZ](https://github.com/ethereum/consensus-specs/blob/dev/ssz/simple-serialize.md)
+//! serialization and merkleization.
+//!
+//! Only the subset of SSZ required to encode the keth outputs is supported: `uint64`, `uint256`,
+//! `boolean`, fixed-size byte vectors, containers, lists of composite types and byte lists.
+//! Optional values are encoded as lists with a limit of one element.
+
+use alloy_primitives::{Address, B256, U256};
+use sha2::{Digest, Sha256};
+
+/// The size in bytes of the offsets of variable-size fields.
+pub const BYTES_PER_LENGTH_OFFSET: usize = 4;
+
+/// The size in bytes of a merkleization chunk.
+pub const BYTES_PER_CHUNK: usize = 32;
+
+/// A type which can be SSZ serialized and merkleized.
+pub trait Ssz {
+    /// Returns the size in bytes of the serialization of the type if it is fixed, `None` if the
+    /// type is variable-size.
+    fn fixed_size(&self) -> Option<usize>;
+
+    /// Appends the SSZ serialization of the value to the buffer.
+    fn ssz_append(&self, buf: &mut Vec<u8>);
+
+    /// Returns the hash tree root of the value.
+    fn hash_tree_root(&self) -> B256;
+
+    /// Returns the SSZ serialization of the value.
+    fn ssz_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.ssz_append(&mut buf);
+        buf
+    }
+}
+
+impl Ssz for bool {
+    fn fixed_size(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        buf.push(u8::from(*self));
+    }
+
+    fn hash_tree_root(&self) -> B256 {
+        pack_chunk(&[u8::from(*self)])
+    }
+}
+
+impl Ssz for u64 {
+    fn fixed_size(&self) -> Option<usize> {
+        Some(8)
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn hash_tree_root(&self) -> B256 {
+        pack_chunk(&self.to_le_bytes())
+    }
+}
+
+impl Ssz for U256 {
+    fn fixed_size(&self) -> Option<usize> {
+        Some(32)
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.to_le_bytes::<32>());
+    }
+
+    fn hash_tree_root(&self) -> B256 {
+        B256::from(self.to_le_bytes::<32>())
+    }
+}
+
+impl Ssz for B256 {
+    fn fixed_size(&self) -> Option<usize> {
+        Some(32)
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.as_slice());
+    }
+
+    fn hash_tree_root(&self) -> B256 {
+        *self
+    }
+}
+
+impl Ssz for Address {
+    fn fixed_size(&self) -> Option<usize> {
+        Some(20)
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.as_slice());
+    }
+
+    fn hash_tree_root(&self) -> B256 {
+        pack_chunk(self.as_slice())
+    }
+}
+
+/// A `List[T, N]` of composite types.
+#[derive(Debug, Clone, Copy)]
+pub struct List<'a, T> {
+    /// The elements of the list.
+    pub items: &'a [T],
+    /// The maximum number of elements of the list.
+    pub limit: usize,
+}
+
+impl<'a, T> List<'a, T> {
+    /// Creates a new [`List`].
+    pub const fn new(items: &'a [T], limit: usize) -> Self {
+        Self { items, limit }
+    }
+}
+
+impl<T: Ssz> Ssz for List<'_, T> {
+    fn fixed_size(&self) -> Option<usize> {
+        None
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        let fields = self.items.iter().map(|item| item as &dyn Ssz).collect::<Vec<_>>();
+        append_container(&fields, buf);
+    }
+
+    fn hash_tree_root(&self) -> B256 {
+        let roots = self.items.iter().map(Ssz::hash_tree_root).collect::<Vec<_>>();
+        mix_in_length(merkleize(&roots, Some(self.limit)), self.items.len())
+    }
+}
+
+/// A `List[byte, N]`.
+#[derive(Debug, Clone, Copy)]
+pub struct ByteList<'a> {
+    /// The bytes of the list.
+    pub bytes: &'a [u8],
+    /// The maximum number of bytes of the list.
+    pub limit: usize,
+}
+
+impl<'a> ByteList<'a> {
+    /// Creates a new [`ByteList`].
+    pub const fn new(bytes: &'a [u8], limit: usize) -> Self {
+        Self { bytes, limit }
+    }
+}
+
+impl Ssz for ByteList<'_> {
+    fn fixed_size(&self) -> Option<usize> {
+        None
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.bytes);
+    }
+
+    fn hash_tree_root(&self) -> B256 {
+        let chunks = self.bytes.chunks(BYTES_PER_CHUNK).map(pack_chunk).collect::<Vec<_>>();
+        let limit = self.limit.div_ceil(BYTES_PER_CHUNK);
+        mix_in_length(merkleize(&chunks, Some(limit)), self.bytes.len())
+    }
+}
+
+/// Appends the serialization of a container (or of a list of composite types) made of the given
+/// fields to the buffer.
+///
+/// Fixed-size fields are serialized in place, variable-size fields are replaced by their offset
+/// and serialized after all the fixed-size parts.
+pub fn append_container(fields: &[&dyn Ssz], buf: &mut Vec<u8>) {
+    let fixed_len: usize =
+        fields.iter().map(|field| field.fixed_size().unwrap_or(BYTES_PER_LENGTH_OFFSET)).sum();
+
+    let mut variable = Vec::new();
+    for field in fields {
+        if field.fixed_size().is_some() {
+            field.ssz_append(buf);
+        } else {
+            let offset = u32::try_from(fixed_len + variable.len()).expect("SSZ offset overflow");
+            buf.extend_from_slice(&offset.to_le_bytes());
+            field.ssz_append(&mut variable);
+        }
+    }
+    buf.extend_from_slice(&variable);
+}
+
+/// Returns the hash tree root of a container made of the given fields.
+pub fn container_root(fields: &[&dyn Ssz]) -> B256 {
+    let roots = fields.iter().map(|field| field.hash_tree_root()).collect::<Vec<_>>();
+    merkleize(&roots, None)
+}
+
+/// Returns the fixed size of a container made of the given fields, `None` if any of its fields is
+/// variable-size.
+pub fn container_fixed_size(fields: &[&dyn Ssz]) -> Option<usize> {
+    fields.iter().map(|field| field.fixed_size()).sum()
+}
+
+/// Merkleizes the chunks into a binary tree padded with zero chunks up to `limit` leaves.
+///
+/// When no limit is given, the tree is padded up to the number of chunks.
+pub fn merkleize(chunks: &[B256], limit: Option<usize>) -> B256 {
+    let limit = limit.unwrap_or(chunks.len());
+    debug_assert!(chunks.len() <= limit, "more chunks than the merkleization limit");
+
+    let depth = limit.max(1).next_power_of_two().trailing_zeros();
+    let mut layer = chunks.to_vec();
+    // The root of a subtree of zero chunks at the current depth.
+    let mut zero = B256::ZERO;
+
+    for _ in 0..depth {
+        if layer.len() % 2 == 1 {
+            layer.push(zero);
+        }
+        layer = layer.chunks(2).map(|pair| hash_pair(&pair[0], &pair[1])).collect();
+        zero = hash_pair(&zero, &zero);
+    }
+
+    layer.first().copied().unwrap_or(zero)
+}
+
+/// Mixes the length of a list into its root.
+pub fn mix_in_length(root: B256, length: usize) -> B256 {
+    let mut length_chunk = [0u8; BYTES_PER_CHUNK];
+    length_chunk[..8].copy_from_slice(&(length as u64).to_le_bytes());
+    hash_pair(&root, &B256::from(length_chunk))
+}
+
+/// Returns the SHA-256 hash of the concatenation of two chunks.
+fn hash_pair(left: &B256, right: &B256) -> B256 {
+    let mut hasher = Sha256::new();
+    hasher.update(left);
+    hasher.update(right);
+    B256::from_slice(&hasher.finalize())
+}
+
+/// Packs at most [`BYTES_PER_CHUNK`] bytes in a single right-padded chunk.
+fn pack_chunk(bytes: &[u8]) -> B256 {
+    let mut chunk = [0u8; BYTES_PER_CHUNK];
+    chunk[..bytes.len()].copy_from_slice(bytes);
+    B256::from(chunk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_basic_types_serialization() {
+        assert_eq!(true.ssz_bytes(), vec![1]);
+        assert_eq!(0x0102u64.ssz_bytes(), vec![2, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(U256::from(1).ssz_bytes()[0], 1);
+        assert_eq!(Address::repeat_byte(0xaa).ssz_bytes(), vec![0xaa; 20]);
+    }
+
+    #[test]
+    fn test_basic_types_root_is_padded_chunk() {
+        let mut expected = [0u8; 32];
+        expected[0] = 5;
+        assert_eq!(5u64.hash_tree_root(), B256::from(expected));
+
+        let mut expected = [0u8; 32];
+        expected[..20].copy_from_slice(&[0xaa; 20]);
+        assert_eq!(Address::repeat_byte(0xaa).hash_tree_root(), B256::from(expected));
+    }
+
+    #[test]
+    fn test_merkleize() {
+        let a = B256::repeat_byte(1);
+        let b = B256::repeat_byte(2);
+
+        // A single chunk is its own root.
+        assert_eq!(merkleize(&[a], None), a);
+        // Two chunks are hashed together.
+        assert_eq!(merkleize(&[a, b], None), hash_pair(&a, &b));
+        // Three chunks are padded with a zero chunk.
+        assert_eq!(
+            merkleize(&[a, b, a], None),
+            hash_pair(&hash_pair(&a, &b), &hash_pair(&a, &B256::ZERO))
+        );
+        // The limit pads the tree with zero subtrees.
+        let zero_1 = hash_pair(&B256::ZERO, &B256::ZERO);
+        assert_eq!(merkleize(&[a], Some(4)), hash_pair(&hash_pair(&a, &B256::ZERO), &zero_1));
+        // An empty tree is a tree of zero chunks.
+        assert_eq!(merkleize(&[], Some(4)), hash_pair(&zero_1, &zero_1));
+    }
+
+    #[test]
+    fn test_container_with_variable_fields() {
+        let bytes = [0xff; 3];
+        let fields: [&dyn Ssz; 3] = [&1u64, &ByteList::new(&bytes, 32), &true];
+
+        // 8 bytes of `uint64`, 4 bytes of offset, 1 byte of boolean then the variable part.
+        let mut buf = Vec::new();
+        append_container(&fields, &mut buf);
+        let expected = [&1u64.to_le_bytes()[..], &[13u8, 0, 0, 0][..], &[1u8][..], &bytes[..]];
+        assert_eq!(buf, expected.concat());
+        assert_eq!(container_fixed_size(&fields), None);
+    }
+
+    #[test]
+    fn test_list_of_variable_items() {
+        let items = [ByteList::new(&[1], 8), ByteList::new(&[2, 3], 8)];
+        let list = List::new(&items, 4);
+
+        // Two offsets, then the items.
+        assert_eq!(list.ssz_bytes(), vec![8, 0, 0, 0, 9, 0, 0, 0, 1, 2, 3]);
+        assert_eq!(
+            list.hash_tree_root(),
+            mix_in_length(
+                merkleize(&[items[0].hash_tree_root(), items[1].hash_tree_root()], Some(4)),
+                2
+            )
+        );
+    }
+
+    #[test]
+    fn test_byte_list_root() {
+        let bytes = [0x42; 40];
+        let list = ByteList::new(&bytes, 64);
+
+        let mut second = [0u8; 32];
+        second[..8].copy_from_slice(&[0x42; 8]);
+        assert_eq!(
+            list.hash_tree_root(),
+            mix_in_length(hash_pair(&B256::repeat_byte(0x42), &B256::from(second)), 40)
+        );
+    }
+}
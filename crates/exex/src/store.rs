@@ -0,0 +1,344 @@
+//! Storage of the proofs and of the metadata of the proven blocks.
+//!
+//! The persistence of the artifacts consumed downstream of the proving, i.e. the proofs, the
+//! program outputs, the programs selected for the blocks and the chain of the proven blocks, is
+//! abstracted behind the [`KethStore`] trait, with two embedded implementations:
+//! - the SQLite [`Database`], which also holds the blocks, traces and jobs of the pipeline;
+//! - the [`RedbStore`], a single redb file holding the artifacts only, e.g. for the standalone
+//!   deployments serving the proven blocks and for the tests, which can run it in memory.
+//!
+//! An instance stores them in its database unless its `proof-store` is configured, see
+//! [`open_store`].
+
+use crate::{
+    chain::ChainLink,
+    db::Database,
+    output::{ProgramOutput, ProofMetadata},
+    program::BlockProgram,
+};
+use redb::{backends::InMemoryBackend, ReadableTable, TableDefinition};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fmt, path::Path, sync::Arc};
+
+/// The storage of the proofs and of the metadata of the proven blocks.
+pub trait KethStore: fmt::Debug + Send + Sync {
+    /// Inserts the proof of a block, replacing a previous one.
+    fn insert_proof(&self, metadata: &ProofMetadata, proof: &[u8]) -> eyre::Result<()>;
+
+    /// Retrieves the proof of a block with its metadata, if any.
+    fn proof(&self, number: u64) -> eyre::Result<Option<(ProofMetadata, Vec<u8>)>>;
+
+    /// Deletes the proof of a block with its metadata, if any.
+    fn delete_proof(&self, number: u64) -> eyre::Result<()>;
+
+    /// Retrieves the numbers of the proven blocks in `[from_block, to_block]`, in ascending order.
+    fn proven_blocks(&self, from_block: u64, to_block: u64) -> eyre::Result<Vec<u64>>;
+
+    /// Inserts the output of the Kakarot program for a block, replacing a previous one.
+    fn insert_program_output(&self, output: &ProgramOutput) -> eyre::Result<()>;
+
+    /// Retrieves the output of the Kakarot program for a block, if any.
+    fn program_output(&self, number: u64) -> eyre::Result<Option<ProgramOutput>>;
+
+    /// Inserts the program selected for a block, replacing a previous one.
+    fn insert_block_program(&self, program: &BlockProgram) -> eyre::Result<()>;
+
+    /// Retrieves the program selected for a block, if any.
+    fn block_program(&self, number: u64) -> eyre::Result<Option<BlockProgram>>;
+
+    /// Inserts a link of the chain of the proven blocks, replacing a previous link of the block.
+    fn insert_chain_link(&self, link: &ChainLink) -> eyre::Result<()>;
+
+    /// Retrieves the link of a block in the chain of the proven blocks, if any.
+    fn chain_link(&self, number: u64) -> eyre::Result<Option<ChainLink>>;
+
+    /// Retrieves the head of the chain of the proven blocks, i.e. its link of highest block number.
+    fn chain_head(&self) -> eyre::Result<Option<ChainLink>>;
+}
+
+impl KethStore for Database {
+    fn insert_proof(&self, metadata: &ProofMetadata, proof: &[u8]) -> eyre::Result<()> {
+        Self::insert_proof(self, metadata, proof)
+    }
+
+    fn proof(&self, number: u64) -> eyre::Result<Option<(ProofMetadata, Vec<u8>)>> {
+        Self::proof(self, number)
+    }
+
+    fn delete_proof(&self, number: u64) -> eyre::Result<()> {
+        Self::delete_proof(self, number)
+    }
+
+    fn proven_blocks(&self, from_block: u64, to_block: u64) -> eyre::Result<Vec<u64>> {
+        Self::proven_blocks(self, from_block, to_block)
+    }
+
+    fn insert_program_output(&self, output: &ProgramOutput) -> eyre::Result<()> {
+        Self::insert_program_output(self, output)
+    }
+
+    fn program_output(&self, number: u64) -> eyre::Result<Option<ProgramOutput>> {
+        Self::program_output(self, number)
+    }
+
+    fn insert_block_program(&self, program: &BlockProgram) -> eyre::Result<()> {
+        Self::insert_block_program(self, program)
+    }
+
+    fn block_program(&self, number: u64) -> eyre::Result<Option<BlockProgram>> {
+        Self::block_program(self, number)
+    }
+
+    fn insert_chain_link(&self, link: &ChainLink) -> eyre::Result<()> {
+        Self::insert_chain_link(self, link)
+    }
+
+    fn chain_link(&self, number: u64) -> eyre::Result<Option<ChainLink>> {
+        Self::chain_link(self, number)
+    }
+
+    fn chain_head(&self) -> eyre::Result<Option<ChainLink>> {
+        Self::chain_head(self)
+    }
+}
+
+/// A [`KethStore`] shared across the stages of an instance, e.g. by the ExEx and the gRPC service.
+pub type SharedStore = Arc<dyn KethStore>;
+
+/// Opens the store of the proofs: the [`RedbStore`] at `path` if given, the SQLite [`Database`]
+/// otherwise.
+pub fn open_store(db: &Database, path: Option<&Path>) -> eyre::Result<SharedStore> {
+    Ok(match path {
+        Some(path) => Arc::new(RedbStore::open(path)?),
+        None => Arc::new(db.clone()),
+    })
+}
+
+/// A table of JSON records keyed by block number.
+type JsonTable = TableDefinition<'static, u64, &'static str>;
+
+/// The metadata of the proofs.
+const PROOF_METADATA: JsonTable = TableDefinition::new("proof_metadata");
+
+/// The proofs.
+const PROOF: TableDefinition<'static, u64, &'static [u8]> = TableDefinition::new("proof");
+
+/// The outputs of the Kakarot program.
+const PROGRAM_OUTPUT: JsonTable = TableDefinition::new("program_output");
+
+/// The programs selected for the blocks.
+const BLOCK_PROGRAM: JsonTable = TableDefinition::new("block_program");
+
+/// The links of the chain of the proven blocks.
+const PROOF_CHAIN: JsonTable = TableDefinition::new("proof_chain");
+
+/// A [`KethStore`] backed by an embedded redb database.
+///
+/// The store is shared across instances using `Arc`, redb serializing the write transactions.
+#[derive(Clone)]
+pub struct RedbStore {
+    /// The redb database.
+    db: Arc<redb::Database>,
+}
+
+impl fmt::Debug for RedbStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedbStore").finish_non_exhaustive()
+    }
+}
+
+impl RedbStore {
+    /// Creates a new `RedbStore` from a redb database, creating the tables if needed.
+    pub fn new(db: redb::Database) -> eyre::Result<Self> {
+        let transaction = db.begin_write()?;
+        transaction.open_table(PROOF)?;
+        for table in [PROOF_METADATA, PROGRAM_OUTPUT, BLOCK_PROGRAM, PROOF_CHAIN] {
+            transaction.open_table(table)?;
+        }
+        transaction.commit()?;
+        Ok(Self { db: Arc::new(db) })
+    }
+
+    /// Opens the redb database at the given path, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        Self::new(redb::Database::create(path)?)
+    }
+
+    /// Creates a store held in memory, dropped with its last instance.
+    pub fn in_memory() -> eyre::Result<Self> {
+        Self::new(redb::Database::builder().create_with_backend(InMemoryBackend::new())?)
+    }
+
+    /// Inserts the JSON record of a block in a table, replacing a previous one.
+    fn insert(&self, table: JsonTable, number: u64, record: &impl Serialize) -> eyre::Result<()> {
+        let data = serde_json::to_string(record)?;
+        let transaction = self.db.begin_write()?;
+        transaction.open_table(table)?.insert(number, data.as_str())?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Retrieves the JSON record of a block from a table, if any.
+    fn get<T: DeserializeOwned>(&self, table: JsonTable, number: u64) -> eyre::Result<Option<T>> {
+        let transaction = self.db.begin_read()?;
+        let table = transaction.open_table(table)?;
+        table.get(number)?.map(|data| Ok(serde_json::from_str(data.value())?)).transpose()
+    }
+}
+
+impl KethStore for RedbStore {
+    fn insert_proof(&self, metadata: &ProofMetadata, proof: &[u8]) -> eyre::Result<()> {
+        let data = serde_json::to_string(metadata)?;
+        let transaction = self.db.begin_write()?;
+        transaction.open_table(PROOF_METADATA)?.insert(metadata.block_number, data.as_str())?;
+        transaction.open_table(PROOF)?.insert(metadata.block_number, proof)?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn proof(&self, number: u64) -> eyre::Result<Option<(ProofMetadata, Vec<u8>)>> {
+        let transaction = self.db.begin_read()?;
+        let metadata = transaction.open_table(PROOF_METADATA)?;
+        let proof = transaction.open_table(PROOF)?;
+
+        match (metadata.get(number)?, proof.get(number)?) {
+            (Some(metadata), Some(proof)) => {
+                Ok(Some((serde_json::from_str(metadata.value())?, proof.value().to_vec())))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn delete_proof(&self, number: u64) -> eyre::Result<()> {
+        let transaction = self.db.begin_write()?;
+        transaction.open_table(PROOF_METADATA)?.remove(number)?;
+        transaction.open_table(PROOF)?.remove(number)?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn proven_blocks(&self, from_block: u64, to_block: u64) -> eyre::Result<Vec<u64>> {
+        if from_block > to_block {
+            return Ok(Vec::new());
+        }
+        let transaction = self.db.begin_read()?;
+        let table = transaction.open_table(PROOF_METADATA)?;
+        let rows = table.range(from_block..=to_block)?;
+
+        rows.map(|row| Ok(row?.0.value())).collect()
+    }
+
+    fn insert_program_output(&self, output: &ProgramOutput) -> eyre::Result<()> {
+        self.insert(PROGRAM_OUTPUT, output.block_number, output)
+    }
+
+    fn program_output(&self, number: u64) -> eyre::Result<Option<ProgramOutput>> {
+        self.get(PROGRAM_OUTPUT, number)
+    }
+
+    fn insert_block_program(&self, program: &BlockProgram) -> eyre::Result<()> {
+        self.insert(BLOCK_PROGRAM, program.block_number, program)
+    }
+
+    fn block_program(&self, number: u64) -> eyre::Result<Option<BlockProgram>> {
+        self.get(BLOCK_PROGRAM, number)
+    }
+
+    fn insert_chain_link(&self, link: &ChainLink) -> eyre::Result<()> {
+        self.insert(PROOF_CHAIN, link.block_number, link)
+    }
+
+    fn chain_link(&self, number: u64) -> eyre::Result<Option<ChainLink>> {
+        self.get(PROOF_CHAIN, number)
+    }
+
+    fn chain_head(&self) -> eyre::Result<Option<ChainLink>> {
+        let transaction = self.db.begin_read()?;
+        let table = transaction.open_table(PROOF_CHAIN)?;
+        table.last()?.map(|(_, data)| Ok(serde_json::from_str(data.value())?)).transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chain, ssz::Ssz};
+    use alloy_primitives::B256;
+    use rusqlite::Connection;
+    use std::path::PathBuf;
+
+    /// Checks the round trip of the artifacts of a store.
+    fn check_round_trip(store: &impl KethStore) {
+        for number in [3, 1, 2] {
+            let metadata = ProofMetadata { block_number: number, ..Default::default() };
+            store.insert_proof(&metadata, &[number as u8]).unwrap();
+        }
+        assert_eq!(store.proven_blocks(2, 10).unwrap(), vec![2, 3]);
+        assert_eq!(store.proven_blocks(10, 2).unwrap(), Vec::<u64>::new());
+        let (metadata, proof) = store.proof(3).unwrap().unwrap();
+        assert_eq!((metadata.block_number, proof), (3, vec![3]));
+        assert_eq!(store.proof(4).unwrap(), None);
+        store.delete_proof(3).unwrap();
+        assert_eq!(store.proof(3).unwrap(), None);
+        assert_eq!(store.proven_blocks(2, 10).unwrap(), vec![2]);
+
+        let output = ProgramOutput { block_number: 1, ..Default::default() };
+        store.insert_program_output(&output).unwrap();
+        assert_eq!(store.program_output(1).unwrap(), Some(output));
+        assert_eq!(store.program_output(2).unwrap(), None);
+
+        let program = BlockProgram {
+            block_number: 1,
+            fork: Some("cancun".to_string()),
+            program: PathBuf::from("os.json"),
+            program_hash: B256::with_last_byte(1),
+        };
+        store.insert_block_program(&program).unwrap();
+        assert_eq!(store.block_program(1).unwrap(), Some(program));
+
+        assert_eq!(store.chain_head().unwrap(), None);
+        let anchor = ChainLink::anchor(&ProgramOutput { block_number: 9, ..Default::default() });
+        let link = ChainLink { block_number: 10, ..anchor };
+        store.insert_chain_link(&link).unwrap();
+        store.insert_chain_link(&anchor).unwrap();
+        assert_eq!(store.chain_link(9).unwrap(), Some(anchor));
+        assert_eq!(store.chain_head().unwrap(), Some(link));
+    }
+
+    #[test]
+    fn test_sqlite_store() {
+        check_round_trip(&Database::new(Connection::open_in_memory().unwrap()).unwrap());
+    }
+
+    #[test]
+    fn test_redb_store() {
+        check_round_trip(&RedbStore::in_memory().unwrap());
+    }
+
+    #[test]
+    fn test_reopen_redb_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keth.redb");
+        let output = ProgramOutput { block_number: 1, ..Default::default() };
+        RedbStore::open(&path).unwrap().insert_program_output(&output).unwrap();
+
+        assert_eq!(RedbStore::open(&path).unwrap().program_output(1).unwrap(), Some(output));
+    }
+
+    #[test]
+    fn test_extend_chain_redb_store() {
+        let store = RedbStore::in_memory().unwrap();
+        let output = ProgramOutput { block_number: 1, ..Default::default() };
+        let metadata = ProofMetadata {
+            block_number: 1,
+            output_root: output.hash_tree_root(),
+            ..Default::default()
+        };
+        store.insert_program_output(&output).unwrap();
+        store.insert_proof(&metadata, b"proof").unwrap();
+
+        let links = chain::extend(&store).unwrap().links;
+        assert_eq!(links, vec![ChainLink::anchor(&output)]);
+        assert_eq!(store.chain_head().unwrap(), Some(links[0]));
+    }
+}
@@ -0,0 +1,717 @@
+//! Geth-compatible `structLogger` traces generated from the Cairo execution of a block.
+//!
+//! Each EVM opcode is executed by an invocation of [`EXEC_OPCODE_FUNCTION`], taking the
+//! `model.EVM*` of the current frame as argument, and the `model.Stack*` and `model.Memory*` of the
+//! frame as implicit arguments. The steps of the transactions are decoded from these invocations:
+//! the stack is a dict from the positions of its items to their `Uint256*`, and the memory a dict
+//! from the indices of its 16-byte words to their value.
+//!
+//! The steps are converted to the struct logs of geth, following the `disableMemory`,
+//! `disableStack`, `disableStorage` and `enableReturnData` options of its `structLogger`, so that
+//! the debugging tools consuming geth traces consume the traces of keth unchanged.
+
+use crate::{
+    attribution::{felt_to_usize, invocations, AttributionError, FrameSpec, EXECUTE_FUNCTION},
+    db::Database,
+    halt::EvmHalt,
+    model::U128_BYTES_SIZE,
+    refund::{extract_refunds, RefundSpec, MAX_REFUND_QUOTIENT},
+};
+use alloy_primitives::{hex, Address, Bytes, B256, U256};
+use cairo_vm::{types::program::Program, vm::trace::trace_entry::RelocatedTraceEntry, Felt252};
+use reth_revm::interpreter::OpCode;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+use thiserror::Error;
+
+/// The full name of the Cairo function executing a single EVM opcode.
+pub const EXEC_OPCODE_FUNCTION: &str = "src.interpreter.Interpreter.exec_opcode";
+
+/// The number of bytes of a word of the `model.Memory` dict.
+pub const MEMORY_WORD_BYTES: usize = 16;
+
+/// The full name of the `model.EVM` struct.
+const EVM_STRUCT: &str = "src.model.model.EVM";
+
+/// The full name of the `model.Message` struct.
+const MESSAGE_STRUCT: &str = "src.model.model.Message";
+
+/// The full name of the `model.Parent` struct.
+const PARENT_STRUCT: &str = "src.model.model.Parent";
+
+/// The full name of the `model.Stack` struct.
+const STACK_STRUCT: &str = "src.model.model.Stack";
+
+/// The full name of the `model.Memory` struct.
+const MEMORY_STRUCT: &str = "src.model.model.Memory";
+
+/// The number of felts of a `DictAccess`: its key, previous value and new value.
+const DICT_ACCESS_SIZE: usize = 3;
+
+/// The value of `EVM.reverted` for an exceptional halt.
+const EXCEPTIONAL_HALT: usize = 2;
+
+/// The `SLOAD` opcode.
+const SLOAD: u8 = 0x54;
+
+/// The `SSTORE` opcode.
+const SSTORE: u8 = 0x55;
+
+/// The `RETURN` opcode.
+pub(crate) const RETURN: u8 = 0xf3;
+
+/// The `REVERT` opcode.
+pub(crate) const REVERT: u8 = 0xfd;
+
+/// Represents errors that can occur when decoding the steps of a Cairo execution.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum StructLogError {
+    /// Error variant indicating that the frame of a function cannot be built.
+    #[error(transparent)]
+    Attribution(#[from] AttributionError),
+
+    /// Error variant indicating that a struct member is missing from the program.
+    #[error("Member '{member}' not found in struct '{struct_name}'")]
+    MissingMember {
+        /// The name of the struct.
+        struct_name: String,
+        /// The name of the member.
+        member: String,
+    },
+
+    /// Error variant indicating that a memory cell does not hold the expected value.
+    #[error("Invalid value at relocated address {0}")]
+    InvalidValue(usize),
+}
+
+/// The offsets of the members of `model.EVM` read by the steps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EvmLayout {
+    /// The offset of the `message` member.
+    pub message: usize,
+    /// The offsets of the `return_data_len` and `return_data` members.
+    pub return_data: (usize, usize),
+    /// The offset of the `program_counter` member.
+    pub program_counter: usize,
+    /// The offset of the `gas_left` member.
+    pub gas_left: usize,
+    /// The offset of the `gas_refund` member.
+    pub gas_refund: usize,
+    /// The offset of the `reverted` member.
+    pub reverted: usize,
+}
+
+/// The offsets of the members of `model.Message` read by the steps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MessageLayout {
+    /// The offsets of the `bytecode_len` and `bytecode` members.
+    pub bytecode: (usize, usize),
+    /// The offset of the `address` member.
+    pub address: usize,
+    /// The offset of the `depth` member.
+    pub depth: usize,
+    /// The offset of the `parent` member.
+    pub parent: usize,
+}
+
+/// The memory layout of the invocations of [`EXEC_OPCODE_FUNCTION`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepLayout {
+    /// The frame of the function.
+    pub frame: FrameSpec,
+    /// The offsets of the `stack` and `memory` implicit arguments.
+    pub implicit_args: (usize, usize),
+    /// The offset of the `evm` argument among the explicit arguments.
+    pub evm_arg: usize,
+    /// The layout of `model.EVM`.
+    pub evm: EvmLayout,
+    /// The layout of `model.Message`.
+    pub message: MessageLayout,
+    /// The offset of the `evm` member of `model.Parent`.
+    pub parent_evm: usize,
+    /// The offsets of the `dict_ptr_start`, `dict_ptr` and `size` members of `model.Stack`.
+    pub stack: (usize, usize, usize),
+    /// The offsets of the `word_dict_start`, `word_dict` and `words_len` members of
+    /// `model.Memory`.
+    pub memory: (usize, usize, usize),
+}
+
+impl StepLayout {
+    /// Builds the layout of the steps from the identifiers of the program.
+    pub fn from_program(program: &Program) -> Result<Self, StructLogError> {
+        let offset = |struct_name: &str, member: &str| {
+            program
+                .get_identifier(struct_name)
+                .and_then(|identifier| identifier.members.as_ref()?.get(member))
+                .map(|member| member.offset)
+                .ok_or_else(|| StructLogError::MissingMember {
+                    struct_name: struct_name.to_string(),
+                    member: member.to_string(),
+                })
+        };
+        let implicit_args = format!("{EXEC_OPCODE_FUNCTION}.ImplicitArgs");
+
+        Ok(Self {
+            frame: FrameSpec::from_program(program, EXEC_OPCODE_FUNCTION)?,
+            implicit_args: (offset(&implicit_args, "stack")?, offset(&implicit_args, "memory")?),
+            evm_arg: offset(&format!("{EXEC_OPCODE_FUNCTION}.Args"), "evm")?,
+            evm: EvmLayout {
+                message: offset(EVM_STRUCT, "message")?,
+                return_data: (
+                    offset(EVM_STRUCT, "return_data_len")?,
+                    offset(EVM_STRUCT, "return_data")?,
+                ),
+                program_counter: offset(EVM_STRUCT, "program_counter")?,
+                gas_left: offset(EVM_STRUCT, "gas_left")?,
+                gas_refund: offset(EVM_STRUCT, "gas_refund")?,
+                reverted: offset(EVM_STRUCT, "reverted")?,
+            },
+            message: MessageLayout {
+                bytecode: (
+                    offset(MESSAGE_STRUCT, "bytecode_len")?,
+                    offset(MESSAGE_STRUCT, "bytecode")?,
+                ),
+                address: offset(MESSAGE_STRUCT, "address")?,
+                depth: offset(MESSAGE_STRUCT, "depth")?,
+                parent: offset(MESSAGE_STRUCT, "parent")?,
+            },
+            parent_evm: offset(PARENT_STRUCT, "evm")?,
+            stack: (
+                offset(STACK_STRUCT, "dict_ptr_start")?,
+                offset(STACK_STRUCT, "dict_ptr")?,
+                offset(STACK_STRUCT, "size")?,
+            ),
+            memory: (
+                offset(MEMORY_STRUCT, "word_dict_start")?,
+                offset(MEMORY_STRUCT, "word_dict")?,
+                offset(MEMORY_STRUCT, "words_len")?,
+            ),
+        })
+    }
+}
+
+/// An EVM opcode executed by the Cairo execution, with the state of its frame before its
+/// execution.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Step {
+    /// The index of the transaction in the block.
+    pub tx_index: u32,
+    /// The program counter.
+    pub pc: u64,
+    /// The opcode.
+    pub op: u8,
+    /// The gas left.
+    pub gas: u64,
+    /// The gas charged by the opcode, including the gas forwarded to a created frame.
+    pub gas_cost: u64,
+    /// The depth of the frame, starting at 1.
+    pub depth: u64,
+    /// The refund counter.
+    pub refund: u64,
+    /// The address of the executing contract.
+    pub address: Address,
+    /// The stack, from its bottom to its top.
+    pub stack: Vec<U256>,
+    /// The memory.
+    pub memory: Bytes,
+    /// The return data of the last call of the frame.
+    pub return_data: Bytes,
+    /// The exceptional halt raised by the opcode.
+    pub error: Option<EvmHalt>,
+}
+
+impl Step {
+    /// Returns the item of the stack at a position from its top, zero past its bottom.
+    pub fn peek(&self, position: usize) -> U256 {
+        self.stack.iter().rev().nth(position).copied().unwrap_or_default()
+    }
+
+    /// Returns the slice of the memory at an offset, zero-padded past its end.
+    pub fn memory_slice(&self, offset: U256, size: U256) -> Bytes {
+        let offset = offset.saturating_to::<usize>();
+        let mut slice = vec![0; size.saturating_to::<usize>()];
+        if let Some(available) = self.memory.get(offset..) {
+            let length = available.len().min(slice.len());
+            slice[..length].copy_from_slice(&available[..length]);
+        }
+        slice.into()
+    }
+
+    /// Returns the output of a frame ending with the step, empty unless the step is a `RETURN` or
+    /// `REVERT` which did not halt.
+    pub fn output(&self) -> Bytes {
+        if self.error.is_some() || !matches!(self.op, RETURN | REVERT) {
+            return Bytes::new();
+        }
+        self.memory_slice(self.peek(0), self.peek(1))
+    }
+}
+
+/// Decodes the steps of the transactions executed in a relocated trace, in order.
+///
+/// The steps outside of the invocations of the transaction function are skipped, and the gas cost
+/// of a step which never returns, i.e. at the end of an interrupted trace, is zero.
+pub fn decode_steps(
+    execute: &FrameSpec,
+    layout: &StepLayout,
+    trace: &[RelocatedTraceEntry],
+    memory: &[Felt252],
+) -> Result<Vec<Step>, StructLogError> {
+    let reader = Reader { memory };
+    let transactions = invocations(execute, trace);
+    let frame = &layout.frame;
+
+    let mut steps = Vec::new();
+    for (start, end) in invocations(frame, trace) {
+        let Some(tx_index) =
+            transactions.iter().position(|(first, last)| (*first..*last).contains(&start))
+        else {
+            continue;
+        };
+
+        let args_start = trace[start]
+            .fp
+            .checked_sub(2 + frame.implicit_args_size + frame.args_size)
+            .ok_or(AttributionError::InvalidFrame(start))?;
+        let evm = reader.read(args_start + frame.implicit_args_size + layout.evm_arg)?;
+        let stack = reader.read(args_start + layout.implicit_args.0)?;
+        let evm_memory = reader.read(args_start + layout.implicit_args.1)?;
+        let state = reader.frame(layout, evm)?;
+
+        let (gas_cost, error) = match trace.get(end) {
+            Some(exit) => {
+                let returns_start = exit
+                    .ap
+                    .checked_sub(frame.return_size)
+                    .ok_or(AttributionError::InvalidFrame(end))?;
+                let returned = reader.read(returns_start)?;
+                let next = reader.frame(layout, returned)?;
+                if next.depth > state.depth {
+                    // The opcode created a frame, the gas forwarded to it is charged to the parent.
+                    let parent = reader.read(next.message + layout.message.parent)?;
+                    let parent_evm = reader.read(parent + layout.parent_evm)?;
+                    let gas_left = reader.read(parent_evm + layout.evm.gas_left)? as u64;
+                    (state.gas.saturating_sub(gas_left), None)
+                } else {
+                    let halted = reader.read(returned + layout.evm.reverted)? == EXCEPTIONAL_HALT;
+                    let error = halted.then(|| {
+                        let output = reader.bytes(returned, layout.evm.return_data).ok();
+                        output.map_or(EvmHalt::Other, |output| EvmHalt::from_kakarot_error(&output))
+                    });
+                    (state.gas.saturating_sub(next.gas), error)
+                }
+            }
+            None => (0, None),
+        };
+
+        steps.push(Step {
+            tx_index: tx_index as u32,
+            pc: state.pc as u64,
+            op: state.op,
+            gas: state.gas,
+            gas_cost,
+            depth: state.depth + 1,
+            refund: reader.read(evm + layout.evm.gas_refund)? as u64,
+            address: state.address,
+            stack: reader.stack(stack, layout.stack)?,
+            memory: reader.memory(evm_memory, layout.memory)?,
+            return_data: reader.bytes(evm, layout.evm.return_data)?,
+            error,
+        });
+    }
+    Ok(steps)
+}
+
+/// The options of the geth `structLogger`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct StructLoggerConfig {
+    /// Whether the memory is omitted from the logs.
+    pub disable_memory: bool,
+    /// Whether the stack is omitted from the logs.
+    pub disable_stack: bool,
+    /// Whether the storage is omitted from the logs.
+    pub disable_storage: bool,
+    /// Whether the return data is included in the logs.
+    pub enable_return_data: bool,
+}
+
+/// A struct log, as formatted by geth.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructLog {
+    /// The program counter.
+    pub pc: u64,
+    /// The mnemonic of the opcode.
+    pub op: String,
+    /// The gas left before the opcode.
+    pub gas: u64,
+    /// The gas charged by the opcode.
+    pub gas_cost: u64,
+    /// The depth of the frame, starting at 1.
+    pub depth: u64,
+    /// The error raised by the opcode.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The stack, from its bottom to its top, as hex quantities.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stack: Option<Vec<String>>,
+    /// The return data of the last call of the frame.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub return_data: Option<Bytes>,
+    /// The memory, as 32-byte hex words.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<Vec<String>>,
+    /// The storage slots accessed by the contract so far, on `SLOAD` and `SSTORE` only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage: Option<BTreeMap<String, String>>,
+    /// The refund counter.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub refund: u64,
+}
+
+/// Converts the steps of a transaction into struct logs.
+///
+/// As geth, the storage of a contract is tracked from its `SLOAD` and `SSTORE` steps, the value of
+/// an `SLOAD` being the top of the stack of the following step.
+pub fn struct_logs(steps: &[Step], config: &StructLoggerConfig) -> Vec<StructLog> {
+    let mut storage: HashMap<Address, BTreeMap<U256, U256>> = HashMap::new();
+
+    steps
+        .iter()
+        .enumerate()
+        .map(|(index, step)| {
+            let mut log_storage = None;
+            if !config.disable_storage && matches!(step.op, SLOAD | SSTORE) {
+                let slots = storage.entry(step.address).or_default();
+                let mut stack = step.stack.iter().rev();
+                match (step.op, stack.next(), stack.next()) {
+                    (SSTORE, Some(key), Some(value)) => {
+                        slots.insert(*key, *value);
+                    }
+                    (SLOAD, Some(key), _) => {
+                        let next = steps.get(index + 1).filter(|next| next.depth == step.depth);
+                        if let Some(value) = next.and_then(|next| next.stack.last()) {
+                            slots.insert(*key, *value);
+                        }
+                    }
+                    _ => {}
+                }
+                log_storage = Some(
+                    slots
+                        .iter()
+                        .map(|(key, value)| {
+                            (hex::encode(B256::from(*key)), hex::encode(B256::from(*value)))
+                        })
+                        .collect(),
+                );
+            }
+
+            StructLog {
+                pc: step.pc,
+                op: opcode_name(step.op),
+                gas: step.gas,
+                gas_cost: step.gas_cost,
+                depth: step.depth,
+                error: step.error.map(|error| error.message().to_string()),
+                stack: (!config.disable_stack)
+                    .then(|| step.stack.iter().map(|value| format!("{value:#x}")).collect()),
+                return_data: config.enable_return_data.then(|| step.return_data.clone()),
+                memory: (!config.disable_memory)
+                    .then(|| step.memory.chunks(32).map(hex::encode).collect()),
+                storage: log_storage,
+                refund: step.refund,
+            }
+        })
+        .collect()
+}
+
+/// The trace of a transaction, as returned by geth's `debug_traceTransaction` with the default
+/// tracer.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionTrace {
+    /// The gas used by the transaction.
+    pub gas: u64,
+    /// Whether the transaction failed.
+    pub failed: bool,
+    /// The output of the transaction, as hex without prefix.
+    pub return_value: String,
+    /// The struct logs of the transaction.
+    pub struct_logs: Vec<StructLog>,
+}
+
+impl ExecutionTrace {
+    /// Builds the trace of a transaction from its gas used and its steps.
+    ///
+    /// The transaction failed if its last top-level step reverted or halted, and its output is
+    /// read from the memory of its last top-level `RETURN` or `REVERT` step.
+    pub fn new(gas: u64, steps: &[Step], config: &StructLoggerConfig) -> Self {
+        let last = steps.iter().rev().find(|step| step.depth == 1);
+        let failed = last.is_some_and(|step| step.op == REVERT || step.error.is_some());
+        let output = last.map(Step::output).unwrap_or_default();
+
+        Self {
+            gas,
+            failed,
+            return_value: hex::encode(output),
+            struct_logs: struct_logs(steps, config),
+        }
+    }
+
+    /// Returns the JSON value of the trace, as returned by geth.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).expect("execution trace is always serializable")
+    }
+}
+
+/// Traces a transaction of a block from its stored Cairo execution, with the program which executed
+/// it.
+pub fn trace_transaction(
+    db: &Database,
+    program: &Path,
+    block_number: u64,
+    tx_index: u32,
+    config: &StructLoggerConfig,
+) -> eyre::Result<ExecutionTrace> {
+    let (gas, steps) = transaction_steps(db, program, block_number, tx_index)?;
+    Ok(ExecutionTrace::new(gas, &steps, config))
+}
+
+/// Loads the gas used and the steps of a transaction of a block from its stored Cairo execution,
+/// with the program which executed it.
+pub fn transaction_steps(
+    db: &Database,
+    program: &Path,
+    block_number: u64,
+    tx_index: u32,
+) -> eyre::Result<(u64, Vec<Step>)> {
+    let program = Program::from_file(program, Some("main"))?;
+    let execute = FrameSpec::execute(&program)?;
+    let (trace, memory) = db
+        .execution_trace(block_number)?
+        .ok_or_else(|| eyre::eyre!("No trace found for block {block_number}"))?;
+
+    let layout = StepLayout::from_program(&program)?;
+    let steps = decode_steps(&execute, &layout, &trace, &memory)?
+        .into_iter()
+        .filter(|step| step.tx_index == tx_index)
+        .collect();
+
+    let refunds =
+        extract_refunds(&RefundSpec::from_program(&program, EXECUTE_FUNCTION)?, &trace, &memory)?;
+    let gas = refunds
+        .iter()
+        .find(|counters| counters.tx_index == tx_index)
+        .map(|counters| counters.gas_used(MAX_REFUND_QUOTIENT))
+        .unwrap_or_default();
+
+    Ok((gas, steps))
+}
+
+/// Returns the mnemonic of an opcode, as formatted by geth.
+pub fn opcode_name(op: u8) -> String {
+    OpCode::new(op)
+        .map_or_else(|| format!("opcode {op:#x} not defined"), |op| op.as_str().to_string())
+}
+
+/// The state of an EVM frame read at the start of a step.
+struct FrameState {
+    /// The relocated address of the `model.Message` of the frame.
+    message: usize,
+    /// The program counter.
+    pc: usize,
+    /// The opcode at the program counter, `STOP` past the end of the bytecode.
+    op: u8,
+    /// The gas left.
+    gas: u64,
+    /// The depth of the frame, starting at 0.
+    depth: u64,
+    /// The address of the executing contract.
+    address: Address,
+}
+
+/// A reader of the values of the relocated memory.
+struct Reader<'a> {
+    /// The relocated memory.
+    memory: &'a [Felt252],
+}
+
+impl Reader<'_> {
+    /// Reads a felt.
+    fn felt(&self, address: usize) -> Result<&Felt252, StructLogError> {
+        self.memory.get(address).ok_or(StructLogError::InvalidValue(address))
+    }
+
+    /// Reads a felt holding a relocated address, a length or a small integer.
+    fn read(&self, address: usize) -> Result<usize, StructLogError> {
+        felt_to_usize(self.felt(address)?).ok_or(StructLogError::InvalidValue(address))
+    }
+
+    /// Reads the frame of a `model.EVM`.
+    fn frame(&self, layout: &StepLayout, evm: usize) -> Result<FrameState, StructLogError> {
+        let message = self.read(evm + layout.evm.message)?;
+        let pc = self.read(evm + layout.evm.program_counter)?;
+        let bytecode_len = self.read(message + layout.message.bytecode.0)?;
+        let bytecode = self.read(message + layout.message.bytecode.1)?;
+        let op = if pc < bytecode_len {
+            let address = bytecode + pc;
+            u8::try_from(self.read(address)?).map_err(|_| StructLogError::InvalidValue(address))?
+        } else {
+            0
+        };
+        let address = self.felt(message + layout.message.address)?.to_bytes_be();
+
+        Ok(FrameState {
+            message,
+            pc,
+            op,
+            gas: self.read(evm + layout.evm.gas_left)? as u64,
+            depth: self.read(message + layout.message.depth)? as u64,
+            address: Address::from_slice(&address[12..]),
+        })
+    }
+
+    /// Reads the bytes of a `(len, ptr)` pair of members of a struct, one byte per felt.
+    fn bytes(&self, base: usize, (len, ptr): (usize, usize)) -> Result<Bytes, StructLogError> {
+        let len = self.read(base + len)?;
+        let ptr = self.read(base + ptr)?;
+        (ptr..ptr + len)
+            .map(|address| {
+                u8::try_from(self.read(address)?).map_err(|_| StructLogError::InvalidValue(address))
+            })
+            .collect()
+    }
+
+    /// Reads the final values of a dict, from the relocated addresses of its first and last access.
+    fn dict(&self, start: usize, end: usize) -> Result<BTreeMap<usize, &Felt252>, StructLogError> {
+        let mut values = BTreeMap::new();
+        for access in (start..end).step_by(DICT_ACCESS_SIZE) {
+            values.insert(self.read(access)?, self.felt(access + 2)?);
+        }
+        Ok(values)
+    }
+
+    /// Reads the items of a `model.Stack`, from its bottom to its top.
+    fn stack(
+        &self,
+        stack: usize,
+        (start, end, size): (usize, usize, usize),
+    ) -> Result<Vec<U256>, StructLogError> {
+        let values = self.dict(self.read(stack + start)?, self.read(stack + end)?)?;
+        (0..self.read(stack + size)?)
+            .map(|position| {
+                let pointer = values
+                    .get(&position)
+                    .and_then(|value| felt_to_usize(value))
+                    .ok_or(StructLogError::InvalidValue(stack + end))?;
+                let low = self.felt(pointer)?.to_bytes_be();
+                let high = self.felt(pointer + 1)?.to_bytes_be();
+                let mut value = [0; 32];
+                value[..U128_BYTES_SIZE].copy_from_slice(&high[U128_BYTES_SIZE..]);
+                value[U128_BYTES_SIZE..].copy_from_slice(&low[U128_BYTES_SIZE..]);
+                Ok(U256::from_be_bytes(value))
+            })
+            .collect()
+    }
+
+    /// Reads the content of a `model.Memory`, its missing words being zero.
+    fn memory(
+        &self,
+        memory: usize,
+        (start, end, words_len): (usize, usize, usize),
+    ) -> Result<Bytes, StructLogError> {
+        let words = self.dict(self.read(memory + start)?, self.read(memory + end)?)?;
+        let mut content = vec![0; self.read(memory + words_len)? * 32];
+        for (index, chunk) in content.chunks_mut(MEMORY_WORD_BYTES).enumerate() {
+            if let Some(word) = words.get(&index) {
+                chunk.copy_from_slice(&word.to_bytes_be()[32 - MEMORY_WORD_BYTES..]);
+            }
+        }
+        Ok(content.into())
+    }
+}
+
+/// Returns whether a value is zero, omitting the zero refunds from the struct logs.
+const fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(op: u8, depth: u64, stack: &[u64]) -> Step {
+        Step {
+            op,
+            depth,
+            gas: 100,
+            gas_cost: 3,
+            stack: stack.iter().map(|value| U256::from(*value)).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_struct_logs_storage() {
+        let steps = [step(SSTORE, 1, &[42, 1]), step(SLOAD, 1, &[2]), step(0x50, 1, &[7])];
+
+        let logs = struct_logs(&steps, &StructLoggerConfig::default());
+        let slot = |value: u8| hex::encode(B256::with_last_byte(value));
+
+        assert_eq!(logs[0].op, "SSTORE");
+        assert_eq!(logs[0].stack, Some(vec!["0x2a".to_string(), "0x1".to_string()]));
+        assert_eq!(logs[0].storage, Some(BTreeMap::from([(slot(1), slot(42))])));
+        // The value of an SLOAD is the top of the stack of the next step.
+        assert_eq!(
+            logs[1].storage,
+            Some(BTreeMap::from([(slot(1), slot(42)), (slot(2), slot(7))]))
+        );
+        assert_eq!(logs[2].storage, None);
+
+        let config = StructLoggerConfig {
+            disable_stack: true,
+            disable_storage: true,
+            disable_memory: true,
+            enable_return_data: true,
+        };
+        let logs = struct_logs(&steps, &config);
+        assert_eq!((logs[0].stack.as_ref(), logs[0].storage.as_ref()), (None, None));
+        assert_eq!(logs[0].memory, None);
+        assert_eq!(logs[0].return_data, Some(Bytes::new()));
+    }
+
+    #[test]
+    fn test_execution_trace_json() {
+        let mut ret = step(RETURN, 1, &[2, 0]);
+        ret.memory = Bytes::from(vec![0xab; 32]);
+        let steps = [step(0x60, 1, &[]), ret];
+
+        let trace = ExecutionTrace::new(21_000, &steps, &StructLoggerConfig::default());
+        assert!(!trace.failed);
+        assert_eq!(trace.return_value, "abab");
+
+        let json = trace.to_json();
+        assert_eq!(json["gas"], 21_000);
+        assert_eq!(json["structLogs"][0]["op"], "PUSH1");
+        assert_eq!(json["structLogs"][0]["gasCost"], 3);
+        assert_eq!(json["structLogs"][1]["memory"][0], "ab".repeat(32));
+        assert!(json["structLogs"][0].get("refund").is_none());
+        assert!(json["structLogs"][0].get("error").is_none());
+
+        let mut halted = step(0x01, 1, &[]);
+        halted.error = Some(EvmHalt::StackUnderflow);
+        let trace = ExecutionTrace::new(21_000, &[halted], &StructLoggerConfig::default());
+        assert!(trace.failed);
+        assert_eq!(trace.struct_logs[0].error.as_deref(), Some("stack underflow"));
+    }
+
+    #[test]
+    fn test_opcode_name() {
+        assert_eq!(opcode_name(0x01), "ADD");
+        assert_eq!(opcode_name(0x0c), "opcode 0xc not defined");
+    }
+}
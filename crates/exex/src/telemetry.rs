@@ -0,0 +1,156 @@
+//! Tracing spans of the execution and proving pipeline of the blocks.
+//!
+//! The journey of a block goes through the [`Stage`]s of the pipeline: its witness is built, the
+//! Kakarot program is run, its traces are serialized to the database, its Cairo PIE is produced
+//! for the prover and submitted. Each stage runs in a span of the [`TARGET`] target carrying the
+//! number of the block, nested in the span of the block when processed by an instance, so that a
+//! single block can be followed in the logs or in a trace viewer. The transactions of the witness
+//! are traced in spans carrying their hash, and the submissions in spans carrying their job id.
+//!
+//! The blocks are sampled by their number with a [`TraceSampling`]: the spans of the blocks out of
+//! the sample are disabled, their events being still logged. The sampling is process-wide, set
+//! with [`set_sampling`] along with the subscriber, and traces all the blocks by default.
+
+use alloy_primitives::B256;
+use reth_tracing::tracing::{debug_span, field, info_span, Span};
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// The target of the spans of the pipeline.
+pub const TARGET: &str = "kkrt::pipeline";
+
+/// The process-wide sampling of the traced blocks, as the number of blocks per traced block.
+static SAMPLING: AtomicU64 = AtomicU64::new(1);
+
+/// A stage of the pipeline of a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// The build of the witness of the block, i.e. the input of the program.
+    Witness,
+    /// The runs of the Kakarot program.
+    Run,
+    /// The serialization of the output and traces of the run to the database.
+    Serialize,
+    /// The production of the Cairo PIE proven by the prover.
+    Prove,
+    /// The submission of the Cairo PIE to the prover.
+    Submit,
+}
+
+impl Stage {
+    /// Returns the name of the stage, i.e. the name of its spans.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Witness => "witness",
+            Self::Run => "run",
+            Self::Serialize => "serialize",
+            Self::Prove => "prove",
+            Self::Submit => "submit",
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The sampling of the traced blocks, tracing one block out of `every` by their number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceSampling {
+    /// The number of blocks per traced block, at least 1.
+    every: u64,
+}
+
+impl TraceSampling {
+    /// Creates a sampling tracing one block out of `every`, all the blocks being traced when
+    /// `every` is 0 or 1.
+    pub const fn every(every: u64) -> Self {
+        Self { every: if every == 0 { 1 } else { every } }
+    }
+
+    /// Returns the process-wide sampling.
+    pub fn current() -> Self {
+        Self::every(SAMPLING.load(Ordering::Relaxed))
+    }
+
+    /// Returns the number of blocks per traced block.
+    pub const fn rate(&self) -> u64 {
+        self.every
+    }
+
+    /// Returns whether the given block is traced.
+    pub const fn is_sampled(&self, number: u64) -> bool {
+        number % self.every == 0
+    }
+}
+
+impl Default for TraceSampling {
+    fn default() -> Self {
+        Self::every(1)
+    }
+}
+
+/// Sets the process-wide sampling of the traced blocks.
+pub fn set_sampling(sampling: TraceSampling) {
+    SAMPLING.store(sampling.rate(), Ordering::Relaxed);
+}
+
+/// Returns the span of a block processed by an instance, disabled when out of the sample.
+pub fn block_span(instance: &str, number: u64) -> Span {
+    if !TraceSampling::current().is_sampled(number) {
+        return Span::none();
+    }
+    info_span!(target: TARGET, "block", instance, number)
+}
+
+/// Returns the span of a stage of a block, disabled when out of the sample.
+///
+/// The span of the [`Stage::Submit`] stage declares an empty `job_id` field, recorded once the
+/// prover accepted the job.
+pub fn stage_span(stage: Stage, number: u64) -> Span {
+    if !TraceSampling::current().is_sampled(number) {
+        return Span::none();
+    }
+    match stage {
+        Stage::Witness => info_span!(target: TARGET, "witness", number),
+        Stage::Run => info_span!(target: TARGET, "run", number),
+        Stage::Serialize => info_span!(target: TARGET, "serialize", number),
+        Stage::Prove => info_span!(target: TARGET, "prove", number),
+        Stage::Submit => info_span!(target: TARGET, "submit", number, job_id = field::Empty),
+    }
+}
+
+/// Returns the span of a transaction of a block, disabled when out of the sample.
+pub fn transaction_span(number: u64, index: usize, tx_hash: B256) -> Span {
+    if !TraceSampling::current().is_sampled(number) {
+        return Span::none();
+    }
+    debug_span!(target: TARGET, "transaction", number, index, %tx_hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_sampling() {
+        let sampling = TraceSampling::every(4);
+        assert_eq!(sampling.rate(), 4);
+        assert!(sampling.is_sampled(0) && sampling.is_sampled(8));
+        assert!(!sampling.is_sampled(3));
+
+        // All the blocks are traced by default, or with a rate of 0
+        assert!((0..10).all(|number| TraceSampling::default().is_sampled(number)));
+        assert_eq!(TraceSampling::every(0), TraceSampling::default());
+    }
+
+    #[test]
+    fn test_stage() {
+        assert_eq!(Stage::Witness.to_string(), "witness");
+        assert_eq!(Stage::Submit.as_str(), "submit");
+    }
+}
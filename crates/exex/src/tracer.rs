@@ -0,0 +1,179 @@
+//! The tracers of the Cairo execution of a transaction, selected by their geth name.
+//!
+//! Besides the `structLogger` and the `callTracer`, two lightweight tracers are computed from the
+//! decoded EVM instruction stream: the `4byteTracer`, counting the calls of each function selector
+//! and calldata size, and the `opcodeTracer`, the frequency histogram of the executed opcodes. Both
+//! match the statistics stored with the block analytics.
+
+use crate::{
+    analytics::{OpcodeStat, SelectorStat},
+    calltracer::{self, CallTracerConfig},
+    db::Database,
+    structlog::{self, ExecutionTrace, StructLoggerConfig},
+};
+use std::{collections::BTreeMap, fmt, path::Path, str::FromStr};
+use thiserror::Error;
+
+/// Represents errors that can occur when selecting a tracer.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TracerError {
+    /// Error variant indicating a tracer name which is not supported.
+    #[error("Unknown tracer '{0}'")]
+    UnknownTracer(String),
+}
+
+/// A tracer of the Cairo execution of a transaction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Tracer {
+    /// The default tracer of geth, logging every executed opcode.
+    #[default]
+    StructLogger,
+    /// The call tree of the transaction.
+    CallTracer,
+    /// The number of calls of each function selector and calldata size.
+    FourByteTracer,
+    /// The number of executions of each opcode.
+    OpcodeTracer,
+}
+
+impl Tracer {
+    /// Returns the name of the tracer, as given to geth.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::StructLogger => "structLogger",
+            Self::CallTracer => "callTracer",
+            Self::FourByteTracer => "4byteTracer",
+            Self::OpcodeTracer => "opcodeTracer",
+        }
+    }
+}
+
+impl FromStr for Tracer {
+    type Err = TracerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" | "structLogger" => Ok(Self::StructLogger),
+            "callTracer" => Ok(Self::CallTracer),
+            "4byteTracer" => Ok(Self::FourByteTracer),
+            "opcodeTracer" => Ok(Self::OpcodeTracer),
+            _ => Err(TracerError::UnknownTracer(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The tracer of a trace and the options of the configurable tracers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TraceOptions {
+    /// The tracer.
+    pub tracer: Tracer,
+    /// The options of the `structLogger`.
+    pub struct_logger: StructLoggerConfig,
+    /// The options of the `callTracer`.
+    pub call_tracer: CallTracerConfig,
+}
+
+impl TraceOptions {
+    /// Returns the options of a tracer from its JSON `tracerConfig`, the defaults when empty.
+    pub fn from_config(tracer: Tracer, config: &str) -> serde_json::Result<Self> {
+        let mut options = Self { tracer, ..Default::default() };
+        if config.trim().is_empty() {
+            return Ok(options);
+        }
+        match tracer {
+            Tracer::StructLogger => options.struct_logger = serde_json::from_str(config)?,
+            Tracer::CallTracer => options.call_tracer = serde_json::from_str(config)?,
+            Tracer::FourByteTracer | Tracer::OpcodeTracer => {}
+        }
+        Ok(options)
+    }
+}
+
+/// Returns the output of the `4byteTracer`: the number of calls by `<selector>-<calldata size>`.
+pub fn four_byte_counts(stats: &[SelectorStat]) -> BTreeMap<String, u64> {
+    stats.iter().map(|stat| (stat.key(), stat.count)).collect()
+}
+
+/// Returns the output of the `opcodeTracer`: the number of executions by opcode mnemonic.
+pub fn opcode_counts(stats: &[OpcodeStat]) -> BTreeMap<String, u64> {
+    let mut counts = BTreeMap::new();
+    for stat in stats {
+        *counts.entry(stat.name().to_string()).or_default() += stat.count;
+    }
+    counts
+}
+
+/// Traces a transaction of a block from its stored Cairo execution with the selected tracer,
+/// returning the JSON output of the tracer.
+pub fn trace_transaction(
+    db: &Database,
+    program: &Path,
+    block_number: u64,
+    tx_index: u32,
+    options: &TraceOptions,
+) -> eyre::Result<serde_json::Value> {
+    let trace = match options.tracer {
+        Tracer::StructLogger => {
+            let (gas, steps) = structlog::transaction_steps(db, program, block_number, tx_index)?;
+            ExecutionTrace::new(gas, &steps, &options.struct_logger).to_json()
+        }
+        Tracer::CallTracer => {
+            calltracer::trace_calls(db, program, block_number, tx_index, &options.call_tracer)?
+                .to_json()
+        }
+        Tracer::FourByteTracer => {
+            let config = CallTracerConfig::default();
+            let root = calltracer::trace_calls(db, program, block_number, tx_index, &config)?;
+            let stats = SelectorStat::from_call_tree(block_number, tx_index, &root);
+            serde_json::to_value(four_byte_counts(&stats))?
+        }
+        Tracer::OpcodeTracer => {
+            let (_, steps) = structlog::transaction_steps(db, program, block_number, tx_index)?;
+            let stats = OpcodeStat::from_steps(block_number, tx_index, &steps);
+            serde_json::to_value(opcode_counts(&stats))?
+        }
+    };
+    Ok(trace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracer_names() {
+        for tracer in
+            [Tracer::StructLogger, Tracer::CallTracer, Tracer::FourByteTracer, Tracer::OpcodeTracer]
+        {
+            assert_eq!(tracer.as_str().parse(), Ok(tracer));
+        }
+        assert_eq!("".parse(), Ok(Tracer::StructLogger));
+        assert_eq!(
+            "prestateTracer".parse::<Tracer>(),
+            Err(TracerError::UnknownTracer("prestateTracer".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_trace_options_from_config() {
+        let options = TraceOptions::from_config(Tracer::CallTracer, r#"{"onlyTopCall":true}"#);
+        assert!(options.unwrap().call_tracer.only_top_call);
+
+        let options = TraceOptions::from_config(Tracer::StructLogger, "").unwrap();
+        assert_eq!(options, TraceOptions::default());
+        assert!(TraceOptions::from_config(Tracer::StructLogger, "[").is_err());
+    }
+
+    #[test]
+    fn test_opcode_counts() {
+        let stat = |opcode: u8, count: u64| OpcodeStat { opcode, count, ..Default::default() };
+        let counts = opcode_counts(&[stat(0x01, 2), stat(0x0c, 1), stat(0x0d, 1)]);
+        assert_eq!(counts, BTreeMap::from([("ADD".to_string(), 2), ("UNKNOWN".to_string(), 2)]));
+    }
+}
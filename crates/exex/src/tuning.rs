@@ -0,0 +1,356 @@
+//! Tuning of the Cairo runner from the profiles of the previously executed blocks.
+//!
+//! Each completed execution records a [`RunProfile`]: the used size of each memory segment and
+//! the number of dictionaries created by the hints. A [`RunnerTuning`], either configured or
+//! learned from the last profiles, pre-sizes the pools of the next runner so that huge blocks do
+//! not reallocate or rehash them repeatedly.
+//!
+//! The pinned Cairo VM does not expose the capacity of its memory segments, which grow as they are
+//! written. The segment capacities pre-size the relocated memory instead, the flat copy of all the
+//! segments built at the end of the run, which the VM otherwise grows cell by cell. The dictionary
+//! pool is pre-sized by seeding the dictionary manager that the dictionary hints look up in the
+//! execution scopes.
+//!
+//! The profiles also record the utilization of each segment, its accessed cells out of its
+//! allocated ones, the other cells being memory holes the prover pays for. The accessed cells of
+//! the program and execution segments are counted by the VM, and the builtin segments are filled
+//! up to their used size, so that no memory cell is read to profile a run. Poorly utilized
+//! segments are reported as [`UtilizationWarning`]s, hinting at a layout or allocation change.
+
+use cairo_vm::{
+    cairo_run::CairoRunConfig, hint_processor::builtin_hint_processor::dict_manager::DictManager,
+    vm::runners::cairo_runner::CairoRunner,
+};
+use serde::{Deserialize, Serialize};
+use std::{cell::RefCell, collections::BTreeMap, fmt, rc::Rc};
+
+/// The name of the dictionary manager in the execution scopes, as used by the dictionary hints.
+pub const DICT_MANAGER_SCOPE: &str = "dict_manager";
+
+/// The name of the program segment in a [`RunProfile`].
+pub const PROGRAM_SEGMENT: &str = "program";
+
+/// The name of the execution segment in a [`RunProfile`].
+pub const EXECUTION_SEGMENT: &str = "execution";
+
+/// The extra capacity added to the learned sizes, as a fraction of the maximum observed size.
+const HEADROOM_DIVISOR: usize = 8;
+
+/// The utilization of a segment below which it is reported, in percent.
+pub const LOW_UTILIZATION_PERCENT: usize = 50;
+
+/// The allocated size of a segment below which its utilization is not reported.
+const MIN_REPORTED_SIZE: usize = 1 << 10;
+
+/// The utilization of a memory segment at the end of a run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SegmentUtilization {
+    /// The allocated size of the segment, finalized to the ratio of the layout for the builtin
+    /// segments of the proof-mode runs.
+    pub allocated: usize,
+    /// The number of cells of the segment accessed by the VM.
+    #[serde(alias = "written")]
+    pub accessed: usize,
+}
+
+impl SegmentUtilization {
+    /// Returns the number of memory holes of the segment, i.e. of allocated cells never accessed.
+    pub const fn holes(&self) -> usize {
+        self.allocated.saturating_sub(self.accessed)
+    }
+
+    /// Returns the accessed share of the allocated cells, in percent.
+    pub const fn percent(&self) -> usize {
+        if self.allocated == 0 {
+            return 100;
+        }
+        self.accessed * 100 / self.allocated
+    }
+}
+
+/// A utilization pattern of a run suggesting a tuning change that would cut its proving cost.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UtilizationWarning {
+    /// The layout allocates many more instances of a builtin than used.
+    OverAllocatedBuiltin {
+        /// The name of the builtin.
+        builtin: String,
+        /// The utilization of its segment, in percent.
+        percent: usize,
+    },
+    /// A segment of the program or of the execution has many memory holes.
+    Holes {
+        /// The name of the segment.
+        segment: String,
+        /// The number of memory holes.
+        holes: usize,
+    },
+}
+
+impl fmt::Display for UtilizationWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OverAllocatedBuiltin { builtin, percent } => write!(
+                f,
+                "builtin {builtin} uses {percent}% of its allocated cells, a layout with a lower \
+                 {builtin} ratio would cut the proving cost"
+            ),
+            Self::Holes { segment, holes } => write!(
+                f,
+                "segment {segment} has {holes} memory holes, allocating its values contiguously \
+                 would cut the proving cost"
+            ),
+        }
+    }
+}
+
+/// The resources used by the execution of a block, as observed at the end of the run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunProfile {
+    /// The used size of the program, execution and builtin segments, by name.
+    pub segments: BTreeMap<String, usize>,
+    /// The number of dictionaries created by the hints.
+    pub dicts: usize,
+    /// The utilization of the program, execution and builtin segments, by name.
+    #[serde(default)]
+    pub utilization: BTreeMap<String, SegmentUtilization>,
+}
+
+impl RunProfile {
+    /// Profiles an ended runner, whose segment sizes are computed.
+    pub fn from_runner(runner: &CairoRunner) -> Self {
+        let segments = &runner.vm.segments;
+        // The builtin segments are filled contiguously, their used cells are all accessed.
+        let mut names = vec![(PROGRAM_SEGMENT, 0, false), (EXECUTION_SEGMENT, 1, false)];
+        names.extend(
+            runner
+                .vm
+                .get_builtin_runners()
+                .iter()
+                .map(|builtin| (builtin.name().to_str(), builtin.base(), true)),
+        );
+
+        let mut sizes = BTreeMap::new();
+        let mut utilization = BTreeMap::new();
+        for (name, index, builtin) in names {
+            let Some(size) = segments.get_segment_used_size(index) else { continue };
+            let accessed = if builtin {
+                size
+            } else {
+                segments.memory.get_amount_of_accessed_addresses_for_segment(index).unwrap_or(size)
+            };
+            let allocated = segments.get_segment_size(index).unwrap_or(size).max(size);
+            sizes.insert(name.to_string(), size);
+            utilization.insert(name.to_string(), SegmentUtilization { allocated, accessed });
+        }
+
+        let dicts = runner
+            .exec_scopes
+            .get_dict_manager()
+            .map(|manager| manager.borrow().trackers.len())
+            .unwrap_or_default();
+
+        Self { segments: sizes, dicts, utilization }
+    }
+
+    /// Returns the total number of memory holes of the profiled segments.
+    pub fn memory_holes(&self) -> usize {
+        self.utilization.values().map(SegmentUtilization::holes).sum()
+    }
+
+    /// Returns the poorly utilized segments of the run, the small ones being ignored.
+    pub fn utilization_warnings(&self) -> Vec<UtilizationWarning> {
+        self.utilization
+            .iter()
+            .filter(|(_, utilization)| {
+                utilization.allocated >= MIN_REPORTED_SIZE &&
+                    utilization.percent() < LOW_UTILIZATION_PERCENT
+            })
+            .map(|(name, utilization)| match name.as_str() {
+                PROGRAM_SEGMENT | EXECUTION_SEGMENT => {
+                    UtilizationWarning::Holes { segment: name.clone(), holes: utilization.holes() }
+                }
+                _ => UtilizationWarning::OverAllocatedBuiltin {
+                    builtin: name.clone(),
+                    percent: utilization.percent(),
+                },
+            })
+            .collect()
+    }
+}
+
+/// Adds some headroom to a size learned from the profiles.
+const fn with_headroom(size: usize) -> usize {
+    size + size / HEADROOM_DIVISOR
+}
+
+/// The tuning of the pools of a Cairo runner.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunnerTuning {
+    /// The number of dictionaries pre-allocated in the dictionary manager, none when `0`.
+    pub dict_pool: usize,
+    /// The number of cells pre-allocated for each memory segment, by name as in [`RunProfile`].
+    #[serde(default)]
+    pub segments: BTreeMap<String, usize>,
+}
+
+impl RunnerTuning {
+    /// Learns the tuning from the profiles of previous blocks: the pools are sized for the largest
+    /// profile, with some headroom.
+    pub fn learn(profiles: &[RunProfile]) -> Self {
+        let dicts = profiles.iter().map(|profile| profile.dicts).max().unwrap_or_default();
+        let mut segments = BTreeMap::<String, usize>::new();
+        for (name, size) in profiles.iter().flat_map(|profile| &profile.segments) {
+            let capacity = segments.entry(name.clone()).or_default();
+            *capacity = (*capacity).max(with_headroom(*size));
+        }
+        Self { dict_pool: with_headroom(dicts), segments }
+    }
+
+    /// Returns the tuning whose pools are large enough for both tunings.
+    pub fn max(mut self, other: Self) -> Self {
+        for (name, size) in other.segments {
+            let capacity = self.segments.entry(name).or_default();
+            *capacity = (*capacity).max(size);
+        }
+        Self { dict_pool: self.dict_pool.max(other.dict_pool), segments: self.segments }
+    }
+
+    /// Returns the number of cells pre-allocated for all the memory segments.
+    pub fn memory_capacity(&self) -> usize {
+        self.segments.values().sum()
+    }
+
+    /// Pre-sizes the pools of an initialized runner, before its execution, the relocated memory
+    /// only when the run relocates it.
+    pub fn apply(&self, runner: &mut CairoRunner, config: &CairoRunConfig<'_>) {
+        // The relocated memory is only written once the run ends, it stays empty until then.
+        let memory_capacity = self.memory_capacity();
+        if config.relocate_mem && memory_capacity > 0 {
+            // The first relocated address is 1.
+            runner.relocated_memory.reserve(memory_capacity + 1);
+        }
+
+        if self.dict_pool == 0 {
+            return;
+        }
+        let mut manager = DictManager::new();
+        manager.trackers.reserve(self.dict_pool);
+        runner.exec_scopes.insert_value(DICT_MANAGER_SCOPE, Rc::new(RefCell::new(manager)));
+    }
+}
+
+/// The configuration of the tuning of the runners of an instance.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TuningConfig {
+    /// The configured tuning, a lower bound of the learned one.
+    pub tuning: RunnerTuning,
+    /// The number of previous blocks whose profiles are learned from, auto-tuning being disabled
+    /// when `None`.
+    pub auto_tune_window: Option<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        hints::KakarotHintProcessor,
+        limits::{run_with_limits, ExecutionLimits, LimitedRun},
+    };
+    use cairo_vm::types::layout_name::LayoutName;
+
+    #[test]
+    fn test_learn_tuning() {
+        let profile = |dicts, execution| RunProfile {
+            segments: BTreeMap::from([(EXECUTION_SEGMENT.to_string(), execution)]),
+            dicts,
+            ..Default::default()
+        };
+        let tuning = |dict_pool, segments: &[(&str, usize)]| RunnerTuning {
+            dict_pool,
+            segments: segments.iter().map(|(name, size)| (name.to_string(), *size)).collect(),
+        };
+
+        assert_eq!(RunnerTuning::learn(&[]), RunnerTuning::default());
+        assert_eq!(
+            RunnerTuning::learn(&[profile(16, 800), profile(64, 1600), profile(8, 80)]),
+            tuning(72, &[(EXECUTION_SEGMENT, 1800)])
+        );
+        assert_eq!(
+            tuning(10, &[(EXECUTION_SEGMENT, 100)])
+                .max(tuning(4, &[(EXECUTION_SEGMENT, 200), (PROGRAM_SEGMENT, 50)])),
+            tuning(10, &[(EXECUTION_SEGMENT, 200), (PROGRAM_SEGMENT, 50)])
+        );
+        assert_eq!(
+            tuning(0, &[(EXECUTION_SEGMENT, 200), (PROGRAM_SEGMENT, 50)]).memory_capacity(),
+            250
+        );
+    }
+
+    #[test]
+    fn test_run_profile() {
+        let program = include_bytes!("../testdata/keccak_add_uint256.json");
+        let config = CairoRunConfig {
+            layout: LayoutName::all_cairo,
+            relocate_mem: true,
+            ..Default::default()
+        };
+        let mut hint_processor = KakarotHintProcessor::default();
+
+        let result = run_with_limits(
+            program,
+            &config,
+            &mut hint_processor,
+            &ExecutionLimits::default(),
+            &RunnerTuning {
+                dict_pool: 4,
+                segments: BTreeMap::from([(EXECUTION_SEGMENT.to_string(), 1 << 16)]),
+            },
+            &mut || false,
+        );
+        let LimitedRun::Completed(runner) = result.unwrap() else {
+            panic!("Expected a completed run");
+        };
+
+        // The relocated memory is written into the pre-allocated cells.
+        assert!(!runner.relocated_memory.is_empty());
+        assert!(runner.relocated_memory.capacity() > 1 << 16);
+
+        let profile = RunProfile::from_runner(&runner);
+        assert!(profile.segments[EXECUTION_SEGMENT] > 0);
+        assert!(profile.segments.contains_key(PROGRAM_SEGMENT));
+        assert_eq!(profile.dicts, 0);
+
+        let execution = profile.utilization[EXECUTION_SEGMENT];
+        assert!(execution.accessed > 0 && execution.accessed <= execution.allocated);
+        assert_eq!(profile.memory_holes(), profile.utilization.values().map(|u| u.holes()).sum());
+    }
+
+    #[test]
+    fn test_utilization_warnings() {
+        let utilization = |allocated, accessed| SegmentUtilization { allocated, accessed };
+        let profile = RunProfile {
+            utilization: BTreeMap::from([
+                (EXECUTION_SEGMENT.to_string(), utilization(4096, 1024)),
+                (PROGRAM_SEGMENT.to_string(), utilization(2048, 2048)),
+                ("keccak".to_string(), utilization(8192, 2048)),
+                // Small segments are not reported.
+                ("ecdsa".to_string(), utilization(64, 0)),
+            ]),
+            ..Default::default()
+        };
+
+        assert_eq!(profile.memory_holes(), 3072 + 6144 + 64);
+        assert_eq!(
+            profile.utilization_warnings(),
+            vec![
+                UtilizationWarning::Holes { segment: EXECUTION_SEGMENT.to_string(), holes: 3072 },
+                UtilizationWarning::OverAllocatedBuiltin {
+                    builtin: "keccak".to_string(),
+                    percent: 25
+                },
+            ]
+        );
+        assert_eq!(utilization(0, 0).percent(), 100);
+    }
+}
@@ -0,0 +1,371 @@
+//! Registry of the parameters of the verifiers the proofs are submitted to.
+//!
+//! A proof only verifies on L1 if it was generated with the layout and channel hash expected by
+//! the verifier contract registered for its program. The registry maps each program hash and
+//! prover version to these parameters, so that a proof generated with mismatched parameters is
+//! rejected locally before its submission, instead of reverting on L1.
+//!
+//! The registry is loaded from a JSON file holding a list of [`VerifierEntry`], and validated when
+//! the instances start. The imported proofs are checked against it, see
+//! [`crate::deferred::import_proofs`], the channel hash being read from the proof parameters of
+//! the Stone proofs, as are the jobs submitted to SHARP, see [`crate::sharp::submit_jobs`].
+
+use crate::output::ProofMetadata;
+use alloy_primitives::{Address, B256};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path};
+use thiserror::Error;
+
+/// The layouts supported by the Stone prover and its verifiers.
+pub const SUPPORTED_LAYOUTS: [&str; 11] = [
+    "plain",
+    "small",
+    "dex",
+    "recursive",
+    "starknet",
+    "starknet_with_keccak",
+    "recursive_large_output",
+    "recursive_with_poseidon",
+    "all_solidity",
+    "all_cairo",
+    "dynamic",
+];
+
+/// Represents errors that can occur when checking the verifier parameters of a proof.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum VerifierError {
+    /// Error variant indicating an unsupported layout.
+    #[error("Unsupported layout '{0}'")]
+    UnsupportedLayout(String),
+
+    /// Error variant indicating a verifier entry without verifier contract or version.
+    #[error("Incomplete verifier entry for program {program_hash} and prover '{prover_version}'")]
+    IncompleteEntry {
+        /// The hash of the program of the entry.
+        program_hash: B256,
+        /// The prover version of the entry.
+        prover_version: String,
+    },
+
+    /// Error variant indicating that several entries share a program hash and prover version.
+    #[error("Duplicate verifier entry for program {program_hash} and prover '{prover_version}'")]
+    DuplicateEntry {
+        /// The hash of the program of the entries.
+        program_hash: B256,
+        /// The prover version of the entries.
+        prover_version: String,
+    },
+
+    /// Error variant indicating that no verifier is registered for a proof.
+    #[error("No verifier registered for program {program_hash} and prover '{prover_version}'")]
+    Unregistered {
+        /// The hash of the program of the proof.
+        program_hash: B256,
+        /// The prover version of the proof.
+        prover_version: String,
+    },
+
+    /// Error variant indicating a program for which no verifier is registered, whatever the prover
+    /// version.
+    #[error("No verifier registered for program {0}")]
+    UnregisteredProgram(B256),
+
+    /// Error variant indicating that a proof was generated with another layout than the one of
+    /// its verifier.
+    #[error("Proof generated with layout '{actual}', its verifier expects '{expected}'")]
+    LayoutMismatch {
+        /// The layout of the verifier.
+        expected: String,
+        /// The layout of the proof.
+        actual: String,
+    },
+
+    /// Error variant indicating that a proof was generated with another channel hash than the one
+    /// of its verifier.
+    #[error("Proof generated with channel hash {actual:?}, its verifier expects {expected:?}")]
+    ChannelHashMismatch {
+        /// The channel hash of the verifier.
+        expected: ChannelHash,
+        /// The channel hash of the proof.
+        actual: ChannelHash,
+    },
+
+    /// Error variant indicating a proof whose parameters cannot be read.
+    #[error("Invalid proof parameters: {0}")]
+    InvalidProof(String),
+}
+
+/// The hash function of the channel of the proofs, i.e. of their Fiat-Shamir transcript.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelHash {
+    /// Keccak256, masked to its 160 least significant bits.
+    Keccak256Masked160Lsb,
+    /// Keccak256, masked to its 248 least significant bits.
+    Keccak256Masked248Lsb,
+    /// Blake2s256, masked to its 248 least significant bits.
+    Blake2s256Masked248Lsb,
+    /// Poseidon3.
+    Poseidon3,
+}
+
+/// The parameters of a Stone proof, as serialized in its JSON.
+#[derive(Debug, Deserialize)]
+struct StoneProof {
+    /// The parameters the proof was generated with.
+    proof_parameters: StoneProofParameters,
+}
+
+/// The parameters of a Stone proof checked against its verifier.
+#[derive(Debug, Deserialize)]
+struct StoneProofParameters {
+    /// The hash function of the channel.
+    channel_hash: ChannelHash,
+}
+
+/// The parameters a proof must be generated with to verify on its verifier.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifierParams {
+    /// The layout of the proven runs.
+    pub layout: String,
+    /// The hash function of the channel.
+    pub channel_hash: ChannelHash,
+    /// The address of the verifier contract.
+    pub verifier: Address,
+    /// The version of the verifier contract.
+    pub verifier_version: String,
+}
+
+/// The verifier parameters of the proofs of a program generated by a prover version.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifierEntry {
+    /// The hash of the proven program.
+    pub program_hash: B256,
+    /// The version of the prover, as recorded in the metadata of the proofs.
+    pub prover_version: String,
+    /// The parameters of the verifier.
+    #[serde(flatten)]
+    pub params: VerifierParams,
+}
+
+/// The verifier parameters, keyed by program hash and prover version.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifierRegistry {
+    /// The parameters of each program hash and prover version.
+    entries: BTreeMap<(B256, String), VerifierParams>,
+}
+
+impl VerifierRegistry {
+    /// Creates a registry from its entries, validating them.
+    pub fn from_entries(entries: Vec<VerifierEntry>) -> Result<Self, VerifierError> {
+        let mut registry = Self::default();
+        for VerifierEntry { program_hash, prover_version, params } in entries {
+            if !SUPPORTED_LAYOUTS.contains(&params.layout.as_str()) {
+                return Err(VerifierError::UnsupportedLayout(params.layout));
+            }
+            if params.verifier.is_zero() || params.verifier_version.is_empty() {
+                return Err(VerifierError::IncompleteEntry { program_hash, prover_version });
+            }
+            if registry.entries.contains_key(&(program_hash, prover_version.clone())) {
+                return Err(VerifierError::DuplicateEntry { program_hash, prover_version });
+            }
+            registry.entries.insert((program_hash, prover_version), params);
+        }
+        Ok(registry)
+    }
+
+    /// Loads and validates the registry from a JSON file holding a list of [`VerifierEntry`].
+    pub fn load(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let entries = serde_json::from_slice(&fs::read(path)?)?;
+        Ok(Self::from_entries(entries)?)
+    }
+
+    /// Returns the number of registered entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether no entry is registered.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the verifier parameters of a program hash and prover version, if registered.
+    pub fn params(&self, program_hash: B256, prover_version: &str) -> Option<&VerifierParams> {
+        self.entries.get(&(program_hash, prover_version.to_string()))
+    }
+
+    /// Checks that a verifier is registered for a program, whatever its prover version, i.e. that
+    /// its proofs can be verified on-chain.
+    pub fn check_program(&self, program_hash: B256) -> Result<(), VerifierError> {
+        if self.entries.keys().any(|(hash, _)| *hash == program_hash) {
+            Ok(())
+        } else {
+            Err(VerifierError::UnregisteredProgram(program_hash))
+        }
+    }
+
+    /// Checks that a proof of the given program, prover version and layout can be submitted to
+    /// its verifier, returning the verifier parameters.
+    pub fn check(
+        &self,
+        program_hash: B256,
+        prover_version: &str,
+        layout: &str,
+    ) -> Result<&VerifierParams, VerifierError> {
+        let params = self.params(program_hash, prover_version).ok_or_else(|| {
+            VerifierError::Unregistered { program_hash, prover_version: prover_version.to_string() }
+        })?;
+        if params.layout != layout {
+            return Err(VerifierError::LayoutMismatch {
+                expected: params.layout.clone(),
+                actual: layout.to_string(),
+            });
+        }
+        Ok(params)
+    }
+
+    /// Checks that a Stone proof can be submitted to its verifier: its program, prover and layout
+    /// as recorded in its metadata, and the channel hash of its proof parameters.
+    pub fn check_proof(
+        &self,
+        metadata: &ProofMetadata,
+        proof: &[u8],
+    ) -> Result<&VerifierParams, VerifierError> {
+        let params = self.check(metadata.program_hash, &metadata.prover, &metadata.layout)?;
+        let proof: StoneProof = serde_json::from_slice(proof)
+            .map_err(|err| VerifierError::InvalidProof(err.to_string()))?;
+        if proof.proof_parameters.channel_hash != params.channel_hash {
+            return Err(VerifierError::ChannelHashMismatch {
+                expected: params.channel_hash,
+                actual: proof.proof_parameters.channel_hash,
+            });
+        }
+        Ok(params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::address;
+
+    fn entry(prover_version: &str, layout: &str) -> VerifierEntry {
+        VerifierEntry {
+            program_hash: B256::with_last_byte(1),
+            prover_version: prover_version.to_string(),
+            params: VerifierParams {
+                layout: layout.to_string(),
+                channel_hash: ChannelHash::Keccak256Masked160Lsb,
+                verifier: address!("47312450B3Ac8b5b8e247a6bB6d523e7605bDb60"),
+                verifier_version: "0.13.2".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_check_proof_parameters() {
+        let registry =
+            VerifierRegistry::from_entries(vec![entry("stone-v6", "all_cairo")]).unwrap();
+        let program_hash = B256::with_last_byte(1);
+
+        assert_eq!(
+            registry.check(program_hash, "stone-v6", "all_cairo").unwrap().verifier_version,
+            "0.13.2"
+        );
+        assert_eq!(
+            registry.check(program_hash, "stone-v6", "recursive"),
+            Err(VerifierError::LayoutMismatch {
+                expected: "all_cairo".to_string(),
+                actual: "recursive".to_string()
+            })
+        );
+        assert_eq!(
+            registry.check(program_hash, "stone-v5", "all_cairo"),
+            Err(VerifierError::Unregistered {
+                program_hash,
+                prover_version: "stone-v5".to_string()
+            })
+        );
+
+        assert_eq!(registry.check_program(program_hash), Ok(()));
+        assert_eq!(
+            registry.check_program(B256::with_last_byte(2)),
+            Err(VerifierError::UnregisteredProgram(B256::with_last_byte(2)))
+        );
+    }
+
+    #[test]
+    fn test_check_proof_channel_hash() {
+        let registry =
+            VerifierRegistry::from_entries(vec![entry("stone-v6", "all_cairo")]).unwrap();
+        let metadata = ProofMetadata {
+            program_hash: B256::with_last_byte(1),
+            layout: "all_cairo".to_string(),
+            prover: "stone-v6".to_string(),
+            ..Default::default()
+        };
+        let proof = |channel_hash: &str| {
+            format!(
+                r#"{{"proof_hex":"0x00","proof_parameters":{{"channel_hash":"{channel_hash}"}}}}"#
+            )
+        };
+
+        registry.check_proof(&metadata, proof("keccak256_masked160_lsb").as_bytes()).unwrap();
+        assert_eq!(
+            registry.check_proof(&metadata, proof("poseidon3").as_bytes()),
+            Err(VerifierError::ChannelHashMismatch {
+                expected: ChannelHash::Keccak256Masked160Lsb,
+                actual: ChannelHash::Poseidon3
+            })
+        );
+        assert!(matches!(
+            registry.check_proof(&metadata, b"proof"),
+            Err(VerifierError::InvalidProof(_))
+        ));
+        assert!(matches!(
+            registry.check_proof(
+                &ProofMetadata { layout: "dex".to_string(), ..metadata },
+                proof("keccak256_masked160_lsb").as_bytes()
+            ),
+            Err(VerifierError::LayoutMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_validate_registry() {
+        assert_eq!(
+            VerifierRegistry::from_entries(vec![entry("stone-v6", "huge")]),
+            Err(VerifierError::UnsupportedLayout("huge".to_string()))
+        );
+        assert_eq!(
+            VerifierRegistry::from_entries(vec![entry("v6", "dex"), entry("v6", "small")]),
+            Err(VerifierError::DuplicateEntry {
+                program_hash: B256::with_last_byte(1),
+                prover_version: "v6".to_string()
+            })
+        );
+
+        let mut incomplete = entry("stone-v6", "dex");
+        incomplete.params.verifier = Address::ZERO;
+        assert!(matches!(
+            VerifierRegistry::from_entries(vec![incomplete]),
+            Err(VerifierError::IncompleteEntry { .. })
+        ));
+    }
+
+    #[test]
+    fn test_deserialize_entries() {
+        let json = r#"[{
+            "program_hash": "0x0000000000000000000000000000000000000000000000000000000000000001",
+            "prover_version": "stone-v6",
+            "layout": "all_cairo",
+            "channel_hash": "keccak256_masked160_lsb",
+            "verifier": "0x47312450b3ac8b5b8e247a6bb6d523e7605bdb60",
+            "verifier_version": "0.13.2"
+        }]"#;
+        let entries: Vec<VerifierEntry> = serde_json::from_str(json).unwrap();
+
+        assert_eq!(entries, vec![entry("stone-v6", "all_cairo")]);
+    }
+}
@@ -0,0 +1,563 @@
+//! Detection of the stalled stages of an instance, with alerting hooks.
+//!
+//! The [`Watchdog`] of an instance periodically checks its stages for stalls:
+//! - the execution, when no block was processed for longer than allowed, e.g. when the node stopped
+//!   syncing or a block hangs the runner, from the [`Heartbeat`] of the instance;
+//! - the proving, when an exported proving job is not proven past its estimated duration since its
+//!   export, estimated per job from the steps of its run with a [`ProverCostModel`];
+//! - the submission, when the nonce of the account submitting the proofs does not progress while it
+//!   has pending transactions, e.g. underpriced ones.
+//!
+//! The database is read off the runtime, and the nonces of the submitter are read from its chain
+//! with `eth_getTransactionCount`.
+//!
+//! Each new stall fires the alert hooks of the [`WatchdogConfig`] once, i.e. a log, a metric or a
+//! webhook, and is forgotten once the stage recovers, so that a stall fires again if it recurs.
+
+use crate::{
+    benchmark::{BlockSample, ProverCostModel},
+    db::Database,
+    deferred::{JobState, ProvingJob},
+    retry,
+};
+use alloy_primitives::{Address, U64};
+use metrics::Label;
+use reth_tracing::tracing::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    collections::HashSet,
+    fmt, fs,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// The default interval between two checks, in seconds.
+pub const DEFAULT_CHECK_INTERVAL_SECS: u64 = 60;
+
+/// The maximum number of in-flight proving jobs checked per stage check.
+const MAX_CHECKED_JOBS: usize = 1000;
+
+/// A stage of an instance watched for stalls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StallStage {
+    /// The execution of the blocks.
+    Execution,
+    /// The proving of the exported blocks.
+    Proving,
+    /// The submission of the proofs on-chain.
+    Submission,
+}
+
+impl StallStage {
+    /// Returns the name of the stage.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Execution => "execution",
+            Self::Proving => "proving",
+            Self::Submission => "submission",
+        }
+    }
+}
+
+impl fmt::Display for StallStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A hook fired on each new stall.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AlertHook {
+    /// Logs the stall as a warning.
+    Log,
+    /// Increments the `kakarot_stalls` counter, labelled by stage.
+    Metric,
+    /// Posts the stall as JSON to a webhook.
+    Webhook {
+        /// The URL of the webhook.
+        url: String,
+    },
+}
+
+/// The configuration of the watchdog of an instance, loaded from a JSON file, e.g.
+/// `{"execution_secs": 600, "proving_secs": 3600, "hooks": [{"type": "log"}]}`.
+///
+/// A stage without threshold is not watched. Without hooks, the stalls are logged.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatchdogConfig {
+    /// The interval between two checks, in seconds.
+    #[serde(default = "default_check_interval_secs")]
+    pub interval_secs: u64,
+    /// The duration without processed block past which the execution is stalled, in seconds.
+    #[serde(default)]
+    pub execution_secs: Option<u64>,
+    /// The duration past the estimated proving duration of an exported job, since its export,
+    /// past which the job is stalled, in seconds.
+    #[serde(default)]
+    pub proving_secs: Option<u64>,
+    /// The cost model of the prover, estimating the proving duration of each job from the steps
+    /// of its run. Without model, the estimate is zero and only `proving_secs` is allowed.
+    #[serde(default)]
+    pub proving_model: Option<ProverCostModel>,
+    /// The duration without progress of the nonce of the submitter, while it has pending
+    /// transactions, past which the submission is stalled, in seconds.
+    #[serde(default)]
+    pub submission_secs: Option<u64>,
+    /// The account submitting the proofs, whose nonce is watched.
+    #[serde(default)]
+    pub submitter: Option<SubmitterConfig>,
+    /// The hooks fired on each new stall.
+    #[serde(default)]
+    pub hooks: Vec<AlertHook>,
+}
+
+/// The account submitting the proofs on-chain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubmitterConfig {
+    /// The URL of the RPC endpoint of the chain the proofs are submitted to.
+    pub rpc_url: String,
+    /// The address of the account.
+    pub address: Address,
+}
+
+/// Returns the default interval between two checks, in seconds.
+const fn default_check_interval_secs() -> u64 {
+    DEFAULT_CHECK_INTERVAL_SECS
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: DEFAULT_CHECK_INTERVAL_SECS,
+            execution_secs: None,
+            proving_secs: None,
+            proving_model: None,
+            submission_secs: None,
+            submitter: None,
+            hooks: Vec::new(),
+        }
+    }
+}
+
+impl WatchdogConfig {
+    /// Loads a configuration from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    /// Returns the interval between two checks, at least a second.
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs.max(1))
+    }
+
+    /// Returns the estimated proving duration of an exported job, in seconds, zero without cost
+    /// model or recorded steps.
+    pub fn proving_estimate(&self, job: &ProvingJob) -> u64 {
+        match (&self.proving_model, job.steps) {
+            (Some(model), Some(steps)) => {
+                let sample =
+                    BlockSample { block_number: job.block_number, steps, ..Default::default() };
+                model.proving_secs(&sample).ceil() as u64
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// The progress of the execution of an instance: the time of its last processed block.
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+    /// The UNIX timestamp, in seconds, of the last beat, with the block processed if any.
+    last: Arc<Mutex<(u64, Option<u64>)>>,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self { last: Arc::new(Mutex::new((retry::now(), None))) }
+    }
+}
+
+impl Heartbeat {
+    /// Records the processing of a block at the given UNIX timestamp.
+    pub fn beat(&self, block_number: u64, now: u64) {
+        *self.last.lock().expect("failed to acquire heartbeat lock") = (now, Some(block_number));
+    }
+
+    /// Returns the UNIX timestamp of the last beat, the start of the instance without processed
+    /// block, with the last processed block if any.
+    pub fn last(&self) -> (u64, Option<u64>) {
+        *self.last.lock().expect("failed to acquire heartbeat lock")
+    }
+}
+
+/// The key identifying a stall across checks: its stage, block and nonce.
+type StallKey = (StallStage, Option<u64>, Option<u64>);
+
+/// A stalled stage of an instance.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stall {
+    /// The name of the instance.
+    pub instance: String,
+    /// The stalled stage.
+    pub stage: StallStage,
+    /// The stalled block, or the last processed block for the execution.
+    pub block_number: Option<u64>,
+    /// The stuck nonce of the submitter, for the submission.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<u64>,
+    /// The UNIX timestamp, in seconds, since which the stage did not progress.
+    pub since: u64,
+    /// The duration of the stall at its detection, in seconds.
+    pub stalled_secs: u64,
+}
+
+impl Stall {
+    /// Returns the key identifying the stall across checks.
+    const fn key(&self) -> StallKey {
+        (self.stage, self.block_number, self.nonce)
+    }
+}
+
+/// The watchdog of an instance, checking its stages for stalls and firing its alert hooks.
+#[derive(Debug)]
+pub struct Watchdog {
+    /// The configuration of the watchdog.
+    config: WatchdogConfig,
+    /// The name of the instance.
+    instance: String,
+    /// The metrics labels of the instance.
+    labels: Vec<Label>,
+    /// The database of the instance.
+    db: Database,
+    /// The heartbeat of the instance.
+    heartbeat: Heartbeat,
+    /// The stalls already alerted, which did not recover yet.
+    active: HashSet<StallKey>,
+    /// The last confirmed nonce of the submitter, with the UNIX timestamp since which it did not
+    /// progress.
+    nonce: Option<(u64, u64)>,
+    /// The client of the webhooks and of the RPC endpoint of the submitter.
+    client: reqwest::Client,
+}
+
+impl Watchdog {
+    /// Creates a new [`Watchdog`] of an instance.
+    pub fn new(
+        config: WatchdogConfig,
+        instance: String,
+        labels: Vec<Label>,
+        db: Database,
+        heartbeat: Heartbeat,
+    ) -> Self {
+        Self {
+            config,
+            instance,
+            labels,
+            db,
+            heartbeat,
+            active: HashSet::new(),
+            nonce: None,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Checks the stages of the instance at each interval until the task is aborted.
+    ///
+    /// Errors are logged rather than returned, so that a failing check does not stop the next
+    /// ones.
+    pub async fn run(mut self) {
+        let mut interval = tokio::time::interval(self.config.interval());
+        loop {
+            interval.tick().await;
+            match self.check(retry::now()).await {
+                Ok(stalls) => {
+                    for stall in self.track(stalls) {
+                        self.alert(&stall).await;
+                    }
+                }
+                Err(err) => error!(instance = %self.instance, %err, "Failed to check for stalls"),
+            }
+        }
+    }
+
+    /// Returns the stalled stages of the instance at the given UNIX timestamp.
+    pub async fn check(&mut self, now: u64) -> eyre::Result<Vec<Stall>> {
+        let mut stalls = Vec::new();
+
+        if let Some(limit) = self.config.execution_secs {
+            let (last, block_number) = self.heartbeat.last();
+            if now.saturating_sub(last) > limit {
+                stalls.push(self.stall(StallStage::Execution, block_number, last, now));
+            }
+        }
+        if let Some(limit) = self.config.proving_secs {
+            let db = self.db.clone();
+            let jobs = tokio::task::spawn_blocking(move || {
+                db.proving_jobs(JobState::Exported, MAX_CHECKED_JOBS)
+            })
+            .await??;
+            for job in jobs {
+                // Jobs exported before their export time was recorded are measured from their
+                // enqueueing.
+                let since = job.exported_at.unwrap_or(job.enqueued_at);
+                let estimate = self.config.proving_estimate(&job).saturating_add(limit);
+                if now.saturating_sub(since) > estimate {
+                    let block_number = Some(job.block_number);
+                    stalls.push(self.stall(StallStage::Proving, block_number, since, now));
+                }
+            }
+        }
+        if let (Some(limit), Some(submitter)) =
+            (self.config.submission_secs, self.config.submitter.clone())
+        {
+            let confirmed = self.transaction_count(&submitter, "latest").await?;
+            let pending = self.transaction_count(&submitter, "pending").await?;
+            stalls.extend(self.check_nonce(confirmed, pending, limit, now));
+        }
+
+        Ok(stalls)
+    }
+
+    /// Returns the stall of the submission, if its confirmed nonce did not progress for longer than
+    /// `limit` while the submitter has pending transactions.
+    fn check_nonce(&mut self, confirmed: u64, pending: u64, limit: u64, now: u64) -> Option<Stall> {
+        let since = match self.nonce {
+            Some((nonce, since)) if nonce == confirmed => since,
+            _ => now,
+        };
+        self.nonce = Some((confirmed, since));
+        (pending > confirmed && now.saturating_sub(since) > limit).then(|| Stall {
+            nonce: Some(confirmed),
+            ..self.stall(StallStage::Submission, None, since, now)
+        })
+    }
+
+    /// Returns the nonce of the submitter at a block tag, e.g. `latest` or `pending`.
+    async fn transaction_count(&self, submitter: &SubmitterConfig, tag: &str) -> eyre::Result<u64> {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_getTransactionCount",
+            "params": [submitter.address, tag],
+        });
+        let response: serde_json::Value = self
+            .client
+            .post(&submitter.rpc_url)
+            .json(&request)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        match response.get("result") {
+            Some(result) => Ok(serde_json::from_value::<U64>(result.clone())?.to()),
+            None => eyre::bail!("eth_getTransactionCount failed: {}", response["error"]),
+        }
+    }
+
+    /// Returns a stall of the instance since the given UNIX timestamp.
+    fn stall(&self, stage: StallStage, block_number: Option<u64>, since: u64, now: u64) -> Stall {
+        Stall {
+            instance: self.instance.clone(),
+            stage,
+            block_number,
+            nonce: None,
+            since,
+            stalled_secs: now.saturating_sub(since),
+        }
+    }
+
+    /// Tracks the stalls of a check, returning the new ones. The stalls of the previous checks
+    /// which are no longer stalled are forgotten.
+    pub fn track(&mut self, stalls: Vec<Stall>) -> Vec<Stall> {
+        let keys: HashSet<_> = stalls.iter().map(Stall::key).collect();
+        for (stage, block_number, nonce) in self.active.difference(&keys) {
+            info!(
+                instance = %self.instance,
+                %stage,
+                ?block_number,
+                ?nonce,
+                "Stage recovered from stall"
+            );
+        }
+        self.active.retain(|key| keys.contains(key));
+        stalls.into_iter().filter(|stall| self.active.insert(stall.key())).collect()
+    }
+
+    /// Fires the alert hooks of a stall, the stall being logged without hooks.
+    async fn alert(&self, stall: &Stall) {
+        let log = [AlertHook::Log];
+        let hooks = if self.config.hooks.is_empty() { &log[..] } else { &self.config.hooks };
+        for hook in hooks {
+            match hook {
+                AlertHook::Log => warn!(
+                    instance = %stall.instance,
+                    stage = %stall.stage,
+                    block_number = ?stall.block_number,
+                    nonce = ?stall.nonce,
+                    stalled_secs = stall.stalled_secs,
+                    "Stage stalled"
+                ),
+                AlertHook::Metric => {
+                    let mut labels = self.labels.clone();
+                    labels.push(Label::new("stage", stall.stage.as_str()));
+                    metrics::counter!("kakarot_stalls", labels).increment(1);
+                }
+                AlertHook::Webhook { url } => {
+                    let response = self.client.post(url).json(stall).send().await;
+                    if let Err(err) = response.and_then(|response| response.error_for_status()) {
+                        warn!(instance = %self.instance, %url, %err, "Failed to post stall alert");
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+    use std::path::PathBuf;
+
+    fn watchdog(config: WatchdogConfig) -> Watchdog {
+        let db = Database::new(Connection::open_in_memory().unwrap()).unwrap();
+        Watchdog::new(config, "prod".to_string(), Vec::new(), db, Heartbeat::default())
+    }
+
+    #[test]
+    fn test_load_watchdog_config() {
+        let config: WatchdogConfig = serde_json::from_str(
+            r#"{
+                "execution_secs": 600,
+                "hooks": [{"type": "log"}, {"type": "webhook", "url": "http://alerts"}]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            WatchdogConfig {
+                execution_secs: Some(600),
+                hooks: vec![
+                    AlertHook::Log,
+                    AlertHook::Webhook { url: "http://alerts".to_string() }
+                ],
+                ..Default::default()
+            }
+        );
+        assert_eq!(config.interval(), Duration::from_secs(DEFAULT_CHECK_INTERVAL_SECS));
+    }
+
+    #[tokio::test]
+    async fn test_execution_stall() {
+        let mut watchdog =
+            watchdog(WatchdogConfig { execution_secs: Some(600), ..Default::default() });
+        watchdog.heartbeat.beat(7, 1000);
+
+        assert_eq!(watchdog.check(1600).await.unwrap(), vec![]);
+        assert_eq!(
+            watchdog.check(1601).await.unwrap(),
+            vec![Stall {
+                instance: "prod".to_string(),
+                stage: StallStage::Execution,
+                block_number: Some(7),
+                nonce: None,
+                since: 1000,
+                stalled_secs: 601,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_proving_stall() {
+        let model = ProverCostModel {
+            name: "stone".to_string(),
+            fixed_secs: 0.0,
+            secs_per_million_steps: 100.0,
+            secs_per_thousand_builtins: Default::default(),
+            hourly_cost: 0.0,
+        };
+        let mut watchdog = watchdog(WatchdogConfig {
+            proving_secs: Some(100),
+            proving_model: Some(model),
+            ..Default::default()
+        });
+        // Job 2 runs twice as many steps as job 1, job 3 is not exported.
+        for (number, steps) in [(1, 1 << 20), (2, 1 << 21), (3, 1 << 20)] {
+            let job = ProvingJob {
+                block_number: number,
+                program: PathBuf::new(),
+                enqueued_at: 0,
+                exported_at: Some(1000),
+                steps: Some(steps),
+            };
+            watchdog.db.enqueue_proving_job(&job).unwrap();
+            if number != 3 {
+                watchdog.db.mark_proving_job_exported(&job).unwrap();
+            }
+        }
+
+        let stalled = |stalls: Vec<Stall>| {
+            stalls.into_iter().map(|stall| (stall.stage, stall.block_number)).collect::<Vec<_>>()
+        };
+        // The jobs are measured from their export, against their estimate of 105 and 210 seconds.
+        assert_eq!(stalled(watchdog.check(1205).await.unwrap()), vec![]);
+        assert_eq!(
+            stalled(watchdog.check(1206).await.unwrap()),
+            vec![(StallStage::Proving, Some(1))]
+        );
+        assert_eq!(
+            stalled(watchdog.check(1311).await.unwrap()),
+            vec![(StallStage::Proving, Some(1)), (StallStage::Proving, Some(2))]
+        );
+    }
+
+    #[test]
+    fn test_submission_stall() {
+        let mut watchdog = watchdog(WatchdogConfig::default());
+
+        // The nonce progresses, or nothing is pending.
+        assert_eq!(watchdog.check_nonce(5, 6, 100, 1000), None);
+        assert_eq!(watchdog.check_nonce(6, 7, 100, 1050), None);
+        assert_eq!(watchdog.check_nonce(6, 6, 100, 1100), None);
+        // The nonce is stuck with pending transactions.
+        assert_eq!(
+            watchdog.check_nonce(6, 8, 100, 1151),
+            Some(Stall {
+                instance: "prod".to_string(),
+                stage: StallStage::Submission,
+                block_number: None,
+                nonce: Some(6),
+                since: 1050,
+                stalled_secs: 101,
+            })
+        );
+        assert_eq!(watchdog.check_nonce(7, 8, 100, 1300), None);
+    }
+
+    #[test]
+    fn test_track_stalls() {
+        let mut watchdog = watchdog(WatchdogConfig::default());
+        let stall = |stage, block_number| Stall {
+            instance: "prod".to_string(),
+            stage,
+            block_number,
+            nonce: None,
+            since: 0,
+            stalled_secs: 0,
+        };
+
+        let stalls = vec![stall(StallStage::Proving, Some(1)), stall(StallStage::Execution, None)];
+        assert_eq!(watchdog.track(stalls.clone()), stalls);
+        // The stalls are only alerted once while they last.
+        assert_eq!(watchdog.track(stalls.clone()), vec![]);
+        // A recovered stall is alerted again when it recurs.
+        assert_eq!(watchdog.track(vec![stall(StallStage::Execution, None)]), vec![]);
+        assert_eq!(watchdog.track(stalls.clone()), vec![stalls[0].clone()]);
+    }
+}
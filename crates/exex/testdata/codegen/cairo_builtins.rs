@@ -0,0 +1,227 @@
+//! Generated from the program identifiers, do not edit.
+
+#[allow(unused_imports)]
+use cairo_vm::{types::relocatable::{MaybeRelocatable, Relocatable}, Felt252};
+use kakarot_exex::serde::{CairoSerde, KakarotSerde, KakarotSerdeError};
+
+/// `starkware.cairo.common.cairo_builtins.BitwiseBuiltin`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitwiseBuiltin {
+    /// `felt` at offset 0.
+    pub x: Felt252,
+    /// `felt` at offset 1.
+    pub y: Felt252,
+    /// `felt` at offset 2.
+    pub x_and_y: Felt252,
+    /// `felt` at offset 3.
+    pub x_xor_y: Felt252,
+    /// `felt` at offset 4.
+    pub x_or_y: Felt252,
+}
+
+impl CairoSerde for BitwiseBuiltin {
+    const STRUCT_NAME: &'static str = "starkware.cairo.common.cairo_builtins.BitwiseBuiltin";
+    const SIZE: usize = 5;
+
+    fn from_cairo(
+        serde: &KakarotSerde,
+        ptr: Relocatable,
+    ) -> Result<Self, KakarotSerdeError> {
+        Ok(Self {
+            x: serde.read_felt((ptr + 0usize)?)?,
+            y: serde.read_felt((ptr + 1usize)?)?,
+            x_and_y: serde.read_felt((ptr + 2usize)?)?,
+            x_xor_y: serde.read_felt((ptr + 3usize)?)?,
+            x_or_y: serde.read_felt((ptr + 4usize)?)?,
+        })
+    }
+}
+
+/// `starkware.cairo.common.cairo_builtins.EcOpBuiltin`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EcOpBuiltin {
+    /// `starkware.cairo.common.ec_point.EcPoint` at offset 0.
+    pub p: [MaybeRelocatable; 2],
+    /// `starkware.cairo.common.ec_point.EcPoint` at offset 2.
+    pub q: [MaybeRelocatable; 2],
+    /// `felt` at offset 4.
+    pub m: Felt252,
+    /// `starkware.cairo.common.ec_point.EcPoint` at offset 5.
+    pub r: [MaybeRelocatable; 2],
+}
+
+impl CairoSerde for EcOpBuiltin {
+    const STRUCT_NAME: &'static str = "starkware.cairo.common.cairo_builtins.EcOpBuiltin";
+    const SIZE: usize = 7;
+
+    fn from_cairo(
+        serde: &KakarotSerde,
+        ptr: Relocatable,
+    ) -> Result<Self, KakarotSerdeError> {
+        Ok(Self {
+            p: serde.read_cells::<2>((ptr + 0usize)?)?,
+            q: serde.read_cells::<2>((ptr + 2usize)?)?,
+            m: serde.read_felt((ptr + 4usize)?)?,
+            r: serde.read_cells::<2>((ptr + 5usize)?)?,
+        })
+    }
+}
+
+/// `starkware.cairo.common.cairo_builtins.HashBuiltin`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashBuiltin {
+    /// `felt` at offset 0.
+    pub x: Felt252,
+    /// `felt` at offset 1.
+    pub y: Felt252,
+    /// `felt` at offset 2.
+    pub result: Felt252,
+}
+
+impl CairoSerde for HashBuiltin {
+    const STRUCT_NAME: &'static str = "starkware.cairo.common.cairo_builtins.HashBuiltin";
+    const SIZE: usize = 3;
+
+    fn from_cairo(
+        serde: &KakarotSerde,
+        ptr: Relocatable,
+    ) -> Result<Self, KakarotSerdeError> {
+        Ok(Self {
+            x: serde.read_felt((ptr + 0usize)?)?,
+            y: serde.read_felt((ptr + 1usize)?)?,
+            result: serde.read_felt((ptr + 2usize)?)?,
+        })
+    }
+}
+
+/// `starkware.cairo.common.cairo_builtins.KeccakBuiltin`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeccakBuiltin {
+    /// `starkware.cairo.common.keccak_state.KeccakBuiltinState` at offset 0.
+    pub input: [MaybeRelocatable; 8],
+    /// `starkware.cairo.common.keccak_state.KeccakBuiltinState` at offset 8.
+    pub output: [MaybeRelocatable; 8],
+}
+
+impl CairoSerde for KeccakBuiltin {
+    const STRUCT_NAME: &'static str = "starkware.cairo.common.cairo_builtins.KeccakBuiltin";
+    const SIZE: usize = 16;
+
+    fn from_cairo(
+        serde: &KakarotSerde,
+        ptr: Relocatable,
+    ) -> Result<Self, KakarotSerdeError> {
+        Ok(Self {
+            input: serde.read_cells::<8>((ptr + 0usize)?)?,
+            output: serde.read_cells::<8>((ptr + 8usize)?)?,
+        })
+    }
+}
+
+/// `starkware.cairo.common.cairo_builtins.ModBuiltin`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModBuiltin {
+    /// `starkware.cairo.common.cairo_builtins.UInt384` at offset 0.
+    pub p: UInt384,
+    /// `starkware.cairo.common.cairo_builtins.UInt384*` at offset 4.
+    pub values_ptr: Option<Relocatable>,
+    /// `felt*` at offset 5.
+    pub offsets_ptr: Option<Relocatable>,
+    /// `felt` at offset 6.
+    pub n: Felt252,
+}
+
+impl CairoSerde for ModBuiltin {
+    const STRUCT_NAME: &'static str = "starkware.cairo.common.cairo_builtins.ModBuiltin";
+    const SIZE: usize = 7;
+
+    fn from_cairo(
+        serde: &KakarotSerde,
+        ptr: Relocatable,
+    ) -> Result<Self, KakarotSerdeError> {
+        Ok(Self {
+            p: UInt384::from_cairo(serde, (ptr + 0usize)?)?,
+            values_ptr: serde.read_pointer((ptr + 4usize)?)?,
+            offsets_ptr: serde.read_pointer((ptr + 5usize)?)?,
+            n: serde.read_felt((ptr + 6usize)?)?,
+        })
+    }
+}
+
+/// `starkware.cairo.common.cairo_builtins.PoseidonBuiltin`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoseidonBuiltin {
+    /// `starkware.cairo.common.poseidon_state.PoseidonBuiltinState` at offset 0.
+    pub input: [MaybeRelocatable; 3],
+    /// `starkware.cairo.common.poseidon_state.PoseidonBuiltinState` at offset 3.
+    pub output: [MaybeRelocatable; 3],
+}
+
+impl CairoSerde for PoseidonBuiltin {
+    const STRUCT_NAME: &'static str = "starkware.cairo.common.cairo_builtins.PoseidonBuiltin";
+    const SIZE: usize = 6;
+
+    fn from_cairo(
+        serde: &KakarotSerde,
+        ptr: Relocatable,
+    ) -> Result<Self, KakarotSerdeError> {
+        Ok(Self {
+            input: serde.read_cells::<3>((ptr + 0usize)?)?,
+            output: serde.read_cells::<3>((ptr + 3usize)?)?,
+        })
+    }
+}
+
+/// `starkware.cairo.common.cairo_builtins.SignatureBuiltin`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureBuiltin {
+    /// `felt` at offset 0.
+    pub pub_key: Felt252,
+    /// `felt` at offset 1.
+    pub message: Felt252,
+}
+
+impl CairoSerde for SignatureBuiltin {
+    const STRUCT_NAME: &'static str = "starkware.cairo.common.cairo_builtins.SignatureBuiltin";
+    const SIZE: usize = 2;
+
+    fn from_cairo(
+        serde: &KakarotSerde,
+        ptr: Relocatable,
+    ) -> Result<Self, KakarotSerdeError> {
+        Ok(Self {
+            pub_key: serde.read_felt((ptr + 0usize)?)?,
+            message: serde.read_felt((ptr + 1usize)?)?,
+        })
+    }
+}
+
+/// `starkware.cairo.common.cairo_builtins.UInt384`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UInt384 {
+    /// `felt` at offset 0.
+    pub d0: Felt252,
+    /// `felt` at offset 1.
+    pub d1: Felt252,
+    /// `felt` at offset 2.
+    pub d2: Felt252,
+    /// `felt` at offset 3.
+    pub d3: Felt252,
+}
+
+impl CairoSerde for UInt384 {
+    const STRUCT_NAME: &'static str = "starkware.cairo.common.cairo_builtins.UInt384";
+    const SIZE: usize = 4;
+
+    fn from_cairo(
+        serde: &KakarotSerde,
+        ptr: Relocatable,
+    ) -> Result<Self, KakarotSerdeError> {
+        Ok(Self {
+            d0: serde.read_felt((ptr + 0usize)?)?,
+            d1: serde.read_felt((ptr + 1usize)?)?,
+            d2: serde.read_felt((ptr + 2usize)?)?,
+            d3: serde.read_felt((ptr + 3usize)?)?,
+        })
+    }
+}
@@ -0,0 +1,113 @@
+//! Generated from the program identifiers, do not edit.
+
+#[allow(unused_imports)]
+use cairo_vm::{types::relocatable::{MaybeRelocatable, Relocatable}, Felt252};
+use kakarot_exex::serde::{CairoSerde, KakarotSerde, KakarotSerdeError};
+
+/// `starkware.cairo.common.uint256.Uint256`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Uint256 {
+    /// `felt` at offset 0.
+    pub low: Felt252,
+    /// `felt` at offset 1.
+    pub high: Felt252,
+}
+
+impl CairoSerde for Uint256 {
+    const STRUCT_NAME: &'static str = "starkware.cairo.common.uint256.Uint256";
+    const SIZE: usize = 2;
+
+    fn from_cairo(
+        serde: &KakarotSerde,
+        ptr: Relocatable,
+    ) -> Result<Self, KakarotSerdeError> {
+        Ok(Self {
+            low: serde.read_felt((ptr + 0usize)?)?,
+            high: serde.read_felt((ptr + 1usize)?)?,
+        })
+    }
+}
+
+/// `starkware.cairo.common.uint256.uint256_reverse_endian.Args`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StarkwareCairoCommonUint256Uint256ReverseEndianArgs {
+    /// `starkware.cairo.common.uint256.Uint256` at offset 0.
+    pub num: Uint256,
+}
+
+impl CairoSerde for StarkwareCairoCommonUint256Uint256ReverseEndianArgs {
+    const STRUCT_NAME: &'static str = "starkware.cairo.common.uint256.uint256_reverse_endian.Args";
+    const SIZE: usize = 2;
+
+    fn from_cairo(
+        serde: &KakarotSerde,
+        ptr: Relocatable,
+    ) -> Result<Self, KakarotSerdeError> {
+        Ok(Self {
+            num: Uint256::from_cairo(serde, (ptr + 0usize)?)?,
+        })
+    }
+}
+
+/// `starkware.cairo.common.uint256.uint256_reverse_endian.ImplicitArgs`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StarkwareCairoCommonUint256Uint256ReverseEndianImplicitArgs {
+    /// `starkware.cairo.common.cairo_builtins.BitwiseBuiltin*` at offset 0.
+    pub bitwise_ptr: Option<Relocatable>,
+}
+
+impl CairoSerde for StarkwareCairoCommonUint256Uint256ReverseEndianImplicitArgs {
+    const STRUCT_NAME: &'static str = "starkware.cairo.common.uint256.uint256_reverse_endian.ImplicitArgs";
+    const SIZE: usize = 1;
+
+    fn from_cairo(
+        serde: &KakarotSerde,
+        ptr: Relocatable,
+    ) -> Result<Self, KakarotSerdeError> {
+        Ok(Self {
+            bitwise_ptr: serde.read_pointer((ptr + 0usize)?)?,
+        })
+    }
+}
+
+/// `starkware.cairo.common.uint256.word_reverse_endian.Args`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StarkwareCairoCommonUint256WordReverseEndianArgs {
+    /// `felt` at offset 0.
+    pub word: Felt252,
+}
+
+impl CairoSerde for StarkwareCairoCommonUint256WordReverseEndianArgs {
+    const STRUCT_NAME: &'static str = "starkware.cairo.common.uint256.word_reverse_endian.Args";
+    const SIZE: usize = 1;
+
+    fn from_cairo(
+        serde: &KakarotSerde,
+        ptr: Relocatable,
+    ) -> Result<Self, KakarotSerdeError> {
+        Ok(Self {
+            word: serde.read_felt((ptr + 0usize)?)?,
+        })
+    }
+}
+
+/// `starkware.cairo.common.uint256.word_reverse_endian.ImplicitArgs`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StarkwareCairoCommonUint256WordReverseEndianImplicitArgs {
+    /// `starkware.cairo.common.cairo_builtins.BitwiseBuiltin*` at offset 0.
+    pub bitwise_ptr: Option<Relocatable>,
+}
+
+impl CairoSerde for StarkwareCairoCommonUint256WordReverseEndianImplicitArgs {
+    const STRUCT_NAME: &'static str = "starkware.cairo.common.uint256.word_reverse_endian.ImplicitArgs";
+    const SIZE: usize = 1;
+
+    fn from_cairo(
+        serde: &KakarotSerde,
+        ptr: Relocatable,
+    ) -> Result<Self, KakarotSerdeError> {
+        Ok(Self {
+            bitwise_ptr: serde.read_pointer((ptr + 0usize)?)?,
+        })
+    }
+}